@@ -0,0 +1,25 @@
+#version 140
+
+uniform mat4 mv_matrix;
+uniform mat4 mvp_matrix;
+uniform mat4 normal_matrix;
+uniform vec4 light_position;
+
+in vec4 _position;
+in vec4 _normal;
+
+out vec3 fragment_normal;
+out vec3 fragment_light_vector;
+out float fragment_depth;
+
+void main() {
+    vec4 position = _position*mv_matrix;
+    vec4 normal = normalize(_normal*normal_matrix);
+    vec4 light_vector = light_position-position;
+
+    fragment_normal = vec3(normal[0],normal[1],normal[2]);
+    fragment_light_vector = vec3(light_vector[0],light_vector[1],light_vector[2]);
+    fragment_depth = position[2];
+
+    gl_Position = _position*mvp_matrix;
+}
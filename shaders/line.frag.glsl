@@ -0,0 +1,50 @@
+#version 140
+
+uniform vec3 colour;
+uniform int depth_cue;
+uniform vec2 depth_range;
+// fragments nearer than slab[0] or further than slab[1] aren't drawn
+uniform vec2 slab;
+
+in float fragment_depth;
+
+#ifdef TRANSLUCENT
+uniform float opacity;
+out vec4 accumulation;
+out vec4 revealage;
+#else
+out vec4 color;
+#endif
+#ifdef PICKING
+uniform vec4 id;
+#endif
+
+vec3 depth_cued(vec3 colour, float depth) {
+    float t = clamp((depth-depth_range[0])/(depth_range[1]-depth_range[0]), 0, 1);
+    if (depth_cue == 1) {
+        return colour*(1.0-0.7*t);
+    } else if (depth_cue == 2) {
+        return clamp(vec3(1.0-2.0*t, 1.0-abs(2.0*t-1.0), 2.0*t-1.0), 0, 1);
+    }
+    return colour;
+}
+
+void main() {
+    float depth = fragment_depth;
+    if (depth < slab[0] || depth > slab[1])
+        discard;
+    vec3 colour3 = depth_cued(colour, depth);
+    #ifdef TRANSLUCENT
+    float weight = opacity * clamp (
+        10.0/(1e-5+pow(abs(depth)/5.0,2.0)+pow(abs(depth)/200.0,6.0)),
+        1e-2,
+        3e3
+    );
+    accumulation = vec4(colour3*opacity, opacity)*weight;
+    revealage = vec4(-log(1.0-min(opacity,0.999)), 0.0, 0.0, 0.0);
+    #elif defined(PICKING)
+    color = id;
+    #else
+    color = vec4(colour3, 1.0);
+    #endif
+}
@@ -0,0 +1,14 @@
+#version 140
+
+uniform mat4 mv_matrix;
+uniform mat4 mvp_matrix;
+
+in vec4 _position;
+
+out float fragment_depth;
+
+void main() {
+    vec4 position = _position*mv_matrix;
+    fragment_depth = position[2];
+    gl_Position = _position*mvp_matrix;
+}
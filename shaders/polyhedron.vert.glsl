@@ -0,0 +1,56 @@
+#version 140
+
+uniform mat4 mv_matrix;
+uniform mat4 mvp_matrix;
+uniform vec4 light_position;
+
+in vec4 _position;
+in vec4 _normal;
+
+out vec3 fragment_normal;
+out vec3 fragment_light_vector;
+out float fragment_depth;
+// Meshes coloured point by point pass each vertex's colour on.
+#ifdef VERTEX_COLOUR
+in vec3 _colour;
+out vec3 fragment_colour;
+#endif
+// Many sticks drawn at once (see batch.rs) each stretch the unit cylinder from
+// one end to the other themselves, so mv_matrix and mvp_matrix are the view's own,
+// and pass their colours on, as meshes coloured point by point do.
+#ifdef INSTANCED
+in vec3 instance_from;
+in vec3 instance_to;
+in float instance_radius;
+in vec3 instance_colour;
+out vec3 fragment_colour;
+#endif
+
+void main() {
+    #ifdef INSTANCED
+    // an axis along the stick and two across it, as bonds::model_matrix() makes
+    vec3 half_length = 0.5*(instance_to-instance_from);
+    vec3 axis = half_length/max(length(half_length), 1e-6);
+    vec3 u = normalize(cross(axis, abs(axis.x) < 0.9 ? vec3(1,0,0) : vec3(0,1,0)));
+    vec3 v = cross(axis, u);
+    vec4 world = vec4(0.5*(instance_from+instance_to) + instance_radius*(_position.x*u+_position.y*v)
+                      + _position.z*half_length, 1.0);
+    vec4 world_normal = vec4(_normal.x*u+_normal.y*v+_normal.z*axis, 0.0);
+    fragment_colour = instance_colour;
+    #else
+    vec4 world = _position;
+    vec4 world_normal = _normal;
+    #endif
+    vec4 position = world*mv_matrix;
+    vec4 normal = normalize(world_normal*mv_matrix);
+    vec4 light_vector = light_position-position;
+    #ifdef VERTEX_COLOUR
+    fragment_colour = _colour;
+    #endif
+
+    fragment_normal = vec3(normal[0],normal[1],normal[2]);
+    fragment_light_vector = vec3(light_vector[0],light_vector[1],light_vector[2]);
+    fragment_depth = position[2];
+
+    gl_Position = world*mvp_matrix;
+}
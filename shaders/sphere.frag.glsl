@@ -0,0 +1,104 @@
+#version 140
+
+#ifdef INSTANCED
+in vec3 fragment_colour;
+in float fragment_size;
+#else
+uniform vec3 colour;
+uniform float size;
+#endif
+uniform int depth_cue;
+uniform vec2 depth_range;
+// fragments nearer than slab[0] or further than slab[1] aren't drawn
+uniform vec2 slab;
+// as for polyhedra, but flat shading draws the sphere as a disc facing
+// the camera, and there's no wireframe of a billboard
+uniform int shading;
+// With an environment (see background.rs) the surface reflects it, the view's
+// direction bounced off the normal and turned into world space to look it up.
+uniform samplerCube environment;
+uniform mat3 environment_rotation;
+uniform float reflectivity;
+
+in vec2 fragment_xy;
+in vec3 fragment_light_vector;
+in float fragment_depth;
+
+// Opaque surfaces write straight to the screen. Translucent ones write to
+// the weighted-blended order-independent transparency buffers (see oit.rs).
+#ifdef TRANSLUCENT
+uniform float opacity;
+out vec4 accumulation;
+out vec4 revealage;
+#else
+out vec4 color;
+#endif
+// The picking pass draws each atom in a colour encoding its index (see id_buffer.rs).
+#ifdef PICKING
+uniform vec4 id;
+#endif
+
+// Mode 0 leaves the colour alone, 1 darkens with depth, 2 replaces the
+// colour with a red-green-blue ramp from depth_range[0] to depth_range[1].
+vec3 depth_cued(vec3 colour, float depth) {
+    float t = clamp((depth-depth_range[0])/(depth_range[1]-depth_range[0]), 0, 1);
+    if (depth_cue == 1) {
+        return colour*(1.0-0.7*t);
+    } else if (depth_cue == 2) {
+        return clamp(vec3(1.0-2.0*t, 1.0-abs(2.0*t-1.0), 2.0*t-1.0), 0, 1);
+    }
+    return colour;
+}
+
+void main() {
+    #ifdef INSTANCED
+    vec3 base_colour = fragment_colour;
+    float radius = fragment_size;
+    #else
+    vec3 base_colour = colour;
+    float radius = size;
+    #endif
+    float xy_squared = dot(fragment_xy,fragment_xy);
+    if (xy_squared > 1)
+        discard;
+    vec3 normal = vec3(fragment_xy[0],fragment_xy[1],-sqrt(1-xy_squared));
+    vec3 light_vector = vec3 (
+        fragment_light_vector[0],
+        fragment_light_vector[1],
+        fragment_light_vector[2]-radius*normal[2]
+    );
+    if (shading == 2)
+        normal = vec3(0,0,-1);
+    float light_distance_squared = dot(light_vector,light_vector);
+    float cos_light_angle = clamp (
+        dot(normal,light_vector) * inversesqrt(light_distance_squared),
+        0,
+        1
+    );
+    float depth = fragment_depth+radius*normal[2];
+    if (depth < slab[0] || depth > slab[1])
+        discard;
+    vec3 colour3 = depth_cued(base_colour, depth)
+                 * (cos_light_angle/light_distance_squared+0.2);
+    if (reflectivity > 0.0) {
+        vec3 reflected = texture(environment, environment_rotation*reflect(vec3(0,0,1), normal)).rgb;
+        colour3 = mix(colour3, reflected, reflectivity);
+    }
+    if (shading == 1)
+        colour3 = 0.5*normal+0.5;
+    #ifdef TRANSLUCENT
+    // Weight nearer fragments more heavily (McGuire & Bavoil, 2013, eq. 10).
+    float weight = opacity * clamp (
+        10.0/(1e-5+pow(abs(depth)/5.0,2.0)+pow(abs(depth)/200.0,6.0)),
+        1e-2,
+        3e3
+    );
+    accumulation = vec4(colour3*opacity, opacity)*weight;
+    // Summed additively, so store -ln(1-a) rather than multiplying (1-a)s.
+    revealage = vec4(-log(1.0-min(opacity,0.999)), 0.0, 0.0, 0.0);
+    #elif defined(PICKING)
+    color = id;
+    #else
+    color = vec4(colour3, 1.0);
+    #endif
+}
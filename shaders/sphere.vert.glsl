@@ -0,0 +1,41 @@
+#version 140
+
+uniform mat4 mv_matrix;
+uniform mat4 mvp_matrix;
+uniform vec4 light_position;
+
+in vec4 _position;
+in vec4 _normal;
+
+out vec2 fragment_xy;
+out vec3 fragment_light_vector;
+out float fragment_depth;
+// Many atoms drawn at once (see batch.rs) each place the quad themselves, turned
+// to face the camera by billboard, so mv_matrix and mvp_matrix are the view's own,
+// and pass their colours and radii on.
+#ifdef INSTANCED
+uniform mat4 billboard;
+in vec3 instance_position;
+in float instance_radius;
+in vec3 instance_colour;
+out vec3 fragment_colour;
+out float fragment_size;
+#endif
+
+void main() {
+    #ifdef INSTANCED
+    vec4 world = vec4(instance_position+instance_radius*(_position*billboard).xyz, 1.0);
+    fragment_colour = instance_colour;
+    fragment_size = instance_radius;
+    #else
+    vec4 world = _position;
+    #endif
+    vec4 position = world*mv_matrix;
+    vec4 light_vector = light_position-position;
+    
+    fragment_xy = vec2(_normal[0],_normal[1]);
+    fragment_light_vector = vec3(light_vector[0],light_vector[1],light_vector[2]);
+    fragment_depth = position[2];
+
+    gl_Position = world*mvp_matrix;
+}
@@ -0,0 +1,116 @@
+use molecule::Molecule;
+
+// ============================================================
+// Arrow Display
+// ============================================================
+/// Which vectors are drawn as arrows: one per atom, from its centre, or the
+/// molecule's dipole, from the middle of its atoms.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ArrowDisplay {
+    Hidden,
+    /// The forces on the atoms, e.g. from a step of a geometry optimization.
+    Forces,
+    /// The atoms' velocities, e.g. from a frame of molecular dynamics.
+    Velocities,
+    Dipole,
+}
+
+impl ArrowDisplay {
+    pub fn next(&self) -> ArrowDisplay {
+        match *self {
+            ArrowDisplay::Hidden     => ArrowDisplay::Forces,
+            ArrowDisplay::Forces     => ArrowDisplay::Velocities,
+            ArrowDisplay::Velocities => ArrowDisplay::Dipole,
+            ArrowDisplay::Dipole     => ArrowDisplay::Hidden,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ArrowDisplay::Hidden     => "hidden",
+            ArrowDisplay::Forces     => "forces",
+            ArrowDisplay::Velocities => "velocities",
+            ArrowDisplay::Dipole     => "dipole",
+        }
+    }
+
+    /// The display with a name (as name() gives it), e.g. for --arrows.
+    pub fn parse(in_name : &str) -> Result<ArrowDisplay, String> {
+        [ArrowDisplay::Hidden, ArrowDisplay::Forces, ArrowDisplay::Velocities, ArrowDisplay::Dipole].iter()
+            .find(|display| display.name() == in_name.to_lowercase()).cloned()
+            .ok_or_else(|| format!("expected hidden, forces, velocities or dipole, not \"{}\"", in_name))
+    }
+
+    /// The arrows' colour: forces red, velocities blue and the dipole green.
+    pub fn colour(&self) -> [f32;3] {
+        match *self {
+            ArrowDisplay::Forces     => [0.9, 0.2, 0.15],
+            ArrowDisplay::Velocities => [0.2, 0.45, 0.95],
+            _                        => [0.2, 0.8, 0.3],
+        }
+    }
+}
+
+/// The shafts' radius, in Angstroms.
+pub const ARROW_RADIUS : f32 = 0.06;
+
+/// The heads' radius and length, as multiples of the shafts' radius. Arrows too
+/// short for a whole head are all head, narrowed to match.
+const HEAD_RADIUS : f32 = 2.5;
+const HEAD_LENGTH : f32 = 6.0;
+
+/// How long the longest arrow is drawn at a scale of 1, in Angstroms, whatever
+/// units the vectors are in.
+pub const LONGEST : f32 = 1.5;
+
+/// The least and most the arrows can be scaled by.
+pub const MIN_SCALE : f32 = 0.05;
+pub const MAX_SCALE : f32 = 20.0;
+
+/// The vectors a display draws, as (the atom, or None for the dipole, where the
+/// arrow starts, the vector). None if the molecule has none of them.
+pub fn vectors(in_molecule : &Molecule, in_display : &ArrowDisplay) -> Option<Vec<(Option<usize>, [f32;3], [f32;3])>> {
+    let atoms = in_molecule.atoms();
+    let vectors : Vec<(Option<usize>, [f32;3], [f32;3])> = match *in_display {
+        ArrowDisplay::Hidden => Vec::new(),
+        ArrowDisplay::Forces => atoms.iter().enumerate()
+            .filter_map(|(i, atom)| atom.force().map(|force| (Some(i), *atom.position(), force)))
+            .collect(),
+        ArrowDisplay::Velocities => atoms.iter().enumerate()
+            .filter_map(|(i, atom)| atom.velocity().map(|velocity| (Some(i), *atom.position(), velocity)))
+            .collect(),
+        ArrowDisplay::Dipole => match *in_molecule.dipole() {
+            Some(dipole) if !atoms.is_empty() => {
+                let mut centre = [0.0f32;3];
+                for atom in atoms {
+                    for k in 0..3 {centre[k] += atom.position()[k]/atoms.len() as f32}
+                }
+                vec![(None, centre, dipole)]
+            },
+            _ => Vec::new(),
+        },
+    };
+    if vectors.is_empty() {None} else {Some(vectors)}
+}
+
+fn length(in_vector : &[f32;3]) -> f32 {
+    (in_vector[0]*in_vector[0]+in_vector[1]*in_vector[1]+in_vector[2]*in_vector[2]).sqrt()
+}
+
+/// Each vector as an arrow scaled by its length, the longest LONGEST at a scale of
+/// 1: its shaft, as (from, to), if it isn't all head, and its head, as (from, tip,
+/// radius). Vectors of no length have no arrow.
+pub fn arrows(in_vectors : &[(Option<usize>, [f32;3], [f32;3])], in_scale : &f32)
+              -> Vec<(Option<usize>, Option<([f32;3], [f32;3])>, ([f32;3], [f32;3], f32))> {
+    let longest = in_vectors.iter().map(|&(_, _, ref vector)| length(vector)).fold(0.0f32, f32::max);
+    if longest <= 0.0 {return Vec::new()}
+    let factor = LONGEST*in_scale/longest;
+    in_vectors.iter().filter(|&&(_, _, ref vector)| length(vector) > 0.0).map(|&(atom, from, vector)| {
+        let drawn = length(&vector)*factor;
+        let head = (HEAD_LENGTH*ARROW_RADIUS).min(drawn);
+        let point = |t : f32| [from[0]+vector[0]*factor*t, from[1]+vector[1]*factor*t, from[2]+vector[2]*factor*t];
+        let neck = point(1.0-head/drawn);
+        let shaft = if head < drawn {Some((from, neck))} else {None};
+        (atom, shaft, (neck, point(1.0), HEAD_RADIUS*head/HEAD_LENGTH))
+    }).collect()
+}
@@ -0,0 +1,113 @@
+use atom::Atom;
+use matrix::Matrix;
+
+// ============================================================
+// Operator
+// ============================================================
+/// One copy of some chains in a biological assembly: the rigid transformation
+/// (PDB REMARK 350 BIOMT) which moves the chains from where they are in the file
+/// to where the copy goes.
+#[derive(Clone, Debug)]
+pub struct Operator {
+    _matrix : Matrix,
+    /// The chains copied, or None for every atom.
+    _chains : Option<Vec<String>>,
+}
+
+impl Operator {
+    pub fn new(in_matrix : &Matrix, in_chains : &Vec<String>) -> Operator {
+        Operator {
+            _matrix : in_matrix.to_owned(),
+            _chains : Some(in_chains.to_owned()),
+        }
+    }
+
+    /// Moves every atom, e.g. for a copy of the whole molecule in a crystal.
+    pub fn for_all(in_matrix : &Matrix) -> Operator {
+        Operator {
+            _matrix : in_matrix.to_owned(),
+            _chains : None,
+        }
+    }
+
+    /// Leaves every atom where it is, e.g. to draw just what's in the file.
+    pub fn identity() -> Operator {
+        Operator::for_all(&Matrix::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0]
+        ]))
+    }
+
+    /// Whether the atom is copied. Atoms outside chains are only copied by identity().
+    pub fn applies_to(&self, in_atom : &Atom) -> bool {
+        match (&self._chains, in_atom.residue()) {
+            (&None, _) => true,
+            (&Some(ref chains), &Some(ref residue)) => chains.iter().any(|chain| chain == residue.chain()),
+            (&Some(_), &None) => false,
+        }
+    }
+
+    /// Where the operator moves a point to.
+    pub fn apply(&self, in_point : &[f32;3]) -> [f32;3] {
+        let p = self._matrix * [in_point[0], in_point[1], in_point[2], 1.0];
+        [p[0], p[1], p[2]]
+    }
+
+    /// How far the operator moves a point.
+    pub fn shift(&self, in_point : &[f32;3]) -> [f32;3] {
+        let p = self.apply(in_point);
+        [p[0]-in_point[0], p[1]-in_point[1], p[2]-in_point[2]]
+    }
+
+    /// A tensor (an atom's displacement parameters, say) turned as the operator turns
+    /// what it copies: R U R^T, for the operator's rotation R.
+    pub fn turned(&self, in_tensor : &[[f32;3];3]) -> [[f32;3];3] {
+        let r = self._matrix.contents();
+        let mut turned = [[0.0f32;3];3];
+        for i in 0..3 {
+            for j in 0..3 {
+                for k in 0..3 {
+                    for l in 0..3 {
+                        turned[i][j] += r[i][k]*in_tensor[k][l]*r[j][l];
+                    }
+                }
+            }
+        }
+        turned
+    }
+}
+
+/// A model matrix moved by `in_shift`, e.g. to draw an atom's copy where an
+/// operator puts it. Only the atom's position is moved, so spheres still face
+/// the camera; ellipsoids are turned as well (see Operator::turned()).
+pub fn shifted(in_model_matrix : &Matrix, in_shift : &[f32;3]) -> Matrix {
+    let mut contents = in_model_matrix.contents().to_owned();
+    for k in 0..3 {
+        contents[k][3] += in_shift[k];
+    }
+    Matrix::new(contents)
+}
+
+// ============================================================
+// Assembly
+// ============================================================
+/// The biological assembly (PDB REMARK 350): the molecule as it's thought to be
+/// in life, made of copies of the chains in the file (the asymmetric unit).
+#[derive(Clone, Debug)]
+pub struct Assembly {
+    _operators : Vec<Operator>,
+}
+
+impl Assembly {
+    pub fn new() -> Assembly {
+        Assembly {
+            _operators : Vec::new(),
+        }
+    }
+
+    pub fn operators(&self) -> &Vec<Operator> {&self._operators}
+    pub fn add_operator(&mut self, in_operator : &Operator) {self._operators.push(in_operator.to_owned())}
+    pub fn is_empty(&self) -> bool {self._operators.is_empty()}
+}
@@ -1,14 +1,66 @@
 use matrix::Matrix;
 use species::Species;
-use camera::Camera;
+use label::LabelContent;
+
+// ============================================================
+// Residue
+// ============================================================
+/// Where an atom sits in a biomolecule, for formats which say (e.g. PDB).
+#[derive(Clone, PartialEq, Debug)]
+pub struct Residue {
+    _chain  : String,
+    /// e.g. "ALA" or "HOH".
+    _name   : String,
+    _number : i32,
+}
+
+impl Residue {
+    pub fn new(in_chain : &str, in_name : &str, in_number : &i32) -> Residue {
+        Residue {
+            _chain  : in_chain.to_owned(),
+            _name   : in_name.to_owned(),
+            _number : in_number.to_owned(),
+        }
+    }
+
+    pub fn chain(&self) -> &str {&self._chain}
+    pub fn name(&self) -> &str {&self._name}
+    pub fn number(&self) -> &i32 {&self._number}
+}
 
 // ============================================================
 // Atom
 // ============================================================
 /// The atom, the fundamental unit of a molecular viewer.
+#[derive(Clone)]
 pub struct Atom<'a> {
-    _species      : &'a Species<'a>,
+    _species      : &'a Species,
     _position     : [f32;3],
+    /// Starts as the species colour, but can be changed, e.g. to colour by a property.
+    _colour       : [f32;3],
+    _opacity      : f32,
+    /// The partial charge, if the file gave one.
+    _charge       : Option<f32>,
+    /// The value of whatever property the atom is coloured by, if any.
+    _property     : Option<f32>,
+    _residue      : Option<Residue>,
+    /// The atom's name within its residue, e.g. "CA" for an alpha carbon.
+    _name         : Option<String>,
+    /// Whether the file lists the atom as a HETATM: not part of a standard residue,
+    /// e.g. a ligand, a water or an ion.
+    _hetero       : bool,
+    /// How smeared out the atom is in a crystal structure, from the PDB file's
+    /// B-factor (temperature factor) column.
+    _b_factor     : Option<f32>,
+    /// The cartesian displacement matrix U (in square Angstroms), if a CIF gave the
+    /// atom's displacement parameters, for drawing it as a thermal ellipsoid.
+    _adp          : Option<[[f32;3];3]>,
+    /// The force on the atom and its velocity, if the file gave them (see arrows.rs),
+    /// in whatever units it gave them in.
+    _force        : Option<[f32;3]>,
+    _velocity     : Option<[f32;3]>,
+    /// Which object (the file it came from, see objects.rs) the atom's in.
+    _object       : usize,
     _model_matrix : Matrix,
 }
 
@@ -20,6 +72,18 @@ impl<'a> Atom<'a> {
         Atom {
             _species      : in_species,
             _position     : in_position.to_owned(),
+            _colour       : in_species.colour().to_owned(),
+            _opacity      : 1.0,
+            _charge       : None,
+            _property     : None,
+            _residue      : None,
+            _name         : None,
+            _hetero       : false,
+            _b_factor     : None,
+            _adp          : None,
+            _force        : None,
+            _velocity     : None,
+            _object       : 0,
             _model_matrix : Matrix::new([
                 [*in_species.size(), 0.0               , 0.0               , in_position[0]],
                 [0.0               , *in_species.size(), 0.0               , in_position[1]],
@@ -29,10 +93,65 @@ impl<'a> Atom<'a> {
         }
     }
 
-    pub fn species(&self) -> &Species<'a> {&self._species}
+    pub fn species(&self) -> &Species {&self._species}
+    pub fn position(&self) -> &[f32;3] {&self._position}
     pub fn model_matrix(&self) -> &Matrix {&self._model_matrix}
+    pub fn colour(&self) -> &[f32;3] {&self._colour}
+    pub fn opacity(&self) -> &f32 {&self._opacity}
+    pub fn charge(&self) -> &Option<f32> {&self._charge}
+    pub fn property(&self) -> &Option<f32> {&self._property}
+    pub fn residue(&self) -> &Option<Residue> {&self._residue}
+    pub fn name(&self) -> &Option<String> {&self._name}
+    pub fn is_hetero(&self) -> bool {self._hetero}
+    pub fn b_factor(&self) -> &Option<f32> {&self._b_factor}
+    pub fn adp(&self) -> &Option<[[f32;3];3]> {&self._adp}
+    pub fn force(&self) -> &Option<[f32;3]> {&self._force}
+    pub fn velocity(&self) -> &Option<[f32;3]> {&self._velocity}
+    pub fn object(&self) -> &usize {&self._object}
+
+    /// Moves the atom, and its model matrix with it.
+    pub fn set_position(&mut self, in_position : &[f32;3]) {
+        let mut contents = self._model_matrix.contents().to_owned();
+        for k in 0..3 {contents[k][3] = in_position[k]}
+        self._model_matrix = Matrix::new(contents);
+        self._position = in_position.to_owned();
+    }
+    pub fn set_colour(&mut self, in_colour : &[f32;3]) {self._colour = in_colour.to_owned()}
+    pub fn reset_colour(&mut self) {self._colour = self._species.colour().to_owned()}
+    pub fn set_charge(&mut self, in_charge : &f32) {self._charge = Some(in_charge.to_owned())}
+    pub fn set_property(&mut self, in_property : &Option<f32>) {self._property = in_property.to_owned()}
+    pub fn set_residue(&mut self, in_residue : &Residue) {self._residue = Some(in_residue.to_owned())}
+    pub fn set_name(&mut self, in_name : &str) {self._name = Some(in_name.to_owned())}
+    pub fn set_hetero(&mut self, in_hetero : &bool) {self._hetero = in_hetero.to_owned()}
+    pub fn set_b_factor(&mut self, in_b_factor : &f32) {self._b_factor = Some(in_b_factor.to_owned())}
+    pub fn set_adp(&mut self, in_adp : &[[f32;3];3]) {self._adp = Some(in_adp.to_owned())}
+    pub fn set_force(&mut self, in_force : &[f32;3]) {self._force = Some(in_force.to_owned())}
+    pub fn set_velocity(&mut self, in_velocity : &[f32;3]) {self._velocity = Some(in_velocity.to_owned())}
+    pub fn set_object(&mut self, in_object : &usize) {self._object = *in_object}
+
+    /// The text of the atom's label, or None for no label. `in_index` is the atom's
+    /// index in its molecule.
+    pub fn label(&self, in_content : &LabelContent, in_index : &usize) -> Option<String> {
+        match *in_content {
+            LabelContent::Off     => None,
+            LabelContent::Element => Some(self._species.symbol().to_owned()),
+            LabelContent::Index   => Some(format!("{}", in_index+1)),
+            LabelContent::Residue => self._residue.as_ref().map(|residue| {
+                format!("{} {}", residue.name(), residue.number())
+            }),
+        }
+    }
+
+    /// Translucent atoms are drawn in the order-independent transparency pass.
+    pub fn is_translucent(&self) -> bool {self._opacity < 1.0}
+
+    pub fn set_opacity(&mut self, in_opacity : &f32) {
+        self._opacity = in_opacity.max(0.0).min(1.0);
+    }
 
-    pub fn rotate_against_camera(&mut self, in_camera : &Camera) {
+    /// Turns the atom's quad by the camera's rotation, undone (see
+    /// Molecule::rotate_atoms_against_camera()), so it faces the camera.
+    pub fn rotate_against(&mut self, in_rotation_matrix : &Matrix) {
 
         let translation_and_scaling_matrix = Matrix::new ([
             [*self._species.size(), 0.0, 0.0, self._position[0]],
@@ -40,11 +159,7 @@ impl<'a> Atom<'a> {
             [0.0, 0.0, *self._species.size(), self._position[2]],
             [0.0, 0.0, 0.0                  , 1.0              ]
         ]);
-        
-        let mut quaternion = in_camera.quaternion().to_owned();
-        quaternion.invert();
-        let rotation_matrix = quaternion.rotation_matrix();
 
-        self._model_matrix = translation_and_scaling_matrix * rotation_matrix;
+        self._model_matrix = translation_and_scaling_matrix * *in_rotation_matrix;
     }
 }
@@ -0,0 +1,81 @@
+use glium::{self, Surface};
+
+use bonds;
+use matrix::Matrix;
+use model::Model;
+use text::{self, TextSystem};
+
+// ============================================================
+// Axes Gizmo
+// ============================================================
+/// The size in pixels of the square in the bottom-left corner the axes are drawn in,
+/// and how far it is from the corner.
+const SIZE : u32 = 120;
+const MARGIN : u32 = 10;
+
+/// How far from the centre (in axis lengths) the edges of the square are.
+const EXTENT : f32 = 1.4;
+
+const AXES : [(&'static str, [f32; 3], [f32; 3]); 3] = [
+    ("x", [1.0, 0.0, 0.0], [0.85, 0.2, 0.15]),
+    ("y", [0.0, 1.0, 0.0], [0.25, 0.7, 0.2]),
+    ("z", [0.0, 0.0, 1.0], [0.2, 0.35, 0.9]),
+];
+
+/// Draws the x, y and z axes in the corner of the window, turned the way the camera
+/// has turned the scene (`rotation`, the rotation part of the view matrix) but not
+/// moved with it, so the axes stay put as the camera pans and zooms. `cylinder` is
+/// the unit cylinder bonds are drawn with.
+pub fn draw<T>(text_system: &TextSystem, target: &mut T, cylinder: &Model, rotation: &Matrix)
+               where T: Surface
+{
+    let (_, h) = target.get_dimensions();
+    // orthographic, looking along z, with the depth taken to -1..1
+    let projection = Matrix::new([
+        [1.0/EXTENT, 0.0       , 0.0       , 0.0],
+        [0.0       , 1.0/EXTENT, 0.0       , 0.0],
+        [0.0       , 0.0       , 1.0/EXTENT, 0.0],
+        [0.0       , 0.0       , 0.0       , 1.0]
+    ]);
+    let params = glium::DrawParameters {
+        backface_culling: glium::BackfaceCullingMode::CullCounterClockwise,
+        viewport: Some(glium::Rect {left: MARGIN, bottom: MARGIN, width: SIZE, height: SIZE}),
+        .. Default::default()
+    };
+
+    // the window has no depth buffer, so the axes are drawn furthest first
+    let tip = |direction: &[f32; 3]| *rotation * [direction[0], direction[1], direction[2], 0.0];
+    let mut order = [0, 1, 2];
+    order.sort_by(|&a, &b| tip(&AXES[b].1)[2].partial_cmp(&tip(&AXES[a].1)[2]).unwrap());
+    for &k in &order {
+        let (_, direction, colour) = AXES[k];
+        let model_matrix = bonds::model_matrix(&[0.0, 0.0, 0.0], &direction, &0.06);
+        let mv_matrix = *rotation * model_matrix;
+        let mvp_matrix = projection * mv_matrix;
+        target.draw(
+            cylinder.vertex_buffer(),
+            cylinder.index_buffer(),
+            &cylinder.program(),
+            &uniform!{
+                mv_matrix      : mv_matrix.contents().to_owned(),
+                mvp_matrix     : mvp_matrix.contents().to_owned(),
+                colour         : colour,
+                light_position : [1.0, 1.0, -3.0, 1.0f32],
+                depth_cue      : 0,
+                depth_range    : [-1.0, 1.0f32],
+                slab           : [-1e30, 1e30f32],
+            },
+            &params,
+        ).unwrap();
+    }
+
+    // each axis's letter just past its tip, in pixels from the window's top-left
+    for &(name, direction, colour) in &AXES {
+        let p = tip(&direction);
+        let scale = 1.0 + 0.25/EXTENT;
+        let x = MARGIN as f32 + (0.5 + 0.5*scale*p[0]/EXTENT)*SIZE as f32;
+        let y = h as f32 - MARGIN as f32 - (0.5 + 0.5*scale*p[1]/EXTENT)*SIZE as f32;
+        text::draw_text(text_system, target, name, [x-8.0, y-8.0], 2.0,
+                        [colour[0], colour[1], colour[2], 1.0]);
+    }
+}
@@ -0,0 +1,250 @@
+use glium::{self, Surface};
+use glium::backend::Facade;
+use glium::texture::{CubeLayer, Cubemap};
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use matrix::Matrix;
+
+// ============================================================
+// Background
+// ============================================================
+/// The files an environment is read from, in its directory: a face of the cube
+/// for each direction, +x, -x, +y, -y, +z and -z, as binary PPMs (P6), the format
+/// --screenshot saves in. The faces are square and all the same size.
+const FACES : [(&'static str, CubeLayer); 6] = [
+    ("px.ppm", CubeLayer::PositiveX),
+    ("nx.ppm", CubeLayer::NegativeX),
+    ("py.ppm", CubeLayer::PositiveY),
+    ("ny.ppm", CubeLayer::NegativeY),
+    ("pz.ppm", CubeLayer::PositiveZ),
+    ("nz.ppm", CubeLayer::NegativeZ),
+];
+
+/// How much of the environment the atoms and sticks reflect, as a fraction of
+/// their colour.
+pub const REFLECTIVITY : f32 = 0.25;
+
+/// What's drawn behind the molecule, over the background colour it's cleared to:
+/// nothing, so the colour's solid; a vertical gradient from the top of the window
+/// to the bottom; or an environment, a cubemap seen all round, which the atoms and
+/// sticks reflect too. The environment goes over the gradient if there's both.
+///
+/// Pictures traced or exported (POV-Ray, SVG) keep to the background colour.
+pub struct Backdrop {
+    vertex_buffer: glium::VertexBuffer<BackdropVertex>,
+    index_buffer: glium::IndexBuffer<u16>,
+    program: glium::Program,
+    /// The top and bottom colours.
+    gradient: Option<([f32; 3], [f32; 3])>,
+    environment: Option<Cubemap>,
+    /// A black cube, for the shaders to reflect when there's no environment (at no
+    /// reflectivity), since they're given one either way.
+    black: Cubemap,
+}
+
+#[derive(Copy, Clone)]
+struct BackdropVertex {
+    position: [f32; 2],
+}
+
+implement_vertex!(BackdropVertex, position);
+
+impl Backdrop {
+    pub fn new<F: ?Sized>(facade: &F, gradient: &Option<([f32; 3], [f32; 3])>) -> Backdrop
+        where F: Facade + Clone
+    {
+        let black = Cubemap::empty(facade, 1).unwrap();
+        for &(_, layer) in FACES.iter() {
+            glium::framebuffer::SimpleFrameBuffer::new(facade, black.main_level().image(layer)).unwrap()
+                .clear_color(0.0, 0.0, 0.0, 1.0);
+        }
+
+        Backdrop {
+            vertex_buffer: glium::VertexBuffer::new(facade,
+                &[
+                    BackdropVertex { position: [-1.0, -1.0] },
+                    BackdropVertex { position: [-1.0,  1.0] },
+                    BackdropVertex { position: [ 1.0,  1.0] },
+                    BackdropVertex { position: [ 1.0, -1.0] },
+                ]
+            ).unwrap(),
+
+            index_buffer: glium::IndexBuffer::new(facade,
+                glium::index::PrimitiveType::TriangleStrip, &[1 as u16, 2, 0, 3]).unwrap(),
+
+            program: glium::Program::from_source(facade,
+                r"
+                    #version 140
+
+                    // the view's ray through each corner, as (x, y) at z = 1
+                    uniform vec2 ray_scale;
+
+                    in vec2 position;
+
+                    out vec2 v_position;
+                    out vec3 v_ray;
+
+                    void main() {
+                        // furthest back, so everything's drawn over it
+                        gl_Position = vec4(position, 1.0, 1.0);
+                        v_position = position;
+                        v_ray = vec3(position*ray_scale, 1.0);
+                    }
+                ",
+                r"
+                    #version 140
+
+                    uniform int mode;
+                    uniform vec3 top;
+                    uniform vec3 bottom;
+                    uniform samplerCube environment;
+                    // view space to world space, to look the rays up in the environment
+                    uniform mat3 environment_rotation;
+
+                    in vec2 v_position;
+                    in vec3 v_ray;
+
+                    out vec4 color;
+
+                    void main() {
+                        if (mode == 2) {
+                            color = vec4(texture(environment, environment_rotation*v_ray).rgb, 1.0);
+                        } else {
+                            color = vec4(mix(bottom, top, 0.5*v_position.y+0.5), 1.0);
+                        }
+                    }
+                ",
+                None
+            ).unwrap(),
+
+            gradient: *gradient,
+            environment: None,
+            black: black,
+        }
+    }
+
+    /// Reads an environment's faces from a directory (see FACES), to draw and
+    /// reflect in place of the gradient or the background colour.
+    pub fn load_environment<F: ?Sized>(&mut self, facade: &F, directory: &str) -> Result<(), String>
+        where F: Facade + Clone
+    {
+        let mut faces = Vec::new();
+        for &(name, layer) in FACES.iter() {
+            let path = Path::new(directory).join(name);
+            let ((w, h), pixels) = read_ppm(&path)?;
+            if w != h {
+                return Err(format!("{} is {}x{}, not square", path.display(), w, h));
+            }
+            if let Some(&((size, _), _, _)) = faces.first() {
+                if w != size {
+                    return Err(format!("{} is {} pixels across, not {} as the others", path.display(), w, size));
+                }
+            }
+            faces.push(((w, h), pixels, layer));
+        }
+        let size = (faces[0].0).0 as u32;
+        let cubemap = Cubemap::empty(facade, size).map_err(|e| format!("couldn't make the environment: {:?}", e))?;
+        for ((w, h), pixels, layer) in faces {
+            let image = glium::texture::RawImage2d::from_raw_rgb(pixels, (w as u32, h as u32));
+            let face = glium::texture::Texture2d::new(facade, image)
+                .map_err(|e| format!("couldn't make the environment: {:?}", e))?;
+            let target = glium::framebuffer::SimpleFrameBuffer::new(facade, cubemap.main_level().image(layer))
+                .map_err(|e| format!("couldn't make the environment: {:?}", e))?;
+            face.as_surface().blit_whole_color_to(
+                &target,
+                &glium::BlitTarget {left: 0, bottom: 0, width: size as i32, height: size as i32},
+                glium::uniforms::MagnifySamplerFilter::Linear,
+            );
+        }
+        self.environment = Some(cubemap);
+        Ok(())
+    }
+
+    pub fn has_environment(&self) -> bool {self.environment.is_some()}
+
+    /// The environment for the atoms and sticks to reflect, or with none, a black
+    /// cube, at no reflectivity.
+    pub fn environment(&self) -> &Cubemap {self.environment.as_ref().unwrap_or(&self.black)}
+
+    pub fn reflectivity(&self) -> f32 {if self.environment.is_some() {REFLECTIVITY} else {0.0}}
+
+    /// Draws the gradient or the environment over the whole of a target just
+    /// cleared to the background colour, behind everything drawn after it. Without
+    /// either, that's the background.
+    pub fn draw<T>(&self, target: &mut T, view_matrix: &Matrix, field_of_view: &f32) where T: Surface {
+        let mode = match (self.environment.is_some(), self.gradient) {
+            (true, _) => 2,
+            (false, Some(_)) => 1,
+            (false, None) => return,
+        };
+        let (top, bottom) = self.gradient.unwrap_or(([0.0; 3], [0.0; 3]));
+        // the smaller side of the window spans the field of view, as in Camera::pixel_ray()
+        let (w, h) = target.get_dimensions();
+        let tan = (field_of_view/2.0).tan();
+        let ray_scale = [w as f32/w.min(h) as f32*tan, h as f32/w.min(h) as f32*tan];
+        target.draw(
+            &self.vertex_buffer,
+            &self.index_buffer,
+            &self.program,
+            &uniform!{
+                mode                 : mode,
+                top                  : top,
+                bottom               : bottom,
+                environment          : self.environment(),
+                environment_rotation : environment_rotation(view_matrix),
+                ray_scale            : ray_scale,
+            },
+            &Default::default(),
+        ).unwrap();
+    }
+}
+
+/// What takes directions in view space to world space, to look them up in the
+/// environment: the view matrix's rotation, undone.
+pub fn environment_rotation(view_matrix: &Matrix) -> [[f32; 3]; 3] {
+    // the shaders read each row as a column, so the rotation comes out transposed,
+    // which undoes it
+    let v = view_matrix.contents();
+    [
+        [v[0][0], v[0][1], v[0][2]],
+        [v[1][0], v[1][1], v[1][2]],
+        [v[2][0], v[2][1], v[2][2]],
+    ]
+}
+
+/// A binary PPM (P6) with 255 as its largest value, as screenshot::write_image()
+/// saves them: its size and its pixels, as RGB bytes from the top row.
+fn read_ppm(path: &Path) -> Result<((usize, usize), Vec<u8>), String> {
+    let mut file = File::open(path).map_err(|e| format!("couldn't open {}: {}", path.display(), e))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+    // the header's four words, each followed by whitespace, with comments from #
+    let mut words = Vec::new();
+    let mut k = 0;
+    while words.len() < 4 && k < bytes.len() {
+        if bytes[k] == b'#' {
+            while k < bytes.len() && bytes[k] != b'\n' {k += 1}
+        } else if (bytes[k] as char).is_whitespace() {
+            k += 1;
+        } else {
+            let start = k;
+            while k < bytes.len() && !(bytes[k] as char).is_whitespace() {k += 1}
+            words.push(String::from_utf8_lossy(&bytes[start..k]).into_owned());
+        }
+    }
+    let numbers : Vec<usize> = words.iter().skip(1).filter_map(|word| word.parse().ok()).collect();
+    if words.first().map(|word| word.as_str()) != Some("P6") || numbers.len() != 3 {
+        return Err(format!("{} isn't a binary PPM (P6)", path.display()));
+    }
+    if numbers[2] != 255 {
+        return Err(format!("{} has values up to {}, not 255", path.display(), numbers[2]));
+    }
+    let (w, h) = (numbers[0], numbers[1]);
+    // one whitespace byte after the header
+    let pixels = bytes.get(k+1..k+1+3*w*h)
+        .ok_or_else(|| format!("{} is shorter than {}x{} pixels", path.display(), w, h))?;
+    Ok(((w, h), pixels.to_vec()))
+}
@@ -0,0 +1,121 @@
+use glium::{self, VertexBuffer};
+use glium::backend::Facade;
+
+// ============================================================
+// Batch
+// ============================================================
+/// How many atoms a structure has (counting each copy an assembly draws) before its
+/// opaque atoms and sticks are each drawn in one go, from instance buffers, rather
+/// than one draw call apiece. Below it, the calls are cheap enough, and drawing them
+/// apiece keeps the per-atom picking pass.
+pub const BATCH_ATOMS : usize = 20000;
+
+/// An atom drawn in a batch: where it is, how big and what colour, the quad turned
+/// to face the camera by the shader (see shaders/sphere.vert.glsl).
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SphereInstance {
+    pub instance_position: [f32; 3],
+    pub instance_radius: f32,
+    pub instance_colour: [f32; 3],
+}
+
+implement_vertex!(SphereInstance, instance_position, instance_radius, instance_colour);
+
+/// A stick drawn in a batch: its ends, radius and colour, the unit cylinder stretched
+/// between the ends by the shader as bonds::model_matrix() would.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct StickInstance {
+    pub instance_from: [f32; 3],
+    pub instance_to: [f32; 3],
+    pub instance_radius: f32,
+    pub instance_colour: [f32; 3],
+}
+
+implement_vertex!(StickInstance, instance_from, instance_to, instance_radius, instance_colour);
+
+/// Whether the GPU can draw instances, which batches need.
+pub fn is_supported<F: ?Sized>(facade: &F) -> bool where F: Facade {
+    let instance = SphereInstance {instance_position: [0.0; 3], instance_radius: 1.0, instance_colour: [0.0; 3]};
+    VertexBuffer::new(facade, &[instance]).ok().map_or(false, |buffer| buffer.per_instance().is_ok())
+}
+
+/// The instances of a batch, on the GPU, kept from frame to frame. The buffer is
+/// persistently mapped where the GPU allows, so a frame that moves the atoms (a
+/// step of a trajectory, say) writes the instances that changed straight into it,
+/// with no new buffer and no copy of the rest, and a frame in which nothing they're
+/// made from changed doesn't make them at all (see keep()). It's made again only
+/// when the number of instances changes.
+pub struct InstanceBuffer<T> where T: glium::Vertex + PartialEq + Send + 'static {
+    buffer: Option<VertexBuffer<T>>,
+    /// What's in the buffer, to find what's changed.
+    instances: Vec<T>,
+    /// How many instances the last update wrote.
+    written: usize,
+}
+
+impl<T> InstanceBuffer<T> where T: glium::Vertex + PartialEq + Send + 'static {
+    pub fn new() -> InstanceBuffer<T> {
+        InstanceBuffer {
+            buffer: None,
+            instances: Vec::new(),
+            written: 0,
+        }
+    }
+
+    /// Takes this frame's instances, as they're made, writing each that differs from
+    /// what's there into the buffer.
+    pub fn update<F: ?Sized, I>(&mut self, facade: &F, instances: I) where F: Facade, I: Iterator<Item = T> {
+        let mut count = 0;
+        let mut written = 0;
+        {
+            let mut mapping = self.buffer.as_mut().map(|buffer| buffer.map_write());
+            for instance in instances {
+                if count < self.instances.len() {
+                    if self.instances[count] != instance {
+                        self.instances[count] = instance;
+                        if let Some(ref mut mapping) = mapping {mapping.set(count, instance)}
+                        written += 1;
+                    }
+                } else {
+                    self.instances.push(instance);
+                }
+                count += 1;
+            }
+        }
+        self.instances.truncate(count);
+        if self.buffer.as_ref().map_or(0, |buffer| buffer.len()) != count {
+            // (an empty buffer can't be drawn, so there's none)
+            self.buffer = if self.instances.is_empty() {
+                None
+            } else {
+                VertexBuffer::persistent(facade, &self.instances)
+                    .or_else(|_| VertexBuffer::dynamic(facade, &self.instances)).ok()
+            };
+            written = count;
+        }
+        self.written = written;
+    }
+
+    /// Keeps last frame's instances, for a frame in which nothing they're made from
+    /// changed, making and writing nothing.
+    pub fn keep(&mut self) {
+        self.written = 0;
+    }
+
+    /// Lets the buffer go, e.g. once the structure is too small to batch.
+    pub fn clear(&mut self) {
+        self.buffer = None;
+        self.instances = Vec::new();
+        self.written = 0;
+    }
+
+    /// The buffer to draw with, or None with no instances.
+    pub fn buffer(&self) -> Option<&VertexBuffer<T>> {self.buffer.as_ref()}
+
+    pub fn count(&self) -> usize {self.instances.len()}
+
+    pub fn written(&self) -> usize {self.written}
+
+    /// How much GPU memory the buffer takes.
+    pub fn bytes(&self) -> usize {self.buffer.as_ref().map_or(0, |buffer| buffer.get_size())}
+}
@@ -0,0 +1,122 @@
+use molecule::Molecule;
+use selection::Selection;
+
+// ============================================================
+// Ligands
+// ============================================================
+/// Residue names of water, which isn't a ligand.
+pub const WATERS : [&'static str; 4] = ["HOH", "WAT", "DOD", "H2O"];
+
+/// Hetero residues with fewer atoms than this (ions, mostly) aren't ligands.
+const MIN_LIGAND_ATOMS : usize = 6;
+
+/// Every ligand: each residue of HETATM records which isn't water and has enough
+/// atoms, in the order they're in the file.
+pub fn ligands(in_molecule : &Molecule) -> Vec<Selection> {
+    let mut residues : Vec<(String, i32, String, Vec<usize>)> = Vec::new();
+    for (i, atom) in in_molecule.atoms().iter().enumerate() {
+        let residue = match *atom.residue() {
+            Some(ref residue) if atom.is_hetero() && !WATERS.contains(&residue.name()) => residue,
+            _ => continue,
+        };
+        let found = residues.iter().position(|&(ref chain, number, ref name, _)| {
+            chain == residue.chain() && number == *residue.number() && name == residue.name()
+        });
+        match found {
+            Some(k) => residues[k].3.push(i),
+            None => residues.push((residue.chain().to_owned(), *residue.number(),
+                                   residue.name().to_owned(), vec![i])),
+        }
+    }
+    residues.iter()
+        .filter(|&&(_, _, _, ref atoms)| atoms.len() >= MIN_LIGAND_ATOMS)
+        .map(|&(_, _, _, ref atoms)| Selection::from_atoms(atoms))
+        .collect()
+}
+
+// ============================================================
+// Binding Site
+// ============================================================
+/// Residues with an atom this close (in Angstroms) to the ligand are in its site.
+pub const SITE_CUTOFF : f32 = 5.0;
+
+/// Nitrogens and oxygens this close are taken to be hydrogen bonded.
+pub const HYDROGEN_BOND_DISTANCE : f32 = 3.5;
+
+/// A ligand and the residues around it: what's shown of the molecule in the binding
+/// site view.
+pub struct BindingSite {
+    _ligand         : Selection,
+    /// The ligand and every residue it comes within the cutoff of, whole.
+    _shown          : Selection,
+    /// Pairs of (ligand atom, site atom), both nitrogen or oxygen, close enough to
+    /// hydrogen bond. Found by distance alone.
+    _hydrogen_bonds : Vec<[usize;2]>,
+    _residue_count  : usize,
+}
+
+impl BindingSite {
+    pub fn new(in_molecule : &Molecule, in_ligand : &Selection, in_cutoff : &f32) -> BindingSite {
+        let atoms = in_molecule.atoms();
+        let distance = |i : usize, j : usize| {
+            let (a, b) = (atoms[i].position(), atoms[j].position());
+            ((b[0]-a[0]).powi(2) + (b[1]-a[1]).powi(2) + (b[2]-a[2]).powi(2)).sqrt()
+        };
+        let is_polar = |i : usize| {
+            let symbol = atoms[i].species().symbol();
+            symbol == "N" || symbol == "O"
+        };
+
+        let mut near : Vec<usize> = Vec::new();
+        let mut hydrogen_bonds = Vec::new();
+        for j in (0..atoms.len()).filter(|j| !in_ligand.contains(j)) {
+            let mut is_near = false;
+            for &i in in_ligand.atoms() {
+                let d = distance(i, j);
+                if d <= *in_cutoff {is_near = true}
+                if d <= HYDROGEN_BOND_DISTANCE && is_polar(i) && is_polar(j) {
+                    hydrogen_bonds.push([i, j]);
+                }
+            }
+            if is_near {near.push(j)}
+        }
+
+        // whole residues, so side chains aren't cut off; atoms not in residues on their own
+        let mut residues : Vec<(String, i32)> = Vec::new();
+        let mut shown = in_ligand.atoms().to_owned();
+        for &j in &near {
+            match *atoms[j].residue() {
+                Some(ref residue) => {
+                    let key = (residue.chain().to_owned(), *residue.number());
+                    if !residues.contains(&key) {residues.push(key)}
+                },
+                None => shown.push(j),
+            }
+        }
+        for (j, atom) in atoms.iter().enumerate() {
+            if let Some(ref residue) = *atom.residue() {
+                if residues.iter().any(|&(ref chain, number)| chain == residue.chain() && number == *residue.number())
+                && !in_ligand.contains(&j) {
+                    shown.push(j);
+                }
+            }
+        }
+
+        BindingSite {
+            _ligand         : in_ligand.to_owned(),
+            _shown          : Selection::from_atoms(&shown),
+            _hydrogen_bonds : hydrogen_bonds,
+            _residue_count  : residues.len(),
+        }
+    }
+
+    pub fn ligand(&self) -> &Selection {&self._ligand}
+    pub fn shown(&self) -> &Selection {&self._shown}
+    pub fn hydrogen_bonds(&self) -> &Vec<[usize;2]> {&self._hydrogen_bonds}
+    pub fn residue_count(&self) -> &usize {&self._residue_count}
+
+    /// Where the site is, and how big, to frame it.
+    pub fn bounding_sphere(&self, in_molecule : &Molecule) -> ([f32;3], f32) {
+        self._shown.bounding_sphere(in_molecule)
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::HashMap;
+
+use cell::Cell;
+use matrix::Matrix;
+use molecule::Molecule;
+
+// ============================================================
+// Covalent Radii
+// ============================================================
+/// Single-bond covalent radii in Angstroms (Cordero et al., Dalton Trans., 2008),
+/// for the elements likely to turn up in a structure file.
+const COVALENT_RADII : [(&'static str, f32); 30] = [
+    ("H" , 0.31), ("Li", 1.28), ("B" , 0.84), ("C" , 0.76), ("N" , 0.71),
+    ("O" , 0.66), ("F" , 0.57), ("Na", 1.66), ("Mg", 1.41), ("Al", 1.21),
+    ("Si", 1.11), ("P" , 1.07), ("S" , 1.05), ("Cl", 1.02), ("K" , 2.03),
+    ("Ca", 1.76), ("Ti", 1.60), ("V" , 1.53), ("Cr", 1.39), ("Mn", 1.39),
+    ("Fe", 1.32), ("Co", 1.26), ("Ni", 1.24), ("Cu", 1.32), ("Zn", 1.22),
+    ("Se", 1.20), ("Br", 1.20), ("Pd", 1.39), ("Ag", 1.45), ("I" , 1.39),
+];
+
+/// The covalent radius of an element, or None if it isn't in the table.
+pub fn covalent_radius(in_symbol : &str) -> Option<f32> {
+    COVALENT_RADII.iter().find(|&&(symbol, _)| symbol == in_symbol).map(|&(_, radius)| radius)
+}
+
+// ============================================================
+// Bonds
+// ============================================================
+/// Two atoms closer than this times the sum of their covalent radii are bonded.
+pub const BOND_TOLERANCE : f32 = 1.15;
+
+/// Every pair of bonded atoms (lowest index first), by distance alone. Atoms of
+/// elements without a covalent radius aren't bonded to anything, and bonds across
+/// periodic boundaries are left to find_periodic_bonds().
+pub fn find_bonds(in_molecule : &Molecule) -> Vec<[usize;2]> {
+    let atoms = in_molecule.atoms();
+    let radii : Vec<Option<f32>> = atoms.iter()
+        .map(|atom| covalent_radius(atom.species().symbol()))
+        .collect();
+    let max_radius = radii.iter().filter_map(|&r| r).fold(0.0f32, f32::max);
+    if max_radius <= 0.0 {return Vec::new()}
+
+    // sort the atoms into cubes the size of the longest possible bond, so only
+    // atoms in neighbouring cubes need comparing
+    let cube_size = 2.0*max_radius*BOND_TOLERANCE;
+    let cube = |position : &[f32;3]| -> [i64;3] {
+        [
+            (position[0]/cube_size).floor() as i64,
+            (position[1]/cube_size).floor() as i64,
+            (position[2]/cube_size).floor() as i64,
+        ]
+    };
+    let mut cubes : HashMap<[i64;3], Vec<usize>> = HashMap::new();
+    for (i, atom) in atoms.iter().enumerate() {
+        if radii[i].is_some() {
+            cubes.entry(cube(atom.position())).or_insert_with(Vec::new).push(i);
+        }
+    }
+
+    let mut bonds = Vec::new();
+    for (i, atom) in atoms.iter().enumerate() {
+        let radius = match radii[i] {Some(r) => r, None => continue};
+        let c = cube(atom.position());
+        for dx in -1..2 {
+            for dy in -1..2 {
+                for dz in -1..2 {
+                    let neighbours = match cubes.get(&[c[0]+dx, c[1]+dy, c[2]+dz]) {
+                        Some(neighbours) => neighbours,
+                        None => continue,
+                    };
+                    for &j in neighbours.iter().filter(|&&j| j > i) {
+                        let cutoff = (radius + radii[j].unwrap())*BOND_TOLERANCE;
+                        let a = atom.position();
+                        let b = atoms[j].position();
+                        let d = [b[0]-a[0], b[1]-a[1], b[2]-a[2]];
+                        if d[0]*d[0]+d[1]*d[1]+d[2]*d[2] < cutoff*cutoff {
+                            bonds.push([i, j]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    bonds.sort();
+    bonds
+}
+
+/// Every pair of atoms bonded across the cell's faces: an atom and the image of
+/// another in a neighbouring cell, given as the atoms (lowest index first) and the
+/// lattice vectors added to the second's position to reach the image. Bonds inside
+/// the cell are left to find_bonds(). Empty without a cell.
+pub fn find_periodic_bonds(in_molecule : &Molecule) -> Vec<([usize;2], [i32;3])> {
+    let cell = match *in_molecule.cell() {
+        Some(cell) => cell,
+        None => return Vec::new(),
+    };
+    let atoms = in_molecule.atoms();
+    let radii : Vec<Option<f32>> = atoms.iter()
+        .map(|atom| covalent_radius(atom.species().symbol()))
+        .collect();
+    let max_radius = radii.iter().filter_map(|&r| r).fold(0.0f32, f32::max);
+    if max_radius <= 0.0 {return Vec::new()}
+
+    // as in find_bonds(), but looking around each atom's images for the others
+    let cube_size = 2.0*max_radius*BOND_TOLERANCE;
+    let cube = |position : &[f32;3]| -> [i64;3] {
+        [
+            (position[0]/cube_size).floor() as i64,
+            (position[1]/cube_size).floor() as i64,
+            (position[2]/cube_size).floor() as i64,
+        ]
+    };
+    let mut cubes : HashMap<[i64;3], Vec<usize>> = HashMap::new();
+    for (i, atom) in atoms.iter().enumerate() {
+        if radii[i].is_some() {
+            cubes.entry(cube(atom.position())).or_insert_with(Vec::new).push(i);
+        }
+    }
+
+    let mut bonds = Vec::new();
+    for (j, atom) in atoms.iter().enumerate() {
+        let radius = match radii[j] {Some(r) => r, None => continue};
+        for image in neighbouring_images() {
+            let shift = cell.to_cartesian(&[image[0] as f32, image[1] as f32, image[2] as f32]);
+            let p = atom.position();
+            let b = [p[0]+shift[0], p[1]+shift[1], p[2]+shift[2]];
+            let c = cube(&b);
+            for dx in -1..2 {
+                for dy in -1..2 {
+                    for dz in -1..2 {
+                        let neighbours = match cubes.get(&[c[0]+dx, c[1]+dy, c[2]+dz]) {
+                            Some(neighbours) => neighbours,
+                            None => continue,
+                        };
+                        // each bond is found from both ends, with opposite images,
+                        // so only the one from the lower index is kept
+                        for &i in neighbours.iter().filter(|&&i| i < j || (i == j && image > [0, 0, 0])) {
+                            let cutoff = (radius + radii[i].unwrap())*BOND_TOLERANCE;
+                            let a = atoms[i].position();
+                            let d = [b[0]-a[0], b[1]-a[1], b[2]-a[2]];
+                            if d[0]*d[0]+d[1]*d[1]+d[2]*d[2] < cutoff*cutoff {
+                                bonds.push(([i, j], image));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    bonds.sort();
+    bonds
+}
+
+/// The 26 cells around the cell, as the lattice vectors to each.
+fn neighbouring_images() -> Vec<[i32;3]> {
+    let mut images = Vec::with_capacity(26);
+    for i in -1..2 {
+        for j in -1..2 {
+            for k in -1..2 {
+                if [i, j, k] != [0, 0, 0] {images.push([i, j, k])}
+            }
+        }
+    }
+    images
+}
+
+/// Where a bond from `in_from` (in the cell) to `in_to` (in a neighbouring one)
+/// leaves the cell, for drawing it cut off at the face. Halfway along if `in_from`
+/// isn't in the cell.
+pub fn cell_exit(in_cell : &Cell, in_from : &[f32;3], in_to : &[f32;3]) -> [f32;3] {
+    let o = in_cell.origin();
+    let from = in_cell.to_fractional(&[in_from[0]-o[0], in_from[1]-o[1], in_from[2]-o[2]]);
+    let d = in_cell.to_fractional(&[in_to[0]-in_from[0], in_to[1]-in_from[1], in_to[2]-in_from[2]]);
+    let mut t = 0.5;
+    if from.iter().all(|&f| f >= 0.0 && f <= 1.0) {
+        t = 1.0f32;
+        for k in 0..3 {
+            if d[k] > 0.0 {t = t.min((1.0-from[k])/d[k])}
+            if d[k] < 0.0 {t = t.min(-from[k]/d[k])}
+        }
+    }
+    [
+        in_from[0]+t*(in_to[0]-in_from[0]),
+        in_from[1]+t*(in_to[1]-in_from[1]),
+        in_from[2]+t*(in_to[2]-in_from[2]),
+    ]
+}
+
+/// The length of each bond, at the atoms' current positions.
+pub fn lengths(in_molecule : &Molecule, in_bonds : &Vec<[usize;2]>) -> Vec<f32> {
+    let positions : Vec<[f32;3]> = in_molecule.atoms().iter().map(|atom| *atom.position()).collect();
+    lengths_between(&positions, in_bonds)
+}
+
+/// The length of each bond with the atoms at some other positions, e.g. a reference
+/// structure's. There must be a position for every atom.
+pub fn lengths_between(in_positions : &Vec<[f32;3]>, in_bonds : &Vec<[usize;2]>) -> Vec<f32> {
+    in_bonds.iter().map(|bond| {
+        let a = in_positions[bond[0]];
+        let b = in_positions[bond[1]];
+        ((b[0]-a[0]).powi(2) + (b[1]-a[1]).powi(2) + (b[2]-a[2]).powi(2)).sqrt()
+    }).collect()
+}
+
+/// The expected length of each bond: the sum of the atoms' covalent radii.
+pub fn ideal_lengths(in_molecule : &Molecule, in_bonds : &Vec<[usize;2]>) -> Vec<f32> {
+    let radius = |i : usize| covalent_radius(in_molecule.atoms()[i].species().symbol()).unwrap_or(0.0);
+    in_bonds.iter().map(|bond| radius(bond[0]) + radius(bond[1])).collect()
+}
+
+/// A rough order for each bond, from how much shorter it is than a single bond
+/// (the sum of the covalent radii): 1, 1.5 (aromatic, or a resonance like a
+/// carboxylate's), 2 or 3. Bonds are only found by distance, so this is a guess,
+/// right for most organic bonds and not meant for metals.
+pub fn estimated_orders(in_molecule : &Molecule, in_bonds : &Vec<[usize;2]>) -> Vec<f32> {
+    let ideal = ideal_lengths(in_molecule, in_bonds);
+    lengths(in_molecule, in_bonds).iter().zip(ideal.iter()).map(|(length, ideal)| {
+        let ratio = length/ideal.max(1e-6);
+        if ratio < 0.84 {3.0} else if ratio < 0.9 {2.0} else if ratio < 0.95 {1.5} else {1.0}
+    }).collect()
+}
+
+/// The lengths each bond is compared with: its length in a reference structure if
+/// there is one (a position for every atom), otherwise its ideal length.
+pub fn reference_lengths(in_molecule : &Molecule, in_bonds : &Vec<[usize;2]>,
+                         in_reference : &Option<Vec<[f32;3]>>) -> Vec<f32> {
+    match *in_reference {
+        Some(ref positions) => lengths_between(positions, in_bonds),
+        None => ideal_lengths(in_molecule, in_bonds),
+    }
+}
+
+/// Places the unit cylinder (see model::DefaultModels) as a bond of some radius
+/// between two points.
+pub fn model_matrix(in_from : &[f32;3], in_to : &[f32;3], in_radius : &f32) -> Matrix {
+    let d = [in_to[0]-in_from[0], in_to[1]-in_from[1], in_to[2]-in_from[2]];
+    let length = (d[0]*d[0]+d[1]*d[1]+d[2]*d[2]).sqrt().max(1e-6);
+    let axis = [d[0]/length, d[1]/length, d[2]/length];
+    // any two directions perpendicular to the bond and each other
+    let other = if axis[0].abs() < 0.9 {[1.0, 0.0, 0.0]} else {[0.0, 1.0, 0.0]};
+    let mut u = [
+        axis[1]*other[2]-axis[2]*other[1],
+        axis[2]*other[0]-axis[0]*other[2],
+        axis[0]*other[1]-axis[1]*other[0],
+    ];
+    let u_length = (u[0]*u[0]+u[1]*u[1]+u[2]*u[2]).sqrt();
+    for k in 0..3 {u[k] /= u_length}
+    let v = [
+        axis[1]*u[2]-axis[2]*u[1],
+        axis[2]*u[0]-axis[0]*u[2],
+        axis[0]*u[1]-axis[1]*u[0],
+    ];
+    let r = *in_radius;
+    let half = length/2.0;
+    Matrix::new([
+        [u[0]*r, v[0]*r, axis[0]*half, (in_from[0]+in_to[0])/2.0],
+        [u[1]*r, v[1]*r, axis[1]*half, (in_from[1]+in_to[1])/2.0],
+        [u[2]*r, v[2]*r, axis[2]*half, (in_from[2]+in_to[2])/2.0],
+        [0.0   , 0.0   , 0.0         , 1.0                      ]
+    ])
+}
+
+// ============================================================
+// Bond Display
+// ============================================================
+/// How bonds are drawn.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum BondDisplay {
+    Hidden,
+    /// Plain grey sticks.
+    Plain,
+    /// Coloured by how far each bond's length is from its reference length: blue
+    /// for short, white for right, red for long.
+    Deviation,
+}
+
+impl BondDisplay {
+    pub fn next(&self) -> BondDisplay {
+        match *self {
+            BondDisplay::Hidden    => BondDisplay::Plain,
+            BondDisplay::Plain     => BondDisplay::Deviation,
+            BondDisplay::Deviation => BondDisplay::Hidden,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            BondDisplay::Hidden    => "hidden",
+            BondDisplay::Plain     => "plain",
+            BondDisplay::Deviation => "coloured by length deviation",
+        }
+    }
+
+    /// The display with a name as --bonds takes it: hidden, plain or deviation.
+    pub fn parse(in_name : &str) -> Result<BondDisplay, String> {
+        match in_name.to_lowercase().as_str() {
+            "hidden"    => Ok(BondDisplay::Hidden),
+            "plain"     => Ok(BondDisplay::Plain),
+            "deviation" => Ok(BondDisplay::Deviation),
+            _ => Err(format!("expected hidden, plain or deviation, not \"{}\"", in_name)),
+        }
+    }
+}
+
+/// How bonds across the cell's faces are drawn, when bonds are.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum PeriodicBonds {
+    Hidden,
+    /// From each atom to the face the bond goes through.
+    Cut,
+    /// All the way to a translucent copy of the atom on the other side.
+    Ghosts,
+}
+
+impl PeriodicBonds {
+    pub fn next(&self) -> PeriodicBonds {
+        match *self {
+            PeriodicBonds::Hidden => PeriodicBonds::Cut,
+            PeriodicBonds::Cut    => PeriodicBonds::Ghosts,
+            PeriodicBonds::Ghosts => PeriodicBonds::Hidden,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            PeriodicBonds::Hidden => "hidden",
+            PeriodicBonds::Cut    => "cut at the cell's faces",
+            PeriodicBonds::Ghosts => "drawn to ghost images",
+        }
+    }
+}
+
+/// The relative deviation (e.g. 0.1 for 10% long) at which bonds are fully red or blue.
+pub const FULL_DEVIATION : f32 = 0.15;
+
+/// The colour of a bond whose length is off from its reference by a fraction
+/// `in_deviation` of it.
+pub fn deviation_colour(in_deviation : &f32) -> [f32;3] {
+    let t = (in_deviation/FULL_DEVIATION).max(-1.0).min(1.0);
+    if t < 0.0 {
+        [1.0+t, 1.0+t, 1.0]
+    } else {
+        [1.0, 1.0-t, 1.0-t]
+    }
+}
+
+// ============================================================
+// Fragments
+// ============================================================
+/// Which fragment (group of atoms joined by bonds) each atom is in, numbered from 0
+/// in order of each fragment's first atom.
+pub fn fragments(in_atom_count : &usize, in_bonds : &Vec<[usize;2]>) -> Vec<usize> {
+    // union-find over the bonds, joining each root to the lower-indexed one
+    let mut parents : Vec<usize> = (0..*in_atom_count).collect();
+    fn root(parents : &mut Vec<usize>, i : usize) -> usize {
+        let mut r = i;
+        while parents[r] != r {r = parents[r]}
+        let mut j = i;
+        while parents[j] != r {
+            let next = parents[j];
+            parents[j] = r;
+            j = next;
+        }
+        r
+    }
+    for bond in in_bonds {
+        let a = root(&mut parents, bond[0]);
+        let b = root(&mut parents, bond[1]);
+        if a < b {parents[b] = a} else if b < a {parents[a] = b}
+    }
+    let mut numbers : HashMap<usize, usize> = HashMap::new();
+    (0..*in_atom_count).map(|i| {
+        let r = root(&mut parents, i);
+        let next = numbers.len();
+        *numbers.entry(r).or_insert(next)
+    }).collect()
+}
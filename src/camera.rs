@@ -1,32 +1,114 @@
-extern crate glium;
-
 use std::f32; // pi
 
 use matrix::Matrix;
 use quaternion::Quaternion;
+use atom::Atom;
+use molecule::Molecule;
+
+pub mod animation;
+
+// ============================================================
+// Projection
+// ============================================================
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Projection {
+    Perspective,
+    /// Parallel rows of atoms stay parallel. The visible region at the focus is
+    /// the same size as with perspective, so switching doesn't jump.
+    Orthographic,
+}
+
+impl Projection {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Projection::Perspective  => "perspective",
+            Projection::Orthographic => "orthographic",
+        }
+    }
+
+    /// The projection with a name (as name() gives it), e.g. for --projection.
+    pub fn parse(in_name : &str) -> Result<Projection, String> {
+        [Projection::Perspective, Projection::Orthographic].iter()
+            .find(|projection| projection.name() == in_name.to_lowercase()).cloned()
+            .ok_or_else(|| format!("expected perspective or orthographic, not \"{}\"", in_name))
+    }
+}
+
+/// One eye of a stereo pair (see Camera::eye_matrices() and stereo.rs).
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+// ============================================================
+// Camera Animation
+// ============================================================
+/// A smooth transition between two views, started by Camera::animate_to(). Its time
+/// is the frames' (see frame_clock.rs), so a replay or movie moves it the same
+/// whatever the frame rate.
+#[derive(Copy, Clone, Debug)]
+struct CameraAnimation {
+    _elapsed          : f32,
+    _duration         : f32,
+    _from_focus       : [f32;3],
+    _from_r           : f32,
+    _from_orientation : Quaternion,
+    _to_focus         : [f32;3],
+    _to_r             : f32,
+    _to_orientation   : Quaternion,
+}
 
 // ============================================================
 // Camera
 // ============================================================
+/// Everything, including the matrices, is (de)serialised, so a saved camera can be
+/// drawn straight away. Call set_screen_size() after loading one into another window.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Camera {
+    #[serde(rename = "focus")]
     _focus              : [f32;3],
+    #[serde(rename = "r")]
     _r                  : f32,
+    #[serde(rename = "quaternion")]
     _quaternion         : Quaternion,
+    #[serde(rename = "cos_half_step")]
     _cos_half_step      : f32,
+    #[serde(rename = "sin_half_step")]
     _sin_half_step      : f32,
+    #[serde(rename = "r_step")]
     _r_step             : f32,
+    #[serde(rename = "field_of_view")]
     _field_of_view      : f32,
+    #[serde(rename = "projection")]
+    _projection         : Projection,
+    #[serde(rename = "near_plane")]
     _near_plane         : f32,
+    #[serde(rename = "far_plane")]
     _far_plane          : f32,
+    #[serde(rename = "screen_size")]
     _screen_size        : [u32;2],
+    /// Width over height of the framebuffer.
+    #[serde(rename = "aspect_ratio")]
+    _aspect_ratio       : f32,
+    #[serde(rename = "view_matrix")]
     _view_matrix        : Matrix,
-    _perspective_matrix : Matrix,
+    /// The distance between the eyes for stereo rendering, as a fraction of the
+    /// distance to the focus, so the depth effect stays comfortable when zooming.
+    #[serde(rename = "eye_separation")]
+    _eye_separation     : f32,
+    #[serde(rename = "projection_matrix")]
+    _projection_matrix  : Matrix,
+    #[serde(rename = "vp_matrix")]
     _vp_matrix          : Matrix,
+    /// The transition in progress, if any. Not worth saving.
+    #[serde(skip)]
+    _animation          : Option<CameraAnimation>,
 }
 
 impl Camera {
     pub fn new (
-        in_display               : &glium::backend::glutin_backend::GlutinFacade,
+        in_screen_size           : &[u32;2],
         in_focus                 : &[f32;3],
         in_theta_degrees         : &f32,
         in_phi_degrees           : &f32,
@@ -37,8 +119,6 @@ impl Camera {
         in_far_plane             : &f32
     ) -> Camera {
 
-        let (w, h) = (*in_display).get_framebuffer_dimensions();
-
         let angular_step_radians = f32::consts::PI/36.0;
         let half_step_radians = angular_step_radians/2.0;
 
@@ -50,13 +130,18 @@ impl Camera {
             _sin_half_step      : half_step_radians.sin(),
             _r_step             : 0.1,
             _field_of_view      : in_field_of_view_degrees*f32::consts::PI/180.0,
+            _projection         : Projection::Perspective,
             _near_plane         : in_near_plane.to_owned(),
             _far_plane          : in_far_plane.to_owned(),
-            _screen_size        : [w, h],
+            _screen_size        : *in_screen_size,
+            _aspect_ratio       : 1.0,   // dummy value
             _view_matrix        : Matrix::new([[0.0;4];4]),   // dummy value
-            _perspective_matrix : Matrix::new([[0.0;4];4]),   // dummy value
+            _eye_separation     : 1.0/30.0,
+            _projection_matrix  : Matrix::new([[0.0;4];4]),   // dummy value
             _vp_matrix          : Matrix::new([[0.0;4];4]),   // dummy value
+            _animation          : None,
         };
+        camera.set_screen_size(&in_screen_size[0], &in_screen_size[1]);
         camera.set_angles(in_theta_degrees, in_phi_degrees, in_psi_degrees, in_r);
         camera
     }
@@ -64,6 +149,17 @@ impl Camera {
     pub fn view_matrix(&self) -> &Matrix {&self._view_matrix}
     pub fn vp_matrix(&self) -> &Matrix {&self._vp_matrix}
     pub fn quaternion(&self) -> &Quaternion {&self._quaternion}
+    pub fn focus(&self) -> &[f32;3] {&self._focus}
+    pub fn r(&self) -> &f32 {&self._r}
+    pub fn projection(&self) -> &Projection {&self._projection}
+    pub fn screen_size(&self) -> &[u32;2] {&self._screen_size}
+    /// In radians, across the smaller screen dimension.
+    pub fn field_of_view(&self) -> &f32 {&self._field_of_view}
+
+    pub fn set_projection(&mut self, in_projection : &Projection) {
+        self._projection = in_projection.to_owned();
+        self.update();
+    }
 
     pub fn set_angles(
         &mut self,
@@ -72,10 +168,26 @@ impl Camera {
         in_psi_degrees   : &f32,
         in_r             : &f32
     ) {
+        self._quaternion = Camera::orientation_from_angles(
+            in_theta_degrees,
+            in_phi_degrees,
+            in_psi_degrees
+        );
+        self._r = in_r.to_owned();
+        self.update();
+    }
+
+    /// The orientation set by set_angles(): spin by psi about the view axis, after
+    /// azimuth by theta, after orbit by phi.
+    pub fn orientation_from_angles(
+        in_theta_degrees : &f32,
+        in_phi_degrees   : &f32,
+        in_psi_degrees   : &f32
+    ) -> Quaternion {
         let half_theta_radians = in_theta_degrees*f32::consts::PI/360.0;
         let half_phi_radians = in_phi_degrees*f32::consts::PI/360.0;
         let half_psi_radians = in_psi_degrees*f32::consts::PI/360.0;
-        self._quaternion = Quaternion::new(
+        Quaternion::new(
             &half_psi_radians.cos(),
             &0.0,
             &0.0,
@@ -90,31 +202,260 @@ impl Camera {
             &0.0,
             &half_phi_radians.sin(),
             &0.0,
-        );
-        
-        self._r = in_r.to_owned();
+        )
+    }
+
+    /// The world-space direction the camera looks along with a given orientation.
+    fn forward(in_orientation : &Quaternion) -> [f32;3] {
+        // the rotation's rows are the view's right, up and forward vectors in world space
+        let rotation = in_orientation.rotation_matrix();
+        let row = rotation.contents()[2];
+        [row[0], row[1], row[2]]
+    }
+
+    /// Moves the camera smoothly to a position and orientation over a number of
+    /// seconds, keeping its distance from the focus. Call animate() every frame.
+    #[allow(dead_code)]
+    pub fn animate_to(&mut self, in_position : &[f32;3], in_orientation : &Quaternion,
+                      in_seconds : &f32) {
+        let forward = Camera::forward(in_orientation);
+        let focus = [
+            in_position[0]+self._r*forward[0],
+            in_position[1]+self._r*forward[1],
+            in_position[2]+self._r*forward[2],
+        ];
+        let r = self._r;
+        self.animate_to_focus(&focus, &r, in_orientation, in_seconds);
+    }
+
+    /// Like animate_to(), but given the focus to end up looking at from distance r.
+    pub fn animate_to_focus(&mut self, in_focus : &[f32;3], in_r : &f32,
+                            in_orientation : &Quaternion, in_seconds : &f32) {
+        let mut orientation = in_orientation.to_owned();
+        orientation.normalise();
+        self._animation = Some(CameraAnimation {
+            _elapsed          : 0.0,
+            _duration         : in_seconds.max(0.0),
+            _from_focus       : self._focus,
+            _from_r           : self._r,
+            _from_orientation : self._quaternion,
+            _to_focus         : in_focus.to_owned(),
+            _to_r             : in_r.to_owned(),
+            _to_orientation   : orientation,
+        });
+        self.animate(&0.0);
+    }
+
+    /// Moves an animation on by a frame's seconds (frame_clock::FrameClock::tick()).
+    /// Returns whether one is still running.
+    pub fn animate(&mut self, in_frame_seconds : &f32) -> bool {
+        let animation = match self._animation.as_mut() {
+            Some(animation) => {
+                animation._elapsed += *in_frame_seconds;
+                *animation
+            },
+            None => return false,
+        };
+        let t = if animation._elapsed >= animation._duration || animation._duration == 0.0 {
+            1.0
+        } else {
+            animation._elapsed/animation._duration
+        };
+        // ease in and out, so the view doesn't jerk into motion or to a stop
+        let eased = t*t*(3.0-2.0*t);
+        for i in 0..3 {
+            self._focus[i] = animation._from_focus[i]
+                           + (animation._to_focus[i]-animation._from_focus[i])*eased;
+        }
+        self._r = animation._from_r + (animation._to_r-animation._from_r)*eased;
+        self._quaternion = animation._from_orientation.slerp(&animation._to_orientation, &eased);
+        if t >= 1.0 {
+            self._animation = None;
+        }
         self.update();
+        self._animation.is_some()
     }
 
-    pub fn zoom_in (&mut self) {if self._r > self._r_step {self._r -= self._r_step} self.update();}
-    pub fn zoom_out (&mut self) {self._r += self._r_step; self.update();}
-    pub fn spin_clockwise (&mut self) {
+    /// Rotates the view by an angle (in radians) about an axis in view space
+    /// (x right, y up, z into the screen).
+    pub fn rotate (&mut self, in_axis : &[f32;3], in_angle_radians : &f32) {
+        let norm = (in_axis[0]*in_axis[0]+in_axis[1]*in_axis[1]+in_axis[2]*in_axis[2]).sqrt();
+        if norm == 0.0 {return}
+        let half_angle = in_angle_radians/2.0;
+        let s = half_angle.sin()/norm;
         self._quaternion.left_multiply(&Quaternion::new(
-            &self._cos_half_step,
-            &0.0,
-            &0.0,
-            &-self._sin_half_step
+            &half_angle.cos(),
+            &(in_axis[0]*s),
+            &(in_axis[1]*s),
+            &(in_axis[2]*s),
         ));
-	    self.update();
+        self.update();
     }
-    pub fn spin_anticlockwise (&mut self) {
-        self._quaternion.left_multiply(&Quaternion::new(
-            &self._cos_half_step,
-            &0.0,
-            &0.0,
-            &self._sin_half_step
-        ));
-	self.update();
+
+    /// Arcball rotation: the cursor positions (in pixels from the top-left of the
+    /// window) are projected onto a virtual sphere filling the smaller screen
+    /// dimension, and the view rotated so the point under the cursor follows it.
+    pub fn arcball (&mut self, in_from : &[i32;2], in_to : &[i32;2]) {
+        let from = self.arcball_point(in_from);
+        let to = self.arcball_point(in_to);
+        let axis = [
+            from[1]*to[2]-from[2]*to[1],
+            from[2]*to[0]-from[0]*to[2],
+            from[0]*to[1]-from[1]*to[0],
+        ];
+        let cos_angle = from[0]*to[0]+from[1]*to[1]+from[2]*to[2];
+        self.rotate(&axis, &cos_angle.max(-1.0).min(1.0).acos());
+    }
+
+    /// The point on the unit arcball sphere under a cursor position. Points outside
+    /// the sphere are brought in to its rim, so dragging there spins about the view axis.
+    fn arcball_point (&self, in_cursor : &[i32;2]) -> [f32;3] {
+        let w = self._screen_size[0] as f32;
+        let h = self._screen_size[1] as f32;
+        let radius = w.min(h)/2.0;
+        let x = (in_cursor[0] as f32 - w/2.0)/radius;
+        let y = (h/2.0 - in_cursor[1] as f32)/radius;
+        let r_squared = x*x+y*y;
+        if r_squared <= 1.0 {
+            // z is into the screen, so the visible half of the sphere has z < 0
+            [x, y, -(1.0-r_squared).sqrt()]
+        } else {
+            let r = r_squared.sqrt();
+            [x/r, y/r, 0.0]
+        }
+    }
+
+    /// How far something at the focus's depth moves, in the world, to follow the
+    /// cursor moving by a number of pixels (down the screen for positive y).
+    pub fn drag_vector (&self, in_pixels : &[f32;2]) -> [f32;3] {
+        let w = self._screen_size[0] as f32;
+        let h = self._screen_size[1] as f32;
+        // the smaller screen dimension spans the field of view at the focus
+        let world_per_pixel = 2.0*self._r*(self._field_of_view/2.0).tan()/w.min(h);
+        // the rotation's rows are the view's right and up vectors in world space
+        let view = self._view_matrix.contents();
+        let mut vector = [0.0f32;3];
+        for i in 0..3 {
+            vector[i] = (view[0][i]*in_pixels[0] - view[1][i]*in_pixels[1])*world_per_pixel;
+        }
+        vector
+    }
+
+    /// Moves the focus (and so the camera) parallel to the image plane, so that
+    /// whatever is at the focus follows the cursor moving by a number of pixels.
+    pub fn pan (&mut self, in_pixels : &[f32;2]) {
+        let vector = self.drag_vector(in_pixels);
+        for i in 0..3 {
+            self._focus[i] -= vector[i];
+        }
+        self.update();
+    }
+
+    /// The world-space ray through a cursor position (in pixels from the top-left of
+    /// the window), as its origin and unit direction. Perspective rays start at the
+    /// camera; orthographic ones start on the camera's plane and all point forward.
+    pub fn ray (&self, in_cursor : &[i32;2]) -> ([f32;3], [f32;3]) {
+        self.pixel_ray(&[in_cursor[0] as f32, in_cursor[1] as f32])
+    }
+
+    /// The same, through any point of the window, e.g. somewhere inside a pixel.
+    pub fn pixel_ray (&self, in_point : &[f32;2]) -> ([f32;3], [f32;3]) {
+        let w = self._screen_size[0] as f32;
+        let h = self._screen_size[1] as f32;
+        // the smaller screen dimension spans the field of view
+        let half_size = w.min(h)/2.0;
+        let tan = (self._field_of_view/2.0).tan();
+        let x = (in_point[0] - w/2.0)/half_size*tan;
+        let y = (h/2.0 - in_point[1])/half_size*tan;
+        // in view space, with z into the screen
+        let (origin, direction) = match self._projection {
+            Projection::Perspective  => ([0.0, 0.0, 0.0, 1.0], [x, y, 1.0, 0.0]),
+            Projection::Orthographic => ([x*self._r, y*self._r, 0.0, 1.0], [0.0, 0.0, 1.0, 0.0]),
+        };
+        let inverse_view_matrix = self._view_matrix.rigid_inverse();
+        let origin = inverse_view_matrix*origin;
+        let direction = inverse_view_matrix*direction;
+        let length = (direction[0]*direction[0]+direction[1]*direction[1]+direction[2]*direction[2]).sqrt();
+        (
+            [origin[0], origin[1], origin[2]],
+            [direction[0]/length, direction[1]/length, direction[2]/length],
+        )
+    }
+
+    /// Where the ray through a cursor position meets the plane through the focus
+    /// facing the camera.
+    pub fn point_on_focus_plane (&self, in_cursor : &[i32;2]) -> [f32;3] {
+        let (origin, direction) = self.ray(in_cursor);
+        let forward = self._view_matrix.contents()[2];
+        let mut along = 0.0;
+        let mut facing = 0.0;
+        for k in 0..3 {
+            along += (self._focus[k]-origin[k])*forward[k];
+            facing += direction[k]*forward[k];
+        }
+        // every ray points into the screen, so facing is never 0
+        let t = along/facing;
+        [origin[0]+t*direction[0], origin[1]+t*direction[1], origin[2]+t*direction[2]]
+    }
+
+    #[allow(dead_code)]
+    pub fn set_focus (&mut self, in_focus : &[f32;3]) {
+        self._focus = in_focus.to_owned();
+        self.update();
+    }
+
+    /// Moves the camera along the view axis, towards (negative) or away from
+    /// (positive) the focus. Never passes through the focus or beyond the far plane.
+    pub fn dolly (&mut self, in_dr : &f32) {
+        self._r = (self._r + in_dr).max(self._r_step).min(self._far_plane - self._r_step);
+        self.update();
+    }
+
+    /// Zooms by narrowing (factor < 1) or widening (factor > 1) the field of view,
+    /// keeping it between 1 and 170 degrees.
+    pub fn scale_field_of_view (&mut self, in_factor : &f32) {
+        let min = f32::consts::PI/180.0;
+        let max = 170.0*f32::consts::PI/180.0;
+        self._field_of_view = (self._field_of_view*in_factor).max(min).min(max);
+        self.update();
+    }
+
+    /// Points the camera at the middle of a molecule, from just far enough away that
+    /// all of it fits on screen with a margin, keeping the current orientation.
+    /// The clipping planes and zoom step are scaled to match. in_radius is the radius
+    /// each atom is drawn with.
+    pub fn fit<R> (&mut self, in_molecule : &Molecule, in_radius : R) where R : Fn(&Atom) -> f32 {
+        let (centre, radius) = in_molecule.bounding_sphere(in_radius);
+        let radius = if radius > 0.0 {radius} else {1.0};
+        let margin = 1.2;
+        // the field of view spans the smaller screen dimension
+        let r = margin*radius/(self._field_of_view/2.0).sin();
+        self._focus = centre;
+        self._r = r;
+        self._r_step = 0.05*r;
+        self._near_plane = 0.01*r;
+        self._far_plane = 10.0*r;
+        self._animation = None;
+        self.update();
+    }
+
+    /// Glides the camera over some seconds to look at a sphere (e.g. a binding site)
+    /// from just far enough away that it fills the screen, keeping the orientation.
+    pub fn animate_to_sphere(&mut self, in_centre : &[f32;3], in_radius : &f32, in_seconds : &f32) {
+        let radius = if *in_radius > 0.0 {*in_radius} else {1.0};
+        let margin = 1.2;
+        let r = margin*radius/(self._field_of_view/2.0).sin();
+        let orientation = self._quaternion;
+        self.animate_to_focus(in_centre, &r, &orientation, in_seconds);
+    }
+
+    pub fn zoom_in (&mut self) {let dr = -self._r_step; self.dolly(&dr);}
+    pub fn zoom_out (&mut self) {let dr = self._r_step; self.dolly(&dr);}
+    /// Turns the view anticlockwise about the line of sight.
+    pub fn spin (&mut self, in_angle_radians : &f32) {
+        let half_angle = in_angle_radians/2.0;
+        self._quaternion.left_multiply(&Quaternion::new(&half_angle.cos(), &0.0, &0.0, &half_angle.sin()));
+        self.update();
     }
     pub fn azimuth_up (&mut self) {
         self._quaternion.left_multiply(&Quaternion::new(
@@ -153,32 +494,76 @@ impl Camera {
         self.update();
     }
     
+    /// Call whenever the framebuffer changes size. A minimised window has no size,
+    /// in which case the old one is kept so the matrices stay finite.
     pub fn set_screen_size(&mut self, in_x : &u32, in_y : &u32) {
+        if *in_x == 0 || *in_y == 0 {return}
         self._screen_size = [*in_x, *in_y];
+        self._aspect_ratio = *in_x as f32 / *in_y as f32;
         self.update();
     }
     
-    pub fn update(&mut self) {
-        // Update perspective matrix
-        let mut w = self._screen_size[0] as f32;
-        let mut h = self._screen_size[1] as f32;
-        if w > h {
-            w = w/h;
-            h = 1.0;
+    pub fn set_eye_separation(&mut self, in_eye_separation : &f32) {
+        self._eye_separation = in_eye_separation.max(0.0);
+    }
+
+    /// The view and view-projection matrices for one eye of a stereo pair, drawn
+    /// into a framebuffer with the given aspect ratio. The eyes are moved apart
+    /// sideways and their frusta sheared (rather than turned in) so that both
+    /// converge on the focus without any vertical parallax.
+    pub fn eye_matrices(&self, in_eye : &Eye, in_aspect_ratio : &f32) -> (Matrix, Matrix) {
+        let offset = match *in_eye {
+            Eye::Left  => -self._eye_separation*self._r/2.0,
+            Eye::Right =>  self._eye_separation*self._r/2.0,
+        };
+        let eye_translation_matrix = Matrix::new([
+            [1.0, 0.0, 0.0, -offset],
+            [0.0, 1.0, 0.0,  0.0   ],
+            [0.0, 0.0, 1.0,  0.0   ],
+            [0.0, 0.0, 0.0,  1.0   ]
+        ]);
+        let view_matrix = eye_translation_matrix*self._view_matrix;
+        let mut projection = self.projection_matrix(in_aspect_ratio).contents().to_owned();
+        // shift x in proportion to depth, so that nothing moves at the focus (z = r)
+        projection[0][2] += projection[0][0]*offset/self._r;
+        (view_matrix, Matrix::new(projection)*view_matrix)
+    }
+
+    /// The projection for a framebuffer of an aspect ratio (width over height).
+    /// The smaller screen dimension spans the field of view.
+    fn projection_matrix(&self, in_aspect_ratio : &f32) -> Matrix {
+        let (w, h) = if *in_aspect_ratio > 1.0 {
+            (*in_aspect_ratio, 1.0)
         } else {
-            h = h/w;
-            w = 1.0;
-        }
+            (1.0, 1.0/in_aspect_ratio)
+        };
         
         let s = 1.0/(self._field_of_view/2.0).tan();
         let n = self._near_plane.to_owned();
         let f = self._far_plane.to_owned();
-        self._perspective_matrix = Matrix::new([
-            [s/w, 0.0, 0.0        , 0.0          ],
-            [0.0, s/h, 0.0        , 0.0          ],
-            [0.0, 0.0, (f+n)/(f-n), 2.0*f*n/(n-f)],
-            [0.0, 0.0, 1.0        , 0.0          ]
-        ]);
+        match self._projection {
+            Projection::Perspective => Matrix::new([
+                [s/w, 0.0, 0.0        , 0.0          ],
+                [0.0, s/h, 0.0        , 0.0          ],
+                [0.0, 0.0, (f+n)/(f-n), 2.0*f*n/(n-f)],
+                [0.0, 0.0, 1.0        , 0.0          ]
+            ]),
+            // the perspective view's scale at the focus, where z = r
+            Projection::Orthographic => {
+                let s = s/self._r;
+                Matrix::new([
+                    [s/w, 0.0, 0.0        , 0.0          ],
+                    [0.0, s/h, 0.0        , 0.0          ],
+                    [0.0, 0.0, 2.0/(f-n)  , (n+f)/(n-f)  ],
+                    [0.0, 0.0, 0.0        , 1.0          ]
+                ])
+            },
+        }
+    }
+
+    pub fn update(&mut self) {
+        let aspect_ratio = self._aspect_ratio;
+        self._projection_matrix = self.projection_matrix(&aspect_ratio);
         
         // Translate so that the focus is centred.
         let focus_translation_matrix = Matrix::new([
@@ -207,7 +592,7 @@ impl Camera {
         self._view_matrix = zoom_matrix
                           * rotation_matrix
                           * focus_translation_matrix;
-        self._vp_matrix = self._perspective_matrix*self._view_matrix;
+        self._vp_matrix = self._projection_matrix*self._view_matrix;
     }
 }
 
@@ -0,0 +1,187 @@
+use std::f32;
+
+use camera::Camera;
+use quaternion::Quaternion;
+
+// ============================================================
+// Keyframe
+// ============================================================
+/// A snapshot of the camera at a moment of an animation.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Keyframe {
+    /// Seconds from the start of the animation.
+    #[serde(rename = "time")]
+    _time          : f32,
+    #[serde(rename = "position")]
+    _position      : [f32;3],
+    #[serde(rename = "orientation")]
+    _orientation   : Quaternion,
+    /// In radians.
+    #[serde(rename = "field_of_view")]
+    _field_of_view : f32,
+    /// The distance from the camera to its focus.
+    #[serde(rename = "r")]
+    _r             : f32,
+}
+
+impl Keyframe {
+    /// Records where the camera is now, at a time in seconds.
+    pub fn from_camera(in_camera : &Camera, in_time : &f32) -> Keyframe {
+        let forward = Camera::forward(&in_camera._quaternion);
+        let mut position = in_camera._focus;
+        for i in 0..3 {
+            position[i] -= in_camera._r*forward[i];
+        }
+        Keyframe {
+            _time          : in_time.to_owned(),
+            _position      : position,
+            _orientation   : in_camera._quaternion,
+            _field_of_view : in_camera._field_of_view,
+            _r             : in_camera._r,
+        }
+    }
+
+    /// Moves the camera to this keyframe, stopping any transition it was making.
+    pub fn apply(&self, in_camera : &mut Camera) {
+        let forward = Camera::forward(&self._orientation);
+        for i in 0..3 {
+            in_camera._focus[i] = self._position[i]+self._r*forward[i];
+        }
+        in_camera._r = self._r;
+        in_camera._quaternion = self._orientation;
+        in_camera._field_of_view = self._field_of_view;
+        in_camera._animation = None;
+        in_camera.update();
+    }
+}
+
+// ============================================================
+// Camera Path
+// ============================================================
+/// How to get from one keyframe to the next.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Interpolation {
+    /// Straight lines between keyframes, turning at a constant rate.
+    Linear,
+    /// A smooth curve through the keyframes, with no sudden changes of direction.
+    CatmullRom,
+}
+
+/// A sequence of keyframes for turntables and fly-throughs.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CameraPath {
+    #[serde(rename = "keyframes")]
+    _keyframes     : Vec<Keyframe>,
+    #[serde(rename = "interpolation")]
+    _interpolation : Interpolation,
+}
+
+impl CameraPath {
+    pub fn new(in_interpolation : &Interpolation) -> CameraPath {
+        CameraPath {
+            _keyframes     : Vec::new(),
+            _interpolation : in_interpolation.to_owned(),
+        }
+    }
+
+    pub fn keyframes(&self) -> &Vec<Keyframe> {&self._keyframes}
+
+    /// Adds a keyframe, keeping them in time order.
+    pub fn add_keyframe(&mut self, in_keyframe : &Keyframe) {
+        let index = self._keyframes.iter().position(|k| k._time > in_keyframe._time)
+                                          .unwrap_or(self._keyframes.len());
+        self._keyframes.insert(index, in_keyframe.to_owned());
+    }
+
+    pub fn clear(&mut self) {self._keyframes.clear()}
+
+    /// The time of the last keyframe.
+    pub fn duration(&self) -> f32 {self._keyframes.last().map_or(0.0, |k| k._time)}
+
+    /// The camera at a time, which is held at the first or last keyframe outside
+    /// the path. None if there are no keyframes.
+    pub fn sample(&self, in_time : &f32) -> Option<Keyframe> {
+        let keyframes = &self._keyframes;
+        if keyframes.is_empty() {return None}
+        let last = keyframes.len()-1;
+        if *in_time <= keyframes[0]._time {return Some(keyframes[0])}
+        if *in_time >= keyframes[last]._time {return Some(keyframes[last])}
+
+        // the segment from keyframe i to keyframe i+1 contains the time
+        let i = keyframes.iter().rposition(|k| k._time <= *in_time).unwrap();
+        let k1 = &keyframes[i];
+        let k2 = &keyframes[i+1];
+        let t = if k2._time > k1._time {(in_time-k1._time)/(k2._time-k1._time)} else {1.0};
+        // the ends are repeated, so the curve starts and stops along the path
+        let k0 = &keyframes[if i > 0 {i-1} else {i}];
+        let k3 = &keyframes[(i+2).min(last)];
+
+        let (position, orientation, field_of_view, r) = match self._interpolation {
+            Interpolation::Linear => {
+                let mut position = [0.0f32;3];
+                for j in 0..3 {
+                    position[j] = lerp(&k1._position[j], &k2._position[j], &t);
+                }
+                (
+                    position,
+                    k1._orientation.slerp(&k2._orientation, &t),
+                    lerp(&k1._field_of_view, &k2._field_of_view, &t),
+                    lerp(&k1._r, &k2._r, &t),
+                )
+            },
+            Interpolation::CatmullRom => {
+                let mut position = [0.0f32;3];
+                for j in 0..3 {
+                    position[j] = catmull_rom(
+                        &[k0._position[j], k1._position[j], k2._position[j], k3._position[j]],
+                        &t
+                    );
+                }
+                (
+                    position,
+                    spherical_catmull_rom(
+                        &[k0._orientation, k1._orientation, k2._orientation, k3._orientation],
+                        &t
+                    ),
+                    catmull_rom(
+                        &[k0._field_of_view, k1._field_of_view, k2._field_of_view, k3._field_of_view],
+                        &t
+                    ).max(f32::consts::PI/180.0),
+                    catmull_rom(&[k0._r, k1._r, k2._r, k3._r], &t).max(0.0),
+                )
+            },
+        };
+        Some(Keyframe {
+            _time          : in_time.to_owned(),
+            _position      : position,
+            _orientation   : orientation,
+            _field_of_view : field_of_view,
+            _r             : r,
+        })
+    }
+}
+
+fn lerp(in_a : &f32, in_b : &f32, in_t : &f32) -> f32 {in_a+(in_b-in_a)*in_t}
+
+/// The uniform Catmull-Rom spline through p[1] (t = 0) and p[2] (t = 1).
+fn catmull_rom(in_p : &[f32;4], in_t : &f32) -> f32 {
+    let p = in_p;
+    let t = *in_t;
+    0.5*(2.0*p[1]
+         + (p[2]-p[0])*t
+         + (2.0*p[0]-5.0*p[1]+4.0*p[2]-p[3])*t*t
+         + (3.0*p[1]-p[0]-3.0*p[2]+p[3])*t*t*t)
+}
+
+/// The same spline for rotations, by the Barry-Goldman pyramid of interpolations
+/// with slerp in place of lerp, so the result is always a proper rotation.
+fn spherical_catmull_rom(in_q : &[Quaternion;4], in_t : &f32) -> Quaternion {
+    let q = in_q;
+    let t = *in_t;
+    let a1 = q[0].slerp(&q[1], &(t+1.0));
+    let a2 = q[1].slerp(&q[2], &t);
+    let a3 = q[2].slerp(&q[3], &(t-1.0));
+    let b1 = a1.slerp(&a2, &((t+1.0)/2.0));
+    let b2 = a2.slerp(&a3, &(t/2.0));
+    b1.slerp(&b2, &t)
+}
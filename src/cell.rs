@@ -0,0 +1,180 @@
+use matrix::{determinant_3x3, Matrix};
+
+// ============================================================
+// Cell
+// ============================================================
+/// A periodic unit cell, given by its three lattice vectors.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Cell {
+    /// The lattice vectors a, b and c, one per row.
+    #[serde(rename = "lattice")]
+    _lattice : [[f32;3];3],
+    /// The inverse of the lattice matrix, for converting to fractional coordinates.
+    #[serde(rename = "inverse")]
+    _inverse : [[f32;3];3],
+    /// The cartesian position of the corner of the cell at fractional (0,0,0).
+    #[serde(rename = "origin")]
+    _origin  : [f32;3],
+}
+
+impl Cell {
+    /// Returns None if the lattice vectors don't span 3D space.
+    pub fn new(in_lattice : &[[f32;3];3], in_origin : &[f32;3]) -> Option<Cell> {
+        let l = in_lattice;
+        let cofactors = [
+            [l[1][1]*l[2][2]-l[1][2]*l[2][1], l[0][2]*l[2][1]-l[0][1]*l[2][2], l[0][1]*l[1][2]-l[0][2]*l[1][1]],
+            [l[1][2]*l[2][0]-l[1][0]*l[2][2], l[0][0]*l[2][2]-l[0][2]*l[2][0], l[0][2]*l[1][0]-l[0][0]*l[1][2]],
+            [l[1][0]*l[2][1]-l[1][1]*l[2][0], l[0][1]*l[2][0]-l[0][0]*l[2][1], l[0][0]*l[1][1]-l[0][1]*l[1][0]],
+        ];
+        let determinant = determinant_3x3(l);
+        if determinant.abs() < 1e-12 {return None}
+        let mut inverse = [[0.0f32;3];3];
+        for i in 0..3 {
+            for j in 0..3 {
+                inverse[i][j] = cofactors[i][j]/determinant;
+            }
+        }
+        Some(Cell {
+            _lattice : in_lattice.to_owned(),
+            _inverse : inverse,
+            _origin  : in_origin.to_owned(),
+        })
+    }
+
+    pub fn lattice(&self) -> &[[f32;3];3] {&self._lattice}
+    pub fn origin(&self) -> &[f32;3] {&self._origin}
+    pub fn is_left_handed(&self) -> bool {determinant_3x3(&self._lattice) < 0.0}
+
+    /// The model matrix taking the unit cube to the cell: fractional coordinates to
+    /// cartesian positions.
+    pub fn matrix(&self) -> Matrix {
+        let (l, o) = (&self._lattice, &self._origin);
+        Matrix::new([
+            [l[0][0], l[1][0], l[2][0], o[0]],
+            [l[0][1], l[1][1], l[2][1], o[1]],
+            [l[0][2], l[1][2], l[2][2], o[2]],
+            [0.0    , 0.0    , 0.0    , 1.0 ],
+        ])
+    }
+
+    /// Problems with the lattice vectors worth telling the user about: a left-handed
+    /// set (negative determinant), or one so flat that it's probably a typo.
+    pub fn warnings(&self) -> Vec<String> {
+        let l = &self._lattice;
+        let determinant = determinant_3x3(l);
+        let lengths : Vec<f32> = l.iter()
+            .map(|v| (v[0]*v[0]+v[1]*v[1]+v[2]*v[2]).sqrt())
+            .collect();
+        let mut warnings = Vec::new();
+        if self.is_left_handed() {
+            warnings.push("the lattice vectors are left-handed".to_owned());
+        }
+        // the volume as a fraction of that of a cuboid with the same edges
+        if determinant.abs() < 1e-2*lengths[0]*lengths[1]*lengths[2] {
+            warnings.push(format!(
+                "the cell is nearly singular (its volume is {:.3e} cubic units)",
+                determinant.abs()
+            ));
+        }
+        warnings
+    }
+
+    /// The same cell made right-handed by reversing c, if it's left-handed.
+    /// The origin moves to the other end of c, so the cell covers the same space.
+    pub fn right_handed(&self) -> Cell {
+        if !self.is_left_handed() {return *self}
+        let mut lattice = self._lattice;
+        let mut origin = self._origin;
+        for k in 0..3 {
+            origin[k] += lattice[2][k];
+            lattice[2][k] = -lattice[2][k];
+        }
+        Cell::new(&lattice, &origin).unwrap()
+    }
+
+    /// The cell moved with the atoms in it, by a rotation and a translation (see
+    /// scene_graph.rs). None if the transformation flattens it.
+    pub fn transformed(&self, in_transformation : &Matrix) -> Option<Cell> {
+        let m = in_transformation.contents();
+        let mut lattice = [[0.0f32;3];3];
+        for k in 0..3 {
+            for i in 0..3 {
+                lattice[k][i] = m[i][0]*self._lattice[k][0]+m[i][1]*self._lattice[k][1]+m[i][2]*self._lattice[k][2];
+            }
+        }
+        let o = &self._origin;
+        let origin = *in_transformation*[o[0], o[1], o[2], 1.0];
+        Cell::new(&lattice, &[origin[0], origin[1], origin[2]])
+    }
+
+    /// Converts a cartesian displacement to fractional coordinates.
+    pub fn to_fractional(&self, in_vector : &[f32;3]) -> [f32;3] {
+        let mut fractional = [0.0f32;3];
+        for j in 0..3 {
+            for i in 0..3 {
+                fractional[j] += in_vector[i]*self._inverse[i][j];
+            }
+        }
+        fractional
+    }
+
+    /// Converts a fractional displacement to cartesian coordinates.
+    pub fn to_cartesian(&self, in_fractional : &[f32;3]) -> [f32;3] {
+        let mut cartesian = [0.0f32;3];
+        for j in 0..3 {
+            for i in 0..3 {
+                cartesian[j] += in_fractional[i]*self._lattice[i][j];
+            }
+        }
+        cartesian
+    }
+
+    /// The periodic image of a position inside the cell: moved by whole lattice
+    /// vectors until its fractional coordinates are all in [0,1).
+    pub fn wrap(&self, in_position : &[f32;3]) -> [f32;3] {
+        let o = &self._origin;
+        let mut fractional = self.to_fractional(&[in_position[0]-o[0], in_position[1]-o[1], in_position[2]-o[2]]);
+        for f in fractional.iter_mut() {
+            *f -= f.floor();
+            // -1e-8 wraps to 1.0 in f32
+            if *f >= 1.0 {*f = 0.0}
+        }
+        let wrapped = self.to_cartesian(&fractional);
+        [o[0]+wrapped[0], o[1]+wrapped[1], o[2]+wrapped[2]]
+    }
+
+    /// The shortest vector from `in_from` to any periodic image of `in_to`,
+    /// and which image that is (in lattice vectors added to `in_to`).
+    pub fn minimum_image(&self, in_from : &[f32;3], in_to : &[f32;3]) -> ([f32;3], [i32;3]) {
+        let displacement = [in_to[0]-in_from[0], in_to[1]-in_from[1], in_to[2]-in_from[2]];
+        let fractional = self.to_fractional(&displacement);
+        let nearest = [
+            -fractional[0].round() as i32,
+            -fractional[1].round() as i32,
+            -fractional[2].round() as i32,
+        ];
+        // Rounding is only exact for orthogonal cells, so check the neighbouring
+        // images too in case the cell is skewed.
+        let mut best = (displacement, [0, 0, 0]);
+        let mut best_length_squared = f32::INFINITY;
+        for di in -1..2 {
+            for dj in -1..2 {
+                for dk in -1..2 {
+                    let image = [nearest[0]+di, nearest[1]+dj, nearest[2]+dk];
+                    let shift = self.to_cartesian(&[image[0] as f32, image[1] as f32, image[2] as f32]);
+                    let vector = [
+                        displacement[0]+shift[0],
+                        displacement[1]+shift[1],
+                        displacement[2]+shift[2],
+                    ];
+                    let length_squared = vector[0]*vector[0]+vector[1]*vector[1]+vector[2]*vector[2];
+                    if length_squared < best_length_squared {
+                        best_length_squared = length_squared;
+                        best = (vector, image);
+                    }
+                }
+            }
+        }
+        best
+    }
+}
@@ -0,0 +1,281 @@
+use std::fs::File;
+use std::io::prelude::*;
+
+use molecule::Molecule;
+
+/// The Dark2 palette from ColorBrewer: turquoise, orange, purple, pink, green,
+/// yellow, brown and grey. The species are drawn in these by default.
+pub const DARK2 : [[f32;3];8] = [
+    [ 27.0/255.0,158.0/255.0,119.0/255.0],
+    [217.0/255.0, 95.0/255.0,  2.0/255.0],
+    [117.0/255.0,112.0/255.0,179.0/255.0],
+    [231.0/255.0, 41.0/255.0,138.0/255.0],
+    [102.0/255.0,166.0/255.0, 30.0/255.0],
+    [230.0/255.0,171.0/255.0,  2.0/255.0],
+    [166.0/255.0,118.0/255.0, 29.0/255.0],
+    [102.0/255.0,102.0/255.0,102.0/255.0],
+];
+
+/// The colour of atoms with nothing to colour them by, e.g. no charge.
+const UNKNOWN : [f32;3] = [0.6, 0.6, 0.6];
+
+/// The CPK colour of an element (as Jmol draws them), by its symbol, e.g. "Ni".
+pub fn cpk(in_symbol : &str) -> [f32;3] {
+    let rgb = match in_symbol.to_lowercase().as_str() {
+        "h"  => [255, 255, 255],
+        "c"  => [144, 144, 144],
+        "n"  => [ 48,  80, 248],
+        "o"  => [255,  13,  13],
+        "f"  => [144, 224,  80],
+        "na" => [171,  92, 242],
+        "mg" => [138, 255,   0],
+        "p"  => [255, 128,   0],
+        "s"  => [255, 255,  48],
+        "cl" => [ 31, 240,  31],
+        "k"  => [143,  64, 212],
+        "ca" => [ 61, 255,   0],
+        "fe" => [224, 102,  51],
+        "ni" => [ 80, 208,  80],
+        "cu" => [200, 128,  51],
+        "zn" => [125, 128, 176],
+        _    => [255,  20, 147],
+    };
+    [rgb[0] as f32/255.0, rgb[1] as f32/255.0, rgb[2] as f32/255.0]
+}
+
+/// The colour a fraction `in_t` (from 0 to 1) of the way from blue, through cyan,
+/// green and yellow, to red.
+pub fn rainbow(in_t : &f32) -> [f32;3] {
+    let t = in_t.max(0.0).min(1.0);
+    let x = 4.0*(1.0-t);
+    let ramp = |centre : f32| (2.0-(x-centre).abs()).max(0.0).min(1.0);
+    [ramp(0.0), ramp(2.0), ramp(4.0)]
+}
+
+/// A colour written "#rrggbb".
+fn parse_hex(in_word : &str) -> Option<[f32;3]> {
+    let hex = in_word.trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {return None}
+    let channel = |k : usize| u8::from_str_radix(&hex[2*k..2*k+2], 16).ok().map(|c| c as f32/255.0);
+    Some([channel(0)?, channel(1)?, channel(2)?])
+}
+
+// ============================================================
+// Palette
+// ============================================================
+/// The colours the schemes draw from: the ones chains and residues are given in
+/// turn, the gradient charges run along, and any elements' own colours. They can be
+/// read from a file, one list a line, with blank lines and lines starting "#"
+/// ignored:
+///
+/// cycle #1b9e77 #d95f02 #7570b3
+/// gradient #2166ac #f7f7f7 #b2182b
+/// element O #ff0000
+///
+/// The gradient goes from the most negative charge to the most positive, through
+/// evenly spaced colours. Anything not in the file is left as the default: Dark2,
+/// blue through white to red, and the CPK colours.
+pub struct Palette {
+    _cycle    : Vec<[f32;3]>,
+    _gradient : Vec<[f32;3]>,
+    _elements : Vec<(String, [f32;3])>,
+}
+
+impl Palette {
+    pub fn new() -> Palette {
+        Palette {
+            _cycle    : DARK2.to_vec(),
+            _gradient : vec![[0.0, 0.0, 1.0], [1.0, 1.0, 1.0], [1.0, 0.0, 0.0]],
+            _elements : Vec::new(),
+        }
+    }
+
+    pub fn read(in_fname : &str) -> Result<Palette, String> {
+        let mut file = File::open(in_fname).map_err(|e| format!("couldn't open {}: {}", in_fname, e))?;
+        let mut flines = String::new();
+        file.read_to_string(&mut flines).map_err(|e| format!("couldn't read {}: {}", in_fname, e))?;
+
+        let mut palette = Palette::new();
+        for (i, line) in flines.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {continue}
+            let words : Vec<&str> = line.split_whitespace().collect();
+            let bad_line = |expected : &str| format!("{} line {}: expected {}", in_fname, i+1, expected);
+            let colours : Option<Vec<[f32;3]>> = words[1..].iter().map(|word| parse_hex(word)).collect();
+            match words[0] {
+                "cycle" => match colours {
+                    Some(ref colours) if !colours.is_empty() => palette._cycle = colours.clone(),
+                    _ => return Err(bad_line("\"cycle\" and colours, e.g. #1b9e77")),
+                },
+                "gradient" => match colours {
+                    Some(ref colours) if colours.len() >= 2 => palette._gradient = colours.clone(),
+                    _ => return Err(bad_line("\"gradient\" and at least 2 colours, e.g. #2166ac")),
+                },
+                "element" => match (words.get(1), words.get(2).and_then(|word| parse_hex(word))) {
+                    (Some(symbol), Some(colour)) if words.len() == 3 => {
+                        palette._elements.push((symbol.to_lowercase(), colour));
+                    },
+                    _ => return Err(bad_line("\"element\", a symbol and a colour, e.g. O #ff0000")),
+                },
+                _ => return Err(bad_line("cycle, gradient or element")),
+            }
+        }
+        Ok(palette)
+    }
+
+    /// The colour for the `in_n`th of whatever is given colours in turn.
+    pub fn cycled(&self, in_n : &usize) -> [f32;3] {self._cycle[in_n % self._cycle.len()]}
+
+    /// The colour a fraction `in_t` (from 0 to 1) of the way along the gradient.
+    pub fn gradient(&self, in_t : &f32) -> [f32;3] {
+        let stops = self._gradient.len()-1;
+        let x = in_t.max(0.0).min(1.0)*stops as f32;
+        let k = (x.floor() as usize).min(stops-1);
+        let f = x - k as f32;
+        let (a, b) = (self._gradient[k], self._gradient[k+1]);
+        [a[0]+f*(b[0]-a[0]), a[1]+f*(b[1]-a[1]), a[2]+f*(b[2]-a[2])]
+    }
+
+    /// An element's colour: the palette's own, or else its CPK colour.
+    pub fn element(&self, in_symbol : &str) -> [f32;3] {
+        let symbol = in_symbol.to_lowercase();
+        match self._elements.iter().find(|element| element.0 == symbol) {
+            Some(&(_, colour)) => colour,
+            None => cpk(in_symbol),
+        }
+    }
+}
+
+// ============================================================
+// Colour Scheme
+// ============================================================
+/// How atoms are coloured, stepped through with shift+C.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ColourScheme {
+    /// Each species in its own colour (Dark2).
+    Species,
+    /// The usual CPK colours for the elements.
+    Element,
+    /// The partial charge along a diverging gradient, from its middle for none to
+    /// its ends for the most charged atom (either way). Atoms without one are grey.
+    Charge,
+    /// Each chain in turn in the palette's colours, in the order they come in the file.
+    Chain,
+    /// Along each chain by residue number, from blue at its first residue through
+    /// the rainbow to red at its last.
+    Rainbow,
+    /// Each residue name in the palette's colours, in alphabetical order.
+    Residue,
+}
+
+impl ColourScheme {
+    pub fn next(&self) -> ColourScheme {
+        match *self {
+            ColourScheme::Species => ColourScheme::Element,
+            ColourScheme::Element => ColourScheme::Charge,
+            ColourScheme::Charge  => ColourScheme::Chain,
+            ColourScheme::Chain   => ColourScheme::Rainbow,
+            ColourScheme::Rainbow => ColourScheme::Residue,
+            ColourScheme::Residue => ColourScheme::Species,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ColourScheme::Species => "species",
+            ColourScheme::Element => "element",
+            ColourScheme::Charge  => "charge",
+            ColourScheme::Chain   => "chain",
+            ColourScheme::Rainbow => "rainbow",
+            ColourScheme::Residue => "residue",
+        }
+    }
+
+    /// The scheme with a name (as name() gives it), e.g. for --colours.
+    pub fn parse(in_name : &str) -> Result<ColourScheme, String> {
+        let schemes = [ColourScheme::Species, ColourScheme::Element, ColourScheme::Charge,
+                       ColourScheme::Chain, ColourScheme::Rainbow, ColourScheme::Residue];
+        schemes.iter().find(|scheme| scheme.name() == in_name.to_lowercase()).cloned()
+            .ok_or_else(|| format!("unknown colour scheme \"{}\" (try species, element, charge, chain, rainbow or residue)", in_name))
+    }
+
+    /// Whether anything in the molecule tells its atoms apart by this scheme, e.g.
+    /// any charges or chains.
+    pub fn applies_to(&self, in_molecule : &Molecule) -> bool {
+        let atoms = in_molecule.atoms();
+        match *self {
+            ColourScheme::Species | ColourScheme::Element => true,
+            ColourScheme::Charge => atoms.iter().any(|atom| atom.charge().is_some()),
+            ColourScheme::Chain | ColourScheme::Rainbow | ColourScheme::Residue => atoms.iter().any(|atom| atom.residue().is_some()),
+        }
+    }
+
+    /// Recolours every atom, and remembers the charges as the atoms' property when
+    /// colouring by charge.
+    pub fn apply(&self, in_molecule : &mut Molecule, in_palette : &Palette) {
+        let atoms : Vec<usize> = (0..in_molecule.atoms().len()).collect();
+        self.apply_to(in_molecule, in_palette, &atoms);
+    }
+
+    /// As apply(), but only some of the atoms, e.g. an object's (see objects.rs).
+    /// They're coloured as they would be with the rest, e.g. each chain as it is
+    /// among all of the molecule's.
+    pub fn apply_to(&self, in_molecule : &mut Molecule, in_palette : &Palette, in_atoms : &[usize]) {
+        if *self == ColourScheme::Species {
+            for &i in in_atoms {
+                let atom = &mut in_molecule.atoms_mut()[i];
+                atom.reset_colour();
+                atom.set_property(&None);
+            }
+            return;
+        }
+        // the chains in the order they come, with the first and last residue numbers
+        // in each, and the residue names sorted
+        let chains = in_molecule.chains();
+        let mut ends = vec![(i32::MAX, i32::MIN); chains.len()];
+        let mut names : Vec<String> = Vec::new();
+        for residue in in_molecule.atoms().iter().filter_map(|atom| atom.residue().as_ref()) {
+            let k = chains.iter().position(|chain| chain == residue.chain()).unwrap_or(0);
+            ends[k] = (ends[k].0.min(*residue.number()), ends[k].1.max(*residue.number()));
+            if !names.iter().any(|name| name == residue.name()) {names.push(residue.name().to_owned())}
+        }
+        names.sort();
+        let max_charge = in_molecule.atoms().iter().filter_map(|atom| *atom.charge())
+            .fold(0.0f32, |max, charge| max.max(charge.abs()));
+        for &i in in_atoms {
+            let atom = &mut in_molecule.atoms_mut()[i];
+            let colour = match *self {
+                ColourScheme::Species => unreachable!(),
+                ColourScheme::Element => in_palette.element(atom.species().symbol()),
+                ColourScheme::Charge => match *atom.charge() {
+                    Some(charge) if max_charge > 0.0 => in_palette.gradient(&(0.5+0.5*charge/max_charge)),
+                    Some(_) => in_palette.gradient(&0.5),
+                    None => UNKNOWN,
+                },
+                ColourScheme::Chain => match *atom.residue() {
+                    Some(ref residue) => {
+                        in_palette.cycled(&chains.iter().position(|chain| chain == residue.chain()).unwrap_or(0))
+                    },
+                    None => UNKNOWN,
+                },
+                ColourScheme::Rainbow => match *atom.residue() {
+                    Some(ref residue) => {
+                        let (first, last) = ends[chains.iter().position(|chain| chain == residue.chain()).unwrap_or(0)];
+                        let t = if last > first {(residue.number()-first) as f32/(last-first) as f32} else {0.0};
+                        rainbow(&t)
+                    },
+                    None => UNKNOWN,
+                },
+                ColourScheme::Residue => match *atom.residue() {
+                    Some(ref residue) => {
+                        in_palette.cycled(&names.iter().position(|name| name == residue.name()).unwrap_or(0))
+                    },
+                    None => UNKNOWN,
+                },
+            };
+            let property = if *self == ColourScheme::Charge {*atom.charge()} else {None};
+            atom.set_colour(&colour);
+            atom.set_property(&property);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use toml;
+
+use scene::{Scene, SceneFile};
+
+// ============================================================
+// Config
+// ============================================================
+/// The user's defaults and theme, from ~/.config/oxide/config.toml (or the file
+/// --config names), in place of the ones built in. Anything left out is as it
+/// would be without a config, and the command line has the last word. E.g.
+///
+/// background = [1.0, 1.0, 1.0]
+/// gradient = [[0.1, 0.1, 0.2], [0.8, 0.8, 0.9]]   # top and bottom, over the background
+/// environment = "cubemaps/studio"     # faces px.ppm, nx.ppm, ... (see background.rs)
+/// field_of_view = 60          # degrees, across the smaller side of the window
+/// wheel_sensitivity = 0.05    # the fraction zoomed per wheel notch
+/// radii = "vdw"               # species, covalent or vdw, as --radii takes
+/// atom_scale = 0.3            # what every atom's radius is scaled by
+/// msaa = 4                    # samples per pixel (0 for none)
+/// vsync = true                # wait for the screen between frames
+/// fps = 60                    # draw at most this many frames a second
+/// controls = "pymol"          # a preset for the mouse, as --controls takes
+/// mouse = "left=rotate, ctrl+left=zoom"   # as --mouse takes
+///
+/// [keys]                      # actions by name (see keys.rs) and their keys
+/// rmsf = "Shift+F"
+/// fit = "F, V"
+///
+/// [startup]                   # shown with nothing else to show, as a TOML scene is
+/// load = "structures/caffeine.xyz"    # relative to the config
+/// bonds = "plain"
+/// projection = "orthographic"
+/// camera = {angles = [30, 45, 0], distance = 20}
+///
+/// [elements.C]
+/// colour = [0.3, 0.3, 0.3]
+/// radius = 0.15
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(rename = "background")]
+    _background        : Option<[f32;3]>,
+    #[serde(rename = "gradient")]
+    _gradient          : Option<[[f32;3];2]>,
+    #[serde(rename = "environment")]
+    _environment       : Option<String>,
+    #[serde(rename = "field_of_view")]
+    _field_of_view     : Option<f32>,
+    #[serde(rename = "wheel_sensitivity")]
+    _wheel_sensitivity : Option<f32>,
+    #[serde(rename = "radii")]
+    _radii             : Option<String>,
+    #[serde(rename = "atom_scale")]
+    _atom_scale        : Option<f32>,
+    #[serde(rename = "msaa")]
+    _msaa              : Option<u16>,
+    #[serde(rename = "vsync")]
+    _vsync             : Option<bool>,
+    #[serde(rename = "fps")]
+    _fps               : Option<f32>,
+    #[serde(rename = "controls")]
+    _controls          : Option<String>,
+    #[serde(rename = "mouse")]
+    _mouse             : Option<String>,
+    /// Keys for actions, by the actions' names. They're checked against the actions
+    /// when they're bound, as the keys can only be named with graphics.
+    #[serde(rename = "keys", default)]
+    _keys              : BTreeMap<String, String>,
+    /// Each element's look, by its symbol.
+    #[serde(rename = "elements", default)]
+    _elements          : BTreeMap<String, ElementStyle>,
+    /// The default file, style and camera (see scene.rs), as written, and then read.
+    #[serde(rename = "startup")]
+    _startup_file      : Option<SceneFile>,
+    #[serde(skip)]
+    _startup           : Option<Scene>,
+}
+
+/// How an element's atoms are drawn: their colour and the radius of their spheres.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ElementStyle {
+    #[serde(rename = "colour", alias = "color")]
+    _colour : Option<[f32;3]>,
+    #[serde(rename = "radius")]
+    _radius : Option<f32>,
+}
+
+impl ElementStyle {
+    pub fn colour(&self) -> &Option<[f32;3]> {&self._colour}
+    pub fn radius(&self) -> &Option<f32> {&self._radius}
+}
+
+impl Config {
+    pub fn read(in_fname : &str) -> Result<Config, String> {
+        let mut file = File::open(in_fname).map_err(|e| format!("couldn't open {}: {}", in_fname, e))?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).map_err(|e| format!("couldn't read {}: {}", in_fname, e))?;
+        let mut config : Config = toml::from_str(&contents).map_err(|e| format!("{}: {}", in_fname, e))?;
+        if let Some(colour) = config._background.filter(|colour| colour.iter().any(|&x| x < 0.0 || x > 1.0)) {
+            return Err(format!("{}: the background {:?} isn't 3 numbers from 0 to 1", in_fname, colour));
+        }
+        if let Some(gradient) = config._gradient.filter(|gradient| gradient.iter().flat_map(|colour| colour.iter())
+                                                                       .any(|&x| x < 0.0 || x > 1.0)) {
+            return Err(format!("{}: the gradient {:?} isn't 2 colours of 3 numbers from 0 to 1", in_fname, gradient));
+        }
+        if let Some(degrees) = config._field_of_view.filter(|&degrees| degrees <= 0.0 || degrees >= 180.0) {
+            return Err(format!("{}: the field of view {} isn't an angle from 0 to 180 degrees", in_fname, degrees));
+        }
+        if let Some(scale) = config._atom_scale.filter(|&scale| scale <= 0.0) {
+            return Err(format!("{}: the atom scale {} isn't positive", in_fname, scale));
+        }
+        if let Some(fps) = config._fps.filter(|&fps| fps <= 0.0) {
+            return Err(format!("{}: the frame rate {} isn't positive", in_fname, fps));
+        }
+        for (symbol, style) in &config._elements {
+            if style._radius.map_or(false, |radius| radius <= 0.0) {
+                return Err(format!("{}: element {} has a radius that isn't positive", in_fname, symbol));
+            }
+        }
+        // (it isn't for figures, so it can't take a screenshot, which would quit)
+        if let Some(file) = config._startup_file.take() {
+            let startup = Scene::from_file(in_fname, file).map_err(|e| e.replacen(": ", ": startup: ", 1))?;
+            if startup.screenshot().is_some() {
+                return Err(format!("{}: startup: the startup scene can't take a screenshot", in_fname));
+            }
+            config._startup = Some(startup);
+        }
+        Ok(config)
+    }
+
+    /// Where the config is, without --config: $XDG_CONFIG_HOME/oxide/config.toml,
+    /// or else ~/.config/oxide/config.toml. None if there's no config there.
+    pub fn default_path() -> Option<PathBuf> {
+        let directory = match env::var_os("XDG_CONFIG_HOME") {
+            Some(directory) => PathBuf::from(directory),
+            None => Path::new(&env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?).join(".config"),
+        };
+        Some(directory.join("oxide").join("config.toml")).filter(|path| path.is_file())
+    }
+
+    pub fn background(&self) -> &Option<[f32;3]> {&self._background}
+    pub fn gradient(&self) -> &Option<[[f32;3];2]> {&self._gradient}
+    pub fn environment(&self) -> &Option<String> {&self._environment}
+    pub fn field_of_view(&self) -> &Option<f32> {&self._field_of_view}
+    pub fn wheel_sensitivity(&self) -> &Option<f32> {&self._wheel_sensitivity}
+    pub fn radii(&self) -> &Option<String> {&self._radii}
+    pub fn atom_scale(&self) -> &Option<f32> {&self._atom_scale}
+    pub fn msaa(&self) -> &Option<u16> {&self._msaa}
+    pub fn vsync(&self) -> &Option<bool> {&self._vsync}
+    pub fn fps(&self) -> &Option<f32> {&self._fps}
+    pub fn controls(&self) -> &Option<String> {&self._controls}
+    pub fn mouse(&self) -> &Option<String> {&self._mouse}
+    pub fn keys(&self) -> &BTreeMap<String, String> {&self._keys}
+    pub fn elements(&self) -> &BTreeMap<String, ElementStyle> {&self._elements}
+    pub fn startup(&self) -> &Option<Scene> {&self._startup}
+}
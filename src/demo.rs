@@ -0,0 +1,180 @@
+use std::f32;
+use std::time::Instant;
+
+use camera::Camera;
+use camera::animation::{CameraPath, Interpolation, Keyframe};
+use cell::Cell;
+use molecule::Molecule;
+use species::DefaultSpecies;
+use trajectory::Trajectory;
+
+/// The distance between neighbouring nickel and oxygen atoms in nickel oxide, in
+/// Angstroms.
+const SPACING : f32 = 2.085;
+
+/// How far apart the water molecules of the benchmark's box are, in Angstroms, as
+/// in liquid water, and the shape of each: the O-H bond length and the H-O-H angle.
+const WATER_SPACING : f32 = 3.1;
+const OH_LENGTH : f32 = 0.96;
+const HOH_DEGREES : f32 = 104.5;
+
+/// How many frames the benchmark's trajectory has, and how far (in Angstroms) each
+/// molecule strays from its place over them.
+const BENCHMARK_FRAMES : usize = 8;
+const WOBBLE : f32 = 0.3;
+
+/// How many keyframes the camera path has on its way round, and how far apart
+/// they are, in seconds.
+const KEYFRAMES : usize = 8;
+const KEYFRAME_SECONDS : f32 = 1.5;
+
+// ============================================================
+// Scene
+// ============================================================
+/// A block of nickel oxide (the rock salt structure) with a given number of atoms,
+/// filled a layer at a time, in a periodic cell around the whole block. Used by
+/// --demo, both to show off and to time drawing a known number of atoms.
+pub fn crystal<'a>(in_atoms : &usize, in_species : &'a DefaultSpecies) -> Molecule<'a> {
+    let mut molecule = Molecule::new();
+    let side = (*in_atoms as f32).cbrt().ceil() as usize;
+    'filling: for k in 0..side {
+        for j in 0..side {
+            for i in 0..side {
+                if molecule.atoms().len() == *in_atoms {break 'filling}
+                let species = if (i+j+k) % 2 == 0 {in_species.nickel()} else {in_species.oxygen()};
+                molecule.add_atom(species, &[i as f32*SPACING, j as f32*SPACING, k as f32*SPACING]);
+            }
+        }
+    }
+    let length = side as f32*SPACING;
+    let lattice = [[length, 0.0, 0.0], [0.0, length, 0.0], [0.0, 0.0, length]];
+    if let Some(cell) = Cell::new(&lattice, &[-0.5*SPACING;3]) {
+        molecule.set_cell(&cell);
+    }
+    molecule
+}
+
+/// A box of water molecules with (to the nearest whole molecule) a given number of
+/// atoms, each molecule turned its own way, in a periodic cell around the whole
+/// box, and a trajectory of them wobbling about their places that loops back to
+/// where it started. Used by --benchmark, to time drawing a very big structure
+/// that moves, as a simulation's would, a frame at a time.
+pub fn water_box<'a>(in_atoms : &usize, in_species : &'a DefaultSpecies) -> (Molecule<'a>, Trajectory) {
+    let mut molecule = Molecule::new();
+    let waters = (*in_atoms/3).max(1);
+    let side = (waters as f32).cbrt().ceil() as usize;
+    let half_angle = 0.5*HOH_DEGREES.to_radians();
+    // each molecule's place, and the way it's turned, about z and then x
+    let mut places = Vec::with_capacity(waters);
+    'filling: for k in 0..side {
+        for j in 0..side {
+            for i in 0..side {
+                if places.len() == waters {break 'filling}
+                let n = places.len() as f32;
+                places.push(([i as f32*WATER_SPACING, j as f32*WATER_SPACING, k as f32*WATER_SPACING],
+                             2.399*n, 1.7*n));
+            }
+        }
+    }
+    let atom_positions = |centre : &[f32;3], turn : f32, tilt : f32| {
+        let hydrogen = |side : f32| {
+            let (x, z) = (side*OH_LENGTH*half_angle.sin(), OH_LENGTH*half_angle.cos());
+            let (y, z) = (-z*tilt.sin(), z*tilt.cos());
+            [centre[0]+x*turn.cos()-y*turn.sin(), centre[1]+x*turn.sin()+y*turn.cos(), centre[2]+z]
+        };
+        [*centre, hydrogen(1.0), hydrogen(-1.0)]
+    };
+    let hydrogen = in_species.by_symbol("H").unwrap();
+    for &(ref centre, turn, tilt) in &places {
+        let positions = atom_positions(centre, turn, tilt);
+        molecule.add_atom(in_species.oxygen(), &positions[0]);
+        molecule.add_atom(hydrogen, &positions[1]);
+        molecule.add_atom(hydrogen, &positions[2]);
+    }
+    let length = side as f32*WATER_SPACING;
+    let lattice = [[length, 0.0, 0.0], [0.0, length, 0.0], [0.0, 0.0, length]];
+    if let Some(cell) = Cell::new(&lattice, &[-0.5*WATER_SPACING;3]) {
+        molecule.set_cell(&cell);
+    }
+    // each molecule goes round its own small loop, out of step with its neighbours
+    let mut trajectory = Trajectory::new();
+    for frame in 0..BENCHMARK_FRAMES {
+        let t = 2.0*f32::consts::PI*frame as f32/BENCHMARK_FRAMES as f32;
+        let mut positions = Vec::with_capacity(3*places.len());
+        for (n, &(ref centre, turn, tilt)) in places.iter().enumerate() {
+            let phase = t + 0.37*n as f32;
+            let moved = [
+                centre[0] + WOBBLE*phase.cos(),
+                centre[1] + WOBBLE*phase.sin(),
+                centre[2] + WOBBLE*(2.0*phase).sin(),
+            ];
+            positions.extend_from_slice(&atom_positions(&moved, turn + 0.5*t.sin(), tilt));
+        }
+        trajectory.add_frame(&positions).unwrap();
+    }
+    (molecule, trajectory)
+}
+
+/// A loop once round the focus, swooping in to half the distance halfway. The
+/// camera is left where it started.
+pub fn camera_path(in_camera : &mut Camera) -> CameraPath {
+    let mut path = CameraPath::new(&Interpolation::CatmullRom);
+    let start = Keyframe::from_camera(in_camera, &0.0);
+    let r = *in_camera.r();
+    let step = 2.0*f32::consts::PI/KEYFRAMES as f32;
+    for k in 0..KEYFRAMES+1 {
+        let swoop = (f32::consts::PI*k as f32/KEYFRAMES as f32).sin();
+        let dr = r*(1.0-0.5*swoop) - in_camera.r();
+        in_camera.dolly(&dr);
+        path.add_keyframe(&Keyframe::from_camera(in_camera, &(k as f32*KEYFRAME_SECONDS)));
+        in_camera.rotate(&[0.0, 1.0, 0.0], &step);
+    }
+    start.apply(in_camera);
+    path
+}
+
+// ============================================================
+// Benchmark
+// ============================================================
+/// Frame times over a lap of the demo's camera path.
+pub struct Benchmark {
+    /// In seconds.
+    _frame_times : Vec<f32>,
+    _last_frame  : Option<Instant>,
+    _laps        : usize,
+}
+
+impl Benchmark {
+    pub fn new() -> Benchmark {
+        Benchmark {
+            _frame_times : Vec::new(),
+            _last_frame  : None,
+            _laps        : 0,
+        }
+    }
+
+    /// Called once a frame, to time it.
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self._last_frame {
+            let elapsed = now.duration_since(last);
+            self._frame_times.push(elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32*1e-9);
+        }
+        self._last_frame = Some(now);
+    }
+
+    /// Sums up the lap just finished, e.g. "lap 1: 312 frames of 1000 atoms, 26.0 fps
+    /// on average, 99% of frames within 41.2 ms, slowest 57.9 ms", and starts the next.
+    pub fn finish_lap(&mut self, in_atoms : &usize) -> String {
+        self._laps += 1;
+        let mut times = ::std::mem::replace(&mut self._frame_times, Vec::new());
+        if times.is_empty() {return format!("lap {}: no frames drawn", self._laps)}
+        times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mean = times.iter().sum::<f32>()/times.len() as f32;
+        let percentile = times[((0.99*times.len() as f32) as usize).min(times.len()-1)];
+        format!(
+            "lap {}: {} frames of {} atoms, {:.1} fps on average, 99% of frames within {:.1} ms, slowest {:.1} ms",
+            self._laps, times.len(), in_atoms, 1.0/mean, 1000.0*percentile, 1000.0*times[times.len()-1]
+        )
+    }
+}
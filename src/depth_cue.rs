@@ -0,0 +1,42 @@
+// ============================================================
+// Depth Cue
+// ============================================================
+/// How (if at all) atom colours are modulated by their depth from the camera.
+/// A cheap way of getting depth perception on machines which can't do SSAO.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum DepthCue {
+    /// Atoms keep their species colour.
+    Off,
+    /// Atoms get darker the further they are from the camera.
+    Value,
+    /// Atoms are coloured red (near) through green to blue (far), "chromatic depth".
+    Hue,
+}
+
+impl DepthCue {
+    /// The next mode along, for cycling through with a single key.
+    pub fn next(&self) -> DepthCue {
+        match *self {
+            DepthCue::Off   => DepthCue::Value,
+            DepthCue::Value => DepthCue::Hue,
+            DepthCue::Hue   => DepthCue::Off,
+        }
+    }
+
+    /// The integer which the shaders switch on.
+    pub fn uniform(&self) -> i32 {
+        match *self {
+            DepthCue::Off   => 0,
+            DepthCue::Value => 1,
+            DepthCue::Hue   => 2,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            DepthCue::Off   => "off",
+            DepthCue::Value => "value",
+            DepthCue::Hue   => "hue",
+        }
+    }
+}
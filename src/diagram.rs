@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::f32;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+use bonds;
+use matrix::Matrix;
+use molecule::Molecule;
+
+/// The most atoms (not counting the hydrogens folded into labels) laid out, since
+/// skeletal diagrams are for small molecules, and each step of the layout takes
+/// the square of the atoms.
+pub const MAX_ATOMS : usize = 200;
+
+/// How many steps the layout is relaxed for.
+const LAYOUT_STEPS : usize = 400;
+
+/// Atoms which aren't bonded (or bonded to the same atom) push each other apart
+/// when closer than this, in bond lengths.
+const REPULSION_RANGE : f32 = 1.6;
+
+/// How far a bond stops short of a labelled atom, so it doesn't run into the label.
+const LABEL_GAP : f32 = 0.3;
+
+/// The pixels per bond length in an SVG.
+const SVG_BOND_LENGTH : f32 = 40.0;
+
+// ============================================================
+// Diagram
+// ============================================================
+/// A 2D skeletal diagram of a small molecule, as chemists draw them: carbons as the
+/// corners of the bonds, other elements as labels with their hydrogens (e.g. OH or
+/// NH2), hydrogens on carbon left out, and double and triple bonds as two and three
+/// lines (orders from bonds::estimated_orders(), with aromatic bonds dashed on the
+/// inside).
+///
+/// The layout starts from the structure seen face on, so rings and planar groups
+/// come out as they look, then every bond is relaxed towards the same length and
+/// the bonds round each atom towards even angles, while atoms which aren't bonded
+/// push each other apart.
+pub struct Diagram {
+    /// Where each atom drawn is, in bond lengths, with y up.
+    _positions : Vec<[f32;2]>,
+    /// What each is labelled with: nothing for carbon, otherwise its element and
+    /// hydrogens.
+    _labels    : Vec<String>,
+    /// The bonds between them, as indices into positions(), with their orders.
+    _bonds     : Vec<([usize;2], f32)>,
+}
+
+impl Diagram {
+    pub fn lay_out(in_molecule : &Molecule, in_bonds : &Vec<[usize;2]>) -> Result<Diagram, String> {
+        let atoms = in_molecule.atoms();
+        let symbol = |i : usize| atoms[i].species().symbol();
+        let orders = bonds::estimated_orders(in_molecule, in_bonds);
+
+        // hydrogens bonded to anything but hydrogen are folded into its label
+        let mut hydrogens = vec![0usize; atoms.len()];
+        let mut folded = vec![false; atoms.len()];
+        for bond in in_bonds {
+            for &(h, other) in &[(bond[0], bond[1]), (bond[1], bond[0])] {
+                if symbol(h) == "H" && symbol(other) != "H" && !folded[h] {
+                    folded[h] = true;
+                    hydrogens[other] += 1;
+                }
+            }
+        }
+        let drawn : Vec<usize> = (0..atoms.len()).filter(|&i| !folded[i]).collect();
+        if drawn.len() > MAX_ATOMS {
+            return Err(format!("can't draw {} atoms in 2D (at most {}), as diagrams are for small molecules",
+                               drawn.len(), MAX_ATOMS));
+        }
+        let mut index = vec![None; atoms.len()];
+        for (k, &i) in drawn.iter().enumerate() {index[i] = Some(k)}
+        let diagram_bonds : Vec<([usize;2], f32)> = in_bonds.iter().zip(orders.iter())
+            .filter_map(|(bond, &order)| match (index[bond[0]], index[bond[1]]) {
+                (Some(a), Some(b)) => Some(([a, b], order)),
+                _ => None,
+            }).collect();
+        let mut bonded = vec![false; drawn.len()];
+        for &(bond, _) in &diagram_bonds {
+            bonded[bond[0]] = true;
+            bonded[bond[1]] = true;
+        }
+        let labels = drawn.iter().enumerate().map(|(k, &i)| match (symbol(i), hydrogens[i]) {
+            // (a lone carbon, e.g. methane's, still needs its label)
+            ("C", _) if bonded[k] => String::new(),
+            (element, 0) => element.to_owned(),
+            (element, 1) => format!("{}H", element),
+            (element, n) => format!("{}H{}", element, n),
+        }).collect();
+
+        let positions : Vec<[f32;3]> = drawn.iter().map(|&i| *atoms[i].position()).collect();
+        let mut diagram = Diagram {
+            _positions : face_on(&positions),
+            _labels    : labels,
+            _bonds     : diagram_bonds,
+        };
+        diagram.relax();
+        // with the longest way across going across
+        let flat : Vec<[f32;3]> = diagram._positions.iter().map(|p| [p[0], p[1], 0.0]).collect();
+        diagram._positions = face_on(&flat);
+        Ok(diagram)
+    }
+
+    pub fn positions(&self) -> &Vec<[f32;2]> {&self._positions}
+    pub fn labels(&self) -> &Vec<String> {&self._labels}
+
+    /// Scales the layout to bond lengths, then moves the atoms a little at a time
+    /// towards the lengths they should be apart.
+    fn relax(&mut self) {
+        let n = self._positions.len();
+        if n < 2 {return}
+        let distance = |p : &[f32;2], q : &[f32;2]| ((q[0]-p[0]).powi(2) + (q[1]-p[1]).powi(2)).sqrt();
+        let mean_bond = if self._bonds.is_empty() {1.5} else {
+            self._bonds.iter().map(|&(bond, _)| distance(&self._positions[bond[0]], &self._positions[bond[1]]))
+                .sum::<f32>()/self._bonds.len() as f32
+        };
+        for p in &mut self._positions {
+            p[0] /= mean_bond.max(1e-3);
+            p[1] /= mean_bond.max(1e-3);
+        }
+
+        // how far apart bonded atoms, and atoms bonded to the same one, should be
+        let mut neighbours = vec![Vec::new(); n];
+        let mut triple = vec![false; n];
+        for &(bond, order) in &self._bonds {
+            neighbours[bond[0]].push(bond[1]);
+            neighbours[bond[1]].push(bond[0]);
+            if order >= 3.0 {
+                triple[bond[0]] = true;
+                triple[bond[1]] = true;
+            }
+        }
+        let mut targets : HashMap<(usize, usize), f32> = HashMap::new();
+        for (centre, around) in neighbours.iter().enumerate() {
+            // 120 degrees apart, as for sp2 (which also draws sp3 chains as zigzags),
+            // straight through an sp carbon, and evenly round anything with more
+            let angle = match around.len() {
+                2 if triple[centre] => f32::consts::PI,
+                0..=3 => 2.0*f32::consts::PI/3.0,
+                degree => 2.0*f32::consts::PI/degree as f32,
+            };
+            for (k, &a) in around.iter().enumerate() {
+                for &b in &around[k+1..] {
+                    targets.entry((a.min(b), a.max(b))).or_insert(2.0*(angle/2.0).sin());
+                }
+            }
+        }
+        for &(bond, _) in &self._bonds {
+            targets.insert((bond[0].min(bond[1]), bond[0].max(bond[1])), 1.0);
+        }
+
+        for step in 0..LAYOUT_STEPS {
+            // large steps to start with, to untangle it, then small ones to settle
+            let rate = 0.5*(1.0 - step as f32/LAYOUT_STEPS as f32) + 0.05;
+            let mut moves = vec![[0.0f32;2]; n];
+            for i in 0..n {
+                for j in i+1..n {
+                    let (p, q) = (self._positions[i], self._positions[j]);
+                    let r = distance(&p, &q);
+                    // (atoms on top of each other are pushed apart across the page)
+                    let direction = if r > 1e-4 {[(q[0]-p[0])/r, (q[1]-p[1])/r]} else {[1.0, 0.0]};
+                    let stretch = match targets.get(&(i, j)) {
+                        Some(&target) => target - r,
+                        None if r < REPULSION_RANGE => 0.5*(REPULSION_RANGE - r),
+                        None => continue,
+                    };
+                    for k in 0..2 {
+                        moves[i][k] -= 0.5*stretch*direction[k];
+                        moves[j][k] += 0.5*stretch*direction[k];
+                    }
+                }
+            }
+            for (p, m) in self._positions.iter_mut().zip(moves.iter()) {
+                // shared between an atom's bonds, so it doesn't overshoot
+                p[0] += rate*m[0]/3.0;
+                p[1] += rate*m[1]/3.0;
+            }
+        }
+    }
+
+    /// The lowest and highest x and y of the atoms.
+    pub fn bounds(&self) -> ([f32;2], [f32;2]) {
+        if self._positions.is_empty() {return ([0.0, 0.0], [0.0, 0.0])}
+        let mut low = [f32::MAX, f32::MAX];
+        let mut high = [f32::MIN, f32::MIN];
+        for p in &self._positions {
+            for k in 0..2 {
+                low[k] = low[k].min(p[k]);
+                high[k] = high[k].max(p[k]);
+            }
+        }
+        (low, high)
+    }
+
+    /// The lines the bonds are drawn as, from and to, in bond lengths.
+    pub fn strokes(&self) -> Vec<([f32;2], [f32;2])> {
+        let n = self._positions.len().max(1) as f32;
+        let centre = [
+            self._positions.iter().map(|p| p[0]).sum::<f32>()/n,
+            self._positions.iter().map(|p| p[1]).sum::<f32>()/n,
+        ];
+        let mut strokes = Vec::new();
+        for &(bond, order) in &self._bonds {
+            let (mut a, mut b) = (self._positions[bond[0]], self._positions[bond[1]]);
+            let length = ((b[0]-a[0]).powi(2) + (b[1]-a[1]).powi(2)).sqrt();
+            if length < 1e-4 {continue}
+            let along = [(b[0]-a[0])/length, (b[1]-a[1])/length];
+            let across = [-along[1], along[0]];
+            if !self._labels[bond[0]].is_empty() {
+                a = [a[0]+LABEL_GAP*along[0], a[1]+LABEL_GAP*along[1]];
+            }
+            if !self._labels[bond[1]].is_empty() {
+                b = [b[0]-LABEL_GAP*along[0], b[1]-LABEL_GAP*along[1]];
+            }
+            let line = |offset : f32, from : f32, to : f32| {
+                let at = |t : f32| [a[0]+t*(b[0]-a[0])+offset*across[0], a[1]+t*(b[1]-a[1])+offset*across[1]];
+                (at(from), at(to))
+            };
+            if order >= 3.0 {
+                for &offset in &[-0.15, 0.0, 0.15] {strokes.push(line(offset, 0.0, 1.0))}
+            } else if order >= 2.0 {
+                for &offset in &[-0.08, 0.08] {strokes.push(line(offset, 0.0, 1.0))}
+            } else if order > 1.0 {
+                // the dashes inside, towards the middle of the molecule
+                let middle = [(a[0]+b[0])/2.0, (a[1]+b[1])/2.0];
+                let inward = across[0]*(centre[0]-middle[0]) + across[1]*(centre[1]-middle[1]);
+                let offset = if inward >= 0.0 {0.15} else {-0.15};
+                strokes.push(line(0.0, 0.0, 1.0));
+                for &(from, to) in &[(0.15, 0.3), (0.42, 0.58), (0.7, 0.85)] {strokes.push(line(offset, from, to))}
+            } else {
+                strokes.push(line(0.0, 0.0, 1.0));
+            }
+        }
+        strokes
+    }
+
+    /// The pixels per bond length and where x = 0, y = 0 goes, to fit the diagram in
+    /// a square `in_size` pixels across (with y down), half a bond in from its edges.
+    pub fn in_square(&self, in_size : &f32) -> (f32, [f32;2]) {
+        let (low, high) = self.bounds();
+        let across = (high[0]-low[0]).max(high[1]-low[1]) + 1.0;
+        let scale = (in_size/across).min(SVG_BOND_LENGTH);
+        let middle = [(low[0]+high[0])/2.0, (low[1]+high[1])/2.0];
+        (scale, [in_size/2.0 - scale*middle[0], in_size/2.0 + scale*middle[1]])
+    }
+
+    /// The diagram as an SVG, black on white, SVG_BOND_LENGTH pixels a bond.
+    pub fn svg(&self) -> String {
+        let (low, high) = self.bounds();
+        let s = SVG_BOND_LENGTH;
+        let (width, height) = ((high[0]-low[0]+1.0)*s, (high[1]-low[1]+1.0)*s);
+        let x = |p : &[f32;2]| (p[0]-low[0]+0.5)*s;
+        let y = |p : &[f32;2]| (high[1]-p[1]+0.5)*s;
+        let mut text = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" \
+                                viewBox=\"0 0 {:.1} {:.1}\">\n  <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n",
+                               width, height, width, height);
+        text.push_str("  <g stroke=\"black\" stroke-width=\"2\" stroke-linecap=\"round\">\n");
+        for (from, to) in self.strokes() {
+            text.push_str(&format!("    <line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\"/>\n",
+                                   x(&from), y(&from), x(&to), y(&to)));
+        }
+        text.push_str("  </g>\n  <g font-family=\"sans-serif\" font-size=\"16\" text-anchor=\"middle\" \
+                       dominant-baseline=\"central\">\n");
+        for (p, label) in self._positions.iter().zip(self._labels.iter()) {
+            if label.is_empty() {continue}
+            // the hydrogens' count as a subscript
+            let digits = label.find(|c : char| c.is_ascii_digit()).unwrap_or(label.len());
+            let subscript = if digits < label.len() {
+                format!("<tspan baseline-shift=\"sub\" font-size=\"12\">{}</tspan>", &label[digits..])
+            } else {
+                String::new()
+            };
+            text.push_str(&format!("    <text x=\"{:.1}\" y=\"{:.1}\">{}{}</text>\n", x(p), y(p), &label[..digits], subscript));
+        }
+        text.push_str("  </g>\n</svg>\n");
+        text
+    }
+
+    pub fn write_svg(&self, in_fname : &str) -> Result<(), String> {
+        let file = File::create(in_fname).map_err(|e| format!("couldn't create {}: {}", in_fname, e))?;
+        let mut file = BufWriter::new(file);
+        file.write_all(self.svg().as_bytes()).and_then(|_| file.flush())
+            .map_err(|e| format!("couldn't write to {}: {}", in_fname, e))
+    }
+}
+
+/// The points seen along the way they spread least, so a flat molecule is seen face
+/// on, with the way they spread most across.
+fn face_on(in_points : &Vec<[f32;3]>) -> Vec<[f32;2]> {
+    let n = in_points.len().max(1) as f32;
+    let mut centre = [0.0f32;3];
+    for p in in_points {
+        for k in 0..3 {centre[k] += p[k]/n}
+    }
+    let mut covariance = [[0.0f32;4];4];
+    for p in in_points {
+        for a in 0..3 {
+            for b in 0..3 {
+                covariance[a][b] += (p[a]-centre[a])*(p[b]-centre[b]);
+            }
+        }
+    }
+    let (values, vectors) = Matrix::new(covariance).symmetric_eigen();
+    let mut axes = [0, 1, 2, 3];
+    axes.sort_by(|&a, &b| values[b].partial_cmp(&values[a]).unwrap());
+    let v = vectors.contents();
+    let project = |d : &[f32;3], axis : usize| d[0]*v[0][axis] + d[1]*v[1][axis] + d[2]*v[2][axis];
+    in_points.iter().map(|p| {
+        let d = [p[0]-centre[0], p[1]-centre[1], p[2]-centre[2]];
+        [project(&d, axes[0]), project(&d, axes[1])]
+    }).collect()
+}
@@ -0,0 +1,102 @@
+use std::f32;
+
+use matrix::Matrix;
+
+/// The probability of an atom being inside its ellipsoid, by default, as in most
+/// ORTEP figures.
+pub const DEFAULT_PROBABILITY : f32 = 0.5;
+
+// ============================================================
+// Displacement Parameters
+// ============================================================
+/// The cartesian displacement matrix U (in square Angstroms) from a CIF's
+/// anisotropic displacement parameters [U11, U22, U33, U12, U13, U23], which are
+/// given along the cell's lattice vectors (one per row of `in_lattice`) in units of
+/// the reciprocal lattice vectors' lengths.
+pub fn cartesian_displacements(in_lattice : &[[f32;3];3], in_u : &[f32;6]) -> [[f32;3];3] {
+    let l = in_lattice;
+    let cross = |a : &[f32;3], b : &[f32;3]| [a[1]*b[2]-a[2]*b[1], a[2]*b[0]-a[0]*b[2], a[0]*b[1]-a[1]*b[0]];
+    let length = |v : &[f32;3]| (v[0]*v[0]+v[1]*v[1]+v[2]*v[2]).sqrt();
+    let volume = {
+        let bc = cross(&l[1], &l[2]);
+        (l[0][0]*bc[0]+l[0][1]*bc[1]+l[0][2]*bc[2]).abs()
+    };
+    let reciprocal = [
+        length(&cross(&l[1], &l[2]))/volume,
+        length(&cross(&l[2], &l[0]))/volume,
+        length(&cross(&l[0], &l[1]))/volume,
+    ];
+    let u = [
+        [in_u[0], in_u[3], in_u[4]],
+        [in_u[3], in_u[1], in_u[5]],
+        [in_u[4], in_u[5], in_u[2]],
+    ];
+    // U_cartesian = A N U N A^T, where A's columns are the lattice vectors and N
+    // holds the reciprocal lengths
+    let mut cartesian = [[0.0f32;3];3];
+    for k in 0..3 {
+        for m in 0..3 {
+            for i in 0..3 {
+                for j in 0..3 {
+                    cartesian[k][m] += l[i][k]*reciprocal[i]*u[i][j]*reciprocal[j]*l[j][m];
+                }
+            }
+        }
+    }
+    cartesian
+}
+
+/// The error function, to within 1.5e-7 (Abramowitz and Stegun 7.1.26).
+fn erf(in_x : f32) -> f32 {
+    let x = in_x.abs();
+    let t = 1.0/(1.0+0.3275911*x);
+    let polynomial = t*(0.254829592 + t*(-0.284496736 + t*(1.421413741 + t*(-1.453152027 + t*1.061405429))));
+    let value = 1.0 - polynomial*(-x*x).exp();
+    if in_x < 0.0 {-value} else {value}
+}
+
+/// How many standard deviations out along each axis the ellipsoid goes, for the
+/// atom to be inside it with some probability (e.g. 1.538 for 0.5): where the
+/// chi distribution with 3 degrees of freedom reaches it.
+pub fn probability_radius(in_probability : &f32) -> f32 {
+    let probability = in_probability.max(0.001).min(0.999);
+    let inside = |r : f32| erf(r/2.0f32.sqrt()) - (2.0/f32::consts::PI).sqrt()*r*(-r*r/2.0).exp();
+    let (mut low, mut high) = (0.0f32, 10.0f32);
+    for _ in 0..50 {
+        let middle = (low+high)/2.0;
+        if inside(middle) < probability {low = middle} else {high = middle}
+    }
+    (low+high)/2.0
+}
+
+// ============================================================
+// Ellipsoid
+// ============================================================
+/// The matrices to draw an atom's ellipsoid with, stretching and turning a unit
+/// sphere: the model matrix, and the one its normals go by (the same rotation, but
+/// with the axes shrunk instead). None if U isn't positive definite, as happens
+/// with poorly refined atoms, which have no ellipsoid.
+pub fn matrices(in_position : &[f32;3], in_u : &[[f32;3];3], in_radius : &f32) -> Option<(Matrix, Matrix)> {
+    let mut u = [[0.0f32;4];4];
+    for i in 0..3 {
+        for j in 0..3 {
+            u[i][j] = in_u[i][j];
+        }
+    }
+    let (values, vectors) = Matrix::new(u).symmetric_eigen();
+    if values[..3].iter().any(|&value| value <= 0.0) {return None}
+    let axes = [in_radius*values[0].sqrt(), in_radius*values[1].sqrt(), in_radius*values[2].sqrt()];
+    let v = vectors.contents();
+    let mut model = [[0.0f32;4];4];
+    let mut normal = [[0.0f32;4];4];
+    for k in 0..3 {
+        for i in 0..3 {
+            model[k][i] = v[k][i]*axes[i];
+            normal[k][i] = v[k][i]/axes[i];
+        }
+        model[k][3] = in_position[k];
+    }
+    model[3][3] = 1.0;
+    normal[3][3] = 1.0;
+    Some((Matrix::new(model), Matrix::new(normal)))
+}
@@ -0,0 +1,62 @@
+use std::time::Instant;
+
+/// How long fading in or out takes, in seconds.
+pub const FADE_SECONDS : f32 = 0.2;
+
+// ============================================================
+// Fade
+// ============================================================
+/// Whether something (the bonds, the tube,...) is shown, with an opacity which
+/// ramps over FADE_SECONDS whenever that changes, so it doesn't pop in and out.
+#[derive(Copy, Clone, Debug)]
+pub struct Fade {
+    _shown : bool,
+    /// The opacity when the ramp started.
+    _from  : f32,
+    /// When the ramp started, or None if it's never changed.
+    _start : Option<Instant>,
+}
+
+impl Fade {
+    /// Starts out shown or not, without fading.
+    pub fn new(in_shown : &bool) -> Fade {
+        Fade {
+            _shown : *in_shown,
+            _from  : if *in_shown {1.0} else {0.0},
+            _start : None,
+        }
+    }
+
+    /// Fades in or out, from wherever it has got to, if that's a change; it can be
+    /// called every frame with what should be shown.
+    pub fn set(&mut self, in_shown : &bool) {
+        if *in_shown == self._shown {return}
+        self._from = self.opacity();
+        self._shown = *in_shown;
+        self._start = Some(Instant::now());
+    }
+
+    /// From 0 (hidden) to 1 (shown), easing in and out.
+    pub fn opacity(&self) -> f32 {
+        let to = if self._shown {1.0} else {0.0};
+        let elapsed = match self._start {
+            Some(start) => start.elapsed(),
+            None => return to,
+        };
+        let seconds = elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32*1e-9;
+        let t = (seconds/FADE_SECONDS).min(1.0);
+        let eased = t*t*(3.0-2.0*t);
+        self._from + (to-self._from)*eased
+    }
+
+    /// Whether there's anything to draw: it's shown, or still fading out.
+    pub fn is_visible(&self) -> bool {self.opacity() > 0.0}
+
+    /// Whether it's part way through fading in or out.
+    pub fn is_fading(&self) -> bool {
+        self._start.map_or(false, |start| {
+            let elapsed = start.elapsed();
+            elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32*1e-9 < FADE_SECONDS
+        })
+    }
+}
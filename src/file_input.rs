@@ -4,29 +4,45 @@ use std::fs::File;
 use std::error::Error;
 use std::io::prelude::*;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use molecule::Molecule;
-use species::DefaultSpecies;
-use model::DefaultModels;
+use cell::Cell;
+use species::{DefaultSpecies, ELEMENTS};
+use trajectory::Trajectory;
+use atom::Residue;
+use missing_residues::MissingResidues;
+use assembly::{Assembly, Operator};
+use packing::Crystal;
+use matrix::{self, Matrix};
+use vibration::Mode;
+use ellipsoid;
+use symmetry::{self, SymmetryOperator};
+use volume::Volume;
+use selection::Selection;
 
 /// Given a valid CASTEP cell file, scrape atomic types, positions and lattice 
 /// vectors into memory. Calculate absolute positions and pass them to main.rs
-/// to construct the molecule. 
+/// to construct the molecule. The lattice is %block lattice_cart's vectors or,
+/// without them, %block lattice_abc's lengths and angles.
 ///
 /// Example: 
 /// cargo run --release test/salt.cell
-pub fn read_cell_file<'a>(fname : &String, default_species : &'a DefaultSpecies) -> Molecule<'a> {
+pub fn read_cell_file<'a>(fname : &String, default_species : &'a DefaultSpecies)
+                          -> Result<Molecule<'a>, String> {
 
     let path = Path::new("test.cell");
     let display = path.display();
 
-    let mut file = File::open(fname).unwrap();
+    let mut file = File::open(fname).map_err(|e| format!("couldn't open {}: {}", fname, e))?;
     let mut flines = String::new();
 
-    file.read_to_string(&mut flines);
+    file.read_to_string(&mut flines).map_err(|e| format!("couldn't read {}: {}", fname, e))?;
     println!("{} contains: \n{}", fname, flines);
     let flines : Vec<&str> = flines.split_terminator('\n').collect();
 
     let mut lattice_cart : Vec<Vec<f32>> = Vec::new();
+    // a, b, c then alpha, beta, gamma, for a cell given by its lengths and angles
+    let mut lattice_abc : Vec<Vec<f32>> = Vec::new();
     let mut positions_frac : Vec<Vec<f32>> = Vec::new();
     let mut species_list : Vec<&str> = Vec::new();
 
@@ -35,25 +51,66 @@ pub fn read_cell_file<'a>(fname : &String, default_species : &'a DefaultSpecies)
             let mut j = i;
             loop {
                 j += 1;
+                if j == flines.len() {
+                    return Err(format!("{}: %block lattice_cart is never ended", fname));
+                }
                 if flines[j].to_lowercase() == "%endblock lattice_cart" {
                     break;
+                } else if flines[j].trim().to_lowercase() == "ang" {
+                    // the units, which are Angstroms anyway
+                    continue;
                 } else {
                     let temp : Vec<f32> = flines[j]
-                        .split_whitespace().map(|s| s.parse::<f32>().unwrap()).collect();
+                        .split_whitespace().map(|s| s.parse::<f32>())
+                        .collect::<Result<Vec<f32>, _>>()
+                        .map_err(|_| format!("{} line {}: expected numbers", fname, j+1))?;
+                    if temp.len() != 3 {
+                        return Err(format!("{} line {}: expected 3 numbers", fname, j+1));
+                    }
                     lattice_cart.push(temp);
                 }
             }
+        } else if line.to_lowercase() == "%block lattice_abc" {
+            let mut j = i;
+            loop {
+                j += 1;
+                if j == flines.len() {
+                    return Err(format!("{}: %block lattice_abc is never ended", fname));
+                }
+                if flines[j].to_lowercase() == "%endblock lattice_abc" {
+                    break;
+                } else if flines[j].trim().to_lowercase() == "ang" {
+                    continue;
+                } else {
+                    let temp : Vec<f32> = flines[j]
+                        .split_whitespace().map(|s| s.parse::<f32>())
+                        .collect::<Result<Vec<f32>, _>>()
+                        .map_err(|_| format!("{} line {}: expected numbers", fname, j+1))?;
+                    if temp.len() != 3 {
+                        return Err(format!("{} line {}: expected 3 numbers", fname, j+1));
+                    }
+                    lattice_abc.push(temp);
+                }
+            }
         } else if line.to_lowercase() == "%block positions_frac" {
             let mut j = i;
             loop {
                 j += 1;
+                if j == flines.len() {
+                    return Err(format!("{}: %block positions_frac is never ended", fname));
+                }
                 if flines[j].to_lowercase() == "%endblock positions_frac" {
                     break;
                 } else {
                     let temp : Vec<&str> = flines[j].split_whitespace().collect();
+                    if temp.len() < 4 {
+                        return Err(format!("{} line {}: expected an element and 3 numbers", fname, j+1));
+                    }
                     let mut temp_pos : Vec<f32> = Vec::new();
                     for k in 1..4 {
-                        temp_pos.push(temp[k].parse().unwrap());
+                        temp_pos.push(temp[k].parse().map_err(
+                            |_| format!("{} line {}: \"{}\" isn't a number", fname, j+1, temp[k])
+                        )?);
                     }
                     let atom = temp[0];
                     positions_frac.push(temp_pos);
@@ -63,21 +120,1143 @@ pub fn read_cell_file<'a>(fname : &String, default_species : &'a DefaultSpecies)
         }
     }
 
+    if lattice_cart.is_empty() && !lattice_abc.is_empty() {
+        if lattice_abc.len() != 2 {
+            return Err(format!("{}: expected the lengths then the angles in %block lattice_abc", fname));
+        }
+        let parameters = [lattice_abc[0][0], lattice_abc[0][1], lattice_abc[0][2],
+                          lattice_abc[1][0], lattice_abc[1][1], lattice_abc[1][2]];
+        lattice_cart = lattice_from_parameters(&parameters).iter().map(|vector| vector.to_vec()).collect();
+    }
+    if lattice_cart.len() != 3 {
+        return Err(format!("{}: expected 3 lattice vectors in %block lattice_cart, or %block lattice_abc", fname));
+    }
     println!("Parsed lattice vectors: {:?}", lattice_cart);
     println!("Parsed fractional coordinates: {:?}", positions_frac);
     println!("Parsed atomic species: {:?}", species_list);
 
     let mut molecule = Molecule::new();
 
-    for (i, atom) in species_list.iter().enumerate() {
+    for (i, symbol) in species_list.iter().enumerate() {
         let mut temp_pos : [f32; 3] = [0.0; 3];
         for k in 0..3 {
             for l in 0..3 {
                 temp_pos[l] += lattice_cart[k][l] * positions_frac[i][k] - lattice_cart[k][l]/2.0;
             }
         }
-        // just stick to oxygen for now
-        molecule.add_atom(default_species.oxygen(), &temp_pos);
+        // "Na:1" is sodium; anything that isn't an element is drawn as oxygen
+        let element : String = symbol.chars().take_while(|c| c.is_alphabetic()).collect();
+        let species = default_species.by_symbol(&element).unwrap_or(default_species.oxygen());
+        molecule.add_atom(species, &temp_pos);
+    }
+
+    // the positions above are shifted by half of each lattice vector, so the cell is too
+    let mut lattice = [[0.0f32;3];3];
+    let mut origin = [0.0f32;3];
+    for k in 0..3 {
+        for l in 0..3 {
+            lattice[k][l] = lattice_cart[k][l];
+            origin[l] -= lattice_cart[k][l]/2.0;
+        }
+    }
+    match Cell::new(&lattice, &origin) {
+        Some(cell) => molecule.set_cell(&cell),
+        None => return Err(format!("{}: the lattice vectors don't span a 3D cell", fname)),
+    }
+   return Ok(molecule)
+}
+
+/// Reads an XYZ file, which may hold several frames one after another:
+///
+/// ```text
+/// <number of atoms>
+/// <comment>
+/// <element> <x> <y> <z> [<charge>]
+/// ...
+/// ```
+///
+/// A fifth column, where there is one, is read as the atom's partial charge.
+/// The molecule is built from the first frame, and every frame (including the
+/// first) goes into the trajectory.
+///
+/// In extended XYZ, the comment says what the columns are instead, e.g.
+/// Properties=species:S:1:pos:R:3:charge:R:1:ligand:L:1, and may give the cell, e.g.
+/// Lattice="5.6 0 0 0 5.6 0 0 0 5.6". Each true/false column (as file_output.rs
+/// writes) is read as a named selection of the atoms it's true for. Forces (forces:R:3)
+/// and velocities (velo:R:3, vel:R:3 or velocities:R:3) are read from every frame, and
+/// the molecule's dipole (dipole="0.1 0 1.8") from the first, to draw as arrows.
+///
+/// Example:
+/// cargo run --release test/water.xyz
+pub fn read_xyz_file<'a>(fname : &String, default_species : &'a DefaultSpecies)
+                         -> Result<(Molecule<'a>, Trajectory), String> {
+    read_xyz(fname, default_species, &AtomicBool::new(false))
+}
+
+fn read_xyz<'a>(fname : &String, default_species : &'a DefaultSpecies, in_cancelled : &AtomicBool)
+                -> Result<(Molecule<'a>, Trajectory), String> {
+    let mut file = File::open(fname).map_err(|e| format!("couldn't open {}: {}", fname, e))?;
+    let mut flines = String::new();
+    file.read_to_string(&mut flines).map_err(|e| format!("couldn't read {}: {}", fname, e))?;
+    let flines : Vec<&str> = flines.lines().collect();
+
+    let mut molecule = Molecule::new();
+    let mut trajectory = Trajectory::new();
+    let mut i = 0;
+    while i < flines.len() {
+        unless_cancelled(fname, in_cancelled)?;
+        if flines[i].trim().is_empty() {
+            i += 1;
+            continue;
+        }
+        let n_atoms : usize = flines[i].trim().parse().map_err(
+            |_| format!("{} line {}: expected the number of atoms", fname, i+1)
+        )?;
+        if i+2+n_atoms > flines.len() {
+            return Err(format!("{} line {}: the file ends in the middle of a frame", fname, i+1));
+        }
+        let comment = flines[i+1];
+        let columns = xyz_columns(comment).map_err(|e| format!("{} line {}: {}", fname, i+2, e))?;
+        let mut groups : Vec<(String, Vec<usize>)> = columns.groups.iter().map(|&(ref name, _)| (name.to_owned(), Vec::new())).collect();
+        let mut positions : Vec<[f32;3]> = Vec::new();
+        let mut forces : Option<Vec<[f32;3]>> = columns.force.map(|_| Vec::new());
+        let mut velocities : Option<Vec<[f32;3]>> = columns.velocity.map(|_| Vec::new());
+        for (j, line) in flines[i+2..i+2+n_atoms].iter().enumerate() {
+            let bad_line = || format!("{} line {}: expected an element and 3 numbers", fname, i+3+j);
+            let temp : Vec<&str> = line.split_whitespace().collect();
+            if temp.len() < columns.width {return Err(bad_line())}
+            let position = [
+                temp[columns.position].parse().map_err(|_| bad_line())?,
+                temp[columns.position+1].parse().map_err(|_| bad_line())?,
+                temp[columns.position+2].parse().map_err(|_| bad_line())?,
+            ];
+            // (an unreadable vector is no force or velocity at all)
+            let vector = |column : Option<usize>| -> Option<[f32;3]> {
+                let k = column?;
+                Some([temp[k].parse().ok()?, temp[k+1].parse().ok()?, temp[k+2].parse().ok()?])
+            };
+            if let Some(ref mut forces) = forces {forces.push(vector(columns.force).unwrap_or([0.0;3]))}
+            if let Some(ref mut velocities) = velocities {velocities.push(vector(columns.velocity).unwrap_or([0.0;3]))}
+            if trajectory.frames().is_empty() {
+                // anything that isn't an element (e.g. a dummy atom) is drawn as oxygen
+                let species = default_species.by_symbol(temp[columns.species])
+                                             .unwrap_or(default_species.oxygen());
+                molecule.add_atom(species, &position);
+                if let Some(charge) = columns.charge.and_then(|k| temp.get(k)).and_then(|s| s.parse::<f32>().ok()) {
+                    molecule.atoms_mut().last_mut().unwrap().set_charge(&charge);
+                }
+                for (&(_, k), &mut (_, ref mut atoms)) in columns.groups.iter().zip(groups.iter_mut()) {
+                    if ["T", "True", "true", "1"].contains(&temp[k]) {atoms.push(j)}
+                }
+            }
+            positions.push(position);
+        }
+        if trajectory.frames().is_empty() {
+            for &(ref name, ref atoms) in &groups {
+                molecule.set_named_selection(name, &Selection::from_atoms(atoms));
+            }
+            if let Some(dipole) = xyz_dipole(comment) {
+                molecule.set_dipole(&dipole);
+            }
+            if let Some(lattice) = xyz_lattice(comment) {
+                let cell = Cell::new(&lattice, &[0.0, 0.0, 0.0]).ok_or_else(
+                    || format!("{} line {}: the lattice vectors don't span a 3D cell", fname, i+2)
+                )?;
+                molecule.set_cell(&cell);
+            }
+        }
+        trajectory.add_frame_with_vectors(&positions, &forces, &velocities).map_err(|e| format!("{}: {}", fname, e))?;
+        if trajectory.frames().len() == 1 {trajectory.show_frame(&0, &mut molecule)}
+        i += 2+n_atoms;
+    }
+
+    if molecule.atoms().is_empty() {
+        return Err(format!("{}: no atoms found", fname));
+    }
+    Ok((molecule, trajectory))
+}
+
+/// Where the element, position, charge, force, velocity and true/false columns of
+/// an XYZ file are, and how many columns there are at least.
+struct XyzColumns {
+    species  : usize,
+    position : usize,
+    charge   : Option<usize>,
+    force    : Option<usize>,
+    velocity : Option<usize>,
+    groups   : Vec<(String, usize)>,
+    width    : usize,
+}
+
+/// A value in an extended XYZ comment, e.g. the 5.6 0 0 ... of Lattice="5.6 0 0 ...".
+fn xyz_value<'a>(in_comment : &'a str, in_key : &str) -> Option<&'a str> {
+    let key = format!("{}=", in_key);
+    let start = in_comment.match_indices(&key)
+        .map(|(k, _)| k)
+        .find(|&k| in_comment[..k].chars().last().map_or(true, |c| c.is_whitespace()))?
+        + key.len();
+    let rest = &in_comment[start..];
+    if rest.starts_with('"') {
+        rest[1..].find('"').map(|end| &rest[1..end+1])
+    } else {
+        Some(rest.split_whitespace().next().unwrap_or(""))
+    }
+}
+
+/// The columns an XYZ comment line gives: extended XYZ's Properties=, or else the
+/// element, position and (if there is one) charge of plain XYZ.
+fn xyz_columns(in_comment : &str) -> Result<XyzColumns, String> {
+    let properties = match xyz_value(in_comment, "Properties") {
+        Some(properties) => properties,
+        None => return Ok(XyzColumns {
+            species : 0, position : 1, charge : Some(4), force : None, velocity : None, groups : Vec::new(), width : 4,
+        }),
+    };
+    let bad_properties = || format!("expected Properties= to be names, types and counts, not \"{}\"", properties);
+    let fields : Vec<&str> = properties.split(':').collect();
+    if fields.len()%3 != 0 {return Err(bad_properties())}
+    let (mut species, mut position, mut charge, mut force, mut velocity) = (None, None, None, None, None);
+    let mut groups = Vec::new();
+    let mut column = 0;
+    for property in fields.chunks(3) {
+        let count : usize = property[2].parse().map_err(|_| bad_properties())?;
+        match (property[0], property[1], count) {
+            ("species", "S", 1) => species = Some(column),
+            ("pos", "R", 3) => position = Some(column),
+            ("charge", "R", 1) => charge = Some(column),
+            ("forces", "R", 3) => force = Some(column),
+            ("velo", "R", 3) | ("vel", "R", 3) | ("velocities", "R", 3) => velocity = Some(column),
+            (name, "L", 1) => groups.push((name.to_owned(), column)),
+            _ => {},
+        }
+        column += count;
+    }
+    match (species, position) {
+        (Some(species), Some(position)) => Ok(XyzColumns {species, position, charge, force, velocity, groups, width : column}),
+        _ => Err(format!("expected Properties= to have species:S:1 and pos:R:3, not \"{}\"", properties)),
+    }
+}
+
+/// The cell an extended XYZ comment gives, as Lattice= and the 3 lattice vectors.
+fn xyz_lattice(in_comment : &str) -> Option<[[f32;3];3]> {
+    let numbers : Vec<f32> = xyz_value(in_comment, "Lattice")?.split_whitespace().filter_map(|s| s.parse().ok()).collect();
+    if numbers.len() != 9 {return None}
+    Some([
+        [numbers[0], numbers[1], numbers[2]],
+        [numbers[3], numbers[4], numbers[5]],
+        [numbers[6], numbers[7], numbers[8]],
+    ])
+}
+
+/// The molecule's dipole an extended XYZ comment gives, as dipole= and its 3 components.
+fn xyz_dipole(in_comment : &str) -> Option<[f32;3]> {
+    let numbers : Vec<f32> = xyz_value(in_comment, "dipole")?.split_whitespace().filter_map(|s| s.parse().ok()).collect();
+    if numbers.len() != 3 {return None}
+    Some([numbers[0], numbers[1], numbers[2]])
+}
+
+/// The characters of a fixed-column line from `from` up to (not including) `to`,
+/// trimmed; whatever part of the range the line covers, if it's short.
+fn columns(line : &str, from : usize, to : usize) -> &str {
+    let to = to.min(line.len());
+    if from >= to {return ""}
+    line.get(from..to).unwrap_or("").trim()
+}
+
+/// How far a BIOMT, SMTRY, mmCIF or CIF operator's rotation can be from orthonormal
+/// (its rows' dot products from 0 or 1) and still be taken for one, rounded to the
+/// few decimal places files write.
+const ROTATION_TOLERANCE : f32 = 1e-2;
+
+/// An operator a file gives as a matrix (a rotation and then a translation), with the
+/// rounding taken out of its rotation, or None if it isn't a rotation at all. A
+/// crystal's symmetry can also invert (in_improper), which an assembly's can't.
+fn rotation_operator(in_matrix : &Matrix, in_improper : bool) -> Option<Matrix> {
+    let mut contents = *in_matrix.contents();
+    let block = [
+        [contents[0][0], contents[0][1], contents[0][2]],
+        [contents[1][0], contents[1][1], contents[1][2]],
+        [contents[2][0], contents[2][1], contents[2][2]],
+    ];
+    let inverts = in_improper && matrix::determinant_3x3(&block) < 0.0;
+    let sign = if inverts {-1.0} else {1.0};
+    for i in 0..3 {
+        for j in 0..3 {
+            contents[i][j] *= sign;
+        }
+    }
+    let turned = Matrix::new(contents);
+    if !turned.is_rotation(&ROTATION_TOLERANCE) {return None}
+    let mut contents = *turned.orthonormalised().contents();
+    for i in 0..3 {
+        for j in 0..3 {
+            contents[i][j] *= sign;
+        }
+    }
+    Some(Matrix::new(contents))
+}
+
+/// One row of a PDB REMARK 290 or 350 matrix, e.g. "BIOMT2   1  0.0 1.0 0.0  0.0" (after
+/// the "REMARK 350"): which row it is (0, 1 or 2) and what's in it. None if it isn't one.
+fn matrix_row(text : &str, in_name : &str) -> Option<(usize, [f32;4])> {
+    let temp : Vec<&str> = text.split_whitespace().collect();
+    if temp.len() < 6 || !temp[0].starts_with(in_name) {return None}
+    let row = match &temp[0][in_name.len()..] {"1" => 0, "2" => 1, "3" => 2, _ => return None};
+    let mut contents = [0.0f32;4];
+    for k in 0..4 {
+        contents[k] = temp[k+2].parse().ok()?;
+    }
+    Some((row, contents))
+}
+
+/// The cell in a PDB CRYST1 record, with a along x and b in the xy plane as the
+/// format has it. None for the 1 Angstrom cube files without a crystal put there.
+fn pdb_cell(line : &str) -> Option<Cell> {
+    let mut numbers = [0.0f32;6];
+    for (k, &(from, to)) in [(6, 15), (15, 24), (24, 33), (33, 40), (40, 47), (47, 54)].iter().enumerate() {
+        numbers[k] = columns(line, from, to).parse().ok()?;
+    }
+    if numbers[0] == 1.0 && numbers[1] == 1.0 && numbers[2] == 1.0 {return None}
+    Cell::new(&lattice_from_parameters(&numbers), &[0.0, 0.0, 0.0])
+}
+
+/// The lattice vectors of a cell given as [a, b, c, alpha, beta, gamma] (lengths in
+/// Angstroms, angles in degrees), with a along x and b in the xy plane.
+fn lattice_from_parameters(in_parameters : &[f32;6]) -> [[f32;3];3] {
+    let (a, b, c) = (in_parameters[0], in_parameters[1], in_parameters[2]);
+    let (cos_alpha, cos_beta) = (in_parameters[3].to_radians().cos(), in_parameters[4].to_radians().cos());
+    let (cos_gamma, sin_gamma) = (in_parameters[5].to_radians().cos(), in_parameters[5].to_radians().sin());
+    let cy = (cos_alpha - cos_beta*cos_gamma)/sin_gamma;
+    let cz = (1.0 - cos_beta*cos_beta - cy*cy).max(0.0).sqrt();
+    [
+        [a, 0.0, 0.0],
+        [b*cos_gamma, b*sin_gamma, 0.0],
+        [c*cos_beta, c*cy, c*cz],
+    ]
+}
+
+/// Reads a PDB file's ATOM and HETATM records, with each atom's name and residue.
+/// Models (MODEL ... ENDMDL) are read as trajectory frames, the first making the
+/// molecule. Residues and atoms the file lists as missing (REMARK 465 and 470) are
+/// read into the molecule's missing residues, and the first biological assembly
+/// (REMARK 350) into its assembly. The crystal (CRYST1 and the REMARK 290 symmetry
+/// operators) goes into the molecule's crystal, not its cell, so distances aren't
+/// periodic.
+///
+/// Example:
+/// cargo run --release test/protein.pdb
+pub fn read_pdb_file<'a>(fname : &String, default_species : &'a DefaultSpecies)
+                         -> Result<(Molecule<'a>, Trajectory), String> {
+    read_pdb(fname, default_species, &AtomicBool::new(false))
+}
+
+fn read_pdb<'a>(fname : &String, default_species : &'a DefaultSpecies, in_cancelled : &AtomicBool)
+                -> Result<(Molecule<'a>, Trajectory), String> {
+    let mut file = File::open(fname).map_err(|e| format!("couldn't open {}: {}", fname, e))?;
+    let mut flines = String::new();
+    file.read_to_string(&mut flines).map_err(|e| format!("couldn't read {}: {}", fname, e))?;
+
+    let mut molecule = Molecule::new();
+    let mut trajectory = Trajectory::new();
+    let mut missing = MissingResidues::new();
+    let mut assembly = Assembly::new();
+    // the assembly being read, the chains its next operators apply to, and the rows
+    // of the operator being read
+    let mut biomolecule = 0;
+    let mut chains : Vec<String> = Vec::new();
+    let mut rows = [[0.0f32, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+    // REMARK 290 comes before CRYST1, so the crystal is put together at the end
+    let mut crystal_cell : Option<Cell> = None;
+    let mut symmetry : Vec<Matrix> = Vec::new();
+    let mut symmetry_rows = rows;
+    let mut positions : Vec<[f32;3]> = Vec::new();
+    // the atoms of each segment, in the order the segments come
+    let mut segments : Vec<(String, Vec<usize>)> = Vec::new();
+    for (i, line) in flines.lines().enumerate() {
+        unless_cancelled(fname, in_cancelled)?;
+        let record = columns(line, 0, 6);
+        if record == "ATOM" || record == "HETATM" {
+            let bad_line = || format!("{} line {}: expected coordinates in columns 31-54", fname, i+1);
+            let position = [
+                columns(line, 30, 38).parse().map_err(|_| bad_line())?,
+                columns(line, 38, 46).parse().map_err(|_| bad_line())?,
+                columns(line, 46, 54).parse().map_err(|_| bad_line())?,
+            ];
+            if trajectory.frames().is_empty() {
+                let name = columns(line, 12, 16);
+                // old files leave the element out, so guess it from the atom name
+                let element = match columns(line, 76, 78) {
+                    "" => name.trim_matches(|c : char| !c.is_alphabetic()).get(0..1).unwrap_or(""),
+                    element => element,
+                };
+                // anything that isn't an element (e.g. a dummy atom) is drawn as oxygen
+                let species = default_species.by_symbol(element)
+                                             .unwrap_or(default_species.oxygen());
+                molecule.add_atom(species, &position);
+                let residue = Residue::new(
+                    columns(line, 21, 22),
+                    columns(line, 17, 20),
+                    &columns(line, 22, 26).parse().unwrap_or(0),
+                );
+                let atom = molecule.atoms_mut().last_mut().unwrap();
+                atom.set_name(name);
+                atom.set_residue(&residue);
+                atom.set_hetero(&(record == "HETATM"));
+                if let Ok(b_factor) = columns(line, 60, 66).parse() {
+                    atom.set_b_factor(&b_factor);
+                }
+                let segment = columns(line, 72, 76);
+                if !segment.is_empty() {
+                    let index = positions.len();
+                    match segments.iter().position(|&(ref name, _)| name == segment) {
+                        Some(k) => segments[k].1.push(index),
+                        None => segments.push((segment.to_owned(), vec![index])),
+                    }
+                }
+            }
+            positions.push(position);
+        } else if record == "ENDMDL" && !positions.is_empty() {
+            trajectory.add_frame(&positions).map_err(|e| format!("{} line {}: {}", fname, i+1, e))?;
+            positions.clear();
+        } else if line.starts_with("REMARK 465") {
+            // the table's header lines don't have a residue number, so are skipped
+            if let Ok(number) = columns(line, 21, 26).parse::<i32>() {
+                missing.add_residue(&Residue::new(columns(line, 19, 20), columns(line, 15, 18), &number));
+            }
+        } else if line.starts_with("REMARK 350") {
+            let text = columns(line, 10, line.len());
+            // only the first assembly is read
+            if text.starts_with("BIOMOLECULE:") {
+                biomolecule = text[12..].trim().parse().unwrap_or(0);
+            } else if biomolecule != 1 {
+                continue;
+            } else if let Some(k) = text.find("CHAINS:") {
+                // "APPLY THE FOLLOWING TO CHAINS: A, B" may go on with "AND CHAINS: C"
+                if !text.starts_with("AND") {chains.clear()}
+                chains.extend(text[k+7..].split(',').map(|s| s.trim().to_owned()).filter(|s| !s.is_empty()));
+            } else if text.starts_with("BIOMT") {
+                let (row, contents) = matrix_row(text, "BIOMT").ok_or_else(
+                    || format!("{} line {}: expected a row of a BIOMT matrix", fname, i+1)
+                )?;
+                rows[row] = contents;
+                if row == 2 {
+                    let matrix = rotation_operator(&Matrix::new(rows), false).ok_or_else(
+                        || format!("{} line {}: the BIOMT matrix isn't a rotation", fname, i+1)
+                    )?;
+                    assembly.add_operator(&Operator::new(&matrix, &chains));
+                }
+            }
+        } else if record == "CRYST1" {
+            crystal_cell = pdb_cell(line);
+        } else if line.starts_with("REMARK 290") && columns(line, 10, line.len()).starts_with("SMTRY") {
+            let (row, contents) = matrix_row(columns(line, 10, line.len()), "SMTRY").ok_or_else(
+                || format!("{} line {}: expected a row of an SMTRY matrix", fname, i+1)
+            )?;
+            symmetry_rows[row] = contents;
+            if row == 2 {
+                symmetry.push(rotation_operator(&Matrix::new(symmetry_rows), true).ok_or_else(
+                    || format!("{} line {}: the SMTRY matrix isn't a rotation", fname, i+1)
+                )?);
+            }
+        } else if line.starts_with("REMARK 470") {
+            if let Ok(number) = columns(line, 20, 24).parse::<i32>() {
+                let residue = Residue::new(columns(line, 19, 20), columns(line, 15, 18), &number);
+                let names : Vec<String> = columns(line, 25, line.len())
+                    .split_whitespace().map(|s| s.to_owned()).collect();
+                if !names.is_empty() {missing.add_atoms(&residue, &names)}
+            }
+        }
+    }
+    // a file without MODEL records is all one frame
+    if !positions.is_empty() {
+        trajectory.add_frame(&positions).map_err(|e| format!("{}: {}", fname, e))?;
+    }
+
+    if molecule.atoms().is_empty() {
+        return Err(format!("{}: no atoms found", fname));
+    }
+    molecule.set_missing_residues(&missing);
+    molecule.set_assembly(&assembly);
+    for &(ref name, ref atoms) in &segments {
+        molecule.set_named_selection(name, &Selection::from_atoms(atoms));
+    }
+    if let (Some(cell), false) = (crystal_cell, symmetry.is_empty()) {
+        let mut crystal = Crystal::new(&cell);
+        for operator in &symmetry {crystal.add_operator(operator)}
+        molecule.set_crystal(&crystal);
+    }
+    Ok((molecule, trajectory))
+}
+
+/// Reads normal modes of vibration: the "Frequencies --" blocks of a Gaussian
+/// frequency calculation's output, or else a simple format of a "mode" line with
+/// the frequency (in cm^-1) followed by each atom's displacement as 3 numbers, with
+/// blank lines and lines starting "#" ignored:
+///
+/// mode 1648.3
+/// 0.00 0.00 0.07
+/// ...
+///
+/// Example:
+/// cargo run --release test/water.xyz --modes test/water.modes
+pub fn read_modes_file(fname : &String) -> Result<Vec<Mode>, String> {
+    let mut file = File::open(fname).map_err(|e| format!("couldn't open {}: {}", fname, e))?;
+    let mut flines = String::new();
+    file.read_to_string(&mut flines).map_err(|e| format!("couldn't read {}: {}", fname, e))?;
+    let flines : Vec<&str> = flines.lines().collect();
+
+    let mut modes = Vec::new();
+    if flines.iter().any(|line| line.trim().starts_with("Frequencies --")) {
+        let mut i = 0;
+        while i < flines.len() {
+            let line = flines[i].trim();
+            i += 1;
+            if !line.starts_with("Frequencies --") {continue}
+            let frequencies : Vec<f32> = line["Frequencies --".len()..].split_whitespace()
+                .map(|s| s.parse().map_err(|_| format!("{} line {}: expected frequencies", fname, i)))
+                .collect::<Result<_, _>>()?;
+            // the displacements follow the reduced masses, force constants,...
+            while i < flines.len() && !flines[i].trim().starts_with("Atom") {i += 1}
+            i += 1;
+            let mut displacements : Vec<Vec<[f32;3]>> = vec![Vec::new(); frequencies.len()];
+            while i < flines.len() {
+                let temp : Vec<f32> = match flines[i].split_whitespace().map(|s| s.parse::<f32>())
+                                                     .collect::<Result<Vec<f32>, _>>() {
+                    Ok(ref numbers) if numbers.len() == 2+3*frequencies.len() => numbers.to_owned(),
+                    _ => break,
+                };
+                for (k, mode) in displacements.iter_mut().enumerate() {
+                    mode.push([temp[2+3*k], temp[3+3*k], temp[4+3*k]]);
+                }
+                i += 1;
+            }
+            if displacements.iter().any(|mode| mode.is_empty()) {
+                return Err(format!("{} line {}: expected each atom's displacements", fname, i+1));
+            }
+            for (frequency, mode) in frequencies.iter().zip(displacements.iter()) {
+                modes.push(Mode::new(frequency, mode));
+            }
+        }
+    } else {
+        let mut mode : Option<(f32, Vec<[f32;3]>)> = None;
+        for (i, line) in flines.iter().enumerate() {
+            let temp : Vec<&str> = line.split_whitespace().collect();
+            if temp.is_empty() || temp[0].starts_with('#') {continue}
+            if temp[0] == "mode" {
+                let frequency = temp.get(1).and_then(|s| s.parse::<f32>().ok()).ok_or_else(
+                    || format!("{} line {}: expected \"mode\" and a frequency", fname, i+1)
+                )?;
+                if let Some((frequency, displacements)) = mode.take() {
+                    modes.push(Mode::new(&frequency, &displacements));
+                }
+                mode = Some((frequency, Vec::new()));
+                continue;
+            }
+            let bad_line = || format!("{} line {}: expected 3 numbers", fname, i+1);
+            let displacements = match mode {
+                Some((_, ref mut displacements)) => displacements,
+                None => return Err(format!("{} line {}: expected a \"mode\" line first", fname, i+1)),
+            };
+            if temp.len() < 3 {return Err(bad_line())}
+            displacements.push([
+                temp[0].parse().map_err(|_| bad_line())?,
+                temp[1].parse().map_err(|_| bad_line())?,
+                temp[2].parse().map_err(|_| bad_line())?,
+            ]);
+        }
+        if let Some((frequency, displacements)) = mode {
+            modes.push(Mode::new(&frequency, &displacements));
+        }
+    }
+
+    if modes.is_empty() {
+        return Err(format!("{}: no normal modes found", fname));
+    }
+    Ok(modes)
+}
+
+/// Reads vectors to draw as arrows (see arrows.rs) into a molecule, for files that
+/// don't carry them: a "forces" or "velocities" line followed by each atom's vector
+/// as 3 numbers, in the molecule's order, and a "dipole" line followed by the
+/// molecule's, with blank lines and lines starting "#" ignored:
+///
+/// forces
+/// 0.00 0.00 -0.12
+/// ...
+/// dipole
+/// 0.0 0.0 1.85
+///
+/// Example:
+/// cargo run --release test/water.xyz --vectors test/water.vectors
+pub fn read_vectors_file(fname : &String, in_molecule : &mut Molecule) -> Result<(), String> {
+    let mut file = File::open(fname).map_err(|e| format!("couldn't open {}: {}", fname, e))?;
+    let mut flines = String::new();
+    file.read_to_string(&mut flines).map_err(|e| format!("couldn't read {}: {}", fname, e))?;
+
+    // each block's name, the line it starts on and its vectors
+    let mut blocks : Vec<(String, usize, Vec<[f32;3]>)> = Vec::new();
+    for (i, line) in flines.lines().enumerate() {
+        let temp : Vec<&str> = line.split_whitespace().collect();
+        if temp.is_empty() || temp[0].starts_with('#') {continue}
+        if ["forces", "velocities", "dipole"].contains(&temp[0]) {
+            blocks.push((temp[0].to_owned(), i+1, Vec::new()));
+            continue;
+        }
+        let bad_line = || format!("{} line {}: expected 3 numbers", fname, i+1);
+        let vectors = match blocks.last_mut() {
+            Some(&mut (_, _, ref mut vectors)) => vectors,
+            None => return Err(format!("{} line {}: expected a \"forces\", \"velocities\" or \"dipole\" line first",
+                                       fname, i+1)),
+        };
+        if temp.len() < 3 {return Err(bad_line())}
+        vectors.push([
+            temp[0].parse().map_err(|_| bad_line())?,
+            temp[1].parse().map_err(|_| bad_line())?,
+            temp[2].parse().map_err(|_| bad_line())?,
+        ]);
+    }
+    if blocks.is_empty() {
+        return Err(format!("{}: no forces, velocities or dipole found", fname));
+    }
+    // checked before any are read in, so a bad file leaves the molecule as it was
+    let n_atoms = in_molecule.atoms().len();
+    for &(ref name, line, ref vectors) in &blocks {
+        let expected = if name == "dipole" {1} else {n_atoms};
+        if vectors.len() != expected {
+            return Err(format!("{} line {}: expected {} vectors after \"{}\", not {}", fname, line, expected, name,
+                               vectors.len()));
+        }
+    }
+    for (name, _, vectors) in blocks {
+        match name.as_str() {
+            "forces" => for (atom, force) in in_molecule.atoms_mut().iter_mut().zip(&vectors) {atom.set_force(force)},
+            "velocities" => for (atom, velocity) in in_molecule.atoms_mut().iter_mut().zip(&vectors) {
+                atom.set_velocity(velocity)
+            },
+            _ => in_molecule.set_dipole(&vectors[0]),
+        }
+    }
+    Ok(())
+}
+
+/// The words of a CIF, with quoted strings ('...' or "...") as single words and
+/// comments dropped, each with the line it's on. Semicolon text fields are skipped.
+fn cif_words(in_text : &str) -> Vec<(usize, String)> {
+    let mut words = Vec::new();
+    let mut in_text_field = false;
+    for (i, line) in in_text.lines().enumerate() {
+        if line.starts_with(';') {
+            in_text_field = !in_text_field;
+            continue;
+        }
+        if in_text_field {continue}
+        let mut chars = line.chars().peekable();
+        loop {
+            while chars.peek().map_or(false, |c| c.is_whitespace()) {chars.next();}
+            let first = match chars.next() {
+                Some(c) => c,
+                None => break,
+            };
+            if first == '#' {break}
+            let mut word = String::new();
+            if first == '\'' || first == '"' {
+                // a quote only ends a string if whitespace follows it
+                while let Some(c) = chars.next() {
+                    if c == first && chars.peek().map_or(true, |next| next.is_whitespace()) {break}
+                    word.push(c);
+                }
+            } else {
+                word.push(first);
+                while chars.peek().map_or(false, |c| !c.is_whitespace()) {
+                    word.push(chars.next().unwrap());
+                }
+            }
+            words.push((i+1, word));
+        }
+    }
+    words
+}
+
+/// A CIF number, without the uncertainty in brackets, e.g. 0.0312 from "0.0312(4)".
+/// None for "?" or "." (unknown or not applicable).
+fn cif_number(in_word : &str) -> Option<f32> {
+    in_word.split('(').next().and_then(|s| s.parse().ok())
+}
+
+/// Reads the atoms of a crystal structure from a CIF: the cell, the fractional
+/// positions in the _atom_site_ loop, and the displacement parameters, both the
+/// anisotropic U's of the _atom_site_aniso_ loop and, for atoms without those, the
+/// isotropic U_iso_or_equiv, for drawing thermal ellipsoids. If the file gives the
+/// symmetry operators (_symmetry_equiv_pos_as_xyz or _space_group_symop_operation_xyz),
+/// the atoms listed are taken as the asymmetric unit and the rest of the cell is
+/// filled from them. Only the first data block is read. A macromolecule's mmCIF,
+/// with Cartesian positions instead, is read as read_mmcif() says.
+///
+/// Example:
+/// cargo run --release test/quartz.cif
+pub fn read_cif_file<'a>(fname : &String, default_species : &'a DefaultSpecies)
+                         -> Result<Molecule<'a>, String> {
+    read_cif(fname, default_species, &AtomicBool::new(false))
+}
+
+fn read_cif<'a>(fname : &String, default_species : &'a DefaultSpecies, in_cancelled : &AtomicBool)
+                -> Result<Molecule<'a>, String> {
+    let mut file = File::open(fname).map_err(|e| format!("couldn't open {}: {}", fname, e))?;
+    let mut flines = String::new();
+    file.read_to_string(&mut flines).map_err(|e| format!("couldn't read {}: {}", fname, e))?;
+    let words = cif_words(&flines);
+
+    let mut parameters : [Option<f32>;6] = [None;6];
+    let parameter_names = [
+        "_cell_length_a", "_cell_length_b", "_cell_length_c",
+        "_cell_angle_alpha", "_cell_angle_beta", "_cell_angle_gamma",
+    ];
+    // the loops, as their column names and rows
+    let mut loops : Vec<(Vec<String>, Vec<Vec<String>>)> = Vec::new();
+    // mmCIF's categories with one row (one assembly operator, say) are given as items
+    // (e.g. _pdbx_struct_oper_list.id 1) rather than loops, so become loops of a row
+    let mut categories : Vec<(String, Vec<String>, Vec<String>)> = Vec::new();
+    let mut blocks = 0;
+    let mut i = 0;
+    while i < words.len() {
+        unless_cancelled(fname, in_cancelled)?;
+        let (line, ref word) = words[i];
+        let lowercase = word.to_lowercase();
+        if lowercase.starts_with("data_") {
+            blocks += 1;
+            if blocks > 1 {break}
+            i += 1;
+        } else if lowercase == "loop_" {
+            i += 1;
+            let mut names = Vec::new();
+            while i < words.len() && words[i].1.starts_with('_') {
+                names.push(words[i].1.to_lowercase());
+                i += 1;
+            }
+            let mut values = Vec::new();
+            while i < words.len() && !words[i].1.starts_with('_') {
+                let lowercase = words[i].1.to_lowercase();
+                if lowercase == "loop_" || lowercase.starts_with("data_") {break}
+                values.push(words[i].1.to_owned());
+                i += 1;
+            }
+            if names.is_empty() || values.len() % names.len() != 0 {
+                return Err(format!("{} line {}: expected a value for each of the loop's columns", fname, line));
+            }
+            let rows = values.chunks(names.len()).map(|row| row.to_vec()).collect();
+            loops.push((names, rows));
+        } else if word.starts_with('_') {
+            // (mmCIF's _cell.length_a is _cell_length_a)
+            if let Some(k) = parameter_names.iter().position(|&name| name == lowercase.replace('.', "_")) {
+                parameters[k] = words.get(i+1).and_then(|&(_, ref value)| cif_number(value));
+                if parameters[k].is_none() {
+                    return Err(format!("{} line {}: expected a number after {}", fname, line, word));
+                }
+            } else if let (Some(dot), Some(&(_, ref value))) = (lowercase.find('.'), words.get(i+1)) {
+                let category = &lowercase[..dot];
+                match categories.iter().position(|&(ref name, _, _)| name == category) {
+                    Some(k) => {
+                        categories[k].1.push(lowercase.to_owned());
+                        categories[k].2.push(value.to_owned());
+                    },
+                    None => categories.push((category.to_owned(), vec![lowercase.to_owned()], vec![value.to_owned()])),
+                }
+            }
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+
+    loops.extend(categories.into_iter().map(|(_, names, values)| (names, vec![values])));
+    let column = |in_names : &Vec<String>, in_name : &str| in_names.iter().position(|name| name == in_name);
+    if loops.iter().any(|&(ref names, _)| column(names, "_atom_site.cartn_x").is_some()) {
+        return read_mmcif(fname, &loops, default_species, in_cancelled);
+    }
+
+    let mut numbers = [0.0f32;6];
+    for k in 0..6 {
+        numbers[k] = parameters[k].ok_or_else(|| format!("{}: no {} found", fname, parameter_names[k]))?;
+    }
+    let lattice = lattice_from_parameters(&numbers);
+    let cell = Cell::new(&lattice, &[0.0, 0.0, 0.0]).ok_or_else(|| format!("{}: the cell is flat", fname))?;
+
+    let sites = loops.iter().find(|&&(ref names, _)| column(names, "_atom_site_fract_x").is_some())
+        .ok_or_else(|| format!("{}: no _atom_site_fract_x loop found", fname))?;
+    let (ref names, ref rows) = *sites;
+    let fractional = [
+        column(names, "_atom_site_fract_x"),
+        column(names, "_atom_site_fract_y"),
+        column(names, "_atom_site_fract_z"),
+    ];
+    let label = column(names, "_atom_site_label");
+    let symbol = column(names, "_atom_site_type_symbol").or(label);
+    let u_iso = column(names, "_atom_site_u_iso_or_equiv");
+
+    let mut molecule = Molecule::new();
+    let mut labels = Vec::new();
+    for row in rows {
+        let bad_row = || format!("{}: expected fractional coordinates for the atom {}", fname,
+                                 label.map_or("", |k| &row[k]));
+        let mut position = [0.0f32;3];
+        for k in 0..3 {
+            position[k] = fractional[k].and_then(|m| cif_number(&row[m])).ok_or_else(&bad_row)?;
+        }
+        // "Fe3+" and "Fe1" are both iron; anything that isn't an element is drawn as
+        // oxygen
+        let element : String = symbol.map_or("", |k| &row[k]).chars().take_while(|c| c.is_alphabetic()).collect();
+        let species = default_species.by_symbol(&element).unwrap_or(default_species.oxygen());
+        molecule.add_atom(species, &cell.to_cartesian(&position));
+        let atom = molecule.atoms_mut().last_mut().unwrap();
+        if let Some(k) = label {atom.set_name(&row[k])}
+        if let Some(u) = u_iso.and_then(|k| cif_number(&row[k])) {
+            atom.set_adp(&[[u, 0.0, 0.0], [0.0, u, 0.0], [0.0, 0.0, u]]);
+        }
+        labels.push(label.map(|k| row[k].to_owned()));
+    }
+
+    let aniso_names = [
+        "_atom_site_aniso_u_11", "_atom_site_aniso_u_22", "_atom_site_aniso_u_33",
+        "_atom_site_aniso_u_12", "_atom_site_aniso_u_13", "_atom_site_aniso_u_23",
+    ];
+    let aniso = loops.iter().find(|&&(ref names, _)| column(names, "_atom_site_aniso_label").is_some());
+    if let Some(&(ref names, ref rows)) = aniso {
+        let aniso_label = column(names, "_atom_site_aniso_label").unwrap();
+        let columns : Vec<Option<usize>> = aniso_names.iter().map(|name| column(names, name)).collect();
+        for row in rows {
+            let index = match labels.iter().position(|l| l.as_ref() == Some(&row[aniso_label])) {
+                Some(index) => index,
+                None => return Err(format!("{}: displacement parameters for the atom {}, which isn't listed",
+                                           fname, row[aniso_label])),
+            };
+            let mut u = [0.0f32;6];
+            for k in 0..6 {
+                u[k] = columns[k].and_then(|m| cif_number(&row[m])).ok_or_else(
+                    || format!("{}: expected {} for the atom {}", fname, aniso_names[k], row[aniso_label])
+                )?;
+            }
+            molecule.atoms_mut()[index].set_adp(&ellipsoid::cartesian_displacements(&lattice, &u));
+        }
     }
-   return molecule
+
+    if molecule.atoms().is_empty() {
+        return Err(format!("{}: no atoms found", fname));
+    }
+    molecule.set_cell(&cell);
+
+    let operators = loops.iter().filter_map(|&(ref names, ref rows)| {
+        column(names, "_symmetry_equiv_pos_as_xyz")
+            .or(column(names, "_space_group_symop_operation_xyz"))
+            .map(|k| rows.iter().map(move |row| &row[k]))
+    }).next();
+    if let Some(operators) = operators {
+        let operators : Vec<SymmetryOperator> = operators.map(|text| {
+            let operator = SymmetryOperator::parse(text).map_err(|e| format!("{}: {}", fname, e))?;
+            // in the cell, it turns (or reflects) the atoms without stretching them
+            let turned = operator.cartesian_rotation(&cell);
+            let mut contents = [[0.0f32, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+            for i in 0..3 {
+                for j in 0..3 {
+                    contents[i][j] = turned[i][j];
+                }
+            }
+            if rotation_operator(&Matrix::new(contents), true).is_none() {
+                return Err(format!("{}: the symmetry operator {} doesn't suit the cell", fname, text));
+            }
+            Ok(operator)
+        }).collect::<Result<_, _>>()?;
+        if let Some(filled) = symmetry::fill_cell(&molecule, &operators) {
+            molecule = filled;
+        }
+    }
+    Ok(molecule)
+}
+
+/// Reads the atoms of a macromolecule from an mmCIF (as the PDB now gives them), from
+/// the loops read_cif_file() has found: the Cartesian positions in the _atom_site
+/// loop, with the atoms' names, residues, chains and B-factors, and the first
+/// biological assembly, the operators of _pdbx_struct_oper_list applied to the
+/// chains _pdbx_struct_assembly_gen says. Only the first model is read.
+fn read_mmcif<'a>(fname : &String, in_loops : &[(Vec<String>, Vec<Vec<String>>)], default_species : &'a DefaultSpecies,
+                  in_cancelled : &AtomicBool) -> Result<Molecule<'a>, String> {
+    let column = |in_names : &Vec<String>, in_name : &str| in_names.iter().position(|name| name == in_name);
+    let find = |in_name : &str| in_loops.iter().find(|&&(ref names, _)| column(names, in_name).is_some());
+    // "." and "?" are values not given
+    let value = |in_row : &Vec<String>, in_column : Option<usize>| -> String {
+        match in_column.map(|k| in_row[k].as_str()) {
+            Some(".") | Some("?") | None => String::new(),
+            Some(text) => text.to_owned(),
+        }
+    };
+
+    let (ref names, ref rows) = *find("_atom_site.cartn_x").ok_or_else(|| format!("{}: no _atom_site loop found", fname))?;
+    let cartesian = [
+        column(names, "_atom_site.cartn_x"),
+        column(names, "_atom_site.cartn_y"),
+        column(names, "_atom_site.cartn_z"),
+    ];
+    let either = |in_author : &str, in_label : &str| column(names, in_author).or(column(names, in_label));
+    let group = column(names, "_atom_site.group_pdb");
+    let symbol = column(names, "_atom_site.type_symbol");
+    let atom_name = either("_atom_site.auth_atom_id", "_atom_site.label_atom_id");
+    let residue_name = either("_atom_site.auth_comp_id", "_atom_site.label_comp_id");
+    let residue_number = either("_atom_site.auth_seq_id", "_atom_site.label_seq_id");
+    let chain = either("_atom_site.auth_asym_id", "_atom_site.label_asym_id");
+    let label_chain = column(names, "_atom_site.label_asym_id");
+    let b_factor = column(names, "_atom_site.b_iso_or_equiv");
+    let model = column(names, "_atom_site.pdbx_pdb_model_num");
+
+    let mut molecule = Molecule::new();
+    // the chains the assembly names (label_asym_id), and the author's names for them,
+    // which the atoms are given
+    let mut chain_names : Vec<(String, String)> = Vec::new();
+    let first_model = rows.first().map(|row| value(row, model));
+    for (j, row) in rows.iter().enumerate() {
+        unless_cancelled(fname, in_cancelled)?;
+        if Some(value(row, model)) != first_model {continue}
+        let mut position = [0.0f32;3];
+        for k in 0..3 {
+            position[k] = cartesian[k].and_then(|m| cif_number(&row[m])).ok_or_else(
+                || format!("{}: expected Cartesian coordinates for atom {} of the _atom_site loop", fname, j+1)
+            )?;
+        }
+        let name = value(row, atom_name);
+        // files without elements are guessed from the atom name, as in PDB files
+        let element = match value(row, symbol) {
+            ref element if element.is_empty() => name.trim_matches(|c : char| !c.is_alphabetic()).get(0..1)
+                                                    .unwrap_or("").to_owned(),
+            element => element,
+        };
+        // anything that isn't an element is drawn as oxygen
+        let species = default_species.by_symbol(&element).unwrap_or(default_species.oxygen());
+        molecule.add_atom(species, &position);
+        let residue = Residue::new(
+            &value(row, chain),
+            &value(row, residue_name),
+            &value(row, residue_number).parse().unwrap_or(0),
+        );
+        let atom = molecule.atoms_mut().last_mut().unwrap();
+        if !name.is_empty() {atom.set_name(&name)}
+        atom.set_residue(&residue);
+        atom.set_hetero(&(value(row, group) == "HETATM"));
+        if let Some(b) = b_factor.and_then(|k| cif_number(&row[k])) {
+            atom.set_b_factor(&b);
+        }
+        let label = value(row, label_chain);
+        if !label.is_empty() && !chain_names.iter().any(|&(ref l, _)| *l == label) {
+            chain_names.push((label, value(row, chain)));
+        }
+    }
+    if molecule.atoms().is_empty() {
+        return Err(format!("{}: no atoms found", fname));
+    }
+
+    // the operators, by their ids
+    let mut operators : Vec<(String, Matrix)> = Vec::new();
+    if let Some(&(ref names, ref rows)) = find("_pdbx_struct_oper_list.id") {
+        let id = column(names, "_pdbx_struct_oper_list.id").unwrap();
+        for row in rows {
+            let mut contents = [[0.0f32, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+            for r in 0..3 {
+                for c in 0..4 {
+                    let name = if c < 3 {
+                        format!("_pdbx_struct_oper_list.matrix[{}][{}]", r+1, c+1)
+                    } else {
+                        format!("_pdbx_struct_oper_list.vector[{}]", r+1)
+                    };
+                    contents[r][c] = column(names, &name).and_then(|k| cif_number(&row[k])).ok_or_else(
+                        || format!("{}: expected {} for the assembly operator {}", fname, name, row[id])
+                    )?;
+                }
+            }
+            let matrix = rotation_operator(&Matrix::new(contents), false).ok_or_else(
+                || format!("{}: the assembly operator {} isn't a rotation", fname, row[id])
+            )?;
+            operators.push((row[id].to_owned(), matrix));
+        }
+    }
+    // and those of the first assembly, each applied to some chains
+    let mut assembly = Assembly::new();
+    if let Some(&(ref names, ref rows)) = find("_pdbx_struct_assembly_gen.oper_expression") {
+        let assembly_id = column(names, "_pdbx_struct_assembly_gen.assembly_id");
+        let expression = column(names, "_pdbx_struct_assembly_gen.oper_expression");
+        let asym_ids = column(names, "_pdbx_struct_assembly_gen.asym_id_list");
+        let first = rows.first().map(|row| value(row, assembly_id));
+        for row in rows.iter().filter(|row| Some(value(row, assembly_id)) == first) {
+            let chains : Vec<String> = value(row, asym_ids).split(',').filter_map(|label| {
+                chain_names.iter().find(|&&(ref l, _)| l == label.trim()).map(|&(_, ref chain)| chain.to_owned())
+            }).fold(Vec::new(), |mut chains, chain| {
+                if !chains.contains(&chain) {chains.push(chain)}
+                chains
+            });
+            for product in oper_expression(&value(row, expression)).map_err(|e| format!("{}: {}", fname, e))? {
+                let mut matrix = Matrix::identity();
+                for id in &product {
+                    let operator = operators.iter().find(|&&(ref k, _)| k == id).ok_or_else(
+                        || format!("{}: the assembly uses the operator {}, which isn't listed", fname, id)
+                    )?;
+                    matrix = matrix*operator.1;
+                }
+                assembly.add_operator(&Operator::new(&matrix, &chains));
+            }
+        }
+    }
+    molecule.set_assembly(&assembly);
+    Ok(molecule)
+}
+
+/// The operators an mmCIF assembly applies (its _pdbx_struct_assembly_gen.oper_expression),
+/// each as the ids of those it's the product of: "1", "1,2,5", "(1-60)", or a product
+/// of lists, e.g. "(1-5)(6-10)", applying one of the second and then one of the first.
+fn oper_expression(in_text : &str) -> Result<Vec<Vec<String>>, String> {
+    let lists : Vec<&str> = if in_text.contains('(') {
+        in_text.split(|c| c == '(' || c == ')').map(|list| list.trim()).filter(|list| !list.is_empty()).collect()
+    } else {
+        vec![in_text]
+    };
+    let mut products : Vec<Vec<String>> = vec![Vec::new()];
+    for list in lists {
+        let mut ids = Vec::new();
+        for item in list.split(',').map(|item| item.trim()) {
+            let range : Vec<&str> = item.splitn(2, '-').collect();
+            match (range.get(0).and_then(|from| from.parse::<usize>().ok()),
+                   range.get(1).and_then(|to| to.parse::<usize>().ok())) {
+                (Some(from), Some(to)) if from <= to => ids.extend((from..to+1).map(|id| id.to_string())),
+                (_, Some(_)) => return Err(format!("expected a range of operators, not \"{}\"", item)),
+                _ if item.is_empty() => return Err(format!("expected operators, not \"{}\"", in_text)),
+                _ => ids.push(item.to_owned()),
+            }
+        }
+        products = products.iter().flat_map(|product| ids.iter().map(move |id| {
+            let mut product = product.to_owned();
+            product.push(id.to_owned());
+            product
+        })).collect();
+    }
+    Ok(products)
+}
+
+/// Angstroms in a bohr, the unit cube files are usually in.
+const BOHR : f32 = 0.529_177;
+
+/// Reads a Gaussian cube file: the atoms, and the values on its grid (an orbital,
+/// a density or a potential) as a volume. Where the file has several orbitals, the
+/// volume is the first. Lengths are in bohr, unless the first axis's number of
+/// points is negative, when they're in Angstroms.
+///
+/// Example:
+/// cargo run --release test/water.cube
+pub fn read_cube_file<'a>(fname : &String, default_species : &'a DefaultSpecies)
+                          -> Result<(Molecule<'a>, Trajectory, Volume), String> {
+    read_cube_file_cancellably(fname, default_species, &AtomicBool::new(false))
+}
+
+/// Reads a cube file as read_cube_file() does, giving up once `in_cancelled` is set
+/// (see read_structure_file_cancellably()).
+pub fn read_cube_file_cancellably<'a>(fname : &String, default_species : &'a DefaultSpecies, in_cancelled : &AtomicBool)
+                                      -> Result<(Molecule<'a>, Trajectory, Volume), String> {
+    let mut file = File::open(fname).map_err(|e| format!("couldn't open {}: {}", fname, e))?;
+    let mut flines = String::new();
+    file.read_to_string(&mut flines).map_err(|e| format!("couldn't read {}: {}", fname, e))?;
+    let flines : Vec<&str> = flines.lines().collect();
+
+    // two lines of comments, then the atoms and the grid's origin, and its axes
+    let numbers = |i : usize, expected : &str| -> Result<Vec<f32>, String> {
+        let bad_line = || format!("{} line {}: expected {}", fname, i+1, expected);
+        let line = flines.get(i).ok_or_else(&bad_line)?;
+        let numbers : Vec<f32> = line.split_whitespace().map(|word| word.parse::<f32>())
+                                     .collect::<Result<Vec<f32>, _>>().map_err(|_| bad_line())?;
+        if numbers.len() < 4 {return Err(bad_line())}
+        Ok(numbers)
+    };
+    let header = numbers(2, "the number of atoms and the origin")?;
+    let mut axes = [[0.0f32;3];3];
+    let mut shape = [0usize;3];
+    let mut angstroms = false;
+    for axis in 0..3 {
+        let line = numbers(3+axis, "a number of points and a step")?;
+        if axis == 0 {angstroms = line[0] < 0.0}
+        shape[axis] = line[0].abs() as usize;
+        axes[axis] = [line[1], line[2], line[3]];
+    }
+    let unit = if angstroms {1.0} else {BOHR};
+    let n_atoms = header[0].abs() as usize;
+    let mut molecule = Molecule::new();
+    let mut positions : Vec<[f32;3]> = Vec::new();
+    for i in 6..6+n_atoms {
+        let line = numbers(i, "an atomic number, a charge and 3 coordinates")?;
+        let number = line[0] as usize;
+        if line.len() < 5 || number == 0 {
+            return Err(format!("{} line {}: expected an atomic number, a charge and 3 coordinates", fname, i+1));
+        }
+        // (atomic numbers past the periodic table are drawn as oxygen)
+        let species = ELEMENTS.get(number-1).and_then(|symbol| default_species.by_symbol(symbol))
+                              .unwrap_or(default_species.oxygen());
+        let position = [line[2]*unit, line[3]*unit, line[4]*unit];
+        molecule.add_atom(species, &position);
+        positions.push(position);
+    }
+    if molecule.atoms().is_empty() {
+        return Err(format!("{}: no atoms found", fname));
+    }
+    let mut trajectory = Trajectory::new();
+    trajectory.add_frame(&positions).map_err(|e| format!("{}: {}", fname, e))?;
+
+    // with a negative number of atoms, a list of the orbitals comes next, and each
+    // point has a value for each of them
+    let mut words = flines[6+n_atoms..].iter().flat_map(|line| line.split_whitespace());
+    let orbitals = if header[0] < 0.0 {
+        let count = words.next().and_then(|word| word.parse::<usize>().ok()).unwrap_or(0);
+        for _ in 0..count {words.next();}
+        count.max(1)
+    } else {
+        1
+    };
+    let points = shape[0]*shape[1]*shape[2];
+    let values : Vec<f32> = words.step_by(orbitals).take(points).take_while(|_| !in_cancelled.load(Ordering::Relaxed))
+                                 .map(|word| word.parse::<f32>())
+                                 .collect::<Result<Vec<f32>, _>>()
+                                 .map_err(|_| format!("{}: expected the values on the grid", fname))?;
+    unless_cancelled(fname, in_cancelled)?;
+    if values.len() < points {
+        return Err(format!("{}: the file ends {} values short of the grid", fname, points-values.len()));
+    }
+    let origin = [header[1]*unit, header[2]*unit, header[3]*unit];
+    for axis in axes.iter_mut() {
+        for k in 0..3 {axis[k] *= unit}
+    }
+    Ok((molecule, trajectory, Volume::new(&origin, &axes, &shape, values)))
+}
+
+/// Reads a structure file of any of the formats above, going by its extension
+/// (anything else is read as a CASTEP cell file). Formats without frames give an
+/// empty trajectory.
+pub fn read_structure_file<'a>(fname : &String, default_species : &'a DefaultSpecies)
+                               -> Result<(Molecule<'a>, Trajectory), String> {
+    read_structure_file_cancellably(fname, default_species, &AtomicBool::new(false))
+}
+
+/// Reads a structure file as read_structure_file() does, giving up (with an error)
+/// once `in_cancelled` is set, e.g. by the viewer reading it in another thread when
+/// Escape is pressed. The readers check it between lines or frames.
+pub fn read_structure_file_cancellably<'a>(fname : &String, default_species : &'a DefaultSpecies,
+                                           in_cancelled : &AtomicBool) -> Result<(Molecule<'a>, Trajectory), String> {
+    let lowercase = fname.to_lowercase();
+    if lowercase.ends_with(".xyz") {
+        read_xyz(fname, default_species, in_cancelled)
+    } else if lowercase.ends_with(".pdb") || lowercase.ends_with(".ent") {
+        read_pdb(fname, default_species, in_cancelled)
+    } else if lowercase.ends_with(".cube") {
+        read_cube_file_cancellably(fname, default_species, in_cancelled)
+            .map(|(molecule, trajectory, _)| (molecule, trajectory))
+    } else if lowercase.ends_with(".cif") {
+        read_cif(fname, default_species, in_cancelled).map(|molecule| (molecule, Trajectory::new()))
+    } else {
+        read_cell_file(fname, default_species).map(|molecule| (molecule, Trajectory::new()))
+    }
+}
+
+/// An error once reading's been cancelled, for the readers to give up with.
+fn unless_cancelled(fname : &String, in_cancelled : &AtomicBool) -> Result<(), String> {
+    if in_cancelled.load(Ordering::Relaxed) {Err(format!("stopped reading {}", fname))} else {Ok(())}
 }
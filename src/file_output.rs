@@ -0,0 +1,93 @@
+//! Writes structures back out, for the formats file_input.rs reads that can keep
+//! what matters about them: extended XYZ and PDB.
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use molecule::Molecule;
+
+/// The longest segment ID a PDB file has room for.
+pub const PDB_SEGMENT_LENGTH : usize = 4;
+
+/// Writes the molecule's atoms where they are now, going by the file's extension:
+/// PDB (.pdb or .ent) or extended XYZ (.xyz). The molecule's named selections go
+/// with them as groups, to be read back in as named selections: in extended XYZ as
+/// a true/false column each, and in PDB as segment IDs, which only have room for
+/// each atom's first group, and the first 4 characters of its name.
+///
+/// Example:
+/// cargo run --release test/water.xyz --select "hydrogens = element H" export structure water_groups.xyz
+pub fn write_structure_file(in_fname : &str, in_molecule : &Molecule) -> Result<(), String> {
+    let lowercase = in_fname.to_lowercase();
+    let pdb = lowercase.ends_with(".pdb") || lowercase.ends_with(".ent");
+    if !pdb && !lowercase.ends_with(".xyz") {
+        return Err(format!("couldn't save {}: expected a .xyz or .pdb file", in_fname));
+    }
+    let file = File::create(in_fname).map_err(|e| format!("couldn't create {}: {}", in_fname, e))?;
+    let mut file = BufWriter::new(file);
+    let written = if pdb {write_pdb(&mut file, in_molecule)} else {write_xyz(&mut file, in_molecule)};
+    written.and_then(|_| file.flush()).map_err(|e| format!("couldn't write to {}: {}", in_fname, e))
+}
+
+/// A group's name as an extended XYZ column can have it: letters, digits and
+/// underscores, not starting with a digit.
+pub fn xyz_group_name(in_name : &str) -> String {
+    let name : String = in_name.chars().map(|c| if c.is_alphanumeric() || c == '_' {c} else {'_'}).collect();
+    if name.chars().next().map_or(true, |c| c.is_numeric()) {format!("_{}", name)} else {name}
+}
+
+/// Extended XYZ: the comment line says what each column is (Properties=), and gives
+/// the cell (Lattice=), as ASE and ovito read it.
+fn write_xyz<W : Write>(in_file : &mut W, in_molecule : &Molecule) -> ::std::io::Result<()> {
+    let atoms = in_molecule.atoms();
+    let groups = in_molecule.named_selections();
+    let charges = atoms.iter().any(|atom| atom.charge().is_some());
+    let mut properties = String::from("species:S:1:pos:R:3");
+    if charges {properties.push_str(":charge:R:1")}
+    for &(ref name, _) in groups {
+        properties.push_str(&format!(":{}:L:1", xyz_group_name(name)));
+    }
+    writeln!(in_file, "{}", atoms.len())?;
+    match *in_molecule.cell() {
+        Some(cell) => {
+            let lattice : Vec<String> = cell.lattice().iter().flat_map(|v| v.iter()).map(|x| format!("{:.6}", x)).collect();
+            writeln!(in_file, "Lattice=\"{}\" Properties={} pbc=\"T T T\"", lattice.join(" "), properties)?;
+        },
+        None => writeln!(in_file, "Properties={}", properties)?,
+    }
+    for (i, atom) in atoms.iter().enumerate() {
+        let position = atom.position();
+        write!(in_file, "{:<2} {:12.6} {:12.6} {:12.6}", atom.species().symbol(), position[0], position[1], position[2])?;
+        if charges {write!(in_file, " {:9.5}", atom.charge().unwrap_or(0.0))?}
+        for &(_, ref selection) in groups {
+            write!(in_file, " {}", if selection.contains(&i) {"T"} else {"F"})?;
+        }
+        writeln!(in_file)?;
+    }
+    Ok(())
+}
+
+/// PDB ATOM and HETATM records, with each atom's name, residue and B-factor where it
+/// has them, and its first group as its segment ID.
+fn write_pdb<W : Write>(in_file : &mut W, in_molecule : &Molecule) -> ::std::io::Result<()> {
+    let groups = in_molecule.named_selections();
+    for (i, atom) in in_molecule.atoms().iter().enumerate() {
+        let symbol = atom.species().symbol();
+        let record = if atom.is_hetero() {"HETATM"} else {"ATOM"};
+        let name = atom.name().as_ref().map_or(symbol, |name| name.as_str());
+        // names of 4 characters start in column 13, and shorter ones in 14
+        let name = if name.chars().count() >= 4 {name.chars().take(4).collect()} else {format!(" {}", name)};
+        let (chain, residue_name, residue_number) = match *atom.residue() {
+            Some(ref residue) => (residue.chain(), residue.name(), *residue.number()),
+            None => ("", "", 0),
+        };
+        let segment : String = groups.iter().find(|&&(_, ref selection)| selection.contains(&i))
+            .map_or(String::new(), |&(ref group, _)| group.chars().take(PDB_SEGMENT_LENGTH).collect());
+        let position = atom.position();
+        writeln!(in_file, "{:<6}{:>5} {:<4} {:>3} {:1}{:>4}    {:8.3}{:8.3}{:8.3}{:6.2}{:6.2}      {:<4}{:>2}",
+                 record, (i+1)%100000, name, residue_name, chain, residue_number,
+                 position[0], position[1], position[2], 1.0, atom.b_factor().unwrap_or(0.0),
+                 segment, symbol.to_uppercase())?;
+    }
+    writeln!(in_file, "END")
+}
@@ -0,0 +1,68 @@
+use measurement::Distance;
+use molecule::Molecule;
+use selection::Selection;
+
+// ============================================================
+// Focus
+// ============================================================
+/// Which atoms stand out in focus mode: the selection, plus every atom within a
+/// shell of it. Everything else is drawn dimmed and greyed out, e.g. to pick out
+/// an active site or a defect for a figure.
+pub struct Focus {
+    /// How close (in the file's length units) an atom has to be to a selected
+    /// atom to be in focus.
+    _shell         : f32,
+    _in_focus      : Vec<bool>,
+    // what _in_focus was worked out from, so it's only redone when one changes
+    _selection     : Selection,
+    _positions     : Vec<[f32;3]>,
+    _minimum_image : bool,
+}
+
+impl Focus {
+    pub fn new(in_shell : &f32) -> Focus {
+        Focus {
+            _shell         : in_shell.max(0.0),
+            _in_focus      : Vec::new(),
+            _selection     : Selection::new(),
+            _positions     : Vec::new(),
+            _minimum_image : false,
+        }
+    }
+
+    pub fn shell(&self) -> &f32 {&self._shell}
+
+    /// Works out which atoms are in focus, unless nothing has changed since last time.
+    pub fn update(&mut self, in_molecule : &Molecule, in_selection : &Selection, in_minimum_image : &bool) {
+        let positions : Vec<[f32;3]> = in_molecule.atoms().iter().map(|atom| *atom.position()).collect();
+        if self._in_focus.len() == positions.len()
+        && self._selection == *in_selection
+        && self._positions == positions
+        && self._minimum_image == *in_minimum_image {
+            return;
+        }
+        self._in_focus = (0..positions.len()).map(|i| {
+            in_selection.contains(&i) || in_selection.atoms().iter().any(|j| {
+                *Distance::between(in_molecule, &i, j, in_minimum_image).value() <= self._shell
+            })
+        }).collect();
+        self._selection = in_selection.to_owned();
+        self._positions = positions;
+        self._minimum_image = in_minimum_image.to_owned();
+    }
+
+    /// Whether an atom is drawn as normal. With nothing selected, every atom is.
+    pub fn is_in_focus(&self, in_atom : &usize) -> bool {
+        self._selection.is_empty() || self._in_focus.get(*in_atom).map_or(true, |&b| b)
+    }
+}
+
+/// How an atom out of focus is drawn: mostly grey, and darker.
+pub fn dimmed(in_colour : &[f32;3]) -> [f32;3] {
+    let grey = 0.299*in_colour[0] + 0.587*in_colour[1] + 0.114*in_colour[2];
+    let mut colour = [0.0f32;3];
+    for k in 0..3 {
+        colour[k] = 0.4*(0.2*in_colour[k] + 0.8*grey);
+    }
+    colour
+}
@@ -0,0 +1,53 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The longest a frame's step can be, in seconds, so that after a stall (a file
+/// loading, the window being dragged) things carry on from where they were rather
+/// than jumping ahead.
+pub const MAX_STEP_SECONDS : f32 = 0.1;
+
+/// The step when the frames must come out the same every time (replaying a
+/// session, recording a movie), whatever the time they take: a 60 Hz frame's.
+pub const FIXED_STEP_SECONDS : f32 = 1.0/60.0;
+
+fn seconds(in_duration : &Duration) -> f32 {
+    in_duration.as_secs() as f32 + in_duration.subsec_nanos() as f32*1e-9
+}
+
+// ============================================================
+// Frame Clock
+// ============================================================
+/// How long each frame's step is, in seconds, which everything animated from frame
+/// to frame (spinning, turning, playing a trajectory) is moved on by, so it goes at
+/// the same speed however fast the frames are drawn. With a target frame rate
+/// (--fps), it also waits out the rest of each frame that comes in early.
+pub struct FrameClock {
+    _last       : Instant,
+    _target_fps : Option<f32>,
+}
+
+impl FrameClock {
+    pub fn new(in_target_fps : &Option<f32>) -> FrameClock {
+        FrameClock {
+            _last       : Instant::now(),
+            _target_fps : *in_target_fps,
+        }
+    }
+
+    pub fn target_fps(&self) -> &Option<f32> {&self._target_fps}
+
+    /// Starts a frame, giving the time since the last one started (or if `in_fixed`,
+    /// FIXED_STEP_SECONDS).
+    pub fn tick(&mut self, in_fixed : &bool) -> f32 {
+        if let Some(fps) = self._target_fps {
+            let left = 1.0/fps - seconds(&self._last.elapsed());
+            if left > 0.0 {
+                thread::sleep(Duration::from_millis((left*1000.0) as u64));
+            }
+        }
+        let now = Instant::now();
+        let step = seconds(&now.duration_since(self._last)).min(MAX_STEP_SECONDS);
+        self._last = now;
+        if *in_fixed {FIXED_STEP_SECONDS} else {step}
+    }
+}
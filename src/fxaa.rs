@@ -6,6 +6,8 @@ use glium::framebuffer::SimpleFrameBuffer;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use stats;
+
 pub struct FxaaSystem {
     context: Rc<Context>,
     vertex_buffer: glium::VertexBuffer<SpriteVertex>,
@@ -143,6 +145,12 @@ impl FxaaSystem {
             target_depth: RefCell::new(None),
         }
     }
+
+    /// How much GPU memory the targets the frame is drawn into take, roughly.
+    pub fn bytes(&self) -> usize {
+        self.target_color.borrow().as_ref().map_or(0, |tex| stats::texture_bytes(tex, &4))
+            + self.target_depth.borrow().as_ref().map_or(0, stats::depth_bytes)
+    }
 }
 
 pub fn draw<T, F, R>(system: &FxaaSystem, target: &mut T, enabled: bool, mut draw: F)
@@ -0,0 +1,133 @@
+extern crate glium;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+use matrix::Matrix;
+use model::Model;
+use surface::Surface;
+
+// ============================================================
+// Geometry
+// ============================================================
+/// The scene as one mesh in world space, for Blender and other modelling tools
+/// (export geometry, or ctrl+E): every copy of a model, e.g. each atom's sphere
+/// and each stick's cylinder, has its transform applied on the CPU, so its
+/// vertices are where they're drawn and nothing is instanced. Each vertex has
+/// its own colour and opacity.
+pub struct Geometry {
+    _positions : Vec<[f32;3]>,
+    _normals   : Vec<[f32;3]>,
+    _colours   : Vec<[f32;4]>,
+    _triangles : Vec<[u32;3]>,
+}
+
+impl Geometry {
+    pub fn new() -> Geometry {
+        Geometry {
+            _positions : Vec::new(),
+            _normals   : Vec::new(),
+            _colours   : Vec::new(),
+            _triangles : Vec::new(),
+        }
+    }
+
+    pub fn triangles(&self) -> &Vec<[u32;3]> {&self._triangles}
+
+    /// Adds a copy of a model, moved by `in_model_matrix`, its normals turned by
+    /// `in_normal_matrix` (the model matrix itself, unless it stretches the model
+    /// more one way than another, as the ellipsoids' do).
+    pub fn add_model(&mut self, in_model : &Model, in_model_matrix : &Matrix, in_normal_matrix : &Matrix,
+                     in_colour : &[f32;4]) {
+        let first = self._positions.len() as u32;
+        for vertex in in_model.vertices() {
+            let p = *in_model_matrix * *vertex.position();
+            let n = *in_normal_matrix * *vertex.normal();
+            self._positions.push([p[0], p[1], p[2]]);
+            self._normals.push(normalised(&[n[0], n[1], n[2]]));
+            self._colours.push(*in_colour);
+        }
+        let indices : Vec<u32> = in_model.indices().iter().map(|&index| first + index as u32).collect();
+        match *in_model.index_type() {
+            glium::index::PrimitiveType::TrianglesList => {
+                self._triangles.extend(indices.chunks(3).filter(|t| t.len() == 3).map(|t| [t[0], t[1], t[2]]));
+            },
+            glium::index::PrimitiveType::TriangleStrip => {
+                // every other triangle of a strip winds the other way
+                for k in 2..indices.len() {
+                    let (a, b, c) = (indices[k-2], indices[k-1], indices[k]);
+                    self._triangles.push(if k%2 == 0 {[a, b, c]} else {[b, a, c]});
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Adds a surface, which is in world space already, coloured as it's drawn.
+    pub fn add_surface(&mut self, in_surface : &Surface, in_opacity : &f32) {
+        let first = self._positions.len() as u32;
+        for vertex in in_surface.vertices() {
+            let (p, n, c) = (vertex.position(), vertex.normal(), vertex.colour());
+            self._positions.push([p[0], p[1], p[2]]);
+            self._normals.push([n[0], n[1], n[2]]);
+            self._colours.push([c[0], c[1], c[2], *in_opacity]);
+        }
+        self._triangles.extend(in_surface.indices().chunks(3).filter(|t| t.len() == 3)
+                               .map(|t| [first+t[0], first+t[1], first+t[2]]));
+    }
+
+    /// Writes the mesh as PLY if the file name ends .ply, otherwise as a Wavefront
+    /// OBJ. OBJ has no standard place for colours, so they're given after each
+    /// vertex's position, as Blender, MeshLab and most others read them, without
+    /// the opacity; PLY has them (and the opacity) in bytes.
+    pub fn write(&self, in_fname : &str) -> Result<(), String> {
+        let file = File::create(in_fname).map_err(|e| format!("couldn't create {}: {}", in_fname, e))?;
+        let mut file = BufWriter::new(file);
+        let written = if in_fname.to_lowercase().ends_with(".ply") {
+            self.write_ply(&mut file)
+        } else {
+            self.write_obj(&mut file)
+        };
+        written.and_then(|_| file.flush()).map_err(|e| format!("couldn't write to {}: {}", in_fname, e))
+    }
+
+    fn write_obj<W : Write>(&self, in_file : &mut W) -> ::std::io::Result<()> {
+        writeln!(in_file, "# {} vertices, {} triangles", self._positions.len(), self._triangles.len())?;
+        for (p, c) in self._positions.iter().zip(&self._colours) {
+            writeln!(in_file, "v {} {} {} {:.4} {:.4} {:.4}", p[0], p[1], p[2], c[0], c[1], c[2])?;
+        }
+        for n in &self._normals {
+            writeln!(in_file, "vn {:.5} {:.5} {:.5}", n[0], n[1], n[2])?;
+        }
+        // (OBJ counts from 1)
+        for t in &self._triangles {
+            writeln!(in_file, "f {0}//{0} {1}//{1} {2}//{2}", t[0]+1, t[1]+1, t[2]+1)?;
+        }
+        Ok(())
+    }
+
+    fn write_ply<W : Write>(&self, in_file : &mut W) -> ::std::io::Result<()> {
+        write!(in_file, "ply\nformat ascii 1.0\nelement vertex {}\n\
+                         property float x\nproperty float y\nproperty float z\n\
+                         property float nx\nproperty float ny\nproperty float nz\n\
+                         property uchar red\nproperty uchar green\nproperty uchar blue\nproperty uchar alpha\n\
+                         element face {}\nproperty list uchar uint vertex_indices\nend_header\n",
+               self._positions.len(), self._triangles.len())?;
+        let byte = |x : f32| (x.max(0.0).min(1.0)*255.0).round() as u8;
+        for k in 0..self._positions.len() {
+            let (p, n, c) = (self._positions[k], self._normals[k], self._colours[k]);
+            writeln!(in_file, "{} {} {} {:.5} {:.5} {:.5} {} {} {} {}", p[0], p[1], p[2], n[0], n[1], n[2],
+                     byte(c[0]), byte(c[1]), byte(c[2]), byte(c[3]))?;
+        }
+        for t in &self._triangles {
+            writeln!(in_file, "3 {} {} {}", t[0], t[1], t[2])?;
+        }
+        Ok(())
+    }
+}
+
+fn normalised(in_v : &[f32;3]) -> [f32;3] {
+    let length = (in_v[0]*in_v[0]+in_v[1]*in_v[1]+in_v[2]*in_v[2]).sqrt().max(1e-12);
+    [in_v[0]/length, in_v[1]/length, in_v[2]/length]
+}
@@ -0,0 +1,205 @@
+use glium::{self, Surface};
+use glium::backend::Facade;
+use glium::backend::Context;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+
+use std::rc::Rc;
+
+use measurement::Distance;
+use molecule::Molecule;
+use selection::Selection;
+use stats;
+
+// ============================================================
+// Distance Matrix Heat Map
+// ============================================================
+/// A plot of the distance between every pair of a set of atoms, drawn over the
+/// scene as a square of coloured cells: dark for close pairs, bright for distant ones.
+/// Atom i's row is i from the top, and its column i from the left.
+pub struct HeatMap {
+    context: Rc<Context>,
+    program: glium::Program,
+    texture: Option<glium::texture::Texture2d>,
+    /// The molecule's indices of the atoms in the plot.
+    atoms: Vec<usize>,
+    /// What the plot was worked out from: the atoms' positions, and the cell (when
+    /// distances are taken to the nearest image), so it's only worked out again
+    /// when one of them changes.
+    positions: Vec<[f32; 3]>,
+    lattice: Option<[[f32; 3]; 3]>,
+    /// Where the plot was last drawn, in pixels from the top-left: position and size.
+    placement: [f32; 3],
+}
+
+#[derive(Copy, Clone)]
+struct PlotVertex {
+    position: [f32; 2],
+    i_tex_coords: [f32; 2],
+}
+
+implement_vertex!(PlotVertex, position, i_tex_coords);
+
+/// The most atoms plotted. The plot has this many squared cells.
+pub const MAX_ATOMS : usize = 300;
+
+impl HeatMap {
+    pub fn new<F: ?Sized>(facade: &F) -> HeatMap where F: Facade + Clone {
+        HeatMap {
+            context: facade.get_context().clone(),
+
+            program: glium::Program::from_source(facade,
+                r"
+                    #version 140
+
+                    uniform vec2 screen_size;
+
+                    in vec2 position;
+                    in vec2 i_tex_coords;
+
+                    out vec2 v_tex_coords;
+
+                    void main() {
+                        // pixels from the top-left to OpenGL's -1..1 from the bottom-left
+                        vec2 ndc = 2.0*position/screen_size - 1.0;
+                        gl_Position = vec4(ndc.x, -ndc.y, 0.0, 1.0);
+                        v_tex_coords = i_tex_coords;
+                    }
+                ",
+                r"
+                    #version 140
+
+                    uniform sampler2D plot;
+
+                    in vec2 v_tex_coords;
+
+                    out vec4 color;
+
+                    void main() {
+                        color = texture(plot, v_tex_coords);
+                    }
+                ",
+                None
+            ).unwrap(),
+
+            texture: None,
+            atoms: Vec::new(),
+            positions: Vec::new(),
+            lattice: None,
+            placement: [0.0; 3],
+        }
+    }
+
+    /// Works out the distances between the selected atoms (or every atom, if none
+    /// are selected), unless neither they nor their positions have changed since
+    /// last time. Fails if there are too many atoms to plot.
+    pub fn update(&mut self, molecule: &Molecule, selection: &Selection, minimum_image: bool)
+                  -> Result<(), String> {
+        let atoms = if selection.is_empty() {
+            Selection::all(molecule).atoms().to_owned()
+        } else {
+            selection.atoms().to_owned()
+        };
+        if atoms.len() > MAX_ATOMS {
+            return Err(format!(
+                "can't plot distances between {} atoms (at most {}); select fewer with E",
+                atoms.len(),
+                MAX_ATOMS
+            ));
+        }
+        let positions : Vec<[f32; 3]> = atoms.iter().map(|&i| *molecule.atoms()[i].position()).collect();
+        let lattice = if minimum_image {molecule.cell().as_ref().map(|cell| *cell.lattice())} else {None};
+        if self.texture.is_some() && atoms == self.atoms && positions == self.positions && lattice == self.lattice {
+            return Ok(());
+        }
+        let n = atoms.len();
+        let mut distances = vec![0.0f32; n*n];
+        let mut max = 0.0f32;
+        for i in 0..n {
+            for j in i+1..n {
+                let distance = *Distance::between(molecule, &atoms[i], &atoms[j], &minimum_image).value();
+                distances[i*n+j] = distance;
+                distances[j*n+i] = distance;
+                max = max.max(distance);
+            }
+        }
+        // textures start from the bottom row, so the first atom's row goes last
+        let mut pixels : Vec<u8> = Vec::with_capacity(4*n*n);
+        for y in 0..n {
+            for x in 0..n {
+                let t = if max > 0.0 {distances[(n-1-y)*n+x]/max} else {0.0};
+                let colour = heat_colour(t);
+                pixels.extend_from_slice(&[
+                    (255.0*colour[0]) as u8,
+                    (255.0*colour[1]) as u8,
+                    (255.0*colour[2]) as u8,
+                    255,
+                ]);
+            }
+        }
+        self.texture = if n > 0 {
+            let image = glium::texture::RawImage2d::from_raw_rgba(pixels, (n as u32, n as u32));
+            Some(glium::texture::Texture2d::new(&self.context, image).unwrap())
+        } else {
+            None
+        };
+        self.atoms = atoms;
+        self.positions = positions;
+        self.lattice = lattice;
+        Ok(())
+    }
+
+    /// The pair of atoms (as the molecule's indices) under a cursor position, if it's
+    /// over the plot where it was last drawn.
+    pub fn pair_at(&self, cursor: &[i32; 2]) -> Option<(usize, usize)> {
+        let n = self.atoms.len();
+        if n == 0 || self.placement[2] <= 0.0 { return None; }
+        let x = (cursor[0] as f32 - self.placement[0])/self.placement[2];
+        let y = (cursor[1] as f32 - self.placement[1])/self.placement[2];
+        if x < 0.0 || x >= 1.0 || y < 0.0 || y >= 1.0 { return None; }
+        let column = ((x*n as f32) as usize).min(n-1);
+        let row = ((y*n as f32) as usize).min(n-1);
+        Some((self.atoms[row], self.atoms[column]))
+    }
+
+    /// How much GPU memory the plot takes.
+    pub fn bytes(&self) -> usize {self.texture.as_ref().map_or(0, |tex| stats::texture_bytes(tex, &4))}
+}
+
+/// From dark purple (t = 0) through red to pale yellow (t = 1).
+fn heat_colour(t: f32) -> [f32; 3] {
+    let stops = [[0.05, 0.03, 0.25], [0.75, 0.15, 0.35], [1.0, 0.95, 0.6]];
+    let t = t.max(0.0).min(1.0)*2.0;
+    let (a, b, f) = if t < 1.0 {(stops[0], stops[1], t)} else {(stops[1], stops[2], t-1.0)};
+    [a[0]+(b[0]-a[0])*f, a[1]+(b[1]-a[1])*f, a[2]+(b[2]-a[2])*f]
+}
+
+/// Draws the plot as a square with its top-left corner at `position`, `size` pixels across.
+pub fn draw<T>(system: &mut HeatMap, target: &mut T, position: [f32; 2], size: f32)
+               where T: Surface
+{
+    system.placement = [position[0], position[1], size];
+    let texture = match system.texture {
+        Some(ref texture) => texture,
+        None => return,
+    };
+    let (w, h) = target.get_dimensions();
+    let (x0, y0) = (position[0], position[1]);
+    let (x1, y1) = (position[0]+size, position[1]+size);
+    let vertices = [
+        PlotVertex { position: [x0, y0], i_tex_coords: [0.0, 1.0] },
+        PlotVertex { position: [x0, y1], i_tex_coords: [0.0, 0.0] },
+        PlotVertex { position: [x1, y1], i_tex_coords: [1.0, 0.0] },
+        PlotVertex { position: [x0, y0], i_tex_coords: [0.0, 1.0] },
+        PlotVertex { position: [x1, y1], i_tex_coords: [1.0, 0.0] },
+        PlotVertex { position: [x1, y0], i_tex_coords: [1.0, 1.0] },
+    ];
+    let vertex_buffer = glium::VertexBuffer::new(&system.context, &vertices).unwrap();
+    let uniforms = uniform! {
+        screen_size: [w as f32, h as f32],
+        plot: texture.sampled()
+            .magnify_filter(MagnifySamplerFilter::Nearest)
+            .minify_filter(MinifySamplerFilter::Nearest),
+    };
+    target.draw(&vertex_buffer, &glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &system.program, &uniforms, &Default::default()).unwrap();
+}
@@ -0,0 +1,29 @@
+use keys::KeyBindings;
+use mouse::{MouseBindings, WheelMode};
+
+// ============================================================
+// Help
+// ============================================================
+/// The help screen, one line per entry: the current key bindings, then the mouse's.
+pub fn help_text(in_mouse_bindings : &MouseBindings, in_key_bindings : &KeyBindings,
+                 in_wheel_mode : &WheelMode) -> String {
+    let keys = in_key_bindings.descriptions();
+    let mut mouse = in_mouse_bindings.descriptions();
+    mouse.push(("left click".to_owned(), "select/deselect (or measure or edit, see F4) the atom under the cursor"));
+    mouse.push(("scroll wheel".to_owned(), in_wheel_mode.name()));
+    mouse.push(("drop a file".to_owned(), "load the structure in it instead (shift+drop adds its atoms)"));
+
+    let width = keys.iter().map(|&(ref key, _)| key.len())
+        .chain(mouse.iter().map(|&(ref input, _)| input.len()))
+        .max().unwrap_or(0);
+
+    let mut text = String::from("Keys\n");
+    for &(ref key, description) in &keys {
+        text.push_str(&format!("  {:width$}  {}\n", key, description, width = width));
+    }
+    text.push_str("\nMouse\n");
+    for &(ref input, action) in &mouse {
+        text.push_str(&format!("  {:width$}  {}\n", input, action, width = width));
+    }
+    text
+}
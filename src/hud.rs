@@ -0,0 +1,70 @@
+use glium::Surface;
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use text::{self, TextSystem};
+
+// ============================================================
+// HUD
+// ============================================================
+/// How many frames the frame time is averaged over.
+const FRAMES_AVERAGED : usize = 30;
+
+/// A heads-up display in the top-right corner, drawn over the scene: how fast
+/// it's drawing, what's drawn, and how.
+pub struct Hud {
+    /// The last few frame times, in seconds.
+    _frame_times : VecDeque<f32>,
+    _last_frame  : Option<Instant>,
+}
+
+impl Hud {
+    pub fn new() -> Hud {
+        Hud {
+            _frame_times : VecDeque::with_capacity(FRAMES_AVERAGED),
+            _last_frame  : None,
+        }
+    }
+
+    /// Called once a frame, to time it.
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self._last_frame {
+            let elapsed = now.duration_since(last);
+            if self._frame_times.len() == FRAMES_AVERAGED {
+                self._frame_times.pop_front();
+            }
+            self._frame_times.push_back(elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32*1e-9);
+        }
+        self._last_frame = Some(now);
+    }
+
+    /// The average time between frames lately, in seconds, or None before the second frame.
+    pub fn frame_time(&self) -> Option<f32> {
+        if self._frame_times.is_empty() {return None}
+        Some(self._frame_times.iter().sum::<f32>()/self._frame_times.len() as f32)
+    }
+
+    /// Draws the frame rate above the other lines, and returns the HUD's height in pixels.
+    pub fn draw<T>(&self, in_text_system : &TextSystem, in_target : &mut T, in_lines : &Vec<String>)
+                   -> f32 where T : Surface {
+        let mut text = match self.frame_time() {
+            Some(time) if time > 0.0 => format!("{:.1} fps ({:.1} ms)", 1.0/time, 1000.0*time),
+            _ => "- fps".to_owned(),
+        };
+        for line in in_lines {
+            text.push('\n');
+            text.push_str(line);
+        }
+        let scale = 2.0;
+        let size = text::text_size(&text, scale);
+        let (w, _) = in_target.get_dimensions();
+        let left = w as f32 - size[0] - 30.0;
+        text::draw_panel(in_text_system, in_target, [left, 10.0],
+                         [size[0]+20.0, size[1]+20.0], [0.0, 0.0, 0.0, 0.6]);
+        text::draw_text(in_text_system, in_target, &text, [left+10.0, 20.0], scale,
+                        [1.0, 1.0, 1.0, 1.0]);
+        size[1]+20.0
+    }
+}
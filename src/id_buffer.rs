@@ -0,0 +1,97 @@
+use glium::{self, Surface};
+use glium::backend::Facade;
+use glium::backend::Context;
+use glium::framebuffer::SimpleFrameBuffer;
+
+use std::rc::Rc;
+
+use matrix::Matrix;
+use stats;
+
+// ============================================================
+// ID Buffer Picking
+// ============================================================
+/// Picks atoms exactly as they're drawn, shader discards and all: the scene is drawn
+/// again with every atom in a flat colour encoding its index, and the colour under the
+/// cursor read back. Only the cursor's pixel is drawn, into a 1x1 framebuffer, by
+/// zooming the projection in on it (see pick_matrix()).
+pub struct IdBuffer {
+    context: Rc<Context>,
+    colour: glium::texture::Texture2d,
+    depth: glium::framebuffer::DepthRenderBuffer,
+}
+
+impl IdBuffer {
+    pub fn new<F: ?Sized>(facade: &F) -> IdBuffer where F: Facade + Clone {
+        IdBuffer {
+            context: facade.get_context().clone(),
+            colour: glium::texture::Texture2d::empty_with_format(facade,
+                glium::texture::UncompressedFloatFormat::U8U8U8U8,
+                glium::texture::MipmapsOption::NoMipmap, 1, 1).unwrap(),
+            depth: glium::framebuffer::DepthRenderBuffer::new(facade,
+                glium::texture::DepthFormat::I24, 1, 1).unwrap(),
+        }
+    }
+
+    /// How much GPU memory the one-pixel target takes.
+    pub fn bytes(&self) -> usize {stats::texture_bytes(&self.colour, &4) + stats::depth_bytes(&self.depth)}
+}
+
+/// The colour atom `index` is drawn in: index+1 in 24 bits across red, green and
+/// blue, so that black (the background) is no atom.
+pub fn id_colour(index: usize) -> [f32; 4] {
+    let id = index+1;
+    [
+        (id & 0xff) as f32/255.0,
+        ((id >> 8) & 0xff) as f32/255.0,
+        ((id >> 16) & 0xff) as f32/255.0,
+        1.0,
+    ]
+}
+
+/// The colour atom `index` is drawn in for the ID view (ctrl+F4): its id_colour()
+/// spread over the colours, so that neighbouring atoms (which differ in the lowest
+/// bits of red) look different, but still never black.
+pub fn view_colour(index: usize) -> [f32; 4] {
+    // an odd multiplier is one-to-one on 24 bits
+    let id = (index+1).wrapping_mul(0x9e3779) & 0xff_ffff;
+    [
+        (id & 0xff) as f32/255.0,
+        ((id >> 8) & 0xff) as f32/255.0,
+        ((id >> 16) & 0xff) as f32/255.0,
+        1.0,
+    ]
+}
+
+/// Goes before a projection matrix to blow the pixel under `cursor` (in pixels from
+/// the top-left of a `screen_size` window) up to fill the whole framebuffer.
+pub fn pick_matrix(cursor: &[i32; 2], screen_size: (u32, u32)) -> Matrix {
+    let (w, h) = (screen_size.0 as f32, screen_size.1 as f32);
+    // the centre of the cursor's pixel in normalised device coordinates
+    let x = 2.0*(cursor[0] as f32 + 0.5)/w - 1.0;
+    let y = 1.0 - 2.0*(cursor[1] as f32 + 0.5)/h;
+    Matrix::new([
+        [w  , 0.0, 0.0, -x*w],
+        [0.0, h  , 0.0, -y*h],
+        [0.0, 0.0, 1.0,  0.0],
+        [0.0, 0.0, 0.0,  1.0],
+    ])
+}
+
+/// Clears the buffer, lets `draw` draw the scene into it (with a depth buffer, each
+/// atom in id_colour() using the picking programs and a pick_matrix() projection),
+/// then returns the index of the atom it drew there, if any.
+pub fn pick<F>(system: &IdBuffer, mut draw: F) -> Option<usize>
+               where F: FnMut(&mut SimpleFrameBuffer)
+{
+    {
+        let mut target = SimpleFrameBuffer::with_depth_buffer(&system.context, &system.colour,
+                                                              &system.depth).unwrap();
+        target.clear_color_and_depth((0.0, 0.0, 0.0, 0.0), 1.0);
+        draw(&mut target);
+    }
+    let pixels : Vec<Vec<(u8, u8, u8, u8)>> = system.colour.read();
+    let (r, g, b, _) = pixels[0][0];
+    let id = r as usize | (g as usize) << 8 | (b as usize) << 16;
+    if id == 0 {None} else {Some(id-1)}
+}
@@ -0,0 +1,430 @@
+use glium::glutin::VirtualKeyCode;
+
+use mouse::Modifiers;
+
+// ============================================================
+// Key Action
+// ============================================================
+/// What pressing a key does, whichever key it's bound to.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum KeyAction {
+    Quit,
+    Help,
+    Hud,
+    Axes,
+    NextTool,
+    ClearMeasurements,
+    BindingSite,
+    NextElement,
+    Sequence,
+    Backbone,
+    Relax,
+    RelaxExternally,
+    NextMode,
+    PreviousMode,
+    SwingMore,
+    SwingLess,
+    Wireframe,
+    Normals,
+    FlatShading,
+    IdView,
+    ReloadShaders,
+    Dismiss,
+    Ellipsoids,
+    Fxaa,
+    ZoomIn,
+    ZoomOut,
+    /// Spins the view while the key's held (see SpinClockwise).
+    SpinAnticlockwise,
+    SpinClockwise,
+    TurnSceneLeft,
+    TurnSceneRight,
+    TurnSceneUp,
+    TurnSceneDown,
+    PutSceneBack,
+    OrbitLeft,
+    OrbitRight,
+    HideChains,
+    AzimuthDown,
+    AzimuthUp,
+    ResetCamera,
+    PovRay,
+    Fit,
+    Svg,
+    Projection,
+    Orbital,
+    Stereo,
+    WheelMode,
+    Undo,
+    Redo,
+    DepthCue,
+    Ghost,
+    Slab,
+    ColourScheme,
+    SlabThinner,
+    SlabThicker,
+    SlabNearer,
+    SlabFurther,
+    RadiusSource,
+    AtomsLarger,
+    AtomsSmaller,
+    Arrows,
+    ArrowsLonger,
+    ArrowsShorter,
+    MinimumImage,
+    Wrap,
+    CellEdges,
+    CellLabels,
+    CellOrigin,
+    AmbientOcclusion,
+    LatticePlanes,
+    MorePlanes,
+    FewerPlanes,
+    PlanesMoreOpaque,
+    PlanesLessOpaque,
+    OrbitalOut,
+    OrbitalIn,
+    PreviousFrame,
+    NextFrame,
+    PlayTrajectory,
+    /// Bound to the keys 1 to 9 (with modifiers, if any), which give the object.
+    ShowObject,
+    SelectObject,
+    PutObjectBack,
+    SplitObject,
+    /// Change the object the selection is in (see Objects::of_selection()).
+    ObjectColours,
+    ObjectBonds,
+    ObjectRadii,
+    TurnObjectLeft,
+    TurnObjectRight,
+    TurnObjectUp,
+    TurnObjectDown,
+    MoveObjectLeft,
+    MoveObjectRight,
+    MoveObjectUp,
+    MoveObjectDown,
+    AverageStructure,
+    RmsfColouring,
+    Query,
+    Console,
+    SelectNext,
+    ExportObj,
+    Opacity,
+    SaveStructure,
+    Statistics,
+    Raytrace,
+    SaveSession,
+    Surface,
+    Bonds,
+    PeriodicBonds,
+    Labels,
+    Assembly,
+    Packing,
+    FocusMode,
+    DistanceMatrix,
+    Diagram,
+    MemoryStats,
+    Keyframe,
+    PlayKeyframes,
+    RecordMovie,
+    Print,
+    ClearKeyframes,
+}
+
+/// Every action, in the order the help lists them: its name in the config's [keys]
+/// table, the keys it's bound to unless that says otherwise, and what it does.
+pub const ACTIONS : [(KeyAction, &'static str, &'static str, &'static str); 119] = [
+    (KeyAction::Quit             , "quit"              , "Escape"        , "quit"),
+    (KeyAction::Help             , "help"              , "F1"            , "show/hide this help"),
+    (KeyAction::Hud              , "hud"               , "F2"            , "show/hide the frame rate and what's drawn"),
+    (KeyAction::Axes             , "axes"              , "F3"            , "show/hide the axes in the corner"),
+    (KeyAction::NextTool         , "tool"              , "F4"            , "clicks: select, measure, delete/add atoms, drag the selection"),
+    (KeyAction::ClearMeasurements, "clear_measurements", "F5"            , "clear the measurements"),
+    (KeyAction::BindingSite      , "binding_site"      , "F6"            , "binding site of the selection, or each ligand in turn, in sticks"),
+    (KeyAction::NextElement      , "element"           , "F7"            , "element of the atoms added (C, N, O, H, S, Ni)"),
+    (KeyAction::Sequence         , "sequence"          , "F8"            , "show/hide the sequence; click a residue to go to it, scroll over it"),
+    (KeyAction::Backbone         , "backbone"          , "F9"            , "backbone: hidden, tube, putty (by B-factor or RMSF)"),
+    (KeyAction::Relax            , "relax"             , "F10"           , "relax the structure in a toy force field / stop"),
+    (KeyAction::RelaxExternally  , "optimiser"         , "Shift+F10"     , "relax it with the --optimiser program, watching each geometry / cancel"),
+    (KeyAction::NextMode         , "next_mode"         , "F11"           , "animate the next normal mode (--modes)"),
+    (KeyAction::PreviousMode     , "previous_mode"     , "Shift+F11"     , "animate the previous normal mode"),
+    (KeyAction::SwingMore        , "swing_more"        , "F12"           , "make the normal modes swing more"),
+    (KeyAction::SwingLess        , "swing_less"        , "Shift+F12"     , "make the normal modes swing less"),
+    (KeyAction::Wireframe        , "wireframe"         , "Ctrl+F1"       , "draw the meshes' wireframes over them"),
+    (KeyAction::Normals          , "normals"           , "Ctrl+F2"       , "colour the meshes and spheres by their normals / light them"),
+    (KeyAction::FlatShading      , "flat_shading"      , "Ctrl+F3"       , "shade each triangle flat / smoothly"),
+    (KeyAction::IdView           , "id_view"           , "Ctrl+F4"       , "show what picking sees, each atom in a colour of its own"),
+    (KeyAction::ReloadShaders    , "reload_shaders"    , "Ctrl+F5"       , "compile the shaders in shaders/ again (done whenever they change)"),
+    (KeyAction::Dismiss          , "dismiss"           , "Delete"        , "dismiss the messages in the status bar"),
+    (KeyAction::Ellipsoids       , "ellipsoids"        , "Tab"           , "draw thermal ellipsoids (CIF displacement parameters) / spheres"),
+    (KeyAction::Fxaa             , "fxaa"              , "Space"         , "toggle FXAA anti-aliasing"),
+    (KeyAction::ZoomIn           , "zoom_in"           , "Up"            , "zoom in"),
+    (KeyAction::ZoomOut          , "zoom_out"          , "Down"          , "zoom out"),
+    (KeyAction::SpinAnticlockwise, "spin_anticlockwise", "Left"          , "spin anticlockwise, while held"),
+    (KeyAction::SpinClockwise    , "spin_clockwise"    , "Right"         , "spin clockwise, while held"),
+    (KeyAction::TurnSceneLeft    , "turn_scene_left"   , "Alt+Left"      , "turn the whole scene left about its own centre"),
+    (KeyAction::TurnSceneRight   , "turn_scene_right"  , "Alt+Right"     , "turn the whole scene right about its own centre"),
+    (KeyAction::TurnSceneUp      , "turn_scene_up"     , "Alt+Up"        , "turn the whole scene up about its own centre"),
+    (KeyAction::TurnSceneDown    , "turn_scene_down"   , "Alt+Down"      , "turn the whole scene down about its own centre"),
+    (KeyAction::PutSceneBack     , "put_scene_back"    , "Ctrl+0"        , "put the whole scene back"),
+    (KeyAction::OrbitLeft        , "orbit_left"        , "H"             , "orbit left"),
+    (KeyAction::OrbitRight       , "orbit_right"       , "L"             , "orbit right"),
+    (KeyAction::HideChains       , "hide_chains"       , "Shift+H"       , "hide the selected atoms' chains (with nothing selected, show them all)"),
+    (KeyAction::AzimuthDown      , "azimuth_down"      , "J"             , "azimuth down"),
+    (KeyAction::AzimuthUp        , "azimuth_up"        , "K"             , "azimuth up"),
+    (KeyAction::ResetCamera      , "reset_camera"      , "R"             , "reset the camera"),
+    (KeyAction::PovRay           , "povray"            , "Ctrl+R"        , "save the view as a POV-Ray scene to scene.pov, spheres and cylinders"),
+    (KeyAction::Fit              , "fit"               , "V"             , "fit the whole molecule in view"),
+    (KeyAction::Svg              , "svg"               , "Ctrl+V"        , "save the view as an SVG drawing to view.svg, outlined circles and sticks"),
+    (KeyAction::Projection       , "projection"        , "O"             , "toggle perspective/orthographic projection"),
+    (KeyAction::Orbital          , "orbital"           , "Shift+O"       , "show/hide a cube file's orbital, blue where positive and red where negative"),
+    (KeyAction::Stereo           , "stereo"            , "B"             , "cycle stereo (off, red/cyan anaglyph, side-by-side)"),
+    (KeyAction::WheelMode        , "wheel"             , "Z"             , "switch the scroll wheel between dolly and field of view"),
+    (KeyAction::Undo             , "undo"              , "Ctrl+Z"        , "undo the last edit"),
+    (KeyAction::Redo             , "redo"              , "Ctrl+Shift+Z"  , "redo the last edit undone"),
+    (KeyAction::DepthCue         , "depth_cue"         , "D"             , "cycle depth cueing (off, value, hue)"),
+    (KeyAction::Ghost            , "ghost"             , "T"             , "toggle ghost (translucent) mode"),
+    (KeyAction::Slab             , "slab"              , "C"             , "toggle slab mode, showing only a slice of the molecule"),
+    (KeyAction::ColourScheme     , "colours"           , "Shift+C"       , "colour atoms by species, element (CPK), charge, chain, rainbow or residue"),
+    (KeyAction::SlabThinner      , "slab_thinner"      , "["             , "make the slab thinner"),
+    (KeyAction::SlabThicker      , "slab_thicker"      , "]"             , "make the slab thicker"),
+    (KeyAction::SlabNearer       , "slab_nearer"       , "-, Numpad-"    , "move the slab towards the camera"),
+    (KeyAction::SlabFurther      , "slab_further"      , "="             , "move the slab away from the camera"),
+    (KeyAction::RadiusSource     , "radii"             , "Ctrl+A"        , "draw the atoms at their species' sizes, covalent or van der Waals radii"),
+    (KeyAction::AtomsLarger      , "atoms_larger"      , "Ctrl+]"        , "draw every atom larger"),
+    (KeyAction::AtomsSmaller     , "atoms_smaller"     , "Ctrl+["        , "draw every atom smaller"),
+    (KeyAction::Arrows           , "arrows"            , "Shift+V"       , "arrows: hidden, forces, velocities, dipole (see --vectors)"),
+    (KeyAction::ArrowsLonger     , "arrows_longer"     , "Shift+]"       , "draw the arrows longer"),
+    (KeyAction::ArrowsShorter    , "arrows_shorter"    , "Shift+["       , "draw the arrows shorter"),
+    (KeyAction::MinimumImage     , "minimum_image"     , "I"             , "toggle minimum-image distances (crystals only)"),
+    (KeyAction::Wrap             , "wrap"              , "Shift+I"       , "wrap the atoms into the cell, in every frame (crystals only)"),
+    (KeyAction::CellEdges        , "cell"              , "Home"          , "show/hide the unit cell"),
+    (KeyAction::CellLabels       , "cell_labels"       , "Shift+Home"    , "show/hide the a/b/c labels on the unit cell"),
+    (KeyAction::CellOrigin       , "cell_origin"       , "Ctrl+Home"     , "mark/unmark the unit cell's origin"),
+    (KeyAction::AmbientOcclusion , "occlusion"         , "Insert"        , "trace ambient occlusion while the view is still / stop"),
+    (KeyAction::LatticePlanes    , "planes"            , "End"           , "show/hide the lattice planes given by --miller"),
+    (KeyAction::MorePlanes       , "more_planes"       , "PageUp"        , "draw more lattice planes"),
+    (KeyAction::FewerPlanes      , "fewer_planes"      , "PageDown"      , "draw fewer lattice planes"),
+    (KeyAction::PlanesMoreOpaque , "planes_opaque"     , "Shift+PageUp"  , "make the lattice planes more opaque"),
+    (KeyAction::PlanesLessOpaque , "planes_clear"      , "Shift+PageDown", "make the lattice planes less opaque"),
+    (KeyAction::OrbitalOut       , "orbital_out"       , "Ctrl+PageUp"   , "draw the orbital's lobes further out (a lower level)"),
+    (KeyAction::OrbitalIn        , "orbital_in"        , "Ctrl+PageDown" , "draw the orbital's lobes further in (a higher level)"),
+    (KeyAction::PreviousFrame    , "previous_frame"    , "Comma"         , "previous trajectory frame"),
+    (KeyAction::NextFrame        , "next_frame"        , "Period"        , "next trajectory frame"),
+    (KeyAction::PlayTrajectory   , "play"              , "Shift+Period"  , "play/pause the trajectory"),
+    (KeyAction::ShowObject       , "show_object"       , "1-9"           , "show/hide each file loaded (object), in the order they were loaded"),
+    (KeyAction::SelectObject     , "select_object"     , "Shift+1-9"     , "select an object's atoms"),
+    (KeyAction::PutObjectBack    , "put_object_back"   , "Ctrl+1-9"      , "put an object back where its file (or its group) put it"),
+    (KeyAction::SplitObject      , "split_object"      , "Ctrl+G"        , "make the selection an object of its own"),
+    (KeyAction::ObjectColours    , "object_colours"    , "Alt+C"         , "colour the selected object by a scheme of its own, or the scene's"),
+    (KeyAction::ObjectBonds      , "object_bonds"      , "Alt+X"         , "the selected object's bonds: the scene's, hidden, plain, by deviation"),
+    (KeyAction::ObjectRadii      , "object_radii"      , "Alt+A"         , "draw the selected object's atoms with radii of their own, or the scene's"),
+    (KeyAction::TurnObjectLeft   , "turn_object_left"  , "Alt+Shift+Left", "turn the selected object left about its own centre"),
+    (KeyAction::TurnObjectRight  , "turn_object_right" , "Alt+Shift+Right", "turn the selected object right about its own centre"),
+    (KeyAction::TurnObjectUp     , "turn_object_up"    , "Alt+Shift+Up"  , "turn the selected object up about its own centre"),
+    (KeyAction::TurnObjectDown   , "turn_object_down"  , "Alt+Shift+Down", "turn the selected object down about its own centre"),
+    (KeyAction::MoveObjectLeft   , "move_object_left"  , "Ctrl+Alt+Left" , "move the selected object left"),
+    (KeyAction::MoveObjectRight  , "move_object_right" , "Ctrl+Alt+Right", "move the selected object right"),
+    (KeyAction::MoveObjectUp     , "move_object_up"    , "Ctrl+Alt+Up"   , "move the selected object up"),
+    (KeyAction::MoveObjectDown   , "move_object_down"  , "Ctrl+Alt+Down" , "move the selected object down"),
+    (KeyAction::AverageStructure , "average"           , "A"             , "toggle the average structure (trajectories only)"),
+    (KeyAction::RmsfColouring    , "rmsf"              , "F"             , "toggle RMSF colouring (trajectories only)"),
+    (KeyAction::Query            , "query"             , "Return"        , "type a selection query, e.g. element O and within 2 of index 1"),
+    (KeyAction::Console          , "console"           , "Shift+Return"  , "type a line of script, e.g. backbone(\"tube\"); turn(0, 1, 0, 360, 10)"),
+    (KeyAction::SelectNext       , "select"            , "E"             , "select nothing, everything, each element, then each fragment"),
+    (KeyAction::ExportObj        , "obj"               , "Ctrl+E"        , "save the scene's spheres, sticks and surfaces to scene.obj, for Blender"),
+    (KeyAction::Opacity          , "opacity"           , "W"             , "step the selection's opacity (opaque, half, faint)"),
+    (KeyAction::SaveStructure    , "save_structure"    , "Ctrl+W"        , "save the structure, with named selections, to structure.pdb (or .xyz)"),
+    (KeyAction::Statistics       , "statistics"        , "S"             , "show/hide statistics for the selection"),
+    (KeyAction::Raytrace         , "raytrace"          , "Ctrl+S"        , "path-trace the view to raytrace.png, with soft shadows"),
+    (KeyAction::SaveSession      , "save_session"      , "Ctrl+Shift+S"  , "save the session (files, camera, display, selections) to oxide.session"),
+    (KeyAction::Surface          , "surface"           , "Shift+S"       , "show the molecular surface (coloured by potential), see-through, or hide it"),
+    (KeyAction::Bonds            , "bonds"             , "X"             , "bonds: hidden, plain, coloured by length deviation"),
+    (KeyAction::PeriodicBonds    , "periodic_bonds"    , "Ctrl+X"        , "bonds across the cell's faces: hidden, cut at the faces, to ghost atoms"),
+    (KeyAction::Labels           , "labels"            , "Q"             , "label atoms (selected, or all): off, element, number, residue"),
+    (KeyAction::Assembly         , "assembly"          , "U"             , "switch between the asymmetric unit and biological assembly (PDB)"),
+    (KeyAction::Packing          , "packing"           , "Y"             , "show/hide the crystal packing around the molecule, with contacts (PDB)"),
+    (KeyAction::FocusMode        , "focus"             , "G"             , "focus mode: dim all but the selection and its surroundings"),
+    (KeyAction::DistanceMatrix   , "distance_matrix"   , "M"             , "show/hide the distance matrix of the selection"),
+    (KeyAction::Diagram          , "diagram"           , "Shift+M"       , "show/hide a 2D skeletal diagram of the molecule (small molecules)"),
+    (KeyAction::MemoryStats      , "memory"            , "Ctrl+M"        , "print the atoms, bonds and triangles drawn, and the memory they take"),
+    (KeyAction::Keyframe         , "keyframe"          , "N"             , "record a camera keyframe"),
+    (KeyAction::PlayKeyframes    , "play_keyframes"    , "P"             , "play/stop the camera keyframes"),
+    (KeyAction::RecordMovie      , "movie"             , "Shift+P"       , "play the camera keyframes into movie.mp4 (with ffmpeg), a frame at a time"),
+    (KeyAction::Print            , "print"             , "Ctrl+P"        , "save the view to print.png, bigger than the window (see --print-size)"),
+    (KeyAction::ClearKeyframes   , "clear_keyframes"   , "Backspace"     , "clear the camera keyframes"),
+];
+
+/// The keys by the names bindings give them (in any case), e.g. "PageUp".
+const KEYS : [(&'static str, VirtualKeyCode); 69] = [
+    ("A", VirtualKeyCode::A), ("B", VirtualKeyCode::B), ("C", VirtualKeyCode::C), ("D", VirtualKeyCode::D),
+    ("E", VirtualKeyCode::E), ("F", VirtualKeyCode::F), ("G", VirtualKeyCode::G), ("H", VirtualKeyCode::H),
+    ("I", VirtualKeyCode::I), ("J", VirtualKeyCode::J), ("K", VirtualKeyCode::K), ("L", VirtualKeyCode::L),
+    ("M", VirtualKeyCode::M), ("N", VirtualKeyCode::N), ("O", VirtualKeyCode::O), ("P", VirtualKeyCode::P),
+    ("Q", VirtualKeyCode::Q), ("R", VirtualKeyCode::R), ("S", VirtualKeyCode::S), ("T", VirtualKeyCode::T),
+    ("U", VirtualKeyCode::U), ("V", VirtualKeyCode::V), ("W", VirtualKeyCode::W), ("X", VirtualKeyCode::X),
+    ("Y", VirtualKeyCode::Y), ("Z", VirtualKeyCode::Z),
+    ("0", VirtualKeyCode::Key0), ("1", VirtualKeyCode::Key1), ("2", VirtualKeyCode::Key2),
+    ("3", VirtualKeyCode::Key3), ("4", VirtualKeyCode::Key4), ("5", VirtualKeyCode::Key5),
+    ("6", VirtualKeyCode::Key6), ("7", VirtualKeyCode::Key7), ("8", VirtualKeyCode::Key8),
+    ("9", VirtualKeyCode::Key9),
+    ("F1", VirtualKeyCode::F1), ("F2", VirtualKeyCode::F2), ("F3", VirtualKeyCode::F3), ("F4", VirtualKeyCode::F4),
+    ("F5", VirtualKeyCode::F5), ("F6", VirtualKeyCode::F6), ("F7", VirtualKeyCode::F7), ("F8", VirtualKeyCode::F8),
+    ("F9", VirtualKeyCode::F9), ("F10", VirtualKeyCode::F10), ("F11", VirtualKeyCode::F11),
+    ("F12", VirtualKeyCode::F12),
+    ("Escape", VirtualKeyCode::Escape), ("Delete", VirtualKeyCode::Delete), ("Tab", VirtualKeyCode::Tab),
+    ("Space", VirtualKeyCode::Space), ("Return", VirtualKeyCode::Return), ("Backspace", VirtualKeyCode::Back),
+    ("Up", VirtualKeyCode::Up), ("Down", VirtualKeyCode::Down), ("Left", VirtualKeyCode::Left),
+    ("Right", VirtualKeyCode::Right), ("Home", VirtualKeyCode::Home), ("End", VirtualKeyCode::End),
+    ("Insert", VirtualKeyCode::Insert), ("PageUp", VirtualKeyCode::PageUp), ("PageDown", VirtualKeyCode::PageDown),
+    ("Comma", VirtualKeyCode::Comma), ("Period", VirtualKeyCode::Period), ("-", VirtualKeyCode::Minus),
+    ("Numpad-", VirtualKeyCode::Subtract), ("=", VirtualKeyCode::Equals), ("[", VirtualKeyCode::LBracket),
+    ("]", VirtualKeyCode::RBracket),
+];
+
+/// The keys 1 to 9 together, for the object actions, which take the object from
+/// the key pressed.
+const DIGITS : &'static str = "1-9";
+const DIGIT_KEYS : [VirtualKeyCode; 9] = [
+    VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3, VirtualKeyCode::Key4, VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6, VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9,
+];
+
+impl KeyAction {
+    pub fn from_name(in_name : &str) -> Option<KeyAction> {
+        ACTIONS.iter().find(|&&(_, name, _, _)| name == in_name).map(|&(action, _, _, _)| action)
+    }
+
+    pub fn name(&self) -> &'static str {
+        ACTIONS.iter().find(|&&(action, _, _, _)| action == *self).map_or("", |&(_, name, _, _)| name)
+    }
+
+    /// Whether it's bound to the keys 1 to 9 together, rather than a key at a time.
+    fn is_on_digits(&self) -> bool {
+        match *self {
+            KeyAction::ShowObject | KeyAction::SelectObject | KeyAction::PutObjectBack => true,
+            _ => false,
+        }
+    }
+}
+
+fn key_name(in_key : &VirtualKeyCode) -> &'static str {
+    KEYS.iter().find(|&&(_, key)| key == *in_key).map_or("?", |&(name, _)| name)
+}
+
+// ============================================================
+// Key Bindings
+// ============================================================
+#[derive(Copy, Clone)]
+struct KeyBinding {
+    _key       : VirtualKeyCode,
+    _modifiers : Modifiers,
+    _action    : KeyAction,
+}
+
+/// Maps (key, modifier keys) onto the action pressing it does. Modifiers must match
+/// exactly, so shift+V isn't V.
+#[derive(Clone)]
+pub struct KeyBindings {
+    _bindings : Vec<KeyBinding>,
+}
+
+impl KeyBindings {
+    /// The keys the help has always listed (see ACTIONS).
+    pub fn default() -> KeyBindings {
+        let mut bindings = KeyBindings {_bindings : Vec::new()};
+        for &(action, name, keys, _) in ACTIONS.iter() {
+            bindings.rebind(name, keys).unwrap_or_else(|message| panic!("the default {}: {}", action.name(), message));
+        }
+        bindings
+    }
+
+    /// Binds an action (by name, as ACTIONS has it) to a comma-separated list of
+    /// keys, e.g. "Ctrl+P, F9", in place of the keys it had. Modifiers are shift,
+    /// ctrl and alt. Keys bound to another action are taken from it, and "" leaves
+    /// the action unbound. The object actions take the keys 1 to 9 as "1-9".
+    pub fn rebind(&mut self, in_name : &str, in_keys : &str) -> Result<(), String> {
+        let action = KeyAction::from_name(in_name).ok_or_else(|| format!("no action \"{}\"", in_name))?;
+        let mut chords = Vec::new();
+        for chord in in_keys.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            let (modifiers, key) = parse_chord(chord)?;
+            match key {
+                None if action.is_on_digits() => {
+                    for &key in DIGIT_KEYS.iter() {chords.push((key, modifiers))}
+                },
+                None => return Err(format!("{} is a key at a time, not \"{}\"", in_name, chord)),
+                Some(_) if action.is_on_digits() => {
+                    return Err(format!("{} takes the keys {} together, not \"{}\"", in_name, DIGITS, chord));
+                },
+                Some(key) => chords.push((key, modifiers)),
+            }
+        }
+        self._bindings.retain(|b| b._action != action);
+        for (key, modifiers) in chords {
+            self._bindings.retain(|b| !(b._key == key && b._modifiers == modifiers));
+            self._bindings.push(KeyBinding {
+                _key       : key,
+                _modifiers : modifiers,
+                _action    : action,
+            });
+        }
+        Ok(())
+    }
+
+    /// Each action's keys as (e.g.) ("Ctrl+R", "save the view as..."), in the order
+    /// of ACTIONS, for the help screen. Unbound actions are left out.
+    pub fn descriptions(&self) -> Vec<(String, &'static str)> {
+        ACTIONS.iter().filter_map(|&(action, _, _, description)| {
+            let mut keys : Vec<String> = Vec::new();
+            for binding in self._bindings.iter().filter(|b| b._action == action) {
+                let name = if action.is_on_digits() {DIGITS} else {key_name(&binding._key)};
+                let key = format!("{}{}", prefix(&binding._modifiers), name);
+                if !keys.contains(&key) {keys.push(key)}
+            }
+            if keys.is_empty() {None} else {Some((keys.join(" or "), description))}
+        }).collect()
+    }
+
+    pub fn action(&self, in_key : &VirtualKeyCode, in_modifiers : &Modifiers) -> Option<KeyAction> {
+        self._bindings.iter()
+            .find(|b| b._key == *in_key && b._modifiers == *in_modifiers)
+            .map(|b| b._action)
+    }
+
+    /// Whether a key's bound to an action with any modifiers, e.g. to see whether a
+    /// key let go of was spinning the view when it was pressed.
+    pub fn is_bound(&self, in_key : &VirtualKeyCode, in_action : &KeyAction) -> bool {
+        self._bindings.iter().any(|b| b._key == *in_key && b._action == *in_action)
+    }
+}
+
+/// e.g. "Ctrl+Shift+", as the help shows them.
+fn prefix(in_modifiers : &Modifiers) -> String {
+    let mut prefix = String::new();
+    if in_modifiers.ctrl() {prefix.push_str("Ctrl+")}
+    if in_modifiers.shift() {prefix.push_str("Shift+")}
+    if in_modifiers.alt() {prefix.push_str("Alt+")}
+    prefix
+}
+
+/// A key and its modifiers, e.g. "ctrl+shift+s", or just the modifiers for "1-9".
+fn parse_chord(in_chord : &str) -> Result<(Modifiers, Option<VirtualKeyCode>), String> {
+    let parts : Vec<&str> = in_chord.split('+').map(|s| s.trim()).collect();
+    let (modifier_names, key_name) = parts.split_at(parts.len()-1);
+    let (mut shift, mut ctrl, mut alt) = (false, false, false);
+    for modifier in modifier_names.iter().map(|s| s.to_lowercase()) {
+        match modifier.as_str() {
+            "shift"            => shift = true,
+            "ctrl" | "control" => ctrl = true,
+            "alt"              => alt = true,
+            _ => return Err(format!("unknown modifier \"{}\" in \"{}\"", modifier, in_chord)),
+        }
+    }
+    let modifiers = Modifiers::new(&shift, &ctrl, &alt);
+    if key_name[0] == DIGITS {return Ok((modifiers, None))}
+    KEYS.iter()
+        .find(|&&(name, _)| name.eq_ignore_ascii_case(key_name[0]))
+        .map(|&(_, key)| (modifiers, Some(key)))
+        .ok_or_else(|| format!("unknown key \"{}\" in \"{}\"", key_name[0], in_chord))
+}
@@ -0,0 +1,45 @@
+// ============================================================
+// Labels
+// ============================================================
+/// What atoms are labelled with.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum LabelContent {
+    Off,
+    /// The element symbol, e.g. "Ni".
+    Element,
+    /// The atom's number in the file, counting from 1.
+    Index,
+    /// The residue's name and number, e.g. "ALA 12", for atoms in one.
+    Residue,
+}
+
+impl LabelContent {
+    pub fn next(&self) -> LabelContent {
+        match *self {
+            LabelContent::Off     => LabelContent::Element,
+            LabelContent::Element => LabelContent::Index,
+            LabelContent::Index   => LabelContent::Residue,
+            LabelContent::Residue => LabelContent::Off,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            LabelContent::Off     => "off",
+            LabelContent::Element => "element symbols",
+            LabelContent::Index   => "atom numbers",
+            LabelContent::Residue => "residues",
+        }
+    }
+
+    /// The content with a name as --labels takes it: off, element, number or residue.
+    pub fn parse(in_name : &str) -> Result<LabelContent, String> {
+        match in_name.to_lowercase().as_str() {
+            "off"     => Ok(LabelContent::Off),
+            "element" => Ok(LabelContent::Element),
+            "number"  => Ok(LabelContent::Index),
+            "residue" => Ok(LabelContent::Residue),
+            _ => Err(format!("expected off, element, number or residue, not \"{}\"", in_name)),
+        }
+    }
+}
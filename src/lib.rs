@@ -0,0 +1,167 @@
+//! Oxide, a molecular viewer, as a library: the core (reading structure files,
+//! analysing them and the maths) for tools with no window, and with the graphics
+//! feature, the viewer itself, to run from another program (see Viewer), or its
+//! drawing of the scene, for a program's own window (see SceneView).
+//!
+//! The types most programs start from are re-exported here; the rest are in
+//! their modules.
+
+// Without the graphics feature, nothing draws, and much of the core is only there
+// for the viewer.
+#![cfg_attr(not(feature = "graphics"), allow(dead_code))]
+
+#[cfg(feature = "graphics")]
+#[macro_use]
+extern crate glium;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate toml;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "graphics")]
+extern crate font8x8;
+#[cfg(feature = "graphics")]
+extern crate rhai;
+#[cfg(feature = "graphics")]
+extern crate miniz_oxide;
+
+// the core: reading, analysing and the maths
+pub mod matrix;
+pub mod quaternion;
+pub mod file_input;
+pub mod file_output;
+pub mod species;
+pub mod atom;
+pub mod molecule;
+pub mod camera;
+pub mod trajectory;
+pub mod depth_cue;
+pub mod cell;
+pub mod measurement;
+pub mod selection;
+pub mod picking;
+pub mod focus;
+pub mod bonds;
+pub mod radii;
+pub mod arrows;
+pub mod missing_residues;
+pub mod label;
+pub mod assembly;
+pub mod packing;
+pub mod binding_site;
+pub mod tool;
+pub mod tube;
+pub mod undo;
+pub mod optimize;
+pub mod fade;
+pub mod demo;
+pub mod vibration;
+pub mod ellipsoid;
+pub mod symmetry;
+pub mod scene;
+pub mod objects;
+pub mod scene_graph;
+pub mod config;
+pub mod slideshow;
+pub mod watch;
+pub mod frame_clock;
+pub mod refine;
+pub mod raytrace;
+pub mod colour;
+pub mod volume;
+pub mod stream;
+pub mod topology;
+pub mod diagram;
+pub mod povray;
+pub mod svg;
+pub mod usage;
+// (which the viewer takes the place of)
+#[cfg(not(feature = "graphics"))]
+pub mod summary;
+
+// the viewer
+#[cfg(feature = "graphics")]
+pub mod fxaa;
+#[cfg(feature = "graphics")]
+pub mod vertex;
+#[cfg(feature = "graphics")]
+pub mod model;
+#[cfg(feature = "graphics")]
+pub mod program;
+#[cfg(feature = "graphics")]
+pub mod oit;
+#[cfg(feature = "graphics")]
+pub mod mouse;
+#[cfg(feature = "graphics")]
+pub mod keys;
+#[cfg(feature = "graphics")]
+pub mod text;
+#[cfg(feature = "graphics")]
+pub mod help;
+#[cfg(feature = "graphics")]
+pub mod notification;
+#[cfg(feature = "graphics")]
+pub mod progress;
+#[cfg(feature = "graphics")]
+pub mod heat_map;
+#[cfg(feature = "graphics")]
+pub mod id_buffer;
+#[cfg(feature = "graphics")]
+pub mod stereo;
+#[cfg(feature = "graphics")]
+pub mod hud;
+#[cfg(feature = "graphics")]
+pub mod axes;
+#[cfg(feature = "graphics")]
+pub mod background;
+#[cfg(feature = "graphics")]
+pub mod sequence;
+#[cfg(feature = "graphics")]
+pub mod replay;
+#[cfg(feature = "graphics")]
+pub mod screenshot;
+#[cfg(feature = "graphics")]
+pub mod miller;
+#[cfg(feature = "graphics")]
+pub mod stats;
+#[cfg(feature = "graphics")]
+pub mod surface;
+#[cfg(feature = "graphics")]
+pub mod recovery;
+#[cfg(feature = "graphics")]
+pub mod session;
+#[cfg(feature = "graphics")]
+pub mod script;
+#[cfg(feature = "graphics")]
+pub mod remote;
+#[cfg(feature = "graphics")]
+pub mod material;
+#[cfg(feature = "graphics")]
+pub mod shading;
+#[cfg(feature = "graphics")]
+pub mod movie;
+#[cfg(feature = "graphics")]
+pub mod geometry;
+#[cfg(feature = "graphics")]
+pub mod batch;
+#[cfg(feature = "graphics")]
+pub mod scene_view;
+#[cfg(feature = "graphics")]
+pub mod viewer;
+
+pub use atom::Atom;
+pub use camera::Camera;
+pub use matrix::Matrix;
+pub use molecule::Molecule;
+pub use quaternion::Quaternion;
+pub use scene::Scene;
+pub use species::DefaultSpecies;
+pub use trajectory::Trajectory;
+#[cfg(feature = "graphics")]
+pub use model::Model as Mesh;
+#[cfg(feature = "graphics")]
+pub use scene_view::SceneView;
+#[cfg(feature = "graphics")]
+pub use viewer::Viewer;
@@ -0,0 +1,180 @@
+use std::fs::File;
+use std::io::prelude::*;
+
+use glium;
+
+use matrix::Matrix;
+use oit;
+
+// ============================================================
+// Material
+// ============================================================
+/// How one representation is drawn against the depth buffer: which fragments pass
+/// the depth test, whether they write depth, which faces are culled, and how far
+/// it's pulled towards the camera.
+///
+/// glium doesn't set glPolygonOffset, so the offset is made by moving the
+/// representation's clip-space depth instead. It's a fraction of the depth range,
+/// so e.g. 0.0005 draws cell edges over the faces of a surface they lie in, and
+/// pulls lines (which have no slope to offset by) forward just as much as faces.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Material {
+    _depth_test  : glium::DepthTest,
+    _depth_write : bool,
+    _culling     : glium::BackfaceCullingMode,
+    _offset      : f32,
+}
+
+impl Material {
+    /// Tested and written as usual, with the backs of faces culled.
+    pub fn new() -> Material {
+        Material {
+            _depth_test  : glium::DepthTest::IfLess,
+            _depth_write : true,
+            _culling     : glium::BackfaceCullingMode::CullCounterClockwise,
+            _offset      : 0.0,
+        }
+    }
+
+    pub fn set_depth_test(&mut self, in_depth_test : &glium::DepthTest) {self._depth_test = *in_depth_test}
+    pub fn set_depth_write(&mut self, in_depth_write : &bool) {self._depth_write = *in_depth_write}
+    pub fn set_culling(&mut self, in_culling : &glium::BackfaceCullingMode) {self._culling = *in_culling}
+    pub fn set_offset(&mut self, in_offset : &f32) {self._offset = *in_offset}
+
+    /// The draw parameters for the opaque pass (and picking).
+    pub fn parameters(&self) -> glium::DrawParameters<'static> {
+        glium::DrawParameters {
+            depth: glium::Depth {
+                test: self._depth_test,
+                write: self._depth_write,
+                .. Default::default()
+            },
+            backface_culling : self._culling,
+            .. Default::default()
+        }
+    }
+
+    /// The draw parameters for the translucent pass, which never writes depth.
+    pub fn translucent_parameters(&self) -> glium::DrawParameters<'static> {
+        oit::translucent_parameters(&self.parameters())
+    }
+
+    /// The view-projection matrix with the offset put in, so z' = z - 2*offset*w.
+    pub fn offset_matrix(&self, in_vp_matrix : &Matrix) -> Matrix {
+        if self._offset == 0.0 {return *in_vp_matrix}
+        let mut shift = [[1.0, 0.0, 0.0, 0.0],
+                         [0.0, 1.0, 0.0, 0.0],
+                         [0.0, 0.0, 1.0, 0.0],
+                         [0.0, 0.0, 0.0, 1.0f32]];
+        shift[2][3] = -2.0*self._offset;
+        Matrix::new(shift) * *in_vp_matrix
+    }
+}
+
+// ============================================================
+// Materials
+// ============================================================
+/// The material of each representation. They can be read from a file (with
+/// --materials), one setting a line, with blank lines and lines starting "#"
+/// ignored:
+///
+/// cell-edges depth always
+/// surface culling none
+/// planes offset -0.001
+/// atoms write off
+///
+/// The representations are atoms (and ellipsoids), sticks (bonds, the tube and the
+/// dashes across gaps), cell-edges, planes and surface. The depth tests are never,
+/// always, less, less-or-equal, equal, not-equal, greater and greater-or-equal;
+/// the culling is none, back or front, and writing on or off. Anything not in the
+/// file is as it would be without it.
+pub struct Materials {
+    _atoms      : Material,
+    _sticks     : Material,
+    _cell_edges : Material,
+    _planes     : Material,
+    _surface    : Material,
+}
+
+impl Materials {
+    pub fn new() -> Materials {
+        // the surface is seen from inside where it's cut by the slab
+        let mut surface = Material::new();
+        surface.set_culling(&glium::BackfaceCullingMode::CullingDisabled);
+        Materials {
+            _atoms      : Material::new(),
+            _sticks     : Material::new(),
+            _cell_edges : Material::new(),
+            _planes     : Material::new(),
+            _surface    : surface,
+        }
+    }
+
+    pub fn read(in_fname : &str) -> Result<Materials, String> {
+        let mut file = File::open(in_fname).map_err(|e| format!("couldn't open {}: {}", in_fname, e))?;
+        let mut flines = String::new();
+        file.read_to_string(&mut flines).map_err(|e| format!("couldn't read {}: {}", in_fname, e))?;
+
+        let mut materials = Materials::new();
+        for (i, line) in flines.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {continue}
+            let words : Vec<&str> = line.split_whitespace().collect();
+            let bad_line = |expected : &str| format!("{} line {}: expected {}", in_fname, i+1, expected);
+            if words.len() != 3 {
+                return Err(bad_line("a representation, a setting and its value, e.g. cell-edges depth always"));
+            }
+            let material = match words[0] {
+                "atoms"      => &mut materials._atoms,
+                "sticks"     => &mut materials._sticks,
+                "cell-edges" => &mut materials._cell_edges,
+                "planes"     => &mut materials._planes,
+                "surface"    => &mut materials._surface,
+                _ => return Err(bad_line("atoms, sticks, cell-edges, planes or surface")),
+            };
+            match words[1] {
+                "depth" => match parse_depth_test(words[2]) {
+                    Some(test) => material.set_depth_test(&test),
+                    None => return Err(bad_line("a depth test, e.g. less or always")),
+                },
+                "write" => match words[2] {
+                    "on"  => material.set_depth_write(&true),
+                    "off" => material.set_depth_write(&false),
+                    _ => return Err(bad_line("on or off")),
+                },
+                "culling" => match words[2] {
+                    "none"  => material.set_culling(&glium::BackfaceCullingMode::CullingDisabled),
+                    "back"  => material.set_culling(&glium::BackfaceCullingMode::CullCounterClockwise),
+                    "front" => material.set_culling(&glium::BackfaceCullingMode::CullClockwise),
+                    _ => return Err(bad_line("none, back or front")),
+                },
+                "offset" => match words[2].parse::<f32>() {
+                    Ok(offset) => material.set_offset(&offset),
+                    Err(_) => return Err(bad_line("a fraction of the depth range, e.g. 0.0005")),
+                },
+                _ => return Err(bad_line("depth, write, culling or offset")),
+            }
+        }
+        Ok(materials)
+    }
+
+    pub fn atoms(&self) -> &Material {&self._atoms}
+    pub fn sticks(&self) -> &Material {&self._sticks}
+    pub fn cell_edges(&self) -> &Material {&self._cell_edges}
+    pub fn planes(&self) -> &Material {&self._planes}
+    pub fn surface(&self) -> &Material {&self._surface}
+}
+
+fn parse_depth_test(in_word : &str) -> Option<glium::DepthTest> {
+    match in_word {
+        "never"            => Some(glium::DepthTest::Ignore),
+        "always"           => Some(glium::DepthTest::Overwrite),
+        "less"             => Some(glium::DepthTest::IfLess),
+        "less-or-equal"    => Some(glium::DepthTest::IfLessOrEqual),
+        "equal"            => Some(glium::DepthTest::IfEqual),
+        "not-equal"        => Some(glium::DepthTest::IfNotEqual),
+        "greater"          => Some(glium::DepthTest::IfMore),
+        "greater-or-equal" => Some(glium::DepthTest::IfMoreOrEqual),
+        _                  => None,
+    }
+}
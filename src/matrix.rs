@@ -1,3 +1,4 @@
+use std::fmt;
 use std::ops::Mul; // multiplication overload
 
 // ============================================================
@@ -5,8 +6,9 @@ use std::ops::Mul; // multiplication overload
 // ============================================================
 // NB: OpenGL (maybe) treats vectors as row vectors, so matrices should be transposed and multiplication reversed?
 /// A 4x4 matrix for holding transformations.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Matrix {
+    #[serde(rename = "contents")]
     _contents : [[f32; 4]; 4]
 }
 
@@ -18,6 +20,259 @@ impl Matrix {
     }
 
     pub fn contents(&self) -> &[[f32;4];4] {&self._contents}
+
+    pub fn identity() -> Matrix {
+        Matrix::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // ====================
+    // Transformations
+    // ====================
+    // Acting on column vectors, (x, y, z, 1), as the rest of the program's matrices
+    // do, so the rightmost of a product is applied first.
+    pub fn translation(in_translation : &[f32;3]) -> Matrix {
+        let t = in_translation;
+        Matrix::new([
+            [1.0, 0.0, 0.0, t[0]],
+            [0.0, 1.0, 0.0, t[1]],
+            [0.0, 0.0, 1.0, t[2]],
+            [0.0, 0.0, 0.0, 1.0 ],
+        ])
+    }
+
+    /// Scales each axis by its own factor.
+    pub fn scaling(in_factors : &[f32;3]) -> Matrix {
+        let s = in_factors;
+        Matrix::new([
+            [s[0], 0.0 , 0.0 , 0.0],
+            [0.0 , s[1], 0.0 , 0.0],
+            [0.0 , 0.0 , s[2], 0.0],
+            [0.0 , 0.0 , 0.0 , 1.0],
+        ])
+    }
+
+    /// A rotation anticlockwise about an axis (looking down it, towards the
+    /// origin), which needn't be normalised. The same rotation as the quaternion
+    /// for the axis and angle gives.
+    pub fn rotation(in_axis : &[f32;3], in_angle_radians : &f32) -> Matrix {
+        let norm = (in_axis[0]*in_axis[0]+in_axis[1]*in_axis[1]+in_axis[2]*in_axis[2]).sqrt();
+        let (x, y, z) = (in_axis[0]/norm, in_axis[1]/norm, in_axis[2]/norm);
+        let (s, c) = in_angle_radians.sin_cos();
+        let d = 1.0-c;
+        Matrix::new([
+            [c+x*x*d  , x*y*d-z*s, x*z*d+y*s, 0.0],
+            [y*x*d+z*s, c+y*y*d  , y*z*d-x*s, 0.0],
+            [z*x*d-y*s, z*y*d+x*s, c+z*z*d  , 0.0],
+            [0.0      , 0.0      , 0.0      , 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Matrix {
+        let m = &self._contents;
+        let mut contents = [[0.0f32;4];4];
+        for i in 0..4 {
+            for j in 0..4 {
+                contents[i][j] = m[j][i];
+            }
+        }
+        Matrix::new(contents)
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let m = &self._contents;
+        (0..4).map(|j| m[0][j]*self.cofactor(0, j)).sum()
+    }
+
+    /// The inverse, by cofactors, or None if the matrix is singular (e.g. a
+    /// scaling by 0). For a rotation and translation, rigid_inverse() is quicker.
+    pub fn inverse(&self) -> Option<Matrix> {
+        let determinant = self.determinant();
+        if determinant.abs() < 1e-12 {return None}
+        let mut contents = [[0.0f32;4];4];
+        for i in 0..4 {
+            for j in 0..4 {
+                // the adjugate is the transposed matrix of cofactors
+                contents[j][i] = self.cofactor(i, j)/determinant;
+            }
+        }
+        Some(Matrix::new(contents))
+    }
+
+    /// The matrix for transforming normals by, as this transforms points: the
+    /// inverse transpose of the upper-left 3x3 block, which keeps them
+    /// perpendicular to surfaces stretched unevenly. None if it's singular.
+    pub fn normal_matrix(&self) -> Option<Matrix> {
+        let mut block = self._contents;
+        for i in 0..3 {
+            block[i][3] = 0.0;
+            block[3][i] = 0.0;
+        }
+        block[3][3] = 1.0;
+        Matrix::new(block).inverse().map(|inverse| inverse.transpose())
+    }
+
+    /// The signed determinant of the 3x3 matrix left without row i and column j.
+    fn cofactor(&self, in_i : usize, in_j : usize) -> f32 {
+        let m = &self._contents;
+        let mut minor = [[0.0f32;3];3];
+        for (a, row) in (0..4).filter(|&r| r != in_i).enumerate() {
+            for (b, column) in (0..4).filter(|&c| c != in_j).enumerate() {
+                minor[a][b] = m[row][column];
+            }
+        }
+        let sign = if (in_i+in_j)%2 == 0 {1.0} else {-1.0};
+        sign*determinant_3x3(&minor)
+    }
+
+    /// The eigenvalues and eigenvectors of a symmetric matrix, by Jacobi rotations.
+    /// The eigenvectors are the columns of the returned matrix, in the same order
+    /// as the eigenvalues. Only the upper triangle is read.
+    pub fn symmetric_eigen(&self) -> ([f32;4], Matrix) {
+        let mut a = [[0.0f64;4];4];
+        let mut v = [[0.0f64;4];4];
+        for i in 0..4 {
+            for j in 0..4 {
+                a[i][j] = if j >= i {self._contents[i][j]} else {self._contents[j][i]} as f64;
+            }
+            v[i][i] = 1.0;
+        }
+        for _ in 0..50 {
+            let mut off_diagonal = 0.0;
+            for p in 0..4 { for q in p+1..4 { off_diagonal += a[p][q]*a[p][q]; } }
+            if off_diagonal < 1e-24 {break}
+            for p in 0..4 {
+                for q in p+1..4 {
+                    if a[p][q] == 0.0 {continue}
+                    // rotate in the p-q plane to zero a[p][q]
+                    let theta = (a[q][q]-a[p][p])/(2.0*a[p][q]);
+                    let t = theta.signum()/(theta.abs()+(theta*theta+1.0).sqrt());
+                    let c = 1.0/(t*t+1.0).sqrt();
+                    let s = t*c;
+                    for k in 0..4 {
+                        let akp = a[k][p];
+                        let akq = a[k][q];
+                        a[k][p] = c*akp-s*akq;
+                        a[k][q] = s*akp+c*akq;
+                    }
+                    for k in 0..4 {
+                        let apk = a[p][k];
+                        let aqk = a[q][k];
+                        a[p][k] = c*apk-s*aqk;
+                        a[q][k] = s*apk+c*aqk;
+                    }
+                    for k in 0..4 {
+                        let vkp = v[k][p];
+                        let vkq = v[k][q];
+                        v[k][p] = c*vkp-s*vkq;
+                        v[k][q] = s*vkp+c*vkq;
+                    }
+                }
+            }
+        }
+        let mut values = [0.0f32;4];
+        let mut vectors = [[0.0f32;4];4];
+        for i in 0..4 {
+            values[i] = a[i][i] as f32;
+            for j in 0..4 {
+                vectors[i][j] = v[i][j] as f32;
+            }
+        }
+        (values, Matrix::new(vectors))
+    }
+
+    /// Whether the upper-left 3x3 block is a proper rotation, i.e. orthonormal with
+    /// determinant +1, to within a tolerance.
+    pub fn is_rotation(&self, in_tolerance : &f32) -> bool {
+        let m = &self._contents;
+        for i in 0..3 {
+            for j in 0..3 {
+                let dot = m[i][0]*m[j][0]+m[i][1]*m[j][1]+m[i][2]*m[j][2];
+                let expected = if i == j {1.0} else {0.0};
+                if (dot-expected).abs() > *in_tolerance {return false}
+            }
+        }
+        (determinant_3x3(&self.block_3x3())-1.0).abs() <= *in_tolerance
+    }
+
+    /// The nearest-looking proper rotation to the upper-left 3x3 block, by Gram-Schmidt
+    /// on its rows, with the last row flipped if that leaves a reflection. The rest
+    /// of the matrix (e.g. a translation) is left alone.
+    pub fn orthonormalised(&self) -> Matrix {
+        let mut rows = self.block_3x3();
+        for i in 0..3 {
+            for j in 0..i {
+                let dot = rows[i][0]*rows[j][0]+rows[i][1]*rows[j][1]+rows[i][2]*rows[j][2];
+                for k in 0..3 {
+                    rows[i][k] -= dot*rows[j][k];
+                }
+            }
+            let norm = (rows[i][0]*rows[i][0]+rows[i][1]*rows[i][1]+rows[i][2]*rows[i][2]).sqrt();
+            for k in 0..3 {
+                rows[i][k] /= norm;
+            }
+        }
+        if determinant_3x3(&rows) < 0.0 {
+            for k in 0..3 {
+                rows[2][k] = -rows[2][k];
+            }
+        }
+        let mut contents = self._contents;
+        for i in 0..3 {
+            for j in 0..3 {
+                contents[i][j] = rows[i][j];
+            }
+        }
+        Matrix::new(contents)
+    }
+
+    /// The inverse of a rigid transformation (a rotation then a translation, like a
+    /// view matrix): the transposed rotation, undoing the translation.
+    pub fn rigid_inverse(&self) -> Matrix {
+        let m = &self._contents;
+        let mut contents = [[0.0f32;4];4];
+        for i in 0..3 {
+            for j in 0..3 {
+                contents[i][j] = m[j][i];
+            }
+            contents[i][3] = -(m[0][i]*m[0][3]+m[1][i]*m[1][3]+m[2][i]*m[2][3]);
+        }
+        contents[3][3] = 1.0;
+        Matrix::new(contents)
+    }
+
+    fn block_3x3(&self) -> [[f32;3];3] {
+        let m = &self._contents;
+        [
+            [m[0][0], m[0][1], m[0][2]],
+            [m[1][0], m[1][1], m[1][2]],
+            [m[2][0], m[2][1], m[2][2]],
+        ]
+    }
+}
+
+/// The determinant of a 3x3 matrix, e.g. a set of lattice vectors (one per row),
+/// which is the signed volume they span.
+pub fn determinant_3x3(in_m : &[[f32;3];3]) -> f32 {
+    let m = in_m;
+    m[0][0]*(m[1][1]*m[2][2]-m[1][2]*m[2][1])
+        - m[0][1]*(m[1][0]*m[2][2]-m[1][2]*m[2][0])
+        + m[0][2]*(m[1][0]*m[2][1]-m[1][1]*m[2][0])
+}
+
+/// One row per line, with the columns lined up.
+impl fmt::Display for Matrix {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        for (i, row) in self._contents.iter().enumerate() {
+            if i > 0 {writeln!(f)?;}
+            write!(f, "[{:10.4} {:10.4} {:10.4} {:10.4}]", row[0], row[1], row[2], row[3])?;
+        }
+        Ok(())
+    }
 }
 
 // Matrix multiplication. TODO: use a linear algebra library.
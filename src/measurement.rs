@@ -0,0 +1,200 @@
+use molecule::Molecule;
+
+// ============================================================
+// Distance
+// ============================================================
+/// The distance between two atoms. If the molecule has a cell and minimum-image
+/// distances are wanted, this is the distance to the nearest periodic image of
+/// the second atom, which is what simulation codes report.
+#[derive(Copy, Clone)]
+pub struct Distance {
+    _atoms : [usize;2],
+    _value : f32,
+    /// The lattice vectors added to the second atom, if a periodic image was nearer.
+    _image : Option<[i32;3]>,
+}
+
+impl Distance {
+    pub fn between(in_molecule : &Molecule, in_first : &usize, in_second : &usize,
+                   in_minimum_image : &bool) -> Distance {
+        let from = in_molecule.atoms()[*in_first].position();
+        let to = in_molecule.atoms()[*in_second].position();
+        let (vector, image) = match (in_molecule.cell(), *in_minimum_image) {
+            (&Some(ref cell), true) => cell.minimum_image(from, to),
+            _ => ([to[0]-from[0], to[1]-from[1], to[2]-from[2]], [0, 0, 0]),
+        };
+        Distance {
+            _atoms : [*in_first, *in_second],
+            _value : (vector[0]*vector[0]+vector[1]*vector[1]+vector[2]*vector[2]).sqrt(),
+            _image : if image == [0, 0, 0] {None} else {Some(image)},
+        }
+    }
+
+    pub fn value(&self) -> &f32 {&self._value}
+
+    /// e.g. "atoms 1-4: 2.8210 (periodic image +0 +1 +0)". Atoms are numbered from 1.
+    pub fn label(&self) -> String {
+        let label = format!("atoms {}-{}: {:.4}", self._atoms[0]+1, self._atoms[1]+1, self._value);
+        match self._image {
+            Some(image) => format!("{} (periodic image {:+} {:+} {:+})", label, image[0], image[1], image[2]),
+            None => label,
+        }
+    }
+}
+
+/// The closest pair of atoms, or None if there are fewer than two.
+pub fn shortest_distance(in_molecule : &Molecule, in_minimum_image : &bool) -> Option<Distance> {
+    let mut shortest : Option<Distance> = None;
+    for i in 0..in_molecule.atoms().len() {
+        for j in i+1..in_molecule.atoms().len() {
+            let distance = Distance::between(in_molecule, &i, &j, in_minimum_image);
+            if shortest.map_or(true, |s| distance.value() < s.value()) {
+                shortest = Some(distance);
+            }
+        }
+    }
+    shortest
+}
+
+/// The vector from one atom to another, to the nearest periodic image of the second
+/// if the molecule has a cell and minimum-image distances are wanted (as Distance).
+fn displacement(in_molecule : &Molecule, in_from : &usize, in_to : &usize, in_minimum_image : &bool) -> [f32;3] {
+    let from = in_molecule.atoms()[*in_from].position();
+    let to = in_molecule.atoms()[*in_to].position();
+    match (in_molecule.cell(), *in_minimum_image) {
+        (&Some(ref cell), true) => cell.minimum_image(from, to).0,
+        _ => [to[0]-from[0], to[1]-from[1], to[2]-from[2]],
+    }
+}
+
+fn dot(a : &[f32;3], b : &[f32;3]) -> f32 {a[0]*b[0] + a[1]*b[1] + a[2]*b[2]}
+
+fn cross(a : &[f32;3], b : &[f32;3]) -> [f32;3] {
+    [a[1]*b[2]-a[2]*b[1], a[2]*b[0]-a[0]*b[2], a[0]*b[1]-a[1]*b[0]]
+}
+
+// ============================================================
+// Measurement
+// ============================================================
+/// A measurement made by clicking atoms: a distance between two, the angle at the
+/// middle one of three, or the dihedral (torsion) angle about the middle two of four.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Measurement {
+    Distance([usize;2]),
+    Angle([usize;3]),
+    Dihedral([usize;4]),
+}
+
+impl Measurement {
+    /// The measurement of some atoms: two, three or four of them. None for any other number.
+    pub fn of(in_atoms : &[usize]) -> Option<Measurement> {
+        match *in_atoms {
+            [a, b]       => Some(Measurement::Distance([a, b])),
+            [a, b, c]    => Some(Measurement::Angle([a, b, c])),
+            [a, b, c, d] => Some(Measurement::Dihedral([a, b, c, d])),
+            _            => None,
+        }
+    }
+
+    pub fn atoms(&self) -> &[usize] {
+        match *self {
+            Measurement::Distance(ref atoms) => atoms,
+            Measurement::Angle(ref atoms)    => atoms,
+            Measurement::Dihedral(ref atoms) => atoms,
+        }
+    }
+
+    /// Where the atoms are, each one put next to the one before it as measured (so
+    /// the points may be periodic images of the atoms), for drawing the measurement.
+    pub fn points(&self, in_molecule : &Molecule, in_minimum_image : &bool) -> Vec<[f32;3]> {
+        let atoms = self.atoms();
+        let mut points = vec![*in_molecule.atoms()[atoms[0]].position()];
+        for pair in atoms.windows(2) {
+            let d = displacement(in_molecule, &pair[0], &pair[1], in_minimum_image);
+            let last = points[points.len()-1];
+            points.push([last[0]+d[0], last[1]+d[1], last[2]+d[2]]);
+        }
+        points
+    }
+
+    /// The distance, or the angle in degrees. Dihedrals go from -180 to 180, positive
+    /// for the last atom turned clockwise from the first looking along the middle two.
+    pub fn value(&self, in_molecule : &Molecule, in_minimum_image : &bool) -> f32 {
+        let p = self.points(in_molecule, in_minimum_image);
+        let vector = |i : usize, j : usize| [p[j][0]-p[i][0], p[j][1]-p[i][1], p[j][2]-p[i][2]];
+        match *self {
+            Measurement::Distance(_) => dot(&vector(0, 1), &vector(0, 1)).sqrt(),
+            Measurement::Angle(_) => {
+                let (u, v) = (vector(1, 0), vector(1, 2));
+                let cos = dot(&u, &v)/(dot(&u, &u)*dot(&v, &v)).sqrt();
+                cos.max(-1.0).min(1.0).acos().to_degrees()
+            },
+            Measurement::Dihedral(_) => {
+                let (b1, b2, b3) = (vector(0, 1), vector(1, 2), vector(2, 3));
+                let (n1, n2) = (cross(&b1, &b2), cross(&b2, &b3));
+                let b2_length = dot(&b2, &b2).sqrt();
+                let y = dot(&cross(&n1, &n2), &b2)/b2_length;
+                y.atan2(dot(&n1, &n2)).to_degrees()
+            },
+        }
+    }
+
+    /// The value on its own, as shown in the scene, e.g. "2.8210" or "109.47 deg".
+    pub fn label(&self, in_molecule : &Molecule, in_minimum_image : &bool) -> String {
+        match *self {
+            Measurement::Distance(_) => format!("{:.4}", self.value(in_molecule, in_minimum_image)),
+            _ => format!("{:.2} deg", self.value(in_molecule, in_minimum_image)),
+        }
+    }
+
+    /// The same measurement once an atom has been removed from the molecule (so the
+    /// atoms after it move down one), or None if it was one of the atoms measured.
+    pub fn without_atom(&self, in_atom : &usize) -> Option<Measurement> {
+        if self.atoms().contains(in_atom) {return None}
+        let atoms : Vec<usize> = self.atoms().iter().map(|&i| if i > *in_atom {i-1} else {i}).collect();
+        Measurement::of(&atoms)
+    }
+
+    /// The same measurement once an atom has been put in at an index, moving the
+    /// atoms from there up one.
+    pub fn with_atom_inserted(&self, in_index : &usize) -> Measurement {
+        let atoms : Vec<usize> = self.atoms().iter().map(|&i| if i >= *in_index {i+1} else {i}).collect();
+        Measurement::of(&atoms).unwrap()
+    }
+
+    /// e.g. "angle 3-1-2: 104.52 deg". Atoms are numbered from 1.
+    pub fn description(&self, in_molecule : &Molecule, in_minimum_image : &bool) -> String {
+        let name = match *self {
+            Measurement::Distance(_) => "distance",
+            Measurement::Angle(_)    => "angle",
+            Measurement::Dihedral(_) => "dihedral",
+        };
+        let atoms : Vec<String> = self.atoms().iter().map(|i| format!("{}", i+1)).collect();
+        format!("{} {}: {}", name, atoms.join("-"), self.label(in_molecule, in_minimum_image))
+    }
+}
+
+/// Points along the arc of an angle, for drawing it: `in_radius` from its vertex,
+/// from the direction of `in_from` round to that of `in_to`.
+pub fn arc(in_vertex : &[f32;3], in_from : &[f32;3], in_to : &[f32;3], in_radius : &f32) -> Vec<[f32;3]> {
+    let unit = |p : &[f32;3]| {
+        let d = [p[0]-in_vertex[0], p[1]-in_vertex[1], p[2]-in_vertex[2]];
+        let length = dot(&d, &d).sqrt().max(1e-6);
+        [d[0]/length, d[1]/length, d[2]/length]
+    };
+    let (u, v) = (unit(in_from), unit(in_to));
+    let angle = dot(&u, &v).max(-1.0).min(1.0).acos();
+    let segments = ((angle.to_degrees()/10.0).ceil() as usize).max(1);
+    (0..segments+1).map(|k| {
+        // spherical interpolation between the two directions
+        let t = k as f32/segments as f32;
+        let (a, b) = if angle < 1e-4 {(1.0-t, t)} else {
+            (((1.0-t)*angle).sin()/angle.sin(), (t*angle).sin()/angle.sin())
+        };
+        [
+            in_vertex[0] + in_radius*(a*u[0]+b*v[0]),
+            in_vertex[1] + in_radius*(a*u[1]+b*v[1]),
+            in_vertex[2] + in_radius*(a*u[2]+b*v[2]),
+        ]
+    }).collect()
+}
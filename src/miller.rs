@@ -0,0 +1,170 @@
+use cell::Cell;
+use vertex::Vertex;
+
+/// How many of a family's planes are drawn at most, however many cut the cell.
+pub const MAX_PLANES : usize = 20;
+
+// ============================================================
+// Miller Planes
+// ============================================================
+/// The lattice planes (hkl): h x + k y + l z = n in fractional coordinates, for
+/// each integer n.
+#[derive(Copy, Clone, Debug)]
+pub struct MillerPlanes {
+    _indices : [i32;3],
+    /// How many of the cells the indices are given in make up the one the planes
+    /// are drawn in, along each lattice vector, for supercells.
+    _repeats : [usize;3],
+}
+
+impl MillerPlanes {
+    /// None for (000), which isn't a plane.
+    pub fn new(in_indices : &[i32;3]) -> Option<MillerPlanes> {
+        if in_indices.iter().all(|&i| i == 0) {return None}
+        Some(MillerPlanes {_indices : *in_indices, _repeats : [1;3]})
+    }
+
+    /// Reads e.g. "1 1 0", "1,1,0" or "(1 -1 0)".
+    pub fn parse(in_text : &str) -> Result<MillerPlanes, String> {
+        let text = in_text.trim().trim_start_matches('(').trim_end_matches(')');
+        let indices : Vec<i32> = text.split(|c : char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse().map_err(|_| format!("\"{}\" isn't a Miller index", s)))
+            .collect::<Result<_, _>>()?;
+        if indices.len() != 3 {return Err(format!("expected 3 Miller indices, not \"{}\"", in_text))}
+        MillerPlanes::new(&[indices[0], indices[1], indices[2]])
+            .ok_or_else(|| "(000) isn't a plane".to_owned())
+    }
+
+    /// The same planes of a crystal, drawn in a supercell of `in_repeats` of its cells.
+    pub fn in_supercell(&self, in_repeats : &[usize;3]) -> MillerPlanes {
+        MillerPlanes {
+            _indices : self._indices,
+            _repeats : [self._repeats[0]*in_repeats[0], self._repeats[1]*in_repeats[1], self._repeats[2]*in_repeats[2]],
+        }
+    }
+
+    /// e.g. "(1 1 0)", or "(1 -1 0)".
+    pub fn name(&self) -> String {
+        format!("({} {} {})", self._indices[0], self._indices[1], self._indices[2])
+    }
+
+    /// Where a point (in fractions of the cell drawn in) is through the family: n
+    /// for a point on the plane n.
+    fn value(&self, in_fractional : &[f32;3]) -> f32 {
+        (0..3).map(|k| (self._indices[k]*self._repeats[k] as i32) as f32*in_fractional[k]).sum()
+    }
+
+    /// The normal to the planes in cartesian coordinates, 1/d long, where d is the
+    /// spacing between them.
+    fn normal(&self, in_cell : &Cell) -> [f32;3] {
+        let mut normal = [0.0f32;3];
+        for i in 0..3 {
+            let mut axis = [0.0f32;3];
+            axis[i] = 1.0;
+            normal[i] = self.value(&in_cell.to_fractional(&axis));
+        }
+        normal
+    }
+
+    /// The distance between neighbouring planes, in Angstroms.
+    pub fn spacing(&self, in_cell : &Cell) -> f32 {
+        let n = self.normal(in_cell);
+        1.0/(n[0]*n[0]+n[1]*n[1]+n[2]*n[2]).sqrt()
+    }
+
+    /// Where up to `in_count` of the planes cut the cell, the ones nearest its
+    /// middle, each as the corners of a polygon in cartesian coordinates, in order
+    /// round it.
+    pub fn polygons(&self, in_cell : &Cell, in_count : &usize) -> Vec<Vec<[f32;3]>> {
+        let corners : Vec<[f32;3]> = (0..8)
+            .map(|k| [(k & 1) as f32, ((k >> 1) & 1) as f32, ((k >> 2) & 1) as f32])
+            .collect();
+        let values : Vec<f32> = corners.iter().map(|c| self.value(c)).collect();
+        let lowest = values.iter().cloned().fold(f32::INFINITY, f32::min).ceil() as i32;
+        let highest = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max).floor() as i32;
+        let middle = self.value(&[0.5, 0.5, 0.5]);
+        let mut planes : Vec<i32> = (lowest..highest+1).collect();
+        planes.sort_by(|&a, &b| (a as f32-middle).abs().partial_cmp(&(b as f32-middle).abs()).unwrap());
+        planes.truncate(*in_count);
+        planes.sort();
+
+        let normal = self.normal(in_cell);
+        let origin = in_cell.origin();
+        let to_cartesian = |f : &[f32;3]| {
+            let c = in_cell.to_cartesian(f);
+            [origin[0]+c[0], origin[1]+c[1], origin[2]+c[2]]
+        };
+        let mut polygons = Vec::new();
+        for n in planes {
+            let n = n as f32;
+            // the corners on the plane, and where it cuts the edges between the
+            // corners either side of it
+            let mut points : Vec<[f32;3]> = Vec::new();
+            for (k, corner) in corners.iter().enumerate() {
+                if (values[k]-n).abs() < 1e-4 {points.push(*corner)}
+            }
+            for a in 0..8 {
+                for axis in 0..3 {
+                    if a & (1 << axis) != 0 {continue}
+                    let b = a | (1 << axis);
+                    let (va, vb) = (values[a]-n, values[b]-n);
+                    if va.abs() < 1e-4 || vb.abs() < 1e-4 || (va > 0.0) == (vb > 0.0) {continue}
+                    let t = va/(va-vb);
+                    let mut point = corners[a];
+                    point[axis] = t;
+                    points.push(point);
+                }
+            }
+            if points.len() < 3 {continue}
+            let points : Vec<[f32;3]> = points.iter().map(&to_cartesian).collect();
+            // in order round the middle of the polygon
+            let count = points.len() as f32;
+            let centre = [
+                points.iter().map(|p| p[0]).sum::<f32>()/count,
+                points.iter().map(|p| p[1]).sum::<f32>()/count,
+                points.iter().map(|p| p[2]).sum::<f32>()/count,
+            ];
+            let u = [points[0][0]-centre[0], points[0][1]-centre[1], points[0][2]-centre[2]];
+            let w = [
+                normal[1]*u[2]-normal[2]*u[1],
+                normal[2]*u[0]-normal[0]*u[2],
+                normal[0]*u[1]-normal[1]*u[0],
+            ];
+            let angle = |p : &[f32;3]| {
+                let d = [p[0]-centre[0], p[1]-centre[1], p[2]-centre[2]];
+                (d[0]*w[0]+d[1]*w[1]+d[2]*w[2]).atan2(d[0]*u[0]+d[1]*u[1]+d[2]*u[2])
+            };
+            let mut points = points;
+            points.sort_by(|a, b| angle(a).partial_cmp(&angle(b)).unwrap());
+            polygons.push(points);
+        }
+        polygons
+    }
+
+    /// The polygons as triangles, for drawing lit from either side: each is put in
+    /// twice, facing each way.
+    pub fn mesh(&self, in_cell : &Cell, in_count : &usize) -> (Vec<Vertex>, Vec<u16>) {
+        let n = self.normal(in_cell);
+        let length = (n[0]*n[0]+n[1]*n[1]+n[2]*n[2]).sqrt();
+        let normal = [n[0]/length, n[1]/length, n[2]/length];
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for polygon in self.polygons(in_cell, in_count) {
+            for &(sign, reversed) in &[(1.0f32, false), (-1.0f32, true)] {
+                let start = vertices.len() as u16;
+                for p in &polygon {
+                    vertices.push(Vertex::new(*p, [sign*normal[0], sign*normal[1], sign*normal[2]]));
+                }
+                for k in 1..polygon.len() as u16-1 {
+                    if reversed {
+                        indices.extend_from_slice(&[start, start+k+1, start+k]);
+                    } else {
+                        indices.extend_from_slice(&[start, start+k, start+k+1]);
+                    }
+                }
+            }
+        }
+        (vertices, indices)
+    }
+}
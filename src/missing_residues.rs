@@ -0,0 +1,175 @@
+use atom::{Atom, Residue};
+use molecule::Molecule;
+
+// ============================================================
+// Missing Residues
+// ============================================================
+/// What a structure file says is missing from the model: whole residues (PDB
+/// REMARK 465), and atoms of residues which are otherwise there (REMARK 470).
+/// Usually parts too disordered to see in the experiment.
+#[derive(Clone, Debug)]
+pub struct MissingResidues {
+    _residues : Vec<Residue>,
+    /// Each incomplete residue with the names of its missing atoms.
+    _atoms    : Vec<(Residue, Vec<String>)>,
+}
+
+impl MissingResidues {
+    pub fn new() -> MissingResidues {
+        MissingResidues {
+            _residues : Vec::new(),
+            _atoms    : Vec::new(),
+        }
+    }
+
+    pub fn add_residue(&mut self, in_residue : &Residue) {self._residues.push(in_residue.to_owned())}
+
+    pub fn add_atoms(&mut self, in_residue : &Residue, in_names : &Vec<String>) {
+        self._atoms.push((in_residue.to_owned(), in_names.to_owned()))
+    }
+
+    pub fn is_empty(&self) -> bool {self._residues.is_empty() && self._atoms.is_empty()}
+}
+
+// ============================================================
+// Backbone Gaps
+// ============================================================
+/// A break in a chain's backbone: consecutive alpha carbons either numbered
+/// more than one residue apart, or too far apart to be neighbours.
+#[derive(Copy, Clone, Debug)]
+pub struct Gap {
+    /// The alpha carbons (as indices into the molecule's atoms) either side of the gap.
+    _atoms   : [usize;2],
+    /// How many residues the numbering skips; 0 if it doesn't, but the atoms are too far apart.
+    _skipped : i32,
+}
+
+impl Gap {
+    pub fn atoms(&self) -> &[usize;2] {&self._atoms}
+}
+
+/// Whether an atom is a protein's alpha carbon: a carbon called "CA" in a residue
+/// (so not a calcium ion, which PDB files also call "CA").
+pub fn is_alpha_carbon(in_atom : &Atom) -> bool {
+    in_atom.residue().is_some() && in_atom.species().symbol() == "C"
+        && in_atom.name().as_ref().map_or(false, |name| name == "CA")
+}
+
+/// The furthest apart (in Angstroms) neighbouring alpha carbons can be: 3.8, plus
+/// some room for cis peptides and poor models.
+const MAX_CA_DISTANCE : f32 = 4.2;
+
+/// Every gap in the backbone of every chain, found from the alpha carbons ("CA").
+pub fn gaps(in_molecule : &Molecule) -> Vec<Gap> {
+    let mut gaps = Vec::new();
+    // the last alpha carbon seen in each chain
+    let mut previous : Vec<(String, usize)> = Vec::new();
+    for (i, atom) in in_molecule.atoms().iter().enumerate() {
+        let residue = match *atom.residue() {
+            Some(ref residue) if is_alpha_carbon(atom) => residue,
+            _ => continue,
+        };
+        match previous.iter().position(|&(ref chain, _)| chain == residue.chain()) {
+            Some(k) => {
+                let j = previous[k].1;
+                let last = in_molecule.atoms()[j].residue().as_ref().unwrap();
+                let skipped = residue.number() - last.number() - 1;
+                let a = in_molecule.atoms()[j].position();
+                let b = atom.position();
+                let distance = ((b[0]-a[0]).powi(2) + (b[1]-a[1]).powi(2) + (b[2]-a[2]).powi(2)).sqrt();
+                if skipped > 0 || distance > MAX_CA_DISTANCE {
+                    gaps.push(Gap {_atoms : [j, i], _skipped : skipped.max(0)});
+                }
+                previous[k].1 = i;
+            },
+            None => previous.push((residue.chain().to_owned(), i)),
+        }
+    }
+    gaps
+}
+
+/// Splits the line across a gap into dashes of about `in_dash` long with spaces as
+/// long between them, as (start, end) pairs, so the gap can be drawn dashed.
+pub fn dashes(in_from : &[f32;3], in_to : &[f32;3], in_dash : &f32) -> Vec<([f32;3], [f32;3])> {
+    let d = [in_to[0]-in_from[0], in_to[1]-in_from[1], in_to[2]-in_from[2]];
+    let length = (d[0]*d[0]+d[1]*d[1]+d[2]*d[2]).sqrt();
+    // an odd number of pieces, so there's a dash at each end
+    let mut pieces = (length/in_dash).round().max(1.0) as usize;
+    if pieces % 2 == 0 {pieces += 1}
+    let point = |t : f32| [in_from[0]+t*d[0], in_from[1]+t*d[1], in_from[2]+t*d[2]];
+    (0..pieces).filter(|k| k % 2 == 0).map(|k| {
+        (point(k as f32/pieces as f32), point((k+1) as f32/pieces as f32))
+    }).collect()
+}
+
+// ============================================================
+// Report
+// ============================================================
+/// Runs of consecutive residues as (e.g.) "A 1-3 (MET ALA SER)", for the report.
+fn residue_runs(in_residues : &Vec<Residue>) -> Vec<String> {
+    let mut runs : Vec<Vec<&Residue>> = Vec::new();
+    for residue in in_residues {
+        let extends = runs.last().map_or(false, |run| {
+            let last = run[run.len()-1];
+            last.chain() == residue.chain() && *last.number()+1 == *residue.number()
+        });
+        if extends {
+            runs.last_mut().unwrap().push(residue);
+        } else {
+            runs.push(vec![residue]);
+        }
+    }
+    runs.iter().map(|run| {
+        let names : Vec<&str> = run.iter().map(|r| r.name()).collect();
+        let (first, last) = (run[0], run[run.len()-1]);
+        if run.len() == 1 {
+            format!("{} {} ({})", first.chain(), first.number(), names.join(" "))
+        } else {
+            format!("{} {}-{} ({})", first.chain(), first.number(), last.number(), names.join(" "))
+        }
+    }).collect()
+}
+
+/// A one-line summary, or None if nothing's missing.
+pub fn summary(in_missing : &MissingResidues, in_gaps : &Vec<Gap>) -> Option<String> {
+    if in_missing.is_empty() && in_gaps.is_empty() {return None}
+    Some(format!(
+        "{} missing residues, {} residues with missing atoms and {} backbone gaps (see the terminal)",
+        in_missing._residues.len(), in_missing._atoms.len(), in_gaps.len()
+    ))
+}
+
+/// Everything missing, a line at a time.
+pub fn report(in_molecule : &Molecule, in_missing : &MissingResidues, in_gaps : &Vec<Gap>) -> String {
+    let mut text = String::new();
+    if !in_missing._residues.is_empty() {
+        text.push_str("Missing residues:\n");
+        for run in residue_runs(&in_missing._residues) {
+            text.push_str(&format!("  chain {}\n", run));
+        }
+    }
+    if !in_missing._atoms.is_empty() {
+        text.push_str("Residues with missing atoms:\n");
+        for &(ref residue, ref names) in &in_missing._atoms {
+            text.push_str(&format!(
+                "  chain {} {} {}: {}\n",
+                residue.chain(), residue.name(), residue.number(), names.join(" ")
+            ));
+        }
+    }
+    if !in_gaps.is_empty() {
+        text.push_str("Backbone gaps:\n");
+        for gap in in_gaps {
+            let before = in_molecule.atoms()[gap._atoms[0]].residue().as_ref().unwrap();
+            let after = in_molecule.atoms()[gap._atoms[1]].residue().as_ref().unwrap();
+            text.push_str(&if gap._skipped > 0 {
+                format!("  chain {} between {} and {} ({} residues)\n",
+                        before.chain(), before.number(), after.number(), gap._skipped)
+            } else {
+                format!("  chain {} between {} and {} (too far apart to be bonded)\n",
+                        before.chain(), before.number(), after.number())
+            });
+        }
+    }
+    text
+}
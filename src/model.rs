@@ -1,5 +1,7 @@
 extern crate glium;
 
+use std::cell::{Ref, RefCell};
+
 use vertex::Vertex;
 use program;
 
@@ -9,49 +11,179 @@ use program;
 /// The mesh of a single object (a triangle, a sphere, a goove...)
 pub struct Model<'a> {
     /// The vertices of the triangles out of which the mesh is made
-    _vertices        : Vec<Vertex>,
+    _vertices            : Vec<Vertex>,
     /// The order in which the vertices should be drawn.
-    _index_type      : glium::index::PrimitiveType,
-    _indices         : Vec<u16>,
-    _program         : &'a glium::Program,
-    _vertex_buffer   : glium::VertexBuffer<Vertex>,
-    _index_buffer    : glium::index::IndexBuffer<u16>,
+    _index_type          : glium::index::PrimitiveType,
+    _indices             : Vec<u16>,
+    _program             : &'a RefCell<glium::Program>,
+    /// The program used when drawing the mesh as part of the translucent pass.
+    _translucent_program : &'a RefCell<glium::Program>,
+    /// The program used when drawing the mesh into the picking ID buffer.
+    _picking_program     : &'a RefCell<glium::Program>,
+    _vertex_buffer       : glium::VertexBuffer<Vertex>,
+    _index_buffer        : glium::index::IndexBuffer<u16>,
 }
 
 impl<'a> Model<'a> {
     pub fn new (
-        in_display    : &glium::backend::glutin_backend::GlutinFacade,
-        in_vertices   : &Vec<Vertex>,
-        in_index_type : &glium::index::PrimitiveType,
-        in_indices    : &Vec<u16>,
-        in_program    : &'a glium::Program,
+        in_display             : &glium::backend::glutin_backend::GlutinFacade,
+        in_vertices            : Vec<Vertex>,
+        in_index_type          : &glium::index::PrimitiveType,
+        in_indices             : Vec<u16>,
+        in_program             : &'a RefCell<glium::Program>,
+        in_translucent_program : &'a RefCell<glium::Program>,
+        in_picking_program     : &'a RefCell<glium::Program>,
     ) -> Model<'a> {
+        // the vertices and indices are moved in, not copied, since big meshes (the
+        // Miller planes, say) are made just to be handed over
+        let vertex_buffer = glium::VertexBuffer::new(in_display, &in_vertices).unwrap();
+        let index_buffer = glium::index::IndexBuffer::new(in_display, *in_index_type, &in_indices).unwrap();
         Model {
-            _vertices      : in_vertices.to_owned(),
-            _index_type    : in_index_type.to_owned(),
-            _indices       : in_indices.to_owned(),
-            _vertex_buffer : glium::VertexBuffer::new(in_display, in_vertices).unwrap(),
-            _index_buffer  : glium::index::IndexBuffer::new (
-                in_display,
-                *in_index_type,
-                in_indices,
-            ).unwrap(),
-            _program       : in_program,
+            _vertices            : in_vertices,
+            _index_type          : in_index_type.to_owned(),
+            _indices             : in_indices,
+            _vertex_buffer       : vertex_buffer,
+            _index_buffer        : index_buffer,
+            _program             : in_program,
+            _translucent_program : in_translucent_program,
+            _picking_program     : in_picking_program,
         }
     }
 
+    pub fn vertices(&self) -> &Vec<Vertex> {&self._vertices}
+    pub fn index_type(&self) -> &glium::index::PrimitiveType {&self._index_type}
+    pub fn indices(&self) -> &Vec<u16> {&self._indices}
     pub fn vertex_buffer(&self) -> &glium::VertexBuffer<Vertex> {&self._vertex_buffer}
     pub fn index_buffer(&self) -> &glium::index::IndexBuffer<u16> {&self._index_buffer}
-    pub fn program(&self) -> &glium::Program {&self._program}
+    pub fn program(&self) -> Ref<glium::Program> {self._program.borrow()}
+    pub fn translucent_program(&self) -> Ref<glium::Program> {self._translucent_program.borrow()}
+    pub fn picking_program(&self) -> Ref<glium::Program> {self._picking_program.borrow()}
+
+    /// How many triangles a copy of the model draws (none for lines).
+    pub fn triangles(&self) -> usize {
+        match self._index_type {
+            glium::index::PrimitiveType::TrianglesList => self._index_buffer.len()/3,
+            glium::index::PrimitiveType::TriangleStrip => self._index_buffer.len().saturating_sub(2),
+            _ => 0,
+        }
+    }
+
+    /// How much GPU memory the vertices and indices take.
+    pub fn bytes(&self) -> usize {self._vertex_buffer.get_size() + self._index_buffer.get_size()}
+}
+
+/// How many sides bonds have, and how many times the icosahedron is split up for
+/// thermal ellipsoids, normally and in the finer models drawn while the view is
+/// still (see refine.rs).
+const CYLINDER_SIDES : usize = 12;
+const FINE_CYLINDER_SIDES : usize = 36;
+const SPHERE_SUBDIVISIONS : usize = 2;
+const FINE_SPHERE_SUBDIVISIONS : usize = 3;
+
+/// An open tube of radius 1 from z = -1 to z = 1, for bonds.
+fn cylinder_mesh(in_sides : &usize) -> (Vec<Vertex>, Vec<u16>) {
+    let sides = *in_sides;
+    let mut cylinder_vertices = Vec::with_capacity(2*sides);
+    let mut cylinder_indices = Vec::with_capacity(6*sides);
+    for k in 0..sides {
+        let angle = 2.0*::std::f32::consts::PI*k as f32/sides as f32;
+        let (x, y) = (angle.cos(), angle.sin());
+        cylinder_vertices.push(Vertex::new([x, y, -1.0], [x, y, 0.0]));
+        cylinder_vertices.push(Vertex::new([x, y,  1.0], [x, y, 0.0]));
+        let (bottom, top) = (2*k as u16, 2*k as u16+1);
+        let (next_bottom, next_top) = (2*((k+1)%sides) as u16, 2*((k+1)%sides) as u16+1);
+        cylinder_indices.extend_from_slice(&[bottom, next_bottom, top, top, next_bottom, next_top]);
+    }
+    (cylinder_vertices, cylinder_indices)
+}
+
+/// A closed cone of radius 1 at its base, z = -1, to a point at z = 1, for the heads
+/// of arrows (see arrows.rs), placed as bonds are.
+fn cone_mesh(in_sides : &usize) -> (Vec<Vertex>, Vec<u16>) {
+    let sides = *in_sides;
+    let mut cone_vertices = Vec::with_capacity(3*sides+1);
+    let mut cone_indices = Vec::with_capacity(6*sides);
+    // the slope's normal: out by 1 and up by 1/2, as the radius falls by 1 over 2
+    let slope = 1.0/1.25f32.sqrt();
+    for k in 0..sides {
+        let angle = 2.0*::std::f32::consts::PI*k as f32/sides as f32;
+        let (x, y) = (angle.cos(), angle.sin());
+        let normal = [x*slope, y*slope, 0.5*slope];
+        cone_vertices.push(Vertex::new([x, y, -1.0], normal));
+        cone_vertices.push(Vertex::new([0.0, 0.0, 1.0], normal));
+        cone_vertices.push(Vertex::new([x, y, -1.0], [0.0, 0.0, -1.0]));
+    }
+    cone_vertices.push(Vertex::new([0.0, 0.0, -1.0], [0.0, 0.0, -1.0]));
+    let centre = (3*sides) as u16;
+    for k in 0..sides {
+        let (rim, tip, base) = (3*k as u16, 3*k as u16+1, 3*k as u16+2);
+        let (next_rim, next_base) = (3*((k+1)%sides) as u16, 3*((k+1)%sides) as u16+2);
+        cone_indices.extend_from_slice(&[rim, next_rim, tip, base, centre, next_base]);
+    }
+    (cone_vertices, cone_indices)
+}
+
+/// A unit sphere made of triangles, by splitting each face of an icosahedron into
+/// four over and over, for stretching into thermal ellipsoids.
+fn geodesic_sphere(in_subdivisions : &usize) -> (Vec<Vertex>, Vec<u16>) {
+    let t = (1.0+5.0f32.sqrt())/2.0;
+    let mut sphere_points : Vec<[f32;3]> = vec![
+        [-1.0, t, 0.0], [1.0, t, 0.0], [-1.0, -t, 0.0], [1.0, -t, 0.0],
+        [0.0, -1.0, t], [0.0, 1.0, t], [0.0, -1.0, -t], [0.0, 1.0, -t],
+        [t, 0.0, -1.0], [t, 0.0, 1.0], [-t, 0.0, -1.0], [-t, 0.0, 1.0],
+    ];
+    let mut sphere_faces : Vec<[u16;3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+    for _ in 0..*in_subdivisions {
+        let mut midpoints : Vec<([u16;2], u16)> = Vec::new();
+        let mut faces = Vec::with_capacity(4*sphere_faces.len());
+        for face in &sphere_faces {
+            let mut middle = [0u16;3];
+            for k in 0..3 {
+                let (a, b) = (face[k].min(face[(k+1)%3]), face[k].max(face[(k+1)%3]));
+                middle[k] = match midpoints.iter().find(|&&(edge, _)| edge == [a, b]) {
+                    Some(&(_, index)) => index,
+                    None => {
+                        let (p, q) = (sphere_points[a as usize], sphere_points[b as usize]);
+                        sphere_points.push([(p[0]+q[0])/2.0, (p[1]+q[1])/2.0, (p[2]+q[2])/2.0]);
+                        midpoints.push(([a, b], sphere_points.len() as u16 - 1));
+                        sphere_points.len() as u16 - 1
+                    },
+                };
+            }
+            faces.push([face[0], middle[0], middle[2]]);
+            faces.push([face[1], middle[1], middle[0]]);
+            faces.push([face[2], middle[2], middle[1]]);
+            faces.push(middle);
+        }
+        sphere_faces = faces;
+    }
+    let sphere_vertices : Vec<Vertex> = sphere_points.iter().map(|p| {
+        let length = (p[0]*p[0]+p[1]*p[1]+p[2]*p[2]).sqrt();
+        let unit = [p[0]/length, p[1]/length, p[2]/length];
+        Vertex::new(unit, unit)
+    }).collect();
+    let sphere_indices : Vec<u16> = sphere_faces.iter().flat_map(|face| face.iter().cloned()).collect();
+    (sphere_vertices, sphere_indices)
 }
 
 pub struct DefaultModels<'a> {
-    _triangle    : Model<'a>,
-    _square      : Model<'a>,
-    _tetrahedron : Model<'a>,
-    _cube        : Model<'a>,
-    _icosahedron : Model<'a>,
-    _sphere      : Model<'a>,
+    _triangle       : Model<'a>,
+    _square         : Model<'a>,
+    _tetrahedron    : Model<'a>,
+    _cube           : Model<'a>,
+    _icosahedron    : Model<'a>,
+    _sphere         : Model<'a>,
+    _cylinder       : Model<'a>,
+    _ellipsoid      : Model<'a>,
+    _fine_cylinder  : Model<'a>,
+    _fine_ellipsoid : Model<'a>,
+    _cone           : Model<'a>,
+    _cell_box       : Model<'a>,
 }
 
 impl<'a> DefaultModels<'a> {
@@ -62,20 +194,28 @@ impl<'a> DefaultModels<'a> {
         let sr_1_2 = 1.0/2.0f32.sqrt();    // for tetrahedron
         let phi = 2.0/(1.0+5.0f32.sqrt()); // for icosahedron
 
+        let (cylinder_vertices, cylinder_indices) = cylinder_mesh(&CYLINDER_SIDES);
+        let (fine_cylinder_vertices, fine_cylinder_indices) = cylinder_mesh(&FINE_CYLINDER_SIDES);
+        let (sphere_vertices, sphere_indices) = geodesic_sphere(&SPHERE_SUBDIVISIONS);
+        let (fine_sphere_vertices, fine_sphere_indices) = geodesic_sphere(&FINE_SPHERE_SUBDIVISIONS);
+        let (cone_vertices, cone_indices) = cone_mesh(&FINE_CYLINDER_SIDES);
+
         DefaultModels {
             // ==============================
             // triangle
             // ==============================
             _triangle : Model::new(
                 in_display,
-                &vec! [
+                vec! [
                     Vertex::new([-1.0, -1.0, 0.0], [0.0, 0.0, 1.0]),
                     Vertex::new([-1.0,  1.0, 0.0], [0.0, 0.0, 1.0]),
                     Vertex::new([ 1.0,  0.0, 0.0], [0.0, 0.0, 1.0]),
                 ],
                 &glium::index::PrimitiveType::TriangleStrip,
-                &vec![0, 1, 2u16],
+                vec![0, 1, 2u16],
                 in_default_programs.polyhedron(),
+                in_default_programs.polyhedron_translucent(),
+                in_default_programs.polyhedron_picking(),
             ),
 
             // ==============================
@@ -83,15 +223,17 @@ impl<'a> DefaultModels<'a> {
             // ==============================
             _square : Model::new(
                 in_display,
-                &vec! [
+                vec! [
                     Vertex::new([-1.0, -1.0, 0.0], [0.0, 0.0, 1.0]),
                     Vertex::new([ 1.0, -1.0, 0.0], [0.0, 0.0, 1.0]),
                     Vertex::new([-1.0,  1.0, 0.0], [0.0, 0.0, 1.0]),
                     Vertex::new([ 1.0,  1.0, 0.0], [0.0, 0.0, 1.0]),
                 ],
                 &glium::index::PrimitiveType::TriangleStrip,
-                &vec![0, 2, 1, 3u16],
+                vec![0, 2, 1, 3u16],
                 in_default_programs.polyhedron(),
+                in_default_programs.polyhedron_translucent(),
+                in_default_programs.polyhedron_picking(),
             ),
 
             // ==============================
@@ -99,15 +241,17 @@ impl<'a> DefaultModels<'a> {
             // ==============================
             _tetrahedron : Model::new(
                 in_display,
-                &vec![
+                vec![
                     Vertex::new([-1.0,  0.0, -sr_1_2],[-1.0,  0.0, -sr_1_2]),
                     Vertex::new([ 1.0,  0.0, -sr_1_2],[ 1.0,  0.0, -sr_1_2]),
                     Vertex::new([ 0.0, -1.0,  sr_1_2],[ 0.0, -1.0,  sr_1_2]),
                     Vertex::new([ 0.0,  1.0,  sr_1_2],[ 0.0,  1.0,  sr_1_2]),
                 ],
                 &glium::index::PrimitiveType::TriangleStrip,
-                &vec![0, 1, 3, 2, 0, 1u16],
+                vec![0, 1, 3, 2, 0, 1u16],
                 in_default_programs.polyhedron(),
+                in_default_programs.polyhedron_translucent(),
+                in_default_programs.polyhedron_picking(),
             ),
 
             // ==============================
@@ -118,7 +262,7 @@ impl<'a> DefaultModels<'a> {
             // n.b. uses TrianglesList not TriangleStrip, because triangle strips don't do corners.
             _cube : Model::new(
                 in_display,
-                &vec![
+                vec![
                     Vertex::new([-1.0, -1.0, -1.0],[-1.0, -1.0, -1.0]),
                     Vertex::new([ 1.0, -1.0, -1.0],[ 1.0, -1.0, -1.0]),
                     Vertex::new([-1.0,  1.0, -1.0],[-1.0,  1.0, -1.0]),
@@ -129,7 +273,7 @@ impl<'a> DefaultModels<'a> {
                     Vertex::new([ 1.0,  1.0,  1.0],[ 1.0,  1.0,  1.0])
                 ],
                 &glium::index::PrimitiveType::TrianglesList,
-                &vec![
+                vec![
                     0, 2, 1, 3, 1, 2,   // the -z face
                     2, 6, 3, 7, 3, 6,   // the  y face
                     4, 5, 6, 7, 6, 5,   // the  z face
@@ -138,6 +282,8 @@ impl<'a> DefaultModels<'a> {
                     0, 4, 2, 6, 2, 4u16 // the -x face
                 ],
                 in_default_programs.polyhedron(),
+                in_default_programs.polyhedron_translucent(),
+                in_default_programs.polyhedron_picking(),
             ),
 
             // ==============================
@@ -145,7 +291,7 @@ impl<'a> DefaultModels<'a> {
             // ==============================
             _icosahedron : Model::new(
                 in_display,
-                &vec![
+                vec![
                     Vertex::new([ 0.0,  1.0,  phi],[ 0.0,  1.0,  phi]),
                     Vertex::new([ 0.0, -1.0,  phi],[ 0.0, -1.0,  phi]),
                     Vertex::new([ 0.0,  1.0, -phi],[ 0.0,  1.0, -phi]),
@@ -160,7 +306,7 @@ impl<'a> DefaultModels<'a> {
                     Vertex::new([-1.0, -phi,  0.0],[-1.0, -phi,  0.0]),
                 ],
                 &glium::index::PrimitiveType::TrianglesList,
-                &vec![
+                vec![
                     0, 8, 2,
                     0, 2, 9,
                     1, 3, 10,
@@ -183,6 +329,8 @@ impl<'a> DefaultModels<'a> {
                     3, 11, 7u16
                 ],
                 in_default_programs.polyhedron(),
+                in_default_programs.polyhedron_translucent(),
+                in_default_programs.polyhedron_picking(),
             ),
 
             // ==============================
@@ -190,15 +338,100 @@ impl<'a> DefaultModels<'a> {
             // ==============================
             _sphere : Model::new(
                 in_display,
-                &vec! [
+                vec! [
                     Vertex::new([-1.0, -1.0, 0.0], [-1.0, -1.0, 0.0]),
                     Vertex::new([ 1.0, -1.0, 0.0], [ 1.0, -1.0, 0.0]),
                     Vertex::new([-1.0,  1.0, 0.0], [-1.0,  1.0, 0.0]),
                     Vertex::new([ 1.0,  1.0, 0.0], [ 1.0,  1.0, 0.0]),
                 ],
                 &glium::index::PrimitiveType::TriangleStrip,
-                &vec![0, 2, 1, 3u16],
+                vec![0, 2, 1, 3u16],
                 in_default_programs.sphere(),
+                in_default_programs.sphere_translucent(),
+                in_default_programs.sphere_picking(),
+            ),
+
+            // ==============================
+            // cylinder
+            // ==============================
+            _cylinder : Model::new(
+                in_display,
+                cylinder_vertices,
+                &glium::index::PrimitiveType::TrianglesList,
+                cylinder_indices,
+                in_default_programs.polyhedron(),
+                in_default_programs.polyhedron_translucent(),
+                in_default_programs.polyhedron_picking(),
+            ),
+
+            // ==============================
+            // ellipsoid
+            // ==============================
+            _ellipsoid : Model::new(
+                in_display,
+                sphere_vertices,
+                &glium::index::PrimitiveType::TrianglesList,
+                sphere_indices,
+                in_default_programs.ellipsoid(),
+                in_default_programs.ellipsoid_translucent(),
+                in_default_programs.ellipsoid_picking(),
+            ),
+
+            // ==============================
+            // finer cylinder and ellipsoid
+            // ==============================
+            _fine_cylinder : Model::new(
+                in_display,
+                fine_cylinder_vertices,
+                &glium::index::PrimitiveType::TrianglesList,
+                fine_cylinder_indices,
+                in_default_programs.polyhedron(),
+                in_default_programs.polyhedron_translucent(),
+                in_default_programs.polyhedron_picking(),
+            ),
+            _fine_ellipsoid : Model::new(
+                in_display,
+                fine_sphere_vertices,
+                &glium::index::PrimitiveType::TrianglesList,
+                fine_sphere_indices,
+                in_default_programs.ellipsoid(),
+                in_default_programs.ellipsoid_translucent(),
+                in_default_programs.ellipsoid_picking(),
+            ),
+
+            // ==============================
+            // cone
+            // ==============================
+            // as fine as the fine cylinder always, since there are few arrows
+            _cone : Model::new(
+                in_display,
+                cone_vertices,
+                &glium::index::PrimitiveType::TrianglesList,
+                cone_indices,
+                in_default_programs.polyhedron(),
+                in_default_programs.polyhedron_translucent(),
+                in_default_programs.polyhedron_picking(),
+            ),
+
+            // ==============================
+            // cell box
+            // ==============================
+            // the edges of the unit cube, which a cell's matrix makes the cell's edges
+            _cell_box : Model::new(
+                in_display,
+                (0..8).map(|k| Vertex::new(
+                    [(k & 1) as f32, ((k >> 1) & 1) as f32, ((k >> 2) & 1) as f32],
+                    [0.0, 0.0, 0.0],
+                )).collect(),
+                &glium::index::PrimitiveType::LinesList,
+                vec![
+                    0, 1, 2, 3, 4, 5, 6, 7, // along a
+                    0, 2, 1, 3, 4, 6, 5, 7, // along b
+                    0, 4, 1, 5, 2, 6, 3, 7, // along c
+                ],
+                in_default_programs.line(),
+                in_default_programs.line_translucent(),
+                in_default_programs.line_picking(),
             ),
         }
     }
@@ -215,4 +448,17 @@ impl<'a> DefaultModels<'a> {
     pub fn icosahedron(&self) -> &Model {&self._icosahedron}
     #[allow(dead_code)]
     pub fn sphere(&self) -> &Model {&self._sphere}
+    pub fn cylinder(&self) -> &Model {&self._cylinder}
+    pub fn ellipsoid(&self) -> &Model {&self._ellipsoid}
+    pub fn fine_cylinder(&self) -> &Model {&self._fine_cylinder}
+    pub fn fine_ellipsoid(&self) -> &Model {&self._fine_ellipsoid}
+    pub fn cone(&self) -> &Model {&self._cone}
+    pub fn cell_box(&self) -> &Model {&self._cell_box}
+
+    /// How much GPU memory every model takes.
+    pub fn bytes(&self) -> usize {
+        [&self._triangle, &self._square, &self._tetrahedron, &self._cube, &self._icosahedron, &self._sphere,
+         &self._cylinder, &self._ellipsoid, &self._fine_cylinder, &self._fine_ellipsoid, &self._cone, &self._cell_box]
+            .iter().map(|model| model.bytes()).sum()
+    }
 }
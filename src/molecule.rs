@@ -1,6 +1,12 @@
 use species::Species;
 use atom::Atom;
 use camera::Camera;
+use matrix::Matrix;
+use cell::Cell;
+use selection::Selection;
+use missing_residues::MissingResidues;
+use assembly::Assembly;
+use packing::Crystal;
 
 // ============================================================
 // Molecule
@@ -9,10 +15,33 @@ use camera::Camera;
 /// The molecule. May also be a cluster, crystal motif,...
 pub struct Molecule<'a> {
     _atoms : Vec<Atom<'a>>,
+    /// The periodic cell, for crystals.
+    _cell  : Option<Cell>,
+    /// Selections saved under a name, e.g. "ligand", in the order they were made.
+    _named_selections : Vec<(String, Selection)>,
+    /// What the file says is missing, for biomolecules.
+    _missing_residues : MissingResidues,
+    /// The biological assembly, if the file gives one.
+    _assembly         : Assembly,
+    /// The crystal the structure was solved in, if the file gives it. Unlike the
+    /// cell, it isn't used for periodic distances.
+    _crystal          : Option<Crystal>,
+    /// The molecule's dipole moment, if the file gives it (see arrows.rs).
+    _dipole           : Option<[f32;3]>,
 }
 
 impl<'a> Molecule<'a> {
-    pub fn new() -> Molecule<'a> {Molecule{_atoms : Vec::new()}}
+    pub fn new() -> Molecule<'a> {
+        Molecule {
+            _atoms            : Vec::new(),
+            _cell             : None,
+            _named_selections : Vec::new(),
+            _missing_residues : MissingResidues::new(),
+            _assembly         : Assembly::new(),
+            _crystal          : None,
+            _dipole           : None,
+        }
+    }
 
     pub fn add_atom(
         &mut self,
@@ -20,12 +49,165 @@ impl<'a> Molecule<'a> {
         in_position : &[f32;3],
     ) {self._atoms.push(Atom::new(in_species, in_position))}
 
-    pub fn atoms(&self) -> &Vec<Atom> {&self._atoms}
+    /// Deletes an atom, and gives it back. The atoms after it move down one, and the
+    /// named selections are renumbered to match, so anything else indexing atoms
+    /// (bonds, the selection, a trajectory) needs the same done to it.
+    pub fn remove_atom(&mut self, in_atom : &usize) -> Atom<'a> {
+        for &mut (_, ref mut selection) in &mut self._named_selections {
+            *selection = selection.without_atom(in_atom);
+        }
+        self._atoms.remove(*in_atom)
+    }
+
+    /// Puts an atom in at an index, moving the atoms from there up one; the
+    /// opposite of remove_atom().
+    pub fn insert_atom(&mut self, in_index : &usize, in_atom : &Atom<'a>) {
+        for &mut (_, ref mut selection) in &mut self._named_selections {
+            *selection = selection.with_atom_inserted(in_index);
+        }
+        self._atoms.insert(*in_index, in_atom.to_owned())
+    }
+
+    /// Moves one atom, along with its model matrix.
+    pub fn move_atom(&mut self, in_atom : &usize, in_position : &[f32;3]) {
+        self._atoms[*in_atom].set_position(in_position)
+    }
+
+    pub fn atoms(&self) -> &Vec<Atom<'a>> {&self._atoms}
+    pub fn atoms_mut(&mut self) -> &mut Vec<Atom<'a>> {&mut self._atoms}
+    pub fn cell(&self) -> &Option<Cell> {&self._cell}
+
+    pub fn set_cell(&mut self, in_cell : &Cell) {self._cell = Some(in_cell.to_owned())}
+
+    pub fn missing_residues(&self) -> &MissingResidues {&self._missing_residues}
+    pub fn set_missing_residues(&mut self, in_missing : &MissingResidues) {
+        self._missing_residues = in_missing.to_owned()
+    }
+
+    pub fn assembly(&self) -> &Assembly {&self._assembly}
+    pub fn set_assembly(&mut self, in_assembly : &Assembly) {self._assembly = in_assembly.to_owned()}
+    pub fn crystal(&self) -> &Option<Crystal> {&self._crystal}
+    pub fn set_crystal(&mut self, in_crystal : &Crystal) {self._crystal = Some(in_crystal.to_owned())}
+    pub fn dipole(&self) -> &Option<[f32;3]> {&self._dipole}
+    pub fn set_dipole(&mut self, in_dipole : &[f32;3]) {self._dipole = Some(in_dipole.to_owned())}
+
+    /// The IDs of the chains the atoms are in, in the order they come in the file.
+    pub fn chains(&self) -> Vec<String> {
+        let mut chains : Vec<String> = Vec::new();
+        for residue in self._atoms.iter().filter_map(|atom| atom.residue().as_ref()) {
+            if !chains.iter().any(|chain| chain == residue.chain()) {chains.push(residue.chain().to_owned())}
+        }
+        chains
+    }
+
+    pub fn named_selections(&self) -> &Vec<(String, Selection)> {&self._named_selections}
+
+    pub fn named_selection(&self, in_name : &str) -> Option<&Selection> {
+        self._named_selections.iter().find(|&&(ref name, _)| name == in_name).map(|&(_, ref s)| s)
+    }
+
+    /// Saves a selection under a name, replacing any selection already called that.
+    pub fn set_named_selection(&mut self, in_name : &str, in_selection : &Selection) {
+        match self._named_selections.iter().position(|&(ref name, _)| name == in_name) {
+            Some(i) => self._named_selections[i].1 = in_selection.to_owned(),
+            None => self._named_selections.push((in_name.to_owned(), in_selection.to_owned())),
+        }
+    }
+
+    /// Moves every atom, e.g. to show another frame of a trajectory.
+    /// There must be exactly one position per atom.
+    pub fn set_positions(&mut self, in_positions : &Vec<[f32;3]>) {
+        assert_eq!(in_positions.len(), self._atoms.len());
+        for (atom, position) in self._atoms.iter_mut().zip(in_positions.iter()) {
+            atom.set_position(position);
+        }
+    }
+
+    /// Colours every atom according to a value, from blue (the smallest value)
+    /// through white to red (the largest), and remembers the values as the atoms'
+    /// property. There must be exactly one value per atom.
+    pub fn colour_by_values(&mut self, in_values : &Vec<f32>) {
+        assert_eq!(in_values.len(), self._atoms.len());
+        let min = in_values.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = in_values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        for (atom, value) in self._atoms.iter_mut().zip(in_values.iter()) {
+            let t = if max > min {(value-min)/(max-min)} else {0.0};
+            atom.set_colour(&value_colour(&t));
+            atom.set_property(&Some(*value));
+        }
+    }
+
+    /// Goes back to colouring atoms by species.
+    pub fn reset_colours(&mut self) {
+        for atom in &mut self._atoms {
+            atom.reset_colour();
+            atom.set_property(&None);
+        }
+    }
+
+    pub fn set_opacity(&mut self, in_opacity : &f32) {
+        for atom in &mut self._atoms {
+            atom.set_opacity(in_opacity);
+        }
+    }
+
+    /// The centre and radius of a sphere containing every atom, including the radius
+    /// it's drawn with (e.g. from radii::AtomRadii). This is the middle of the
+    /// bounding box, which needn't be the smallest sphere.
+    pub fn bounding_sphere<R>(&self, in_radius : R) -> ([f32;3], f32) where R : Fn(&Atom) -> f32 {
+        if self._atoms.is_empty() {return ([0.0;3], 0.0)}
+        let mut min = [f32::INFINITY;3];
+        let mut max = [f32::NEG_INFINITY;3];
+        for atom in &self._atoms {
+            for k in 0..3 {
+                min[k] = min[k].min(atom.position()[k]);
+                max[k] = max[k].max(atom.position()[k]);
+            }
+        }
+        let centre = [(min[0]+max[0])/2.0, (min[1]+max[1])/2.0, (min[2]+max[2])/2.0];
+        let mut radius = 0.0f32;
+        for atom in &self._atoms {
+            let p = atom.position();
+            let distance = ((p[0]-centre[0]).powi(2)+(p[1]-centre[1]).powi(2)+(p[2]-centre[2]).powi(2)).sqrt();
+            radius = radius.max(distance + in_radius(atom));
+        }
+        (centre, radius)
+    }
 
+    /// Turns every atom's quad to face the camera. The rotation is the same for
+    /// all of them, so it's found once, which counts with a million atoms.
     pub fn rotate_atoms_against_camera(&mut self, in_camera : &Camera) {
+        let rotation_matrix = billboard_matrix(in_camera);
         for atom in &mut self._atoms {
-            atom.rotate_against_camera(in_camera);
+            atom.rotate_against(&rotation_matrix);
+        }
+    }
+
+    /// As rotate_atoms_against_camera(), but only for some of the atoms, those drawn
+    /// apiece when the rest are in a batch (whose quads its shader turns).
+    pub fn rotate_some_against_camera(&mut self, in_camera : &Camera, in_atoms : &[usize]) {
+        let rotation_matrix = billboard_matrix(in_camera);
+        for &i in in_atoms {
+            self._atoms[i].rotate_against(&rotation_matrix);
         }
     }
 }
 
+/// The camera's rotation, undone, which turns the atoms' quads to face it: each
+/// atom's model matrix has it, and batches of atoms are given it (see batch.rs).
+pub fn billboard_matrix(in_camera : &Camera) -> Matrix {
+    let mut quaternion = in_camera.quaternion().to_owned();
+    quaternion.invert();
+    quaternion.rotation_matrix()
+}
+
+/// The colour for a value scaled to between 0 and 1: blue for 0, through white,
+/// to red for 1.
+pub fn value_colour(in_t : &f32) -> [f32;3] {
+    let t = *in_t;
+    if t < 0.5 {
+        [2.0*t, 2.0*t, 1.0]
+    } else {
+        [1.0, 2.0-2.0*t, 2.0-2.0*t]
+    }
+}
@@ -0,0 +1,328 @@
+extern crate glium;
+
+use glium::glutin::{ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode};
+
+// ============================================================
+// Modifiers
+// ============================================================
+/// Which modifier keys are held down. glutin doesn't report this with mouse
+/// events, so it's tracked from the keyboard events instead.
+#[derive(Copy, Clone, PartialEq)]
+pub struct Modifiers {
+    _shift : bool,
+    _ctrl  : bool,
+    _alt   : bool,
+}
+
+impl Modifiers {
+    pub fn new(in_shift : &bool, in_ctrl : &bool, in_alt : &bool) -> Modifiers {
+        Modifiers {
+            _shift : in_shift.to_owned(),
+            _ctrl  : in_ctrl.to_owned(),
+            _alt   : in_alt.to_owned(),
+        }
+    }
+
+    pub fn none() -> Modifiers {Modifiers::new(&false, &false, &false)}
+
+    pub fn shift(&self) -> bool {self._shift}
+    pub fn ctrl(&self) -> bool {self._ctrl}
+    pub fn alt(&self) -> bool {self._alt}
+
+    /// e.g. "shift+ctrl+", in the same form that MouseBindings::parse() reads.
+    fn prefix(&self) -> String {
+        let mut prefix = String::new();
+        if self._shift {prefix.push_str("shift+")}
+        if self._ctrl {prefix.push_str("ctrl+")}
+        if self._alt {prefix.push_str("alt+")}
+        prefix
+    }
+}
+
+// ============================================================
+// Mouse Bindings
+// ============================================================
+/// What dragging the mouse does.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum MouseAction {
+    /// Arcball rotation.
+    Rotate,
+    /// Rotation about the view axis by dragging left/right.
+    Spin,
+    /// Moving the focus parallel to the screen.
+    Pan,
+    Zoom,
+}
+
+impl MouseAction {
+    pub fn from_name(in_name : &str) -> Option<MouseAction> {
+        match in_name {
+            "rotate" => Some(MouseAction::Rotate),
+            "spin"   => Some(MouseAction::Spin),
+            "pan"    => Some(MouseAction::Pan),
+            "zoom"   => Some(MouseAction::Zoom),
+            _        => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            MouseAction::Rotate => "rotate",
+            MouseAction::Spin   => "spin",
+            MouseAction::Pan    => "pan",
+            MouseAction::Zoom   => "zoom",
+        }
+    }
+}
+
+/// The names of the interaction presets, for `--controls`.
+pub const PRESETS : [&'static str; 4] = ["oxide", "pymol", "vmd", "vesta"];
+
+#[derive(Copy, Clone)]
+struct MouseBinding {
+    _button    : MouseButton,
+    _modifiers : Modifiers,
+    _action    : MouseAction,
+}
+
+/// Maps (mouse button, modifier keys) onto the action a drag performs.
+#[derive(Clone)]
+pub struct MouseBindings {
+    _bindings : Vec<MouseBinding>,
+}
+
+impl MouseBindings {
+    /// No bindings at all, for building up with `bind()`.
+    pub fn new() -> MouseBindings {MouseBindings{_bindings : Vec::new()}}
+
+    /// The "oxide" preset: left-drag rotates, right-drag zooms, and middle-drag
+    /// (or shift+left-drag) pans.
+    pub fn default() -> MouseBindings {MouseBindings::preset("oxide").unwrap()}
+
+    /// Bindings approximating the mouse controls of other viewers, so their users
+    /// feel at home. See PRESETS for the names.
+    pub fn preset(in_name : &str) -> Option<MouseBindings> {
+        let spec = match in_name.to_lowercase().as_str() {
+            "oxide" => "left=rotate, right=zoom, middle=pan, shift+left=pan",
+            "pymol" => "left=rotate, right=zoom, middle=pan, shift+right=spin",
+            "vmd"   => "left=rotate, middle=spin, shift+left=spin, ctrl+left=pan",
+            "vesta" => "left=rotate, right=zoom, middle=pan, shift+left=spin",
+            _       => return None,
+        };
+        Some(MouseBindings::parse(spec).unwrap())
+    }
+
+    /// Parses a comma-separated list of bindings, e.g. "left=rotate, ctrl+right=zoom".
+    /// Buttons are left, right or middle. Modifiers are shift, ctrl and alt.
+    pub fn parse(in_spec : &str) -> Result<MouseBindings, String> {
+        let mut bindings = MouseBindings::new();
+        for item in in_spec.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            let parts : Vec<&str> = item.split('=').map(|s| s.trim()).collect();
+            if parts.len() != 2 {
+                return Err(format!("expected <button>=<action>, found \"{}\"", item));
+            }
+            let action = match MouseAction::from_name(&parts[1].to_lowercase()) {
+                Some(action) => action,
+                None => return Err(format!("unknown mouse action \"{}\"", parts[1])),
+            };
+            let mut button = None;
+            let (mut shift, mut ctrl, mut alt) = (false, false, false);
+            for key in parts[0].split('+').map(|s| s.trim().to_lowercase()) {
+                match key.as_str() {
+                    "shift"             => shift = true,
+                    "ctrl" | "control"  => ctrl = true,
+                    "alt"               => alt = true,
+                    "left"              => button = Some(MouseButton::Left),
+                    "right"             => button = Some(MouseButton::Right),
+                    "middle"            => button = Some(MouseButton::Middle),
+                    _ => return Err(format!("unknown mouse button or modifier \"{}\"", key)),
+                }
+            }
+            match button {
+                Some(button) => bindings.bind(&button, &Modifiers::new(&shift, &ctrl, &alt), &action),
+                None => return Err(format!("no mouse button given in \"{}\"", item)),
+            }
+        }
+        Ok(bindings)
+    }
+
+    /// Binds a button and modifier combination, replacing any existing binding for it.
+    pub fn bind(&mut self, in_button : &MouseButton, in_modifiers : &Modifiers, in_action : &MouseAction) {
+        self._bindings.retain(|b| !(b._button == *in_button && b._modifiers == *in_modifiers));
+        self._bindings.push(MouseBinding {
+            _button    : in_button.to_owned(),
+            _modifiers : in_modifiers.to_owned(),
+            _action    : in_action.to_owned(),
+        });
+    }
+
+    /// Every binding as (e.g.) ("shift+left drag", "pan"), for the help screen.
+    pub fn descriptions(&self) -> Vec<(String, &'static str)> {
+        self._bindings.iter().map(|b| {
+            let button = match b._button {
+                MouseButton::Left     => "left".to_owned(),
+                MouseButton::Right    => "right".to_owned(),
+                MouseButton::Middle   => "middle".to_owned(),
+                MouseButton::Other(n) => format!("button {}", n),
+            };
+            (format!("{}{} drag", b._modifiers.prefix(), button), b._action.name())
+        }).collect()
+    }
+
+    pub fn action(&self, in_button : &MouseButton, in_modifiers : &Modifiers) -> Option<MouseAction> {
+        self._bindings.iter()
+            .find(|b| b._button == *in_button && b._modifiers == *in_modifiers)
+            .map(|b| b._action)
+    }
+}
+
+// ============================================================
+// Wheel Mode
+// ============================================================
+/// What the scroll wheel does.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum WheelMode {
+    /// Moves the camera towards/away from the focus.
+    Dolly,
+    /// Changes the field of view, leaving the camera where it is.
+    FieldOfView,
+}
+
+impl WheelMode {
+    pub fn next(&self) -> WheelMode {
+        match *self {
+            WheelMode::Dolly       => WheelMode::FieldOfView,
+            WheelMode::FieldOfView => WheelMode::Dolly,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            WheelMode::Dolly       => "dolly",
+            WheelMode::FieldOfView => "field of view",
+        }
+    }
+}
+
+// ============================================================
+// Drag
+// ============================================================
+/// A single movement of the cursor while a bound button is held.
+#[derive(Copy, Clone)]
+pub struct Drag {
+    _action : MouseAction,
+    _from   : [i32;2],
+    _to     : [i32;2],
+}
+
+impl Drag {
+    pub fn action(&self) -> &MouseAction {&self._action}
+    /// Cursor positions in pixels from the top-left corner of the window.
+    pub fn from(&self) -> &[i32;2] {&self._from}
+    pub fn to(&self) -> &[i32;2] {&self._to}
+
+    /// The distance moved in pixels (x right, y down).
+    pub fn delta(&self) -> [f32;2] {
+        [
+            (self._to[0] - self._from[0]) as f32,
+            (self._to[1] - self._from[1]) as f32,
+        ]
+    }
+}
+
+// ============================================================
+// Mouse
+// ============================================================
+/// Tracks the cursor, buttons and modifiers, and turns drags into actions.
+pub struct Mouse {
+    _bindings  : MouseBindings,
+    _modifiers : Modifiers,
+    _position  : [i32;2],
+    /// The button being dragged with, and what the drag does.
+    _drag      : Option<(MouseButton, MouseAction)>,
+    /// Where the left button went down, while it's held.
+    _press     : Option<[i32;2]>,
+}
+
+/// How far (in pixels, along x or y) the cursor can move between pressing and
+/// releasing the left button for it to count as a click rather than a drag.
+const CLICK_TOLERANCE : i32 = 3;
+
+impl Mouse {
+    pub fn new(in_bindings : &MouseBindings) -> Mouse {
+        Mouse {
+            _bindings  : in_bindings.to_owned(),
+            _modifiers : Modifiers::none(),
+            _position  : [0, 0],
+            _drag      : None,
+            _press     : None,
+        }
+    }
+
+    /// The cursor position in pixels from the top-left corner of the window.
+    pub fn position(&self) -> &[i32;2] {&self._position}
+    pub fn modifiers(&self) -> &Modifiers {&self._modifiers}
+
+    /// Keeps track of the modifier keys. Call with every keyboard event.
+    pub fn key(&mut self, in_state : &ElementState, in_key : &VirtualKeyCode) {
+        let pressed = *in_state == ElementState::Pressed;
+        match *in_key {
+            VirtualKeyCode::LShift   | VirtualKeyCode::RShift   => self._modifiers._shift = pressed,
+            VirtualKeyCode::LControl | VirtualKeyCode::RControl => self._modifiers._ctrl = pressed,
+            VirtualKeyCode::LAlt     | VirtualKeyCode::RAlt     => self._modifiers._alt = pressed,
+            _ => {},
+        }
+    }
+
+    /// Starts or ends drags. Returns the cursor position if this released the left
+    /// button without it having moved (much) since it was pressed: a click.
+    pub fn button(&mut self, in_state : &ElementState, in_button : &MouseButton) -> Option<[i32;2]> {
+        match *in_state {
+            ElementState::Pressed => {
+                if self._drag.is_none() {
+                    self._drag = self._bindings.action(in_button, &self._modifiers)
+                                               .map(|action| (*in_button, action));
+                }
+                if *in_button == MouseButton::Left {
+                    self._press = Some(self._position);
+                }
+                None
+            },
+            ElementState::Released => {
+                if self._drag.map_or(false, |(button, _)| button == *in_button) {
+                    self._drag = None;
+                }
+                if *in_button != MouseButton::Left {return None}
+                let position = self._position;
+                self._press.take().and_then(|press| {
+                    if (position[0]-press[0]).abs() <= CLICK_TOLERANCE
+                    && (position[1]-press[1]).abs() <= CLICK_TOLERANCE {
+                        Some(position)
+                    } else {
+                        None
+                    }
+                })
+            },
+        }
+    }
+
+    /// How far the wheel was scrolled, in notches. Positive is away from the user.
+    /// Touchpads report pixels, which are counted as 20 to a notch.
+    pub fn scrolled(&self, in_delta : &MouseScrollDelta) -> f32 {
+        match *in_delta {
+            MouseScrollDelta::LineDelta(_, y)  => y,
+            MouseScrollDelta::PixelDelta(_, y) => y/20.0,
+        }
+    }
+
+    /// Moves the cursor, returning the resulting drag if a bound button is held.
+    pub fn moved(&mut self, in_x : &i32, in_y : &i32) -> Option<Drag> {
+        let from = self._position;
+        self._position = [*in_x, *in_y];
+        self._drag.map(|(_, action)| Drag {
+            _action : action,
+            _from   : from,
+            _to     : [*in_x, *in_y],
+        })
+    }
+}
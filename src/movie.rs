@@ -0,0 +1,120 @@
+use std::io::prelude::*;
+use std::process::{Child, Command, Stdio};
+use std::time::Instant;
+
+use screenshot;
+
+/// Frames per second, unless --frame-rate says otherwise.
+pub const DEFAULT_FRAME_RATE : f32 = 30.0;
+
+/// File names ending in these are encoded by ffmpeg; any other is numbered images.
+const VIDEO_EXTENSIONS : [&'static str; 5] = [".mp4", ".mkv", ".mov", ".webm", ".avi"];
+
+/// Where a movie's frames go.
+enum Output {
+    /// Numbered images, e.g. movie00000.png, movie00001.png and so on: the file name
+    /// before its extension, and the extension.
+    Images(String, String),
+    /// Raw RGB frames down a pipe to ffmpeg, which encodes them as they come.
+    Ffmpeg(Child),
+}
+
+// ============================================================
+// Movie
+// ============================================================
+/// A movie of the view while the camera path plays (shift+P), or of a trajectory
+/// (render movie), a frame for every frame drawn. The frames are a fixed step of
+/// 1/frame rate apart in the animation, however long each takes to draw, so the
+/// movie plays smoothly even where the viewer stuttered recording it.
+///
+/// The frames are drawn off the screen at a fixed size (see screenshot::render),
+/// without the overlays, so resizing the window doesn't spoil the movie.
+pub struct Movie {
+    _fname      : String,
+    _output     : Output,
+    _size       : (u32, u32),
+    _frame_rate : f32,
+    _frames     : usize,
+    _started    : Instant,
+}
+
+impl Movie {
+    /// Starts a movie of about `in_size` pixels. Video (an .mp4, say) needs ffmpeg on
+    /// the path.
+    pub fn start(in_fname : &str, in_size : &(u32, u32), in_frame_rate : &f32) -> Result<Movie, String> {
+        // even, as the yuv420p which most players need halves both
+        let size = ((in_size.0/2*2).max(2), (in_size.1/2*2).max(2));
+        let lower = in_fname.to_lowercase();
+        let output = if VIDEO_EXTENSIONS.iter().any(|extension| lower.ends_with(extension)) {
+            // raw frames in on its input, video out
+            let arguments = format!("-y -loglevel error -f rawvideo -pix_fmt rgb24 -s {}x{} -r {} -i - -pix_fmt yuv420p",
+                                    size.0, size.1, in_frame_rate);
+            let child = Command::new("ffmpeg")
+                .args(arguments.split_whitespace())
+                .arg(in_fname)
+                .stdin(Stdio::piped())
+                .spawn()
+                .map_err(|e| format!("couldn't start ffmpeg (is it installed?): {}", e))?;
+            Output::Ffmpeg(child)
+        } else {
+            match in_fname.rfind('.').filter(|&dot| !in_fname[dot..].contains('/')) {
+                Some(dot) => Output::Images(in_fname[..dot].to_owned(), in_fname[dot+1..].to_owned()),
+                None => Output::Images(in_fname.to_owned(), "png".to_owned()),
+            }
+        };
+        Ok(Movie {
+            _fname      : in_fname.to_owned(),
+            _output     : output,
+            _size       : size,
+            _frame_rate : *in_frame_rate,
+            _frames     : 0,
+            _started    : Instant::now(),
+        })
+    }
+
+    pub fn fname(&self) -> &str {&self._fname}
+    pub fn size(&self) -> &(u32, u32) {&self._size}
+    pub fn frame_rate(&self) -> &f32 {&self._frame_rate}
+    pub fn started(&self) -> &Instant {&self._started}
+    /// How many frames have been added so far.
+    pub fn frames(&self) -> &usize {&self._frames}
+
+    /// How far into the animation the next frame is, in seconds.
+    pub fn time(&self) -> f32 {self._frames as f32/self._frame_rate}
+
+    /// Adds a frame, given as RGB bytes from the top (as screenshot::render gives
+    /// them), of size().
+    pub fn add_frame(&mut self, in_size : &(usize, usize), in_rgb : &Vec<u8>) -> Result<(), String> {
+        match self._output {
+            Output::Images(ref stem, ref extension) => {
+                screenshot::write_image(&format!("{}{:05}.{}", stem, self._frames, extension), in_size, in_rgb)?;
+            },
+            Output::Ffmpeg(ref mut child) => {
+                let stdin = child.stdin.as_mut().ok_or_else(|| "ffmpeg has stopped taking frames".to_owned())?;
+                stdin.write_all(in_rgb).map_err(|e| format!("couldn't send ffmpeg a frame: {}", e))?;
+            },
+        }
+        self._frames += 1;
+        Ok(())
+    }
+
+    /// Ends the movie (waiting for ffmpeg to finish encoding) and says what was saved.
+    pub fn finish(self) -> Result<String, String> {
+        let seconds = self.time();
+        match self._output {
+            Output::Images(stem, extension) => Ok(format!(
+                "Saved {} frames ({:.1} seconds) to {}00000.{} onwards", self._frames, seconds, stem, extension
+            )),
+            Output::Ffmpeg(mut child) => {
+                // closing its input tells ffmpeg there are no more frames
+                drop(child.stdin.take());
+                let status = child.wait().map_err(|e| format!("couldn't wait for ffmpeg: {}", e))?;
+                if status.success() {
+                    Ok(format!("Saved a {:.1} second movie ({} frames) to {}", seconds, self._frames, self._fname))
+                } else {
+                    Err(format!("ffmpeg couldn't make {} ({})", self._fname, status))
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use glium::Surface;
+
+use std::time::{Duration, Instant};
+
+use text::{self, TextSystem};
+
+// ============================================================
+// Notification
+// ============================================================
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Level {
+    /// e.g. a file loaded. Disappears after a few seconds.
+    Info,
+    /// Something the user should know about, e.g. a suspicious cell.
+    Warning,
+    /// Something failed, e.g. a file couldn't be read.
+    Error,
+}
+
+impl Level {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Level::Info    => "info",
+            Level::Warning => "warning",
+            Level::Error   => "error",
+        }
+    }
+
+    fn colour(&self) -> [f32;4] {
+        match *self {
+            Level::Info    => [1.0, 1.0, 1.0, 1.0],
+            Level::Warning => [1.0, 0.85, 0.3, 1.0],
+            Level::Error   => [1.0, 0.45, 0.4, 1.0],
+        }
+    }
+}
+
+struct Notification {
+    _level : Level,
+    _text  : String,
+    _time  : Instant,
+}
+
+// ============================================================
+// Notifications
+// ============================================================
+/// Messages shown in a bar along the bottom of the window, newest at the bottom.
+/// Info messages expire by themselves. Warnings and errors stay until dismissed.
+pub struct Notifications {
+    _messages : Vec<Notification>,
+}
+
+/// How long info messages stay up.
+const INFO_SECONDS : u64 = 5;
+/// How many messages are shown at once.
+const MAX_SHOWN : usize = 5;
+
+impl Notifications {
+    pub fn new() -> Notifications {Notifications{_messages : Vec::new()}}
+
+    /// Shows a message, and prints it to the terminal for the record.
+    pub fn push(&mut self, in_level : &Level, in_text : &str) {
+        match *in_level {
+            Level::Info => println!("{}", in_text),
+            _ => println!("{}: {}", in_level.name(), in_text),
+        }
+        self._messages.push(Notification {
+            _level : in_level.to_owned(),
+            _text  : in_text.to_owned(),
+            _time  : Instant::now(),
+        });
+    }
+
+    pub fn info(&mut self, in_text : &str) {self.push(&Level::Info, in_text)}
+    pub fn warning(&mut self, in_text : &str) {self.push(&Level::Warning, in_text)}
+    pub fn error(&mut self, in_text : &str) {self.push(&Level::Error, in_text)}
+
+    /// Clears every message, including warnings and errors.
+    pub fn dismiss_all(&mut self) {self._messages.clear()}
+
+    /// Forgets info messages which have been up long enough.
+    pub fn expire(&mut self) {
+        let lifetime = Duration::from_secs(INFO_SECONDS);
+        self._messages.retain(|m| m._level != Level::Info || m._time.elapsed() < lifetime);
+    }
+
+    /// Draws the most recent messages along the bottom of the target.
+    pub fn draw<T>(&self, in_text_system : &TextSystem, in_target : &mut T) where T : Surface {
+        if self._messages.is_empty() {return}
+        let (w, h) = in_target.get_dimensions();
+        let scale = 2.0;
+        let line_height = 12.0*scale;
+        let shown = &self._messages[self._messages.len().saturating_sub(MAX_SHOWN)..];
+        let top = h as f32 - shown.len() as f32*line_height - 4.0;
+        text::draw_panel(in_text_system, in_target, [0.0, top-4.0],
+                         [w as f32, h as f32-top+4.0], [0.0, 0.0, 0.0, 0.6]);
+        for (i, message) in shown.iter().enumerate() {
+            let line = match message._level {
+                Level::Info => message._text.to_owned(),
+                _ => format!("{}: {}", message._level.name(), message._text),
+            };
+            text::draw_text(in_text_system, in_target, &line,
+                            [8.0, top + i as f32*line_height], scale, message._level.colour());
+        }
+    }
+}
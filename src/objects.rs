@@ -0,0 +1,242 @@
+use atom::Atom;
+use bonds::BondDisplay;
+use colour::{ColourScheme, Palette};
+use matrix::Matrix;
+use molecule::Molecule;
+use radii::{AtomRadii, RadiusSource};
+use selection::Selection;
+use slideshow;
+use trajectory::Trajectory;
+
+// ============================================================
+// Object
+// ============================================================
+/// A structure in the scene: one of the files loaded, e.g. a protein, or the
+/// ligand docked into it. Its atoms are all in the one molecule, and know which
+/// object they're in (see Atom::object()), so everything drawn and measured goes
+/// across objects as it always has. Its colour scheme, bonds and radii are the
+/// scene's, unless it's been given its own.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Object {
+    #[serde(rename = "name")]
+    _name          : String,
+    #[serde(rename = "visible")]
+    _visible       : bool,
+    #[serde(rename = "colour_scheme", default)]
+    _colour_scheme : Option<ColourScheme>,
+    #[serde(rename = "bond_display", default)]
+    _bond_display  : Option<BondDisplay>,
+    #[serde(rename = "radius_source", default)]
+    _radius_source : Option<RadiusSource>,
+}
+
+impl Object {
+    fn new(in_name : &str) -> Object {
+        Object {
+            _name          : in_name.to_owned(),
+            _visible       : true,
+            _colour_scheme : None,
+            _bond_display  : None,
+            _radius_source : None,
+        }
+    }
+
+    pub fn name(&self) -> &str {&self._name}
+    pub fn is_visible(&self) -> bool {self._visible}
+    pub fn colour_scheme(&self) -> &Option<ColourScheme> {&self._colour_scheme}
+    pub fn bond_display(&self) -> &Option<BondDisplay> {&self._bond_display}
+    pub fn radius_source(&self) -> &Option<RadiusSource> {&self._radius_source}
+}
+
+/// The object of atoms in none, such as those added by hand away from any object's
+/// atoms. They're always shown, and stay put when the objects (even the whole
+/// scene) are moved.
+pub const NO_OBJECT : usize = ::std::usize::MAX;
+
+// ============================================================
+// Objects
+// ============================================================
+/// The objects in the scene, in the order they were loaded, each shown or hidden
+/// on its own (keys 1 to 9, or the script's show() and hide()) and drawn as the
+/// scene is or as it's been given (alt+C, alt+X and alt+A, for the object selected).
+/// Each is a node in the scene graph too, which moves it (see scene_graph.rs).
+#[derive(Clone)]
+pub struct Objects {
+    _objects : Vec<Object>,
+}
+
+impl Objects {
+    pub fn new() -> Objects {Objects {_objects : Vec::new()}}
+
+    pub fn objects(&self) -> &Vec<Object> {&self._objects}
+
+    /// Adds an object for a file, where the file put it, giving its index to give
+    /// its atoms.
+    pub fn add(&mut self, in_fname : &str) -> usize {
+        self._objects.push(Object::new(&slideshow::short_name(in_fname)));
+        self._objects.len()-1
+    }
+
+    /// Starts again with the one file's object, e.g. after replacing the structure.
+    pub fn reset(&mut self, in_fname : &str) {
+        self._objects.clear();
+        self.add(in_fname);
+    }
+
+    /// Whether an atom's object is shown. Atoms in no object (see NO_OBJECT) are.
+    pub fn is_visible(&self, in_object : &usize) -> bool {
+        self._objects.get(*in_object).map_or(true, |object| object._visible)
+    }
+
+    pub fn get(&self, in_object : &usize) -> Result<&Object, String> {
+        if *in_object == NO_OBJECT {return Err("no object (the atoms are in none)".to_owned())}
+        self._objects.get(*in_object).ok_or_else(|| {
+            format!("no object {} (there {} {})", in_object+1, if self._objects.len() == 1 {"is"} else {"are"},
+                    self._objects.len())
+        })
+    }
+
+    pub fn set_visible(&mut self, in_object : &usize, in_visible : &bool) -> Result<(), String> {
+        self.get(in_object)?;
+        self._objects[*in_object]._visible = *in_visible;
+        Ok(())
+    }
+
+    /// Gives an object its own colour scheme, bonds or radii, or None for the scene's.
+    /// Colours are only set by colour(), which the scheme should be applied with.
+    pub fn set_colour_scheme(&mut self, in_object : &usize, in_scheme : &Option<ColourScheme>) -> Result<(), String> {
+        self.get(in_object)?;
+        self._objects[*in_object]._colour_scheme = *in_scheme;
+        Ok(())
+    }
+
+    pub fn set_bond_display(&mut self, in_object : &usize, in_display : &Option<BondDisplay>) -> Result<(), String> {
+        self.get(in_object)?;
+        self._objects[*in_object]._bond_display = *in_display;
+        Ok(())
+    }
+
+    pub fn set_radius_source(&mut self, in_object : &usize, in_source : &Option<RadiusSource>) -> Result<(), String> {
+        self.get(in_object)?;
+        self._objects[*in_object]._radius_source = *in_source;
+        Ok(())
+    }
+
+    /// Colours the atoms by the scene's colour scheme, but for those of objects with
+    /// schemes of their own.
+    pub fn colour(&self, in_scheme : &ColourScheme, in_molecule : &mut Molecule, in_palette : &Palette) {
+        in_scheme.apply(in_molecule, in_palette);
+        for (k, object) in self._objects.iter().enumerate() {
+            if let Some(scheme) = object._colour_scheme {
+                scheme.apply_to(in_molecule, in_palette, self.atoms(in_molecule, &k).atoms());
+            }
+        }
+    }
+
+    /// How an atom's bonds are drawn, if its object says (rather than the scene).
+    pub fn bond_display(&self, in_object : &usize) -> Option<BondDisplay> {
+        self._objects.get(*in_object).and_then(|object| object._bond_display)
+    }
+
+    /// Whether any object draws its bonds whatever the scene does.
+    pub fn shows_bonds(&self) -> bool {
+        self._objects.iter().any(|object| object._bond_display.map_or(false, |display| display != BondDisplay::Hidden))
+    }
+
+    /// The radius an atom is drawn with: from its object's radius source, if it has
+    /// one, scaled as the scene's are.
+    pub fn radius(&self, in_atom : &Atom, in_radii : &AtomRadii) -> f32 {
+        self.radii(in_atom, in_radii).radius(in_atom.species())
+    }
+
+    /// What an atom's model matrix is scaled by to draw it with radius(), as
+    /// AtomRadii::factor().
+    pub fn factor(&self, in_atom : &Atom, in_radii : &AtomRadii) -> f32 {
+        self.radii(in_atom, in_radii).factor(in_atom.species())
+    }
+
+    fn radii(&self, in_atom : &Atom, in_radii : &AtomRadii) -> AtomRadii {
+        match self._objects.get(*in_atom.object()).and_then(|object| object._radius_source) {
+            Some(source) => AtomRadii::new(&source, in_radii.scale()),
+            None => *in_radii,
+        }
+    }
+
+    /// The one object the selected atoms are all in, for the keys that change or
+    /// move an object.
+    pub fn of_selection(&self, in_molecule : &Molecule, in_selection : &Selection) -> Result<usize, String> {
+        let mut objects = in_selection.atoms().iter().map(|&i| *in_molecule.atoms()[i].object());
+        let object = objects.next().ok_or("nothing selected (select an object with Shift+1-9)")?;
+        if objects.any(|other| other != object) {
+            return Err("atoms of more than one object selected".to_owned());
+        }
+        self.get(&object).map(|_| object)
+    }
+
+    /// The object's atoms.
+    pub fn atoms(&self, in_molecule : &Molecule, in_object : &usize) -> Selection {
+        let atoms = in_molecule.atoms().iter().enumerate()
+            .filter(|&(_, atom)| atom.object() == in_object)
+            .map(|(i, _)| i).collect();
+        Selection::from_atoms(&atoms)
+    }
+
+    /// Moves an object's atoms by a transformation, in every frame of the trajectory
+    /// as well as the frame showing, so they stay where they've been put. (The scene
+    /// graph says where, see scene_graph.rs.) Their forces and velocities turn with
+    /// them, and the molecule's dipole with the first object's, the file it came from.
+    pub fn move_atoms(&self, in_object : &usize, in_transformation : &Matrix, in_molecule : &mut Molecule,
+                      in_trajectory : &mut Trajectory) {
+        let atoms = self.atoms(in_molecule, in_object);
+        let moved = |position : &[f32;3]| {
+            let p = *in_transformation*[position[0], position[1], position[2], 1.0];
+            [p[0], p[1], p[2]]
+        };
+        let turned = |vector : &[f32;3]| {
+            let v = *in_transformation*[vector[0], vector[1], vector[2], 0.0];
+            [v[0], v[1], v[2]]
+        };
+        for &i in atoms.atoms() {
+            let position = moved(in_molecule.atoms()[i].position());
+            let atom = &mut in_molecule.atoms_mut()[i];
+            atom.set_position(&position);
+            if let Some(force) = *atom.force() {atom.set_force(&turned(&force))}
+            if let Some(velocity) = *atom.velocity() {atom.set_velocity(&turned(&velocity))}
+        }
+        if let (0, Some(dipole)) = (*in_object, *in_molecule.dipole()) {
+            in_molecule.set_dipole(&turned(&dipole));
+        }
+        for k in 0..in_trajectory.frames().len() {
+            for &i in atoms.atoms() {
+                let position = moved(&in_trajectory.frames()[k][i]);
+                in_trajectory.set_position(&k, &i, &position);
+            }
+        }
+        in_trajectory.turn_vectors(atoms.atoms(), turned);
+    }
+
+    /// Makes some atoms an object of their own (part of a file, e.g. one chain),
+    /// giving its index.
+    pub fn split(&mut self, in_name : &str, in_atoms : &Selection, in_molecule : &mut Molecule) -> usize {
+        self._objects.push(Object::new(in_name));
+        let object = self._objects.len()-1;
+        for &i in in_atoms.atoms() {in_molecule.atoms_mut()[i].set_object(&object)}
+        object
+    }
+
+    /// Shows and hides the objects as saved (in a session, say), and puts each atom
+    /// back in the object it was saved in, so those split off (see split()) are made
+    /// again. Objects past the ones there are now that no atom is in are left out.
+    pub fn restore(&mut self, in_saved : &[Object], in_atom_objects : &[usize], in_molecule : &mut Molecule) {
+        let count = self._objects.len();
+        let used = in_atom_objects.iter().take(in_molecule.atoms().len())
+            .filter(|&&object| object < in_saved.len())
+            .map(|&object| object+1).max().unwrap_or(0);
+        for (k, saved) in in_saved.iter().enumerate().take(count.max(used)) {
+            if k < count {self._objects[k] = saved.to_owned()} else {self._objects.push(saved.to_owned())}
+        }
+        for (atom, object) in in_molecule.atoms_mut().iter_mut().zip(in_atom_objects.iter()) {
+            if *object < self._objects.len() {atom.set_object(object)}
+        }
+    }
+}
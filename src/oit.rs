@@ -0,0 +1,221 @@
+use glium::{self, Surface};
+use glium::backend::Facade;
+use glium::backend::Context;
+use glium::framebuffer::{SimpleFrameBuffer, MultiOutputFrameBuffer};
+use glium::texture::UncompressedFloatFormat;
+use glium::texture::MipmapsOption;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use stats;
+
+// ============================================================
+// Order-Independent Transparency
+// ============================================================
+/// Weighted-blended order-independent transparency (McGuire & Bavoil, 2013).
+///
+/// Opaque geometry is drawn into an offscreen colour + depth target as usual.
+/// Translucent geometry is then drawn, depth-tested against the opaque depth but
+/// not writing to it, into two extra targets:
+/// * accumulation - the sum of weighted, premultiplied colours and of the weights,
+/// * revealage    - the sum of -ln(1-alpha), i.e. how much of the background shows through.
+/// Both only ever need additive blending, so the draw order doesn't matter.
+/// Finally everything is composited onto the real target in one full-screen pass.
+pub struct OitSystem {
+    context: Rc<Context>,
+    vertex_buffer: glium::VertexBuffer<SpriteVertex>,
+    index_buffer: glium::IndexBuffer<u16>,
+    program: glium::Program,
+    target_color: RefCell<Option<glium::texture::Texture2d>>,
+    target_accumulation: RefCell<Option<glium::texture::Texture2d>>,
+    target_revealage: RefCell<Option<glium::texture::Texture2d>>,
+    target_depth: RefCell<Option<glium::framebuffer::DepthRenderBuffer>>,
+}
+
+#[derive(Copy, Clone)]
+struct SpriteVertex {
+    position: [f32; 2],
+    i_tex_coords: [f32; 2],
+}
+
+implement_vertex!(SpriteVertex, position, i_tex_coords);
+
+impl OitSystem {
+    pub fn new<F: ?Sized>(facade: &F) -> OitSystem where F: Facade + Clone {
+        OitSystem {
+            context: facade.get_context().clone(),
+
+            vertex_buffer: glium::VertexBuffer::new(facade,
+                &[
+                    SpriteVertex { position: [-1.0, -1.0], i_tex_coords: [0.0, 0.0] },
+                    SpriteVertex { position: [-1.0,  1.0], i_tex_coords: [0.0, 1.0] },
+                    SpriteVertex { position: [ 1.0,  1.0], i_tex_coords: [1.0, 1.0] },
+                    SpriteVertex { position: [ 1.0, -1.0], i_tex_coords: [1.0, 0.0] }
+                ]
+            ).unwrap(),
+
+            index_buffer: glium::index::IndexBuffer::new(facade,
+                glium::index::PrimitiveType::TriangleStrip, &[1 as u16, 2, 0, 3]).unwrap(),
+
+            program: glium::Program::from_source(facade,
+                r"
+                    #version 140
+
+                    in vec2 position;
+                    in vec2 i_tex_coords;
+
+                    out vec2 v_tex_coords;
+
+                    void main() {
+                        gl_Position = vec4(position, 0.0, 1.0);
+                        v_tex_coords = i_tex_coords;
+                    }
+                ",
+                r"
+                    #version 140
+
+                    uniform sampler2D opaque;
+                    uniform sampler2D accumulation;
+                    uniform sampler2D revealage;
+
+                    in vec2 v_tex_coords;
+
+                    out vec4 color;
+
+                    void main() {
+                        vec4 background = texture(opaque, v_tex_coords);
+                        vec4 accumulated = texture(accumulation, v_tex_coords);
+                        float revealed = exp(-texture(revealage, v_tex_coords).r);
+                        vec3 average = accumulated.rgb / max(accumulated.a, 1e-5);
+                        color = vec4(mix(average, background.rgb, revealed), background.a);
+                    }
+                ",
+                None
+            ).unwrap(),
+
+            target_color: RefCell::new(None),
+            target_accumulation: RefCell::new(None),
+            target_revealage: RefCell::new(None),
+            target_depth: RefCell::new(None),
+        }
+    }
+
+    /// How much GPU memory the targets the frame is drawn into take, roughly.
+    pub fn bytes(&self) -> usize {
+        self.target_color.borrow().as_ref().map_or(0, |tex| stats::texture_bytes(tex, &4))
+            + self.target_accumulation.borrow().as_ref().map_or(0, |tex| stats::texture_bytes(tex, &8))
+            + self.target_revealage.borrow().as_ref().map_or(0, |tex| stats::texture_bytes(tex, &2))
+            + self.target_depth.borrow().as_ref().map_or(0, stats::depth_bytes)
+    }
+}
+
+/// Draw parameters for the translucent pass: test against (as the opaque pass would),
+/// but don't write to, the opaque depth buffer, and sum everything into the
+/// accumulation/revealage targets.
+pub fn translucent_parameters<'a>(in_opaque_parameters : &glium::DrawParameters<'a>)
+                                  -> glium::DrawParameters<'a> {
+    let additive = glium::BlendingFunction::Addition {
+        source      : glium::LinearBlendingFactor::One,
+        destination : glium::LinearBlendingFactor::One,
+    };
+    glium::DrawParameters {
+        depth: glium::Depth {
+            test: in_opaque_parameters.depth.test,
+            write: false,
+            .. Default::default()
+        },
+        blend: glium::Blend {
+            color: additive,
+            alpha: additive,
+            constant_value: (0.0, 0.0, 0.0, 0.0),
+        },
+        .. in_opaque_parameters.clone()
+    }
+}
+
+fn resized_texture(target_texture : &mut Option<glium::texture::Texture2d>,
+                   context : &Rc<Context>,
+                   format : UncompressedFloatFormat,
+                   dimensions : (u32, u32)) {
+    let clear = if let &Some(ref tex) = &*target_texture {
+        tex.get_width() != dimensions.0 || tex.get_height().unwrap() != dimensions.1
+    } else {
+        false
+    };
+    if clear { *target_texture = None; }
+
+    if target_texture.is_none() {
+        let texture = glium::texture::Texture2d::empty_with_format(context,
+                                                                   format,
+                                                                   MipmapsOption::NoMipmap,
+                                                                   dimensions.0,
+                                                                   dimensions.1).unwrap();
+        *target_texture = Some(texture);
+    }
+}
+
+/// Draws the opaque pass, then the translucent pass, then composites both onto `target`.
+/// The opaque pass is responsible for clearing its own colour and depth.
+pub fn draw<T, F, G>(system: &OitSystem, target: &mut T, mut draw_opaque: F, mut draw_translucent: G)
+                     where T: Surface,
+                           F: FnMut(&mut SimpleFrameBuffer),
+                           G: FnMut(&mut MultiOutputFrameBuffer)
+{
+    let target_dimensions = target.get_dimensions();
+
+    let mut target_color = system.target_color.borrow_mut();
+    let mut target_accumulation = system.target_accumulation.borrow_mut();
+    let mut target_revealage = system.target_revealage.borrow_mut();
+    let mut target_depth = system.target_depth.borrow_mut();
+
+    resized_texture(&mut *target_color, &system.context,
+                    UncompressedFloatFormat::U8U8U8U8, target_dimensions);
+    resized_texture(&mut *target_accumulation, &system.context,
+                    UncompressedFloatFormat::F16F16F16F16, target_dimensions);
+    resized_texture(&mut *target_revealage, &system.context,
+                    UncompressedFloatFormat::F16, target_dimensions);
+    let target_color = target_color.as_ref().unwrap();
+    let target_accumulation = target_accumulation.as_ref().unwrap();
+    let target_revealage = target_revealage.as_ref().unwrap();
+
+    {
+        let clear = if let &Some(ref tex) = &*target_depth {
+            tex.get_dimensions() != target_dimensions
+        } else {
+            false
+        };
+        if clear { *target_depth = None; }
+    }
+
+    if target_depth.is_none() {
+        let texture = glium::framebuffer::DepthRenderBuffer::new(&system.context,
+                                                                  glium::texture::DepthFormat::I24,
+                                                                  target_dimensions.0 as u32,
+                                                                  target_dimensions.1 as u32).unwrap();
+        *target_depth = Some(texture);
+    }
+    let target_depth = target_depth.as_ref().unwrap();
+
+    draw_opaque(&mut SimpleFrameBuffer::with_depth_buffer(&system.context, target_color,
+                                                                           target_depth).unwrap());
+
+    {
+        let mut translucent = MultiOutputFrameBuffer::with_depth_buffer(
+            &system.context,
+            vec![("accumulation", target_accumulation), ("revealage", target_revealage)],
+            target_depth
+        ).unwrap();
+        translucent.clear_color(0.0, 0.0, 0.0, 0.0);
+        draw_translucent(&mut translucent);
+    }
+
+    let uniforms = uniform! {
+        opaque: &*target_color,
+        accumulation: &*target_accumulation,
+        revealage: &*target_revealage,
+    };
+
+    target.draw(&system.vertex_buffer, &system.index_buffer, &system.program, &uniforms,
+                &Default::default()).unwrap();
+}
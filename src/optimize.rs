@@ -0,0 +1,404 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::io::BufReader;
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{self, Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Instant;
+
+use bonds;
+use molecule::Molecule;
+use stream;
+
+// ============================================================
+// Force Field
+// ============================================================
+/// Lennard-Jones parameters from UFF (Rappe et al., J. Am. Chem. Soc., 1992) as
+/// (element, well distance in Angstroms, well depth in kcal/mol).
+const LENNARD_JONES : [(&'static str, f32, f32); 6] = [
+    ("H" , 2.886, 0.044), ("C" , 3.851, 0.105), ("N" , 3.660, 0.069),
+    ("O" , 3.500, 0.060), ("S" , 4.035, 0.274), ("Ni", 2.834, 0.015),
+];
+
+/// For elements not in the table.
+const DEFAULT_LENNARD_JONES : (f32, f32) = (3.5, 0.05);
+
+/// How stiff bonds are, in kcal/mol/Angstrom^2.
+const BOND_STIFFNESS : f32 = 350.0;
+
+/// Atoms further apart (in Angstroms) than this don't feel each other.
+const CUTOFF : f32 = 6.0;
+
+/// A toy force field, good enough to pull hand-placed atoms into something like
+/// sensible positions: each bond is a spring at the sum of its atoms' covalent
+/// radii, and atoms not bonded to each other (or to a common neighbour) attract
+/// and repel with a Lennard-Jones potential. There are no angle, torsion or charge
+/// terms, and periodic cells are ignored.
+pub struct ForceField {
+    _bonds    : Vec<[usize;2]>,
+    _lengths  : Vec<f32>,
+    /// The pairs of atoms (lowest index first) within two bonds of each other.
+    _excluded : HashSet<[usize;2]>,
+    /// Each atom's Lennard-Jones well distance and depth.
+    _wells    : Vec<(f32, f32)>,
+}
+
+impl ForceField {
+    pub fn new(in_molecule : &Molecule, in_bonds : &Vec<[usize;2]>) -> ForceField {
+        let n = in_molecule.atoms().len();
+        let mut neighbours : Vec<Vec<usize>> = vec![Vec::new(); n];
+        for bond in in_bonds {
+            neighbours[bond[0]].push(bond[1]);
+            neighbours[bond[1]].push(bond[0]);
+        }
+        let pair = |a : usize, b : usize| if a < b {[a, b]} else {[b, a]};
+        let mut excluded = HashSet::new();
+        for (i, bonded) in neighbours.iter().enumerate() {
+            for &j in bonded {
+                excluded.insert(pair(i, j));
+                for &k in neighbours[j].iter().filter(|&&k| k != i) {
+                    excluded.insert(pair(i, k));
+                }
+            }
+        }
+        ForceField {
+            _bonds    : in_bonds.to_owned(),
+            _lengths  : bonds::ideal_lengths(in_molecule, in_bonds),
+            _excluded : excluded,
+            _wells    : in_molecule.atoms().iter().map(|atom| {
+                let symbol = atom.species().symbol();
+                LENNARD_JONES.iter().find(|&&(element, _, _)| element == symbol)
+                             .map_or(DEFAULT_LENNARD_JONES, |&(_, distance, depth)| (distance, depth))
+            }).collect(),
+        }
+    }
+
+    /// The energy (in kcal/mol) of the atoms at some positions, and the force
+    /// (in kcal/mol/Angstrom) on each.
+    pub fn energy_and_forces(&self, in_positions : &Vec<[f32;3]>) -> (f32, Vec<[f32;3]>) {
+        let mut energy = 0.0;
+        let mut forces = vec![[0.0f32;3]; in_positions.len()];
+        // pushes atoms i and j apart by a force along the line between them
+        let push = |forces : &mut Vec<[f32;3]>, i : usize, j : usize, d : &[f32;3], r : f32, force : f32| {
+            for k in 0..3 {
+                forces[i][k] -= force*d[k]/r;
+                forces[j][k] += force*d[k]/r;
+            }
+        };
+        let separation = |i : usize, j : usize| {
+            let (a, b) = (in_positions[i], in_positions[j]);
+            let d = [b[0]-a[0], b[1]-a[1], b[2]-a[2]];
+            (d, (d[0]*d[0]+d[1]*d[1]+d[2]*d[2]).sqrt().max(1e-3))
+        };
+
+        for (bond, length) in self._bonds.iter().zip(self._lengths.iter()) {
+            let (d, r) = separation(bond[0], bond[1]);
+            let stretch = r - length;
+            energy += BOND_STIFFNESS*stretch*stretch;
+            push(&mut forces, bond[0], bond[1], &d, r, -2.0*BOND_STIFFNESS*stretch);
+        }
+
+        // only atoms in neighbouring cubes of the cutoff's size can be close enough
+        let cube = |p : &[f32;3]| [
+            (p[0]/CUTOFF).floor() as i64, (p[1]/CUTOFF).floor() as i64, (p[2]/CUTOFF).floor() as i64,
+        ];
+        let mut cubes : HashMap<[i64;3], Vec<usize>> = HashMap::new();
+        for (i, position) in in_positions.iter().enumerate() {
+            cubes.entry(cube(position)).or_insert_with(Vec::new).push(i);
+        }
+        for (i, position) in in_positions.iter().enumerate() {
+            let c = cube(position);
+            for dx in -1..2 {
+                for dy in -1..2 {
+                    for dz in -1..2 {
+                        let others = match cubes.get(&[c[0]+dx, c[1]+dy, c[2]+dz]) {
+                            Some(others) => others,
+                            None => continue,
+                        };
+                        for &j in others.iter().filter(|&&j| j > i) {
+                            if self._excluded.contains(&[i, j]) {continue}
+                            let (d, r) = separation(i, j);
+                            if r > CUTOFF {continue}
+                            let distance = (self._wells[i].0*self._wells[j].0).sqrt();
+                            let depth = (self._wells[i].1*self._wells[j].1).sqrt();
+                            let x6 = (distance/r).powi(6);
+                            energy += depth*(x6*x6 - 2.0*x6);
+                            // -dE/dr, positive for repulsion
+                            push(&mut forces, i, j, &d, r, 12.0*depth*(x6*x6 - x6)/r);
+                        }
+                    }
+                }
+            }
+        }
+        (energy, forces)
+    }
+}
+
+// ============================================================
+// Relaxation
+// ============================================================
+/// The largest force (in kcal/mol/Angstrom) on any atom when the relaxation stops.
+const FORCE_TOLERANCE : f32 = 0.05;
+const MAX_STEPS : usize = 5000;
+/// The furthest an atom moves in one step, in Angstroms, so overlapping atoms
+/// don't fly apart.
+const MAX_DISPLACEMENT : f32 = 0.1;
+
+// the FIRE minimiser's settings (Bitzek et al., Phys. Rev. Lett., 2006)
+const START_TIME_STEP : f32 = 0.01;
+const MAX_TIME_STEP   : f32 = 0.05;
+const START_ALPHA     : f32 = 0.1;
+const MIN_STEPS       : usize = 5;
+
+/// A minimisation of the force field's energy, a step at a time so it can be
+/// watched. It uses FIRE: steepest descent which builds up speed while it keeps
+/// going downhill, and stops dead when it overshoots. Every atom has the same mass.
+pub struct Relaxation {
+    _force_field : ForceField,
+    /// Where the atoms were before, e.g. to undo it.
+    _start       : Vec<[f32;3]>,
+    _velocities  : Vec<[f32;3]>,
+    _time_step   : f32,
+    _alpha       : f32,
+    /// How many steps in a row have gone downhill.
+    _downhill    : usize,
+    _steps       : usize,
+    _energy      : f32,
+    _max_force   : f32,
+    _stopped     : bool,
+}
+
+impl Relaxation {
+    pub fn new(in_molecule : &Molecule, in_bonds : &Vec<[usize;2]>) -> Relaxation {
+        let positions : Vec<[f32;3]> = in_molecule.atoms().iter().map(|atom| *atom.position()).collect();
+        Relaxation {
+            _force_field : ForceField::new(in_molecule, in_bonds),
+            _velocities  : vec![[0.0;3]; positions.len()],
+            _start       : positions,
+            _time_step   : START_TIME_STEP,
+            _alpha       : START_ALPHA,
+            _downhill    : 0,
+            _steps       : 0,
+            _energy      : 0.0,
+            _max_force   : ::std::f32::INFINITY,
+            _stopped     : false,
+        }
+    }
+
+    pub fn start(&self) -> &Vec<[f32;3]> {&self._start}
+    pub fn steps(&self) -> &usize {&self._steps}
+    pub fn energy(&self) -> &f32 {&self._energy}
+    pub fn max_force(&self) -> &f32 {&self._max_force}
+
+    /// Stops it where it is.
+    pub fn stop(&mut self) {self._stopped = true}
+
+    /// Whether it's converged, given up or been stopped, or the molecule's atoms
+    /// have been added to or deleted since it started.
+    pub fn is_finished(&self, in_molecule : &Molecule) -> bool {
+        self._stopped || self._max_force < FORCE_TOLERANCE || self._steps >= MAX_STEPS
+            || in_molecule.atoms().len() != self._start.len()
+    }
+
+    /// Moves the atoms one step downhill.
+    pub fn step(&mut self, in_molecule : &mut Molecule) {
+        if self.is_finished(in_molecule) {return}
+        let positions : Vec<[f32;3]> = in_molecule.atoms().iter().map(|atom| *atom.position()).collect();
+        let (energy, forces) = self._force_field.energy_and_forces(&positions);
+        self._energy = energy;
+        self._max_force = forces.iter()
+            .map(|f| (f[0]*f[0]+f[1]*f[1]+f[2]*f[2]).sqrt())
+            .fold(0.0f32, f32::max);
+
+        let dot = |a : &Vec<[f32;3]>, b : &Vec<[f32;3]>| -> f32 {
+            a.iter().zip(b.iter()).map(|(x, y)| x[0]*y[0]+x[1]*y[1]+x[2]*y[2]).sum()
+        };
+        let power = dot(&forces, &self._velocities);
+        if power > 0.0 {
+            // steer the velocity towards the force
+            let speed = dot(&self._velocities, &self._velocities).sqrt();
+            let force = dot(&forces, &forces).sqrt().max(1e-12);
+            for (v, f) in self._velocities.iter_mut().zip(forces.iter()) {
+                for k in 0..3 {
+                    v[k] = (1.0-self._alpha)*v[k] + self._alpha*speed*f[k]/force;
+                }
+            }
+            self._downhill += 1;
+            if self._downhill > MIN_STEPS {
+                self._time_step = (1.1*self._time_step).min(MAX_TIME_STEP);
+                self._alpha *= 0.99;
+            }
+        } else {
+            for v in &mut self._velocities {*v = [0.0;3]}
+            self._time_step *= 0.5;
+            self._alpha = START_ALPHA;
+            self._downhill = 0;
+        }
+
+        let dt = self._time_step;
+        for (i, (v, f)) in self._velocities.iter_mut().zip(forces.iter()).enumerate() {
+            let mut d = [0.0f32;3];
+            for k in 0..3 {
+                v[k] += f[k]*dt;
+                d[k] = v[k]*dt;
+            }
+            let length = (d[0]*d[0]+d[1]*d[1]+d[2]*d[2]).sqrt();
+            let scale = if length > MAX_DISPLACEMENT {MAX_DISPLACEMENT/length} else {1.0};
+            let p = positions[i];
+            in_molecule.move_atom(&i, &[p[0]+scale*d[0], p[1]+scale*d[1], p[2]+scale*d[2]]);
+        }
+        self._steps += 1;
+    }
+}
+
+// ============================================================
+// External Relaxation
+// ============================================================
+/// A relaxation by another program (--optimiser, run with shift+F10), for when the
+/// toy force field won't do, e.g. a script round xtb or a DFT code. The command is
+/// run by the shell with the structure in the xyz format: in a temporary file whose
+/// path stands in for "{}" in the command, or on its standard input if there's no
+/// "{}". It writes each geometry it steps through to its standard output in the xyz
+/// format too, the same atoms in the same order, and they're shown as they come.
+/// It's finished when its output ends.
+///
+/// Nothing else may go to the standard output, so most programs need a wrapper.
+/// xtb, for one, writes its log there and the geometries to xtbopt.log, so this
+/// runs it out of the way and passes the geometries on as they're written:
+///
+/// ```text
+/// --optimiser 'cd "$(mktemp -d)" && { xtb {} --opt > xtb.out 2>&1 & } && tail -F --pid=$! xtbopt.log 2>/dev/null'
+/// ```
+///
+/// The geometries are read on a thread of their own, as the stream's frames are
+/// (see stream.rs). Dropping it (e.g. to cancel) kills the program, and on Unix
+/// everything it started (as the wrapper above starts xtb and tail), since the
+/// shell is put in a process group of its own and the whole group is killed.
+pub struct ExternalRelaxation {
+    _command : String,
+    _child   : Child,
+    _frames  : Receiver<Result<Vec<[f32;3]>, String>>,
+    /// Where the atoms were before, e.g. to undo it or to put them back on cancelling.
+    _start   : Vec<[f32;3]>,
+    /// The structure, written for the command to read, removed at the end.
+    _input   : Option<PathBuf>,
+    _steps   : usize,
+    _started : Instant,
+}
+
+impl ExternalRelaxation {
+    pub fn start(in_command : &str, in_molecule : &Molecule) -> Result<ExternalRelaxation, String> {
+        let mut xyz = format!("{}\nfrom oxide\n", in_molecule.atoms().len());
+        for atom in in_molecule.atoms() {
+            let p = atom.position();
+            xyz.push_str(&format!("{} {} {} {}\n", atom.species().symbol(), p[0], p[1], p[2]));
+        }
+        let input = if in_command.contains("{}") {
+            let path = env::temp_dir().join(format!("oxide-relax-{}.xyz", process::id()));
+            File::create(&path).and_then(|mut file| file.write_all(xyz.as_bytes()))
+                .map_err(|e| format!("couldn't write the structure to {}: {}", path.display(), e))?;
+            Some(path)
+        } else {
+            None
+        };
+        let command = match input {
+            Some(ref path) => in_command.replace("{}", &path.to_string_lossy()),
+            None => in_command.to_owned(),
+        };
+        let (shell, flag) = if cfg!(windows) {("cmd", "/C")} else {("sh", "-c")};
+        let mut shell = Command::new(shell);
+        shell.arg(flag)
+             .arg(&command)
+             .stdin(if input.is_some() {Stdio::null()} else {Stdio::piped()})
+             .stdout(Stdio::piped());
+        #[cfg(unix)]
+        shell.process_group(0);
+        let mut child = shell.spawn().map_err(|e| format!("couldn't run \"{}\": {}", command, e))?;
+        // (written from a thread of its own, so a program which writes before it's
+        // read all its input can't hold everything up)
+        if let Some(mut stdin) = child.stdin.take() {
+            thread::spawn(move || {stdin.write_all(xyz.as_bytes()).ok();});
+        }
+        let stdout = child.stdout.take().ok_or_else(|| format!("couldn't read from \"{}\"", command))?;
+        let (sender, receiver) = mpsc::sync_channel(stream::BUFFERED_FRAMES);
+        let atom_count = in_molecule.atoms().len();
+        thread::spawn(move || {
+            let mut lines = BufReader::new(stdout).lines();
+            let mut line_number = 0;
+            // (the channel closing without an error is the output ending)
+            while let Some(frame) = stream::read_frame(&mut lines, &mut line_number, &atom_count).transpose() {
+                let last = frame.is_err();
+                if sender.send(frame).is_err() || last {return}
+            }
+        });
+        Ok(ExternalRelaxation {
+            _command : in_command.to_owned(),
+            _child   : child,
+            _frames  : receiver,
+            _start   : in_molecule.atoms().iter().map(|atom| *atom.position()).collect(),
+            _input   : input,
+            _steps   : 0,
+            _started : Instant::now(),
+        })
+    }
+
+    pub fn command(&self) -> &str {&self._command}
+    pub fn started(&self) -> &Instant {&self._started}
+    pub fn start_positions(&self) -> &Vec<[f32;3]> {&self._start}
+    /// How many geometries have come back so far.
+    pub fn steps(&self) -> &usize {&self._steps}
+
+    /// The newest geometry since the last call, if any have come, and, once the
+    /// program's finished, whether it finished properly.
+    pub fn receive(&mut self) -> (Option<Vec<[f32;3]>>, Option<Result<(), String>>) {
+        let mut newest = None;
+        loop {
+            match self._frames.try_recv() {
+                Ok(Ok(positions)) => {
+                    self._steps += 1;
+                    newest = Some(positions);
+                },
+                Ok(Err(message)) => return (newest, Some(Err(message))),
+                Err(TryRecvError::Empty) => return (newest, None),
+                Err(TryRecvError::Disconnected) => {
+                    let finished = match self._child.wait() {
+                        Ok(status) if status.success() => Ok(()),
+                        Ok(status) => Err(format!("it {}", status)),
+                        Err(e) => Err(format!("couldn't wait for it: {}", e)),
+                    };
+                    return (newest, Some(finished));
+                },
+            }
+        }
+    }
+}
+
+impl Drop for ExternalRelaxation {
+    fn drop(&mut self) {
+        // (which does nothing if it's already finished)
+        if let Ok(None) = self._child.try_wait() {
+            kill(&mut self._child);
+            self._child.wait().ok();
+        }
+        if let Some(ref path) = self._input {
+            fs::remove_file(path).ok();
+        }
+    }
+}
+
+/// Kills a program run by the shell, with everything in its process group.
+#[cfg(unix)]
+fn kill(in_child : &mut Child) {
+    // (a negative id is the group's)
+    unsafe {libc::kill(-(in_child.id() as libc::pid_t), libc::SIGKILL);}
+}
+
+#[cfg(not(unix))]
+fn kill(in_child : &mut Child) {
+    in_child.kill().ok();
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use assembly::Operator;
+use cell::Cell;
+use matrix::Matrix;
+use molecule::Molecule;
+
+// ============================================================
+// Crystal
+// ============================================================
+/// The crystal a structure was solved in (PDB CRYST1 and REMARK 290): the unit
+/// cell, and the symmetry operators which fill it from the asymmetric unit.
+#[derive(Clone, Debug)]
+pub struct Crystal {
+    _cell      : Cell,
+    /// In cartesian coordinates (SMTRY), the identity included.
+    _operators : Vec<Matrix>,
+}
+
+impl Crystal {
+    pub fn new(in_cell : &Cell) -> Crystal {
+        Crystal {
+            _cell      : in_cell.to_owned(),
+            _operators : Vec::new(),
+        }
+    }
+
+    pub fn cell(&self) -> &Cell {&self._cell}
+    pub fn operators(&self) -> &Vec<Matrix> {&self._operators}
+    pub fn add_operator(&mut self, in_operator : &Matrix) {self._operators.push(in_operator.to_owned())}
+}
+
+// ============================================================
+// Packing
+// ============================================================
+/// Atoms of neighbouring copies closer than this (in Angstroms) are in contact.
+pub const CONTACT_DISTANCE : f32 = 4.0;
+
+/// The atoms of a molecule sorted into cubes of some size, to find the ones near a point.
+struct Grid {
+    _size  : f32,
+    _cubes : HashMap<[i64;3], Vec<usize>>,
+}
+
+impl Grid {
+    fn new(in_positions : &Vec<[f32;3]>, in_size : &f32) -> Grid {
+        let mut grid = Grid {_size : *in_size, _cubes : HashMap::new()};
+        for (i, position) in in_positions.iter().enumerate() {
+            let cube = grid.cube(position);
+            grid._cubes.entry(cube).or_insert_with(Vec::new).push(i);
+        }
+        grid
+    }
+
+    fn cube(&self, in_position : &[f32;3]) -> [i64;3] {
+        [
+            (in_position[0]/self._size).floor() as i64,
+            (in_position[1]/self._size).floor() as i64,
+            (in_position[2]/self._size).floor() as i64,
+        ]
+    }
+
+    /// Every atom within the grid's size of a point, with its distance.
+    fn near(&self, in_positions : &Vec<[f32;3]>, in_point : &[f32;3]) -> Vec<(usize, f32)> {
+        let c = self.cube(in_point);
+        let mut near = Vec::new();
+        for dx in -1..2 {
+            for dy in -1..2 {
+                for dz in -1..2 {
+                    let atoms = match self._cubes.get(&[c[0]+dx, c[1]+dy, c[2]+dz]) {
+                        Some(atoms) => atoms,
+                        None => continue,
+                    };
+                    for &i in atoms {
+                        let p = in_positions[i];
+                        let d = ((p[0]-in_point[0]).powi(2) + (p[1]-in_point[1]).powi(2)
+                               + (p[2]-in_point[2]).powi(2)).sqrt();
+                        if d < self._size {near.push((i, d))}
+                    }
+                }
+            }
+        }
+        near
+    }
+}
+
+/// The copies of the molecule made by the crystal's symmetry operators, moved by up
+/// to a cell along each lattice vector, which come within `in_contact` of the molecule
+/// (the molecule itself left out). With them, how close each atom comes to an atom
+/// of another copy, for atoms within `in_contact` of one.
+pub fn neighbours(in_molecule : &Molecule, in_crystal : &Crystal, in_contact : &f32)
+                  -> (Vec<Operator>, Vec<Option<f32>>) {
+    let positions : Vec<[f32;3]> = in_molecule.atoms().iter().map(|atom| *atom.position()).collect();
+    let grid = Grid::new(&positions, in_contact);
+    let mut copies = Vec::new();
+    let mut contacts : Vec<Option<f32>> = vec![None; positions.len()];
+    for symmetry in in_crystal.operators() {
+        for a in -1..2 {
+            for b in -1..2 {
+                for c in -1..2 {
+                    let shift = in_crystal.cell().to_cartesian(&[a as f32, b as f32, c as f32]);
+                    let mut contents = symmetry.contents().to_owned();
+                    for k in 0..3 {contents[k][3] += shift[k]}
+                    let operator = Operator::for_all(&Matrix::new(contents));
+                    // the molecule itself
+                    if positions.iter().all(|p| {
+                        let q = operator.shift(p);
+                        q[0].abs() < 1e-3 && q[1].abs() < 1e-3 && q[2].abs() < 1e-3
+                    }) {
+                        continue;
+                    }
+                    let mut touches = false;
+                    for (j, position) in positions.iter().enumerate() {
+                        for (i, distance) in grid.near(&positions, &operator.apply(position)) {
+                            touches = true;
+                            // atom i touches atom j's copy, so j touches i's inverse copy
+                            for &k in &[i, j] {
+                                contacts[k] = Some(contacts[k].map_or(distance, |d| d.min(distance)));
+                            }
+                        }
+                    }
+                    if touches {copies.push(operator)}
+                }
+            }
+        }
+    }
+    (copies, contacts)
+}
+
+/// The colour of an atom in a contact of some length: red for the closest, fading
+/// to orange at `in_contact`.
+pub fn contact_colour(in_distance : &f32, in_contact : &f32) -> [f32;3] {
+    let t = (in_distance/in_contact).max(0.0).min(1.0);
+    [1.0, 0.6*t, 0.1]
+}
+
+// ============================================================
+// Supercell
+// ============================================================
+/// Where the atoms are in a supercell `in_repeats` cells along each lattice vector:
+/// every copy of the positions, one lattice translation after another with a
+/// changing fastest.
+pub fn supercell_positions(in_cell : &Cell, in_positions : &Vec<[f32;3]>, in_repeats : &[usize;3]) -> Vec<[f32;3]> {
+    let mut positions = Vec::with_capacity(in_positions.len()*in_repeats[0]*in_repeats[1]*in_repeats[2]);
+    for c in 0..in_repeats[2] {
+        for b in 0..in_repeats[1] {
+            for a in 0..in_repeats[0] {
+                let shift = in_cell.to_cartesian(&[a as f32, b as f32, c as f32]);
+                positions.extend(in_positions.iter().map(|p| [p[0]+shift[0], p[1]+shift[1], p[2]+shift[2]]));
+            }
+        }
+    }
+    positions
+}
+
+/// The molecule repeated `in_repeats` times along each lattice vector of its cell,
+/// in a cell that much bigger, since one cell of most materials doesn't show much.
+/// The copies' atoms follow each other in the order of supercell_positions(), and
+/// the bonds between neighbouring copies are found along with the rest when the
+/// bonds are found again. Only the atoms and the cell are kept, not what a PDB file
+/// gives (the residues' gaps, the assembly,...). None without a cell.
+pub fn make_supercell<'a>(in_molecule : &Molecule<'a>, in_repeats : &[usize;3]) -> Option<Molecule<'a>> {
+    let cell = (*in_molecule.cell())?;
+    let positions : Vec<[f32;3]> = in_molecule.atoms().iter().map(|atom| *atom.position()).collect();
+    let mut supercell = Molecule::new();
+    for (k, position) in supercell_positions(&cell, &positions, in_repeats).iter().enumerate() {
+        let mut atom = in_molecule.atoms()[k % positions.len()].clone();
+        atom.set_position(position);
+        supercell.atoms_mut().push(atom);
+    }
+    let l = cell.lattice();
+    let mut lattice = [[0.0f32;3];3];
+    for i in 0..3 {
+        for j in 0..3 {
+            lattice[i][j] = l[i][j]*in_repeats[i] as f32;
+        }
+    }
+    supercell.set_cell(&Cell::new(&lattice, cell.origin())?);
+    Some(supercell)
+}
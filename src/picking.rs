@@ -0,0 +1,55 @@
+use atom::Atom;
+use molecule::Molecule;
+
+// ============================================================
+// Picking
+// ============================================================
+/// The nearest atom hit by a ray, treating each atom as a sphere of the radius it's
+/// drawn with (`in_radius`, see radii.rs), along with the distance along the ray to
+/// where it's hit. Hits for which `in_is_visible` returns false (e.g. points clipped
+/// away by the slab) are passed through, so the atom behind can be picked.
+pub fn pick<R, F>(in_molecule : &Molecule, in_radius : R, in_origin : &[f32;3], in_direction : &[f32;3],
+                  in_is_visible : F) -> Option<(usize, f32)>
+                  where R : Fn(&Atom) -> f32, F : Fn(&[f32;3]) -> bool
+{
+    let mut nearest : Option<(usize, f32)> = None;
+    for (i, atom) in in_molecule.atoms().iter().enumerate() {
+        let t = match intersect_sphere(in_origin, in_direction, atom.position(), &in_radius(atom)) {
+            Some(t) => t,
+            None => continue,
+        };
+        if nearest.map_or(false, |(_, nearest_t)| nearest_t <= t) {continue}
+        let point = [
+            in_origin[0]+t*in_direction[0],
+            in_origin[1]+t*in_direction[1],
+            in_origin[2]+t*in_direction[2],
+        ];
+        if in_is_visible(&point) {
+            nearest = Some((i, t));
+        }
+    }
+    nearest
+}
+
+/// How far along a ray (with a unit direction) it enters a sphere, or None if it
+/// misses, or the sphere is entirely behind the origin. Rays starting inside a
+/// sphere hit it at 0.
+fn intersect_sphere(in_origin : &[f32;3], in_direction : &[f32;3],
+                    in_centre : &[f32;3], in_radius : &f32) -> Option<f32> {
+    let offset = [
+        in_centre[0]-in_origin[0],
+        in_centre[1]-in_origin[1],
+        in_centre[2]-in_origin[2],
+    ];
+    // the distance along the ray to the point nearest the centre
+    let along = offset[0]*in_direction[0]+offset[1]*in_direction[1]+offset[2]*in_direction[2];
+    let distance_squared = offset[0]*offset[0]+offset[1]*offset[1]+offset[2]*offset[2] - along*along;
+    let radius_squared = in_radius*in_radius;
+    if distance_squared > radius_squared {return None}
+    let half_chord = (radius_squared-distance_squared).sqrt();
+    if along+half_chord < 0.0 {
+        None
+    } else {
+        Some((along-half_chord).max(0.0))
+    }
+}
@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+use camera::{Camera, Projection};
+use raytrace::Shape;
+
+// ============================================================
+// POV-Ray
+// ============================================================
+/// Writes the frame as a POV-Ray scene (export povray, or ctrl+R), for figures
+/// finished there: each atom as a sphere and each stick as a cylinder, in the
+/// colours and opacities they're drawn in, with the camera, light and background
+/// of the view. Everything is in world space, and the camera's right, up and
+/// direction vectors are the view's, so the picture comes out the same way round
+/// whichever way the world's axes go. Rendered at the window's size (as the comment
+/// at the top says), it lines up with the screen.
+pub fn write_scene(in_fname : &str, in_shapes : &[Shape], in_camera : &Camera, in_light : &[f32;3],
+                   in_background : &[f32;3]) -> Result<(), String> {
+    let file = File::create(in_fname).map_err(|e| format!("couldn't create {}: {}", in_fname, e))?;
+    let mut file = BufWriter::new(file);
+    write_pov(&mut file, in_shapes, in_camera, in_light, in_background)
+        .and_then(|_| file.flush())
+        .map_err(|e| format!("couldn't write to {}: {}", in_fname, e))
+}
+
+fn write_pov<W : Write>(in_file : &mut W, in_shapes : &[Shape], in_camera : &Camera, in_light : &[f32;3],
+                        in_background : &[f32;3]) -> ::std::io::Result<()> {
+    let (w, h) = (in_camera.screen_size()[0].max(1), in_camera.screen_size()[1].max(1));
+    writeln!(in_file, "// {} spheres, {} cylinders and {} cones, from oxide",
+             in_shapes.iter().filter(|shape| match **shape {Shape::Sphere {..} => true, _ => false}).count(),
+             in_shapes.iter().filter(|shape| match **shape {Shape::Stick {..} => true, _ => false}).count(),
+             in_shapes.iter().filter(|shape| match **shape {Shape::Cone {..} => true, _ => false}).count())?;
+    writeln!(in_file, "// render with e.g. povray +I<this file> +W{} +H{} +A", w, h)?;
+    writeln!(in_file, "#version 3.7;")?;
+    writeln!(in_file, "global_settings {{ assumed_gamma 1.0 }}")?;
+    writeln!(in_file)?;
+
+    // the view's axes in world space: x to the right, y up and z into the screen
+    let to_world = in_camera.view_matrix().rigid_inverse();
+    let world = |v : [f32;4]| {
+        let v = to_world*v;
+        [v[0], v[1], v[2]]
+    };
+    let location = world([0.0, 0.0, 0.0, 1.0]);
+    let (right, up, direction) = (world([1.0, 0.0, 0.0, 0.0]), world([0.0, 1.0, 0.0, 0.0]), world([0.0, 0.0, 1.0, 0.0]));
+    // the smaller screen dimension spans the field of view (at the focus, for
+    // orthographic views)
+    let span = 2.0*(in_camera.field_of_view()/2.0).tan();
+    let span = match *in_camera.projection() {
+        Projection::Perspective  => span,
+        Projection::Orthographic => span*in_camera.r(),
+    };
+    let shortest = w.min(h) as f32;
+    let (width, height) = (span*w as f32/shortest, span*h as f32/shortest);
+    writeln!(in_file, "camera {{")?;
+    if *in_camera.projection() == Projection::Orthographic {writeln!(in_file, "  orthographic")?}
+    writeln!(in_file, "  location {}", vector(&location))?;
+    writeln!(in_file, "  right {}", vector(&scaled(&right, &width)))?;
+    writeln!(in_file, "  up {}", vector(&scaled(&up, &height)))?;
+    writeln!(in_file, "  direction {}", vector(&direction))?;
+    writeln!(in_file, "}}")?;
+    writeln!(in_file)?;
+
+    writeln!(in_file, "background {{ color rgb {} }}", vector(in_background))?;
+    // the light, and a fainter one at the camera so the shadowed sides aren't black
+    writeln!(in_file, "light_source {{ {} color rgb 1 }}", vector(in_light))?;
+    writeln!(in_file, "light_source {{ {} color rgb 0.3 shadowless }}", vector(&location))?;
+    writeln!(in_file)?;
+    writeln!(in_file, "#declare Atom_Finish = finish {{ ambient 0.1 diffuse 0.8 specular 0.4 roughness 0.02 }}")?;
+    writeln!(in_file, "#declare Stick_Finish = finish {{ ambient 0.1 diffuse 0.8 specular 0.2 roughness 0.05 }}")?;
+    writeln!(in_file)?;
+
+    for shape in in_shapes {
+        match *shape {
+            Shape::Sphere {centre, radius, colour, opacity} => {
+                if opacity <= 0.0 || radius <= 0.0 {continue}
+                writeln!(in_file, "sphere {{ {}, {:.4} pigment {{ {} }} finish {{ Atom_Finish }} }}",
+                         vector(&centre), radius, pigment(&colour, &opacity))?;
+            },
+            // (POV-Ray won't have cylinders with no length)
+            Shape::Stick {from, to, radius, colour, opacity} => {
+                if opacity <= 0.0 || radius <= 0.0 || from == to {continue}
+                writeln!(in_file, "cylinder {{ {}, {}, {:.4} open pigment {{ {} }} finish {{ Stick_Finish }} }}",
+                         vector(&from), vector(&to), radius, pigment(&colour, &opacity))?;
+            },
+            Shape::Cone {base, tip, radius, colour, opacity} => {
+                if opacity <= 0.0 || radius <= 0.0 || base == tip {continue}
+                writeln!(in_file, "cone {{ {}, {:.4}, {}, 0 pigment {{ {} }} finish {{ Stick_Finish }} }}",
+                         vector(&base), radius, vector(&tip), pigment(&colour, &opacity))?;
+            },
+        }
+    }
+    Ok(())
+}
+
+fn vector(in_v : &[f32;3]) -> String {
+    format!("<{:.4}, {:.4}, {:.4}>", in_v[0], in_v[1], in_v[2])
+}
+
+/// A colour, with whatever isn't opaque let through.
+fn pigment(in_colour : &[f32;3], in_opacity : &f32) -> String {
+    if *in_opacity >= 1.0 {
+        format!("color rgb {}", vector(in_colour))
+    } else {
+        format!("color rgbt <{:.4}, {:.4}, {:.4}, {:.4}>", in_colour[0], in_colour[1], in_colour[2], 1.0-in_opacity)
+    }
+}
+
+fn scaled(in_v : &[f32;3], in_s : &f32) -> [f32;3] {[in_v[0]*in_s, in_v[1]*in_s, in_v[2]*in_s]}
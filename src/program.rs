@@ -1,144 +1,233 @@
 extern crate glium;
 
+use std::cell::RefCell;
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::PathBuf;
+
+/// The shaders' files, in the shader directory (see shader_directory()), each with
+/// the copy built in, which is used when the directory isn't there (e.g. with the
+/// program copied elsewhere):
+///
+/// polyhedron: lit meshes, coloured as a whole or (for the surface) point by point
+/// sphere: the atoms, as impostors on quads
+/// ellipsoid: the polyhedron vertex shader, but with the normals transformed by
+///     their own matrix, since an ellipsoid's model matrix stretches the sphere
+///     unevenly. Drawn with the polyhedron fragment shaders.
+/// line: unlit lines in a flat colour, e.g. the edges of the unit cell, depth cued
+///     and cut by the slab like everything else
+///
+/// The fragment shaders are each compiled three ways: as they are, with TRANSLUCENT
+/// defined (for the order-independent transparency buffers) and with PICKING defined
+/// (for the picking pass). The sphere and polyhedron shaders are compiled once more
+/// with INSTANCED defined, for the opaque atoms and sticks of big structures, each
+/// kind drawn in one go (see batch.rs).
+const SHADERS : [(&'static str, &'static str); 7] = [
+    ("polyhedron.vert.glsl", include_str!("../shaders/polyhedron.vert.glsl")),
+    ("polyhedron.frag.glsl", include_str!("../shaders/polyhedron.frag.glsl")),
+    ("sphere.vert.glsl",     include_str!("../shaders/sphere.vert.glsl")),
+    ("sphere.frag.glsl",     include_str!("../shaders/sphere.frag.glsl")),
+    ("ellipsoid.vert.glsl",  include_str!("../shaders/ellipsoid.vert.glsl")),
+    ("line.vert.glsl",       include_str!("../shaders/line.vert.glsl")),
+    ("line.frag.glsl",       include_str!("../shaders/line.frag.glsl")),
+];
+
+/// Where the shaders are read from: $OXIDE_SHADERS, or else the shaders directory
+/// of the source tree.
+pub fn shader_directory() -> PathBuf {
+    match env::var_os("OXIDE_SHADERS") {
+        Some(directory) => PathBuf::from(directory),
+        None => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("shaders"),
+    }
+}
+
+/// The shaders' files, to watch for changes, or none if the shader directory isn't
+/// there and the built-in copies are used.
+pub fn shader_files() -> Vec<String> {
+    let directory = shader_directory();
+    if !directory.is_dir() {return Vec::new()}
+    SHADERS.iter().map(|&(name, _)| directory.join(name).to_string_lossy().into_owned()).collect()
+}
+
+/// Each shader's source, from its file if the shader directory is there, or else
+/// built in.
+fn read_sources() -> Result<Vec<String>, String> {
+    let directory = shader_directory();
+    if !directory.is_dir() {
+        return Ok(SHADERS.iter().map(|&(_, source)| source.to_owned()).collect());
+    }
+    SHADERS.iter().map(|&(name, _)| {
+        let path = directory.join(name);
+        let mut file = File::open(&path).map_err(|e| format!("couldn't open {}: {}", path.display(), e))?;
+        let mut source = String::new();
+        file.read_to_string(&mut source).map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+        Ok(source)
+    }).collect()
+}
+
+/// The programs models are drawn with. Each is kept in a RefCell so it can be made
+/// again from the shaders' files (see reload()) while the models still point at it.
 pub struct DefaultPrograms {
-    _polyhedron : glium::Program,
-    _sphere     : glium::Program,
+    _polyhedron             : RefCell<glium::Program>,
+    _sphere                 : RefCell<glium::Program>,
+    _polyhedron_translucent : RefCell<glium::Program>,
+    _sphere_translucent     : RefCell<glium::Program>,
+    _polyhedron_picking     : RefCell<glium::Program>,
+    _sphere_picking         : RefCell<glium::Program>,
+    _ellipsoid              : RefCell<glium::Program>,
+    _ellipsoid_translucent  : RefCell<glium::Program>,
+    _ellipsoid_picking      : RefCell<glium::Program>,
+    _line                   : RefCell<glium::Program>,
+    _line_translucent       : RefCell<glium::Program>,
+    _line_picking           : RefCell<glium::Program>,
+    _surface                : RefCell<glium::Program>,
+    _surface_translucent    : RefCell<glium::Program>,
+    _sphere_instanced       : RefCell<glium::Program>,
+    _polyhedron_instanced   : RefCell<glium::Program>,
 }
 
 impl DefaultPrograms {
+    /// Compiles the shaders in the shader directory, or if they're missing or don't
+    /// compile, the built-in ones.
     pub fn new(in_display : &glium::backend::glutin_backend::GlutinFacade) -> DefaultPrograms {
-        
-        // ====================
-        // Polyhedron shaders
-        // ====================
-        // Vertex shader in OpenGL v140 (written in GLSL)
-        let vertex_shader_polyhedron : &'static str = r#"
-            #version 140
-
-            uniform mat4 mv_matrix;
-            uniform mat4 mvp_matrix;
-            uniform vec4 light_position;
-
-            in vec4 _position;
-            in vec4 _normal;
-
-            out vec3 fragment_normal;
-            out vec3 fragment_light_vector;
-
-            void main() {
-                vec4 position = _position*mv_matrix;
-                vec4 normal = normalize(_normal*mv_matrix);
-                vec4 light_vector = light_position-position;
-
-                fragment_normal = vec3(normal[0],normal[1],normal[2]);
-                fragment_light_vector = vec3(light_vector[0],light_vector[1],light_vector[2]);
-
-                gl_Position = _position*mvp_matrix;
-            }
-        "#;
-
-        // Fragment/Pixel shader in OpenGL v140 (written in GLSL)
-        let fragment_shader_polyhedron : &'static str = r#"
-            #version 140
-
-            uniform vec3 colour;
-
-            in vec3 fragment_normal;
-            in vec3 fragment_light_vector;
-
-            out vec4 color;
-
-            void main() {
-                float normal_squared = dot(fragment_normal,fragment_normal);
-                float light_distance_squared = dot(fragment_light_vector,fragment_light_vector);
-                float cos_light_angle = clamp (
-                    dot(fragment_normal,fragment_light_vector)
-                        * inversesqrt(light_distance_squared*normal_squared),
-                    0,
-                    1
-                );
-                vec3 colour3 = colour*(cos_light_angle/light_distance_squared+0.2);
-                color = vec4((colour3), 1.0);
-            }
-        "#;
-
-        // ====================
-        // Sphere shaders
-        // ====================
-        // Vertex shader in OpenGL v140 (written in GLSL)
-        let vertex_shader_sphere : &'static str = r#"
-            #version 140
-
-            uniform mat4 mv_matrix;
-            uniform mat4 mvp_matrix;
-            uniform vec4 light_position;
-
-            in vec4 _position;
-            in vec4 _normal;
-            
-            out vec2 fragment_xy;
-            out vec3 fragment_light_vector;
-
-            void main() {
-                vec4 position = _position*mv_matrix;
-                vec4 light_vector = light_position-position;
-                
-                fragment_xy = vec2(_normal[0],_normal[1]);
-                fragment_light_vector = vec3(light_vector[0],light_vector[1],light_vector[2]);
-
-                gl_Position = _position*mvp_matrix;
-            }
-        "#;
-
-        // Fragment/Pixel shader in OpenGL v140 (written in GLSL)
-        let fragment_shader_sphere : &'static str = r#"
-            #version 140
-
-            uniform vec3 colour;
-            uniform float size;
-            
-            in vec2 fragment_xy;
-            in vec3 fragment_light_vector;
-
-            out vec4 color;
-
-            void main() {
-                float xy_squared = dot(fragment_xy,fragment_xy);
-                if (xy_squared > 1)
-                    discard;
-                vec3 normal = vec3(fragment_xy[0],fragment_xy[1],-sqrt(1-xy_squared));
-                vec3 light_vector = vec3 (
-                    fragment_light_vector[0],
-                    fragment_light_vector[1],
-                    fragment_light_vector[2]-size*normal[2]
-                );
-                float light_distance_squared = dot(light_vector,light_vector);
-                float cos_light_angle = clamp (
-                    dot(normal,light_vector) * inversesqrt(light_distance_squared),
-                    0,
-                    1
-                );
-                vec3 colour3 = colour*(cos_light_angle/light_distance_squared+0.2);
-                color = vec4(colour3, 1.0);
-            }
-        "#;
-        
+        let programs = read_sources().and_then(|sources| compile(in_display, &sources)).unwrap_or_else(|message| {
+            println!("Using the built-in shaders: {}", message);
+            let sources : Vec<String> = SHADERS.iter().map(|&(_, source)| source.to_owned()).collect();
+            compile(in_display, &sources).unwrap()
+        });
+        let mut programs = programs.into_iter().map(RefCell::new);
+        let mut next = || programs.next().unwrap();
         DefaultPrograms {
-            _polyhedron : glium::Program::from_source(
-                in_display,
-                vertex_shader_polyhedron,
-                fragment_shader_polyhedron,
-                None
-            ).unwrap(),
-            _sphere : glium::Program::from_source(
-                in_display,
-                vertex_shader_sphere,
-                fragment_shader_sphere,
-                None
-            ).unwrap(),
+            _polyhedron             : next(),
+            _sphere                 : next(),
+            _polyhedron_translucent : next(),
+            _sphere_translucent     : next(),
+            _polyhedron_picking     : next(),
+            _sphere_picking         : next(),
+            _ellipsoid              : next(),
+            _ellipsoid_translucent  : next(),
+            _ellipsoid_picking      : next(),
+            _line                   : next(),
+            _line_translucent       : next(),
+            _line_picking           : next(),
+            _surface                : next(),
+            _surface_translucent    : next(),
+            _sphere_instanced       : next(),
+            _polyhedron_instanced   : next(),
+        }
+    }
+
+    /// Reads the shaders' files again and compiles them, in place of the programs
+    /// there are. If any doesn't compile, none are replaced, and the compiler's
+    /// message is given back.
+    pub fn reload(&self, in_display : &glium::backend::glutin_backend::GlutinFacade) -> Result<(), String> {
+        let programs = read_sources().and_then(|sources| compile(in_display, &sources))?;
+        for (cell, program) in self.cells().iter().zip(programs) {
+            *cell.borrow_mut() = program;
         }
+        Ok(())
     }
 
-    pub fn polyhedron(&self) -> &glium::Program {&self._polyhedron}
-    pub fn sphere(&self) -> &glium::Program {&self._sphere}
+    /// The programs, in the order compile() makes them.
+    fn cells(&self) -> [&RefCell<glium::Program>; 16] {
+        [
+            &self._polyhedron, &self._sphere, &self._polyhedron_translucent, &self._sphere_translucent,
+            &self._polyhedron_picking, &self._sphere_picking, &self._ellipsoid, &self._ellipsoid_translucent,
+            &self._ellipsoid_picking, &self._line, &self._line_translucent, &self._line_picking,
+            &self._surface, &self._surface_translucent, &self._sphere_instanced, &self._polyhedron_instanced,
+        ]
+    }
+
+    pub fn polyhedron(&self) -> &RefCell<glium::Program> {&self._polyhedron}
+    pub fn sphere(&self) -> &RefCell<glium::Program> {&self._sphere}
+    pub fn polyhedron_translucent(&self) -> &RefCell<glium::Program> {&self._polyhedron_translucent}
+    pub fn sphere_translucent(&self) -> &RefCell<glium::Program> {&self._sphere_translucent}
+    pub fn polyhedron_picking(&self) -> &RefCell<glium::Program> {&self._polyhedron_picking}
+    pub fn sphere_picking(&self) -> &RefCell<glium::Program> {&self._sphere_picking}
+    pub fn ellipsoid(&self) -> &RefCell<glium::Program> {&self._ellipsoid}
+    pub fn ellipsoid_translucent(&self) -> &RefCell<glium::Program> {&self._ellipsoid_translucent}
+    pub fn ellipsoid_picking(&self) -> &RefCell<glium::Program> {&self._ellipsoid_picking}
+    pub fn line(&self) -> &RefCell<glium::Program> {&self._line}
+    pub fn line_translucent(&self) -> &RefCell<glium::Program> {&self._line_translucent}
+    pub fn line_picking(&self) -> &RefCell<glium::Program> {&self._line_picking}
+    pub fn surface(&self) -> &RefCell<glium::Program> {&self._surface}
+    pub fn surface_translucent(&self) -> &RefCell<glium::Program> {&self._surface_translucent}
+    pub fn sphere_instanced(&self) -> &RefCell<glium::Program> {&self._sphere_instanced}
+    pub fn polyhedron_instanced(&self) -> &RefCell<glium::Program> {&self._polyhedron_instanced}
+}
+
+/// Every program from the shaders' sources (in the order of SHADERS), in the order
+/// of DefaultPrograms' fields, or the first compiler error.
+fn compile(in_display : &glium::backend::glutin_backend::GlutinFacade, in_sources : &[String])
+           -> Result<Vec<glium::Program>, String> {
+    let (vertex_polyhedron, fragment_polyhedron) = (&in_sources[0], &in_sources[1]);
+    let (vertex_sphere, fragment_sphere) = (&in_sources[2], &in_sources[3]);
+    let vertex_ellipsoid = &in_sources[4];
+    let (vertex_line, fragment_line) = (&in_sources[5], &in_sources[6]);
+
+    // ====================
+    // Surface variants
+    // ====================
+    // The polyhedron shaders with VERTEX_COLOUR defined, taking each vertex's
+    // colour rather than one for the whole mesh.
+    let vertex_surface = vertex_colour(vertex_polyhedron);
+    let fragment_surface = vertex_colour(fragment_polyhedron);
+
+    // ====================
+    // Instanced variants
+    // ====================
+    // The sphere shaders and the polyhedron vertex shader with INSTANCED defined,
+    // taking each atom's or stick's place, size and colour from the instance buffers.
+    // The sticks' colours reach the fragment shader as the surface's do.
+    let vertex_sphere_instanced = instanced(vertex_sphere);
+    let vertex_polyhedron_instanced = instanced(vertex_polyhedron);
+
+    // each program's vertex and fragment shaders, by their files' indices in SHADERS
+    // (for the compiler's errors), and their sources
+    let (polyhedron, sphere, ellipsoid, line) = ((0, 1), (2, 3), (4, 1), (5, 6));
+    let programs : [((usize, usize), &str, String); 16] = [
+        (polyhedron, vertex_polyhedron, fragment_polyhedron.to_owned()),
+        (sphere, vertex_sphere, fragment_sphere.to_owned()),
+        (polyhedron, vertex_polyhedron, translucent(fragment_polyhedron)),
+        (sphere, vertex_sphere, translucent(fragment_sphere)),
+        (polyhedron, vertex_polyhedron, picking(fragment_polyhedron)),
+        (sphere, vertex_sphere, picking(fragment_sphere)),
+        (ellipsoid, vertex_ellipsoid, fragment_polyhedron.to_owned()),
+        (ellipsoid, vertex_ellipsoid, translucent(fragment_polyhedron)),
+        (ellipsoid, vertex_ellipsoid, picking(fragment_polyhedron)),
+        (line, vertex_line, fragment_line.to_owned()),
+        (line, vertex_line, translucent(fragment_line)),
+        (line, vertex_line, picking(fragment_line)),
+        (polyhedron, &vertex_surface, fragment_surface.to_owned()),
+        (polyhedron, &vertex_surface, translucent(&fragment_surface)),
+        (sphere, &vertex_sphere_instanced, instanced(fragment_sphere)),
+        (polyhedron, &vertex_polyhedron_instanced, fragment_surface.to_owned()),
+    ];
+    programs.iter().map(|&((vertex_file, fragment_file), vertex, ref fragment)| {
+        glium::Program::from_source(in_display, vertex, fragment, None).map_err(|e| {
+            format!("{} with {} didn't compile: {}", SHADERS[vertex_file].0, SHADERS[fragment_file].0, e)
+        })
+    }).collect()
+}
+
+// ====================
+// Variants
+// ====================
+// The same shaders, but with TRANSLUCENT, PICKING, VERTEX_COLOUR or INSTANCED defined
+// straight after the version line.
+fn translucent(in_shader : &str) -> String {
+    in_shader.replacen("#version 140", "#version 140\n#define TRANSLUCENT", 1)
+}
+
+fn picking(in_shader : &str) -> String {
+    in_shader.replacen("#version 140", "#version 140\n#define PICKING", 1)
+}
+
+fn vertex_colour(in_shader : &str) -> String {
+    in_shader.replacen("#version 140", "#version 140\n#define VERTEX_COLOUR", 1)
+}
+
+fn instanced(in_shader : &str) -> String {
+    in_shader.replacen("#version 140", "#version 140\n#define INSTANCED", 1)
 }
@@ -0,0 +1,150 @@
+use glium::Surface;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Instant;
+
+use text::{self, TextSystem};
+
+/// The window's title, without any progress in it.
+pub const TITLE : &'static str = "Oxide: Molecular Visualisation";
+
+/// How wide the bars are, in pixels.
+const BAR_WIDTH : f32 = 400.0;
+
+// ============================================================
+// Progress
+// ============================================================
+/// How far a long task has got, shared between whatever's doing it (another
+/// thread, say) and the viewer drawing its bar: the steps done out of how many, and
+/// whether the user has cancelled it, which the task checks every so often.
+#[derive(Clone)]
+pub struct Progress {
+    _done      : Arc<AtomicUsize>,
+    _total     : Arc<AtomicUsize>,
+    _cancelled : Arc<AtomicBool>,
+}
+
+impl Progress {
+    /// None done out of `in_total` steps, or out of an unknown number with 0.
+    pub fn new(in_total : &usize) -> Progress {
+        Progress {
+            _done      : Arc::new(AtomicUsize::new(0)),
+            _total     : Arc::new(AtomicUsize::new(*in_total)),
+            _cancelled : Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn set(&self, in_done : &usize, in_total : &usize) {
+        self._total.store(*in_total, Ordering::Relaxed);
+        self._done.store(*in_done, Ordering::Relaxed);
+    }
+
+    /// The fraction done, or None if how much there is to do isn't known.
+    pub fn fraction(&self) -> Option<f32> {
+        let total = self._total.load(Ordering::Relaxed);
+        if total == 0 {return None}
+        Some((self._done.load(Ordering::Relaxed) as f32/total as f32).min(1.0))
+    }
+
+    pub fn cancel(&self) {self._cancelled.store(true, Ordering::Relaxed)}
+    pub fn is_cancelled(&self) -> bool {self._cancelled.load(Ordering::Relaxed)}
+}
+
+// ============================================================
+// Tasks
+// ============================================================
+/// The long tasks there are, so a click on one's bar cancels the right one.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TaskKind {
+    Loading,
+    Surface,
+    Movie,
+    Optimiser,
+    Trace,
+}
+
+/// A long task as the progress panel shows it.
+pub struct Task {
+    _kind        : TaskKind,
+    _label       : String,
+    _fraction    : Option<f32>,
+    _start       : Instant,
+    _cancellable : bool,
+}
+
+impl Task {
+    pub fn new(in_kind : &TaskKind, in_label : &str, in_fraction : &Option<f32>, in_start : &Instant,
+               in_cancellable : &bool) -> Task {
+        Task {
+            _kind        : *in_kind,
+            _label       : in_label.to_owned(),
+            _fraction    : *in_fraction,
+            _start       : *in_start,
+            _cancellable : *in_cancellable,
+        }
+    }
+
+    pub fn kind(&self) -> &TaskKind {&self._kind}
+
+    /// The label, how far it's got and for how long, e.g. "Making the surface: 42% (12 s)".
+    fn text(&self) -> String {
+        let seconds = self._start.elapsed().as_secs();
+        let mut text = match self._fraction {
+            Some(fraction) => format!("{}: {:.0}% ({} s)", self._label, 100.0*fraction, seconds),
+            None => format!("{} ({} s)", self._label, seconds),
+        };
+        if self._cancellable {text.push_str(". Click to cancel")}
+        text
+    }
+}
+
+/// Draws a panel for each task, one under the other down the middle from `in_top`,
+/// each with a bar filling up as it goes (or, when how far it's got isn't known, a
+/// block going back and forth), and gives where each cancellable task's panel is,
+/// to click.
+pub fn draw<T>(in_text_system : &TextSystem, in_target : &mut T, in_top : &f32, in_tasks : &[Task])
+               -> Vec<(TaskKind, [f32;2], [f32;2])> where T : Surface {
+    let (w, _) = in_target.get_dimensions();
+    let mut buttons = Vec::new();
+    let mut top = *in_top;
+    for task in in_tasks {
+        let line = task.text();
+        let size = text::text_size(&line, 2.0);
+        let width = size[0].max(BAR_WIDTH);
+        let corner = [(w as f32 - width)/2.0 - 10.0, top];
+        let panel_size = [width+20.0, size[1]+40.0];
+        let colour = if task._cancellable {[0.6, 0.15, 0.1, 0.85]} else {[0.0, 0.0, 0.0, 0.75]};
+        text::draw_panel(in_text_system, in_target, corner, panel_size, colour);
+        text::draw_text(in_text_system, in_target, &line, [corner[0]+10.0, corner[1]+10.0], 2.0, [1.0, 1.0, 1.0, 1.0]);
+        let bar_corner = [corner[0]+10.0, corner[1]+size[1]+18.0];
+        text::draw_panel(in_text_system, in_target, bar_corner, [width, 12.0], [1.0, 1.0, 1.0, 0.25]);
+        let (from, to) = match task._fraction {
+            Some(fraction) => (0.0, fraction),
+            None => {
+                let t = task._start.elapsed().subsec_nanos() as f32*1e-9;
+                let x = 0.8*(1.0 - (2.0*t - 1.0).abs());
+                (x, x+0.2)
+            },
+        };
+        text::draw_panel(in_text_system, in_target, [bar_corner[0]+from*width, bar_corner[1]],
+                         [(to-from)*width, 12.0], [1.0, 1.0, 1.0, 0.9]);
+        if task._cancellable {buttons.push((task._kind, corner, panel_size))}
+        top += panel_size[1] + 10.0;
+    }
+    buttons
+}
+
+/// The window's title with how far the tasks have got in front, e.g. "[42%] Oxide:
+/// ...", which shows in the taskbar (or dock) while the window's hidden behind
+/// others. With several, the least far; "[...]" if that isn't known.
+pub fn window_title(in_tasks : &[Task]) -> String {
+    if in_tasks.is_empty() {return TITLE.to_owned()}
+    let fractions : Option<Vec<f32>> = in_tasks.iter().map(|task| task._fraction).collect();
+    match fractions.and_then(|fractions| fractions.into_iter().fold(None, |least : Option<f32>, f| {
+        Some(least.map_or(f, |least| least.min(f)))
+    })) {
+        Some(fraction) => format!("[{:.0}%] {}", 100.0*fraction, TITLE),
+        None => format!("[...] {}", TITLE),
+    }
+}
@@ -1,12 +1,14 @@
 use matrix::Matrix;
 
+use std::fmt;
 use std::ops::Mul; // multiplication overload
 
 // ============================================================
 // Quaternions
 // ============================================================
-#[derive(Copy,Clone,Debug)]
+#[derive(Copy,Clone,Debug,Serialize,Deserialize)]
 pub struct Quaternion {
+    #[serde(rename = "contents")]
     _contents : [f32;4],
 }
 
@@ -62,6 +64,39 @@ impl Quaternion {
         }
     }
 
+    /// Spherical linear interpolation from this rotation (t = 0) to another (t = 1),
+    /// at a constant angular speed and the short way round.
+    pub fn slerp(&self, in_other : &Quaternion, in_t : &f32) -> Quaternion {
+        let mut other = in_other._contents;
+        let mut cos_angle = 0.0;
+        for i in 0..4 {
+            cos_angle += self._contents[i]*other[i];
+        }
+        // q and -q are the same rotation, so pick whichever is nearer
+        if cos_angle < 0.0 {
+            cos_angle = -cos_angle;
+            for element in &mut other {
+                *element = -*element;
+            }
+        }
+        let (a, b) = if cos_angle > 0.9995 {
+            // nearly parallel, where slerp is numerically unstable and lerp is as good
+            (1.0-in_t, in_t.to_owned())
+        } else {
+            let angle = cos_angle.acos();
+            (((1.0-in_t)*angle).sin()/angle.sin(), (in_t*angle).sin()/angle.sin())
+        };
+        let c = &self._contents;
+        let mut result = Quaternion::new(
+            &(a*c[0]+b*other[0]),
+            &(a*c[1]+b*other[1]),
+            &(a*c[2]+b*other[2]),
+            &(a*c[3]+b*other[3]),
+        );
+        result.normalise();
+        result
+    }
+
     #[allow(dead_code)]
     pub fn right_multiply(&mut self, in_other : &Quaternion) {
         let sr : f32 = self.r().to_owned();
@@ -99,6 +134,13 @@ impl Quaternion {
     }
 }
 
+/// e.g. "0.7071 +0.7071i +0.0000j +0.0000k".
+impl fmt::Display for Quaternion {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.4} {:+.4}i {:+.4}j {:+.4}k", self.r(), self.i(), self.j(), self.k())
+    }
+}
+
 impl Mul<Quaternion> for Quaternion {
     type Output = Quaternion;
     fn mul (self, in_other : Quaternion) -> Quaternion {
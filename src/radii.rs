@@ -0,0 +1,104 @@
+use bonds;
+use species::Species;
+
+// ============================================================
+// Van der Waals Radii
+// ============================================================
+/// Van der Waals radii in Angstroms (Bondi, J. Phys. Chem., 1964), for the elements
+/// likely to turn up in a structure file. Others are taken to be VDW_RADIUS.
+const VDW_RADII : [(&'static str, f32); 12] = [
+    ("H" , 1.20), ("C" , 1.70), ("N" , 1.55), ("O" , 1.52), ("F" , 1.47), ("Na", 2.27),
+    ("Mg", 1.73), ("P" , 1.80), ("S" , 1.80), ("Cl", 1.75), ("K" , 2.75), ("Ni", 1.63),
+];
+const VDW_RADIUS : f32 = 1.8;
+
+pub fn vdw_radius(in_symbol : &str) -> f32 {
+    VDW_RADII.iter().find(|&&(symbol, _)| symbol == in_symbol).map_or(VDW_RADIUS, |&(_, radius)| radius)
+}
+
+/// The radius atoms of elements with no covalent radius (see bonds.rs) are drawn
+/// with, when the covalent radii are.
+const COVALENT_RADIUS : f32 = 1.5;
+
+/// The least and most the radii can be scaled by.
+pub const MIN_SCALE : f32 = 0.05;
+pub const MAX_SCALE : f32 = 5.0;
+
+// ============================================================
+// Atom Radii
+// ============================================================
+/// Which radii the atoms are drawn with.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum RadiusSource {
+    /// Each species' own size, small enough for the bonds to show (ball and stick).
+    Species,
+    /// The covalent radii bonds are found with.
+    Covalent,
+    /// The van der Waals radii, the atoms touching (space filling).
+    VanDerWaals,
+}
+
+impl RadiusSource {
+    pub fn next(&self) -> RadiusSource {
+        match *self {
+            RadiusSource::Species     => RadiusSource::Covalent,
+            RadiusSource::Covalent    => RadiusSource::VanDerWaals,
+            RadiusSource::VanDerWaals => RadiusSource::Species,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            RadiusSource::Species     => "species",
+            RadiusSource::Covalent    => "covalent",
+            RadiusSource::VanDerWaals => "vdw",
+        }
+    }
+
+    /// The source with a name (as name() gives it), e.g. for --radii.
+    pub fn parse(in_name : &str) -> Result<RadiusSource, String> {
+        [RadiusSource::Species, RadiusSource::Covalent, RadiusSource::VanDerWaals].iter()
+            .find(|source| source.name() == in_name.to_lowercase()).cloned()
+            .ok_or_else(|| format!("expected species, covalent or vdw, not \"{}\"", in_name))
+    }
+}
+
+/// How big the atoms are drawn: their radii from one of the sources, all scaled by
+/// the same factor, so the same structure can be drawn as balls and sticks or
+/// space filling. Everything that draws or picks an atom takes its radius from here
+/// rather than from its species.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct AtomRadii {
+    _source : RadiusSource,
+    _scale  : f32,
+}
+
+impl AtomRadii {
+    pub fn new(in_source : &RadiusSource, in_scale : &f32) -> AtomRadii {
+        AtomRadii {
+            _source : *in_source,
+            _scale  : in_scale.max(MIN_SCALE).min(MAX_SCALE),
+        }
+    }
+
+    pub fn source(&self) -> &RadiusSource {&self._source}
+    pub fn scale(&self) -> &f32 {&self._scale}
+
+    pub fn set_source(&mut self, in_source : &RadiusSource) {self._source = *in_source}
+    pub fn set_scale(&mut self, in_scale : &f32) {self._scale = in_scale.max(MIN_SCALE).min(MAX_SCALE)}
+
+    /// The radius a species' atoms are drawn with.
+    pub fn radius(&self, in_species : &Species) -> f32 {
+        self._scale*match self._source {
+            RadiusSource::Species     => *in_species.size(),
+            RadiusSource::Covalent    => bonds::covalent_radius(in_species.symbol()).unwrap_or(COVALENT_RADIUS),
+            RadiusSource::VanDerWaals => vdw_radius(in_species.symbol()),
+        }
+    }
+
+    /// What an atom's model matrix (which is scaled by its species' size) is scaled
+    /// by to draw it with its radius.
+    pub fn factor(&self, in_species : &Species) -> f32 {
+        self.radius(in_species)/in_species.size()
+    }
+}
@@ -0,0 +1,464 @@
+use std::f32;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Instant;
+
+use camera::Camera;
+
+/// How many paths are traced through each pixel, unless asked for otherwise.
+pub const DEFAULT_SAMPLES : usize = 64;
+
+/// How many times a path bounces off surfaces before it's given up on.
+const MAX_BOUNCES : usize = 3;
+
+/// The light is a sphere this big (in Angstroms), so shadows have soft edges.
+const LIGHT_RADIUS : f32 = 1.0;
+
+/// How bright the light and the sky are. The sky lights whatever it can see, which
+/// is what darkens crevices (ambient occlusion).
+const LIGHT_BRIGHTNESS : f32 = 0.8;
+const SKY_BRIGHTNESS : f32 = 0.6;
+
+// ============================================================
+// Scene
+// ============================================================
+/// Something to trace: an atom's sphere, a stick (a bond, a piece of the tube, a
+/// dash), as an open cylinder whose ends are hidden in the atoms, or an arrow's head,
+/// as a cone closed at its base. Translucent ones let some of the paths through.
+#[derive(Copy, Clone, Debug)]
+pub enum Shape {
+    Sphere {centre : [f32;3], radius : f32, colour : [f32;3], opacity : f32},
+    Stick {from : [f32;3], to : [f32;3], radius : f32, colour : [f32;3], opacity : f32},
+    Cone {base : [f32;3], tip : [f32;3], radius : f32, colour : [f32;3], opacity : f32},
+}
+
+impl Shape {
+    fn colour(&self) -> &[f32;3] {
+        match *self {
+            Shape::Sphere {ref colour, ..} | Shape::Stick {ref colour, ..} | Shape::Cone {ref colour, ..} => colour,
+        }
+    }
+
+    fn opacity(&self) -> f32 {
+        match *self {
+            Shape::Sphere {opacity, ..} | Shape::Stick {opacity, ..} | Shape::Cone {opacity, ..} => opacity,
+        }
+    }
+
+    /// The corners of a box round it.
+    fn bounds(&self) -> ([f32;3], [f32;3]) {
+        match *self {
+            Shape::Sphere {centre, radius, ..} => {
+                ([centre[0]-radius, centre[1]-radius, centre[2]-radius],
+                 [centre[0]+radius, centre[1]+radius, centre[2]+radius])
+            },
+            Shape::Stick {from, to, radius, ..} | Shape::Cone {base : from, tip : to, radius, ..} => {
+                let mut low = [0.0f32;3];
+                let mut high = [0.0f32;3];
+                for k in 0..3 {
+                    low[k] = from[k].min(to[k]) - radius;
+                    high[k] = from[k].max(to[k]) + radius;
+                }
+                (low, high)
+            },
+        }
+    }
+
+    /// How far along a ray (with a unit direction) it's first hit, beyond `in_near`,
+    /// with the normal there.
+    fn hit(&self, in_origin : &[f32;3], in_direction : &[f32;3], in_near : &f32) -> Option<(f32, [f32;3])> {
+        let (o, d) = (in_origin, in_direction);
+        match *self {
+            Shape::Sphere {centre, radius, ..} => {
+                let oc = sub(o, &centre);
+                let b = dot(&oc, d);
+                let c = dot(&oc, &oc) - radius*radius;
+                let discriminant = b*b - c;
+                if discriminant < 0.0 {return None}
+                let root = discriminant.sqrt();
+                let t = if -b-root > *in_near {-b-root} else if -b+root > *in_near {-b+root} else {return None};
+                let p = along(o, d, &t);
+                Some((t, scale(&sub(&p, &centre), &(1.0/radius))))
+            },
+            Shape::Stick {from, to, radius, ..} => {
+                let axis = sub(&to, &from);
+                let length = dot(&axis, &axis).sqrt();
+                if length <= 0.0 {return None}
+                let axis = scale(&axis, &(1.0/length));
+                // the ray and its start, less their parts along the axis
+                let oc = sub(o, &from);
+                let d_perpendicular = sub(d, &scale(&axis, &dot(d, &axis)));
+                let oc_perpendicular = sub(&oc, &scale(&axis, &dot(&oc, &axis)));
+                let a = dot(&d_perpendicular, &d_perpendicular);
+                if a < 1e-12 {return None}
+                let b = dot(&d_perpendicular, &oc_perpendicular);
+                let c = dot(&oc_perpendicular, &oc_perpendicular) - radius*radius;
+                let discriminant = b*b - a*c;
+                if discriminant < 0.0 {return None}
+                let root = discriminant.sqrt();
+                for &t in &[(-b-root)/a, (-b+root)/a] {
+                    if t <= *in_near {continue}
+                    let p = along(o, d, &t);
+                    let height = dot(&sub(&p, &from), &axis);
+                    if height < 0.0 || height > length {continue}
+                    let normal = sub(&sub(&p, &from), &scale(&axis, &height));
+                    return Some((t, scale(&normal, &(1.0/radius))));
+                }
+                None
+            },
+            Shape::Cone {base, tip, radius, ..} => {
+                let axis = sub(&base, &tip);
+                let length = dot(&axis, &axis).sqrt();
+                if length <= 0.0 || radius <= 0.0 {return None}
+                let axis = scale(&axis, &(1.0/length));
+                // the side: points whose angle to the axis at the tip is the cone's
+                let cos2 = length*length/(length*length+radius*radius);
+                let oc = sub(o, &tip);
+                let (d_axis, oc_axis) = (dot(d, &axis), dot(&oc, &axis));
+                let a = d_axis*d_axis - cos2;
+                let b = d_axis*oc_axis - cos2*dot(d, &oc);
+                let c = oc_axis*oc_axis - cos2*dot(&oc, &oc);
+                let mut best : Option<(f32, [f32;3])> = None;
+                let discriminant = b*b - a*c;
+                if a.abs() > 1e-12 && discriminant >= 0.0 {
+                    let root = discriminant.sqrt();
+                    for &t in &[(-b-root)/a, (-b+root)/a] {
+                        if t <= *in_near || best.map_or(false, |(nearest, _)| t >= nearest) {continue}
+                        let cp = sub(&along(o, d, &t), &tip);
+                        let height = dot(&cp, &axis);
+                        if height < 0.0 || height > length {continue}
+                        let normal = sub(&scale(&cp, &cos2), &scale(&axis, &height));
+                        let size = dot(&normal, &normal).sqrt();
+                        if size > 0.0 {best = Some((t, scale(&normal, &(1.0/size))))}
+                    }
+                }
+                // the base
+                if d_axis.abs() > 1e-12 {
+                    let t = dot(&sub(&base, o), &axis)/d_axis;
+                    let off = sub(&along(o, d, &t), &base);
+                    if t > *in_near && dot(&off, &off) <= radius*radius && best.map_or(true, |(nearest, _)| t < nearest) {
+                        best = Some((t, axis));
+                    }
+                }
+                best
+            },
+        }
+    }
+}
+
+/// A bounding volume hierarchy over the shapes, so a ray only tests those in boxes
+/// it goes through.
+enum Node {
+    Leaf {low : [f32;3], high : [f32;3], shapes : Vec<usize>},
+    Split {low : [f32;3], high : [f32;3], children : Box<(Node, Node)>},
+}
+
+impl Node {
+    fn new(in_shapes : &Vec<Shape>, in_indices : Vec<usize>) -> Node {
+        let bounds : Vec<([f32;3], [f32;3])> = in_indices.iter().map(|&i| in_shapes[i].bounds()).collect();
+        let mut low = [f32::INFINITY;3];
+        let mut high = [f32::NEG_INFINITY;3];
+        for &(l, h) in &bounds {
+            for k in 0..3 {
+                low[k] = low[k].min(l[k]);
+                high[k] = high[k].max(h[k]);
+            }
+        }
+        if in_indices.len() <= 4 {return Node::Leaf {low, high, shapes : in_indices}}
+        // split along the longest side, half the shapes either side
+        let axis = (0..3).fold(0, |best, k| if high[k]-low[k] > high[best]-low[best] {k} else {best});
+        let centre = |i : usize| {
+            let (l, h) = in_shapes[i].bounds();
+            l[axis]+h[axis]
+        };
+        let mut indices = in_indices;
+        indices.sort_by(|&a, &b| centre(a).partial_cmp(&centre(b)).unwrap_or(::std::cmp::Ordering::Equal));
+        let upper = indices.split_off(indices.len()/2);
+        Node::Split {low, high, children : Box::new((Node::new(in_shapes, indices), Node::new(in_shapes, upper)))}
+    }
+
+    /// Whether a ray meets the box before `in_far`.
+    fn is_crossed(in_low : &[f32;3], in_high : &[f32;3], in_origin : &[f32;3], in_inverse : &[f32;3], in_far : &f32) -> bool {
+        let (mut near, mut far) = (0.0f32, *in_far);
+        for k in 0..3 {
+            let t0 = (in_low[k]-in_origin[k])*in_inverse[k];
+            let t1 = (in_high[k]-in_origin[k])*in_inverse[k];
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        near <= far
+    }
+
+    /// The nearest shape hit closer than `in_far`, with how far along and the normal.
+    fn nearest(&self, in_shapes : &Vec<Shape>, in_origin : &[f32;3], in_direction : &[f32;3],
+               in_inverse : &[f32;3], in_near : &f32, in_far : &f32) -> Option<(usize, f32, [f32;3])> {
+        match *self {
+            Node::Leaf {ref low, ref high, ref shapes} => {
+                if !Node::is_crossed(low, high, in_origin, in_inverse, in_far) {return None}
+                let mut best = None;
+                let mut far = *in_far;
+                for &i in shapes {
+                    if let Some((t, normal)) = in_shapes[i].hit(in_origin, in_direction, in_near) {
+                        if t < far {
+                            far = t;
+                            best = Some((i, t, normal));
+                        }
+                    }
+                }
+                best
+            },
+            Node::Split {ref low, ref high, ref children} => {
+                if !Node::is_crossed(low, high, in_origin, in_inverse, in_far) {return None}
+                let first = children.0.nearest(in_shapes, in_origin, in_direction, in_inverse, in_near, in_far);
+                let far = first.map_or(*in_far, |(_, t, _)| t);
+                children.1.nearest(in_shapes, in_origin, in_direction, in_inverse, in_near, &far).or(first)
+            },
+        }
+    }
+}
+
+/// The atoms and sticks of a frame, lit as the window lights them, for tracing.
+pub struct Scene {
+    _shapes     : Vec<Shape>,
+    _tree       : Option<Node>,
+    /// Where the light is, in world coordinates.
+    _light      : [f32;3],
+    _background : [f32;3],
+}
+
+impl Scene {
+    pub fn new(in_shapes : Vec<Shape>, in_light : &[f32;3], in_background : &[f32;3]) -> Scene {
+        let tree = if in_shapes.is_empty() {None} else {Some(Node::new(&in_shapes, (0..in_shapes.len()).collect()))};
+        Scene {
+            _shapes     : in_shapes,
+            _tree       : tree,
+            _light      : *in_light,
+            _background : *in_background,
+        }
+    }
+
+    fn nearest(&self, in_origin : &[f32;3], in_direction : &[f32;3], in_far : &f32) -> Option<(usize, f32, [f32;3])> {
+        let inverse = [1.0/in_direction[0], 1.0/in_direction[1], 1.0/in_direction[2]];
+        self._tree.as_ref()?.nearest(&self._shapes, in_origin, in_direction, &inverse, &1e-4, in_far)
+    }
+
+    /// Whether anything is in the way between two points. Translucent shapes block
+    /// the light by chance, as often as they're opaque.
+    fn is_blocked(&self, in_from : &[f32;3], in_to : &[f32;3], in_random : &mut Random) -> bool {
+        let mut origin = *in_from;
+        let mut distance = dot(&sub(in_to, in_from), &sub(in_to, in_from)).sqrt();
+        let direction = scale(&sub(in_to, in_from), &(1.0/distance));
+        while let Some((i, t, _)) = self.nearest(&origin, &direction, &distance) {
+            if in_random.next() < self._shapes[i].opacity() {return true}
+            origin = along(&origin, &direction, &t);
+            distance -= t;
+        }
+        false
+    }
+
+    /// The light coming back along a ray from the camera.
+    fn radiance(&self, in_origin : &[f32;3], in_direction : &[f32;3], in_random : &mut Random) -> [f32;3] {
+        let mut colour = [0.0f32;3];
+        let mut throughput = [1.0f32;3];
+        let (mut origin, mut direction) = (*in_origin, *in_direction);
+        let mut bounces = 0;
+        loop {
+            let (i, t, normal) = match self.nearest(&origin, &direction, &f32::INFINITY) {
+                Some(hit) => hit,
+                None => {
+                    // the first ray sees the background, and the later ones the sky
+                    let sky = if bounces == 0 {self._background} else {[SKY_BRIGHTNESS;3]};
+                    for k in 0..3 {colour[k] += throughput[k]*sky[k]}
+                    return colour;
+                },
+            };
+            let shape = &self._shapes[i];
+            let point = along(&origin, &direction, &t);
+            // through a translucent shape, as often as it's clear
+            if in_random.next() >= shape.opacity() {
+                origin = point;
+                continue;
+            }
+            if bounces == MAX_BOUNCES {return colour}
+            // facing the ray, for rays from inside a translucent shape
+            let normal = if dot(&normal, &direction) > 0.0 {scale(&normal, &-1.0)} else {normal};
+            let albedo = shape.colour();
+            // the light, from a random point on it
+            let on_light = along(&self._light, &in_random.direction(), &LIGHT_RADIUS);
+            let to_light = sub(&on_light, &point);
+            let light_distance = dot(&to_light, &to_light).sqrt();
+            let cos_light = dot(&normal, &to_light)/light_distance;
+            if cos_light > 0.0 && !self.is_blocked(&along(&point, &normal, &1e-3), &on_light, in_random) {
+                for k in 0..3 {colour[k] += throughput[k]*albedo[k]*LIGHT_BRIGHTNESS*cos_light}
+            }
+            // and on round the scene, in a direction around the normal (more often
+            // near it, as the light a surface reflects falls off)
+            for k in 0..3 {throughput[k] *= albedo[k]}
+            let scatter = in_random.direction();
+            let bounce = add(&normal, &scatter);
+            let length = dot(&bounce, &bounce).sqrt();
+            direction = if length > 1e-6 {scale(&bounce, &(1.0/length))} else {normal};
+            origin = along(&point, &normal, &1e-3);
+            bounces += 1;
+        }
+    }
+}
+
+// ============================================================
+// Rendering
+// ============================================================
+/// Traces a picture of the scene as the camera sees it, the size of the window,
+/// with `in_samples` paths through random points in each pixel, averaged. The rows
+/// are shared out between a thread for each processor. It's the same picture every
+/// time: each pixel's random numbers start from the pixel.
+pub fn render(in_scene : Scene, in_camera : &Camera, in_samples : &usize) -> ((usize, usize), Vec<u8>) {
+    let (rows, cancelled) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicBool::new(false)));
+    trace(in_scene, in_camera, in_samples, &rows, &cancelled).unwrap_or(((0, 0), Vec::new()))
+}
+
+/// As render(), counting the rows traced, and giving up (with None) once cancelled.
+fn trace(in_scene : Scene, in_camera : &Camera, in_samples : &usize, in_rows : &Arc<AtomicUsize>,
+         in_cancelled : &Arc<AtomicBool>) -> Option<((usize, usize), Vec<u8>)> {
+    let (width, height) = (in_camera.screen_size()[0] as usize, in_camera.screen_size()[1] as usize);
+    let samples = (*in_samples).max(1);
+    let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(height.max(1));
+    let scene = Arc::new(in_scene);
+    let camera = Arc::new(in_camera.clone());
+    let workers : Vec<_> = (0..threads).map(|n| {
+        let (scene, camera) = (scene.clone(), camera.clone());
+        let (rows, cancelled) = (in_rows.clone(), in_cancelled.clone());
+        thread::spawn(move || {
+            (0..height).filter(|y| y % threads == n).take_while(|_| !cancelled.load(Ordering::Relaxed)).map(|y| {
+                let mut row = Vec::with_capacity(3*width);
+                for x in 0..width {
+                    let mut random = Random::new(&((y*width+x) as u32));
+                    let mut sum = [0.0f32;3];
+                    for _ in 0..samples {
+                        let point = [x as f32 + random.next(), y as f32 + random.next()];
+                        let (origin, direction) = camera.pixel_ray(&point);
+                        let radiance = scene.radiance(&origin, &direction, &mut random);
+                        for k in 0..3 {sum[k] += radiance[k]}
+                    }
+                    for k in 0..3 {
+                        row.push((255.0*(sum[k]/samples as f32).max(0.0).min(1.0)).round() as u8);
+                    }
+                }
+                rows.fetch_add(1, Ordering::Relaxed);
+                (y, row)
+            }).collect::<Vec<(usize, Vec<u8>)>>()
+        })
+    }).collect();
+    let mut rows = vec![Vec::new(); height];
+    for worker in workers {
+        for (y, row) in worker.join().unwrap_or_default() {rows[y] = row}
+    }
+    if in_cancelled.load(Ordering::Relaxed) {return None}
+    let mut rgb = Vec::with_capacity(3*width*height);
+    for row in rows {
+        // (a row from a thread which panicked is left black)
+        if row.is_empty() {rgb.extend(::std::iter::repeat(0).take(3*width))} else {rgb.extend(row)}
+    }
+    Some(((width, height), rgb))
+}
+
+// ============================================================
+// Trace Job
+// ============================================================
+/// A picture being traced in another thread (for ctrl+S), so the window carries on
+/// while it's done, with how many of its rows are done for the viewer to show.
+/// Dropping it cancels it.
+pub struct TraceJob {
+    _fname     : String,
+    _height    : usize,
+    _rows      : Arc<AtomicUsize>,
+    _cancelled : Arc<AtomicBool>,
+    _traced    : Receiver<((usize, usize), Vec<u8>)>,
+    _started   : Instant,
+}
+
+impl TraceJob {
+    /// Traces as render() does, for saving to `in_fname`.
+    pub fn start(in_scene : Scene, in_camera : &Camera, in_samples : &usize, in_fname : &str) -> TraceJob {
+        let (rows, cancelled) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicBool::new(false)));
+        let (sender, receiver) = mpsc::channel();
+        let (working, cancelling) = (rows.clone(), cancelled.clone());
+        let (camera, samples) = (in_camera.clone(), *in_samples);
+        thread::spawn(move || {
+            if let Some(traced) = trace(in_scene, &camera, &samples, &working, &cancelling) {
+                // (nothing's listening if it's been dropped)
+                let _ = sender.send(traced);
+            }
+        });
+        TraceJob {
+            _fname     : in_fname.to_owned(),
+            _height    : in_camera.screen_size()[1] as usize,
+            _rows      : rows,
+            _cancelled : cancelled,
+            _traced    : receiver,
+            _started   : Instant::now(),
+        }
+    }
+
+    pub fn fname(&self) -> &str {&self._fname}
+    pub fn started(&self) -> &Instant {&self._started}
+
+    /// The fraction of the rows traced.
+    pub fn fraction(&self) -> f32 {
+        if self._height == 0 {return 1.0}
+        (self._rows.load(Ordering::Relaxed) as f32/self._height as f32).min(1.0)
+    }
+
+    /// The picture's size and pixels, once it's been traced; an error if the thread
+    /// tracing it gave up.
+    pub fn receive(&self) -> Option<Result<((usize, usize), Vec<u8>), String>> {
+        match self._traced.try_recv() {
+            Ok(traced) => Some(Ok(traced)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("tracing the picture failed".to_owned())),
+        }
+    }
+}
+
+impl Drop for TraceJob {
+    fn drop(&mut self) {self._cancelled.store(true, Ordering::Relaxed)}
+}
+
+/// A xorshift random number generator, one for each pixel.
+struct Random {
+    _state : u32,
+}
+
+impl Random {
+    fn new(in_seed : &u32) -> Random {
+        // mixed up, since neighbouring pixels' seeds differ by so little, and never 0
+        let mut state = in_seed.wrapping_mul(2654435761) ^ 0x9e3779b9;
+        if state == 0 {state = 1}
+        Random {_state : state}
+    }
+
+    /// From 0 up to 1.
+    fn next(&mut self) -> f32 {
+        self._state ^= self._state << 13;
+        self._state ^= self._state >> 17;
+        self._state ^= self._state << 5;
+        (self._state >> 8) as f32/16777216.0
+    }
+
+    /// A direction picked at random, evenly over the sphere.
+    fn direction(&mut self) -> [f32;3] {
+        let z = 2.0*self.next()-1.0;
+        let angle = 2.0*f32::consts::PI*self.next();
+        let r = (1.0-z*z).max(0.0).sqrt();
+        [r*angle.cos(), r*angle.sin(), z]
+    }
+}
+
+fn add(a : &[f32;3], b : &[f32;3]) -> [f32;3] {[a[0]+b[0], a[1]+b[1], a[2]+b[2]]}
+fn sub(a : &[f32;3], b : &[f32;3]) -> [f32;3] {[a[0]-b[0], a[1]-b[1], a[2]-b[2]]}
+fn scale(a : &[f32;3], s : &f32) -> [f32;3] {[a[0]*s, a[1]*s, a[2]*s]}
+fn dot(a : &[f32;3], b : &[f32;3]) -> f32 {a[0]*b[0]+a[1]*b[1]+a[2]*b[2]}
+fn along(in_origin : &[f32;3], in_direction : &[f32;3], in_t : &f32) -> [f32;3] {
+    add(in_origin, &scale(in_direction, in_t))
+}
@@ -0,0 +1,72 @@
+use bonds::BondDisplay;
+use camera::Camera;
+use colour::ColourScheme;
+use label::LabelContent;
+use measurement::Measurement;
+use miller::MillerPlanes;
+use molecule::Molecule;
+use objects::Objects;
+use radii::AtomRadii;
+use remote::Remote;
+use replay::{Player, Recorder};
+use scene_graph::SceneGraph;
+use script::Script;
+use selection::Selection;
+use stream::FrameStream;
+use surface::SurfaceDisplay;
+use trajectory::Trajectory;
+use tube::TubeDisplay;
+use undo::History;
+use vibration::Mode;
+use volume::Volume;
+
+/// How many times the context can be lost before the viewer gives up, rather than
+/// reopening its window over and over on a driver that keeps resetting.
+pub const MAX_RECOVERIES : usize = 3;
+
+// ============================================================
+// Recovery
+// ============================================================
+/// What's kept of a session when the GL context is lost (a driver reset, or the
+/// machine suspending and resuming). Every buffer, texture and program belongs to
+/// the lost context, so the viewer opens a new window and makes them all again,
+/// then carries on with this in place of the files and the session it started
+/// with: nothing here is the GPU's (the species the atoms are of included, see
+/// species.rs), so it's all kept as it was.
+///
+/// The structure comes back as it was, edits and all, with what's needed to undo
+/// them and the measurements made. A recording, replay or script carries on where
+/// it was, and the remote and streamed frames stay connected.
+pub struct Recovery<'a> {
+    pub molecule            : Molecule<'a>,
+    pub trajectory          : Trajectory,
+    pub objects             : Objects,
+    pub scene_graph         : SceneGraph,
+    /// The files the atoms came from, for saving the session.
+    pub loaded_fnames       : Vec<String>,
+    pub orbital             : Option<Volume>,
+    pub miller              : Option<MillerPlanes>,
+    pub normal_modes        : Vec<Mode>,
+    pub reference_positions : Option<Vec<[f32;3]>>,
+    pub history             : History<'a>,
+    pub measurements        : Vec<Measurement>,
+    /// How many frames have been drawn, which the recording and replay count by.
+    pub frame_count         : usize,
+    pub recorder            : Option<Recorder>,
+    pub player              : Option<Player>,
+    pub script              : Option<Script>,
+    pub remote              : Option<Remote>,
+    pub frame_stream        : Option<FrameStream>,
+    pub camera              : Camera,
+    pub selection           : Selection,
+    pub frame               : Option<usize>,
+    pub colour_scheme       : ColourScheme,
+    pub bond_display        : BondDisplay,
+    pub tube_display        : TubeDisplay,
+    pub label_content       : LabelContent,
+    pub surface_display     : SurfaceDisplay,
+    pub atom_radii          : AtomRadii,
+    pub hidden_chains       : Vec<String>,
+    /// How many times the context has been lost this session, this time included.
+    pub recoveries          : usize,
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use atom::Atom;
+use molecule::Molecule;
+
+/// How long the view has to have been still for before it's refined, in seconds.
+pub const STILL_SECONDS : f32 = 0.3;
+
+/// How many rays are traced each frame while the view is still, between all the
+/// atoms, and how many each atom gets before tracing stops.
+const RAYS_PER_FRAME : usize = 4000;
+pub const RAYS_PER_ATOM : u32 = 256;
+
+/// How far out from an atom's surface rays look for atoms in the way, in Angstroms.
+const OCCLUSION_DISTANCE : f32 = 2.0;
+
+/// How much darker an atom whose every ray is blocked is drawn.
+const OCCLUSION_STRENGTH : f32 = 0.6;
+
+// ============================================================
+// Refinement
+// ============================================================
+/// Whether the view has been still for long enough to be worth drawing better:
+/// with the finer bonds and ellipsoids, and tracing ambient occlusion. Anything
+/// happening (input, the camera moving, atoms moving) starts the wait again.
+pub struct Refinement {
+    /// When the view last stopped changing, or None while it's changing.
+    _still_since : Option<Instant>,
+}
+
+impl Refinement {
+    pub fn new() -> Refinement {Refinement {_still_since : None}}
+
+    /// Called once a frame, with whether anything changed since the last.
+    pub fn update(&mut self, in_changed : &bool) {
+        if *in_changed {
+            self._still_since = None;
+        } else if self._still_since.is_none() {
+            self._still_since = Some(Instant::now());
+        }
+    }
+
+    pub fn is_refined(&self) -> bool {
+        self._still_since.map_or(false, |since| {
+            let elapsed = since.elapsed();
+            elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32*1e-9 >= STILL_SECONDS
+        })
+    }
+}
+
+// ============================================================
+// Ambient Occlusion
+// ============================================================
+/// How much of the sky each atom sees, found by tracing rays on the CPU out from
+/// its surface and counting how many other atoms stop, a few thousand rays a frame
+/// so the shading fills in gradually. It doesn't depend on the camera, so it's
+/// kept while the view turns, but moving any atom, or drawing the atoms bigger or
+/// smaller, starts it again.
+pub struct Occlusion {
+    /// Where the atoms were when tracing started, with their radii.
+    _positions  : Vec<[f32;3]>,
+    _radii      : Vec<f32>,
+    /// The atoms near enough to each one to get in the way of its rays.
+    _neighbours : Vec<Vec<usize>>,
+    _rays       : Vec<u32>,
+    _blocked    : Vec<u32>,
+    /// The atom the next ray is traced from.
+    _next       : usize,
+    /// For a xorshift random number generator.
+    _seed       : u32,
+}
+
+impl Occlusion {
+    pub fn new() -> Occlusion {
+        Occlusion {
+            _positions  : Vec::new(),
+            _radii      : Vec::new(),
+            _neighbours : Vec::new(),
+            _rays       : Vec::new(),
+            _blocked    : Vec::new(),
+            _next       : 0,
+            _seed       : 2463534242,
+        }
+    }
+
+    /// Traces a frame's worth of rays, from and between the atoms as drawn with
+    /// `in_radius`, starting again first if the atoms have moved or their radii have
+    /// changed. Gives whether any were traced, so the shading's changed.
+    pub fn trace<R : Fn(&Atom) -> f32>(&mut self, in_molecule : &Molecule, in_radius : R) -> bool {
+        let atoms = in_molecule.atoms();
+        let changed = atoms.len() != self._positions.len()
+            || atoms.iter().zip(self._positions.iter().zip(self._radii.iter()))
+                   .any(|(atom, (p, &r))| atom.position() != p || in_radius(atom) != r);
+        if changed {self.start(in_molecule, &in_radius)}
+        if atoms.is_empty() || self.samples() >= RAYS_PER_ATOM {return false}
+        for _ in 0..RAYS_PER_FRAME {
+            let i = self._next;
+            self._next = (self._next+1) % atoms.len();
+            // from a random point on the surface, in a direction around the normal
+            // there (more often near it, as light falls on a surface)
+            let normal = self.random_direction();
+            let scatter = self.random_direction();
+            let direction = normalised(&[normal[0]+scatter[0], normal[1]+scatter[1], normal[2]+scatter[2]]);
+            let (p, r) = (self._positions[i], self._radii[i]);
+            let origin = [p[0]+r*normal[0], p[1]+r*normal[1], p[2]+r*normal[2]];
+            let blocked = self._neighbours[i].iter().any(|&j| {
+                hits(&origin, &direction, &self._positions[j], &self._radii[j])
+            });
+            self._rays[i] += 1;
+            if blocked {self._blocked[i] += 1}
+        }
+        true
+    }
+
+    fn start<R : Fn(&Atom) -> f32>(&mut self, in_molecule : &Molecule, in_radius : &R) {
+        let atoms = in_molecule.atoms();
+        self._positions = atoms.iter().map(|atom| *atom.position()).collect();
+        self._radii = atoms.iter().map(|atom| in_radius(atom)).collect();
+        self._rays = vec![0; atoms.len()];
+        self._blocked = vec![0; atoms.len()];
+        self._next = 0;
+
+        // sort the atoms into cubes, so that only neighbouring cubes need searching
+        let max_radius = self._radii.iter().cloned().fold(0.0f32, f32::max);
+        let reach = OCCLUSION_DISTANCE + 2.0*max_radius;
+        let cube = |position : &[f32;3]| -> [i64;3] {
+            [
+                (position[0]/reach).floor() as i64,
+                (position[1]/reach).floor() as i64,
+                (position[2]/reach).floor() as i64,
+            ]
+        };
+        let mut cubes : HashMap<[i64;3], Vec<usize>> = HashMap::new();
+        for (i, position) in self._positions.iter().enumerate() {
+            cubes.entry(cube(position)).or_insert_with(Vec::new).push(i);
+        }
+        let mut neighbours = Vec::with_capacity(atoms.len());
+        for (i, a) in self._positions.iter().enumerate() {
+            let c = cube(a);
+            let mut near = Vec::new();
+            for dx in -1..2 {
+                for dy in -1..2 {
+                    for dz in -1..2 {
+                        let others = match cubes.get(&[c[0]+dx, c[1]+dy, c[2]+dz]) {
+                            Some(others) => others,
+                            None => continue,
+                        };
+                        for &j in others.iter().filter(|&&j| j != i) {
+                            let b = self._positions[j];
+                            let d = [b[0]-a[0], b[1]-a[1], b[2]-a[2]];
+                            let cutoff = self._radii[i] + OCCLUSION_DISTANCE + self._radii[j];
+                            if d[0]*d[0]+d[1]*d[1]+d[2]*d[2] < cutoff*cutoff {near.push(j)}
+                        }
+                    }
+                }
+            }
+            neighbours.push(near);
+        }
+        self._neighbours = neighbours;
+    }
+
+    /// A direction picked at random, evenly over the sphere.
+    fn random_direction(&mut self) -> [f32;3] {
+        loop {
+            let v = [2.0*self.random()-1.0, 2.0*self.random()-1.0, 2.0*self.random()-1.0];
+            let length_squared = v[0]*v[0]+v[1]*v[1]+v[2]*v[2];
+            if length_squared > 1e-4 && length_squared <= 1.0 {return normalised(&v)}
+        }
+    }
+
+    /// From 0 to 1.
+    fn random(&mut self) -> f32 {
+        self._seed ^= self._seed << 13;
+        self._seed ^= self._seed >> 17;
+        self._seed ^= self._seed << 5;
+        self._seed as f32/4294967296.0
+    }
+
+    /// How many rays every atom has had, at least.
+    pub fn samples(&self) -> u32 {self._rays.iter().cloned().min().unwrap_or(0)}
+
+    /// What to multiply an atom's colour by: 1 for one out in the open (or not
+    /// traced yet), less the more its rays are blocked.
+    pub fn shade(&self, in_atom : &usize) -> f32 {
+        match (self._rays.get(*in_atom), self._blocked.get(*in_atom)) {
+            (Some(&rays), Some(&blocked)) if rays > 0 => 1.0 - OCCLUSION_STRENGTH*blocked as f32/rays as f32,
+            _ => 1.0,
+        }
+    }
+}
+
+fn normalised(in_vector : &[f32;3]) -> [f32;3] {
+    let length = (in_vector[0]*in_vector[0]+in_vector[1]*in_vector[1]+in_vector[2]*in_vector[2]).sqrt();
+    [in_vector[0]/length, in_vector[1]/length, in_vector[2]/length]
+}
+
+/// Whether a ray (with a unit direction) passes through a sphere within
+/// OCCLUSION_DISTANCE of its origin.
+fn hits(in_origin : &[f32;3], in_direction : &[f32;3], in_centre : &[f32;3], in_radius : &f32) -> bool {
+    let to_centre = [in_centre[0]-in_origin[0], in_centre[1]-in_origin[1], in_centre[2]-in_origin[2]];
+    let along = to_centre[0]*in_direction[0]+to_centre[1]*in_direction[1]+to_centre[2]*in_direction[2];
+    let distance_squared = to_centre[0]*to_centre[0]+to_centre[1]*to_centre[1]+to_centre[2]*to_centre[2];
+    let off_squared = distance_squared - along*along;
+    let r_squared = in_radius*in_radius;
+    if off_squared > r_squared {return false}
+    // where the ray enters (or, starting inside, leaves) the sphere
+    let half_chord = (r_squared-off_squared).sqrt();
+    let (entry, exit) = (along-half_chord, along+half_chord);
+    exit > 0.0 && entry < OCCLUSION_DISTANCE
+}
@@ -0,0 +1,265 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+#[cfg(unix)]
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+
+use arrows::ArrowDisplay;
+use bonds::BondDisplay;
+use camera::Projection;
+use colour::ColourScheme;
+use label::LabelContent;
+use radii::RadiusSource;
+use script::{self, Command, Request};
+use surface::SurfaceDisplay;
+use tube::TubeDisplay;
+
+// ============================================================
+// Remote
+// ============================================================
+/// Commands from other programs (--remote), a notebook say, one to a line, read
+/// from a TCP port on this machine (--remote :5001), a Unix socket (--remote
+/// /tmp/oxide.sock) or standard input (--remote -), e.g.
+///
+/// load protein.pdb
+/// colour fragment 2 1 0.5 0
+/// turn 0 1 0 90 2
+/// group 2 complex
+/// rotate complex 0 0 1 45
+/// screenshot out.png
+///
+/// They're the script's commands (see script.rs), with the arguments after the
+/// name: files, queries and groups take the rest of the line, and turn's seconds
+/// can be left off. Each line is answered once it's been done, with "ok" or
+/// "error: " and why, so a program can wait for a screenshot before reading it.
+///
+/// Each connection is read on a thread of its own, which sends the viewer one
+/// command at a time as a script does, so the commands are done on the viewer's
+/// thread between frames. Standard input, unlike a port or socket, ends: once it
+/// has, the remote is finished, as a script is.
+pub struct Remote {
+    _address  : String,
+    _requests : Receiver<Request>,
+    /// Sent to once standard input has been read to the end.
+    _finished : Option<Receiver<()>>,
+}
+
+impl Remote {
+    pub fn listen(in_address : &str) -> Result<Remote, String> {
+        let (requests, requested) = mpsc::channel();
+        let mut finished = None;
+        if in_address == "-" {
+            let (finish, stdin_finished) = mpsc::channel();
+            finished = Some(stdin_finished);
+            thread::spawn(move || {
+                let stdin = io::stdin();
+                serve(stdin.lock(), io::stdout(), &requests);
+                finish.send(()).ok();
+            });
+        } else if let Some(port) = tcp_port(in_address) {
+            // only this machine's programs, unless a host is given
+            let host = &in_address[..in_address.len()-port.len()-1];
+            let host = if host.is_empty() {"127.0.0.1"} else {host};
+            let listener = TcpListener::bind((host, port.parse::<u16>().unwrap()))
+                .map_err(|e| format!("couldn't listen on {}: {}", in_address, e))?;
+            thread::spawn(move || {
+                for connection in listener.incoming() {
+                    let connection = match connection {Ok(connection) => connection, Err(_) => continue};
+                    let requests = requests.clone();
+                    thread::spawn(move || {
+                        if let Ok(reader) = connection.try_clone() {
+                            serve(BufReader::new(reader), connection, &requests);
+                        }
+                    });
+                }
+            });
+        } else {
+            listen_unix(in_address, requests)?;
+        }
+        Ok(Remote {
+            _address  : in_address.to_owned(),
+            _requests : requested,
+            _finished : finished,
+        })
+    }
+
+    pub fn address(&self) -> &str {&self._address}
+
+    /// The command a connection's waiting on, if one's come in.
+    pub fn next(&self) -> Option<Request> {self._requests.try_recv().ok()}
+
+    /// Whether standard input has ended, each of its commands done. A port or socket
+    /// never finishes, as another program can always connect.
+    pub fn finished(&self) -> bool {
+        self._finished.as_ref().map_or(false, |finished| match finished.try_recv() {
+            Ok(()) | Err(TryRecvError::Disconnected) => true,
+            Err(TryRecvError::Empty) => false,
+        })
+    }
+}
+
+impl Drop for Remote {
+    /// Takes the Unix socket away, so the next viewer can listen there.
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            if self._address != "-" && tcp_port(&self._address).is_none() {
+                fs::remove_file(&self._address).ok();
+            }
+        }
+    }
+}
+
+/// The port, if the address is [host]:port.
+fn tcp_port(in_address : &str) -> Option<&str> {
+    let port = &in_address[in_address.rfind(':')?+1..];
+    port.parse::<u16>().ok().map(|_| port)
+}
+
+#[cfg(unix)]
+fn listen_unix(in_address : &str, in_requests : Sender<Request>) -> Result<(), String> {
+    // a socket left by a viewer that didn't close properly is in the way
+    if fs::metadata(in_address).map(|metadata| metadata.file_type().is_socket()).unwrap_or(false) {
+        fs::remove_file(in_address).ok();
+    }
+    let listener = UnixListener::bind(in_address).map_err(|e| format!("couldn't listen on {}: {}", in_address, e))?;
+    thread::spawn(move || {
+        for connection in listener.incoming() {
+            let connection = match connection {Ok(connection) => connection, Err(_) => continue};
+            let requests = in_requests.clone();
+            thread::spawn(move || {
+                if let Ok(reader) = connection.try_clone() {
+                    serve(BufReader::new(reader), connection, &requests);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn listen_unix(in_address : &str, _ : Sender<Request>) -> Result<(), String> {
+    Err(format!("expected -, or a port as [host]:port, not \"{}\" (there are no Unix sockets here)", in_address))
+}
+
+/// Reads a connection's commands till it closes (or the viewer does), answering
+/// each once it's done.
+fn serve<R : BufRead, W : Write>(in_reader : R, mut in_writer : W, in_requests : &Sender<Request>) {
+    for line in in_reader.lines() {
+        let line = match line {Ok(line) => line, Err(_) => return};
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {continue}
+        let done = match parse(line) {
+            Ok(command) => {
+                let (reply, replied) = mpsc::channel();
+                if in_requests.send((command, reply)).is_err() {return}
+                match replied.recv() {
+                    Ok(done) => done,
+                    Err(_) => return,
+                }
+            },
+            Err(message) => Err(message),
+        };
+        let answer = match done {
+            Ok(()) => "ok".to_owned(),
+            Err(message) => format!("error: {}", message),
+        };
+        if writeln!(in_writer, "{}", answer).and_then(|_| in_writer.flush()).is_err() {return}
+    }
+}
+
+/// A command line, e.g. "turn 0 1 0 90 2".
+fn parse(in_line : &str) -> Result<Command, String> {
+    let (name, rest) = match in_line.find(char::is_whitespace) {
+        Some(i) => (&in_line[..i], in_line[i..].trim()),
+        None => (in_line, ""),
+    };
+    let words : Vec<&str> = rest.split_whitespace().collect();
+    let numbers = |in_words : &[&str]| -> Result<Vec<f32>, String> {
+        in_words.iter().map(|word| word.parse::<f32>().map_err(|_| format!("expected a number, not \"{}\"", word)))
+            .collect()
+    };
+    // the arguments, which must be there
+    let given = |in_what : &str| -> Result<&str, String> {
+        if rest.is_empty() {Err(format!("{} needs {}", name, in_what))} else {Ok(rest)}
+    };
+    // a group and the numbers after it
+    let group_and = |in_count : usize| -> Result<(String, Vec<f32>), String> {
+        if words.len() <= in_count {return Err(format!("{} needs a group and {} numbers", name, in_count))}
+        let numbers = numbers(&words[words.len()-in_count..])?;
+        Ok((words[..words.len()-in_count].join(" "), numbers))
+    };
+    let exactly = |in_count : usize| -> Result<Vec<f32>, String> {
+        if words.len() != in_count {return Err(format!("{} needs {} numbers", name, in_count))}
+        numbers(&words)
+    };
+    // an object, counting from 1, and the numbers after it
+    let object_and = |in_count : usize| -> Result<(usize, Vec<f32>), String> {
+        if words.len() != in_count+1 {
+            return Err(if in_count == 0 {format!("{} needs an object", name)}
+                       else {format!("{} needs an object and {} numbers", name, in_count)});
+        }
+        let k = words[0].parse::<i64>().map_err(|_| format!("expected an object from 1, not \"{}\"", words[0]))?;
+        Ok((script::object(&k)?, numbers(&words[1..])?))
+    };
+    // a node of the scene graph (an object's number, "cell", a group or "scene") and
+    // the numbers after it
+    let node_and = |in_count : usize| -> Result<(String, Vec<f32>), String> {
+        if words.len() != in_count+1 {
+            return Err(if in_count == 0 {format!("{} needs an object or group", name)}
+                       else {format!("{} needs an object or group and {} numbers", name, in_count)});
+        }
+        Ok((words[0].to_owned(), numbers(&words[1..])?))
+    };
+    match name {
+        "load" => Ok(Command::Load(given("a file")?.to_owned())),
+        "add" => Ok(Command::Add(given("a file")?.to_owned())),
+        "select" => Ok(Command::Select(given("a query")?.to_owned())),
+        "colour" => group_and(3).map(|(group, c)| Command::Colour(group, [c[0], c[1], c[2]])),
+        "colours" => Ok(Command::ColourScheme(ColourScheme::parse(given("a scheme")?)?)),
+        "opacity" => group_and(1).map(|(group, o)| Command::Opacity(group, o[0])),
+        "bonds" => Ok(Command::Bonds(BondDisplay::parse(given("a mode")?)?)),
+        "backbone" => Ok(Command::Backbone(TubeDisplay::parse(given("a mode")?)?)),
+        "radii" => {
+            if words.len() != 2 {return Err("radii needs a source (species, covalent or vdw) and a scale".to_owned())}
+            let scale = words[1].parse::<f32>().map_err(|_| format!("expected a number, not \"{}\"", words[1]))?;
+            Ok(Command::Radii(RadiusSource::parse(words[0])?, scale))
+        },
+        "arrows" => {
+            if words.len() != 2 {return Err("arrows needs a mode (hidden, forces, velocities or dipole) and a scale".to_owned())}
+            let scale = words[1].parse::<f32>().map_err(|_| format!("expected a number, not \"{}\"", words[1]))?;
+            Ok(Command::Arrows(ArrowDisplay::parse(words[0])?, scale))
+        },
+        "labels" => Ok(Command::Labels(LabelContent::parse(given("a mode")?)?)),
+        "surface" => Ok(Command::Surface(SurfaceDisplay::parse(given("a mode")?)?)),
+        "projection" => Ok(Command::Projection(Projection::parse(given("a mode")?)?)),
+        "focus" => exactly(3).map(|x| Command::Focus([x[0], x[1], x[2]])),
+        "angles" => exactly(3).map(|a| Command::Angles([a[0], a[1], a[2]])),
+        "distance" => exactly(1).map(|d| Command::Distance(d[0])),
+        "fit" => Ok(Command::Fit),
+        "turn" => {
+            if words.len() != 4 && words.len() != 5 {return Err("turn needs x, y, z, degrees and seconds".to_owned())}
+            let t = numbers(&words)?;
+            Ok(Command::Turn([t[0], t[1], t[2]], t[3], t.get(4).cloned().unwrap_or(0.0).max(0.0)))
+        },
+        "frame" => given("a frame")?.parse().map(Command::Frame)
+            .map_err(|_| format!("expected a frame from 0, not \"{}\"", rest)),
+        "show" => object_and(0).map(|(k, _)| Command::Show(k)),
+        "hide" => object_and(0).map(|(k, _)| Command::Hide(k)),
+        "move" => node_and(3).map(|(node, x)| Command::Move(node, [x[0], x[1], x[2]])),
+        "rotate" => node_and(4).map(|(node, r)| Command::Rotate(node, [r[0], r[1], r[2]], r[3])),
+        "put_back" => node_and(0).map(|(node, _)| Command::PutBack(node)),
+        "group" => {
+            if words.len() != 2 {return Err("group needs an object or group, and the group to put it in".to_owned())}
+            Ok(Command::Group(words[0].to_owned(), words[1].to_owned()))
+        },
+        "screenshot" => Ok(Command::Screenshot(given("a file")?.to_owned())),
+        "quit" => Ok(Command::Quit),
+        _ => Err(format!("no command \"{}\"", name)),
+    }
+}
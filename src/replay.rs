@@ -0,0 +1,210 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::time::Instant;
+
+use glium::glutin::{ElementState, Event, MouseButton, MouseScrollDelta, TouchPhase, VirtualKeyCode};
+
+/// The keys recorded: those the viewer does something with.
+const KEYS : [VirtualKeyCode; 66] = [
+    VirtualKeyCode::A, VirtualKeyCode::B, VirtualKeyCode::C, VirtualKeyCode::D, VirtualKeyCode::E,
+    VirtualKeyCode::F, VirtualKeyCode::G, VirtualKeyCode::H, VirtualKeyCode::I, VirtualKeyCode::J,
+    VirtualKeyCode::K, VirtualKeyCode::L, VirtualKeyCode::M, VirtualKeyCode::N, VirtualKeyCode::O,
+    VirtualKeyCode::P, VirtualKeyCode::Q, VirtualKeyCode::R, VirtualKeyCode::S, VirtualKeyCode::T,
+    VirtualKeyCode::U, VirtualKeyCode::V, VirtualKeyCode::W, VirtualKeyCode::X, VirtualKeyCode::Y,
+    VirtualKeyCode::Z,
+    VirtualKeyCode::F1, VirtualKeyCode::F2, VirtualKeyCode::F3, VirtualKeyCode::F4, VirtualKeyCode::F5,
+    VirtualKeyCode::F6, VirtualKeyCode::F7, VirtualKeyCode::F8, VirtualKeyCode::F9, VirtualKeyCode::F10,
+    VirtualKeyCode::F11, VirtualKeyCode::F12,
+    VirtualKeyCode::Escape, VirtualKeyCode::Tab, VirtualKeyCode::Home, VirtualKeyCode::End, VirtualKeyCode::Insert,
+    VirtualKeyCode::PageUp, VirtualKeyCode::PageDown, VirtualKeyCode::Delete, VirtualKeyCode::Back, VirtualKeyCode::Return,
+    VirtualKeyCode::Space, VirtualKeyCode::Up, VirtualKeyCode::Down, VirtualKeyCode::Left,
+    VirtualKeyCode::Right, VirtualKeyCode::Comma, VirtualKeyCode::Period, VirtualKeyCode::LBracket,
+    VirtualKeyCode::RBracket, VirtualKeyCode::Minus, VirtualKeyCode::Equals, VirtualKeyCode::Subtract,
+    VirtualKeyCode::LShift, VirtualKeyCode::RShift, VirtualKeyCode::LControl, VirtualKeyCode::RControl,
+    VirtualKeyCode::LAlt, VirtualKeyCode::RAlt,
+];
+
+fn state_name(in_state : &ElementState) -> &'static str {
+    match *in_state {
+        ElementState::Pressed  => "pressed",
+        ElementState::Released => "released",
+    }
+}
+
+fn parse_state(in_word : &str) -> Option<ElementState> {
+    match in_word {
+        "pressed"  => Some(ElementState::Pressed),
+        "released" => Some(ElementState::Released),
+        _          => None,
+    }
+}
+
+/// An event as a line of a session file (after the frame and time), e.g.
+/// "key pressed F4", "button released left", "moved 312 220", "wheel lines 0 1",
+/// "char a", "resized 800 600" or "dropped /home/me/water.xyz". None for events which aren't recorded.
+pub fn event_line(in_event : &Event) -> Option<String> {
+    match *in_event {
+        Event::KeyboardInput(ref state, _, Some(ref key)) if KEYS.contains(key) => {
+            Some(format!("key {} {:?}", state_name(state), key))
+        },
+        Event::MouseInput(ref state, ref button) => Some(format!("button {} {}", state_name(state), match *button {
+            MouseButton::Left     => "left".to_owned(),
+            MouseButton::Right    => "right".to_owned(),
+            MouseButton::Middle   => "middle".to_owned(),
+            MouseButton::Other(n) => format!("{}", n),
+        })),
+        Event::MouseMoved(x, y) => Some(format!("moved {} {}", x, y)),
+        Event::MouseWheel(MouseScrollDelta::LineDelta(x, y), _) => Some(format!("wheel lines {} {}", x, y)),
+        Event::MouseWheel(MouseScrollDelta::PixelDelta(x, y), _) => Some(format!("wheel pixels {} {}", x, y)),
+        Event::ReceivedCharacter(c) if !c.is_whitespace() => Some(format!("char {}", c)),
+        Event::ReceivedCharacter(' ') => Some("char space".to_owned()),
+        Event::Resized(w, h) => Some(format!("resized {} {}", w, h)),
+        Event::DroppedFile(ref path) => Some(format!("dropped {}", path.display())),
+        _ => None,
+    }
+}
+
+/// The event a line from event_line() stands for, or None if it isn't one.
+pub fn parse_event(in_line : &str) -> Option<Event> {
+    let words : Vec<&str> = in_line.split_whitespace().collect();
+    match (words.get(0).cloned(), words.len()) {
+        (Some("key"), 3) => {
+            let key = KEYS.iter().find(|key| format!("{:?}", key) == words[2])?;
+            Some(Event::KeyboardInput(parse_state(words[1])?, 0, Some(*key)))
+        },
+        (Some("button"), 3) => Some(Event::MouseInput(parse_state(words[1])?, match words[2] {
+            "left"   => MouseButton::Left,
+            "right"  => MouseButton::Right,
+            "middle" => MouseButton::Middle,
+            other    => MouseButton::Other(other.parse().ok()?),
+        })),
+        (Some("moved"), 3) => Some(Event::MouseMoved(words[1].parse().ok()?, words[2].parse().ok()?)),
+        (Some("wheel"), 4) => {
+            let (x, y) = (words[2].parse().ok()?, words[3].parse().ok()?);
+            let delta = match words[1] {
+                "lines"  => MouseScrollDelta::LineDelta(x, y),
+                "pixels" => MouseScrollDelta::PixelDelta(x, y),
+                _        => return None,
+            };
+            Some(Event::MouseWheel(delta, TouchPhase::Moved))
+        },
+        (Some("char"), 2) if words[1] == "space" => Some(Event::ReceivedCharacter(' ')),
+        (Some("char"), 2) if words[1].chars().count() == 1 => {
+            Some(Event::ReceivedCharacter(words[1].chars().next().unwrap()))
+        },
+        (Some("resized"), 3) => Some(Event::Resized(words[1].parse().ok()?, words[2].parse().ok()?)),
+        // the path is the rest of the line, spaces and all
+        (Some("dropped"), n) if n > 1 => Some(Event::DroppedFile(in_line.trim()["dropped".len()..].trim().into())),
+        _ => None,
+    }
+}
+
+// ============================================================
+// Recorder
+// ============================================================
+/// Writes the input events of a session to a file, each with the frame it came in
+/// and the seconds since the start, after a line with the window's size:
+///
+/// window 1024 768
+/// 12 0.200 key pressed F4
+/// 40 0.667 moved 312 220
+///
+/// so the session can be replayed, e.g. to go with a bug report.
+pub struct Recorder {
+    _file  : BufWriter<File>,
+    _start : Instant,
+}
+
+impl Recorder {
+    pub fn new(in_fname : &str, in_window : &(u32, u32)) -> Result<Recorder, String> {
+        let file = File::create(in_fname).map_err(|e| format!("couldn't create {}: {}", in_fname, e))?;
+        let mut recorder = Recorder {
+            _file  : BufWriter::new(file),
+            _start : Instant::now(),
+        };
+        writeln!(recorder._file, "window {} {}", in_window.0, in_window.1)
+            .map_err(|e| format!("couldn't write to {}: {}", in_fname, e))?;
+        Ok(recorder)
+    }
+
+    /// Writes the events of a frame. They're flushed straight away, so a session
+    /// which ends in a crash is still all there.
+    pub fn record(&mut self, in_frame : &usize, in_events : &Vec<Event>) {
+        let elapsed = self._start.elapsed();
+        let seconds = elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32*1e-9;
+        let lines : Vec<String> = in_events.iter().filter_map(event_line).collect();
+        if lines.is_empty() {return}
+        for line in lines {
+            if writeln!(self._file, "{} {:.3} {}", in_frame, seconds, line).is_err() {return}
+        }
+        let _ = self._file.flush();
+    }
+}
+
+// ============================================================
+// Player
+// ============================================================
+/// A recorded session, played back by giving each frame the events recorded in the
+/// frame with the same number, so what depends only on the input (picking, the
+/// camera, the keys) happens just as it did. Animations timed by the clock, e.g. the
+/// camera gliding back on R, may have got a different way along.
+pub struct Player {
+    _window : Option<(u32, u32)>,
+    _events : Vec<(usize, Event)>,
+    /// The index of the next event to give.
+    _next   : usize,
+}
+
+impl Player {
+    /// Reads a file written by a Recorder. Blank lines and lines starting "#" are
+    /// ignored, so sessions can be written by hand.
+    pub fn read(in_fname : &str) -> Result<Player, String> {
+        let mut file = File::open(in_fname).map_err(|e| format!("couldn't open {}: {}", in_fname, e))?;
+        let mut flines = String::new();
+        file.read_to_string(&mut flines).map_err(|e| format!("couldn't read {}: {}", in_fname, e))?;
+
+        let mut window = None;
+        let mut events = Vec::new();
+        for (i, line) in flines.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {continue}
+            let words : Vec<&str> = line.split_whitespace().collect();
+            if words[0] == "window" {
+                let size : Vec<u32> = words[1..].iter().filter_map(|s| s.parse().ok()).collect();
+                if size.len() != 2 {return Err(format!("{} line {}: expected \"window\", a width and a height", in_fname, i+1))}
+                window = Some((size[0], size[1]));
+                continue;
+            }
+            let bad_line = || format!("{} line {}: expected a frame, a time and an event", in_fname, i+1);
+            if words.len() < 3 {return Err(bad_line())}
+            let frame : usize = words[0].parse().map_err(|_| bad_line())?;
+            words[1].parse::<f32>().map_err(|_| bad_line())?;
+            let event = parse_event(&words[2..].join(" ")).ok_or_else(bad_line)?;
+            if events.last().map_or(false, |&(last, _)| frame < last) {
+                return Err(format!("{} line {}: the frames go backwards", in_fname, i+1));
+            }
+            events.push((frame, event));
+        }
+        Ok(Player {
+            _window : window,
+            _events : events,
+            _next   : 0,
+        })
+    }
+
+    /// The size of the window the session was recorded in, if the file says.
+    pub fn window(&self) -> &Option<(u32, u32)> {&self._window}
+
+    /// The events recorded in a frame (or any before it not yet given).
+    pub fn events(&mut self, in_frame : &usize) -> Vec<Event> {
+        let start = self._next;
+        while self._next < self._events.len() && self._events[self._next].0 <= *in_frame {
+            self._next += 1;
+        }
+        self._events[start..self._next].iter().map(|&(_, ref event)| event.clone()).collect()
+    }
+
+    /// Whether every event has been given.
+    pub fn is_finished(&self) -> bool {self._next == self._events.len()}
+}
@@ -0,0 +1,296 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use serde_json;
+use toml;
+
+use bonds::BondDisplay;
+use camera::Projection;
+use label::LabelContent;
+use tube::TubeDisplay;
+
+// ============================================================
+// Scene
+// ============================================================
+/// A figure written down in a file, so it can be made again exactly and reviewed
+/// like code: what to load, how to draw it, where the camera and light are, and
+/// where to save it. One setting a line, with blank lines and lines starting "#"
+/// ignored:
+///
+/// load protein.pdb
+/// select ligand = resname LIG
+/// colour ligand 1.0 0.5 0.0
+/// opacity * 0.3
+/// bonds plain
+/// backbone tube
+/// labels residue
+/// projection orthographic
+/// camera focus 10.2 4.5 -3.0
+/// camera angles 30 45 0
+/// camera distance 40
+/// light 2 0 0
+/// screenshot figure.ppm
+///
+/// Paths are relative to the scene file. Anything not set is left as it would be
+/// without a scene. With a screenshot, the figure is drawn without a window, saved
+/// and the program quits.
+///
+/// The same scene can be written in TOML (figure.scene.toml), or in JSON
+/// (figure.scene.json) for scenes written by other programs, with the settings
+/// as keys:
+///
+/// load = "protein.pdb"
+/// select = ["ligand = resname LIG"]
+/// colour = [{group = "ligand", colour = [1.0, 0.5, 0.0]}]
+/// opacity = [{group = "*", opacity = 0.3}]
+/// bonds = "plain"
+/// backbone = "tube"
+/// labels = "residue"
+/// projection = "orthographic"
+/// camera = {focus = [10.2, 4.5, -3.0], angles = [30, 45, 0], distance = 40}
+/// light = [2, 0, 0]
+/// screenshot = "figure.ppm"
+///
+/// The config's startup scene (see config.rs) is a scene too, shown whenever the
+/// program is run with nothing else to show.
+#[derive(Clone)]
+pub struct Scene {
+    _structure  : Option<String>,
+    /// Named selection queries, as --select takes them.
+    _queries    : Vec<String>,
+    /// Applied in order, like --opacity, but after the queries.
+    _opacities  : Vec<(String, f32)>,
+    _colours    : Vec<(String, [f32;3])>,
+    _bonds      : Option<BondDisplay>,
+    _backbone   : Option<TubeDisplay>,
+    _labels     : Option<LabelContent>,
+    _projection : Option<Projection>,
+    _focus      : Option<[f32;3]>,
+    /// Theta, phi and psi, in degrees, as the camera takes them.
+    _angles     : Option<[f32;3]>,
+    _distance   : Option<f32>,
+    _light      : Option<[f32;3]>,
+    _screenshot : Option<String>,
+}
+
+/// A scene as TOML or JSON write it, checked and turned into a Scene by
+/// Scene::from_file().
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneFile {
+    load       : Option<String>,
+    #[serde(default)]
+    select     : Vec<String>,
+    #[serde(default, alias = "color")]
+    colour     : Vec<GroupColour>,
+    #[serde(default)]
+    opacity    : Vec<GroupOpacity>,
+    bonds      : Option<String>,
+    backbone   : Option<String>,
+    labels     : Option<String>,
+    projection : Option<String>,
+    #[serde(default)]
+    camera     : SceneCamera,
+    light      : Option<[f32;3]>,
+    screenshot : Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GroupColour {
+    group  : String,
+    #[serde(alias = "color")]
+    colour : [f32;3],
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GroupOpacity {
+    group   : String,
+    opacity : f32,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct SceneCamera {
+    focus    : Option<[f32;3]>,
+    angles   : Option<[f32;3]>,
+    distance : Option<f32>,
+}
+
+impl Scene {
+    fn empty() -> Scene {
+        Scene {
+            _structure  : None,
+            _queries    : Vec::new(),
+            _opacities  : Vec::new(),
+            _colours    : Vec::new(),
+            _bonds      : None,
+            _backbone   : None,
+            _labels     : None,
+            _projection : None,
+            _focus      : None,
+            _angles     : None,
+            _distance   : None,
+            _light      : None,
+            _screenshot : None,
+        }
+    }
+
+    /// Whether a file is a scene, by its name: figure.scene, or figure.scene.toml or
+    /// figure.scene.json.
+    pub fn is_scene_fname(in_fname : &str) -> bool {
+        let fname = in_fname.to_lowercase();
+        fname.ends_with(".scene") || fname.ends_with(".scene.toml") || fname.ends_with(".scene.json")
+    }
+
+    pub fn read(in_fname : &str) -> Result<Scene, String> {
+        let mut file = File::open(in_fname).map_err(|e| format!("couldn't open {}: {}", in_fname, e))?;
+        let mut flines = String::new();
+        file.read_to_string(&mut flines).map_err(|e| format!("couldn't read {}: {}", in_fname, e))?;
+        let directory = Path::new(in_fname).parent().unwrap_or(Path::new(""));
+        let path = |in_path : &str| directory.join(in_path).to_string_lossy().into_owned();
+
+        let lowercase = in_fname.to_lowercase();
+        if lowercase.ends_with(".toml") {
+            let file_scene = toml::from_str(&flines).map_err(|e| format!("{}: {}", in_fname, e))?;
+            return Scene::from_file(in_fname, file_scene);
+        }
+        if lowercase.ends_with(".json") {
+            let file_scene = serde_json::from_str(&flines).map_err(|e| format!("{}: {}", in_fname, e))?;
+            return Scene::from_file(in_fname, file_scene);
+        }
+
+        let mut scene = Scene::empty();
+        for (i, line) in flines.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {continue}
+            let words : Vec<&str> = line.split_whitespace().collect();
+            let rest = line[words[0].len()..].trim();
+            let bad_line = |in_expected : &str| format!("{} line {}: expected {}", in_fname, i+1, in_expected);
+            let numbers = |in_words : &[&str]| -> Option<Vec<f32>> {
+                in_words.iter().map(|s| s.parse().ok()).collect()
+            };
+            let three = |in_words : &[&str]| numbers(in_words).filter(|v| v.len() == 3).map(|v| [v[0], v[1], v[2]]);
+            match words[0] {
+                "load" if !rest.is_empty() => scene._structure = Some(path(rest)),
+                "load" => return Err(bad_line("\"load\" and a file")),
+                "select" if rest.contains('=') => scene._queries.push(rest.to_owned()),
+                "select" => return Err(bad_line("\"select\", a name, \"=\" and a query")),
+                "colour" | "color" => {
+                    let (colour, group) = if words.len() >= 5 {
+                        (three(&words[words.len()-3..]), words[1..words.len()-3].join(" "))
+                    } else {
+                        (None, String::new())
+                    };
+                    match colour {
+                        Some(colour) if !group.is_empty() => scene._colours.push((group, colour)),
+                        _ => return Err(bad_line("\"colour\", a group and 3 numbers from 0 to 1")),
+                    }
+                },
+                "opacity" => {
+                    let opacity = words.last().and_then(|s| s.parse::<f32>().ok());
+                    let group = if words.len() >= 3 {words[1..words.len()-1].join(" ")} else {String::new()};
+                    match opacity {
+                        Some(opacity) if !group.is_empty() => scene._opacities.push((group, opacity)),
+                        _ => return Err(bad_line("\"opacity\", a group and an opacity")),
+                    }
+                },
+                "bonds" => scene._bonds = Some(match rest {
+                    "hidden"    => BondDisplay::Hidden,
+                    "plain"     => BondDisplay::Plain,
+                    "deviation" => BondDisplay::Deviation,
+                    _ => return Err(bad_line("\"bonds\" and hidden, plain or deviation")),
+                }),
+                "backbone" => scene._backbone = Some(match rest {
+                    "hidden" => TubeDisplay::Hidden,
+                    "tube"   => TubeDisplay::Tube,
+                    "putty"  => TubeDisplay::Putty,
+                    _ => return Err(bad_line("\"backbone\" and hidden, tube or putty")),
+                }),
+                "labels" => scene._labels = Some(match rest {
+                    "off"     => LabelContent::Off,
+                    "element" => LabelContent::Element,
+                    "number"  => LabelContent::Index,
+                    "residue" => LabelContent::Residue,
+                    _ => return Err(bad_line("\"labels\" and off, element, number or residue")),
+                }),
+                "projection" => scene._projection = Some(match rest {
+                    "perspective"  => Projection::Perspective,
+                    "orthographic" => Projection::Orthographic,
+                    _ => return Err(bad_line("\"projection\" and perspective or orthographic")),
+                }),
+                "camera" => match words.get(1).cloned() {
+                    Some("focus") => scene._focus = Some(
+                        three(&words[2..]).ok_or_else(|| bad_line("\"camera focus\" and 3 numbers"))?
+                    ),
+                    Some("angles") => scene._angles = Some(
+                        three(&words[2..]).ok_or_else(|| bad_line("\"camera angles\" and 3 angles in degrees"))?
+                    ),
+                    Some("distance") => scene._distance = Some(
+                        numbers(&words[2..]).filter(|v| v.len() == 1 && v[0] > 0.0).map(|v| v[0])
+                            .ok_or_else(|| bad_line("\"camera distance\" and a distance"))?
+                    ),
+                    _ => return Err(bad_line("\"camera\" and focus, angles or distance")),
+                },
+                "light" => scene._light = Some(three(&words[1..]).ok_or_else(|| bad_line("\"light\" and 3 numbers"))?),
+                "screenshot" if !rest.is_empty() => scene._screenshot = Some(path(rest)),
+                "screenshot" => return Err(bad_line("\"screenshot\" and a file")),
+                _ => return Err(bad_line("a setting (load, select, colour, opacity, bonds, backbone, \
+                                          labels, projection, camera, light or screenshot)")),
+            }
+        }
+        Ok(scene)
+    }
+
+    /// The scene a TOML or JSON file holds (or a table of one, as the config's
+    /// startup scene is), checked as read() checks the lines of a .scene file.
+    pub fn from_file(in_fname : &str, in_file : SceneFile) -> Result<Scene, String> {
+        let directory = Path::new(in_fname).parent().unwrap_or(Path::new(""));
+        let path = |in_path : &String| directory.join(in_path).to_string_lossy().into_owned();
+        let bad_setting = |in_setting : &str, in_why : String| format!("{}: {}: {}", in_fname, in_setting, in_why);
+        if let Some(query) = in_file.select.iter().find(|query| !query.contains('=')) {
+            return Err(bad_setting("select", format!("expected a name, \"=\" and a query, not \"{}\"", query)));
+        }
+        if let Some(distance) = in_file.camera.distance.filter(|&distance| distance <= 0.0) {
+            return Err(bad_setting("camera", format!("the distance {} isn't positive", distance)));
+        }
+        let mut scene = Scene::empty();
+        scene._structure = in_file.load.as_ref().map(path);
+        scene._queries = in_file.select;
+        scene._colours = in_file.colour.into_iter().map(|colour| (colour.group, colour.colour)).collect();
+        scene._opacities = in_file.opacity.into_iter().map(|opacity| (opacity.group, opacity.opacity)).collect();
+        if let Some(ref bonds) = in_file.bonds {
+            scene._bonds = Some(BondDisplay::parse(bonds).map_err(|e| bad_setting("bonds", e))?);
+        }
+        if let Some(ref backbone) = in_file.backbone {
+            scene._backbone = Some(TubeDisplay::parse(backbone).map_err(|e| bad_setting("backbone", e))?);
+        }
+        if let Some(ref labels) = in_file.labels {
+            scene._labels = Some(LabelContent::parse(labels).map_err(|e| bad_setting("labels", e))?);
+        }
+        if let Some(ref projection) = in_file.projection {
+            scene._projection = Some(Projection::parse(projection).map_err(|e| bad_setting("projection", e))?);
+        }
+        scene._focus = in_file.camera.focus;
+        scene._angles = in_file.camera.angles;
+        scene._distance = in_file.camera.distance;
+        scene._light = in_file.light;
+        scene._screenshot = in_file.screenshot.as_ref().map(path);
+        Ok(scene)
+    }
+
+    pub fn structure(&self) -> &Option<String> {&self._structure}
+    pub fn queries(&self) -> &Vec<String> {&self._queries}
+    pub fn opacities(&self) -> &Vec<(String, f32)> {&self._opacities}
+    pub fn colours(&self) -> &Vec<(String, [f32;3])> {&self._colours}
+    pub fn bonds(&self) -> &Option<BondDisplay> {&self._bonds}
+    pub fn backbone(&self) -> &Option<TubeDisplay> {&self._backbone}
+    pub fn labels(&self) -> &Option<LabelContent> {&self._labels}
+    pub fn projection(&self) -> &Option<Projection> {&self._projection}
+    pub fn focus(&self) -> &Option<[f32;3]> {&self._focus}
+    pub fn angles(&self) -> &Option<[f32;3]> {&self._angles}
+    pub fn distance(&self) -> &Option<f32> {&self._distance}
+    pub fn light(&self) -> &Option<[f32;3]> {&self._light}
+    pub fn screenshot(&self) -> &Option<String> {&self._screenshot}
+}
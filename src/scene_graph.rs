@@ -0,0 +1,286 @@
+use matrix::Matrix;
+use molecule::Molecule;
+use objects::{Objects, NO_OBJECT};
+use trajectory::Trajectory;
+
+/// The node for the whole scene, which everything else is under.
+pub const ROOT : usize = 0;
+
+// ============================================================
+// Node
+// ============================================================
+/// What a node carries, besides the nodes under it.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Content {
+    /// Nothing: the whole scene, or a group.
+    Group,
+    /// An object's atoms (see objects.rs), and so the measurements and labels on them.
+    Object(usize),
+    /// The molecule's unit cell, for crystals.
+    Cell,
+}
+
+/// Something moved as one, within the group it's in.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Node {
+    #[serde(rename = "name")]
+    _name    : String,
+    /// The group it's in: None only for the whole scene.
+    #[serde(rename = "parent")]
+    _parent  : Option<usize>,
+    /// Where it's been moved to within its group, from where its group (and, for an
+    /// object, its file) put it.
+    #[serde(rename = "local")]
+    _local   : Matrix,
+    #[serde(rename = "content")]
+    _content : Content,
+}
+
+impl Node {
+    pub fn name(&self) -> &str {&self._name}
+    pub fn parent(&self) -> &Option<usize> {&self._parent}
+    pub fn local(&self) -> &Matrix {&self._local}
+    pub fn content(&self) -> &Content {&self._content}
+}
+
+// ============================================================
+// Scene Graph
+// ============================================================
+/// How what's in the scene has been moved: a tree of nodes, each moved within the
+/// group it's in, so moving a group moves everything in it, and moving the whole
+/// scene turns the molecule about its own centre rather than turning the camera.
+/// The nodes are the objects, named by their numbers, the cell, named "cell", the
+/// groups made of them, and the whole scene, named "scene".
+///
+/// The atoms are moved to where the nodes put them, since their positions are where
+/// they're drawn, measured and picked, so measurements and labels go with them. The
+/// cell is moved with them too, so wrapping and periodic distances still hold. Each
+/// node's transformation is kept, to put it back, save it in a session and move the
+/// atoms again when their files are read again.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SceneGraph {
+    #[serde(rename = "nodes")]
+    _nodes : Vec<Node>,
+}
+
+impl SceneGraph {
+    /// The whole scene, with the cell in it (if there is one) and nothing else.
+    pub fn new() -> SceneGraph {
+        let mut graph = SceneGraph {_nodes : Vec::new()};
+        graph.add("scene", None, Content::Group);
+        graph.add("cell", Some(ROOT), Content::Cell);
+        graph
+    }
+
+    pub fn nodes(&self) -> &Vec<Node> {&self._nodes}
+
+    fn add(&mut self, in_name : &str, in_parent : Option<usize>, in_content : Content) -> usize {
+        self._nodes.push(Node {
+            _name    : in_name.to_owned(),
+            _parent  : in_parent,
+            _local   : Matrix::identity(),
+            _content : in_content,
+        });
+        self._nodes.len()-1
+    }
+
+    /// Adds a node for a new object, in the whole scene, where its file put it.
+    pub fn add_object(&mut self, in_object : &usize) -> usize {
+        self.add(&format!("{}", in_object+1), Some(ROOT), Content::Object(*in_object))
+    }
+
+    /// Adds a node for an object split from another (see Objects::split()), moved
+    /// as that one has been, so its atoms stay where they are.
+    pub fn add_split(&mut self, in_object : &usize, in_from : &usize) -> usize {
+        let (parent, local) = match self.object_node(in_from) {
+            Ok(from) => (self._nodes[from]._parent, self._nodes[from]._local),
+            Err(_) => (Some(ROOT), Matrix::identity()),
+        };
+        let node = self.add(&format!("{}", in_object+1), parent, Content::Object(*in_object));
+        self._nodes[node]._local = local;
+        node
+    }
+
+    /// Starts again with the one object, e.g. after replacing the structure.
+    pub fn reset(&mut self) {
+        *self = SceneGraph::new();
+        self.add_object(&0);
+    }
+
+    pub fn find(&self, in_name : &str) -> Result<usize, String> {
+        self._nodes.iter().position(|node| node._name == in_name)
+            .ok_or_else(|| format!("no object or group \"{}\"", in_name))
+    }
+
+    pub fn object_node(&self, in_object : &usize) -> Result<usize, String> {
+        self._nodes.iter().position(|node| node._content == Content::Object(*in_object))
+            .ok_or_else(|| if *in_object == NO_OBJECT {
+                "no object (the atoms are in none)".to_owned()
+            } else {
+                format!("no object {}", in_object+1)
+            })
+    }
+
+    /// The group with a name, made (in the whole scene) if there's none yet.
+    pub fn group(&mut self, in_name : &str) -> Result<usize, String> {
+        match self.find(in_name) {
+            Ok(node) if self._nodes[node]._content == Content::Group => Ok(node),
+            Ok(_) => Err(format!("\"{}\" isn't a group", in_name)),
+            Err(_) if in_name.is_empty() => Err("a group needs a name".to_owned()),
+            Err(_) if in_name.parse::<usize>().is_ok() => Err(format!("no object {}", in_name)),
+            Err(_) => Ok(self.add(in_name, Some(ROOT), Content::Group)),
+        }
+    }
+
+    /// Where a node's been moved to in the whole scene: its own transformation after
+    /// those of the groups it's in.
+    pub fn world(&self, in_node : &usize) -> Matrix {
+        let node = &self._nodes[*in_node];
+        match node._parent {
+            Some(parent) => self.world(&parent)*node._local,
+            None => node._local,
+        }
+    }
+
+    fn parent_world(&self, in_node : &usize) -> Matrix {
+        self._nodes[*in_node]._parent.map_or(Matrix::identity(), |parent| self.world(&parent))
+    }
+
+    /// Whether a node is in a group, however far down, or is it.
+    pub fn is_under(&self, in_node : &usize, in_group : &usize) -> bool {
+        let mut node = Some(*in_node);
+        while let Some(k) = node {
+            if k == *in_group {return true}
+            node = self._nodes[k]._parent;
+        }
+        false
+    }
+
+    /// Puts a node in a group, leaving it where it is.
+    pub fn set_parent(&mut self, in_node : &usize, in_group : &usize) -> Result<(), String> {
+        let (name, group_name) = (self._nodes[*in_node]._name.to_owned(), self._nodes[*in_group]._name.to_owned());
+        if *in_node == ROOT {return Err("the whole scene isn't in a group".to_owned())}
+        if self._nodes[*in_group]._content != Content::Group {return Err(format!("\"{}\" isn't a group", group_name))}
+        if self.is_under(in_group, in_node) {
+            return Err(format!("\"{}\" can't be put in \"{}\", which is in it", name, group_name));
+        }
+        let local = self.world(in_group).inverse()
+            .ok_or_else(|| format!("\"{}\" has been flattened", group_name))?*self.world(in_node);
+        let node = &mut self._nodes[*in_node];
+        node._parent = Some(*in_group);
+        node._local = local;
+        Ok(())
+    }
+
+    /// Moves a node, and everything in it, by a transformation about its centre (a
+    /// rotation, say, about the middle of its atoms).
+    pub fn transform(&mut self, in_node : &usize, in_transformation : &Matrix, in_objects : &Objects,
+                     in_molecule : &mut Molecule, in_trajectory : &mut Trajectory) -> Result<(), String> {
+        let centre = self.centre(in_node, in_objects, in_molecule)?;
+        let about_centre = Matrix::translation(&centre)*(*in_transformation)
+            *Matrix::translation(&[-centre[0], -centre[1], -centre[2]]);
+        let parent = self.parent_world(in_node);
+        let inverse = parent.inverse()
+            .ok_or_else(|| format!("\"{}\" is in a group that's been flattened", self._nodes[*in_node]._name))?;
+        let local = inverse*about_centre*parent*self._nodes[*in_node]._local;
+        self._nodes[*in_node]._local = local;
+        self.move_under(in_node, &about_centre, in_objects, in_molecule, in_trajectory);
+        Ok(())
+    }
+
+    /// Puts a node back where its group puts it (or, for an object not in a group,
+    /// where its file did), with everything in it.
+    pub fn put_back(&mut self, in_node : &usize, in_objects : &Objects, in_molecule : &mut Molecule,
+                    in_trajectory : &mut Trajectory) -> Result<(), String> {
+        let inverse = self.world(in_node).inverse()
+            .ok_or_else(|| format!("\"{}\" can't be put back", self._nodes[*in_node]._name))?;
+        let back = self.parent_world(in_node)*inverse;
+        self._nodes[*in_node]._local = Matrix::identity();
+        self.move_under(in_node, &back, in_objects, in_molecule, in_trajectory);
+        Ok(())
+    }
+
+    /// Takes up the nodes as saved (in a session, say), over the files they came from
+    /// just read back in, and moves everything to where they put it. Objects that
+    /// aren't there now leave empty groups, and those not saved are added.
+    pub fn restore(&mut self, in_saved : &SceneGraph, in_objects : &Objects, in_molecule : &mut Molecule,
+                   in_trajectory : &mut Trajectory) {
+        *self = in_saved.to_owned();
+        let count = in_objects.objects().len();
+        for node in &mut self._nodes {
+            if let Content::Object(object) = node._content {
+                if object >= count {node._content = Content::Group}
+            }
+        }
+        for object in 0..count {
+            if self.object_node(&object).is_err() {self.add_object(&object);}
+        }
+        for node in 0..self._nodes.len() {
+            let world = self.world(&node);
+            self.move_content(&node, &world, in_objects, in_molecule, in_trajectory);
+        }
+    }
+
+    /// Moves each object's atoms from where their files put them to where the nodes
+    /// do, e.g. after the files have been read again. (The cell, which isn't read
+    /// again, is where it was.)
+    pub fn reapply(&self, in_objects : &Objects, in_molecule : &mut Molecule, in_trajectory : &mut Trajectory) {
+        for (k, node) in self._nodes.iter().enumerate() {
+            if let Content::Object(_) = node._content {
+                self.move_content(&k, &self.world(&k), in_objects, in_molecule, in_trajectory);
+            }
+        }
+    }
+
+    /// The middle of what's in a node: its atoms, or, with none, the cell.
+    fn centre(&self, in_node : &usize, in_objects : &Objects, in_molecule : &Molecule) -> Result<[f32;3], String> {
+        let mut centre = [0.0f32;3];
+        let mut count = 0;
+        let mut has_cell = false;
+        for (k, node) in self._nodes.iter().enumerate() {
+            if !self.is_under(&k, in_node) {continue}
+            match node._content {
+                Content::Object(object) => {
+                    for &i in in_objects.atoms(in_molecule, &object).atoms() {
+                        let position = in_molecule.atoms()[i].position();
+                        for j in 0..3 {centre[j] += position[j]}
+                        count += 1;
+                    }
+                },
+                Content::Cell => has_cell = true,
+                Content::Group => {},
+            }
+        }
+        match *in_molecule.cell() {
+            _ if count > 0 => Ok([centre[0]/count as f32, centre[1]/count as f32, centre[2]/count as f32]),
+            Some(cell) if has_cell => {
+                let middle = cell.matrix()*[0.5, 0.5, 0.5, 1.0];
+                Ok([middle[0], middle[1], middle[2]])
+            },
+            _ => Err(format!("there's nothing in \"{}\" to move", self._nodes[*in_node]._name)),
+        }
+    }
+
+    /// Moves everything in a node, however far down, by a transformation.
+    fn move_under(&self, in_node : &usize, in_transformation : &Matrix, in_objects : &Objects,
+                  in_molecule : &mut Molecule, in_trajectory : &mut Trajectory) {
+        for k in 0..self._nodes.len() {
+            if self.is_under(&k, in_node) {
+                self.move_content(&k, in_transformation, in_objects, in_molecule, in_trajectory);
+            }
+        }
+    }
+
+    /// Moves what a node carries (not the nodes in it) by a transformation.
+    fn move_content(&self, in_node : &usize, in_transformation : &Matrix, in_objects : &Objects,
+                    in_molecule : &mut Molecule, in_trajectory : &mut Trajectory) {
+        match self._nodes[*in_node]._content {
+            Content::Object(object) => in_objects.move_atoms(&object, in_transformation, in_molecule, in_trajectory),
+            Content::Cell => {
+                let moved = in_molecule.cell().and_then(|cell| cell.transformed(in_transformation));
+                if let Some(cell) = moved {in_molecule.set_cell(&cell)}
+            },
+            Content::Group => {},
+        }
+    }
+}
@@ -0,0 +1,649 @@
+use std::borrow::Cow;
+
+use glium::{self, Surface, VertexBuffer};
+use glium::framebuffer::SimpleFrameBuffer;
+
+use assembly::{self, Operator};
+use atom::Atom;
+use background::{self, Backdrop};
+use batch::{SphereInstance, StickInstance};
+use bonds;
+use camera::Camera;
+use depth_cue::DepthCue;
+use ellipsoid;
+use fxaa::{self, FxaaSystem};
+use id_buffer;
+use material::Materials;
+use matrix::Matrix;
+use model::{DefaultModels, Model};
+use molecule::{self, Molecule};
+use oit::{self, OitSystem};
+use program::DefaultPrograms;
+use shading::Shading;
+use stereo::{self, StereoMode, StereoSystem};
+use surface::{self, SurfaceDisplay};
+use text::{self, TextSystem};
+
+/// A stick, as (from, to, colour, radius, opacity): a bond, a dash, a piece of the
+/// tube or an arrow's shaft (or, with the tip for `to`, an arrow's head).
+pub type Stick = ([f32;3], [f32;3], [f32;3], f32, f32);
+
+/// A label, at a point in the scene.
+pub type Label = ([f32;3], String);
+
+// ============================================================
+// Resources
+// ============================================================
+/// What a scene is drawn with, each made once for a display: the programs, and the
+/// models drawn with them (which point at them, so are made from them), the
+/// materials, the backdrop, the text and the passes a frame goes through.
+#[derive(Clone, Copy)]
+pub struct Resources<'a> {
+    pub programs    : &'a DefaultPrograms,
+    pub models      : &'a DefaultModels<'a>,
+    pub materials   : &'a Materials,
+    pub backdrop    : &'a Backdrop,
+    pub text_system : &'a TextSystem,
+    pub fxaa        : &'a FxaaSystem,
+    pub oit         : &'a OitSystem,
+    pub stereo      : &'a StereoSystem,
+}
+
+// ============================================================
+// Scene View
+// ============================================================
+/// The molecule in 3D, as the viewer draws it in its window, for a program to draw
+/// into a surface of its own: the atoms (and their copies), sticks, surfaces, cell,
+/// planes and labels, with the depth cue, slab, shading, stereo and FXAA, but none
+/// of the panels drawn over them. The viewer makes one each frame from its state;
+/// a program can start from new() and set what it likes:
+///
+/// let mut target = display.draw();
+/// molecule.rotate_atoms_against_camera(&camera);
+/// SceneView::new(&molecule, &camera, resources).draw(&mut target);
+/// target.finish().unwrap();
+///
+/// (The atoms' quads are turned to face the camera by the molecule, so it's done
+/// before each frame.) Where it's borrowed a list of something for each atom, an
+/// empty one means each atom's drawn as it is.
+#[derive(Clone, Copy)]
+pub struct SceneView<'a> {
+    pub molecule  : &'a Molecule<'a>,
+    pub camera    : &'a Camera,
+    pub resources : Resources<'a>,
+
+    /// Each atom drawn, where it's moved to and the operator it's a copy by (see
+    /// assembly.rs), or None for every atom once, where it is.
+    pub copies       : Option<&'a [(usize, [f32;3], usize)]>,
+    pub operators    : &'a [Operator],
+    /// Images of atoms drawn translucent, at the far ends of bonds across the cell.
+    pub ghosts       : &'a [(usize, [f32;3])],
+    pub ghost_opacity : f32,
+    /// What colour each atom's drawn, shaded, how much its species' size is scaled
+    /// by and how much of it is showing, as it fades.
+    pub colours      : &'a [[f32;3]],
+    pub scales       : &'a [f32],
+    pub visibilities : &'a [f32],
+    /// How much each atom's scaled by in the ID view, as picking does.
+    pub pick_scales  : &'a [f32],
+    pub sticks       : &'a [Stick],
+    pub arrow_heads  : &'a [Stick],
+    pub labels             : &'a [Label],
+    pub cell_labels        : &'a [Label],
+    pub measurement_labels : &'a [Label],
+
+    /// The opaque atoms and sticks drawn in one go, if they're batched, and which
+    /// atoms are in the batch (see batch.rs).
+    pub sphere_batch : Option<&'a VertexBuffer<SphereInstance>>,
+    pub stick_batch  : Option<&'a VertexBuffer<StickInstance>>,
+    pub in_batch     : &'a [bool],
+
+    pub surface         : Option<&'a surface::Surface>,
+    pub surface_display : SurfaceDisplay,
+    pub lobes           : Option<(&'a surface::Surface, &'a surface::Surface)>,
+    pub lobe_opacities  : [f32;2],
+    pub planes          : Option<&'a Model<'a>>,
+    pub plane_colour    : [f32;3],
+    pub plane_opacity   : f32,
+
+    pub light_position     : [f32;4],
+    pub background         : [f32;3],
+    pub depth_cue          : DepthCue,
+    pub depth_range        : [f32;2],
+    pub slab               : [f32;2],
+    pub shading            : Shading,
+    pub wireframe_visible  : bool,
+    pub id_view            : bool,
+    pub ellipsoids_visible : bool,
+    pub ellipsoid_radius   : f32,
+    /// With the finer meshes, once the view has settled.
+    pub refined            : bool,
+    pub cell_edges_visible : bool,
+    pub cell_colour        : [f32;3],
+    pub label_height       : f32,
+    pub label_colour       : [f32;4],
+    pub measurement_colour : [f32;3],
+    pub stereo_mode        : StereoMode,
+    pub fxaa_enabled       : bool,
+}
+
+impl<'a> SceneView<'a> {
+    /// The molecule as the viewer starts: every atom, coloured as it is, lit and
+    /// anti-aliased on the viewer's background, with no sticks, labels or cell.
+    pub fn new(in_molecule : &'a Molecule<'a>, in_camera : &'a Camera, in_resources : Resources<'a>) -> SceneView<'a> {
+        let half_width = in_molecule.bounding_sphere(|atom| *atom.species().size()).1;
+        SceneView {
+            molecule  : in_molecule,
+            camera    : in_camera,
+            resources : in_resources,
+
+            copies             : None,
+            operators          : &[],
+            ghosts             : &[],
+            ghost_opacity      : 0.4,
+            colours            : &[],
+            scales             : &[],
+            visibilities       : &[],
+            pick_scales        : &[],
+            sticks             : &[],
+            arrow_heads        : &[],
+            labels             : &[],
+            cell_labels        : &[],
+            measurement_labels : &[],
+
+            sphere_batch : None,
+            stick_batch  : None,
+            in_batch     : &[],
+
+            surface         : None,
+            surface_display : SurfaceDisplay::Hidden,
+            lobes           : None,
+            lobe_opacities  : [1.0, 1.0],
+            planes          : None,
+            plane_colour    : [0.85, 0.65, 0.2],
+            plane_opacity   : 0.4,
+
+            light_position     : [2.0, 0.0, 0.0, 1.0],
+            background         : [0.93, 0.91, 0.835],
+            depth_cue          : DepthCue::Off,
+            depth_range        : [in_camera.r() - half_width, in_camera.r() + half_width],
+            slab               : [-1e30, 1e30],
+            shading            : Shading::Lit,
+            wireframe_visible  : false,
+            id_view            : false,
+            ellipsoids_visible : false,
+            ellipsoid_radius   : ellipsoid::probability_radius(&ellipsoid::DEFAULT_PROBABILITY),
+            refined            : true,
+            cell_edges_visible : false,
+            cell_colour        : [0.3, 0.3, 0.3],
+            label_height       : 0.25,
+            label_colour       : [0.1, 0.1, 0.1, 1.0],
+            measurement_colour : [0.1, 0.5, 0.9],
+            stereo_mode        : StereoMode::Off,
+            fxaa_enabled       : true,
+        }
+    }
+
+    /// Draws the scene into `in_target`, clearing it first, through FXAA and in
+    /// stereo if they're on.
+    pub fn draw<S : Surface>(&self, in_target : &mut S) {
+        let (fxaa_system, fxaa_enabled) = (self.resources.fxaa, self.fxaa_enabled);
+        match self.stereo_mode {
+            StereoMode::Off => fxaa::draw(fxaa_system, in_target, fxaa_enabled, |target| {
+                self.draw_view(target, self.camera.view_matrix(), self.camera.vp_matrix())
+            }),
+            mode => stereo::draw(self.resources.stereo, in_target, mode, |target, eye, aspect_ratio| {
+                let (view_matrix, vp_matrix) = self.camera.eye_matrices(&eye, &aspect_ratio);
+                fxaa::draw(fxaa_system, target, fxaa_enabled, |target| {
+                    self.draw_view(target, &view_matrix, &vp_matrix)
+                })
+            }),
+        }
+    }
+
+    fn copies(&self) -> Cow<'a, [(usize, [f32;3], usize)]> {
+        match self.copies {
+            Some(copies) => Cow::Borrowed(copies),
+            None => Cow::Owned((0..self.molecule.atoms().len()).map(|i| (i, [0.0;3], 0)).collect()),
+        }
+    }
+
+    fn colour(&self, i : usize, atom : &Atom) -> [f32;3] {self.colours.get(i).cloned().unwrap_or(*atom.colour())}
+    fn scale(&self, i : usize) -> f32 {self.scales.get(i).cloned().unwrap_or(1.0)}
+    fn visibility(&self, i : usize) -> f32 {self.visibilities.get(i).cloned().unwrap_or(1.0)}
+    fn is_batched(&self, i : usize) -> bool {self.in_batch.get(i) == Some(&true)}
+
+    // atoms fading in or out are drawn with the translucent ones
+    fn is_opaque(&self, i : usize) -> bool {
+        !self.molecule.atoms()[i].is_translucent() && self.visibility(i) >= 1.0
+    }
+
+    /// Draws everything, as seen with the given view and view-projection matrices.
+    fn draw_view(&self, in_target : &mut SimpleFrameBuffer, in_view_matrix : &Matrix, in_vp_matrix : &Matrix) {
+        let (view_matrix, vp_matrix) = (in_view_matrix, in_vp_matrix);
+        let Resources {programs, models, materials, backdrop, text_system, ..} = self.resources;
+        let molecule = self.molecule;
+        let copies = self.copies();
+        let (depth_cue, depth_range, slab) = (self.depth_cue, self.depth_range, self.slab);
+        let light_position = *view_matrix * self.light_position;
+        // each representation is drawn with the depth test and culling of its
+        // material, and its offset
+        let atom_params = materials.atoms().parameters();
+        let atom_translucent_params = materials.atoms().translucent_parameters();
+        let stick_params = materials.sticks().parameters();
+        let stick_translucent_params = materials.sticks().translucent_parameters();
+        let cell_params = materials.cell_edges().parameters();
+        let plane_params = materials.planes().translucent_parameters();
+        let surface_params = materials.surface().parameters();
+        let surface_translucent_params = materials.surface().translucent_parameters();
+        // the wireframe is drawn over the faces it's the edges of
+        let wireframe_params = glium::DrawParameters {
+            depth : glium::Depth {
+                test : glium::DepthTest::IfLessOrEqual,
+                .. Default::default()
+            },
+            polygon_mode : glium::PolygonMode::Line,
+            .. Default::default()
+        };
+        let atom_vp_matrix = materials.atoms().offset_matrix(vp_matrix);
+        let stick_vp_matrix = materials.sticks().offset_matrix(vp_matrix);
+        let cell_vp_matrix = materials.cell_edges().offset_matrix(vp_matrix);
+        let plane_vp_matrix = materials.planes().offset_matrix(vp_matrix);
+        let surface_vp_matrix = materials.surface().offset_matrix(vp_matrix);
+        // the atoms and sticks reflect the environment, if there is one
+        let environment_rotation = background::environment_rotation(view_matrix);
+        // ghosts of atoms are drawn fainter by `fading`
+        let atom_uniforms = |i : usize, atom : &Atom, shift : &[f32;3], fading : f32, shading : Shading| {
+            let scale = self.scale(i);
+            let mut model_matrix = assembly::shifted(atom.model_matrix(), shift).contents().to_owned();
+            for row in 0..3 {
+                for column in 0..3 {
+                    model_matrix[row][column] *= scale;
+                }
+            }
+            let model_matrix = Matrix::new(model_matrix);
+            let mv_matrix = *view_matrix * model_matrix;
+            let mvp_matrix = atom_vp_matrix * model_matrix;
+            uniform!{
+            mv_matrix      : mv_matrix.contents().to_owned(),
+            mvp_matrix     : mvp_matrix.contents().to_owned(),
+            colour         : self.colour(i, atom),
+            light_position : light_position,
+            size           : *atom.species().size()*scale,
+            depth_cue      : depth_cue.uniform(),
+            shading        : shading.uniform(),
+            depth_range    : depth_range,
+            slab           : slab,
+            opacity        : *atom.opacity()*self.visibility(i)*fading,
+            environment          : backdrop.environment(),
+            environment_rotation : environment_rotation,
+            reflectivity         : backdrop.reflectivity(),
+            }
+        };
+        // atoms with displacement parameters, while ellipsoids are shown, are their
+        // thermal ellipsoids instead
+        let ellipsoid_uniforms = |i : usize, atom : &Atom, shift : &[f32;3], operator : usize, shading : Shading| {
+            if !self.ellipsoids_visible {return None}
+            let position = atom.position();
+            let position = [position[0]+shift[0], position[1]+shift[1], position[2]+shift[2]];
+            let radius = self.ellipsoid_radius*self.scale(i);
+            let adp = atom.adp().as_ref()?;
+            let adp = self.operators.get(operator).map_or(*adp, |operator| operator.turned(adp));
+            let (model_matrix, normal) = ellipsoid::matrices(&position, &adp, &radius)?;
+            let mv_matrix = *view_matrix * model_matrix;
+            let mvp_matrix = atom_vp_matrix * model_matrix;
+            let normal_matrix = *view_matrix * normal;
+            Some(uniform!{
+            mv_matrix      : mv_matrix.contents().to_owned(),
+            mvp_matrix     : mvp_matrix.contents().to_owned(),
+            normal_matrix  : normal_matrix.contents().to_owned(),
+            colour         : self.colour(i, atom),
+            light_position : light_position,
+            depth_cue      : depth_cue.uniform(),
+            shading        : shading.uniform(),
+            depth_range    : depth_range,
+            slab           : slab,
+            opacity        : *atom.opacity()*self.visibility(i),
+            environment          : backdrop.environment(),
+            environment_rotation : environment_rotation,
+            reflectivity         : backdrop.reflectivity(),
+            })
+        };
+        let sphere_model = models.sphere();
+        let ellipsoid_model = if self.refined {models.fine_ellipsoid()} else {models.ellipsoid()};
+        let cylinder = if self.refined {models.fine_cylinder()} else {models.cylinder()};
+        let cone = models.cone();
+        let stick_uniforms = |&(from, to, colour, radius, opacity) : &Stick, shading : Shading| {
+            let model_matrix = bonds::model_matrix(&from, &to, &radius);
+            let mv_matrix = *view_matrix * model_matrix;
+            let mvp_matrix = stick_vp_matrix * model_matrix;
+            uniform!{
+            mv_matrix      : mv_matrix.contents().to_owned(),
+            mvp_matrix     : mvp_matrix.contents().to_owned(),
+            colour         : colour,
+            light_position : light_position,
+            depth_cue      : depth_cue.uniform(),
+            shading        : shading.uniform(),
+            depth_range    : depth_range,
+            slab           : slab,
+            opacity        : opacity,
+            environment          : backdrop.environment(),
+            environment_rotation : environment_rotation,
+            reflectivity         : backdrop.reflectivity(),
+            }
+        };
+        // the batches place their atoms and sticks themselves, so are given the
+        // view's own matrices, and turn the atoms' quads to face the camera
+        let billboard = molecule::billboard_matrix(self.camera);
+        let batch_uniforms = |vp_matrix : &Matrix, shading : Shading| {
+            uniform!{
+            mv_matrix      : view_matrix.contents().to_owned(),
+            mvp_matrix     : vp_matrix.contents().to_owned(),
+            billboard      : billboard.contents().to_owned(),
+            light_position : light_position,
+            depth_cue      : depth_cue.uniform(),
+            shading        : shading.uniform(),
+            depth_range    : depth_range,
+            slab           : slab,
+            environment          : backdrop.environment(),
+            environment_rotation : environment_rotation,
+            reflectivity         : backdrop.reflectivity(),
+            }
+        };
+        let batched = self.sphere_batch.is_some() || self.stick_batch.is_some();
+        let lobes : Vec<(&surface::Surface, f32)> = self.lobes.iter().flat_map(|&(positive, negative)| {
+            vec![(positive, self.lobe_opacities[0]), (negative, self.lobe_opacities[1])]
+        }).collect();
+        let shading = self.shading;
+        oit::draw(self.resources.oit, in_target, |target| {
+            if self.id_view {
+                // what id_buffer::pick() draws, on black, which is no atom
+                target.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
+                for &(i, ref shift, _) in copies.iter() {
+                    let atom = &molecule.atoms()[i];
+                    let scale = self.pick_scales.get(i).cloned().unwrap_or_else(|| self.scale(i));
+                    let model_matrix = assembly::shifted(atom.model_matrix(), shift)*Matrix::scaling(&[scale;3]);
+                    let mv_matrix = *view_matrix * model_matrix;
+                    let mvp_matrix = atom_vp_matrix * model_matrix;
+                    target.draw(
+                        sphere_model.vertex_buffer(),
+                        sphere_model.index_buffer(),
+                        &sphere_model.picking_program(),
+                        &uniform!{
+                        mv_matrix  : mv_matrix.contents().to_owned(),
+                        mvp_matrix : mvp_matrix.contents().to_owned(),
+                        size       : *atom.species().size()*scale,
+                        slab       : slab,
+                        id         : id_buffer::view_colour(i),
+                        },
+                        &atom_params,
+                    ).unwrap();
+                }
+                return;
+            }
+            let background = self.background;
+            target.clear_color_and_depth((background[0], background[1], background[2], 1.0), 1.0);
+            backdrop.draw(target, view_matrix, self.camera.field_of_view());
+            for &(i, ref shift, k) in copies.iter().filter(|&&(i, _, _)| self.is_opaque(i)) {
+                let atom = &molecule.atoms()[i];
+                if let Some(uniforms) = ellipsoid_uniforms(i, atom, shift, k, shading) {
+                    target.draw(
+                        ellipsoid_model.vertex_buffer(),
+                        ellipsoid_model.index_buffer(),
+                        &ellipsoid_model.program(),
+                        &uniforms,
+                        &atom_params,
+                    ).unwrap();
+                    continue;
+                }
+                if self.is_batched(i) {continue}
+                target.draw(
+                    sphere_model.vertex_buffer(),
+                    sphere_model.index_buffer(),
+                    &sphere_model.program(),
+                    &atom_uniforms(i, atom, shift, 1.0, shading),
+                    &atom_params,
+                ).unwrap();
+            }
+            if let Some(instances) = self.sphere_batch {
+                target.draw(
+                    (sphere_model.vertex_buffer(), instances.per_instance().unwrap()),
+                    sphere_model.index_buffer(),
+                    &programs.sphere_instanced().borrow(),
+                    &batch_uniforms(&atom_vp_matrix, shading),
+                    &atom_params,
+                ).unwrap();
+            }
+            for stick in self.sticks.iter().filter(|stick| stick.4 >= 1.0 && !batched) {
+                target.draw(
+                    cylinder.vertex_buffer(),
+                    cylinder.index_buffer(),
+                    &cylinder.program(),
+                    &stick_uniforms(stick, shading),
+                    &stick_params,
+                ).unwrap();
+            }
+            if let Some(instances) = self.stick_batch {
+                target.draw(
+                    (cylinder.vertex_buffer(), instances.per_instance().unwrap()),
+                    cylinder.index_buffer(),
+                    &programs.polyhedron_instanced().borrow(),
+                    &batch_uniforms(&stick_vp_matrix, shading),
+                    &stick_params,
+                ).unwrap();
+            }
+            for head in self.arrow_heads {
+                target.draw(
+                    cone.vertex_buffer(),
+                    cone.index_buffer(),
+                    &cone.program(),
+                    &stick_uniforms(head, shading),
+                    &stick_params,
+                ).unwrap();
+            }
+            let opaque_surface = self.surface.filter(|_| self.surface_display == SurfaceDisplay::Opaque);
+            let opaque_lobes = lobes.iter().filter(|&&(_, opacity)| opacity >= 1.0).map(|&(lobe, _)| lobe);
+            for mesh in opaque_surface.into_iter().chain(opaque_lobes) {
+                target.draw(
+                    mesh.vertex_buffer(),
+                    mesh.index_buffer(),
+                    &programs.surface().borrow(),
+                    &uniform!{
+                    mv_matrix      : view_matrix.contents().to_owned(),
+                    mvp_matrix     : surface_vp_matrix.contents().to_owned(),
+                    light_position : light_position,
+                    depth_cue      : depth_cue.uniform(),
+                    shading        : shading.uniform(),
+                    depth_range    : depth_range,
+                    slab           : slab,
+                    },
+                    &surface_params,
+                ).unwrap();
+            }
+            if let (Some(cell), true) = (*molecule.cell(), self.cell_edges_visible) {
+                let cell_box = models.cell_box();
+                let model_matrix = cell.matrix();
+                let mv_matrix = *view_matrix * model_matrix;
+                let mvp_matrix = cell_vp_matrix * model_matrix;
+                target.draw(
+                    cell_box.vertex_buffer(),
+                    cell_box.index_buffer(),
+                    &cell_box.program(),
+                    &uniform!{
+                    mv_matrix   : mv_matrix.contents().to_owned(),
+                    mvp_matrix  : mvp_matrix.contents().to_owned(),
+                    colour      : self.cell_colour,
+                    depth_cue   : depth_cue.uniform(),
+                    depth_range : depth_range,
+                    slab        : slab,
+                    },
+                    &cell_params,
+                ).unwrap();
+            }
+            // the spheres are billboards, so only the meshes have wireframes
+            if self.wireframe_visible {
+                for &(i, ref shift, k) in copies.iter() {
+                    let atom = &molecule.atoms()[i];
+                    if let Some(uniforms) = ellipsoid_uniforms(i, atom, shift, k, Shading::Wireframe) {
+                        target.draw(
+                            ellipsoid_model.vertex_buffer(),
+                            ellipsoid_model.index_buffer(),
+                            &ellipsoid_model.program(),
+                            &uniforms,
+                            &wireframe_params,
+                        ).unwrap();
+                    }
+                }
+                for stick in self.sticks.iter().filter(|stick| stick.4 < 1.0 || !batched) {
+                    target.draw(
+                        cylinder.vertex_buffer(),
+                        cylinder.index_buffer(),
+                        &cylinder.program(),
+                        &stick_uniforms(stick, Shading::Wireframe),
+                        &wireframe_params,
+                    ).unwrap();
+                }
+                if let Some(instances) = self.stick_batch {
+                    target.draw(
+                        (cylinder.vertex_buffer(), instances.per_instance().unwrap()),
+                        cylinder.index_buffer(),
+                        &programs.polyhedron_instanced().borrow(),
+                        &batch_uniforms(&stick_vp_matrix, Shading::Wireframe),
+                        &wireframe_params,
+                    ).unwrap();
+                }
+                for head in self.arrow_heads {
+                    target.draw(
+                        cone.vertex_buffer(),
+                        cone.index_buffer(),
+                        &cone.program(),
+                        &stick_uniforms(head, Shading::Wireframe),
+                        &wireframe_params,
+                    ).unwrap();
+                }
+                let surface = self.surface.filter(|_| self.surface_display != SurfaceDisplay::Hidden);
+                for mesh in surface.into_iter().chain(lobes.iter().map(|&(lobe, _)| lobe)) {
+                    target.draw(
+                        mesh.vertex_buffer(),
+                        mesh.index_buffer(),
+                        &programs.surface().borrow(),
+                        &uniform!{
+                        mv_matrix      : view_matrix.contents().to_owned(),
+                        mvp_matrix     : surface_vp_matrix.contents().to_owned(),
+                        light_position : light_position,
+                        shading        : Shading::Wireframe.uniform(),
+                        slab           : slab,
+                        },
+                        &wireframe_params,
+                    ).unwrap();
+                }
+                if let Some(planes) = self.planes {
+                    target.draw(
+                        planes.vertex_buffer(),
+                        planes.index_buffer(),
+                        &planes.program(),
+                        &uniform!{
+                        mv_matrix      : view_matrix.contents().to_owned(),
+                        mvp_matrix     : plane_vp_matrix.contents().to_owned(),
+                        colour         : self.plane_colour,
+                        light_position : light_position,
+                        shading        : Shading::Wireframe.uniform(),
+                        slab           : slab,
+                        },
+                        &wireframe_params,
+                    ).unwrap();
+                }
+            }
+            let label_height = self.label_height;
+            let (c, m) = (self.cell_colour, self.measurement_colour);
+            text::draw_labels(text_system, target, self.labels, view_matrix, vp_matrix, label_height,
+                              self.label_colour);
+            text::draw_labels(text_system, target, self.cell_labels, view_matrix, vp_matrix, label_height,
+                              [c[0], c[1], c[2], 1.0]);
+            text::draw_labels(text_system, target, self.measurement_labels, view_matrix, vp_matrix, label_height,
+                              [m[0], m[1], m[2], 1.0]);
+        }, |target| {
+            if self.id_view {return}
+            for &(i, ref shift, k) in copies.iter().filter(|&&(i, _, _)| !self.is_opaque(i)) {
+                let atom = &molecule.atoms()[i];
+                if let Some(uniforms) = ellipsoid_uniforms(i, atom, shift, k, shading) {
+                    target.draw(
+                        ellipsoid_model.vertex_buffer(),
+                        ellipsoid_model.index_buffer(),
+                        &ellipsoid_model.translucent_program(),
+                        &uniforms,
+                        &atom_translucent_params,
+                    ).unwrap();
+                    continue;
+                }
+                target.draw(
+                    sphere_model.vertex_buffer(),
+                    sphere_model.index_buffer(),
+                    &sphere_model.translucent_program(),
+                    &atom_uniforms(i, atom, shift, 1.0, shading),
+                    &atom_translucent_params,
+                ).unwrap();
+            }
+            for &(i, ref shift) in self.ghosts {
+                let atom = &molecule.atoms()[i];
+                target.draw(
+                    sphere_model.vertex_buffer(),
+                    sphere_model.index_buffer(),
+                    &sphere_model.translucent_program(),
+                    &atom_uniforms(i, atom, shift, self.ghost_opacity, shading),
+                    &atom_translucent_params,
+                ).unwrap();
+            }
+            for stick in self.sticks.iter().filter(|stick| stick.4 < 1.0) {
+                target.draw(
+                    cylinder.vertex_buffer(),
+                    cylinder.index_buffer(),
+                    &cylinder.translucent_program(),
+                    &stick_uniforms(stick, shading),
+                    &stick_translucent_params,
+                ).unwrap();
+            }
+            let translucent_surface = self.surface.filter(|_| self.surface_display == SurfaceDisplay::Translucent)
+                .map(|surface| (surface, surface::TRANSLUCENT_OPACITY));
+            let translucent_lobes = lobes.iter().filter(|&&(_, opacity)| opacity < 1.0).cloned();
+            for (mesh, opacity) in translucent_surface.into_iter().chain(translucent_lobes) {
+                target.draw(
+                    mesh.vertex_buffer(),
+                    mesh.index_buffer(),
+                    &programs.surface_translucent().borrow(),
+                    &uniform!{
+                    mv_matrix      : view_matrix.contents().to_owned(),
+                    mvp_matrix     : surface_vp_matrix.contents().to_owned(),
+                    light_position : light_position,
+                    depth_cue      : depth_cue.uniform(),
+                    shading        : shading.uniform(),
+                    depth_range    : depth_range,
+                    slab           : slab,
+                    opacity        : opacity,
+                    },
+                    &surface_translucent_params,
+                ).unwrap();
+            }
+            // the planes' corners are already where they go in the cell
+            if let Some(planes) = self.planes {
+                target.draw(
+                    planes.vertex_buffer(),
+                    planes.index_buffer(),
+                    &planes.translucent_program(),
+                    &uniform!{
+                    mv_matrix      : view_matrix.contents().to_owned(),
+                    mvp_matrix     : plane_vp_matrix.contents().to_owned(),
+                    colour         : self.plane_colour,
+                    light_position : light_position,
+                    depth_cue      : depth_cue.uniform(),
+                    shading        : shading.uniform(),
+                    depth_range    : depth_range,
+                    slab           : slab,
+                    opacity        : self.plane_opacity,
+                    },
+                    &plane_params,
+                ).unwrap();
+            }
+        });
+    }
+}
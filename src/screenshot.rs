@@ -0,0 +1,168 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+use glium::{self, Surface};
+use glium::backend::glutin_backend::GlutinFacade;
+use glium::framebuffer::SimpleFrameBuffer;
+use miniz_oxide;
+
+/// The size of the framebuffer drawn into in software: glium's headless contexts
+/// always report this size, whatever they're made with.
+pub const SOFTWARE_SIZE : (u32, u32) = (800, 600);
+
+// ============================================================
+// Software rendering
+// ============================================================
+/// A display with no window, drawn by Mesa's OSMesa software rasteriser (llvmpipe
+/// where Mesa was built with it), for machines with no GPU or display server, e.g.
+/// to replay sessions in CI. There are no events, so a replayed session stands in
+/// for the user. Needs libOSMesa with OpenGL 3.1.
+pub fn software_display() -> Result<GlutinFacade, String> {
+    use glium::DisplayBuild;
+    glium::glutin::HeadlessRendererBuilder::new(SOFTWARE_SIZE.0, SOFTWARE_SIZE.1)
+        .build_glium()
+        .map_err(|e| format!("couldn't draw in software: {}", e))
+}
+
+// ============================================================
+// Screenshot
+// ============================================================
+/// Saves the last frame drawn, so a headless run leaves something to look at or
+/// compare: as a PNG if the file name ends .png, otherwise as a binary PPM, which
+/// anything can read.
+pub fn save(in_display : &GlutinFacade, in_fname : &str) -> Result<(), String> {
+    let (size, rgb) = rgb_image(&in_display.read_front_buffer());
+    write_image(in_fname, &size, &rgb)
+}
+
+/// The size and RGB bytes of pixels read back from OpenGL.
+fn rgb_image(in_pixels : &Vec<Vec<(u8, u8, u8, u8)>>) -> ((usize, usize), Vec<u8>) {
+    let (width, height) = (in_pixels.get(0).map_or(0, |row| row.len()), in_pixels.len());
+    let mut bytes = Vec::with_capacity(3*width*height);
+    // OpenGL's rows go from the bottom up, and images' from the top down
+    for row in in_pixels.iter().rev() {
+        for &(r, g, b, _) in row {
+            bytes.extend_from_slice(&[r, g, b]);
+        }
+    }
+    ((width, height), bytes)
+}
+
+// ============================================================
+// Prints
+// ============================================================
+/// How big a print (ctrl+P) is, e.g. for a journal figure, which a screenshot of the
+/// window is too small for.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PrintSize {
+    /// So many times the window's width and height.
+    Scale(u32),
+    /// A width and height in pixels. The view is widened or narrowed to fit.
+    Pixels(u32, u32),
+}
+
+impl PrintSize {
+    /// Reads a size as given to --print-size: a scale such as "4x", or a width and
+    /// height in pixels such as "3000x2000".
+    pub fn parse(in_text : &str) -> Option<PrintSize> {
+        let numbers : Vec<&str> = in_text.trim().split('x').collect();
+        if numbers.len() != 2 {return None}
+        let first = numbers[0].parse::<u32>().ok().filter(|&n| n > 0)?;
+        if numbers[1].is_empty() {return Some(PrintSize::Scale(first))}
+        let second = numbers[1].parse::<u32>().ok().filter(|&n| n > 0)?;
+        Some(PrintSize::Pixels(first, second))
+    }
+
+    /// The width and height in pixels, for a window of the given size.
+    pub fn pixels(&self, in_window_size : &(u32, u32)) -> (u32, u32) {
+        match *self {
+            PrintSize::Scale(scale) => (scale*in_window_size.0, scale*in_window_size.1),
+            PrintSize::Pixels(width, height) => (width, height),
+        }
+    }
+}
+
+/// Lets `draw` draw into a framebuffer of its own (with a depth buffer) of `in_size`
+/// pixels, off the screen, and gives back the size and RGB bytes of what it drew,
+/// for write_image().
+pub fn render<F>(in_display : &GlutinFacade, in_size : &(u32, u32), mut draw : F)
+                 -> Result<((usize, usize), Vec<u8>), String>
+                 where F : FnMut(&mut SimpleFrameBuffer)
+{
+    let (width, height) = *in_size;
+    // e.g. bigger than the driver's largest texture
+    let too_big = |e : String| format!("couldn't make a {}x{} framebuffer ({})", width, height, e);
+    let colour = glium::texture::Texture2d::empty_with_format(in_display,
+        glium::texture::UncompressedFloatFormat::U8U8U8U8,
+        glium::texture::MipmapsOption::NoMipmap, width, height).map_err(|e| too_big(format!("{:?}", e)))?;
+    let depth = glium::framebuffer::DepthRenderBuffer::new(in_display,
+        glium::texture::DepthFormat::I24, width, height).map_err(|e| too_big(format!("{:?}", e)))?;
+    {
+        let mut target = SimpleFrameBuffer::with_depth_buffer(in_display, &colour, &depth).map_err(|e| too_big(format!("{:?}", e)))?;
+        target.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
+        draw(&mut target);
+    }
+    Ok(rgb_image(&colour.read()))
+}
+
+/// Writes an image given as RGB bytes, row by row from the top: as a PNG if the
+/// file name ends .png, otherwise as a binary PPM.
+pub fn write_image(in_fname : &str, in_size : &(usize, usize), in_rgb : &Vec<u8>) -> Result<(), String> {
+    let file = File::create(in_fname).map_err(|e| format!("couldn't create {}: {}", in_fname, e))?;
+    let mut file = BufWriter::new(file);
+    let written = if in_fname.to_lowercase().ends_with(".png") {
+        file.write_all(&png(in_size, in_rgb))
+    } else {
+        write!(file, "P6\n{} {}\n255\n", in_size.0, in_size.1).and_then(|_| file.write_all(in_rgb))
+    };
+    written.and_then(|_| file.flush()).map_err(|e| format!("couldn't write to {}: {}", in_fname, e))
+}
+
+// ============================================================
+// PNG
+// ============================================================
+/// A PNG file of RGB bytes, its rows unfiltered and deflated.
+fn png(in_size : &(usize, usize), in_rgb : &Vec<u8>) -> Vec<u8> {
+    let (width, height) = *in_size;
+    // each row starts with its filter, 0 for none
+    let mut raw = Vec::with_capacity((3*width+1)*height);
+    for row in in_rgb.chunks(3*width.max(1)).take(height) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let zlib = miniz_oxide::deflate::compress_to_vec_zlib(&raw, 6);
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&be_bytes(width as u32));
+    header.extend_from_slice(&be_bytes(height as u32));
+    // 8 bits a channel, RGB, and the only compression, filtering and interlacing
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = vec![137, 80, 78, 71, 13, 10, 26, 10];
+    for &(kind, ref data) in &[(b"IHDR", header), (b"IDAT", zlib), (b"IEND", Vec::new())] {
+        png.extend_from_slice(&be_bytes(data.len() as u32));
+        let start = png.len();
+        png.extend_from_slice(kind);
+        png.extend_from_slice(data);
+        let crc = crc32(&png[start..]);
+        png.extend_from_slice(&be_bytes(crc));
+    }
+    png
+}
+
+fn be_bytes(in_value : u32) -> [u8;4] {
+    [(in_value >> 24) as u8, (in_value >> 16) as u8, (in_value >> 8) as u8, in_value as u8]
+}
+
+/// The CRC each PNG chunk ends with.
+fn crc32(in_bytes : &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for &byte in in_bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {0xedb88320 ^ (crc >> 1)} else {crc >> 1};
+        }
+    }
+    !crc
+}
@@ -0,0 +1,250 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+use rhai::{Dynamic, Engine, EvalAltResult};
+
+use arrows::ArrowDisplay;
+use bonds::BondDisplay;
+use camera::Projection;
+use colour::ColourScheme;
+use label::LabelContent;
+use radii::RadiusSource;
+use surface::SurfaceDisplay;
+use tube::TubeDisplay;
+
+// ============================================================
+// Commands
+// ============================================================
+/// What a script asks the viewer to do. Groups are as the scene files take them (a
+/// named selection, an element or a fragment, see Selection::by_name()), and queries
+/// as typed at the prompt (see selection/query.rs).
+#[derive(Clone, Debug)]
+pub enum Command {
+    /// Load a structure file in place of the one showing.
+    Load(String),
+    /// Add a structure file's atoms to the ones showing.
+    Add(String),
+    Select(String),
+    Colour(String, [f32;3]),
+    ColourScheme(ColourScheme),
+    Opacity(String, f32),
+    Bonds(BondDisplay),
+    Backbone(TubeDisplay),
+    /// Draw the atoms with a source's radii, all scaled by a factor (see radii.rs).
+    Radii(RadiusSource, f32),
+    /// Draw the forces, velocities or dipole as arrows, the longest scaled from
+    /// arrows::LONGEST by a factor (see arrows.rs).
+    Arrows(ArrowDisplay, f32),
+    Labels(LabelContent),
+    Surface(SurfaceDisplay),
+    Projection(Projection),
+    Focus([f32;3]),
+    /// Theta, phi and psi, in degrees, as the camera takes them.
+    Angles([f32;3]),
+    Distance(f32),
+    /// Fit the molecule in view.
+    Fit,
+    /// Turn the camera about an axis, by some degrees over some seconds.
+    Turn([f32;3], f32, f32),
+    /// Show a trajectory frame, counting from 0.
+    Frame(usize),
+    /// Show an object (a file loaded, see objects.rs), counting from 0 here though
+    /// from 1 in scripts, as the keys do.
+    Show(usize),
+    Hide(usize),
+    /// Move a node of the scene graph (an object's number, "cell", a group or
+    /// "scene", see scene_graph.rs) by x, y and z.
+    Move(String, [f32;3]),
+    /// Turn a node about its centre, about an axis by some degrees.
+    Rotate(String, [f32;3], f32),
+    /// Put a node back where its group puts it.
+    PutBack(String),
+    /// Put a node in a group, made if there's none by that name.
+    Group(String, String),
+    /// Save the next frame drawn.
+    Screenshot(String),
+    Quit,
+}
+
+/// An object's index from the number a script gives it, counting from 1.
+pub fn object(in_number : &i64) -> Result<usize, String> {
+    if *in_number < 1 {return Err(format!("objects count from 1, not {}", in_number))}
+    Ok(*in_number as usize-1)
+}
+
+/// The name of an object's node in the scene graph, from the number a script gives
+/// it.
+fn object_node(in_number : &i64) -> Result<String, String> {
+    object(in_number).map(|k| format!("{}", k+1))
+}
+
+/// A command, and where to say how it went: Err with why, if it couldn't be done.
+pub type Request = (Command, Sender<Result<(), String>>);
+
+// ============================================================
+// Script
+// ============================================================
+/// A script (--script, or a line typed at the console with shift+Return) in Rhai
+/// (https://rhai.rs), for figures made the same way every time, e.g.
+///
+/// load("protein.pdb");
+/// select("ligand = resname LIG");
+/// colour("ligand", 1.0, 0.5, 0.0);
+/// backbone("tube");
+/// for i in 0..36 {
+///     turn(0, 1, 0, 10, 0.2);
+///     screenshot(`frame${i}.png`);
+/// }
+///
+/// It runs in a thread of its own, sending the viewer one command at a time and
+/// waiting for each to be done (a screenshot, for the frame to be drawn), so it can
+/// loop and branch as it likes. A command that can't be done stops the script.
+///
+/// The commands: load(file), add(file), select(query), colour(group, r, g, b),
+/// colours(scheme), opacity(group, opacity), bonds(mode), backbone(mode),
+/// radii(source, scale), arrows(mode, scale), labels(mode), surface(mode),
+/// projection(mode), focus(x, y, z), angles(theta, phi, psi), distance(d), fit(),
+/// turn(x, y, z, degrees, seconds) (which waits while it turns), frame(k), show(object), hide(object), move(node, x, y, z),
+/// rotate(node, x, y, z, degrees), put_back(node), group(node, group),
+/// wait(seconds), screenshot(file) and quit(). Objects count from 1, and nodes are
+/// objects (by number), "cell", groups (by name) or "scene" (see scene_graph.rs),
+/// so that rotate("scene", 0, 1, 0, 90) turns everything about its centre.
+pub struct Script {
+    _name     : String,
+    _requests : Receiver<Request>,
+    _finished : Receiver<Result<(), String>>,
+}
+
+impl Script {
+    /// Runs a script, called `in_name` in messages.
+    pub fn start(in_name : &str, in_source : &str) -> Script {
+        let (requests, requested) = mpsc::channel();
+        let (finish, finished) = mpsc::channel();
+        let source = in_source.to_owned();
+        thread::spawn(move || {
+            let engine = engine(&requests);
+            finish.send(engine.run(&source).map_err(|e| e.to_string())).ok();
+        });
+        Script {
+            _name     : in_name.to_owned(),
+            _requests : requested,
+            _finished : finished,
+        }
+    }
+
+    pub fn read(in_fname : &str) -> Result<Script, String> {
+        let mut file = File::open(in_fname).map_err(|e| format!("couldn't open {}: {}", in_fname, e))?;
+        let mut source = String::new();
+        file.read_to_string(&mut source).map_err(|e| format!("couldn't read {}: {}", in_fname, e))?;
+        Ok(Script::start(in_fname, &source))
+    }
+
+    pub fn name(&self) -> &str {&self._name}
+
+    /// The command the script's waiting on, if it's sent one.
+    pub fn next(&self) -> Option<Request> {self._requests.try_recv().ok()}
+
+    /// How the script ended, once it has: Err with why, if it was stopped.
+    pub fn finished(&self) -> Option<Result<(), String>> {
+        match self._finished.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("the script stopped".to_owned())),
+        }
+    }
+}
+
+/// Sends the viewer a command and waits until it's been done.
+fn send(in_requests : &Sender<Request>, in_command : Command) -> Result<(), Box<EvalAltResult>> {
+    let (reply, replied) = mpsc::channel();
+    in_requests.send((in_command, reply)).map_err(|_| "the viewer has closed".to_owned())?;
+    match replied.recv() {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(message)) => Err(message.into()),
+        Err(_) => Err("the viewer has closed".into()),
+    }
+}
+
+/// A number given to a command, whether the script wrote it with a decimal point
+/// or not.
+fn number(in_value : &Dynamic) -> Result<f32, Box<EvalAltResult>> {
+    in_value.as_float().map(|x| x as f32)
+        .or_else(|_| in_value.as_int().map(|n| n as f32))
+        .map_err(|kind| format!("expected a number, not {}", kind).into())
+}
+
+/// The Rhai engine with the commands, each sending requests to the viewer.
+fn engine(in_requests : &Sender<Request>) -> Engine {
+    let mut engine = Engine::new();
+    macro_rules! command {
+        ($name:expr, |$($argument:ident : $kind:ty),*| $make:expr) => {{
+            let requests = in_requests.clone();
+            engine.register_fn($name, move |$($argument : $kind),*| -> Result<(), Box<EvalAltResult>> {
+                let command : Result<Command, Box<EvalAltResult>> = $make;
+                send(&requests, command?)
+            });
+        }};
+    }
+    command!("load", |fname : &str| Ok(Command::Load(fname.to_owned())));
+    command!("add", |fname : &str| Ok(Command::Add(fname.to_owned())));
+    command!("select", |query : &str| Ok(Command::Select(query.to_owned())));
+    command!("colour", |group : &str, r : Dynamic, g : Dynamic, b : Dynamic| {
+        Ok(Command::Colour(group.to_owned(), [number(&r)?, number(&g)?, number(&b)?]))
+    });
+    command!("colours", |scheme : &str| Ok(Command::ColourScheme(ColourScheme::parse(scheme)?)));
+    command!("opacity", |group : &str, opacity : Dynamic| Ok(Command::Opacity(group.to_owned(), number(&opacity)?)));
+    command!("bonds", |mode : &str| Ok(Command::Bonds(BondDisplay::parse(mode)?)));
+    command!("backbone", |mode : &str| Ok(Command::Backbone(TubeDisplay::parse(mode)?)));
+    command!("radii", |source : &str, scale : Dynamic| {
+        Ok(Command::Radii(RadiusSource::parse(source)?, number(&scale)?))
+    });
+    command!("arrows", |mode : &str, scale : Dynamic| {
+        Ok(Command::Arrows(ArrowDisplay::parse(mode)?, number(&scale)?))
+    });
+    command!("labels", |mode : &str| Ok(Command::Labels(LabelContent::parse(mode)?)));
+    command!("surface", |mode : &str| Ok(Command::Surface(SurfaceDisplay::parse(mode)?)));
+    command!("projection", |mode : &str| Ok(Command::Projection(Projection::parse(mode)?)));
+    command!("focus", |x : Dynamic, y : Dynamic, z : Dynamic| {
+        Ok(Command::Focus([number(&x)?, number(&y)?, number(&z)?]))
+    });
+    command!("angles", |theta : Dynamic, phi : Dynamic, psi : Dynamic| {
+        Ok(Command::Angles([number(&theta)?, number(&phi)?, number(&psi)?]))
+    });
+    command!("distance", |d : Dynamic| Ok(Command::Distance(number(&d)?)));
+    command!("fit", | | Ok(Command::Fit));
+    command!("turn", |x : Dynamic, y : Dynamic, z : Dynamic, degrees : Dynamic, seconds : Dynamic| {
+        Ok(Command::Turn([number(&x)?, number(&y)?, number(&z)?], number(&degrees)?, number(&seconds)?.max(0.0)))
+    });
+    command!("frame", |k : i64| {
+        if k < 0 {return Err(format!("no frame {}", k).into())}
+        Ok(Command::Frame(k as usize))
+    });
+    command!("show", |k : i64| Ok(Command::Show(object(&k)?)));
+    command!("hide", |k : i64| Ok(Command::Hide(object(&k)?)));
+    // (nodes by name, or objects by number)
+    command!("move", |node : &str, x : Dynamic, y : Dynamic, z : Dynamic| {
+        Ok(Command::Move(node.to_owned(), [number(&x)?, number(&y)?, number(&z)?]))
+    });
+    command!("move", |k : i64, x : Dynamic, y : Dynamic, z : Dynamic| {
+        Ok(Command::Move(object_node(&k)?, [number(&x)?, number(&y)?, number(&z)?]))
+    });
+    command!("rotate", |node : &str, x : Dynamic, y : Dynamic, z : Dynamic, degrees : Dynamic| {
+        Ok(Command::Rotate(node.to_owned(), [number(&x)?, number(&y)?, number(&z)?], number(&degrees)?))
+    });
+    command!("rotate", |k : i64, x : Dynamic, y : Dynamic, z : Dynamic, degrees : Dynamic| {
+        Ok(Command::Rotate(object_node(&k)?, [number(&x)?, number(&y)?, number(&z)?], number(&degrees)?))
+    });
+    command!("put_back", |node : &str| Ok(Command::PutBack(node.to_owned())));
+    command!("put_back", |k : i64| Ok(Command::PutBack(object_node(&k)?)));
+    command!("group", |node : &str, group : &str| Ok(Command::Group(node.to_owned(), group.to_owned())));
+    command!("group", |k : i64, group : &str| Ok(Command::Group(object_node(&k)?, group.to_owned())));
+    command!("screenshot", |fname : &str| Ok(Command::Screenshot(fname.to_owned())));
+    command!("quit", | | Ok(Command::Quit));
+    engine.register_fn("wait", |seconds : Dynamic| -> Result<(), Box<EvalAltResult>> {
+        thread::sleep(Duration::from_millis((number(&seconds)?.max(0.0)*1000.0) as u64));
+        Ok(())
+    });
+    engine
+}
@@ -0,0 +1,229 @@
+use molecule::Molecule;
+
+pub mod query;
+
+// ============================================================
+// Selection
+// ============================================================
+/// A set of atoms, by index into a molecule's atoms, in increasing order.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Selection {
+    #[serde(rename = "atoms")]
+    _atoms : Vec<usize>,
+}
+
+impl Selection {
+    pub fn new() -> Selection {Selection{_atoms : Vec::new()}}
+
+    pub fn all(in_molecule : &Molecule) -> Selection {
+        Selection{_atoms : (0..in_molecule.atoms().len()).collect()}
+    }
+
+    /// Some atoms, in any order.
+    pub fn from_atoms(in_atoms : &Vec<usize>) -> Selection {
+        let mut atoms = in_atoms.to_owned();
+        atoms.sort();
+        atoms.dedup();
+        Selection{_atoms : atoms}
+    }
+
+    /// Every atom of an element, e.g. "O".
+    pub fn by_symbol(in_molecule : &Molecule, in_symbol : &str) -> Selection {
+        Selection {
+            _atoms : in_molecule.atoms().iter().enumerate()
+                .filter(|&(_, atom)| atom.species().symbol() == in_symbol)
+                .map(|(i, _)| i)
+                .collect(),
+        }
+    }
+
+    /// Every atom of a fragment, given which fragment each atom is in (see bonds::fragments()).
+    pub fn by_fragment(in_fragments : &Vec<usize>, in_fragment : &usize) -> Selection {
+        Selection {
+            _atoms : in_fragments.iter().enumerate()
+                .filter(|&(_, fragment)| fragment == in_fragment)
+                .map(|(i, _)| i)
+                .collect(),
+        }
+    }
+
+    /// A group of atoms by name: a named selection (see Molecule::named_selection()),
+    /// "*" for everything, "fragment <n>" (counting from 1) or an element symbol.
+    pub fn by_name(in_molecule : &Molecule, in_fragments : &Vec<usize>, in_name : &str)
+                   -> Result<Selection, String> {
+        let name = in_name.trim();
+        if let Some(selection) = in_molecule.named_selection(name) {
+            return Ok(selection.to_owned());
+        }
+        if name == "*" {
+            return Ok(Selection::all(in_molecule));
+        }
+        let selection = if name.starts_with("fragment") {
+            match name["fragment".len()..].trim().parse::<usize>() {
+                Ok(n) if n > 0 => Selection::by_fragment(in_fragments, &(n-1)),
+                _ => return Err(format!("expected a fragment number from 1, found \"{}\"", name)),
+            }
+        } else {
+            Selection::by_symbol(in_molecule, name)
+        };
+        if selection.is_empty() {
+            Err(format!("no atoms match \"{}\"", name))
+        } else {
+            Ok(selection)
+        }
+    }
+
+    pub fn atoms(&self) -> &Vec<usize> {&self._atoms}
+    pub fn is_empty(&self) -> bool {self._atoms.is_empty()}
+
+    pub fn contains(&self, in_atom : &usize) -> bool {self._atoms.binary_search(in_atom).is_ok()}
+
+    /// The same atoms once an atom has been removed from the molecule: without it,
+    /// and with the atoms after it moved down one.
+    pub fn without_atom(&self, in_atom : &usize) -> Selection {
+        Selection {
+            _atoms : self._atoms.iter()
+                .filter(|&i| i != in_atom)
+                .map(|&i| if i > *in_atom {i-1} else {i})
+                .collect(),
+        }
+    }
+
+    /// The same atoms once an atom has been put in at an index (see
+    /// Molecule::insert_atom()), so the atoms from there on move up one.
+    pub fn with_atom_inserted(&self, in_index : &usize) -> Selection {
+        Selection {
+            _atoms : self._atoms.iter().map(|&i| if i >= *in_index {i+1} else {i}).collect(),
+        }
+    }
+
+    /// The centre of the atoms' bounding box, and the distance from it to the
+    /// furthest atom, e.g. to frame them with the camera.
+    pub fn bounding_sphere(&self, in_molecule : &Molecule) -> ([f32;3], f32) {
+        let atoms = in_molecule.atoms();
+        let mut min = [::std::f32::INFINITY;3];
+        let mut max = [::std::f32::NEG_INFINITY;3];
+        for &i in &self._atoms {
+            for k in 0..3 {
+                min[k] = min[k].min(atoms[i].position()[k]);
+                max[k] = max[k].max(atoms[i].position()[k]);
+            }
+        }
+        let centre = [(min[0]+max[0])/2.0, (min[1]+max[1])/2.0, (min[2]+max[2])/2.0];
+        let radius = self._atoms.iter().map(|&i| {
+            let p = atoms[i].position();
+            ((p[0]-centre[0]).powi(2) + (p[1]-centre[1]).powi(2) + (p[2]-centre[2]).powi(2)).sqrt()
+        }).fold(0.0f32, f32::max);
+        (centre, radius)
+    }
+
+    /// Adds an atom if it isn't selected, or removes it if it is. Returns whether
+    /// it's now selected.
+    pub fn toggle(&mut self, in_atom : &usize) -> bool {
+        match self._atoms.binary_search(in_atom) {
+            Ok(i) => {self._atoms.remove(i); false},
+            Err(i) => {self._atoms.insert(i, in_atom.to_owned()); true},
+        }
+    }
+}
+
+/// The distinct element symbols in a molecule, in the order they first appear.
+pub fn symbols(in_molecule : &Molecule) -> Vec<String> {
+    let mut symbols : Vec<String> = Vec::new();
+    for atom in in_molecule.atoms() {
+        if !symbols.iter().any(|s| s == atom.species().symbol()) {
+            symbols.push(atom.species().symbol().to_owned());
+        }
+    }
+    symbols
+}
+
+// ============================================================
+// Selection Statistics
+// ============================================================
+/// A summary of the selected atoms, for the statistics panel.
+pub struct SelectionStatistics {
+    _count         : usize,
+    /// Each element with how many of its atoms are selected.
+    _composition   : Vec<(String, usize)>,
+    _centroid      : [f32;3],
+    /// The size of the selection's bounding box along x, y and z.
+    _extent        : [f32;3],
+    /// None unless every selected atom has a charge.
+    _total_charge  : Option<f32>,
+    /// The mean over the selected atoms which have a property value.
+    _mean_property : Option<f32>,
+}
+
+impl SelectionStatistics {
+    pub fn new(in_molecule : &Molecule, in_selection : &Selection) -> SelectionStatistics {
+        let mut composition : Vec<(String, usize)> = Vec::new();
+        let mut centroid = [0.0f32;3];
+        let mut min = [f32::INFINITY;3];
+        let mut max = [f32::NEG_INFINITY;3];
+        let mut total_charge = Some(0.0f32);
+        let (mut property_sum, mut property_count) = (0.0f32, 0);
+        for &i in in_selection.atoms() {
+            let atom = &in_molecule.atoms()[i];
+            let symbol = atom.species().symbol();
+            match composition.iter().position(|&(ref s, _)| s == symbol) {
+                Some(j) => composition[j].1 += 1,
+                None => composition.push((symbol.to_owned(), 1)),
+            }
+            for k in 0..3 {
+                centroid[k] += atom.position()[k]/in_selection.atoms().len() as f32;
+                min[k] = min[k].min(atom.position()[k]);
+                max[k] = max[k].max(atom.position()[k]);
+            }
+            total_charge = match (total_charge, *atom.charge()) {
+                (Some(total), Some(charge)) => Some(total+charge),
+                _ => None,
+            };
+            if let Some(property) = *atom.property() {
+                property_sum += property;
+                property_count += 1;
+            }
+        }
+        SelectionStatistics {
+            _count         : in_selection.atoms().len(),
+            _composition   : composition,
+            _centroid      : centroid,
+            _extent        : if in_selection.is_empty() {
+                [0.0;3]
+            } else {
+                [max[0]-min[0], max[1]-min[1], max[2]-min[2]]
+            },
+            _total_charge  : if in_selection.is_empty() {None} else {total_charge},
+            _mean_property : if property_count > 0 {
+                Some(property_sum/property_count as f32)
+            } else {
+                None
+            },
+        }
+    }
+
+    /// The statistics as lines of text.
+    pub fn text(&self) -> String {
+        let composition : Vec<String> = self._composition.iter()
+            .map(|&(ref symbol, n)| format!("{}{}", symbol, n))
+            .collect();
+        let mut text = format!("Selected atoms: {}\n", self._count);
+        text.push_str(&format!("Composition:    {}\n", composition.join(" ")));
+        text.push_str(&format!(
+            "Centroid:       {:.3} {:.3} {:.3}\n",
+            self._centroid[0], self._centroid[1], self._centroid[2]
+        ));
+        text.push_str(&format!(
+            "Extent:         {:.3} x {:.3} x {:.3}\n",
+            self._extent[0], self._extent[1], self._extent[2]
+        ));
+        text.push_str(&match self._total_charge {
+            Some(charge) => format!("Total charge:   {:+.3}\n", charge),
+            None => "Total charge:   unknown\n".to_owned(),
+        });
+        if let Some(property) = self._mean_property {
+            text.push_str(&format!("Mean property:  {:.4}\n", property));
+        }
+        text
+    }
+}
@@ -0,0 +1,424 @@
+use std::collections::HashMap;
+
+use measurement::Distance;
+use molecule::Molecule;
+use selection::Selection;
+
+// ============================================================
+// Selection Queries
+// ============================================================
+// A query picks out atoms by what they are and where they are, e.g.
+//     element C N and not within 4.0 of selection "ligand"
+//
+//     query   := and ("or" and)*
+//     and     := not ("and" not)*
+//     not     := "not" not | "within" <distance> "of" not | primary
+//     primary := "(" query ")" | "all" | "none" | "selected"
+//              | "element" <symbol>... | "index" <range>... | "fragment" <range>...
+//              | "resid" <range>... | "resname" <name>... | "chain" <name>...
+//              | "selection" <name>
+//
+// Ranges are a number or two joined by a dash, e.g. 10-20, and count from 1.
+// Keywords are case-insensitive; symbols and names aren't. Names with spaces
+// go in double quotes.
+
+/// What a query is evaluated against.
+pub struct QueryContext<'a, 'b : 'a> {
+    pub molecule      : &'a Molecule<'b>,
+    /// Which fragment each atom is in (see bonds::fragments()).
+    pub fragments     : &'a Vec<usize>,
+    /// What "selected" refers to.
+    pub current       : &'a Selection,
+    /// Whether "within" measures to the nearest periodic image.
+    pub minimum_image : bool,
+}
+
+/// The atoms a query picks out.
+pub fn evaluate(in_query : &str, in_context : &QueryContext) -> Result<Selection, String> {
+    let mut parser = Parser {_tokens : tokenise(in_query)?, _position : 0};
+    let query = parser.query()?;
+    if let Some(token) = parser.peek() {
+        return Err(format!("unexpected \"{}\"", token.text()));
+    }
+    let mask = query.mask(in_context)?;
+    Ok(Selection {
+        _atoms : mask.iter().enumerate().filter(|&(_, &b)| b).map(|(i, _)| i).collect(),
+    })
+}
+
+/// Runs a command as typed at the selection prompt: a query, or "name = query" to
+/// also save the selection under a name. Returns the selection and its name, if any.
+pub fn run(in_command : &str, in_molecule : &mut Molecule, in_fragments : &Vec<usize>,
+           in_current : &Selection, in_minimum_image : &bool) -> Result<(Selection, Option<String>), String> {
+    let (name, query) = split_assignment(in_command);
+    let selection = evaluate(query, &QueryContext {
+        molecule      : in_molecule,
+        fragments     : in_fragments,
+        current       : in_current,
+        minimum_image : *in_minimum_image,
+    })?;
+    if let Some(name) = name {
+        in_molecule.set_named_selection(name, &selection);
+    }
+    Ok((selection, name.map(|name| name.to_owned())))
+}
+
+/// Splits "name = query" into the name and the query: a selection to save under a
+/// name. Plain queries come back without a name.
+fn split_assignment(in_command : &str) -> (Option<&str>, &str) {
+    if let Some(i) = in_command.find('=') {
+        let name = in_command[..i].trim();
+        if !name.is_empty() && !name.contains(|c : char| c.is_whitespace() || c == '"' || c == '(') {
+            return (Some(name), in_command[i+1..].trim());
+        }
+    }
+    (None, in_command.trim())
+}
+
+// ============================================================
+// Tokens
+// ============================================================
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Open,
+    Close,
+}
+
+impl Token {
+    fn text(&self) -> String {
+        match *self {
+            Token::Word(ref word) => word.to_owned(),
+            Token::Quoted(ref name) => format!("\"{}\"", name),
+            Token::Open => "(".to_owned(),
+            Token::Close => ")".to_owned(),
+        }
+    }
+
+    /// Whether this is a particular keyword.
+    fn is(&self, in_keyword : &str) -> bool {
+        match *self {
+            Token::Word(ref word) => word.to_lowercase() == in_keyword,
+            _ => false,
+        }
+    }
+}
+
+fn tokenise(in_query : &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = in_query.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' {
+            chars.next();
+            tokens.push(Token::Open);
+        } else if c == ')' {
+            chars.next();
+            tokens.push(Token::Close);
+        } else if c == '"' {
+            chars.next();
+            let mut name = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => name.push(c),
+                    None => return Err("missing closing \"".to_owned()),
+                }
+            }
+            tokens.push(Token::Quoted(name));
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '(' || c == ')' || c == '"' {break}
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        }
+    }
+    Ok(tokens)
+}
+
+// ============================================================
+// Parser
+// ============================================================
+/// A parsed query.
+enum Query {
+    All,
+    Nothing,
+    Selected,
+    Element(Vec<String>),
+    Index(Vec<(i64, i64)>),
+    Fragment(Vec<(i64, i64)>),
+    ResidueNumber(Vec<(i64, i64)>),
+    ResidueName(Vec<String>),
+    Chain(Vec<String>),
+    Named(String),
+    Within(f32, Box<Query>),
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+}
+
+const KEYWORDS : [&'static str; 4] = ["and", "or", "not", "of"];
+
+struct Parser {
+    _tokens   : Vec<Token>,
+    _position : usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {self._tokens.get(self._position)}
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self._tokens.get(self._position).cloned();
+        self._position += 1;
+        token
+    }
+
+    fn query(&mut self) -> Result<Query, String> {
+        let mut query = self.and()?;
+        while self.peek().map_or(false, |t| t.is("or")) {
+            self.next();
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, String> {
+        let mut query = self.not()?;
+        while self.peek().map_or(false, |t| t.is("and")) {
+            self.next();
+            query = Query::And(Box::new(query), Box::new(self.not()?));
+        }
+        Ok(query)
+    }
+
+    fn not(&mut self) -> Result<Query, String> {
+        if self.peek().map_or(false, |t| t.is("not")) {
+            self.next();
+            return Ok(Query::Not(Box::new(self.not()?)));
+        }
+        if self.peek().map_or(false, |t| t.is("within")) {
+            self.next();
+            let distance = match self.next() {
+                Some(Token::Word(ref word)) => match word.parse::<f32>() {
+                    Ok(distance) => distance,
+                    Err(_) => return Err(format!("expected a distance after within, found \"{}\"", word)),
+                },
+                _ => return Err("expected a distance after within".to_owned()),
+            };
+            if !self.next().map_or(false, |t| t.is("of")) {
+                return Err(format!("expected \"of\" after within {}", distance));
+            }
+            return Ok(Query::Within(distance, Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Query, String> {
+        let token = match self.next() {
+            Some(token) => token,
+            None => return Err("the query ends too soon".to_owned()),
+        };
+        if token == Token::Open {
+            let query = self.query()?;
+            return match self.next() {
+                Some(Token::Close) => Ok(query),
+                _ => Err("missing )".to_owned()),
+            };
+        }
+        let keyword = match token {
+            Token::Word(ref word) => word.to_lowercase(),
+            _ => return Err(format!("unexpected \"{}\"", token.text())),
+        };
+        match keyword.as_str() {
+            "all"       => Ok(Query::All),
+            "none"      => Ok(Query::Nothing),
+            "selected"  => Ok(Query::Selected),
+            "element"   => Ok(Query::Element(self.names(&keyword)?)),
+            "resname"   => Ok(Query::ResidueName(self.names(&keyword)?)),
+            "chain"     => Ok(Query::Chain(self.names(&keyword)?)),
+            "index"     => Ok(Query::Index(self.ranges(&keyword)?)),
+            "fragment"  => Ok(Query::Fragment(self.ranges(&keyword)?)),
+            "resid"     => Ok(Query::ResidueNumber(self.ranges(&keyword)?)),
+            "selection" => match self.next() {
+                Some(Token::Word(name)) | Some(Token::Quoted(name)) => Ok(Query::Named(name)),
+                _ => Err("expected a name after selection".to_owned()),
+            },
+            _ => Err(format!("unknown keyword \"{}\"", keyword)),
+        }
+    }
+
+    /// The words (or quoted names) following a keyword, up to the next keyword or bracket.
+    fn names(&mut self, in_keyword : &str) -> Result<Vec<String>, String> {
+        let mut names = Vec::new();
+        loop {
+            match self.peek() {
+                Some(&Token::Word(ref word)) if !KEYWORDS.contains(&word.to_lowercase().as_str()) => {
+                    names.push(word.to_owned())
+                },
+                Some(&Token::Quoted(ref name)) => names.push(name.to_owned()),
+                _ => break,
+            }
+            self._position += 1;
+        }
+        if names.is_empty() {
+            Err(format!("expected something after {}", in_keyword))
+        } else {
+            Ok(names)
+        }
+    }
+
+    fn ranges(&mut self, in_keyword : &str) -> Result<Vec<(i64, i64)>, String> {
+        self.names(in_keyword)?.iter().map(|name| parse_range(name)).collect()
+    }
+}
+
+/// e.g. "10" or "10-20". The second number may be negative, as in "-5--2".
+fn parse_range(in_text : &str) -> Result<(i64, i64), String> {
+    let error = || format!("expected a number or range, found \"{}\"", in_text);
+    // skip a leading minus sign when looking for the dash
+    let dash = in_text.char_indices().skip(1).find(|&(_, c)| c == '-').map(|(i, _)| i);
+    match dash {
+        Some(i) => {
+            let from = in_text[..i].parse::<i64>().map_err(|_| error())?;
+            let to = in_text[i+1..].parse::<i64>().map_err(|_| error())?;
+            Ok((from.min(to), from.max(to)))
+        },
+        None => {
+            let n = in_text.parse::<i64>().map_err(|_| error())?;
+            Ok((n, n))
+        },
+    }
+}
+
+fn in_ranges(in_ranges : &Vec<(i64, i64)>, in_value : i64) -> bool {
+    in_ranges.iter().any(|&(from, to)| from <= in_value && in_value <= to)
+}
+
+// ============================================================
+// Evaluation
+// ============================================================
+impl Query {
+    /// Whether each atom is picked out.
+    fn mask(&self, in_context : &QueryContext) -> Result<Vec<bool>, String> {
+        let atoms = in_context.molecule.atoms();
+        let n = atoms.len();
+        Ok(match *self {
+            Query::All => vec![true; n],
+            Query::Nothing => vec![false; n],
+            Query::Selected => selection_mask(in_context.current, n),
+            Query::Element(ref symbols) => atoms.iter()
+                .map(|atom| symbols.iter().any(|s| s == atom.species().symbol()))
+                .collect(),
+            Query::Index(ref ranges) => (0..n).map(|i| in_ranges(ranges, i as i64 + 1)).collect(),
+            Query::Fragment(ref ranges) => in_context.fragments.iter()
+                .map(|&fragment| in_ranges(ranges, fragment as i64 + 1))
+                .collect(),
+            Query::ResidueNumber(ref ranges) => atoms.iter()
+                .map(|atom| atom.residue().as_ref().map_or(false, |r| in_ranges(ranges, *r.number() as i64)))
+                .collect(),
+            Query::ResidueName(ref names) => atoms.iter()
+                .map(|atom| atom.residue().as_ref().map_or(false, |r| names.iter().any(|name| name == r.name())))
+                .collect(),
+            Query::Chain(ref names) => atoms.iter()
+                .map(|atom| atom.residue().as_ref().map_or(false, |r| names.iter().any(|name| name == r.chain())))
+                .collect(),
+            Query::Named(ref name) => match in_context.molecule.named_selection(name) {
+                Some(selection) => selection_mask(selection, n),
+                None => return Err(format!("there's no selection called \"{}\"", name)),
+            },
+            Query::Within(distance, ref query) => {
+                let targets : Vec<usize> = query.mask(in_context)?.iter().enumerate()
+                    .filter(|&(_, &b)| b).map(|(i, _)| i).collect();
+                within(in_context, &targets, distance)
+            },
+            Query::Not(ref query) => query.mask(in_context)?.iter().map(|&b| !b).collect(),
+            Query::And(ref a, ref b) => {
+                let b = b.mask(in_context)?;
+                a.mask(in_context)?.iter().zip(b.iter()).map(|(&a, &b)| a && b).collect()
+            },
+            Query::Or(ref a, ref b) => {
+                let b = b.mask(in_context)?;
+                a.mask(in_context)?.iter().zip(b.iter()).map(|(&a, &b)| a || b).collect()
+            },
+        })
+    }
+}
+
+/// Which atoms are the targets or within `in_distance` of one. The targets are
+/// sorted into cubes the size of the distance, so each atom is only compared with
+/// those in the cubes around it. Measuring to the nearest periodic image, every
+/// atom is first wrapped into the cell and the cubes around each of its neighbouring
+/// images are searched too.
+fn within(in_context : &QueryContext, in_targets : &Vec<usize>, in_distance : f32) -> Vec<bool> {
+    let molecule = in_context.molecule;
+    let cell = match (*molecule.cell(), in_context.minimum_image) {
+        (Some(cell), true) => Some(cell),
+        _ => None,
+    };
+    let positions : Vec<[f32;3]> = molecule.atoms().iter()
+        .map(|atom| match cell {
+            Some(cell) => cell.wrap(atom.position()),
+            None => *atom.position(),
+        })
+        .collect();
+    let mut images = vec![[0.0f32;3]];
+    if let Some(cell) = cell {
+        for i in -1..2 {
+            for j in -1..2 {
+                for k in -1..2 {
+                    if [i, j, k] != [0, 0, 0] {
+                        images.push(cell.to_cartesian(&[i as f32, j as f32, k as f32]));
+                    }
+                }
+            }
+        }
+    }
+
+    let cube_size = in_distance.max(1e-3);
+    let cube = |p : &[f32;3]| [
+        (p[0]/cube_size).floor() as i64, (p[1]/cube_size).floor() as i64, (p[2]/cube_size).floor() as i64,
+    ];
+    let mut cubes : HashMap<[i64;3], Vec<usize>> = HashMap::new();
+    for &j in in_targets {
+        cubes.entry(cube(&positions[j])).or_insert_with(Vec::new).push(j);
+    }
+
+    let mut mask = vec![false; positions.len()];
+    for &j in in_targets {mask[j] = true}
+    for (i, position) in positions.iter().enumerate() {
+        if mask[i] {continue}
+        'images: for shift in images.iter() {
+            let c = cube(&[position[0]+shift[0], position[1]+shift[1], position[2]+shift[2]]);
+            for dx in -1..2 {
+                for dy in -1..2 {
+                    for dz in -1..2 {
+                        let targets = match cubes.get(&[c[0]+dx, c[1]+dy, c[2]+dz]) {
+                            Some(targets) => targets,
+                            None => continue,
+                        };
+                        if targets.iter().any(|j| {
+                            *Distance::between(molecule, &i, j, &in_context.minimum_image).value() <= in_distance
+                        }) {
+                            mask[i] = true;
+                            break 'images;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    mask
+}
+
+fn selection_mask(in_selection : &Selection, in_atom_count : usize) -> Vec<bool> {
+    let mut mask = vec![false; in_atom_count];
+    for &i in in_selection.atoms() {
+        if i < in_atom_count {mask[i] = true}
+    }
+    mask
+}
@@ -0,0 +1,185 @@
+use glium::Surface;
+
+use atom::Residue;
+use binding_site::WATERS;
+use molecule::Molecule;
+use selection::Selection;
+use text::{self, TextSystem};
+
+// ============================================================
+// One-Letter Codes
+// ============================================================
+/// The one-letter codes of the standard amino acids (and selenomethionine, which
+/// stands in for methionine in many crystal structures) and of the nucleotides.
+const ONE_LETTER_CODES : [(&'static str, char); 31] = [
+    ("ALA", 'A'), ("ARG", 'R'), ("ASN", 'N'), ("ASP", 'D'), ("CYS", 'C'),
+    ("GLN", 'Q'), ("GLU", 'E'), ("GLY", 'G'), ("HIS", 'H'), ("ILE", 'I'),
+    ("LEU", 'L'), ("LYS", 'K'), ("MET", 'M'), ("PHE", 'F'), ("PRO", 'P'),
+    ("SER", 'S'), ("THR", 'T'), ("TRP", 'W'), ("TYR", 'Y'), ("VAL", 'V'),
+    ("SEC", 'U'), ("PYL", 'O'), ("MSE", 'M'),
+    ("A"  , 'A'), ("C"  , 'C'), ("G"  , 'G'), ("U"  , 'U'),
+    ("DA" , 'A'), ("DC" , 'C'), ("DG" , 'G'), ("DT" , 'T'),
+];
+
+/// A residue's one-letter code, or 'X' for anything else (e.g. a ligand).
+fn one_letter_code(in_name : &str) -> char {
+    ONE_LETTER_CODES.iter().find(|&&(name, _)| name == in_name).map_or('X', |&(_, code)| code)
+}
+
+// ============================================================
+// Sequence
+// ============================================================
+/// A residue in the sequence, with its atoms.
+pub struct SequenceResidue {
+    _residue : Residue,
+    _code    : char,
+    _atoms   : Selection,
+}
+
+impl SequenceResidue {
+    pub fn residue(&self) -> &Residue {&self._residue}
+    pub fn atoms(&self) -> &Selection {&self._atoms}
+}
+
+struct Chain {
+    _name     : String,
+    _residues : Vec<SequenceResidue>,
+}
+
+/// How many rows of the sequence are shown at once; the wheel scrolls through the rest.
+const MAX_ROWS : usize = 5;
+
+/// The sequence of each chain as a strip of one-letter codes along the top of the
+/// window, wrapped to its width, with the selected residues highlighted. Residues
+/// can be clicked on (see residue_at()) to find them in the molecule.
+pub struct Sequence {
+    _chains : Vec<Chain>,
+    /// The first row shown, as scrolled to with the wheel.
+    _scroll : f32,
+    // where the strip and each residue were last drawn, as (left, top, right, bottom)
+    _area   : [f32;4],
+    _boxes  : Vec<([f32;4], usize, usize)>,
+}
+
+impl Sequence {
+    /// The residues of each chain, in the order they're first seen. Waters, and
+    /// atoms without a residue, are left out.
+    pub fn new(in_molecule : &Molecule) -> Sequence {
+        let mut chains : Vec<Chain> = Vec::new();
+        let mut atoms : Vec<Vec<Vec<usize>>> = Vec::new();
+        for (i, atom) in in_molecule.atoms().iter().enumerate() {
+            let residue = match *atom.residue() {
+                Some(ref residue) if !WATERS.contains(&residue.name()) => residue,
+                _ => continue,
+            };
+            let c = match chains.iter().position(|chain| chain._name == residue.chain()) {
+                Some(c) => c,
+                None => {
+                    chains.push(Chain {_name : residue.chain().to_owned(), _residues : Vec::new()});
+                    atoms.push(Vec::new());
+                    chains.len()-1
+                },
+            };
+            let is_new = chains[c]._residues.last().map_or(true, |last| last._residue != *residue);
+            if is_new {
+                chains[c]._residues.push(SequenceResidue {
+                    _residue : residue.to_owned(),
+                    _code    : one_letter_code(residue.name()),
+                    _atoms   : Selection::new(),
+                });
+                atoms[c].push(Vec::new());
+            }
+            atoms[c].last_mut().unwrap().push(i);
+        }
+        for (chain, atoms) in chains.iter_mut().zip(atoms.iter()) {
+            for (residue, atoms) in chain._residues.iter_mut().zip(atoms.iter()) {
+                residue._atoms = Selection::from_atoms(atoms);
+            }
+        }
+        Sequence {
+            _chains : chains,
+            _scroll : 0.0,
+            _area   : [0.0;4],
+            _boxes  : Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {self._chains.is_empty()}
+
+    /// Scrolls the rows shown by a number of (possibly fractional) rows, down the
+    /// sequence for positive.
+    pub fn scroll(&mut self, in_rows : &f32) {self._scroll = (self._scroll + in_rows).max(0.0)}
+
+    /// Whether a cursor position is over the strip where it was last drawn.
+    pub fn contains(&self, in_cursor : &[i32;2]) -> bool {
+        let (x, y) = (in_cursor[0] as f32, in_cursor[1] as f32);
+        x >= self._area[0] && x < self._area[2] && y >= self._area[1] && y < self._area[3]
+    }
+
+    /// The residue under a cursor position, where the strip was last drawn.
+    pub fn residue_at(&self, in_cursor : &[i32;2]) -> Option<&SequenceResidue> {
+        let (x, y) = (in_cursor[0] as f32, in_cursor[1] as f32);
+        self._boxes.iter()
+            .find(|&&(ref b, _, _)| x >= b[0] && x < b[2] && y >= b[1] && y < b[3])
+            .map(|&(_, c, r)| &self._chains[c]._residues[r])
+    }
+
+    /// Draws the strip across the window, `in_top` pixels from the top, with the
+    /// residues that have any atom selected in `in_tint`. Returns its height in pixels.
+    pub fn draw<T>(&mut self, in_text_system : &TextSystem, in_target : &mut T, in_selection : &Selection,
+                   in_tint : &[f32;3], in_top : &f32) -> f32 where T : Surface {
+        let scale = 2.0;
+        let glyph = text::text_size("X", scale)[0];
+        let line_height = text::text_size("X\nX", scale)[1] - text::text_size("X", scale)[1];
+        let (w, _) = in_target.get_dimensions();
+        let w = w as f32;
+
+        // each row starts with the chain and the number of its first residue
+        let prefix = 8;
+        let per_row = (((w - 20.0)/glyph) as usize).saturating_sub(prefix).max(10);
+        let mut rows = Vec::new();
+        for (c, chain) in self._chains.iter().enumerate() {
+            for start in (0..chain._residues.len()).filter(|r| r % per_row == 0) {
+                rows.push((c, start));
+            }
+        }
+        let first = (self._scroll as usize).min(rows.len().saturating_sub(MAX_ROWS));
+        self._scroll = self._scroll.min(first as f32 + 0.99);
+        let shown = &rows[first..(first+MAX_ROWS).min(rows.len())];
+
+        let height = shown.len() as f32*line_height + 16.0;
+        self._area = [0.0, *in_top, w, in_top + height];
+        self._boxes.clear();
+        text::draw_panel(in_text_system, in_target, [0.0, *in_top], [w, height], [0.0, 0.0, 0.0, 0.6]);
+        let tint = [in_tint[0], in_tint[1], in_tint[2], 1.0];
+        for (n, &(c, start)) in shown.iter().enumerate() {
+            let chain = &self._chains[c];
+            let residues = &chain._residues[start..(start+per_row).min(chain._residues.len())];
+            let top = in_top + 8.0 + n as f32*line_height;
+            let left = 10.0 + prefix as f32*glyph;
+            let number = format!("{:<2}{:>5}", chain._name, residues[0]._residue.number());
+            text::draw_text(in_text_system, in_target, &number, [10.0, top], scale, [0.7, 0.7, 0.7, 1.0]);
+            // the selected residues' letters are drawn dark on the tint, the rest light
+            let mut plain = String::new();
+            let mut selected = String::new();
+            let is_selected : Vec<bool> = residues.iter()
+                .map(|residue| residue._atoms.atoms().iter().any(|i| in_selection.contains(i)))
+                .collect();
+            for (k, residue) in residues.iter().enumerate() {
+                let x = left + k as f32*glyph;
+                // one tint behind each run of selected residues
+                if is_selected[k] && (k == 0 || !is_selected[k-1]) {
+                    let run = is_selected[k..].iter().take_while(|&&b| b).count();
+                    text::draw_panel(in_text_system, in_target, [x, top-2.0],
+                                     [run as f32*glyph, glyph+4.0], tint);
+                }
+                plain.push(if is_selected[k] {' '} else {residue._code});
+                selected.push(if is_selected[k] {residue._code} else {' '});
+                self._boxes.push(([x, top-2.0, x+glyph, top+line_height-2.0], c, start+k));
+            }
+            text::draw_text(in_text_system, in_target, &plain, [left, top], scale, [1.0, 1.0, 1.0, 1.0]);
+            text::draw_text(in_text_system, in_target, &selected, [left, top], scale, [0.1, 0.1, 0.1, 1.0]);
+        }
+        height
+    }
+}
@@ -0,0 +1,141 @@
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::io::BufWriter;
+use serde_json;
+
+use bonds::BondDisplay;
+use camera::Camera;
+use colour::ColourScheme;
+use label::LabelContent;
+use measurement::Measurement;
+use molecule::Molecule;
+use objects::{Object, Objects};
+use scene_graph::SceneGraph;
+use selection::Selection;
+use surface::SurfaceDisplay;
+use tube::TubeDisplay;
+
+/// Where ctrl+shift+S saves the session.
+pub const SHORTCUT_FNAME : &'static str = "oxide.session";
+
+// ============================================================
+// Session
+// ============================================================
+/// Where the user had got to, saved as JSON (ctrl+shift+S) and given back on the
+/// command line (oxide.session) to carry on tomorrow: the files loaded, the camera,
+/// what's shown and how, and the selections and measurements. The structure is
+/// read back in from its files, so edits since loading aren't kept (though objects
+/// split off are split again, and objects and groups moved as a whole are moved
+/// again), and atoms past the end of the structure as it's read back are left out.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Session {
+    /// The structure files, the first one's trajectory and all, as full paths.
+    #[serde(rename = "files")]
+    _files            : Vec<String>,
+    #[serde(rename = "camera")]
+    _camera           : Camera,
+    #[serde(rename = "frame")]
+    _frame            : Option<usize>,
+    #[serde(rename = "colour_scheme")]
+    _colour_scheme    : ColourScheme,
+    #[serde(rename = "bond_display")]
+    _bond_display     : BondDisplay,
+    #[serde(rename = "tube_display")]
+    _tube_display     : TubeDisplay,
+    #[serde(rename = "label_content")]
+    _label_content    : LabelContent,
+    #[serde(rename = "surface_display")]
+    _surface_display  : SurfaceDisplay,
+    #[serde(rename = "hidden_chains")]
+    _hidden_chains    : Vec<String>,
+    #[serde(rename = "selection")]
+    _selection        : Selection,
+    #[serde(rename = "named_selections")]
+    _named_selections : Vec<(String, Selection)>,
+    #[serde(rename = "measurements")]
+    _measurements     : Vec<Measurement>,
+    /// Each file's object, shown or hidden.
+    #[serde(rename = "objects", default)]
+    _objects          : Vec<Object>,
+    /// Each atom's object, for those split off from their files' (ctrl+G).
+    #[serde(rename = "atom_objects", default)]
+    _atom_objects     : Vec<usize>,
+    /// Where the objects, the cell and the groups of them have been moved.
+    #[serde(rename = "scene_graph", default)]
+    _scene_graph      : Option<SceneGraph>,
+}
+
+impl Session {
+    /// The session as the viewer has it: the files loaded, the view, and the
+    /// molecule's named selections and its atoms' objects.
+    pub fn new(in_files : &Vec<String>, in_camera : &Camera, in_frame : &Option<usize>,
+               in_colour_scheme : &ColourScheme, in_bond_display : &BondDisplay, in_tube_display : &TubeDisplay,
+               in_label_content : &LabelContent, in_surface_display : &SurfaceDisplay,
+               in_hidden_chains : &Vec<String>, in_selection : &Selection, in_measurements : &Vec<Measurement>,
+               in_molecule : &Molecule, in_objects : &Objects, in_scene_graph : &SceneGraph) -> Session {
+        Session {
+            _files            : in_files.to_owned(),
+            _camera           : in_camera.clone(),
+            _frame            : *in_frame,
+            _colour_scheme    : *in_colour_scheme,
+            _bond_display     : *in_bond_display,
+            _tube_display     : *in_tube_display,
+            _label_content    : *in_label_content,
+            _surface_display  : *in_surface_display,
+            _hidden_chains    : in_hidden_chains.to_owned(),
+            _selection        : in_selection.to_owned(),
+            _named_selections : in_molecule.named_selections().to_owned(),
+            _measurements     : in_measurements.to_owned(),
+            _objects          : in_objects.objects().to_owned(),
+            _atom_objects     : in_molecule.atoms().iter().map(|atom| *atom.object()).collect(),
+            _scene_graph      : Some(in_scene_graph.to_owned()),
+        }
+    }
+
+    pub fn files(&self) -> &Vec<String> {&self._files}
+    pub fn camera(&self) -> &Camera {&self._camera}
+    pub fn frame(&self) -> &Option<usize> {&self._frame}
+    pub fn colour_scheme(&self) -> &ColourScheme {&self._colour_scheme}
+    pub fn bond_display(&self) -> &BondDisplay {&self._bond_display}
+    pub fn tube_display(&self) -> &TubeDisplay {&self._tube_display}
+    pub fn label_content(&self) -> &LabelContent {&self._label_content}
+    pub fn surface_display(&self) -> &SurfaceDisplay {&self._surface_display}
+    pub fn hidden_chains(&self) -> &Vec<String> {&self._hidden_chains}
+    pub fn objects(&self) -> &Vec<Object> {&self._objects}
+    pub fn atom_objects(&self) -> &Vec<usize> {&self._atom_objects}
+    pub fn scene_graph(&self) -> &Option<SceneGraph> {&self._scene_graph}
+
+    pub fn load(in_fname : &str) -> Result<Session, String> {
+        let file = File::open(in_fname).map_err(|e| format!("couldn't open {}: {}", in_fname, e))?;
+        serde_json::from_reader(file).map_err(|e| format!("couldn't read {}: {}", in_fname, e))
+    }
+
+    /// Writes the session, with its files made into full paths so it can be loaded
+    /// from anywhere.
+    pub fn save(&self, in_fname : &str) -> Result<(), String> {
+        let mut session = self.clone();
+        session._files = self._files.iter().map(|file| {
+            fs::canonicalize(file).map(|path| path.to_string_lossy().into_owned()).unwrap_or(file.to_owned())
+        }).collect();
+        let file = File::create(in_fname).map_err(|e| format!("couldn't create {}: {}", in_fname, e))?;
+        let mut file = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut file, &session).map_err(|e| e.to_string())
+            .and_then(|_| file.flush().map_err(|e| e.to_string()))
+            .map_err(|e| format!("couldn't write to {}: {}", in_fname, e))
+    }
+
+    /// The selection, named selections and measurements, without the atoms a
+    /// molecule of this many atoms doesn't have.
+    pub fn restore_selections(&self, in_molecule : &mut Molecule) -> (Selection, Vec<Measurement>) {
+        let n = in_molecule.atoms().len();
+        let kept = |in_selection : &Selection| -> Selection {
+            Selection::from_atoms(&in_selection.atoms().iter().cloned().filter(|&i| i < n).collect())
+        };
+        for &(ref name, ref selection) in &self._named_selections {
+            in_molecule.set_named_selection(name, &kept(selection));
+        }
+        let measurements = self._measurements.iter().cloned()
+            .filter(|measurement| measurement.atoms().iter().all(|&i| i < n)).collect();
+        (kept(&self._selection), measurements)
+    }
+}
@@ -0,0 +1,40 @@
+// ============================================================
+// Shading
+// ============================================================
+/// How the meshes and spheres are shaded: lit as usual, or one of the debug views
+/// for working on mesh generators and seeing what's wrong with the geometry in a
+/// user's screenshot (ctrl+F2 and ctrl+F3).
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Shading {
+    /// Lit from the light, with the normals smoothed between the vertices.
+    Lit,
+    /// Each fragment coloured by its normal in view space, x, y and z as red,
+    /// green and blue (from -1 as black to 1 as full).
+    Normals,
+    /// Each triangle lit by its own face, so the facets show. Spheres, which are
+    /// drawn without any, come out as flat discs.
+    Flat,
+    /// In one dark colour, for the wireframe drawn over the meshes (ctrl+F1).
+    Wireframe,
+}
+
+impl Shading {
+    /// The integer which the shaders switch on.
+    pub fn uniform(&self) -> i32 {
+        match *self {
+            Shading::Lit       => 0,
+            Shading::Normals   => 1,
+            Shading::Flat      => 2,
+            Shading::Wireframe => 3,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Shading::Lit       => "lit",
+            Shading::Normals   => "normals",
+            Shading::Flat      => "flat",
+            Shading::Wireframe => "wireframe",
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::f32;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+/// How long each structure is shown for, in seconds, by default.
+pub const DEFAULT_SECONDS : f32 = 15.0;
+
+/// How fast the structure shown turns, in degrees a second.
+const TURNTABLE_DEGREES_PER_SECOND : f32 = 20.0;
+
+/// Whether a file is one the viewer can read, by its extension.
+pub fn is_structure(in_fname : &str) -> bool {
+    let lowercase = in_fname.to_lowercase();
+    [".xyz", ".pdb", ".ent", ".cif", ".cell"].iter().any(|extension| lowercase.ends_with(extension))
+}
+
+// ============================================================
+// Slideshow
+// ============================================================
+/// Shows each structure file in a directory in turn, in order of name, for a
+/// lab display or an open day: every so often the next one is loaded, and the one
+/// shown turns slowly all the while. The directory is looked in again for each
+/// change, so files copied in (or taken out) join (or leave) the show.
+pub struct Slideshow {
+    _directory : String,
+    _seconds   : f32,
+    /// The file showing, or None before the first.
+    _current   : Option<String>,
+    _start     : Instant,
+}
+
+impl Slideshow {
+    pub fn new(in_directory : &str, in_seconds : &f32) -> Slideshow {
+        Slideshow {
+            _directory : in_directory.to_owned(),
+            _seconds   : *in_seconds,
+            _current   : None,
+            _start     : Instant::now(),
+        }
+    }
+
+    pub fn directory(&self) -> &str {&self._directory}
+
+    /// The structure files in the directory, sorted by name.
+    pub fn files(&self) -> Result<Vec<String>, String> {
+        let entries = fs::read_dir(&self._directory)
+            .map_err(|e| format!("couldn't read the directory {}: {}", self._directory, e))?;
+        let mut files : Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().map(|kind| kind.is_file()).unwrap_or(false))
+            .filter_map(|entry| entry.path().to_str().map(|path| path.to_owned()))
+            .filter(|path| is_structure(path))
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    /// Moves on to the file after the one showing (going back to the start after
+    /// the last), restarting the clock. None if the directory has none.
+    pub fn advance(&mut self) -> Result<Option<String>, String> {
+        let files = self.files()?;
+        self._start = Instant::now();
+        let next = match self._current {
+            Some(ref current) => files.iter().find(|fname| *fname > current).or_else(|| files.first()),
+            None => files.first(),
+        }.cloned();
+        self._current = next.clone();
+        Ok(next)
+    }
+
+    /// Whether the file showing has been up for long enough.
+    pub fn is_due(&self) -> bool {
+        let elapsed = self._start.elapsed();
+        elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32*1e-9 >= self._seconds
+    }
+
+    /// How far to turn the structure (in radians) in a frame's step (see
+    /// frame_clock.rs).
+    pub fn turn(&self, in_seconds : &f32) -> f32 {
+        in_seconds*TURNTABLE_DEGREES_PER_SECOND*f32::consts::PI/180.0
+    }
+}
+
+/// The name the file goes by in messages: its path without the directory.
+pub fn short_name(in_fname : &str) -> String {
+    Path::new(in_fname).file_name().and_then(|name| name.to_str()).unwrap_or(in_fname).to_owned()
+}
@@ -1,64 +1,127 @@
-use model;
-use model::Model;
+use colour;
 
 // ============================================================
 // Species
 // ============================================================
-pub struct Species<'a> {
-    _mesh   : &'a Model<'a>,
+/// An element as its atoms are drawn. Every species is drawn with the same sphere
+/// (see model.rs), so species hold nothing of the GPU's, and the atoms of one
+/// outlive a lost GL context (see recovery.rs).
+pub struct Species {
+    /// The element symbol, e.g. "Ni".
+    _symbol : String,
     _size   : f32,
     _colour : [f32;3],
 }
 
-impl<'a> Species<'a> {
+impl Species {
     pub fn new (
-        in_mesh   : &'a Model,
+        in_symbol : &str,
         in_size   : &f32,
         in_colour : &[f32;3],
-    ) -> Species<'a> {
+    ) -> Species {
         Species {
-            _mesh   : in_mesh,
+            _symbol : in_symbol.to_owned(),
             _size   : in_size.to_owned(),
             _colour : in_colour.to_owned()
         }
     }
 
-    pub fn mesh(&self) -> &Model {&self._mesh}
+    pub fn symbol(&self) -> &str {&self._symbol}
     pub fn size(&self) -> &f32  {&self._size}
     pub fn colour(&self) -> &[f32;3] {&self._colour}
+
+    pub fn set_size(&mut self, in_size : &f32) {self._size = in_size.to_owned()}
+    pub fn set_colour(&mut self, in_colour : &[f32;3]) {self._colour = in_colour.to_owned()}
 }
 
-pub struct DefaultSpecies<'a> {
-    _carbon  : Species<'a>,
-    _nickel  : Species<'a>,
-    _sulphur : Species<'a>,
-    _oxygen  : Species<'a>,
+/// The element symbols, by atomic number (from 1).
+pub const ELEMENTS : [&'static str; 118] = [
+    "H",                                                                                  "He",
+    "Li", "Be",                                                  "B",  "C",  "N",  "O",  "F",  "Ne",
+    "Na", "Mg",                                                  "Al", "Si", "P",  "S",  "Cl", "Ar",
+    "K",  "Ca", "Sc", "Ti", "V",  "Cr", "Mn", "Fe", "Co", "Ni", "Cu", "Zn", "Ga", "Ge", "As", "Se", "Br", "Kr",
+    "Rb", "Sr", "Y",  "Zr", "Nb", "Mo", "Tc", "Ru", "Rh", "Pd", "Ag", "Cd", "In", "Sn", "Sb", "Te", "I",  "Xe",
+    "Cs", "Ba",
+          "La", "Ce", "Pr", "Nd", "Pm", "Sm", "Eu", "Gd", "Tb", "Dy", "Ho", "Er", "Tm", "Yb", "Lu",
+                "Hf", "Ta", "W",  "Re", "Os", "Ir", "Pt", "Au", "Hg", "Tl", "Pb", "Bi", "Po", "At", "Rn",
+    "Fr", "Ra",
+          "Ac", "Th", "Pa", "U",  "Np", "Pu", "Am", "Cm", "Bk", "Cf", "Es", "Fm", "Md", "No", "Lr",
+                "Rf", "Db", "Sg", "Bh", "Hs", "Mt", "Ds", "Rg", "Cn", "Nh", "Fl", "Mc", "Lv", "Ts", "Og",
+];
+
+/// A species for every element, so each atom knows what it is (for its colour,
+/// radii, bonds and the element query). Those common in molecules have their own
+/// colours and sizes; the rest are all drawn alike.
+pub struct DefaultSpecies {
+    _hydrogen : Species,
+    _carbon  : Species,
+    _nitrogen : Species,
+    _nickel  : Species,
+    _sulphur : Species,
+    _oxygen  : Species,
+    /// The other elements, in the order of ELEMENTS.
+    _others  : Vec<Species>,
 }
 
-impl<'a> DefaultSpecies<'a> {
-    pub fn new (in_default_models : &'a model::DefaultModels) -> DefaultSpecies<'a> {
-        // ==============================
-        // Dark2
-        // ==============================
-        // let turquoise = [ 27.0/255.0,158.0/255.0,119.0/255.0];
-        let orange    = [217.0/255.0, 95.0/255.0,  2.0/255.0];
-        let blue      = [117.0/255.0,112.0/255.0,179.0/255.0];
-        // let pink      = [231.0/255.0, 41.0/255.0,138.0/255.0];
-        let green     = [102.0/255.0,166.0/255.0, 30.0/255.0];
-        let yellow    = [230.0/255.0,171.0/255.0,  2.0/255.0];
-        // let brown     = [166.0/255.0,118.0/255.0, 29.0/255.0];
-        // let grey      = [102.0/255.0,102.0/255.0,102.0/255.0];
+impl DefaultSpecies {
+    pub fn new () -> DefaultSpecies {
+        let orange = colour::DARK2[1];
+        let blue   = colour::DARK2[2];
+        let pink   = colour::DARK2[3];
+        let green  = colour::DARK2[4];
+        let yellow = colour::DARK2[5];
+        let brown  = colour::DARK2[6];
+        let grey   = colour::DARK2[7];
 
         DefaultSpecies {
-            _carbon  : Species::new(in_default_models.sphere(), &0.1, &blue),
-            _nickel  : Species::new(in_default_models.sphere(), &0.2, &orange),
-            _sulphur : Species::new(in_default_models.sphere(), &0.4, &yellow),
-            _oxygen  : Species::new(in_default_models.sphere(), &0.2, &green),
+            _hydrogen : Species::new("H", &0.05, &grey),
+            _carbon  : Species::new("C", &0.1, &blue),
+            _nitrogen : Species::new("N", &0.1, &pink),
+            _nickel  : Species::new("Ni", &0.2, &orange),
+            _sulphur : Species::new("S", &0.4, &yellow),
+            _oxygen  : Species::new("O", &0.2, &green),
+            _others  : ELEMENTS.iter()
+                .filter(|&&symbol| !["H", "C", "N", "Ni", "S", "O"].contains(&symbol))
+                .map(|symbol| Species::new(symbol, &0.2, &brown))
+                .collect(),
         }
     }
 
-    pub fn carbon(&self) -> &Species {&self._carbon}
+    /// The species for an element symbol, e.g. "Ni" (or "NI"), or None if it isn't
+    /// an element's.
+    pub fn by_symbol(&self, in_symbol : &str) -> Option<&Species> {
+        match in_symbol.to_lowercase().as_str() {
+            "h"  => Some(&self._hydrogen),
+            "c"  => Some(&self._carbon),
+            "n"  => Some(&self._nitrogen),
+            "ni" => Some(&self._nickel),
+            "s"  => Some(&self._sulphur),
+            "o"  => Some(&self._oxygen),
+            symbol => self._others.iter().find(|species| species.symbol().to_lowercase() == symbol),
+        }
+    }
+
+    /// Changes how an element's atoms are drawn, e.g. from the config, before any
+    /// are made. An error if there's no default species for the element.
+    pub fn restyle(&mut self, in_symbol : &str, in_size : &Option<f32>, in_colour : &Option<[f32;3]>)
+                   -> Result<(), String> {
+        let species = match in_symbol.to_lowercase().as_str() {
+            "h"  => &mut self._hydrogen,
+            "c"  => &mut self._carbon,
+            "n"  => &mut self._nitrogen,
+            "ni" => &mut self._nickel,
+            "s"  => &mut self._sulphur,
+            "o"  => &mut self._oxygen,
+            symbol => match self._others.iter_mut().find(|species| species.symbol().to_lowercase() == symbol) {
+                Some(species) => species,
+                None => return Err(format!("there's no element \"{}\"", in_symbol)),
+            },
+        };
+        if let Some(size) = *in_size {species.set_size(&size)}
+        if let Some(colour) = *in_colour {species.set_colour(&colour)}
+        Ok(())
+    }
+
     pub fn nickel(&self) -> &Species {&self._nickel}
-    pub fn sulphur(&self) -> &Species {&self._sulphur}
     pub fn oxygen(&self) -> &Species {&self._oxygen}
 }
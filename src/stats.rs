@@ -0,0 +1,71 @@
+use glium;
+
+/// How much GPU memory a texture takes, roughly, with so many bytes a pixel.
+pub fn texture_bytes(in_texture : &glium::texture::Texture2d, in_bytes_per_pixel : &usize) -> usize {
+    in_texture.get_width() as usize*in_texture.get_height().unwrap_or(1) as usize*in_bytes_per_pixel
+}
+
+/// How much GPU memory a 24-bit depth buffer takes, roughly (it's padded to 32).
+pub fn depth_bytes(in_depth : &glium::framebuffer::DepthRenderBuffer) -> usize {
+    let (w, h) = in_depth.get_dimensions();
+    4*w as usize*h as usize
+}
+
+/// A number of bytes for people, e.g. "12.3 MB".
+pub fn size_name(in_bytes : &usize) -> String {
+    let bytes = *in_bytes as f64;
+    if bytes >= 1024.0*1024.0*1024.0 {
+        format!("{:.1} GB", bytes/(1024.0*1024.0*1024.0))
+    } else if bytes >= 1024.0*1024.0 {
+        format!("{:.1} MB", bytes/(1024.0*1024.0))
+    } else if bytes >= 1024.0 {
+        format!("{:.1} kB", bytes/1024.0)
+    } else {
+        format!("{} B", in_bytes)
+    }
+}
+
+// ============================================================
+// Report
+// ============================================================
+/// What the viewer is holding and drawing, for the stats command (and ctrl+M): how
+/// many atoms, bonds and triangles there are, and how much memory the meshes, the
+/// textures the frame is drawn through and the trajectory take, so the users of huge
+/// systems can see what's filling the GPU or the RAM, and what to turn off.
+///
+/// The memory is worked out from the sizes and formats asked for, so it's what the
+/// viewer uses, not what the driver puts aside for it.
+pub struct Report {
+    /// Each line's name, and its count or size, in sections with headings.
+    _lines : Vec<(String, String)>,
+}
+
+impl Report {
+    pub fn new() -> Report {Report {_lines : Vec::new()}}
+
+    pub fn heading(&mut self, in_heading : &str) {
+        self._lines.push((format!("{}:", in_heading), String::new()));
+    }
+
+    pub fn count(&mut self, in_name : &str, in_count : &usize) {
+        self._lines.push((format!("  {}", in_name), format!("{}", in_count)));
+    }
+
+    pub fn memory(&mut self, in_name : &str, in_bytes : &usize) {
+        self._lines.push((format!("  {}", in_name), size_name(in_bytes)));
+    }
+
+    /// The lines, with the counts and sizes lined up on the right.
+    pub fn text(&self) -> String {
+        let width = self._lines.iter().map(|&(ref name, ref value)| name.len()+value.len()).max().unwrap_or(0) + 2;
+        let mut text = String::new();
+        for &(ref name, ref value) in &self._lines {
+            if value.is_empty() {
+                text.push_str(&format!("{}\n", name));
+            } else {
+                text.push_str(&format!("{}{:>w$}\n", name, value, w = width-name.len()));
+            }
+        }
+        text
+    }
+}
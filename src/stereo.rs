@@ -0,0 +1,192 @@
+use glium::{self, Surface};
+use glium::backend::Facade;
+use glium::backend::Context;
+use glium::framebuffer::SimpleFrameBuffer;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use camera::Eye;
+use stats;
+
+// ============================================================
+// Stereo Mode
+// ============================================================
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum StereoMode {
+    Off,
+    /// Red/cyan glasses: the left eye's brightness in red, the right eye's green and blue.
+    Anaglyph,
+    /// The left eye's view in the left half of the window, the right eye's in the
+    /// right half, for parallel free-viewing or 3D screens.
+    SideBySide,
+}
+
+impl StereoMode {
+    pub fn next(&self) -> StereoMode {
+        match *self {
+            StereoMode::Off        => StereoMode::Anaglyph,
+            StereoMode::Anaglyph   => StereoMode::SideBySide,
+            StereoMode::SideBySide => StereoMode::Off,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            StereoMode::Off        => "off",
+            StereoMode::Anaglyph   => "red/cyan anaglyph",
+            StereoMode::SideBySide => "side-by-side",
+        }
+    }
+}
+
+// ============================================================
+// Stereo
+// ============================================================
+/// Draws the scene once per eye into offscreen textures, then combines them onto the
+/// real target in one full-screen pass.
+pub struct StereoSystem {
+    context: Rc<Context>,
+    vertex_buffer: glium::VertexBuffer<SpriteVertex>,
+    index_buffer: glium::IndexBuffer<u16>,
+    program: glium::Program,
+    target_left: RefCell<Option<glium::texture::Texture2d>>,
+    target_right: RefCell<Option<glium::texture::Texture2d>>,
+}
+
+#[derive(Copy, Clone)]
+struct SpriteVertex {
+    position: [f32; 2],
+    i_tex_coords: [f32; 2],
+}
+
+implement_vertex!(SpriteVertex, position, i_tex_coords);
+
+impl StereoSystem {
+    pub fn new<F: ?Sized>(facade: &F) -> StereoSystem where F: Facade + Clone {
+        StereoSystem {
+            context: facade.get_context().clone(),
+
+            vertex_buffer: glium::VertexBuffer::new(facade,
+                &[
+                    SpriteVertex { position: [-1.0, -1.0], i_tex_coords: [0.0, 0.0] },
+                    SpriteVertex { position: [-1.0,  1.0], i_tex_coords: [0.0, 1.0] },
+                    SpriteVertex { position: [ 1.0,  1.0], i_tex_coords: [1.0, 1.0] },
+                    SpriteVertex { position: [ 1.0, -1.0], i_tex_coords: [1.0, 0.0] }
+                ]
+            ).unwrap(),
+
+            index_buffer: glium::index::IndexBuffer::new(facade,
+                glium::index::PrimitiveType::TriangleStrip, &[1 as u16, 2, 0, 3]).unwrap(),
+
+            program: glium::Program::from_source(facade,
+                r"
+                    #version 140
+
+                    in vec2 position;
+                    in vec2 i_tex_coords;
+
+                    out vec2 v_tex_coords;
+
+                    void main() {
+                        gl_Position = vec4(position, 0.0, 1.0);
+                        v_tex_coords = i_tex_coords;
+                    }
+                ",
+                r"
+                    #version 140
+
+                    uniform sampler2D left;
+                    uniform sampler2D right;
+                    // 1 for anaglyph, 2 for side-by-side, as StereoMode
+                    uniform int mode;
+
+                    in vec2 v_tex_coords;
+
+                    out vec4 color;
+
+                    void main() {
+                        if (mode == 2) {
+                            if (v_tex_coords.x < 0.5) {
+                                color = texture(left, vec2(2.0*v_tex_coords.x, v_tex_coords.y));
+                            } else {
+                                color = texture(right, vec2(2.0*v_tex_coords.x-1.0, v_tex_coords.y));
+                            }
+                        } else {
+                            // half-colour anaglyph, which keeps some colour without
+                            // too much rivalry between the eyes
+                            vec4 l = texture(left, v_tex_coords);
+                            vec4 r = texture(right, v_tex_coords);
+                            float brightness = dot(l.rgb, vec3(0.299, 0.587, 0.114));
+                            color = vec4(brightness, r.g, r.b, 1.0);
+                        }
+                    }
+                ",
+                None
+            ).unwrap(),
+
+            target_left: RefCell::new(None),
+            target_right: RefCell::new(None),
+        }
+    }
+
+    /// How much GPU memory the eyes' targets take, roughly (none until stereo is on).
+    pub fn bytes(&self) -> usize {
+        self.target_left.borrow().as_ref().map_or(0, |tex| stats::texture_bytes(tex, &4))
+            + self.target_right.borrow().as_ref().map_or(0, |tex| stats::texture_bytes(tex, &4))
+    }
+}
+
+fn resized_texture(target_texture : &mut Option<glium::texture::Texture2d>,
+                   context : &Rc<Context>,
+                   dimensions : (u32, u32)) {
+    let clear = if let &Some(ref tex) = &*target_texture {
+        tex.get_width() != dimensions.0 || tex.get_height().unwrap() != dimensions.1
+    } else {
+        false
+    };
+    if clear { *target_texture = None; }
+
+    if target_texture.is_none() {
+        let texture = glium::texture::Texture2d::empty(context, dimensions.0, dimensions.1).unwrap();
+        *target_texture = Some(texture);
+    }
+}
+
+/// Calls `draw_eye` for the left and then the right eye, with a framebuffer to draw
+/// into and that framebuffer's aspect ratio, then combines them onto `target`.
+/// Mustn't be called with StereoMode::Off.
+pub fn draw<T, F>(system: &StereoSystem, target: &mut T, mode: StereoMode, mut draw_eye: F)
+                  where T: Surface,
+                        F: FnMut(&mut SimpleFrameBuffer, Eye, f32)
+{
+    let (w, h) = target.get_dimensions();
+    // each eye gets half the width side-by-side
+    let eye_dimensions = match mode {
+        StereoMode::SideBySide => ((w/2).max(1), h),
+        _ => (w, h),
+    };
+    let aspect_ratio = eye_dimensions.0 as f32 / eye_dimensions.1.max(1) as f32;
+
+    let mut target_left = system.target_left.borrow_mut();
+    let mut target_right = system.target_right.borrow_mut();
+    resized_texture(&mut *target_left, &system.context, eye_dimensions);
+    resized_texture(&mut *target_right, &system.context, eye_dimensions);
+    let target_left = target_left.as_ref().unwrap();
+    let target_right = target_right.as_ref().unwrap();
+
+    draw_eye(&mut SimpleFrameBuffer::new(&system.context, target_left).unwrap(), Eye::Left, aspect_ratio);
+    draw_eye(&mut SimpleFrameBuffer::new(&system.context, target_right).unwrap(), Eye::Right, aspect_ratio);
+
+    let uniforms = uniform! {
+        left: &*target_left,
+        right: &*target_right,
+        mode: match mode {
+            StereoMode::SideBySide => 2i32,
+            _ => 1i32,
+        },
+    };
+
+    target.draw(&system.vertex_buffer, &system.index_buffer, &system.program, &uniforms,
+                &Default::default()).unwrap();
+}
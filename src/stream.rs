@@ -0,0 +1,344 @@
+use std::io::{self, BufRead, BufReader, Lines, Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How many frames can wait to be shown before the stream stops reading more.
+pub const BUFFERED_FRAMES : usize = 16;
+
+/// How many of the frames streamed the trajectory keeps, the oldest let go as new
+/// ones come, so a simulation watched for hours doesn't fill the memory.
+pub const KEPT_FRAMES : usize = 2000;
+
+/// What a WebSocket server adds to the client's key before hashing it, to show
+/// it's a WebSocket server (RFC 6455, 1.3).
+const WEBSOCKET_GUID : &'static str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+// ============================================================
+// Frame Stream
+// ============================================================
+/// Frames of a simulation as it runs, e.g. a job on a cluster, read from a TCP
+/// connection to it (--stream host:port) or a WebSocket (--stream ws://host:port/path,
+/// the frames in its text or binary messages) in the xyz format: the number of
+/// atoms, a comment line, then an element and x, y and z on each atom's line, frame
+/// after frame. The atoms must be those of the structure loaded, in the same order,
+/// and a frame with another number of them ends the stream.
+/// (There's no TLS, so for wss:// forward a port, e.g. with ssh -L, and use ws://.)
+///
+/// The frames are read on a thread of their own, as the surface is worked out,
+/// into a buffer of BUFFERED_FRAMES. If the viewer falls behind and the buffer
+/// fills, the thread stops reading, so the connection's buffers fill in turn and
+/// TCP holds the simulation back, rather than frames being dropped or piling up in
+/// memory. The trajectory keeps the last KEPT_FRAMES of them.
+pub struct FrameStream {
+    _address : String,
+    _frames  : Receiver<Result<Vec<[f32;3]>, String>>,
+}
+
+impl FrameStream {
+    /// Connects to a simulation whose frames have `in_atom_count` atoms.
+    pub fn connect(in_address : &str, in_atom_count : &usize) -> Result<FrameStream, String> {
+        let (sender, receiver) = mpsc::sync_channel(BUFFERED_FRAMES);
+        let atom_count = *in_atom_count;
+        if in_address.starts_with("wss://") {
+            return Err(format!("couldn't connect to {}: wss:// isn't supported, but ws:// through a \
+                                forwarded port (ssh -L) is", in_address));
+        } else if in_address.starts_with("ws://") {
+            let socket = WebSocket::connect(in_address)
+                .map_err(|e| format!("couldn't connect to {}: {}", in_address, e))?;
+            thread::spawn(move || send_frames(BufReader::new(socket), atom_count, sender));
+        } else {
+            let connection = TcpStream::connect(in_address)
+                .map_err(|e| format!("couldn't connect to {}: {}", in_address, e))?;
+            thread::spawn(move || send_frames(BufReader::new(connection), atom_count, sender));
+        }
+        Ok(FrameStream {
+            _address : in_address.to_owned(),
+            _frames  : receiver,
+        })
+    }
+
+    pub fn address(&self) -> &str {&self._address}
+
+    /// The frames which have arrived since the last call, oldest first, and why the
+    /// stream has ended, if it has.
+    pub fn receive(&self) -> (Vec<Vec<[f32;3]>>, Option<String>) {
+        let mut frames = Vec::new();
+        loop {
+            match self._frames.try_recv() {
+                Ok(Ok(frame)) => frames.push(frame),
+                Ok(Err(message)) => return (frames, Some(message)),
+                Err(TryRecvError::Empty) => return (frames, None),
+                Err(TryRecvError::Disconnected) => return (frames, Some("lost the connection".to_owned())),
+            }
+        }
+    }
+}
+
+/// Reads frames until the connection ends, sending each on to the viewer.
+fn send_frames<B : BufRead>(in_reader : B, in_atom_count : usize,
+                            in_sender : SyncSender<Result<Vec<[f32;3]>, String>>) {
+    let mut lines = in_reader.lines();
+    let mut line_number = 0;
+    loop {
+        let frame = match read_frame(&mut lines, &mut line_number, &in_atom_count) {
+            Ok(Some(frame)) => Ok(frame),
+            Ok(None) => Err("the simulation closed the connection".to_owned()),
+            Err(message) => Err(message),
+        };
+        let last = frame.is_err();
+        // (stopping when the viewer's let go of the stream)
+        if in_sender.send(frame).is_err() || last {return}
+    }
+}
+
+// ============================================================
+// WebSocket
+// ============================================================
+/// The client's end of a WebSocket (RFC 6455), read as the bytes of the messages
+/// the server sends one after another, whatever frames they come in. Pings are
+/// answered, and a close from the server is the end of the bytes.
+struct WebSocket {
+    _reader    : BufReader<TcpStream>,
+    _writer    : TcpStream,
+    /// How much is left of the data frame being read.
+    _remaining : u64,
+    _closed    : bool,
+    /// For the masks the client puts on what it sends.
+    _random    : u32,
+}
+
+impl WebSocket {
+    /// Connects to a ws://host[:port][/path] address and asks the server to upgrade
+    /// to a WebSocket.
+    fn connect(in_address : &str) -> Result<WebSocket, String> {
+        let rest = &in_address["ws://".len()..];
+        let (host, path) = match rest.find('/') {
+            Some(k) => (&rest[..k], &rest[k..]),
+            None => (rest, "/"),
+        };
+        let authority = if host.contains(':') {host.to_owned()} else {format!("{}:80", host)};
+        let connection = TcpStream::connect(&authority).map_err(|e| e.to_string())?;
+        let mut writer = connection.try_clone().map_err(|e| e.to_string())?;
+        let mut random = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.subsec_nanos()).unwrap_or(0)
+                         ^ 0x9e3779b9;
+        let nonce : Vec<u8> = (0..16).map(|_| next_random(&mut random) as u8).collect();
+        let key = base64(&nonce);
+        write!(writer, "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                        Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n", path, host, key)
+            .map_err(|e| e.to_string())?;
+
+        // the server's answer: 101, with the key hashed as only a WebSocket server would
+        let mut reader = BufReader::new(connection);
+        let mut status = String::new();
+        reader.read_line(&mut status).map_err(|e| e.to_string())?;
+        if status.split_whitespace().nth(1) != Some("101") {
+            return Err(format!("the server didn't take up the WebSocket: {}", status.trim()));
+        }
+        let mut accept = None;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+                return Err("the server closed the connection during the handshake".to_owned());
+            }
+            if line.trim().is_empty() {break}
+            if let Some(k) = line.find(':') {
+                if line[..k].trim().eq_ignore_ascii_case("sec-websocket-accept") {
+                    accept = Some(line[k+1..].trim().to_owned());
+                }
+            }
+        }
+        if accept != Some(accept_key(&key)) {
+            return Err("the server's handshake was wrong (is it a WebSocket server?)".to_owned());
+        }
+        Ok(WebSocket {
+            _reader    : reader,
+            _writer    : writer,
+            _remaining : 0,
+            _closed    : false,
+            _random    : random,
+        })
+    }
+
+    /// Sends a frame, masked, as what clients send must be.
+    fn send(&mut self, in_opcode : u8, in_payload : &[u8]) -> io::Result<()> {
+        let mask = next_random(&mut self._random).to_be_bytes();
+        let mut frame = vec![0x80 | in_opcode, 0x80 | in_payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(in_payload.iter().enumerate().map(|(k, byte)| byte ^ mask[k % 4]));
+        self._writer.write_all(&frame)
+    }
+
+    /// Reads the next frame's header, giving its opcode and length.
+    fn read_header(&mut self) -> io::Result<(u8, u64)> {
+        let invalid = |message : &str| io::Error::new(io::ErrorKind::InvalidData, message.to_owned());
+        let mut header = [0u8;2];
+        self._reader.read_exact(&mut header)?;
+        if header[1] & 0x80 != 0 {return Err(invalid("the server masked a frame"))}
+        let length = match header[1] & 0x7f {
+            126 => {
+                let mut bytes = [0u8;2];
+                self._reader.read_exact(&mut bytes)?;
+                u16::from_be_bytes(bytes) as u64
+            },
+            127 => {
+                let mut bytes = [0u8;8];
+                self._reader.read_exact(&mut bytes)?;
+                u64::from_be_bytes(bytes)
+            },
+            length => length as u64,
+        };
+        Ok((header[0] & 0x0f, length))
+    }
+}
+
+impl Read for WebSocket {
+    fn read(&mut self, out_buffer : &mut [u8]) -> io::Result<usize> {
+        while self._remaining == 0 {
+            if self._closed || out_buffer.is_empty() {return Ok(0)}
+            let (opcode, length) = self.read_header()?;
+            match opcode {
+                // (a message's first frame, text or binary, or the rest of it)
+                0 | 1 | 2 => self._remaining = length,
+                8 | 9 | 10 => {
+                    if length > 125 {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, "a control frame was too long"));
+                    }
+                    let mut payload = vec![0u8; length as usize];
+                    self._reader.read_exact(&mut payload)?;
+                    match opcode {
+                        8 => {
+                            // (the server may have gone already)
+                            let _ = self.send(8, &payload[..payload.len().min(2)]);
+                            self._closed = true;
+                        },
+                        9 => self.send(10, &payload)?,
+                        _ => {},
+                    }
+                },
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown opcode {}", opcode))),
+            }
+        }
+        let wanted = (out_buffer.len() as u64).min(self._remaining) as usize;
+        let read = self._reader.read(&mut out_buffer[..wanted])?;
+        if read == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the connection closed in the middle of a frame"));
+        }
+        self._remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+/// What a WebSocket server answers a client's Sec-WebSocket-Key with, in its
+/// Sec-WebSocket-Accept header: the key and WEBSOCKET_GUID hashed with SHA-1, in
+/// base64.
+pub fn accept_key(in_key : &str) -> String {
+    base64(&sha1(format!("{}{}", in_key, WEBSOCKET_GUID).as_bytes()))
+}
+
+/// A xorshift random number generator, for the handshake's key and the masks.
+fn next_random(in_state : &mut u32) -> u32 {
+    if *in_state == 0 {*in_state = 1}
+    *in_state ^= *in_state << 13;
+    *in_state ^= *in_state >> 17;
+    *in_state ^= *in_state << 5;
+    *in_state
+}
+
+fn base64(in_bytes : &[u8]) -> String {
+    const ALPHABET : &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::new();
+    for chunk in in_bytes.chunks(3) {
+        let bits = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for k in 0..4 {
+            if k <= chunk.len() {
+                text.push(ALPHABET[(bits >> (18-6*k) & 0x3f) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+/// The SHA-1 hash (FIPS 180-4), which the handshake needs, and only it.
+fn sha1(in_bytes : &[u8]) -> [u8;20] {
+    let mut h : [u32;5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut message = in_bytes.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {message.push(0)}
+    message.extend_from_slice(&((in_bytes.len() as u64)*8).to_be_bytes());
+    for block in message.chunks(64) {
+        let mut w = [0u32;80];
+        for t in 0..16 {
+            w[t] = u32::from_be_bytes([block[4*t], block[4*t+1], block[4*t+2], block[4*t+3]]);
+        }
+        for t in 16..80 {
+            w[t] = (w[t-3] ^ w[t-8] ^ w[t-14] ^ w[t-16]).rotate_left(1);
+        }
+        let (mut a, mut b, mut c, mut d, mut e) = (h[0], h[1], h[2], h[3], h[4]);
+        for t in 0..80 {
+            let (f, k) = match t {
+                0..=19  => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _       => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(w[t]);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (word, value) in h.iter_mut().zip(&[a, b, c, d, e]) {*word = word.wrapping_add(*value)}
+    }
+    let mut digest = [0u8;20];
+    for (k, word) in h.iter().enumerate() {digest[4*k..4*k+4].copy_from_slice(&word.to_be_bytes())}
+    digest
+}
+
+/// Reads the next frame, which must have `in_atom_count` atoms, or gives None if the
+/// connection closes between frames. (Also used for the frames an external
+/// optimiser writes, see optimize.rs.)
+pub fn read_frame<B : BufRead>(in_lines : &mut Lines<B>, in_line_number : &mut usize, in_atom_count : &usize)
+                           -> Result<Option<Vec<[f32;3]>>, String> {
+    let mut next_line = |line_number : &mut usize| -> Result<Option<String>, String> {
+        *line_number += 1;
+        match in_lines.next() {
+            Some(Ok(line)) => Ok(Some(line)),
+            Some(Err(e)) => Err(format!("couldn't read line {}: {}", line_number, e)),
+            None => Ok(None),
+        }
+    };
+    let n_atoms : usize = loop {
+        match next_line(in_line_number)? {
+            Some(ref line) if line.trim().is_empty() => continue,
+            Some(line) => break line.trim().parse().map_err(
+                |_| format!("line {}: expected the number of atoms", in_line_number)
+            )?,
+            None => return Ok(None),
+        }
+    };
+    // (checked before anything's made that big, as it's whatever came down the wire)
+    if n_atoms != *in_atom_count {
+        return Err(format!("line {}: a frame of {} atoms, but the structure has {}",
+                           in_line_number, n_atoms, in_atom_count));
+    }
+    let ended = |line_number : &usize| format!("line {}: the connection closed in the middle of a frame", line_number);
+    next_line(in_line_number)?.ok_or_else(|| ended(in_line_number))?;
+    let mut positions = Vec::with_capacity(n_atoms);
+    for _ in 0..n_atoms {
+        let line = next_line(in_line_number)?.ok_or_else(|| ended(in_line_number))?;
+        let bad_line = || format!("line {}: expected an element and 3 numbers", in_line_number);
+        let words : Vec<&str> = line.split_whitespace().collect();
+        if words.len() < 4 {return Err(bad_line())}
+        positions.push([
+            words[1].parse().map_err(|_| bad_line())?,
+            words[2].parse().map_err(|_| bad_line())?,
+            words[3].parse().map_err(|_| bad_line())?,
+        ]);
+    }
+    Ok(Some(positions))
+}
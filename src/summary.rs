@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+
+use {bonds, diagram, file_input, file_output, species, topology, usage};
+
+/// What the oxide program does without the graphics feature, where there's no
+/// viewer: each structure file given is read and what's in it printed, its
+/// formula, bonds, fragments, frames and cell. With export graph (e.g. export graph
+/// molecule.dot) or export diagram (e.g. export diagram molecule.svg), the last
+/// one's bond graph or 2D diagram is written too, and with export structure (e.g.
+/// export structure molecule.pdb), the last one itself, in another format.
+pub fn run(in_args : &[String]) {
+    let mut fnames : Vec<String> = in_args.to_vec();
+    if fnames.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}", usage::USAGE);
+        return;
+    }
+    let mut graph_fname : Option<String> = None;
+    let mut diagram_fname : Option<String> = None;
+    let mut structure_fname : Option<String> = None;
+    while let Some(k) = fnames.iter().position(|arg| arg == "export").filter(|&k| k+2 < fnames.len()) {
+        let fname = fnames.remove(k+2);
+        match fnames[k+1].as_str() {
+            "graph" => graph_fname = Some(fname),
+            "diagram" => diagram_fname = Some(fname),
+            "structure" => structure_fname = Some(fname),
+            "geometry" => println!("Ignoring export geometry: the scene is only built with the graphics feature"),
+            "povray" => println!("Ignoring export povray: there's no view to export without the graphics feature"),
+            "svg" => println!("Ignoring export svg: there's no view to export without the graphics feature"),
+            other => println!("Ignoring export {}: expected export graph, diagram or structure", other),
+        }
+        fnames.drain(k..k+2);
+    }
+    let default_species = species::DefaultSpecies::new();
+    let last = fnames.len().saturating_sub(1);
+    for (k, fname) in fnames.iter().enumerate() {
+        let (molecule, trajectory) = match file_input::read_structure_file(fname, &default_species) {
+            Ok(structure) => structure,
+            Err(message) => {
+                println!("Couldn't read {}: {}", fname, message);
+                continue;
+            },
+        };
+        let mut elements : BTreeMap<&str, usize> = BTreeMap::new();
+        for atom in molecule.atoms() {
+            *elements.entry(atom.species().symbol()).or_insert(0) += 1;
+        }
+        let formula : Vec<String> = elements.iter().map(|(symbol, count)| format!("{}{}", symbol, count)).collect();
+        let bonds = bonds::find_bonds(&molecule);
+        let fragments = bonds::fragments(&molecule.atoms().len(), &bonds).iter().max().map_or(0, |&f| f+1);
+        println!("{}: {} atoms ({}), {} bonds, {} fragments", fname, molecule.atoms().len(),
+                 formula.join(" "), bonds.len(), fragments);
+        if trajectory.frames().len() > 1 {
+            println!("  {} frames", trajectory.frames().len());
+        }
+        if let Some(cell) = *molecule.cell() {
+            let length = |v : &[f32;3]| (v[0]*v[0]+v[1]*v[1]+v[2]*v[2]).sqrt();
+            let lattice = cell.lattice();
+            println!("  cell {:.3} x {:.3} x {:.3} Angstroms", length(&lattice[0]), length(&lattice[1]),
+                     length(&lattice[2]));
+        }
+        if let Some(graph_fname) = graph_fname.as_ref().filter(|_| k == last) {
+            match topology::write_graph(graph_fname, &molecule, &bonds) {
+                Ok(()) => println!("  saved the bond graph to {}", graph_fname),
+                Err(message) => println!("  couldn't save the bond graph: {}", message),
+            }
+        }
+        if let Some(diagram_fname) = diagram_fname.as_ref().filter(|_| k == last) {
+            match diagram::Diagram::lay_out(&molecule, &bonds).and_then(|diagram| diagram.write_svg(diagram_fname)) {
+                Ok(()) => println!("  saved the 2D diagram to {}", diagram_fname),
+                Err(message) => println!("  couldn't save the 2D diagram: {}", message),
+            }
+        }
+        if let Some(structure_fname) = structure_fname.as_ref().filter(|_| k == last) {
+            match file_output::write_structure_file(structure_fname, &molecule) {
+                Ok(()) => println!("  saved the structure to {}", structure_fname),
+                Err(message) => println!("  couldn't save the structure: {}", message),
+            }
+        }
+    }
+}
@@ -0,0 +1,343 @@
+extern crate glium;
+
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Instant;
+
+use binding_site::WATERS;
+use molecule::Molecule;
+use progress::Progress;
+use radii;
+use vertex::ColouredVertex;
+use volume::Volume;
+
+/// How far apart the points the density is found at are, in Angstroms.
+const GRID_SPACING : f32 = 0.5;
+
+/// How quickly each atom's density falls off: the lower, the more neighbouring atoms
+/// blend together. On its own, an atom's surface is at its van der Waals radius.
+const BLOBBINESS : f32 = 1.5;
+
+/// Atoms further than this (in Angstroms) from a point on the surface are left out
+/// of its potential, which is shifted to go smoothly to 0 there.
+const POTENTIAL_CUTOFF : f32 = 12.0;
+
+/// Coulomb's constant, in volt Angstroms per elementary charge, so the potential is
+/// in volts from charges in e. There's no solvent or dielectric.
+const COULOMB : f32 = 14.4;
+
+/// How opaque the surface is when it's translucent.
+pub const TRANSLUCENT_OPACITY : f32 = 0.5;
+
+/// What shift+S steps through: no surface, an opaque one, and one the atoms show
+/// through.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum SurfaceDisplay {
+    Hidden,
+    Opaque,
+    Translucent,
+}
+
+impl SurfaceDisplay {
+    pub fn next(&self) -> SurfaceDisplay {
+        match *self {
+            SurfaceDisplay::Hidden      => SurfaceDisplay::Opaque,
+            SurfaceDisplay::Opaque      => SurfaceDisplay::Translucent,
+            SurfaceDisplay::Translucent => SurfaceDisplay::Hidden,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SurfaceDisplay::Hidden      => "hidden",
+            SurfaceDisplay::Opaque      => "opaque",
+            SurfaceDisplay::Translucent => "translucent",
+        }
+    }
+
+    /// The display with a name (as name() gives it).
+    pub fn parse(in_name : &str) -> Result<SurfaceDisplay, String> {
+        [SurfaceDisplay::Hidden, SurfaceDisplay::Opaque, SurfaceDisplay::Translucent].iter()
+            .find(|display| display.name() == in_name.to_lowercase()).cloned()
+            .ok_or_else(|| format!("expected hidden, opaque or translucent, not \"{}\"", in_name))
+    }
+}
+
+// ============================================================
+// Surface
+// ============================================================
+/// A Gaussian surface round the molecule (leaving out waters): each atom has a
+/// density falling off as a Gaussian from its centre, and the surface is where
+/// their sum is 1, found on a grid by marching tetrahedra. It's much like the
+/// solvent-excluded surface, without its sharp creases. Each vertex is coloured by
+/// the Coulomb potential of the atoms' partial charges there, red for negative
+/// through white to blue for positive, scaled to the largest either way; without
+/// charges, it's plain.
+///
+/// It's also what isosurfaces of volumes are drawn as, e.g. the lobes of orbitals
+/// from cube files, in a colour each.
+pub struct Surface {
+    /// The vertices and triangles are kept, as well as drawn, for saving the surface
+    /// (see geometry.rs).
+    _vertices      : Vec<ColouredVertex>,
+    _indices       : Vec<u32>,
+    _vertex_buffer : glium::VertexBuffer<ColouredVertex>,
+    /// The surface can have more vertices than u16 indices reach.
+    _index_buffer  : glium::index::IndexBuffer<u32>,
+    /// The largest potential, either way, in volts, or None without charges.
+    _potential     : Option<f32>,
+}
+
+impl Surface {
+    /// The molecular surface from what a SurfaceJob found, coloured by the potential
+    /// where there are charges, and otherwise all `in_colour`.
+    fn from_found(in_display : &glium::backend::glutin_backend::GlutinFacade, in_found : Found,
+                  in_colour : &[f32;3]) -> Surface {
+        let (positions, normals, indices, potentials) = in_found;
+        let potential = potentials.as_ref().map(|potentials| {
+            potentials.iter().fold(0.0f32, |max, p| max.max(p.abs()))
+        });
+        let vertices : Vec<ColouredVertex> = (0..positions.len()).map(|k| {
+            let colour = match (potentials.as_ref(), potential) {
+                (Some(potentials), Some(max)) if max > 0.0 => potential_colour(&(potentials[k]/max)),
+                _ => *in_colour,
+            };
+            ColouredVertex::new(positions[k], normals[k], colour)
+        }).collect();
+        Surface::from_vertices(in_display, vertices, indices, potential)
+    }
+
+    /// Where the values of a volume are at a level, all in one colour, e.g. one lobe
+    /// of an orbital.
+    pub fn isosurface(in_display : &glium::backend::glutin_backend::GlutinFacade, in_volume : &Volume,
+                      in_level : &f32, in_colour : &[f32;3]) -> Surface {
+        let (positions, normals, indices) = in_volume.isosurface(in_level);
+        let vertices : Vec<ColouredVertex> = (0..positions.len())
+            .map(|k| ColouredVertex::new(positions[k], normals[k], *in_colour))
+            .collect();
+        Surface::from_vertices(in_display, vertices, indices, None)
+    }
+
+    /// Takes the vertices and indices over, rather than copying them, since a big
+    /// structure's surface can have millions.
+    fn from_vertices(in_display : &glium::backend::glutin_backend::GlutinFacade, in_vertices : Vec<ColouredVertex>,
+                     in_indices : Vec<u32>, in_potential : Option<f32>) -> Surface {
+        let vertex_buffer = glium::VertexBuffer::new(in_display, &in_vertices).unwrap();
+        let index_buffer = glium::index::IndexBuffer::new(
+            in_display, glium::index::PrimitiveType::TrianglesList, &in_indices
+        ).unwrap();
+        Surface {
+            _vertices      : in_vertices,
+            _indices       : in_indices,
+            _vertex_buffer : vertex_buffer,
+            _index_buffer  : index_buffer,
+            _potential     : in_potential,
+        }
+    }
+
+    pub fn vertices(&self) -> &Vec<ColouredVertex> {&self._vertices}
+    pub fn indices(&self) -> &Vec<u32> {&self._indices}
+    pub fn vertex_buffer(&self) -> &glium::VertexBuffer<ColouredVertex> {&self._vertex_buffer}
+    pub fn index_buffer(&self) -> &glium::index::IndexBuffer<u32> {&self._index_buffer}
+    pub fn potential(&self) -> &Option<f32> {&self._potential}
+
+    pub fn triangles(&self) -> usize {self._index_buffer.len()/3}
+
+    /// How much GPU memory the vertices and indices take.
+    pub fn bytes(&self) -> usize {self._vertex_buffer.get_size() + self._index_buffer.get_size()}
+}
+
+/// The colour for a potential scaled to between -1 and 1: red for -1, through white,
+/// to blue for 1.
+pub fn potential_colour(in_t : &f32) -> [f32;3] {
+    let t = in_t.max(-1.0).min(1.0);
+    if t < 0.0 {
+        [1.0, 1.0+t, 1.0+t]
+    } else {
+        [1.0-t, 1.0-t, 1.0]
+    }
+}
+
+/// What finding the molecular surface gives: the vertices, their normals, the
+/// triangles and, with charges, the potential at each vertex.
+type Found = (Vec<[f32;3]>, Vec<[f32;3]>, Vec<u32>, Option<Vec<f32>>);
+
+// ============================================================
+// Surface Job
+// ============================================================
+/// The molecular surface being found in another thread, as it takes a while for
+/// big molecules, with how far it's got for the viewer to show. Dropping it (e.g.
+/// after an edit) cancels it.
+pub struct SurfaceJob {
+    _progress : Progress,
+    _colour   : [f32;3],
+    _found    : Receiver<Found>,
+    _started  : Instant,
+}
+
+impl SurfaceJob {
+    pub fn start(in_molecule : &Molecule, in_colour : &[f32;3]) -> SurfaceJob {
+        let atoms = surrounded(in_molecule);
+        let charges = charges(in_molecule);
+        let progress = Progress::new(&0);
+        let (sender, receiver) = mpsc::channel();
+        let working = progress.clone();
+        thread::spawn(move || {
+            let (positions, normals, indices) = match gaussian_surface(&atoms, &working) {
+                Some(surface) => surface,
+                None => return,
+            };
+            let potentials = match charges {
+                Some(ref charges) => match potentials(charges, &positions, &working) {
+                    Some(potentials) => Some(potentials),
+                    None => return,
+                },
+                None => None,
+            };
+            // (nothing's listening if it's been dropped)
+            let _ = sender.send((positions, normals, indices, potentials));
+        });
+        SurfaceJob {
+            _progress : progress,
+            _colour   : *in_colour,
+            _found    : receiver,
+            _started  : Instant::now(),
+        }
+    }
+
+    pub fn progress(&self) -> &Progress {&self._progress}
+    pub fn started(&self) -> &Instant {&self._started}
+
+    /// The surface, ready to draw, once it's been found; an error if the thread
+    /// finding it gave up.
+    pub fn receive(&self, in_display : &glium::backend::glutin_backend::GlutinFacade)
+                   -> Option<Result<Surface, String>> {
+        match self._found.try_recv() {
+            Ok(found) => Some(Ok(Surface::from_found(in_display, found, &self._colour))),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("finding the molecular surface failed".to_owned())),
+        }
+    }
+}
+
+impl Drop for SurfaceJob {
+    fn drop(&mut self) {self._progress.cancel()}
+}
+
+/// The atoms the surface goes round, with their van der Waals radii.
+fn surrounded(in_molecule : &Molecule) -> Vec<([f32;3], f32)> {
+    in_molecule.atoms().iter()
+        .filter(|atom| atom.residue().as_ref().map_or(true, |residue| !WATERS.contains(&residue.name())))
+        .map(|atom| (*atom.position(), radii::vdw_radius(atom.species().symbol())))
+        .collect()
+}
+
+/// The atoms' partial charges, where they are, or None if no atom has a charge.
+fn charges(in_molecule : &Molecule) -> Option<Vec<([f32;3], f32)>> {
+    if in_molecule.atoms().iter().all(|atom| atom.charge().is_none()) {return None}
+    Some(in_molecule.atoms().iter()
+        .filter_map(|atom| atom.charge().map(|charge| (*atom.position(), charge)))
+        .filter(|&(_, charge)| charge != 0.0)
+        .collect())
+}
+
+/// The vertices (with their normals) and triangles of the Gaussian surface round
+/// atoms with these radii. The density, atom by atom, is the first half of
+/// `in_progress` (the potentials are the second), and None if it's cancelled.
+fn gaussian_surface(in_atoms : &[([f32;3], f32)], in_progress : &Progress)
+                    -> Option<(Vec<[f32;3]>, Vec<[f32;3]>, Vec<u32>)> {
+    let atoms = in_atoms;
+    if atoms.is_empty() {return Some((Vec::new(), Vec::new(), Vec::new()))}
+
+    // each atom's density is left out where it's under a thousandth
+    let reach = |radius : f32| radius*(1.0 + 1000.0f32.ln()/BLOBBINESS).sqrt();
+    let max_reach = atoms.iter().map(|&(_, r)| reach(r)).fold(0.0f32, f32::max);
+    let mut low = [f32::INFINITY;3];
+    let mut high = [f32::NEG_INFINITY;3];
+    for &(p, _) in atoms {
+        for k in 0..3 {
+            low[k] = low[k].min(p[k]-max_reach-GRID_SPACING);
+            high[k] = high[k].max(p[k]+max_reach+GRID_SPACING);
+        }
+    }
+    let n = [
+        ((high[0]-low[0])/GRID_SPACING).ceil() as usize + 1,
+        ((high[1]-low[1])/GRID_SPACING).ceil() as usize + 1,
+        ((high[2]-low[2])/GRID_SPACING).ceil() as usize + 1,
+    ];
+    let axes = [[GRID_SPACING, 0.0, 0.0], [0.0, GRID_SPACING, 0.0], [0.0, 0.0, GRID_SPACING]];
+    let mut density = Volume::new(&low, &axes, &n, vec![0.0f32; n[0]*n[1]*n[2]]);
+
+    // the density on the grid
+    for (i, &(p, r)) in atoms.iter().enumerate() {
+        if in_progress.is_cancelled() {return None}
+        in_progress.set(&i, &(2*atoms.len()));
+        let extent = reach(r);
+        let from = |k : usize| (((p[k]-extent-low[k])/GRID_SPACING).floor().max(0.0)) as usize;
+        let to = |k : usize| ((((p[k]+extent-low[k])/GRID_SPACING).ceil()) as usize).min(n[k]-1);
+        for x in from(0)..to(0)+1 {
+            for y in from(1)..to(1)+1 {
+                for z in from(2)..to(2)+1 {
+                    let q = density.position(&[x, y, z]);
+                    let d2 = (q[0]-p[0])*(q[0]-p[0])+(q[1]-p[1])*(q[1]-p[1])+(q[2]-p[2])*(q[2]-p[2]);
+                    if d2 > extent*extent {continue}
+                    let at = density.index(&[x, y, z]);
+                    density.values_mut()[at] += (-BLOBBINESS*(d2/(r*r)-1.0)).exp();
+                }
+            }
+        }
+    }
+    // out of the molecule, down the density
+    in_progress.set(&atoms.len(), &(2*atoms.len()));
+    Some(density.isosurface(&1.0))
+}
+
+/// The Coulomb potential (in volts) of the partial charges at each point. They're
+/// the second half of `in_progress`, and None if it's cancelled.
+fn potentials(in_charges : &[([f32;3], f32)], in_points : &Vec<[f32;3]>, in_progress : &Progress)
+              -> Option<Vec<f32>> {
+    let charges = in_charges;
+
+    // sort the charges into cubes, so that only neighbouring cubes need searching
+    let cube = |position : &[f32;3]| -> [i64;3] {
+        [
+            (position[0]/POTENTIAL_CUTOFF).floor() as i64,
+            (position[1]/POTENTIAL_CUTOFF).floor() as i64,
+            (position[2]/POTENTIAL_CUTOFF).floor() as i64,
+        ]
+    };
+    let mut cubes : HashMap<[i64;3], Vec<usize>> = HashMap::new();
+    for (i, &(position, _)) in charges.iter().enumerate() {
+        cubes.entry(cube(&position)).or_insert_with(Vec::new).push(i);
+    }
+    let mut potentials = Vec::with_capacity(in_points.len());
+    for (k, p) in in_points.iter().enumerate() {
+        if k%1000 == 0 {
+            if in_progress.is_cancelled() {return None}
+            in_progress.set(&(in_points.len()+k), &(2*in_points.len()));
+        }
+        let c = cube(p);
+        let mut potential = 0.0f32;
+        for dx in -1..2 {
+            for dy in -1..2 {
+                for dz in -1..2 {
+                    let near = match cubes.get(&[c[0]+dx, c[1]+dy, c[2]+dz]) {
+                        Some(near) => near,
+                        None => continue,
+                    };
+                    for &i in near {
+                        let (q, charge) = charges[i];
+                        let r = ((p[0]-q[0])*(p[0]-q[0])+(p[1]-q[1])*(p[1]-q[1])+(p[2]-q[2])*(p[2]-q[2])).sqrt();
+                        if r < POTENTIAL_CUTOFF {
+                            // (no closer than an Angstrom, for points right on an atom)
+                            potential += COULOMB*charge*(1.0/r.max(1.0) - 1.0/POTENTIAL_CUTOFF);
+                        }
+                    }
+                }
+            }
+        }
+        potentials.push(potential);
+    }
+    Some(potentials)
+}
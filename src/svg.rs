@@ -0,0 +1,146 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+use camera::Camera;
+use raytrace::Shape;
+
+/// How thick the outlines are, in the window's pixels.
+const OUTLINE_WIDTH : f32 = 1.0;
+
+/// Something drawn, flattened onto the screen.
+enum Flat {
+    Circle {centre : [f32;2], radius : f32},
+    Capsule {from : [f32;2], to : [f32;2], radius : f32},
+    Triangle {corners : [[f32;2];3]},
+}
+
+// ============================================================
+// SVG
+// ============================================================
+/// Writes the view as an SVG (export svg, or ctrl+V), for schematic figures on
+/// posters and slides, which need to scale: each atom's sphere as a flat circle,
+/// each stick as a capsule and each arrow's head as a triangle, in the colours and opacities they're drawn in, each
+/// outlined. They're projected through the camera onto a picture the size of the
+/// window and painted from the back to the front, each atom at its centre's depth
+/// and each stick at its middle's, so a stick from an atom in front hides the atom
+/// behind where it goes into it, as it does on the screen.
+pub fn write_view(in_fname : &str, in_shapes : &[Shape], in_camera : &Camera, in_background : &[f32;3])
+                  -> Result<(), String> {
+    let file = File::create(in_fname).map_err(|e| format!("couldn't create {}: {}", in_fname, e))?;
+    let mut file = BufWriter::new(file);
+    file.write_all(svg(in_shapes, in_camera, in_background).as_bytes()).and_then(|_| file.flush())
+        .map_err(|e| format!("couldn't write to {}: {}", in_fname, e))
+}
+
+fn svg(in_shapes : &[Shape], in_camera : &Camera, in_background : &[f32;3]) -> String {
+    let (w, h) = (in_camera.screen_size()[0].max(1) as f32, in_camera.screen_size()[1].max(1) as f32);
+    let view_matrix = *in_camera.view_matrix();
+    let vp_matrix = *in_camera.vp_matrix();
+    // the screen's x axis in world space, to measure radii along
+    let right = view_matrix.rigid_inverse()*[1.0, 0.0, 0.0, 0.0];
+    // where a point is on the picture (in pixels from the top left) and how far it is
+    // into the screen, or None if it's behind the camera
+    let project = |p : &[f32;3]| -> Option<([f32;2], f32)> {
+        let clip = vp_matrix*[p[0], p[1], p[2], 1.0];
+        if clip[3] <= 0.0 {return None}
+        let depth = (view_matrix*[p[0], p[1], p[2], 1.0])[2];
+        Some(([(clip[0]/clip[3]+1.0)/2.0*w, (1.0-clip[1]/clip[3])/2.0*h], depth))
+    };
+    let projected_radius = |p : &[f32;3], centre : &[f32;2], r : &f32| -> Option<f32> {
+        let edge = [p[0]+r*right[0], p[1]+r*right[1], p[2]+r*right[2]];
+        project(&edge).map(|(q, _)| ((q[0]-centre[0]).powi(2)+(q[1]-centre[1]).powi(2)).sqrt())
+    };
+
+    // everything in front of the camera, with its depth, colour and opacity
+    let mut flats : Vec<(f32, Flat, [f32;3], f32)> = Vec::new();
+    for shape in in_shapes {
+        match *shape {
+            Shape::Sphere {centre, radius, colour, opacity} => {
+                if opacity <= 0.0 {continue}
+                if let Some((c, depth)) = project(&centre) {
+                    if let Some(r) = projected_radius(&centre, &c, &radius) {
+                        flats.push((depth, Flat::Circle {centre : c, radius : r}, colour, opacity));
+                    }
+                }
+            },
+            Shape::Stick {from, to, radius, colour, opacity} => {
+                if opacity <= 0.0 {continue}
+                if let (Some((a, depth_a)), Some((b, depth_b))) = (project(&from), project(&to)) {
+                    let ra = projected_radius(&from, &a, &radius);
+                    let rb = projected_radius(&to, &b, &radius);
+                    if let (Some(ra), Some(rb)) = (ra, rb) {
+                        let flat = Flat::Capsule {from : a, to : b, radius : (ra+rb)/2.0};
+                        flats.push(((depth_a+depth_b)/2.0, flat, colour, opacity));
+                    }
+                }
+            },
+            // its outline from the side: the tip, and the base's ends across the screen
+            Shape::Cone {base, tip, radius, colour, opacity} => {
+                if opacity <= 0.0 {continue}
+                if let (Some((a, depth_a)), Some((b, depth_b))) = (project(&base), project(&tip)) {
+                    if let Some(r) = projected_radius(&base, &a, &radius) {
+                        let d = [b[0]-a[0], b[1]-a[1]];
+                        let length = (d[0]*d[0]+d[1]*d[1]).sqrt();
+                        let flat = if length < 1e-3 {
+                            Flat::Circle {centre : a, radius : r}
+                        } else {
+                            let n = [-d[1]/length*r, d[0]/length*r];
+                            Flat::Triangle {corners : [[a[0]+n[0], a[1]+n[1]], b, [a[0]-n[0], a[1]-n[1]]]}
+                        };
+                        flats.push(((depth_a+depth_b)/2.0, flat, colour, opacity));
+                    }
+                }
+            },
+        }
+    }
+    // the furthest first
+    flats.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(::std::cmp::Ordering::Equal));
+
+    let mut text = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" \
+                            viewBox=\"0 0 {:.0} {:.0}\">\n  <rect width=\"100%\" height=\"100%\" fill=\"{}\"/>\n",
+                           w, h, w, h, hex(in_background));
+    text.push_str(&format!("  <g stroke=\"black\" stroke-width=\"{}\" stroke-linejoin=\"round\">\n", OUTLINE_WIDTH));
+    for (_, flat, colour, opacity) in flats {
+        let fill = if opacity >= 1.0 {
+            format!("fill=\"{}\"", hex(&colour))
+        } else {
+            format!("fill=\"{}\" fill-opacity=\"{:.3}\" stroke-opacity=\"{:.3}\"", hex(&colour), opacity, opacity)
+        };
+        match flat {
+            Flat::Circle {centre, radius} => text.push_str(&format!(
+                "    <circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\" {}/>\n", centre[0], centre[1], radius, fill
+            )),
+            Flat::Capsule {from, to, radius} => {
+                let d = [to[0]-from[0], to[1]-from[1]];
+                let length = (d[0]*d[0]+d[1]*d[1]).sqrt();
+                // seen end on, it's just its round end
+                if length < 1e-3 {
+                    text.push_str(&format!("    <circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\" {}/>\n",
+                                           from[0], from[1], radius, fill));
+                    continue;
+                }
+                // along both sides, round each end
+                let n = [-d[1]/length*radius, d[0]/length*radius];
+                text.push_str(&format!(
+                    "    <path d=\"M {:.2} {:.2} L {:.2} {:.2} A {r:.2} {r:.2} 0 0 0 {:.2} {:.2} \
+                     L {:.2} {:.2} A {r:.2} {r:.2} 0 0 0 {:.2} {:.2} Z\" {}/>\n",
+                    from[0]+n[0], from[1]+n[1], to[0]+n[0], to[1]+n[1], to[0]-n[0], to[1]-n[1],
+                    from[0]-n[0], from[1]-n[1], from[0]+n[0], from[1]+n[1], fill, r = radius
+                ));
+            },
+            Flat::Triangle {corners} => text.push_str(&format!(
+                "    <path d=\"M {:.2} {:.2} L {:.2} {:.2} L {:.2} {:.2} Z\" {}/>\n",
+                corners[0][0], corners[0][1], corners[1][0], corners[1][1], corners[2][0], corners[2][1], fill
+            )),
+        }
+    }
+    text.push_str("  </g>\n</svg>\n");
+    text
+}
+
+/// A colour as SVG writes it, e.g. #eee8d5.
+fn hex(in_colour : &[f32;3]) -> String {
+    let byte = |x : f32| (x.max(0.0).min(1.0)*255.0).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", byte(in_colour[0]), byte(in_colour[1]), byte(in_colour[2]))
+}
@@ -0,0 +1,170 @@
+use cell::Cell;
+use molecule::Molecule;
+
+/// Atoms the symmetry puts closer together than this (in Angstroms) are on the
+/// same site, e.g. an atom on a mirror plane and its reflection.
+pub const SITE_TOLERANCE : f32 = 0.1;
+
+// ============================================================
+// Symmetry Operator
+// ============================================================
+/// One of a space group's symmetry operations, in fractional coordinates, as a CIF
+/// gives them, e.g. "-x+1/2, y, z+1/2".
+#[derive(Copy, Clone, Debug)]
+pub struct SymmetryOperator {
+    _rotation    : [[f32;3];3],
+    _translation : [f32;3],
+}
+
+impl SymmetryOperator {
+    /// Reads the three comma-separated lines of an operator, each a sum of terms
+    /// like "-x", "2y", "+1/2" or "0.25".
+    pub fn parse(in_text : &str) -> Result<SymmetryOperator, String> {
+        let bad_operator = || format!("\"{}\" isn't a symmetry operator", in_text);
+        let components : Vec<&str> = in_text.split(',').collect();
+        if components.len() != 3 {return Err(bad_operator())}
+        let mut operator = SymmetryOperator {
+            _rotation    : [[0.0;3];3],
+            _translation : [0.0;3],
+        };
+        for (i, component) in components.iter().enumerate() {
+            let text : String = component.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_lowercase();
+            if text.is_empty() {return Err(bad_operator())}
+            // split into terms before each sign, keeping the sign
+            let mut terms = Vec::new();
+            let mut term = String::new();
+            for c in text.chars() {
+                if (c == '+' || c == '-') && !term.is_empty() {
+                    terms.push(term);
+                    term = String::new();
+                }
+                term.push(c);
+            }
+            terms.push(term);
+            for term in terms {
+                let (sign, rest) = match term.chars().next() {
+                    Some('-') => (-1.0, &term[1..]),
+                    Some('+') => (1.0, &term[1..]),
+                    _ => (1.0, &term[..]),
+                };
+                let axis = match rest.chars().last() {
+                    Some('x') => Some(0),
+                    Some('y') => Some(1),
+                    Some('z') => Some(2),
+                    _ => None,
+                };
+                let number = match axis {
+                    Some(_) => &rest[..rest.len()-1],
+                    None => rest,
+                };
+                let number = number.trim_end_matches('*');
+                let value = if number.is_empty() {
+                    if axis.is_none() {return Err(bad_operator())}
+                    1.0
+                } else {
+                    let mut parts = number.split('/');
+                    let numerator : f32 = parts.next().and_then(|s| s.parse().ok()).ok_or_else(&bad_operator)?;
+                    match parts.next() {
+                        Some(s) => numerator/s.parse::<f32>().ok().filter(|&d| d != 0.0).ok_or_else(&bad_operator)?,
+                        None => numerator,
+                    }
+                };
+                match axis {
+                    Some(j) => operator._rotation[i][j] += sign*value,
+                    None => operator._translation[i] += sign*value,
+                }
+            }
+        }
+        Ok(operator)
+    }
+
+    /// Where the operator takes a point, in fractional coordinates.
+    pub fn apply(&self, in_fractional : &[f32;3]) -> [f32;3] {
+        let (r, t) = (&self._rotation, &self._translation);
+        let mut result = *t;
+        for i in 0..3 {
+            for j in 0..3 {
+                result[i] += r[i][j]*in_fractional[j];
+            }
+        }
+        result
+    }
+
+    /// The operator's rotation (or reflection) in cartesian coordinates, for turning
+    /// displacement parameters with the atoms.
+    pub fn cartesian_rotation(&self, in_cell : &Cell) -> [[f32;3];3] {
+        let mut rotation = [[0.0f32;3];3];
+        for m in 0..3 {
+            let mut axis = [0.0f32;3];
+            axis[m] = 1.0;
+            let fractional = in_cell.to_fractional(&axis);
+            let mut turned = [0.0f32;3];
+            for i in 0..3 {
+                for j in 0..3 {
+                    turned[i] += self._rotation[i][j]*fractional[j];
+                }
+            }
+            let column = in_cell.to_cartesian(&turned);
+            for k in 0..3 {
+                rotation[k][m] = column[k];
+            }
+        }
+        rotation
+    }
+}
+
+// ============================================================
+// Expansion
+// ============================================================
+/// The whole cell's contents from the asymmetric unit: each atom put through every
+/// operator and back into the cell, with the copies which land on a site already
+/// taken (within SITE_TOLERANCE, across the cell's edges too) dropped. Each atom's
+/// copies follow it, the untransformed one first if the operators start with the
+/// identity as they usually do. Displacement parameters are turned with the atoms.
+/// None if the molecule has no cell.
+pub fn fill_cell<'a>(in_molecule : &Molecule<'a>, in_operators : &Vec<SymmetryOperator>) -> Option<Molecule<'a>> {
+    let cell = (*in_molecule.cell())?;
+    let origin = cell.origin();
+    let mut filled = Molecule::new();
+    let mut sites : Vec<[f32;3]> = Vec::new();
+    for atom in in_molecule.atoms() {
+        let p = atom.position();
+        let fractional = cell.to_fractional(&[p[0]-origin[0], p[1]-origin[1], p[2]-origin[2]]);
+        for operator in in_operators {
+            let mut site = operator.apply(&fractional);
+            for k in 0..3 {
+                site[k] -= site[k].floor();
+            }
+            let taken = sites.iter().any(|other| {
+                let mut d = [site[0]-other[0], site[1]-other[1], site[2]-other[2]];
+                for k in 0..3 {
+                    d[k] -= d[k].round();
+                }
+                let d = cell.to_cartesian(&d);
+                d[0]*d[0]+d[1]*d[1]+d[2]*d[2] < SITE_TOLERANCE*SITE_TOLERANCE
+            });
+            if taken {continue}
+            sites.push(site);
+            let shift = cell.to_cartesian(&site);
+            let mut copy = atom.clone();
+            copy.set_position(&[origin[0]+shift[0], origin[1]+shift[1], origin[2]+shift[2]]);
+            if let Some(u) = *atom.adp() {
+                let r = operator.cartesian_rotation(&cell);
+                let mut turned = [[0.0f32;3];3];
+                for i in 0..3 {
+                    for j in 0..3 {
+                        for k in 0..3 {
+                            for m in 0..3 {
+                                turned[i][j] += r[i][k]*u[k][m]*r[j][m];
+                            }
+                        }
+                    }
+                }
+                copy.set_adp(&turned);
+            }
+            filled.atoms_mut().push(copy);
+        }
+    }
+    filled.set_cell(&cell);
+    Some(filled)
+}
@@ -0,0 +1,316 @@
+use glium::{self, Surface};
+use glium::backend::Facade;
+use glium::backend::Context;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+
+use font8x8::legacy::BASIC_LEGACY;
+
+use std::rc::Rc;
+
+use matrix::Matrix;
+use stats;
+
+// ============================================================
+// Text
+// ============================================================
+/// Draws ASCII text and plain rectangles over the scene, in pixels from the
+/// top-left of the window, using the public domain 8x8 bitmap font from font8x8.
+///
+/// Every glyph sits side by side in one 1024x8 texture. Character 0 (which the
+/// font leaves blank) is made solid, so rectangles are drawn as stretched glyphs.
+///
+/// Text can also be drawn in the scene as labels (see draw_labels()).
+pub struct TextSystem {
+    context: Rc<Context>,
+    program: glium::Program,
+    label_program: glium::Program,
+    font: glium::texture::Texture2d,
+}
+
+#[derive(Copy, Clone)]
+struct GlyphVertex {
+    position: [f32; 2],
+    i_tex_coords: [f32; 2],
+}
+
+implement_vertex!(GlyphVertex, position, i_tex_coords);
+
+/// A corner of a label's glyph: the point in the scene the label is attached to,
+/// and where the corner is from there in (unscaled) pixels, down being positive y.
+#[derive(Copy, Clone)]
+struct LabelVertex {
+    anchor: [f32; 3],
+    offset: [f32; 2],
+    i_tex_coords: [f32; 2],
+}
+
+implement_vertex!(LabelVertex, anchor, offset, i_tex_coords);
+
+/// The size of a glyph, and the distance between lines, in (unscaled) pixels.
+const GLYPH_SIZE : f32 = 8.0;
+const LINE_HEIGHT : f32 = 10.0;
+
+impl TextSystem {
+    pub fn new<F: ?Sized>(facade: &F) -> TextSystem where F: Facade + Clone {
+        // white everywhere, with the font's bits as alpha. Bit 0 is the leftmost
+        // pixel of a row, and textures start from the bottom row.
+        let mut pixels : Vec<u8> = Vec::with_capacity(4*128*8*8);
+        for y in 0..8 {
+            for glyph in 0..128 {
+                let row = if glyph == 0 {0xff} else {BASIC_LEGACY[glyph][7-y]};
+                for x in 0..8 {
+                    let alpha = if row & (1 << x) != 0 {255} else {0};
+                    pixels.extend_from_slice(&[255, 255, 255, alpha]);
+                }
+            }
+        }
+        let image = glium::texture::RawImage2d::from_raw_rgba(pixels, (128*8, 8));
+
+        TextSystem {
+            context: facade.get_context().clone(),
+
+            program: glium::Program::from_source(facade,
+                r"
+                    #version 140
+
+                    uniform vec2 screen_size;
+
+                    in vec2 position;
+                    in vec2 i_tex_coords;
+
+                    out vec2 v_tex_coords;
+
+                    void main() {
+                        // pixels from the top-left to OpenGL's -1..1 from the bottom-left
+                        vec2 ndc = 2.0*position/screen_size - 1.0;
+                        gl_Position = vec4(ndc.x, -ndc.y, 0.0, 1.0);
+                        v_tex_coords = i_tex_coords;
+                    }
+                ",
+                r"
+                    #version 140
+
+                    uniform sampler2D font;
+                    uniform vec4 colour;
+
+                    in vec2 v_tex_coords;
+
+                    out vec4 color;
+
+                    void main() {
+                        color = vec4(colour.rgb, colour.a*texture(font, v_tex_coords).a);
+                    }
+                ",
+                None
+            ).unwrap(),
+
+            label_program: glium::Program::from_source(facade,
+                r"
+                    #version 140
+
+                    uniform mat4 vp_matrix;
+                    // the screen's right and up directions in the scene, scaled to
+                    // the size of a pixel of a glyph
+                    uniform vec3 right;
+                    uniform vec3 up;
+
+                    in vec3 anchor;
+                    in vec2 offset;
+                    in vec2 i_tex_coords;
+
+                    out vec2 v_tex_coords;
+
+                    void main() {
+                        vec3 position = anchor + offset.x*right - offset.y*up;
+                        gl_Position = vec4(position, 1.0)*vp_matrix;
+                        v_tex_coords = i_tex_coords;
+                    }
+                ",
+                r"
+                    #version 140
+
+                    uniform sampler2D font;
+                    uniform vec4 colour;
+
+                    in vec2 v_tex_coords;
+
+                    out vec4 color;
+
+                    void main() {
+                        // the gaps between strokes mustn't hide what's behind them
+                        if (texture(font, v_tex_coords).a < 0.5) {
+                            discard;
+                        }
+                        color = colour;
+                    }
+                ",
+                None
+            ).unwrap(),
+
+            font: glium::texture::Texture2d::new(facade, image).unwrap(),
+        }
+    }
+
+    /// How much GPU memory the font takes.
+    pub fn bytes(&self) -> usize {stats::texture_bytes(&self.font, &4)}
+}
+
+/// The width and height in pixels of some (possibly multi-line) text.
+pub fn text_size(text: &str, scale: f32) -> [f32; 2] {
+    let lines = text.lines().count().max(1);
+    let columns = text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+    [
+        columns as f32*GLYPH_SIZE*scale,
+        ((lines-1) as f32*LINE_HEIGHT + GLYPH_SIZE)*scale,
+    ]
+}
+
+/// Pushes the two triangles covering a glyph's cell onto `vertices`.
+fn push_glyph(vertices: &mut Vec<GlyphVertex>, glyph: usize, position: [f32; 2], size: [f32; 2]) {
+    let u0 = glyph as f32/128.0;
+    let u1 = (glyph+1) as f32/128.0;
+    let (x0, y0) = (position[0], position[1]);
+    let (x1, y1) = (position[0]+size[0], position[1]+size[1]);
+    let corners = [
+        ([x0, y0], [u0, 1.0]), ([x0, y1], [u0, 0.0]), ([x1, y1], [u1, 0.0]),
+        ([x0, y0], [u0, 1.0]), ([x1, y1], [u1, 0.0]), ([x1, y0], [u1, 1.0]),
+    ];
+    for &(position, tex_coords) in &corners {
+        vertices.push(GlyphVertex { position: position, i_tex_coords: tex_coords });
+    }
+}
+
+fn draw_vertices<T>(system: &TextSystem, target: &mut T, vertices: &[GlyphVertex], colour: [f32; 4])
+                    where T: Surface
+{
+    if vertices.is_empty() { return; }
+    let (w, h) = target.get_dimensions();
+    let vertex_buffer = glium::VertexBuffer::new(&system.context, vertices).unwrap();
+    let uniforms = uniform! {
+        screen_size: [w as f32, h as f32],
+        colour: colour,
+        font: system.font.sampled()
+            .magnify_filter(MagnifySamplerFilter::Nearest)
+            .minify_filter(MinifySamplerFilter::Nearest),
+    };
+    let params = glium::DrawParameters {
+        blend: glium::Blend::alpha_blending(),
+        .. Default::default()
+    };
+    target.draw(&vertex_buffer, &glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &system.program, &uniforms, &params).unwrap();
+}
+
+/// Draws text with its top-left corner at `position`. Glyphs are 8 pixels square
+/// times `scale`. Characters outside ASCII are drawn as '?'.
+pub fn draw_text<T>(system: &TextSystem, target: &mut T, text: &str, position: [f32; 2],
+                    scale: f32, colour: [f32; 4]) where T: Surface
+{
+    let mut vertices = Vec::new();
+    for (row, line) in text.lines().enumerate() {
+        for (column, character) in line.chars().enumerate() {
+            let glyph = if (character as u32) < 128 {character as usize} else {'?' as usize};
+            if glyph == ' ' as usize { continue; }
+            push_glyph(&mut vertices, glyph, [
+                position[0] + column as f32*GLYPH_SIZE*scale,
+                position[1] + row as f32*LINE_HEIGHT*scale,
+            ], [GLYPH_SIZE*scale, GLYPH_SIZE*scale]);
+        }
+    }
+    draw_vertices(system, target, &vertices, colour);
+}
+
+/// Draws a filled rectangle, e.g. as a background behind text.
+pub fn draw_panel<T>(system: &TextSystem, target: &mut T, position: [f32; 2], size: [f32; 2],
+                     colour: [f32; 4]) where T: Surface
+{
+    let mut vertices = Vec::new();
+    push_glyph(&mut vertices, 0, position, size);
+    draw_vertices(system, target, &vertices, colour);
+}
+
+/// Draws each (from, to) pair as a line `width` pixels wide, e.g. for a diagram.
+pub fn draw_lines<T>(system: &TextSystem, target: &mut T, lines: &[([f32; 2], [f32; 2])], width: f32,
+                     colour: [f32; 4]) where T: Surface
+{
+    let mut vertices = Vec::new();
+    for &(from, to) in lines {
+        let length = ((to[0]-from[0]).powi(2) + (to[1]-from[1]).powi(2)).sqrt();
+        if length <= 0.0 { continue; }
+        let across = [-(to[1]-from[1])/length*width/2.0, (to[0]-from[0])/length*width/2.0];
+        // a rectangle along the line, as the stretched solid glyph 0
+        let u1 = 1.0/128.0;
+        let corners = [
+            ([from[0]+across[0], from[1]+across[1]], [0.0, 1.0]),
+            ([from[0]-across[0], from[1]-across[1]], [0.0, 0.0]),
+            ([to[0]-across[0], to[1]-across[1]], [u1, 0.0]),
+            ([from[0]+across[0], from[1]+across[1]], [0.0, 1.0]),
+            ([to[0]-across[0], to[1]-across[1]], [u1, 0.0]),
+            ([to[0]+across[0], to[1]+across[1]], [u1, 1.0]),
+        ];
+        for &(position, tex_coords) in &corners {
+            vertices.push(GlyphVertex { position: position, i_tex_coords: tex_coords });
+        }
+    }
+    draw_vertices(system, target, &vertices, colour);
+}
+
+/// Draws each (point, text) pair as a label in the scene: a single line of text
+/// centred on the point and facing the camera, `height` (in the scene's length units)
+/// tall, so it shrinks with distance and is hidden by whatever is in front of it.
+/// The target needs a depth buffer, holding the depth of the scene drawn so far.
+pub fn draw_labels<T>(system: &TextSystem, target: &mut T, labels: &[([f32; 3], String)],
+                      view_matrix: &Matrix, vp_matrix: &Matrix, height: f32, colour: [f32; 4])
+                      where T: Surface
+{
+    let mut vertices = Vec::new();
+    for &(anchor, ref text) in labels {
+        let width = text.chars().count() as f32*GLYPH_SIZE;
+        for (column, character) in text.chars().enumerate() {
+            let glyph = if (character as u32) < 128 {character as usize} else {'?' as usize};
+            if glyph == ' ' as usize { continue; }
+            let u0 = glyph as f32/128.0;
+            let u1 = (glyph+1) as f32/128.0;
+            let x0 = column as f32*GLYPH_SIZE - width/2.0;
+            let x1 = x0 + GLYPH_SIZE;
+            let (y0, y1) = (-GLYPH_SIZE/2.0, GLYPH_SIZE/2.0);
+            let corners = [
+                ([x0, y0], [u0, 1.0]), ([x0, y1], [u0, 0.0]), ([x1, y1], [u1, 0.0]),
+                ([x0, y0], [u0, 1.0]), ([x1, y1], [u1, 0.0]), ([x1, y0], [u1, 1.0]),
+            ];
+            for &(offset, tex_coords) in &corners {
+                vertices.push(LabelVertex { anchor: anchor, offset: offset, i_tex_coords: tex_coords });
+            }
+        }
+    }
+    if vertices.is_empty() { return; }
+
+    // the first two rows of the view matrix are the screen's right and up directions
+    // (times the zoom, which is taken out)
+    let view = view_matrix.contents();
+    let direction = |row: [f32; 4]| {
+        let length = (row[0]*row[0] + row[1]*row[1] + row[2]*row[2]).sqrt();
+        let scale = height/GLYPH_SIZE/length;
+        [row[0]*scale, row[1]*scale, row[2]*scale]
+    };
+    let vertex_buffer = glium::VertexBuffer::new(&system.context, &vertices).unwrap();
+    let uniforms = uniform! {
+        vp_matrix: vp_matrix.contents().to_owned(),
+        right: direction(view[0]),
+        up: direction(view[1]),
+        colour: colour,
+        font: system.font.sampled()
+            .magnify_filter(MagnifySamplerFilter::Nearest)
+            .minify_filter(MinifySamplerFilter::Nearest),
+    };
+    let params = glium::DrawParameters {
+        depth: glium::Depth {
+            test: glium::draw_parameters::DepthTest::IfLessOrEqual,
+            write: true,
+            .. Default::default()
+        },
+        .. Default::default()
+    };
+    target.draw(&vertex_buffer, &glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &system.label_program, &uniforms, &params).unwrap();
+}
@@ -0,0 +1,56 @@
+// ============================================================
+// Tool
+// ============================================================
+/// What clicking atoms does: select them, measure between them, or edit the molecule.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Tool {
+    Select,
+    Distance,
+    Angle,
+    Dihedral,
+    /// Clicking an atom deletes it.
+    Delete,
+    /// Clicking puts a new atom on the plane through the focus facing the camera.
+    Add,
+    /// Dragging with the rotate button moves the selected atoms across the screen.
+    Drag,
+}
+
+impl Tool {
+    pub fn next(&self) -> Tool {
+        match *self {
+            Tool::Select   => Tool::Distance,
+            Tool::Distance => Tool::Angle,
+            Tool::Angle    => Tool::Dihedral,
+            Tool::Dihedral => Tool::Delete,
+            Tool::Delete   => Tool::Add,
+            Tool::Add      => Tool::Drag,
+            Tool::Drag     => Tool::Select,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Tool::Select   => "select",
+            Tool::Distance => "measure distances",
+            Tool::Angle    => "measure angles",
+            Tool::Dihedral => "measure dihedrals",
+            Tool::Delete   => "delete atoms",
+            Tool::Add      => "add atoms",
+            Tool::Drag     => "drag the selection",
+        }
+    }
+
+    /// How many atoms make a measurement; 0 for the tools which don't measure.
+    pub fn atoms_needed(&self) -> usize {
+        match *self {
+            Tool::Distance => 2,
+            Tool::Angle    => 3,
+            Tool::Dihedral => 4,
+            _              => 0,
+        }
+    }
+}
+
+/// The elements the add tool cycles through.
+pub const ELEMENTS : [&'static str; 6] = ["C", "N", "O", "H", "S", "Ni"];
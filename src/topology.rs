@@ -0,0 +1,87 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+use bonds;
+use molecule::Molecule;
+
+// ============================================================
+// Topology
+// ============================================================
+/// Writes the bond graph, for graph analyses or 2D depictions in other tools: each
+/// atom a node, with its element (and charge, if it has one), and each bond an
+/// edge, with its estimated order (see bonds::estimated_orders()) and length. As
+/// GraphML if the file name ends .graphml, otherwise in Graphviz's DOT, where the
+/// double and triple bonds are drawn as double and triple lines. The nodes are
+/// numbered from 0, as the atoms are.
+pub fn write_graph(in_fname : &str, in_molecule : &Molecule, in_bonds : &Vec<[usize;2]>) -> Result<(), String> {
+    let text = if in_fname.to_lowercase().ends_with(".graphml") {
+        graphml(in_molecule, in_bonds)
+    } else {
+        dot(in_molecule, in_bonds)
+    };
+    let file = File::create(in_fname).map_err(|e| format!("couldn't create {}: {}", in_fname, e))?;
+    let mut file = BufWriter::new(file);
+    file.write_all(text.as_bytes()).and_then(|_| file.flush())
+        .map_err(|e| format!("couldn't write to {}: {}", in_fname, e))
+}
+
+fn graphml(in_molecule : &Molecule, in_bonds : &Vec<[usize;2]>) -> String {
+    let mut text = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n\
+        \x20 <key id=\"element\" for=\"node\" attr.name=\"element\" attr.type=\"string\"/>\n\
+        \x20 <key id=\"charge\" for=\"node\" attr.name=\"charge\" attr.type=\"double\"/>\n\
+        \x20 <key id=\"order\" for=\"edge\" attr.name=\"order\" attr.type=\"double\"/>\n\
+        \x20 <key id=\"length\" for=\"edge\" attr.name=\"length\" attr.type=\"double\"/>\n\
+        \x20 <graph id=\"molecule\" edgedefault=\"undirected\">\n");
+    for (i, atom) in in_molecule.atoms().iter().enumerate() {
+        text.push_str(&format!("    <node id=\"n{}\">\n      <data key=\"element\">{}</data>\n",
+                               i, escaped(atom.species().symbol())));
+        if let Some(charge) = *atom.charge() {
+            text.push_str(&format!("      <data key=\"charge\">{}</data>\n", charge));
+        }
+        text.push_str("    </node>\n");
+    }
+    let orders = bonds::estimated_orders(in_molecule, in_bonds);
+    let lengths = bonds::lengths(in_molecule, in_bonds);
+    for (k, bond) in in_bonds.iter().enumerate() {
+        text.push_str(&format!("    <edge source=\"n{}\" target=\"n{}\">\n      \
+                                <data key=\"order\">{}</data>\n      \
+                                <data key=\"length\">{:.4}</data>\n    </edge>\n",
+                               bond[0], bond[1], orders[k], lengths[k]));
+    }
+    text.push_str("  </graph>\n</graphml>\n");
+    text
+}
+
+fn dot(in_molecule : &Molecule, in_bonds : &Vec<[usize;2]>) -> String {
+    let mut text = String::from("graph molecule {\n  node [shape=circle];\n");
+    for (i, atom) in in_molecule.atoms().iter().enumerate() {
+        let symbol = escaped(atom.species().symbol());
+        match *atom.charge() {
+            Some(charge) => text.push_str(&format!("  {} [label=\"{}\", element=\"{}\", charge={}];\n",
+                                                   i, symbol, symbol, charge)),
+            None => text.push_str(&format!("  {} [label=\"{}\", element=\"{}\"];\n", i, symbol, symbol)),
+        }
+    }
+    let orders = bonds::estimated_orders(in_molecule, in_bonds);
+    let lengths = bonds::lengths(in_molecule, in_bonds);
+    for (k, bond) in in_bonds.iter().enumerate() {
+        // (Graphviz draws an edge of several colours as parallel lines)
+        let colour = match orders[k] as u32 {
+            3 => "black:invis:black:invis:black",
+            2 => "black:invis:black",
+            _ if orders[k] > 1.0 => "black:invis:gray",
+            _ => "black",
+        };
+        text.push_str(&format!("  {} -- {} [order={}, length={:.4}, color=\"{}\"];\n",
+                               bond[0], bond[1], orders[k], lengths[k], colour));
+    }
+    text.push_str("}\n");
+    text
+}
+
+/// Text which can go in an XML element or a quoted DOT string as it is.
+fn escaped(in_text : &str) -> String {
+    in_text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,288 @@
+use cell::Cell;
+use matrix::Matrix;
+use molecule::Molecule;
+use quaternion::Quaternion;
+
+/// How fast a trajectory plays (shift+Period), in frames a second.
+pub const PLAYBACK_FRAMES_PER_SECOND : f32 = 15.0;
+
+// ============================================================
+// Trajectory
+// ============================================================
+/// An atom in one frame: its position, and its force and velocity if the frame
+/// gives them.
+pub type FrameAtom = ([f32;3], Option<[f32;3]>, Option<[f32;3]>);
+
+/// A sequence of frames, each holding a position for every atom of a molecule, and
+/// the forces on them and their velocities for frames whose file gave them (see
+/// arrows.rs), e.g. each step of an optimisation.
+pub struct Trajectory {
+    _frames     : Vec<Vec<[f32;3]>>,
+    _forces     : Vec<Option<Vec<[f32;3]>>>,
+    _velocities : Vec<Option<Vec<[f32;3]>>>,
+}
+
+impl Trajectory {
+    pub fn new() -> Trajectory {
+        Trajectory {
+            _frames     : Vec::new(),
+            _forces     : Vec::new(),
+            _velocities : Vec::new(),
+        }
+    }
+
+    /// Adds a frame. Every frame must have the same number of atoms as the first.
+    pub fn add_frame(&mut self, in_positions : &Vec<[f32;3]>) -> Result<(), String> {
+        self.add_frame_with_vectors(in_positions, &None, &None)
+    }
+
+    /// Adds a frame, with a force and a velocity for every atom where it has them.
+    pub fn add_frame_with_vectors(&mut self, in_positions : &Vec<[f32;3]>, in_forces : &Option<Vec<[f32;3]>>,
+                                  in_velocities : &Option<Vec<[f32;3]>>) -> Result<(), String> {
+        if let Some(first) = self._frames.first() {
+            if first.len() != in_positions.len() {
+                return Err(format!(
+                    "frame {} has {} atoms, but the first frame has {}",
+                    self._frames.len(),
+                    in_positions.len(),
+                    first.len()
+                ));
+            }
+        }
+        for vectors in in_forces.iter().chain(in_velocities.iter()) {
+            if vectors.len() != in_positions.len() {
+                return Err(format!(
+                    "frame {} has {} atoms, but {} vectors",
+                    self._frames.len(),
+                    in_positions.len(),
+                    vectors.len()
+                ));
+            }
+        }
+        self._frames.push(in_positions.to_owned());
+        self._forces.push(in_forces.to_owned());
+        self._velocities.push(in_velocities.to_owned());
+        Ok(())
+    }
+
+    pub fn frames(&self) -> &Vec<Vec<[f32;3]>> {&self._frames}
+
+    /// The forces on the atoms in a frame, if it gave them.
+    pub fn forces(&self, in_frame : &usize) -> Option<&Vec<[f32;3]>> {
+        self._forces.get(*in_frame).and_then(|forces| forces.as_ref())
+    }
+
+    /// The atoms' velocities in a frame, if it gave them.
+    pub fn velocities(&self, in_frame : &usize) -> Option<&Vec<[f32;3]>> {
+        self._velocities.get(*in_frame).and_then(|velocities| velocities.as_ref())
+    }
+
+    /// Moves the molecule's atoms to a frame, giving them its forces and velocities
+    /// where it has them (and leaving those they have where it hasn't).
+    pub fn show_frame(&self, in_frame : &usize, in_molecule : &mut Molecule) {
+        in_molecule.set_positions(&self._frames[*in_frame]);
+        if let Some(forces) = self.forces(in_frame) {
+            for (atom, force) in in_molecule.atoms_mut().iter_mut().zip(forces.iter()) {
+                atom.set_force(force);
+            }
+        }
+        if let Some(velocities) = self.velocities(in_frame) {
+            for (atom, velocity) in in_molecule.atoms_mut().iter_mut().zip(velocities.iter()) {
+                atom.set_velocity(velocity);
+            }
+        }
+    }
+
+    /// An atom in every frame, e.g. to put back with insert_atom() once it's removed.
+    pub fn atom(&self, in_atom : &usize) -> Vec<FrameAtom> {
+        (0..self._frames.len()).map(|k| (
+            self._frames[k][*in_atom],
+            self._forces[k].as_ref().map(|forces| forces[*in_atom]),
+            self._velocities[k].as_ref().map(|velocities| velocities[*in_atom]),
+        )).collect()
+    }
+
+    /// Removes an atom from every frame, giving back what it was in each.
+    pub fn remove_atom(&mut self, in_atom : &usize) -> Vec<FrameAtom> {
+        let atom = self.atom(in_atom);
+        for frame in &mut self._frames {
+            frame.remove(*in_atom);
+        }
+        for vectors in self._forces.iter_mut().chain(self._velocities.iter_mut()).filter_map(|v| v.as_mut()) {
+            vectors.remove(*in_atom);
+        }
+        atom
+    }
+
+    /// Puts an atom back in every frame at an index, as it was in each frame.
+    pub fn insert_atom(&mut self, in_index : &usize, in_atom : &Vec<FrameAtom>) {
+        for (k, &(position, force, velocity)) in in_atom.iter().enumerate().take(self._frames.len()) {
+            self._frames[k].insert(*in_index, position);
+            if let Some(ref mut forces) = self._forces[k] {forces.insert(*in_index, force.unwrap_or([0.0;3]))}
+            if let Some(ref mut velocities) = self._velocities[k] {
+                velocities.insert(*in_index, velocity.unwrap_or([0.0;3]));
+            }
+        }
+    }
+
+    /// Takes the last atom out of every frame, e.g. to undo add_atom().
+    pub fn pop_atom(&mut self) {
+        for frame in &mut self._frames {
+            frame.pop();
+        }
+        for vectors in self._forces.iter_mut().chain(self._velocities.iter_mut()).filter_map(|v| v.as_mut()) {
+            vectors.pop();
+        }
+    }
+
+    /// Adds an atom to the end of every frame, at the same position in each, with no
+    /// force or velocity in those that have them.
+    pub fn add_atom(&mut self, in_position : &[f32;3]) {
+        for frame in &mut self._frames {
+            frame.push(in_position.to_owned());
+        }
+        for vectors in self._forces.iter_mut().chain(self._velocities.iter_mut()).filter_map(|v| v.as_mut()) {
+            vectors.push([0.0;3]);
+        }
+    }
+
+    /// Turns the forces and velocities of some atoms in every frame, as their
+    /// positions are turned, e.g. when an object is rotated.
+    pub fn turn_vectors<F>(&mut self, in_atoms : &[usize], in_turn : F) where F : Fn(&[f32;3]) -> [f32;3] {
+        for vectors in self._forces.iter_mut().chain(self._velocities.iter_mut()).filter_map(|v| v.as_mut()) {
+            for &i in in_atoms {
+                vectors[i] = in_turn(&vectors[i]);
+            }
+        }
+    }
+
+    /// Lets the oldest frames go, keeping at most the last `in_count`, and gives how
+    /// many went.
+    pub fn keep_last(&mut self, in_count : &usize) -> usize {
+        let dropped = self._frames.len().saturating_sub(*in_count);
+        self._frames.drain(..dropped);
+        self._forces.drain(..dropped);
+        self._velocities.drain(..dropped);
+        dropped
+    }
+
+    /// Moves an atom in one frame, if there is such a frame.
+    pub fn set_position(&mut self, in_frame : &usize, in_atom : &usize, in_position : &[f32;3]) {
+        if let Some(positions) = self._frames.get_mut(*in_frame) {
+            positions[*in_atom] = in_position.to_owned();
+        }
+    }
+
+    /// Moves every atom in every frame into the cell, by whole lattice vectors.
+    pub fn wrap(&mut self, in_cell : &Cell) {
+        for frame in &mut self._frames {
+            for position in frame.iter_mut() {
+                *position = in_cell.wrap(position);
+            }
+        }
+    }
+
+    /// The average structure, after superposing every frame onto the first.
+    pub fn average_structure(&self) -> Vec<[f32;3]> {
+        average(&self.superposed_frames())
+    }
+
+    /// The root-mean-square fluctuation of each atom about the average structure,
+    /// after superposing every frame onto the first so that rigid-body motion
+    /// of the whole molecule doesn't count.
+    pub fn rmsf(&self) -> Vec<f32> {
+        let frames = self.superposed_frames();
+        let mean = average(&frames);
+        let mut rmsf = vec![0.0f32; mean.len()];
+        for frame in &frames {
+            for (i, position) in frame.iter().enumerate() {
+                for k in 0..3 {
+                    rmsf[i] += (position[k]-mean[i][k]).powi(2);
+                }
+            }
+        }
+        for value in &mut rmsf {
+            *value = (*value/frames.len() as f32).sqrt();
+        }
+        rmsf
+    }
+
+    /// Every frame rotated and translated onto the first, minimising the RMSD.
+    fn superposed_frames(&self) -> Vec<Vec<[f32;3]>> {
+        match self._frames.first() {
+            Some(reference) => self._frames.iter().map(|frame| superpose(frame, reference)).collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+fn centroid(in_positions : &Vec<[f32;3]>) -> [f32;3] {
+    let mut centroid = [0.0f32;3];
+    for position in in_positions {
+        for k in 0..3 {
+            centroid[k] += position[k]/in_positions.len() as f32;
+        }
+    }
+    centroid
+}
+
+fn average(in_frames : &Vec<Vec<[f32;3]>>) -> Vec<[f32;3]> {
+    let mut mean = vec![[0.0f32;3]; in_frames.first().map_or(0, |frame| frame.len())];
+    for frame in in_frames {
+        for (i, position) in frame.iter().enumerate() {
+            for k in 0..3 {
+                mean[i][k] += position[k]/in_frames.len() as f32;
+            }
+        }
+    }
+    mean
+}
+
+/// The best fit (least-squares) rigid superposition of `in_positions` onto
+/// `in_reference`, by Horn's quaternion method (J. Opt. Soc. Am. A 4, 629, 1987).
+pub fn superpose(in_positions : &Vec<[f32;3]>, in_reference : &Vec<[f32;3]>) -> Vec<[f32;3]> {
+    let position_centroid = centroid(in_positions);
+    let reference_centroid = centroid(in_reference);
+
+    // s[a][b] = sum of (centred position)_a * (centred reference)_b
+    let mut s = [[0.0f32;3];3];
+    for (position, reference) in in_positions.iter().zip(in_reference.iter()) {
+        for a in 0..3 {
+            for b in 0..3 {
+                s[a][b] += (position[a]-position_centroid[a])*(reference[b]-reference_centroid[b]);
+            }
+        }
+    }
+    let n = Matrix::new([
+        [s[0][0]+s[1][1]+s[2][2], s[1][2]-s[2][1]        , s[2][0]-s[0][2]         , s[0][1]-s[1][0]         ],
+        [0.0                    , s[0][0]-s[1][1]-s[2][2], s[0][1]+s[1][0]         , s[2][0]+s[0][2]         ],
+        [0.0                    , 0.0                    , -s[0][0]+s[1][1]-s[2][2], s[1][2]+s[2][1]         ],
+        [0.0                    , 0.0                    , 0.0                     , -s[0][0]-s[1][1]+s[2][2]],
+    ]);
+
+    // The optimal rotation is the eigenvector with the largest eigenvalue.
+    let (values, vectors) = n.symmetric_eigen();
+    let mut best = 0;
+    for i in 1..4 {
+        if values[i] > values[best] {best = i}
+    }
+    let v = vectors.contents();
+    let mut quaternion = Quaternion::new(&v[0][best], &v[1][best], &v[2][best], &v[3][best]);
+    quaternion.normalise();
+    let rotation = quaternion.rotation_matrix();
+
+    in_positions.iter().map(|position| {
+        let centred = [
+            position[0]-position_centroid[0],
+            position[1]-position_centroid[1],
+            position[2]-position_centroid[2],
+            1.0
+        ];
+        let rotated = rotation * centred;
+        [
+            rotated[0]+reference_centroid[0],
+            rotated[1]+reference_centroid[1],
+            rotated[2]+reference_centroid[2],
+        ]
+    }).collect()
+}
@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+
+use missing_residues::{self, Gap};
+use molecule::Molecule;
+use trajectory::Trajectory;
+
+// ============================================================
+// Tube Display
+// ============================================================
+/// How the protein backbone is drawn.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum TubeDisplay {
+    Hidden,
+    /// A smooth tube through the alpha carbons, of even thickness.
+    Tube,
+    /// "Putty": the tube gets thicker, and goes from blue through white to red, with
+    /// each residue's B-factor (or its RMSF, in a trajectory), to show which parts
+    /// of the protein are floppy.
+    Putty,
+}
+
+impl TubeDisplay {
+    pub fn next(&self) -> TubeDisplay {
+        match *self {
+            TubeDisplay::Hidden => TubeDisplay::Tube,
+            TubeDisplay::Tube   => TubeDisplay::Putty,
+            TubeDisplay::Putty  => TubeDisplay::Hidden,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            TubeDisplay::Hidden => "hidden",
+            TubeDisplay::Tube   => "tube",
+            TubeDisplay::Putty  => "putty",
+        }
+    }
+
+    /// The display with a name (as name() gives it), e.g. for --backbone.
+    pub fn parse(in_name : &str) -> Result<TubeDisplay, String> {
+        [TubeDisplay::Hidden, TubeDisplay::Tube, TubeDisplay::Putty].iter()
+            .find(|display| display.name() == in_name.to_lowercase()).cloned()
+            .ok_or_else(|| format!("expected hidden, tube or putty, not \"{}\"", in_name))
+    }
+}
+
+/// The tube's radius, in Angstroms.
+pub const TUBE_RADIUS : f32 = 0.3;
+
+/// The putty's radius for the least and most mobile residues.
+pub const PUTTY_RADII : [f32;2] = [0.15, 1.2];
+
+/// How many straight pieces the tube is made of between neighbouring alpha carbons.
+const PIECES_PER_RESIDUE : usize = 8;
+
+// ============================================================
+// Traces
+// ============================================================
+/// The alpha carbons of each chain in order, split wherever the backbone has a gap,
+/// as indices into the molecule's atoms. Pieces with a single residue are left out.
+pub fn traces(in_molecule : &Molecule, in_gaps : &Vec<Gap>) -> Vec<Vec<usize>> {
+    let mut traces = Vec::new();
+    // the trace still being added to in each chain
+    let mut open : Vec<(String, Vec<usize>)> = Vec::new();
+    for (i, atom) in in_molecule.atoms().iter().enumerate() {
+        if !missing_residues::is_alpha_carbon(atom) {continue}
+        let chain = atom.residue().as_ref().unwrap().chain();
+        match open.iter().position(|&(ref name, _)| name == chain) {
+            Some(k) => {
+                let last = *open[k].1.last().unwrap();
+                if in_gaps.iter().any(|gap| *gap.atoms() == [last, i]) {
+                    traces.push(::std::mem::replace(&mut open[k].1, vec![i]));
+                } else {
+                    open[k].1.push(i);
+                }
+            },
+            None => open.push((chain.to_owned(), vec![i])),
+        }
+    }
+    traces.extend(open.into_iter().map(|(_, trace)| trace));
+    traces.retain(|trace| trace.len() > 1);
+    traces
+}
+
+/// Which atoms are in the residues the traces go through (ligands and waters
+/// aren't), so they can be hidden behind the tube.
+pub fn on_traces(in_molecule : &Molecule, in_traces : &Vec<Vec<usize>>) -> Vec<bool> {
+    let atoms = in_molecule.atoms();
+    let residues : HashSet<(String, i32)> = in_traces.iter().flat_map(|trace| trace.iter()).map(|&i| {
+        let residue = atoms[i].residue().as_ref().unwrap();
+        (residue.chain().to_owned(), *residue.number())
+    }).collect();
+    atoms.iter().map(|atom| match *atom.residue() {
+        Some(ref residue) => residues.contains(&(residue.chain().to_owned(), *residue.number())),
+        None => false,
+    }).collect()
+}
+
+/// How mobile each atom is, for the putty: its RMSF over a trajectory, or else its
+/// B-factor. None if neither is known.
+pub fn mobility(in_molecule : &Molecule, in_trajectory : &Trajectory) -> Option<Vec<f32>> {
+    if in_trajectory.frames().len() > 1 {
+        Some(in_trajectory.rmsf())
+    } else {
+        in_molecule.atoms().iter().map(|atom| *atom.b_factor()).collect()
+    }
+}
+
+/// Each atom's value averaged over its residue, then scaled so the residues the
+/// traces go through run from 0 (the smallest) to 1 (the largest).
+pub fn putty_values(in_molecule : &Molecule, in_traces : &Vec<Vec<usize>>, in_values : &Vec<f32>) -> Vec<f32> {
+    let atoms = in_molecule.atoms();
+    let mut averages = in_values.to_owned();
+    // a residue's atoms are next to each other in the file
+    let mut start = 0;
+    while start < atoms.len() {
+        let mut end = start+1;
+        if atoms[start].residue().is_some() {
+            while end < atoms.len() && atoms[end].residue() == atoms[start].residue() {end += 1}
+        }
+        let mean = in_values[start..end].iter().sum::<f32>()/(end-start) as f32;
+        for value in &mut averages[start..end] {*value = mean}
+        start = end;
+    }
+    let on_trace = in_traces.iter().flat_map(|trace| trace.iter()).map(|&i| averages[i]);
+    let min = on_trace.clone().fold(::std::f32::INFINITY, f32::min);
+    let max = on_trace.fold(::std::f32::NEG_INFINITY, f32::max);
+    averages.iter().map(|value| if max > min {((value-min)/(max-min)).max(0.0).min(1.0)} else {0.0}).collect()
+}
+
+/// The tube along a smooth (Catmull-Rom) curve through some points, as straight
+/// pieces (start, end, radius, and how far the piece is from one point's value to
+/// the next). The radius and value are blended between the points'.
+pub fn pieces(in_points : &Vec<[f32;3]>, in_radii : &Vec<f32>, in_values : &Vec<f32>)
+             -> Vec<([f32;3], [f32;3], f32, f32)> {
+    let n = in_points.len();
+    let mut pieces = Vec::with_capacity(n*PIECES_PER_RESIDUE);
+    if n < 2 {return pieces}
+    // the ends of the curve are made by repeating the first and last points
+    let point = |k : isize| in_points[k.max(0).min(n as isize - 1) as usize];
+    for s in 0..n-1 {
+        let (p0, p1, p2, p3) = (point(s as isize-1), point(s as isize), point(s as isize+1), point(s as isize+2));
+        let at = |t : f32| {
+            let mut p = [0.0f32;3];
+            for k in 0..3 {
+                p[k] = 0.5*(2.0*p1[k] + (p2[k]-p0[k])*t + (2.0*p0[k]-5.0*p1[k]+4.0*p2[k]-p3[k])*t*t
+                            + (3.0*p1[k]-p0[k]-3.0*p2[k]+p3[k])*t*t*t);
+            }
+            p
+        };
+        for k in 0..PIECES_PER_RESIDUE {
+            let t0 = k as f32/PIECES_PER_RESIDUE as f32;
+            let t1 = (k+1) as f32/PIECES_PER_RESIDUE as f32;
+            let t = (t0+t1)/2.0;
+            let radius = in_radii[s] + (in_radii[s+1]-in_radii[s])*t;
+            let value = in_values[s] + (in_values[s+1]-in_values[s])*t;
+            let (mut from, mut to) = (at(t0), at(t1));
+            // each piece overlaps the next a little, to close the joins on the outside of bends
+            let d = [to[0]-from[0], to[1]-from[1], to[2]-from[2]];
+            let length = (d[0]*d[0]+d[1]*d[1]+d[2]*d[2]).sqrt().max(1e-6);
+            let overlap = 0.2*radius/length;
+            for j in 0..3 {
+                from[j] -= overlap*d[j];
+                to[j] += overlap*d[j];
+            }
+            pieces.push((from, to, radius, value));
+        }
+    }
+    pieces
+}
@@ -0,0 +1,329 @@
+use atom::Atom;
+use bonds::BondDisplay;
+use colour::ColourScheme;
+use measurement::Measurement;
+use molecule::Molecule;
+use objects::Objects;
+use radii::RadiusSource;
+use selection::Selection;
+use trajectory::{FrameAtom, Trajectory};
+
+// ============================================================
+// Renumbering
+// ============================================================
+/// How an edit renumbered the atoms, so that whatever else refers to atoms by
+/// index (the selection, measurements) can follow.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Renumbering {
+    Unchanged,
+    /// The atom at this index was removed, and those after it moved down one.
+    Removed(usize),
+    /// An atom was put in at this index, and those from there on moved up one.
+    Inserted(usize),
+}
+
+impl Renumbering {
+    pub fn selection(&self, in_selection : &Selection) -> Selection {
+        match *self {
+            Renumbering::Unchanged   => in_selection.to_owned(),
+            Renumbering::Removed(i)  => in_selection.without_atom(&i),
+            Renumbering::Inserted(i) => in_selection.with_atom_inserted(&i),
+        }
+    }
+
+    /// The measurements renumbered; those of a removed atom are dropped.
+    pub fn measurements(&self, in_measurements : &Vec<Measurement>) -> Vec<Measurement> {
+        match *self {
+            Renumbering::Unchanged   => in_measurements.to_owned(),
+            Renumbering::Removed(i)  => in_measurements.iter().filter_map(|m| m.without_atom(&i)).collect(),
+            Renumbering::Inserted(i) => in_measurements.iter().map(|m| m.with_atom_inserted(&i)).collect(),
+        }
+    }
+}
+
+// ============================================================
+// Edit
+// ============================================================
+/// A change to the molecule, holding what's needed both to make it and to take it
+/// back. Each edits the molecule, the trajectory's frames (or, for moves, the frame
+/// shown) and the reference positions bond lengths are compared with, if any, or
+/// how an object's drawn: its colours, bonds and radii (see objects.rs).
+pub enum Edit<'a> {
+    /// A new atom, put on the end.
+    AddAtom(Atom<'a>),
+    /// Deleting the atom at `index`; the rest is what it takes to put it back.
+    DeleteAtom {
+        index     : usize,
+        atom      : Atom<'a>,
+        frames    : Vec<FrameAtom>,
+        reference : Option<[f32;3]>,
+    },
+    MoveAtoms {
+        atoms : Vec<usize>,
+        from  : Vec<[f32;3]>,
+        to    : Vec<[f32;3]>,
+    },
+    SetOpacity {
+        atoms : Vec<usize>,
+        from  : Vec<f32>,
+        to    : f32,
+    },
+    /// Colouring atoms the one colour, e.g. a group with the script's colour().
+    SetColour {
+        atoms : Vec<usize>,
+        from  : Vec<[f32;3]>,
+        to    : [f32;3],
+    },
+    /// Giving an object a colour scheme of its own (or the scene's, None), with the
+    /// colours of the atoms that changed.
+    SetObjectColours {
+        object       : usize,
+        from         : Option<ColourScheme>,
+        to           : Option<ColourScheme>,
+        atoms        : Vec<usize>,
+        colours_from : Vec<[f32;3]>,
+        colours_to   : Vec<[f32;3]>,
+    },
+    SetObjectBonds {
+        object : usize,
+        from   : Option<BondDisplay>,
+        to     : Option<BondDisplay>,
+    },
+    SetObjectRadii {
+        object : usize,
+        from   : Option<RadiusSource>,
+        to     : Option<RadiusSource>,
+    },
+}
+
+impl<'a> Edit<'a> {
+    /// Deleting an atom, as it is now.
+    pub fn delete_atom(in_molecule : &Molecule<'a>, in_trajectory : &Trajectory,
+                       in_reference : &Option<Vec<[f32;3]>>, in_atom : &usize) -> Edit<'a> {
+        Edit::DeleteAtom {
+            index     : *in_atom,
+            atom      : in_molecule.atoms()[*in_atom].to_owned(),
+            frames    : in_trajectory.atom(in_atom),
+            reference : in_reference.as_ref().map(|positions| positions[*in_atom]),
+        }
+    }
+
+    /// Setting some atoms' opacity, from what it is now.
+    pub fn set_opacity(in_molecule : &Molecule, in_atoms : &Vec<usize>, in_opacity : &f32) -> Edit<'a> {
+        Edit::SetOpacity {
+            atoms : in_atoms.to_owned(),
+            from  : in_atoms.iter().map(|&i| *in_molecule.atoms()[i].opacity()).collect(),
+            to    : *in_opacity,
+        }
+    }
+
+    /// Colouring some atoms, from what they are now.
+    pub fn set_colour(in_molecule : &Molecule, in_atoms : &Vec<usize>, in_colour : &[f32;3]) -> Edit<'a> {
+        Edit::SetColour {
+            atoms : in_atoms.to_owned(),
+            from  : in_atoms.iter().map(|&i| *in_molecule.atoms()[i].colour()).collect(),
+            to    : *in_colour,
+        }
+    }
+
+    /// An object's colour scheme, which has already been changed (from `in_from`)
+    /// and its atoms coloured, from what they were (`in_colours`).
+    pub fn set_object_colours(in_objects : &Objects, in_molecule : &Molecule, in_object : &usize,
+                              in_from : &Option<ColourScheme>, in_colours : &Vec<[f32;3]>) -> Edit<'a> {
+        let atoms : Vec<usize> = in_molecule.atoms().iter().zip(in_colours.iter()).enumerate()
+            .filter(|&(_, (atom, colour))| atom.colour() != colour).map(|(i, _)| i).collect();
+        Edit::SetObjectColours {
+            object       : *in_object,
+            from         : *in_from,
+            to           : in_objects.get(in_object).ok().and_then(|object| *object.colour_scheme()),
+            colours_from : atoms.iter().map(|&i| in_colours[i]).collect(),
+            colours_to   : atoms.iter().map(|&i| *in_molecule.atoms()[i].colour()).collect(),
+            atoms        : atoms,
+        }
+    }
+
+    /// e.g. "deleting atom 3".
+    pub fn description(&self) -> String {
+        match *self {
+            Edit::AddAtom(ref atom) => format!("adding a {} atom", atom.species().symbol()),
+            Edit::DeleteAtom {index, ..} => format!("deleting atom {}", index+1),
+            Edit::MoveAtoms {ref atoms, ..} => format!("moving {} atoms", atoms.len()),
+            Edit::SetOpacity {ref atoms, ..} => format!("setting the opacity of {} atoms", atoms.len()),
+            Edit::SetColour {ref atoms, ..} => format!("colouring {} atoms", atoms.len()),
+            Edit::SetObjectColours {object, ..} => format!("colouring object {}", object+1),
+            Edit::SetObjectBonds {object, ..} => format!("setting object {}'s bonds", object+1),
+            Edit::SetObjectRadii {object, ..} => format!("setting object {}'s radii", object+1),
+        }
+    }
+
+    /// Makes the edit. `in_frame` is the trajectory frame shown, if any.
+    fn apply(&self, in_molecule : &mut Molecule<'a>, in_trajectory : &mut Trajectory, in_objects : &mut Objects,
+             in_reference : &mut Option<Vec<[f32;3]>>, in_frame : &Option<usize>) -> Renumbering {
+        match *self {
+            Edit::AddAtom(ref atom) => {
+                let index = in_molecule.atoms().len();
+                in_molecule.insert_atom(&index, atom);
+                in_trajectory.add_atom(atom.position());
+                if let Some(ref mut positions) = *in_reference {positions.push(*atom.position())}
+                Renumbering::Inserted(index)
+            },
+            Edit::DeleteAtom {index, ..} => {
+                in_molecule.remove_atom(&index);
+                in_trajectory.remove_atom(&index);
+                if let Some(ref mut positions) = *in_reference {positions.remove(index);}
+                Renumbering::Removed(index)
+            },
+            Edit::MoveAtoms {ref atoms, ref to, ..} => {
+                move_atoms(in_molecule, in_trajectory, in_frame, atoms, to);
+                Renumbering::Unchanged
+            },
+            Edit::SetOpacity {ref atoms, to, ..} => {
+                for &i in atoms {
+                    in_molecule.atoms_mut()[i].set_opacity(&to);
+                }
+                Renumbering::Unchanged
+            },
+            Edit::SetColour {ref atoms, to, ..} => {
+                for &i in atoms {
+                    in_molecule.atoms_mut()[i].set_colour(&to);
+                }
+                Renumbering::Unchanged
+            },
+            Edit::SetObjectColours {object, to, ref atoms, ref colours_to, ..} => {
+                in_objects.set_colour_scheme(&object, &to).ok();
+                set_colours(in_molecule, atoms, colours_to);
+                Renumbering::Unchanged
+            },
+            Edit::SetObjectBonds {object, to, ..} => {
+                in_objects.set_bond_display(&object, &to).ok();
+                Renumbering::Unchanged
+            },
+            Edit::SetObjectRadii {object, to, ..} => {
+                in_objects.set_radius_source(&object, &to).ok();
+                Renumbering::Unchanged
+            },
+        }
+    }
+
+    /// Takes the edit back.
+    fn revert(&self, in_molecule : &mut Molecule<'a>, in_trajectory : &mut Trajectory, in_objects : &mut Objects,
+              in_reference : &mut Option<Vec<[f32;3]>>, in_frame : &Option<usize>) -> Renumbering {
+        match *self {
+            Edit::AddAtom(_) => {
+                let index = in_molecule.atoms().len()-1;
+                in_molecule.remove_atom(&index);
+                in_trajectory.pop_atom();
+                if let Some(ref mut positions) = *in_reference {positions.pop();}
+                Renumbering::Removed(index)
+            },
+            Edit::DeleteAtom {index, ref atom, ref frames, reference} => {
+                in_molecule.insert_atom(&index, atom);
+                in_trajectory.insert_atom(&index, frames);
+                if let (&mut Some(ref mut positions), Some(position)) = (in_reference, reference) {
+                    positions.insert(index, position);
+                }
+                Renumbering::Inserted(index)
+            },
+            Edit::MoveAtoms {ref atoms, ref from, ..} => {
+                move_atoms(in_molecule, in_trajectory, in_frame, atoms, from);
+                Renumbering::Unchanged
+            },
+            Edit::SetOpacity {ref atoms, ref from, ..} => {
+                for (&i, opacity) in atoms.iter().zip(from.iter()) {
+                    in_molecule.atoms_mut()[i].set_opacity(opacity);
+                }
+                Renumbering::Unchanged
+            },
+            Edit::SetColour {ref atoms, ref from, ..} => {
+                set_colours(in_molecule, atoms, from);
+                Renumbering::Unchanged
+            },
+            Edit::SetObjectColours {object, from, ref atoms, ref colours_from, ..} => {
+                in_objects.set_colour_scheme(&object, &from).ok();
+                set_colours(in_molecule, atoms, colours_from);
+                Renumbering::Unchanged
+            },
+            Edit::SetObjectBonds {object, from, ..} => {
+                in_objects.set_bond_display(&object, &from).ok();
+                Renumbering::Unchanged
+            },
+            Edit::SetObjectRadii {object, from, ..} => {
+                in_objects.set_radius_source(&object, &from).ok();
+                Renumbering::Unchanged
+            },
+        }
+    }
+}
+
+fn set_colours(in_molecule : &mut Molecule, in_atoms : &Vec<usize>, in_colours : &Vec<[f32;3]>) {
+    for (&i, colour) in in_atoms.iter().zip(in_colours.iter()) {
+        in_molecule.atoms_mut()[i].set_colour(colour);
+    }
+}
+
+fn move_atoms(in_molecule : &mut Molecule, in_trajectory : &mut Trajectory, in_frame : &Option<usize>,
+              in_atoms : &Vec<usize>, in_positions : &Vec<[f32;3]>) {
+    for (i, position) in in_atoms.iter().zip(in_positions.iter()) {
+        in_molecule.move_atom(i, position);
+        if let Some(frame) = *in_frame {in_trajectory.set_position(&frame, i, position)}
+    }
+}
+
+// ============================================================
+// History
+// ============================================================
+/// The edits made, so they can be undone, and those undone, so they can be redone.
+pub struct History<'a> {
+    _done   : Vec<Edit<'a>>,
+    _undone : Vec<Edit<'a>>,
+}
+
+impl<'a> History<'a> {
+    pub fn new() -> History<'a> {
+        History {
+            _done   : Vec::new(),
+            _undone : Vec::new(),
+        }
+    }
+
+    /// Makes an edit and remembers it. Whatever was undone can't be redone after.
+    pub fn make(&mut self, in_edit : Edit<'a>, in_molecule : &mut Molecule<'a>, in_trajectory : &mut Trajectory,
+                in_objects : &mut Objects, in_reference : &mut Option<Vec<[f32;3]>>, in_frame : &Option<usize>)
+                -> Renumbering {
+        let renumbering = in_edit.apply(in_molecule, in_trajectory, in_objects, in_reference, in_frame);
+        self.record(in_edit);
+        renumbering
+    }
+
+    /// Remembers an edit which has already been made, e.g. a drag, which moves the
+    /// atoms as it goes.
+    pub fn record(&mut self, in_edit : Edit<'a>) {
+        self._done.push(in_edit);
+        self._undone.clear();
+    }
+
+    /// Takes back the last edit, if there is one, giving what it was and how it
+    /// renumbered the atoms.
+    pub fn undo(&mut self, in_molecule : &mut Molecule<'a>, in_trajectory : &mut Trajectory, in_objects : &mut Objects,
+                in_reference : &mut Option<Vec<[f32;3]>>, in_frame : &Option<usize>)
+                -> Option<(String, Renumbering)> {
+        self._done.pop().map(|edit| {
+            let renumbering = edit.revert(in_molecule, in_trajectory, in_objects, in_reference, in_frame);
+            let description = edit.description();
+            self._undone.push(edit);
+            (description, renumbering)
+        })
+    }
+
+    /// Makes the last edit undone again, if there is one.
+    pub fn redo(&mut self, in_molecule : &mut Molecule<'a>, in_trajectory : &mut Trajectory, in_objects : &mut Objects,
+                in_reference : &mut Option<Vec<[f32;3]>>, in_frame : &Option<usize>)
+                -> Option<(String, Renumbering)> {
+        self._undone.pop().map(|edit| {
+            let renumbering = edit.apply(in_molecule, in_trajectory, in_objects, in_reference, in_frame);
+            let description = edit.description();
+            self._done.push(edit);
+            (description, renumbering)
+        })
+    }
+}
@@ -0,0 +1,92 @@
+// ============================================================
+// Usage
+// ============================================================
+/// What --help prints: every argument the viewer takes, so it can be driven from
+/// scripts. Keep in step with the argument handling in viewer.rs (and summary.rs).
+pub const USAGE : &'static str = "\
+Usage: oxide [options] [structure files...] [figure.scene] [saved.session]
+
+Several structure files are drawn together, e.g. a protein and a ligand docked
+into it, each file's first frame. With none, the config's startup scene (its
+[startup] table) is shown, or an empty window to drop a file on. A scene can be
+written in TOML or JSON too, as figure.scene.toml or figure.scene.json. A session
+saved with ctrl+shift+S loads its files again and carries on where it was left.
+
+Drawing
+  --colours SCHEME            species, element, charge, chain, rainbow or residue
+  --palette FILE              colours for the schemes (see colour.rs)
+  --bonds MODE                hidden, plain or deviation
+  --backbone MODE             hidden, tube or putty
+  --radii SOURCE              the atoms' radii: species, covalent or vdw
+  --atom-scale F              what every atom's radius is scaled by
+  --arrows MODE               hidden, forces, velocities or dipole, as arrows
+  --arrow-scale F             how much longer the arrows are drawn
+  --labels MODE               off, element, number or residue
+  --background R,G,B          the background colour, each from 0 to 1
+  --gradient R,G,B,R,G,B      a background from the top colour to the bottom one
+  --environment DIR           a cubemap to draw behind and reflect (see background.rs)
+  --opacity QUERIES           e.g. \"*=0.3, fragment 2=1\"
+  --select NAME=QUERY         a named selection, as typed at the prompt
+  --hide-chains IDS           e.g. B,C
+  --surface                   start with the molecular surface shown
+  --materials FILE            depth testing and culling (see material.rs)
+  --iso LEVEL                 where a cube file's orbital is drawn
+  --lobe-opacity POS,NEG      how opaque the orbital's lobes are
+  --ellipsoid-probability PC  the chance of an atom being in its ellipsoid
+  --miller \"H K L\"            lattice planes to draw through the cell
+  --supercell AxBxC           repeat a crystal's atoms along each lattice vector
+  --wrap                      move every atom into the cell
+  --fix-cells                 make left-handed cells right-handed
+  --bond-reference FILE       compare bond lengths with this structure
+
+View
+  --size WxH                  the window's size, e.g. 1920x1080
+  --vsync                     wait for the screen between frames
+  --fps N                     draw at most N frames a second
+  --camera-focus X,Y,Z        what the camera looks at
+  --camera-angles T,P,S       theta, phi and psi, in degrees
+  --camera-distance D         how far the camera is from its focus
+  --projection MODE           perspective or orthographic
+  --controls PRESET           the mouse bindings, e.g. pymol
+  --mouse BINDINGS            the mouse bindings, one by one
+  --wheel-sensitivity F       how far each wheel notch moves
+  --eye-separation D          for stereo
+  --focus-shell D             how far around the selection focus mode reaches
+
+Without a window
+  --headless                  draw without showing the window, then quit
+  --software                  draw in software (with OSMesa)
+  --screenshot FILE           save the last frame
+  --samples N                 paths per pixel, for render raytrace (and ctrl+S)
+  --print-size SIZE           e.g. 4x or 3000x2000, for render print (and ctrl+P)
+  --frame-rate FPS            for render movie (and shift+P)
+  render raytrace FILE        path-trace the view
+  render print FILE           save a picture bigger than the window
+  render movie FILE           make a movie
+  export graph FILE           the bond graph, as GraphML or DOT
+  export diagram FILE         a 2D diagram, as SVG
+  export structure FILE       the structure, as XYZ or PDB, with named selections
+  export geometry FILE        the scene's geometry, as OBJ or PLY
+  export povray FILE          the view as a POV-Ray scene
+  export svg FILE             the view as an SVG drawing
+  stats                       print what's drawn and the memory it takes
+
+Other
+  --config FILE               defaults and theme, instead of ~/.config/oxide/config.toml
+  --demo ATOMS                a generated crystal, to measure the frame rate
+  --benchmark ATOMS           a generated box of moving water, timed over a lap, then quit
+  --watch                     read the structure files back in when they change
+  --slideshow DIRECTORY       show each structure file in turn
+  --slideshow-seconds S       how long each is shown
+  --modes FILE                normal modes to animate
+  --vectors FILE              forces, velocities or a dipole to draw as arrows
+  --stream ADDRESS            take frames from a running simulation, on host:port or
+                              ws://host:port/path, keeping the last 2000
+  --optimiser COMMAND         relax the structure with this program (shift+F10)
+  --record FILE               write the session's input to a file
+  --replay FILE               play a session back
+  --script FILE               run a Rhai script: load, select, colour, turn, screenshot...
+  --remote ADDRESS            take the script's commands, a line each, on [host]:port,
+                              a Unix socket or - (standard input)
+  --help                      print this
+";
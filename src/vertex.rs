@@ -14,6 +14,37 @@ impl Vertex {
             _normal   : [in_normal[0],in_normal[1],in_normal[2],0.0]
         }
     }
+
+    pub fn position(&self) -> &[f32;4] {&self._position}
+    pub fn normal(&self) -> &[f32;4] {&self._normal}
 }
 
 implement_vertex!(Vertex, _position, _normal);
+
+// ============================================================
+// Coloured Vertex
+// ============================================================
+/// A vertex with its own colour, for meshes coloured point by point (e.g. the
+/// molecular surface, by potential), drawn with the surface programs.
+#[derive(Copy, Clone)]
+pub struct ColouredVertex {
+    _position : [f32;4],
+    _normal   : [f32;4],
+    _colour   : [f32;3],
+}
+
+impl ColouredVertex {
+    pub fn new(in_position : [f32; 3], in_normal : [f32;3], in_colour : [f32;3]) -> ColouredVertex {
+        ColouredVertex {
+            _position : [in_position[0],in_position[1],in_position[2],1.0],
+            _normal   : [in_normal[0],in_normal[1],in_normal[2],0.0],
+            _colour   : in_colour,
+        }
+    }
+
+    pub fn position(&self) -> &[f32;4] {&self._position}
+    pub fn normal(&self) -> &[f32;4] {&self._normal}
+    pub fn colour(&self) -> &[f32;3] {&self._colour}
+}
+
+implement_vertex!(ColouredVertex, _position, _normal, _colour);
@@ -0,0 +1,75 @@
+use std::f32;
+use std::time::Instant;
+
+use molecule::Molecule;
+
+/// How long one swing of a normal mode takes, in seconds, whatever its frequency.
+const PERIOD_SECONDS : f32 = 1.0;
+
+/// How far the atom which moves most in a mode goes either way, in Angstroms, to
+/// start with.
+pub const DEFAULT_AMPLITUDE : f32 = 0.3;
+
+// ============================================================
+// Mode
+// ============================================================
+/// A normal mode of vibration, from a frequency calculation.
+#[derive(Clone, Debug)]
+pub struct Mode {
+    /// In wavenumbers (cm^-1); imaginary frequencies are given as negative.
+    _frequency     : f32,
+    /// How each atom moves, scaled so the one which moves most moves by 1.
+    _displacements : Vec<[f32;3]>,
+}
+
+impl Mode {
+    pub fn new(in_frequency : &f32, in_displacements : &Vec<[f32;3]>) -> Mode {
+        let largest = in_displacements.iter()
+            .map(|d| (d[0]*d[0]+d[1]*d[1]+d[2]*d[2]).sqrt())
+            .fold(0.0f32, f32::max);
+        let scale = if largest > 0.0 {1.0/largest} else {0.0};
+        Mode {
+            _frequency     : *in_frequency,
+            _displacements : in_displacements.iter().map(|d| [d[0]*scale, d[1]*scale, d[2]*scale]).collect(),
+        }
+    }
+
+    pub fn frequency(&self) -> &f32 {&self._frequency}
+    pub fn displacements(&self) -> &Vec<[f32;3]> {&self._displacements}
+}
+
+// ============================================================
+// Vibration
+// ============================================================
+/// One of the modes being animated: the atoms swing to and fro along it about the
+/// positions they had when it started, which they go back to when it stops.
+pub struct Vibration {
+    _mode        : usize,
+    _equilibrium : Vec<[f32;3]>,
+    _start       : Instant,
+}
+
+impl Vibration {
+    /// Animating the `in_mode`th mode, about where the atoms are now.
+    pub fn new(in_mode : &usize, in_molecule : &Molecule) -> Vibration {
+        Vibration {
+            _mode        : *in_mode,
+            _equilibrium : in_molecule.atoms().iter().map(|atom| *atom.position()).collect(),
+            _start       : Instant::now(),
+        }
+    }
+
+    pub fn mode(&self) -> &usize {&self._mode}
+    pub fn equilibrium(&self) -> &Vec<[f32;3]> {&self._equilibrium}
+
+    /// Where the atoms are now, with the atom which moves most going at most
+    /// `in_amplitude` (in Angstroms) from equilibrium.
+    pub fn positions(&self, in_modes : &Vec<Mode>, in_amplitude : &f32) -> Vec<[f32;3]> {
+        let elapsed = self._start.elapsed();
+        let seconds = elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32*1e-9;
+        let swing = in_amplitude*(2.0*f32::consts::PI*seconds/PERIOD_SECONDS).sin();
+        self._equilibrium.iter().zip(in_modes[self._mode].displacements().iter()).map(|(p, d)| {
+            [p[0]+swing*d[0], p[1]+swing*d[1], p[2]+swing*d[2]]
+        }).collect()
+    }
+}
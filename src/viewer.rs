@@ -0,0 +1,4349 @@
+use {
+    glium::{self, DisplayBuild, Surface},
+    molecule::Molecule,
+    camera::{Camera, Projection},
+    camera::animation::{CameraPath, Interpolation, Keyframe},
+    depth_cue::DepthCue,
+    shading::Shading,
+    mouse::{Mouse, MouseAction, MouseBindings, WheelMode},
+    keys::{KeyAction, KeyBindings},
+    selection::{Selection, SelectionStatistics},
+    stereo::StereoMode,
+    bonds::{BondDisplay, PeriodicBonds},
+    radii::{AtomRadii, RadiusSource},
+    arrows::ArrowDisplay,
+    label::LabelContent,
+    measurement::Measurement,
+    tool::Tool,
+    tube::TubeDisplay,
+    undo::{Edit, History, Renumbering},
+    fade::Fade,
+    matrix::Matrix,
+    scene_view::SceneView,
+    script::Command,
+    std::cell::RefCell,
+    std::mem,
+    std::sync::atomic::{AtomicBool, Ordering},
+    std::sync::mpsc::Sender,
+    std::thread,
+    std::time::{Duration, Instant},
+};
+use {
+    arrows, assembly, atom, axes, background, batch, binding_site, bonds, cell, colour, config,
+    demo, diagram, ellipsoid, file_input, file_output, focus, frame_clock, fxaa, geometry, heat_map,
+    help, hud, id_buffer, material, measurement, miller, missing_residues, model, molecule, mouse,
+    movie, notification, objects, oit, optimize, packing, picking, povray, program, progress,
+    raytrace, recovery, refine, remote, replay, scene, scene_graph, scene_view, screenshot, script,
+    selection, sequence, session, slideshow, species, stats, stereo, stream, surface, svg, text, tool,
+    topology, trajectory, tube, usage, vibration, volume, watch,
+};
+
+// ============================================================
+// Viewer
+// ============================================================
+/// The viewer, as the oxide program runs it, for running from another program: a
+/// window showing the structure files given, or a molecule the program made, with
+/// the controls in help.rs. What's shown, and how, is set with the arguments the
+/// program takes (see usage.rs), and can be changed while it runs with a script
+/// (--script) or by sending it commands (--remote). A program with a window (or
+/// any surface) of its own draws the scene into it with SceneView instead.
+///
+/// A molecule is made of species::DefaultSpecies::new()'s species, say:
+///
+/// let species = DefaultSpecies::new();
+/// let mut molecule = Molecule::new();
+/// molecule.add_atom(species.oxygen(), &[0.0, 0.0, 0.0]);
+/// Viewer::with_molecule(molecule, &["--bonds".to_owned(), "plain".to_owned()]).run();
+pub struct Viewer<'a> {
+    _args       : Vec<String>,
+    /// Shown in place of structure files, with its trajectory (if any).
+    _molecule   : Option<Molecule<'a>>,
+    _trajectory : Option<trajectory::Trajectory>,
+    /// Where the camera starts, in place of looking at the whole molecule.
+    _camera     : Option<Camera>,
+}
+
+impl<'a> Viewer<'a> {
+    /// A viewer given these arguments, as on the command line, without the
+    /// program's name, e.g. ["protein.pdb", "--backbone", "tube"].
+    pub fn new(in_args : &[String]) -> Viewer<'a> {
+        Viewer {
+            _args       : in_args.to_vec(),
+            _molecule   : None,
+            _trajectory : None,
+            _camera     : None,
+        }
+    }
+
+    /// A viewer showing a molecule, drawn as the arguments say. Structure files
+    /// among them are ignored.
+    pub fn with_molecule(in_molecule : Molecule<'a>, in_args : &[String]) -> Viewer<'a> {
+        let mut viewer = Viewer::new(in_args);
+        viewer._molecule = Some(in_molecule);
+        viewer
+    }
+
+    /// A viewer showing a molecule and a trajectory of it, each frame a position
+    /// for every atom, as with_molecule() does.
+    pub fn with_trajectory(in_molecule : Molecule<'a>, in_trajectory : trajectory::Trajectory, in_args : &[String])
+                           -> Viewer<'a> {
+        let mut viewer = Viewer::with_molecule(in_molecule, in_args);
+        viewer._trajectory = Some(in_trajectory);
+        viewer
+    }
+
+    /// Starts the camera here (its screen size is set to the window's), rather than
+    /// looking at the whole molecule.
+    pub fn set_camera(&mut self, in_camera : &Camera) {self._camera = Some(in_camera.to_owned())}
+
+    /// Opens the window and runs until it's closed.
+    pub fn run(self) {
+        if self._args.iter().any(|arg| arg == "--help" || arg == "-h") {
+            print!("{}", usage::USAGE);
+            return;
+        }
+        let config = read_config(&self._args);
+        // (made before the window, as the atoms of them outlive its GL context)
+        let default_species = make_species(&config);
+        // after the GL context is lost, the viewer opens a new window and makes what
+        // it draws with again, carrying on with the session as it was (see recovery.rs)
+        let given_trajectory = self._trajectory;
+        let mut given = self._molecule.map(|molecule| {
+            (molecule, given_trajectory.unwrap_or_else(trajectory::Trajectory::new))
+        });
+        let mut camera = self._camera;
+        let mut recovery = None;
+        while let Some(lost) = run(&self._args, &config, &default_species, given.take(), camera.take(), recovery) {
+            if lost.recoveries > recovery::MAX_RECOVERIES {
+                println!("The GL context was lost {} times, so giving up", lost.recoveries);
+                return;
+            }
+            println!("The GL context was lost (the driver reset, or the machine slept), so starting again");
+            recovery = Some(lost);
+        }
+    }
+}
+
+/// The user's defaults and theme (see config.rs), which the arguments override:
+/// the file --config names, or else the one in the usual place.
+fn read_config(in_args : &[String]) -> config::Config {
+    let config_fname = in_args.iter().position(|arg| arg == "--config").and_then(|k| in_args.get(k+1)).cloned()
+        .or_else(|| config::Config::default_path().map(|path| path.to_string_lossy().into_owned()));
+    match config_fname.map(|fname| config::Config::read(&fname)) {
+        Some(Ok(config)) => config,
+        Some(Err(message)) => {
+            println!("Ignoring the config: {}", message);
+            config::Config::default()
+        },
+        None => config::Config::default(),
+    }
+}
+
+/// The species the atoms are made of, styled as the config says.
+fn make_species(in_config : &config::Config) -> species::DefaultSpecies {
+    let mut default_species = species::DefaultSpecies::new();
+    for (symbol, style) in in_config.elements() {
+        if let Err(message) = default_species.restyle(symbol, style.radius(), style.colour()) {
+            println!("Ignoring the config's [elements.{}]: {}", symbol, message);
+        }
+    }
+    default_species
+}
+
+/// Reads a file in another thread with `in_read`, which gives up once the flag it's
+/// given is set, while the window says what's loading, until it's read (giving what
+/// was read) or Escape or closing the window cancels it (giving None). Without a
+/// window, it's just read. The window being closed or resized meanwhile is put in
+/// `in_deferred`, for the event loop to see to, and once it's been closed nothing
+/// more is read.
+fn load<T, F>(in_display : &glium::backend::glutin_backend::GlutinFacade, in_text_system : &text::TextSystem,
+              in_background : &[f32;3], in_headless : &bool, in_deferred : &RefCell<Vec<glium::glutin::Event>>,
+              in_fname : &str, in_read : F) -> Option<Result<T, String>>
+              where T : Send, F : FnOnce(&AtomicBool) -> Result<T, String> + Send {
+    let closed = |ev : &glium::glutin::Event| match *ev {glium::glutin::Event::Closed => true, _ => false};
+    if in_deferred.borrow().iter().any(&closed) {return None}
+    let cancelled = AtomicBool::new(false);
+    let started = Instant::now();
+    let read = thread::scope(|scope| {
+        let flag = &cancelled;
+        let reading = scope.spawn(move || in_read(flag));
+        while !*in_headless && !reading.is_finished() {
+            let loading = [progress::Task::new(&progress::TaskKind::Loading,
+                                               &format!("Loading {} (Escape to cancel)", in_fname),
+                                               &None, &started, &false)];
+            let mut target = in_display.draw();
+            target.clear_color_and_depth((in_background[0], in_background[1], in_background[2], 1.0), 1.0);
+            let (_, h) = target.get_dimensions();
+            progress::draw(in_text_system, &mut target, &(h as f32/2.0 - 30.0), &loading);
+            target.finish().ok();
+            if let Some(window) = in_display.get_window() {window.set_title(&progress::window_title(&loading))}
+            for ev in in_display.poll_events() {
+                match ev {
+                    glium::glutin::Event::KeyboardInput(
+                        glium::glutin::ElementState::Pressed, _, Some(glium::glutin::VirtualKeyCode::Escape)
+                    ) => cancelled.store(true, Ordering::Relaxed),
+                    glium::glutin::Event::Closed | glium::glutin::Event::Resized(_, _) => {
+                        if closed(&ev) {cancelled.store(true, Ordering::Relaxed)}
+                        in_deferred.borrow_mut().push(ev);
+                    },
+                    _ => {},
+                }
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        reading.join().unwrap_or_else(|_| Err(format!("reading {} failed", in_fname)))
+    });
+    if cancelled.load(Ordering::Relaxed) {None} else {Some(read)}
+}
+
+/// Runs the viewer until it's closed (giving None) or loses its GL context (giving
+/// what's needed to carry on in a new one). A molecule (and its trajectory) given
+/// is shown in place of the structure files.
+fn run<'a>(in_args : &[String], in_config : &config::Config, in_species : &'a species::DefaultSpecies,
+           in_molecule : Option<(Molecule<'a>, trajectory::Trajectory)>, in_camera : Option<Camera>,
+           in_recovery : Option<recovery::Recovery<'a>>) -> Option<recovery::Recovery<'a>> {
+    // ==============================
+    // Read command-line arguments
+    // ==============================
+    let args = in_args;
+    let config = in_config;
+    let default_species = in_species;
+    // what was kept when the last GL context was lost, carried on with in place of
+    // the files, session and recording the arguments start with
+    let mut recovery = in_recovery;
+    let recovering = recovery.is_some();
+    let mut fname : Option<String> = None;
+    // more structure files, drawn with the first, e.g. a ligand docked into a protein
+    let mut more_fnames : Vec<String> = Vec::new();
+    let mut mouse_bindings = MouseBindings::default();
+    if let Some(ref preset) = *config.controls() {
+        match MouseBindings::preset(preset) {
+            Some(bindings) => mouse_bindings = bindings,
+            None => println!("Ignoring the config's controls: unknown preset \"{}\" (try {})",
+                             preset, mouse::PRESETS.join(", ")),
+        }
+    }
+    if let Some(ref spec) = *config.mouse() {
+        match MouseBindings::parse(spec) {
+            Ok(bindings) => mouse_bindings = bindings,
+            Err(message) => println!("Ignoring the config's mouse: {}", message),
+        }
+    }
+    // the radii the atoms are drawn with, all scaled the same (see radii.rs)
+    let mut atom_radii = AtomRadii::new(&RadiusSource::Species, &config.atom_scale().unwrap_or(1.0));
+    if let Some(ref name) = *config.radii() {
+        match RadiusSource::parse(name) {
+            Ok(source) => atom_radii.set_source(&source),
+            Err(message) => println!("Ignoring the config's radii: {}", message),
+        }
+    }
+    // which vectors are drawn as arrows, and how much longer than arrows::LONGEST
+    // the longest is drawn
+    let mut arrow_display = ArrowDisplay::Hidden;
+    let mut arrow_scale = 1.0f32;
+    let mut key_bindings = KeyBindings::default();
+    for (name, keys) in config.keys() {
+        if let Err(message) = key_bindings.rebind(name, keys) {
+            println!("Ignoring the config's keys for {}: {}", name, message);
+        }
+    }
+    // the fraction of the distance to the focus (or of the field of view) per wheel notch
+    let mut wheel_sensitivity = config.wheel_sensitivity().unwrap_or(0.1f32);
+    let mut eye_separation : Option<f32> = None;
+    // how far around the selection focus mode leaves atoms undimmed
+    let mut focus_shell = 3.0f32;
+    // e.g. "*=0.3, fragment 2=1": opacities for groups of atoms, applied in order
+    let mut opacities : Option<String> = None;
+    // selection queries to run at the start, as if typed at the prompt
+    let mut queries : Vec<String> = Vec::new();
+    // a structure to compare bond lengths with, instead of covalent radii
+    let mut bond_reference : Option<String> = None;
+    // whether to pick atoms by casting rays on the CPU rather than reading back an ID buffer
+    let mut cpu_picking = false;
+    // whether to correct left-handed cells rather than just warning about them
+    let mut fix_cells = false;
+    // whether to move every atom into the cell (in every frame) as it's loaded
+    let mut wrap_atoms = false;
+    // how many atoms of crystal to generate instead of reading a file, while the
+    // camera loops round it and the frame rate is reported
+    let mut demo_atoms : Option<usize> = None;
+    // how many atoms of water to generate for --benchmark, which plays their
+    // trajectory over one lap of the camera path, reports the frame rate and quits
+    let mut benchmark_atoms : Option<usize> = None;
+    // normal modes from a frequency calculation, to animate
+    let mut modes_fname : Option<String> = None;
+    // forces, velocities or a dipole to draw as arrows, for files that don't carry them
+    let mut vectors_fname : Option<String> = None;
+    // a running simulation to take frames from as it makes them
+    let mut stream_address : Option<String> = None;
+    // a program to relax the structure with on shift+F10 (see optimize.rs)
+    let mut optimiser_command : Option<String> = None;
+    // a session to write the input events to, or to play back instead of the user's
+    // input, and whether to play it back without showing the window and then quit
+    let mut record_fname : Option<String> = None;
+    let mut replay_fname : Option<String> = None;
+    // a script to run (see script.rs)
+    let mut script_fname : Option<String> = None;
+    // where to take commands from other programs (see remote.rs)
+    let mut remote_address : Option<String> = None;
+    let mut headless = false;
+    // whether to draw in software rather than a window, and a file to save the last
+    // frame to when a run without a window quits
+    let mut software = false;
+    let mut screenshot_fname : Option<String> = None;
+    // a file to save a path-traced picture of the last frame to, instead, and how
+    // many paths go through each pixel
+    let mut raytrace_fname : Option<String> = None;
+    let mut raytrace_samples = raytrace::DEFAULT_SAMPLES;
+    // a file to save a print of the last frame to (drawn bigger than the window),
+    // and how big prints are, for that and ctrl+P
+    let mut render_print_fname : Option<String> = None;
+    let mut print_size = screenshot::PrintSize::Scale(4);
+    // where the camera is to start with, instead of looking at the whole molecule
+    // (over a scene's), and how big the window is, e.g. for a figure drawn without one
+    let mut view_focus : Option<[f32;3]> = None;
+    let mut view_angles : Option<[f32;3]> = None;
+    let mut view_distance : Option<f32> = None;
+    let mut view_projection : Option<Projection> = None;
+    let mut window_size : Option<(u32, u32)> = None;
+    // whether to wait for the screen between frames, and the most frames to draw a
+    // second (see frame_clock.rs)
+    let mut vsync = config.vsync().unwrap_or(false);
+    let mut target_fps = *config.fps();
+    // how the bonds, backbone and labels are drawn to start with (over a scene's),
+    // and the background
+    let mut view_bonds : Option<BondDisplay> = None;
+    let mut view_backbone : Option<TubeDisplay> = None;
+    let mut view_labels : Option<LabelContent> = None;
+    let mut background_colour : Option<[f32;3]> = None;
+    // top and bottom colours, and a directory of cubemap faces (see background.rs)
+    let mut background_gradient = *config.gradient();
+    let mut environment_directory = config.environment().to_owned();
+    // a movie to make of the trajectory (or, without one, of the camera going round
+    // the molecule), and its frames per second, for that and shift+P
+    let mut movie_fname : Option<String> = None;
+    let mut frame_rate = movie::DEFAULT_FRAME_RATE;
+    // files to write the bond graph (GraphML or DOT) and a 2D diagram (SVG) to after
+    // the last frame
+    let mut graph_fname : Option<String> = None;
+    let mut diagram_fname : Option<String> = None;
+    // a file to write the structure to (XYZ or PDB, with the named selections as
+    // groups) after the last frame
+    let mut structure_fname : Option<String> = None;
+    // a file to write the scene's geometry to (OBJ or PLY) after the last frame
+    let mut geometry_fname : Option<String> = None;
+    // a POV-Ray scene of the last frame, for ray-traced figures
+    let mut povray_fname : Option<String> = None;
+    // an SVG of the last frame's view, for figures that scale
+    let mut svg_fname : Option<String> = None;
+    // whether to print what's drawn and the memory it takes after the last frame
+    let mut print_stats = false;
+    // how many cells to repeat a crystal's atoms in along each lattice vector
+    let mut supercell : Option<[usize;3]> = None;
+    // a figure described in a file: what to load, how to draw it and where to save it
+    let mut scene : Option<scene::Scene> = None;
+    // a session saved with ctrl+shift+S, to carry on where it was left
+    let mut session : Option<session::Session> = None;
+    // a family of lattice planes to draw through the cell, e.g. (1 1 0)
+    let mut miller : Option<miller::MillerPlanes> = None;
+    // a directory whose structure files are shown in turn, and for how long each
+    let mut slideshow_directory : Option<String> = None;
+    // whether to read the structure files back in when they're written again
+    let mut watch_files = false;
+    let mut slideshow_seconds = slideshow::DEFAULT_SECONDS;
+    // how the atoms are coloured to start with, and a file of colours for the schemes
+    let mut colour_scheme = colour::ColourScheme::Species;
+    let mut palette_fname : Option<String> = None;
+    // whether to start with the molecular surface shown
+    let mut surface_shown = false;
+    // a file of how each representation is tested against the depth buffer and culled
+    let mut materials_fname : Option<String> = None;
+    // where a cube file's orbital is drawn, +iso_level as one lobe and -iso_level as
+    // the other, and how opaque the positive and negative lobes are
+    let mut iso_level = 0.02f32;
+    let mut lobe_opacities = [1.0, 1.0f32];
+    // chains to hide, by their IDs. Shift+H hides the selected atoms' chains, or
+    // with nothing selected shows every chain again.
+    let mut hidden_chains : Vec<String> = Vec::new();
+    // the chance of an atom being inside its thermal ellipsoid
+    let mut ellipsoid_probability = ellipsoid::DEFAULT_PROBABILITY;
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--config" && i+1 < args.len() {
+            // e.g. --config light.toml, read above
+            i += 1;
+        } else if args[i] == "--controls" && i+1 < args.len() {
+            // e.g. --controls pymol
+            i += 1;
+            match MouseBindings::preset(&args[i]) {
+                Some(bindings) => mouse_bindings = bindings,
+                None => println!(
+                    "Ignoring --controls: unknown preset \"{}\" (try {})",
+                    args[i],
+                    mouse::PRESETS.join(", ")
+                ),
+            }
+        } else if args[i] == "--wheel-sensitivity" && i+1 < args.len() {
+            i += 1;
+            match args[i].parse::<f32>() {
+                Ok(sensitivity) => wheel_sensitivity = sensitivity,
+                Err(_) => println!("Ignoring --wheel-sensitivity: \"{}\" isn't a number", args[i]),
+            }
+        } else if args[i] == "--mouse" && i+1 < args.len() {
+            // e.g. --mouse "left=rotate, ctrl+left=zoom"
+            i += 1;
+            match MouseBindings::parse(&args[i]) {
+                Ok(bindings) => mouse_bindings = bindings,
+                Err(message) => println!("Ignoring --mouse: {}", message),
+            }
+        } else if args[i] == "--eye-separation" && i+1 < args.len() {
+            // e.g. --eye-separation 0.05, as a fraction of the distance to the focus
+            i += 1;
+            match args[i].parse::<f32>() {
+                Ok(separation) => eye_separation = Some(separation),
+                Err(_) => println!("Ignoring --eye-separation: \"{}\" isn't a number", args[i]),
+            }
+        } else if args[i] == "--focus-shell" && i+1 < args.len() {
+            // e.g. --focus-shell 5
+            i += 1;
+            match args[i].parse::<f32>() {
+                Ok(shell) => focus_shell = shell,
+                Err(_) => println!("Ignoring --focus-shell: \"{}\" isn't a number", args[i]),
+            }
+        } else if args[i] == "--opacity" && i+1 < args.len() {
+            i += 1;
+            opacities = Some(args[i].to_owned());
+        } else if args[i] == "--select" && i+1 < args.len() {
+            // e.g. --select "ligand = resname LIG", repeatable
+            i += 1;
+            queries.push(args[i].to_owned());
+        } else if args[i] == "--bond-reference" && i+1 < args.len() {
+            // e.g. --bond-reference optimised.xyz, with the same atoms in the same order
+            i += 1;
+            bond_reference = Some(args[i].to_owned());
+        } else if args[i] == "--cpu-picking" {
+            cpu_picking = true;
+        } else if args[i] == "--fix-cells" {
+            fix_cells = true;
+        } else if args[i] == "--wrap" {
+            wrap_atoms = true;
+        } else if args[i] == "--modes" && i+1 < args.len() {
+            // e.g. --modes water.log, with the same atoms in the same order
+            i += 1;
+            modes_fname = Some(args[i].to_owned());
+        } else if args[i] == "--vectors" && i+1 < args.len() {
+            // e.g. --vectors forces.txt, with the same atoms in the same order
+            i += 1;
+            vectors_fname = Some(args[i].to_owned());
+        } else if args[i] == "--stream" && i+1 < args.len() {
+            // e.g. --stream cluster:5000 or ws://cluster:8080/frames, with the same atoms
+            // in the same order
+            i += 1;
+            stream_address = Some(args[i].to_owned());
+        } else if args[i] == "--optimiser" && i+1 < args.len() {
+            // e.g. --optimiser "relax.sh {}", where {} is the structure as xyz and the
+            // geometries come back on standard output (see optimize.rs for xtb's wrapper)
+            i += 1;
+            optimiser_command = Some(args[i].to_owned());
+        } else if args[i] == "--record" && i+1 < args.len() {
+            // e.g. --record session.txt
+            i += 1;
+            record_fname = Some(args[i].to_owned());
+        } else if args[i] == "--replay" && i+1 < args.len() {
+            i += 1;
+            replay_fname = Some(args[i].to_owned());
+        } else if args[i] == "--script" && i+1 < args.len() {
+            // e.g. --script figure.rhai
+            i += 1;
+            script_fname = Some(args[i].to_owned());
+        } else if args[i] == "--remote" && i+1 < args.len() {
+            // e.g. --remote :5001, --remote /tmp/oxide.sock or --remote - (standard input)
+            i += 1;
+            remote_address = Some(args[i].to_owned());
+        } else if args[i] == "--headless" {
+            headless = true;
+        } else if args[i] == "--software" {
+            software = true;
+        } else if args[i] == "--screenshot" && i+1 < args.len() {
+            // e.g. --screenshot frame.ppm
+            i += 1;
+            screenshot_fname = Some(args[i].to_owned());
+        } else if args[i] == "render" && i+2 < args.len() && args[i+1] == "raytrace" {
+            // e.g. render raytrace out.png
+            i += 2;
+            raytrace_fname = Some(args[i].to_owned());
+            headless = true;
+        } else if args[i] == "render" && i+2 < args.len() && args[i+1] == "print" {
+            // e.g. render print figure.png
+            i += 2;
+            render_print_fname = Some(args[i].to_owned());
+            headless = true;
+        } else if args[i] == "render" && i+2 < args.len() && args[i+1] == "movie" {
+            // e.g. render movie spin.mp4, or frames/spin.png for numbered images
+            i += 2;
+            movie_fname = Some(args[i].to_owned());
+            headless = true;
+        } else if args[i] == "--frame-rate" && i+1 < args.len() {
+            // e.g. --frame-rate 24
+            i += 1;
+            match args[i].parse::<f32>() {
+                Ok(rate) if rate > 0.0 => frame_rate = rate,
+                _ => println!("Ignoring --frame-rate: \"{}\" isn't a number of frames per second", args[i]),
+            }
+        } else if args[i] == "--print-size" && i+1 < args.len() {
+            // e.g. --print-size 4x, or --print-size 3000x2000
+            i += 1;
+            match screenshot::PrintSize::parse(&args[i]) {
+                Some(size) => print_size = size,
+                None => println!("Ignoring --print-size: expected e.g. 4x or 3000x2000, not \"{}\"", args[i]),
+            }
+        } else if (args[i] == "--camera-focus" || args[i] == "--camera-angles") && i+1 < args.len() {
+            // e.g. --camera-focus "10.2 4.5 -3.0", or --camera-angles 30,45,0 (theta, phi
+            // and psi, in degrees)
+            i += 1;
+            let numbers : Vec<f32> = args[i].split(|c : char| c == ',' || c.is_whitespace())
+                .filter(|word| !word.is_empty()).filter_map(|word| word.parse().ok()).collect();
+            if numbers.len() != 3 {
+                println!("Ignoring {}: expected 3 numbers, not \"{}\"", args[i-1], args[i]);
+            } else if args[i-1] == "--camera-focus" {
+                view_focus = Some([numbers[0], numbers[1], numbers[2]]);
+            } else {
+                view_angles = Some([numbers[0], numbers[1], numbers[2]]);
+            }
+        } else if args[i] == "--camera-distance" && i+1 < args.len() {
+            // e.g. --camera-distance 40
+            i += 1;
+            match args[i].parse::<f32>() {
+                Ok(distance) if distance > 0.0 => view_distance = Some(distance),
+                _ => println!("Ignoring --camera-distance: \"{}\" isn't a distance", args[i]),
+            }
+        } else if args[i] == "--projection" && i+1 < args.len() {
+            // e.g. --projection orthographic
+            i += 1;
+            match args[i].as_str() {
+                "perspective"  => view_projection = Some(Projection::Perspective),
+                "orthographic" => view_projection = Some(Projection::Orthographic),
+                _ => println!("Ignoring --projection: expected perspective or orthographic, not \"{}\"", args[i]),
+            }
+        } else if args[i] == "--size" && i+1 < args.len() {
+            // e.g. --size 1920x1080
+            i += 1;
+            let pixels : Vec<u32> = args[i].split('x').filter_map(|s| s.trim().parse().ok()).collect();
+            match pixels.len() {
+                2 if pixels[0] > 0 && pixels[1] > 0 => window_size = Some((pixels[0], pixels[1])),
+                _ => println!("Ignoring --size: expected e.g. 1920x1080, not \"{}\"", args[i]),
+            }
+        } else if args[i] == "--vsync" {
+            vsync = true;
+        } else if args[i] == "--fps" && i+1 < args.len() {
+            // e.g. --fps 30, or --fps 0 for as many as can be drawn
+            i += 1;
+            match args[i].parse::<f32>() {
+                Ok(fps) if fps > 0.0 => target_fps = Some(fps),
+                Ok(fps) if fps == 0.0 => target_fps = None,
+                _ => println!("Ignoring --fps: expected a frame rate, not \"{}\"", args[i]),
+            }
+        } else if args[i] == "--bonds" && i+1 < args.len() {
+            // e.g. --bonds deviation
+            i += 1;
+            match args[i].as_str() {
+                "hidden"    => view_bonds = Some(BondDisplay::Hidden),
+                "plain"     => view_bonds = Some(BondDisplay::Plain),
+                "deviation" => view_bonds = Some(BondDisplay::Deviation),
+                _ => println!("Ignoring --bonds: expected hidden, plain or deviation, not \"{}\"", args[i]),
+            }
+        } else if args[i] == "--radii" && i+1 < args.len() {
+            // e.g. --radii vdw
+            i += 1;
+            match RadiusSource::parse(&args[i]) {
+                Ok(source) => atom_radii.set_source(&source),
+                Err(message) => println!("Ignoring --radii: {}", message),
+            }
+        } else if args[i] == "--atom-scale" && i+1 < args.len() {
+            i += 1;
+            match args[i].parse::<f32>() {
+                Ok(scale) if scale > 0.0 => atom_radii.set_scale(&scale),
+                _ => println!("Ignoring --atom-scale: \"{}\" isn't a positive number", args[i]),
+            }
+        } else if args[i] == "--arrows" && i+1 < args.len() {
+            // e.g. --arrows forces
+            i += 1;
+            match ArrowDisplay::parse(&args[i]) {
+                Ok(display) => arrow_display = display,
+                Err(message) => println!("Ignoring --arrows: {}", message),
+            }
+        } else if args[i] == "--arrow-scale" && i+1 < args.len() {
+            i += 1;
+            match args[i].parse::<f32>() {
+                Ok(scale) if scale > 0.0 => arrow_scale = scale.max(arrows::MIN_SCALE).min(arrows::MAX_SCALE),
+                _ => println!("Ignoring --arrow-scale: \"{}\" isn't a positive number", args[i]),
+            }
+        } else if args[i] == "--backbone" && i+1 < args.len() {
+            // e.g. --backbone putty
+            i += 1;
+            match args[i].as_str() {
+                "hidden" => view_backbone = Some(TubeDisplay::Hidden),
+                "tube"   => view_backbone = Some(TubeDisplay::Tube),
+                "putty"  => view_backbone = Some(TubeDisplay::Putty),
+                _ => println!("Ignoring --backbone: expected hidden, tube or putty, not \"{}\"", args[i]),
+            }
+        } else if args[i] == "--labels" && i+1 < args.len() {
+            // e.g. --labels element
+            i += 1;
+            match args[i].as_str() {
+                "off"     => view_labels = Some(LabelContent::Off),
+                "element" => view_labels = Some(LabelContent::Element),
+                "number"  => view_labels = Some(LabelContent::Index),
+                "residue" => view_labels = Some(LabelContent::Residue),
+                _ => println!("Ignoring --labels: expected off, element, number or residue, not \"{}\"", args[i]),
+            }
+        } else if args[i] == "--background" && i+1 < args.len() {
+            // e.g. --background 1,1,1, or "0 0 0"
+            i += 1;
+            let numbers : Vec<f32> = args[i].split(|c : char| c == ',' || c.is_whitespace())
+                .filter(|word| !word.is_empty()).filter_map(|word| word.parse().ok()).collect();
+            if numbers.len() == 3 && numbers.iter().all(|&x| x >= 0.0 && x <= 1.0) {
+                background_colour = Some([numbers[0], numbers[1], numbers[2]]);
+            } else {
+                println!("Ignoring --background: expected 3 numbers from 0 to 1, not \"{}\"", args[i]);
+            }
+        } else if args[i] == "--gradient" && i+1 < args.len() {
+            // e.g. --gradient 0.1,0.1,0.2,0.8,0.8,0.9, the top and then the bottom
+            i += 1;
+            let numbers : Vec<f32> = args[i].split(|c : char| c == ',' || c.is_whitespace())
+                .filter(|word| !word.is_empty()).filter_map(|word| word.parse().ok()).collect();
+            if numbers.len() == 6 && numbers.iter().all(|&x| x >= 0.0 && x <= 1.0) {
+                background_gradient = Some([[numbers[0], numbers[1], numbers[2]], [numbers[3], numbers[4], numbers[5]]]);
+            } else {
+                println!("Ignoring --gradient: expected 6 numbers from 0 to 1, not \"{}\"", args[i]);
+            }
+        } else if args[i] == "--environment" && i+1 < args.len() {
+            i += 1;
+            environment_directory = Some(args[i].to_owned());
+        } else if args[i] == "export" && i+2 < args.len() && args[i+1] == "graph" {
+            // e.g. export graph molecule.graphml, or molecule.dot
+            i += 2;
+            graph_fname = Some(args[i].to_owned());
+            headless = true;
+        } else if args[i] == "export" && i+2 < args.len() && args[i+1] == "diagram" {
+            // e.g. export diagram molecule.svg
+            i += 2;
+            diagram_fname = Some(args[i].to_owned());
+            headless = true;
+        } else if args[i] == "export" && i+2 < args.len() && args[i+1] == "structure" {
+            // e.g. export structure molecule.xyz, or molecule.pdb
+            i += 2;
+            structure_fname = Some(args[i].to_owned());
+            headless = true;
+        } else if args[i] == "export" && i+2 < args.len() && args[i+1] == "geometry" {
+            // e.g. export geometry scene.obj, or scene.ply
+            i += 2;
+            geometry_fname = Some(args[i].to_owned());
+            headless = true;
+        } else if args[i] == "export" && i+2 < args.len() && args[i+1] == "povray" {
+            // e.g. export povray figure.pov
+            i += 2;
+            povray_fname = Some(args[i].to_owned());
+            headless = true;
+        } else if args[i] == "export" && i+2 < args.len() && args[i+1] == "svg" {
+            // e.g. export svg figure.svg
+            i += 2;
+            svg_fname = Some(args[i].to_owned());
+            headless = true;
+        } else if args[i] == "stats" {
+            // e.g. stats protein.pdb
+            print_stats = true;
+            headless = true;
+        } else if args[i] == "--samples" && i+1 < args.len() {
+            // e.g. --samples 256, for render raytrace (and ctrl+S)
+            i += 1;
+            match args[i].parse::<usize>() {
+                Ok(samples) if samples > 0 => raytrace_samples = samples,
+                _ => println!("Ignoring --samples: \"{}\" isn't a number of samples", args[i]),
+            }
+        } else if args[i] == "--supercell" && i+1 < args.len() {
+            // e.g. --supercell 2x2x2, or just 2
+            i += 1;
+            let repeats : Vec<usize> = args[i].split('x').filter_map(|s| s.trim().parse().ok()).collect();
+            match repeats.len() {
+                1 if repeats[0] > 0 => supercell = Some([repeats[0]; 3]),
+                3 if repeats.iter().all(|&n| n > 0) => supercell = Some([repeats[0], repeats[1], repeats[2]]),
+                _ => println!("Ignoring --supercell: expected e.g. 2x2x2, not \"{}\"", args[i]),
+            }
+        } else if args[i] == "--miller" && i+1 < args.len() {
+            // e.g. --miller "1 1 0"
+            i += 1;
+            match miller::MillerPlanes::parse(&args[i]) {
+                Ok(planes) => miller = Some(planes),
+                Err(message) => println!("Ignoring --miller: {}", message),
+            }
+        } else if args[i] == "--watch" {
+            watch_files = true;
+        } else if args[i] == "--slideshow" && i+1 < args.len() {
+            // e.g. --slideshow structures/
+            i += 1;
+            slideshow_directory = Some(args[i].to_owned());
+        } else if args[i] == "--slideshow-seconds" && i+1 < args.len() {
+            // e.g. --slideshow-seconds 30
+            i += 1;
+            match args[i].parse::<f32>() {
+                Ok(seconds) if seconds > 0.0 => slideshow_seconds = seconds,
+                _ => println!("Ignoring --slideshow-seconds: \"{}\" isn't a number of seconds", args[i]),
+            }
+        } else if args[i] == "--colours" && i+1 < args.len() {
+            // e.g. --colours charge
+            i += 1;
+            match colour::ColourScheme::parse(&args[i]) {
+                Ok(scheme) => colour_scheme = scheme,
+                Err(message) => println!("Ignoring --colours: {}", message),
+            }
+        } else if args[i] == "--palette" && i+1 < args.len() {
+            // e.g. --palette colours.txt (see colour.rs)
+            i += 1;
+            palette_fname = Some(args[i].to_owned());
+        } else if args[i] == "--materials" && i+1 < args.len() {
+            // e.g. --materials materials.txt (see material.rs)
+            i += 1;
+            materials_fname = Some(args[i].to_owned());
+        } else if args[i] == "--iso" && i+1 < args.len() {
+            // e.g. --iso 0.05
+            i += 1;
+            match args[i].parse::<f32>() {
+                Ok(level) if level > 0.0 => iso_level = level,
+                _ => println!("Ignoring --iso: \"{}\" isn't a positive value", args[i]),
+            }
+        } else if args[i] == "--lobe-opacity" && i+1 < args.len() {
+            // e.g. --lobe-opacity 1.0,0.5 (positive, negative)
+            i += 1;
+            let opacities : Vec<f32> = args[i].split(',').filter_map(|word| word.trim().parse::<f32>().ok()).collect();
+            if opacities.len() == 2 && opacities.iter().all(|&opacity| opacity > 0.0 && opacity <= 1.0) {
+                lobe_opacities = [opacities[0], opacities[1]];
+            } else {
+                println!("Ignoring --lobe-opacity: expected two opacities from 0 to 1, e.g. 1.0,0.5");
+            }
+        } else if args[i] == "--surface" {
+            surface_shown = true;
+        } else if args[i] == "--hide-chains" && i+1 < args.len() {
+            // e.g. --hide-chains B,C
+            i += 1;
+            hidden_chains = args[i].split(',').map(|chain| chain.trim().to_owned()).filter(|chain| !chain.is_empty()).collect();
+        } else if args[i] == "--ellipsoid-probability" && i+1 < args.len() {
+            // e.g. --ellipsoid-probability 30, in percent
+            i += 1;
+            match args[i].parse::<f32>() {
+                Ok(percent) if percent > 0.0 && percent < 100.0 => ellipsoid_probability = percent/100.0,
+                _ => println!("Ignoring --ellipsoid-probability: \"{}\" isn't a percentage", args[i]),
+            }
+        } else if args[i] == "--demo" && i+1 < args.len() {
+            // e.g. --demo 10000
+            i += 1;
+            match args[i].parse::<usize>() {
+                Ok(atoms) if atoms > 0 => demo_atoms = Some(atoms),
+                _ => println!("Ignoring --demo: \"{}\" isn't a number of atoms", args[i]),
+            }
+        } else if args[i] == "--benchmark" && i+1 < args.len() {
+            // e.g. --benchmark 1000000
+            i += 1;
+            match args[i].parse::<usize>() {
+                Ok(atoms) if atoms >= 3 => benchmark_atoms = Some(atoms),
+                _ => println!("Ignoring --benchmark: \"{}\" isn't a number of atoms (of 3 or more)", args[i]),
+            }
+        } else if scene::Scene::is_scene_fname(&args[i]) {
+            // e.g. figure.scene, or figure.scene.toml (see scene.rs)
+            match scene::Scene::read(&args[i]) {
+                Ok(file_scene) => scene = Some(file_scene),
+                Err(message) => println!("Ignoring the scene: {}", message),
+            }
+        } else if args[i].to_lowercase().ends_with(".session") {
+            // e.g. oxide.session (see session.rs)
+            match session::Session::load(&args[i]) {
+                Ok(file_session) => session = Some(file_session),
+                Err(message) => println!("Ignoring the session: {}", message),
+            }
+        } else if fname.is_none() {
+            fname = Some(args[i].to_owned());
+        } else {
+            more_fnames.push(args[i].to_owned());
+        }
+        i += 1;
+    }
+    // with nothing else to show, the startup scene says what to load and how to
+    // draw it, and without one the window starts empty, asking for a file
+    if let Some(ref session) = session {
+        if fname.is_none() && !session.files().is_empty() {
+            fname = Some(session.files()[0].to_owned());
+            more_fnames = session.files()[1..].to_vec();
+        }
+    }
+    // the demo and the benchmark make their own structures, and read no files
+    let generated = demo_atoms.is_some() || benchmark_atoms.is_some();
+    // and a molecule given by the program running the viewer is shown in place of files
+    let given = in_molecule.is_some();
+    if scene.is_none() && session.is_none() && fname.is_none() && !generated && !given && slideshow_directory.is_none() {
+        scene = config.startup().to_owned();
+    }
+    if let Some(ref scene) = scene {
+        if scene.structure().is_some() {fname = scene.structure().to_owned()}
+        queries.extend(scene.queries().iter().cloned());
+        if scene.screenshot().is_some() {
+            screenshot_fname = scene.screenshot().to_owned();
+            headless = true;
+        }
+        view_projection = view_projection.or(*scene.projection());
+        view_focus = view_focus.or(*scene.focus());
+        view_angles = view_angles.or(*scene.angles());
+        view_distance = view_distance.or(*scene.distance());
+    }
+
+    // the slideshow starts with the first file in its directory, whatever else was
+    // asked for, and carries on even if there are none yet
+    let mut slideshow = slideshow_directory.map(|directory| slideshow::Slideshow::new(&directory, &slideshow_seconds));
+    if let Some(mut show) = slideshow.take() {
+        match show.advance() {
+            Ok(first) => {
+                if first.is_none() {println!("No structure files in {} yet", show.directory())}
+                fname = first.or(fname);
+                slideshow = Some(show);
+            },
+            Err(message) => println!("Ignoring --slideshow: {}", message),
+        }
+    }
+    if given && (fname.is_some() || !more_fnames.is_empty()) {
+        println!("Ignoring the structure files: the viewer was given a molecule to show");
+        fname = None;
+        more_fnames.clear();
+    }
+
+    // ==============================
+    // Make display
+    // ==============================
+    // (a replay, script or remote carries on through a lost context from where it
+    // was, rather than starting again)
+    let mut player = match (recovery.as_mut(), replay_fname) {
+        (Some(kept), _) => kept.player.take(),
+        (None, Some(ref fname)) => match replay::Player::read(fname) {
+            Ok(player) => Some(player),
+            Err(message) => {
+                println!("Ignoring --replay: {}", message);
+                None
+            },
+        },
+        (None, None) => None,
+    };
+    // the script starts straight away, and its commands are done once there's a
+    // molecule to do them to
+    let mut script = match (recovery.as_mut(), script_fname) {
+        (Some(kept), _) => kept.script.take(),
+        (None, Some(ref fname)) => match script::Script::read(fname) {
+            Ok(script) => Some(script),
+            Err(message) => {
+                println!("Ignoring --script: {}", message);
+                None
+            },
+        },
+        (None, None) => None,
+    };
+    // listening from the start, so a program can connect as soon as it's started us
+    let mut remote = match (recovery.as_mut(), remote_address) {
+        (Some(kept), _) => kept.remote.take(),
+        (None, Some(ref address)) => match remote::Remote::listen(address) {
+            Ok(remote) => Some(remote),
+            Err(message) => {
+                println!("Ignoring --remote: {}", message);
+                None
+            },
+        },
+        (None, None) => None,
+    };
+    // a run without a window draws until the session has been replayed (or the
+    // script has run, or just the one frame, for a screenshot) and the fades and
+    // camera have settled, then quits. Taking commands, it runs until told to quit.
+    headless = headless || software;
+    if headless && player.is_none() && script.is_none() && remote.is_none() && screenshot_fname.is_none() && raytrace_fname.is_none()
+        && render_print_fname.is_none() && movie_fname.is_none() && graph_fname.is_none()
+        && diagram_fname.is_none() && structure_fname.is_none() && geometry_fname.is_none() && povray_fname.is_none()
+        && svg_fname.is_none() && !print_stats {
+        println!("Ignoring --headless and --software: there's nothing to replay or take a screenshot of");
+        headless = false;
+        software = false;
+    }
+    let recorded_window = player.as_ref().and_then(|player| *player.window());
+    // a session is replayed in a window of the size it was recorded in, so the
+    // clicks land on the same atoms. Without a GPU or display server to open one on,
+    // it's drawn in software instead.
+    let display : glium::backend::glutin_backend::GlutinFacade = if software {
+        match screenshot::software_display() {
+            Ok(display) => display,
+            Err(message) => {
+                println!("{} (is libOSMesa installed?)", message);
+                return None;
+            },
+        }
+    } else {
+        let mut window_builder = glium::glutin::WindowBuilder::new()
+            .with_title(progress::TITLE.to_string())
+            .with_visibility(!headless)
+            // so a driver reset shows up as a lost context, rather than as garbage
+            .with_gl_robustness(glium::glutin::Robustness::TryRobustLoseContextOnReset);
+        if vsync {
+            window_builder = window_builder.with_vsync();
+        }
+        if let Some((w, h)) = recorded_window.or(window_size) {
+            window_builder = window_builder.with_dimensions(w, h);
+        } else if slideshow.is_some() && !headless {
+            window_builder = window_builder.with_fullscreen(glium::glutin::get_primary_monitor());
+        }
+        // without the multisampling asked for, a window without any
+        let msaa = config.msaa().unwrap_or(0);
+        let built = if msaa > 0 {
+            window_builder.clone().with_multisampling(msaa).build_glium().or_else(|e| {
+                println!("Couldn't open a window with {}x MSAA ({}), so trying without", msaa, e);
+                window_builder.build_glium()
+            })
+        } else {
+            window_builder.build_glium()
+        };
+        match built {
+            Ok(display) => display,
+            Err(e) => {
+                println!("Couldn't open a window ({}), so drawing in software", e);
+                software = true;
+                headless = true;
+                match screenshot::software_display() {
+                    Ok(display) => display,
+                    Err(message) => {
+                        println!("{} (is libOSMesa installed?)", message);
+                        return None;
+                    },
+                }
+            },
+        }
+    };
+    if software {
+        if let Some((w, h)) = window_size.filter(|&size| size != screenshot::SOFTWARE_SIZE) {
+            println!("Ignoring --size {}x{}: software draws {}x{} (render print draws any size)",
+                     w, h, screenshot::SOFTWARE_SIZE.0, screenshot::SOFTWARE_SIZE.1);
+        }
+        if let Some((w, h)) = recorded_window.filter(|&size| size != screenshot::SOFTWARE_SIZE) {
+            println!("The session was recorded in a {}x{} window, but software draws {}x{}, so clicks may miss",
+                     w, h, screenshot::SOFTWARE_SIZE.0, screenshot::SOFTWARE_SIZE.1);
+        }
+    }
+    // (carrying on after a lost context, the recording goes on in the same file)
+    let mut recorder = match (recovery.as_mut(), record_fname) {
+        (Some(kept), _) => kept.recorder.take(),
+        (None, Some(ref fname)) => {
+            let window = display.get_window().and_then(|window| window.get_inner_size()).unwrap_or((0, 0));
+            match replay::Recorder::new(fname, &window) {
+                Ok(recorder) => Some(recorder),
+                Err(message) => {
+                    println!("Ignoring --record: {}", message);
+                    None
+                },
+            }
+        },
+        (None, None) => None,
+    };
+
+    // ==============================
+    // Make shaders
+    // ==============================
+    let default_programs = program::DefaultPrograms::new(&display);
+    // the shaders are compiled again when their files change (or with ctrl+F5), so
+    // lighting can be tried out without starting again, keeping the last that
+    // compiled if they don't
+    let shader_files = program::shader_files();
+    let mut shader_watch = watch::FileWatch::new(&shader_files);
+    let mut shaders_requested = false;
+
+    // ==============================
+    // Make models
+    // ==============================
+    let default_models = model::DefaultModels::new(&display, &default_programs);
+
+    // what's behind the molecule, on the screen and in pictures traced or exported,
+    // and over it on the screen (see background.rs). With only a gradient, the
+    // pictures have its middle.
+    let background = background_colour.or(*config.background())
+        .or_else(|| background_gradient.map(|[top, bottom]| {
+            [(top[0]+bottom[0])/2.0, (top[1]+bottom[1])/2.0, (top[2]+bottom[2])/2.0]
+        }))
+        .unwrap_or([0.93, 0.91, 0.835f32]);
+    let mut backdrop = background::Backdrop::new(&display, &background_gradient.map(|[top, bottom]| (top, bottom)));
+    if let Some(ref directory) = environment_directory {
+        match backdrop.load_environment(&display, directory) {
+            Ok(()) => println!("Drawing the environment in {}", directory),
+            Err(message) => println!("Ignoring the environment: {}", message),
+        }
+    }
+    let backdrop = backdrop;
+    let text_system = text::TextSystem::new(&display);
+    // reading a big file takes a while, so it's read in another thread while the
+    // window says what it's loading, and Escape gives up on it (see load()). The
+    // window being closed or resized meanwhile is seen to with the next frame's events.
+    let deferred_events : RefCell<Vec<glium::glutin::Event>> = RefCell::new(Vec::new());
+    let load_structure = |in_fname : &String| {
+        load(&display, &text_system, &background, &headless, &deferred_events, in_fname,
+             |cancelled| file_input::read_structure_file_cancellably(in_fname, default_species, cancelled))
+    };
+
+    // ==================================
+    // Make molecule from file
+    // ==================================
+    let mut notifications = notification::Notifications::new();
+    let mut molecule = Molecule::new();
+    let mut trajectory = trajectory::Trajectory::new();
+    // each file's atoms, to show, hide and move on their own
+    let mut objects = objects::Objects::new();
+    // where they, the cell and the groups of them have been moved
+    let mut scene_graph = scene_graph::SceneGraph::new();
+    // the values on the grid of a cube file, e.g. an orbital
+    let mut orbital : Option<volume::Volume> = None;
+    if let Some(ref mut kept) = recovery {
+        // the atoms as they were, edits and all, rather than read in again
+        molecule = mem::replace(&mut kept.molecule, Molecule::new());
+        trajectory = mem::replace(&mut kept.trajectory, trajectory::Trajectory::new());
+        objects = mem::replace(&mut kept.objects, objects::Objects::new());
+        scene_graph = mem::replace(&mut kept.scene_graph, scene_graph::SceneGraph::new());
+        orbital = kept.orbital.take();
+        miller = kept.miller.take();
+        atom_radii = kept.atom_radii;
+    } else if let Some((given_molecule, given_trajectory)) = in_molecule {
+        molecule = given_molecule;
+        trajectory = given_trajectory;
+        if trajectory.frames().first().map_or(false, |positions| positions.len() != molecule.atoms().len()) {
+            notifications.warning(&format!("Ignoring the trajectory: its frames don't move exactly the {} atoms",
+                                           molecule.atoms().len()));
+            trajectory = trajectory::Trajectory::new();
+        }
+        let object = objects.add("molecule");
+        scene_graph.add_object(&object);
+        notifications.info(&format!("Showing {} atoms", molecule.atoms().len()));
+    } else if let Some(atoms) = demo_atoms {
+        molecule = demo::crystal(&atoms, default_species);
+        notifications.info(&format!("Demo: {} atoms of nickel oxide, with the frame rate printed each lap",
+                                    molecule.atoms().len()));
+    } else if let Some(atoms) = benchmark_atoms {
+        let (water, frames) = demo::water_box(&atoms, default_species);
+        molecule = water;
+        trajectory = frames;
+        notifications.info(&format!("Benchmark: {} atoms of water over {} frames, with the frame rate printed after a lap",
+                                    molecule.atoms().len(), trajectory.frames().len()));
+    } else if let Some(ref fname) = fname {
+        // Load file and, if successful, make models
+        println!("Loading {}...", fname);
+        let loaded = if fname.to_lowercase().ends_with(".cube") {
+            load(&display, &text_system, &background, &headless, &deferred_events, fname,
+                 |cancelled| file_input::read_cube_file_cancellably(fname, default_species, cancelled))
+                .map(|read| read.map(|(file_molecule, file_trajectory, volume)| {
+                    orbital = Some(volume);
+                    (file_molecule, file_trajectory)
+                }))
+        } else {
+            load_structure(fname)
+        };
+        match loaded {
+            Some(Ok((file_molecule, file_trajectory))) => {
+                molecule = file_molecule;
+                trajectory = file_trajectory;
+                let object = objects.add(fname);
+                scene_graph.add_object(&object);
+                notifications.info(&if trajectory.frames().len() > 1 {
+                    format!("Loaded {} atoms over {} frames from {}",
+                            molecule.atoms().len(), trajectory.frames().len(), fname)
+                } else {
+                    format!("Loaded {} atoms from {}", molecule.atoms().len(), fname)
+                });
+            },
+            Some(Err(message)) => notifications.error(&message),
+            None => notifications.info(&format!("Stopped loading {}", fname)),
+        }
+    }
+    // the other files' atoms go after the first's, where they are in their first
+    // frames, and stay put through the first's trajectory
+    if !generated && !recovering {
+        for more_fname in &more_fnames {
+            match load_structure(more_fname) {
+                Some(Ok((more_molecule, more_trajectory))) => {
+                    let object = objects.add(more_fname);
+                    scene_graph.add_object(&object);
+                    for atom in more_molecule.atoms() {
+                        molecule.atoms_mut().push(atom.to_owned());
+                        molecule.atoms_mut().last_mut().unwrap().set_object(&object);
+                        if !trajectory.frames().is_empty() {trajectory.add_atom(atom.position())}
+                    }
+                    notifications.info(&if more_trajectory.frames().len() > 1 {
+                        format!("Added {} atoms from {} (its first frame of {})",
+                                more_molecule.atoms().len(), more_fname, more_trajectory.frames().len())
+                    } else {
+                        format!("Added {} atoms from {}", more_molecule.atoms().len(), more_fname)
+                    });
+                },
+                Some(Err(message)) => notifications.error(&message),
+                None => notifications.info(&format!("Stopped loading {}", more_fname)),
+            }
+        }
+    }
+    // the files the atoms came from, for saving the session
+    let mut loaded_fnames : Vec<String> = if let Some(ref mut kept) = recovery {
+        mem::replace(&mut kept.loaded_fnames, Vec::new())
+    } else if generated {
+        Vec::new()
+    } else {
+        fname.iter().chain(&more_fnames).cloned().collect()
+    };
+
+    // (what's kept after a lost context has had all this done to it)
+    if let Some(cell) = (*molecule.cell()).filter(|_| !recovering) {
+        for warning in cell.warnings() {
+            notifications.warning(&warning);
+        }
+        if cell.is_left_handed() {
+            if fix_cells {
+                molecule.set_cell(&cell.right_handed());
+                notifications.info("Reversed the third lattice vector to make the cell right-handed");
+            } else {
+                notifications.warning("Run with --fix-cells to make the cell right-handed");
+            }
+        }
+    }
+    if wrap_atoms && !recovering {
+        match *molecule.cell() {
+            Some(cell) => {
+                let wrapped = molecule.atoms().iter().map(|atom| cell.wrap(atom.position())).collect();
+                molecule.set_positions(&wrapped);
+                trajectory.wrap(&cell);
+            },
+            None => notifications.warning("Ignoring --wrap: the structure has no cell"),
+        }
+    }
+    if let Some(repeats) = supercell.filter(|_| !recovering) {
+        match packing::make_supercell(&molecule, &repeats) {
+            Some(bigger) => {
+                if let Some(cell) = *molecule.cell() {
+                    let mut frames = trajectory::Trajectory::new();
+                    for frame in trajectory.frames() {
+                        frames.add_frame(&packing::supercell_positions(&cell, frame, &repeats)).unwrap();
+                    }
+                    trajectory = frames;
+                }
+                miller = miller.map(|planes| planes.in_supercell(&repeats));
+                notifications.info(&format!("Made a {}x{}x{} supercell of {} atoms",
+                                            repeats[0], repeats[1], repeats[2], bigger.atoms().len()));
+                molecule = bigger;
+            },
+            None => notifications.warning("Ignoring --supercell: the structure has no cell"),
+        }
+    }
+
+    // breaks in protein backbones, from residues the file says are missing or
+    // from alpha carbons too far apart, drawn as dashed lines
+    let mut gaps = missing_residues::gaps(&molecule);
+    if let Some(summary) = missing_residues::summary(molecule.missing_residues(), &gaps) {
+        notifications.warning(&summary);
+        print!("{}", missing_residues::report(&molecule, molecule.missing_residues(), &gaps));
+    }
+    // the protein backbone, for drawing as a tube: the alpha carbons of each piece of chain
+    let mut traces = tube::traces(&molecule, &gaps);
+    let mut on_traces = tube::on_traces(&molecule, &traces);
+
+    // measurements use the nearest periodic image when the molecule has a cell
+    // fragments are groups of bonded atoms, e.g. separate molecules
+    let mut bonds = bonds::find_bonds(&molecule);
+    let mut fragments = bonds::fragments(&molecule.atoms().len(), &bonds);
+    let mut fragment_count = fragments.iter().max().map_or(0, |&n| n+1);
+    // and, in crystals, bonds across the cell's faces (which don't join fragments)
+    let mut periodic_bonds = bonds::find_periodic_bonds(&molecule);
+    println!("Found {} bonds, making {} fragments", bonds.len(), fragment_count);
+    // what each bond's length is compared with when colouring by deviation
+    // (kept as positions, since editing the molecule changes the bonds)
+    let mut reference_positions = recovery.as_mut().and_then(|kept| kept.reference_positions.take());
+    if let Some(fname) = bond_reference.as_ref().filter(|_| !recovering) {
+        match file_input::read_xyz_file(fname, default_species) {
+            Ok((reference, _)) => if reference.atoms().len() == molecule.atoms().len() {
+                reference_positions = Some(reference.atoms().iter().map(|atom| *atom.position()).collect());
+                notifications.info(&format!("Comparing bond lengths with {}", fname));
+            } else {
+                notifications.warning(&format!(
+                    "Ignoring --bond-reference {}: it has {} atoms, not {}",
+                    fname, reference.atoms().len(), molecule.atoms().len()
+                ));
+            },
+            Err(message) => notifications.warning(&format!("Ignoring --bond-reference: {}", message)),
+        }
+    }
+    let mut reference_lengths = bonds::reference_lengths(&molecule, &bonds, &reference_positions);
+    let mut normal_modes = recovery.as_mut().map_or(Vec::new(), |kept| mem::replace(&mut kept.normal_modes, Vec::new()));
+    if let Some(fname) = modes_fname.as_ref().filter(|_| !recovering) {
+        match file_input::read_modes_file(fname) {
+            Ok(file_modes) => if file_modes.iter().all(|mode| mode.displacements().len() == molecule.atoms().len()) {
+                notifications.info(&format!("Read {} normal modes from {} (F11 animates them)", file_modes.len(), fname));
+                normal_modes = file_modes;
+            } else {
+                notifications.warning(&format!(
+                    "Ignoring --modes {}: its modes don't move exactly the {} atoms", fname, molecule.atoms().len()
+                ));
+            },
+            Err(message) => notifications.warning(&format!("Ignoring --modes: {}", message)),
+        }
+    }
+    if let Some(fname) = vectors_fname.as_ref().filter(|_| !recovering) {
+        match file_input::read_vectors_file(fname, &mut molecule) {
+            Ok(()) => notifications.info(&format!("Read the vectors in {} (shift+V draws them as arrows)", fname)),
+            Err(message) => notifications.warning(&format!("Ignoring --vectors: {}", message)),
+        }
+    }
+    if let Some(spec) = opacities.as_ref().filter(|_| !recovering) {
+        for item in spec.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            let parts : Vec<&str> = item.split('=').collect();
+            let opacity = if parts.len() == 2 {parts[1].trim().parse::<f32>().ok()} else {None};
+            match (opacity, Selection::by_name(&molecule, &fragments, parts[0])) {
+                (Some(opacity), Ok(group)) => for &i in group.atoms() {
+                    molecule.atoms_mut()[i].set_opacity(&opacity);
+                },
+                (None, _) => notifications.warning(
+                    &format!("Ignoring --opacity \"{}\": expected <group>=<opacity>", item)
+                ),
+                (_, Err(message)) => notifications.warning(
+                    &format!("Ignoring --opacity \"{}\": {}", item, message)
+                ),
+            }
+        }
+    }
+
+    let mut minimum_image = true;
+    if let Some(distance) = measurement::shortest_distance(&molecule, &minimum_image) {
+        println!("Shortest distance: {}", distance.label());
+    }
+
+    // ==============================
+    // Make camera
+    // ==============================
+    // camera focus (the point the camera is pointing at). The focus, distance and
+    // clipping planes are replaced when the camera is fitted to the molecule below.
+    let camera_focus = [0.0,0.0,0.0];
+    // camera position
+    let camera_theta_degrees = 0.0;
+    let camera_phi_degrees = 0.0;
+    let camera_psi_degrees = 0.0;
+    let camera_r = 2.0;
+    // field of view and clipping planes
+    let camera_field_of_view_degrees = config.field_of_view().unwrap_or(90.0);
+    let camera_near_plane = 1.0;
+    let camera_far_plane = 10.0;
+
+    let (width, height) = display.get_framebuffer_dimensions();
+    let mut camera = Camera::new (
+        &[width, height],
+        &camera_focus,
+	&camera_theta_degrees,
+	&camera_phi_degrees,
+	&camera_psi_degrees,
+	&camera_r,
+        &camera_field_of_view_degrees,
+        &camera_near_plane,
+        &camera_far_plane
+    );
+
+    camera.fit(&molecule, |atom| objects.radius(atom, &atom_radii));
+    if let Some(separation) = eye_separation {
+        camera.set_eye_separation(&separation);
+    }
+    if let Some(projection) = view_projection {camera.set_projection(&projection)}
+    if let Some(focus) = view_focus {camera.set_focus(&focus)}
+    let r = view_distance.unwrap_or(*camera.r());
+    match view_angles {
+        Some(angles) => camera.set_angles(&angles[0], &angles[1], &angles[2], &r),
+        None => {
+            let dr = r - camera.r();
+            camera.dolly(&dr);
+        },
+    }
+    if let Some(given_camera) = in_camera {
+        camera = given_camera;
+        camera.set_screen_size(&width, &height);
+    }
+    // R goes back to this view
+    let mut camera_focus = camera.focus().to_owned();
+    let mut camera_r = camera.r().to_owned();
+
+    // ==============================
+    // Run everything
+    // ==============================
+
+    // each representation is drawn with the depth test and culling of its material
+    let materials = match materials_fname {
+        Some(ref fname) => material::Materials::read(fname).unwrap_or_else(|message| {
+            notifications.warning(&format!("Ignoring --materials: {}", message));
+            material::Materials::new()
+        }),
+        None => material::Materials::new(),
+    };
+    // (picking draws the atoms as they're drawn, see scene_view.rs)
+    let atom_params = materials.atoms().parameters();
+    
+    let light_position = match scene.as_ref().and_then(|scene| *scene.light()) {
+        Some(light) => [light[0], light[1], light[2], 1.0f32],
+        None => [2.0,0.0,0.0,1.0f32],
+    };
+
+    // depth cueing fades from the front to the back of the molecule
+    let mut depth_cue = DepthCue::Off;
+    let mut depth_cue_half_width = molecule.bounding_sphere(|atom| objects.radius(atom, &atom_radii)).1;
+    // debug views: ctrl+F1 draws the meshes' wireframes over them, ctrl+F2 and ctrl+F3
+    // colour them by their normals or shade them flat, and ctrl+F4 shows what the
+    // picking pass draws (each atom's ID in a colour of its own)
+    let mut shading = Shading::Lit;
+    let mut wireframe_visible = false;
+    let mut id_view = false;
+
+    // slab mode only draws a slice through the molecule, centred slab_offset behind
+    // the focus. Both are in proportion to the size of the molecule.
+    let mut slab_enabled = false;
+    let mut slab_half_width = 0.25;
+    let mut slab_offset = 0.0;
+    let slab_step = 0.05;
+
+    let mut fxaa_enabled = true;
+    let fxaa = fxaa::FxaaSystem::new(&display);
+    let oit = oit::OitSystem::new(&display);
+    let stereo = stereo::StereoSystem::new(&display);
+    let mut stereo_mode = StereoMode::Off;
+    // what the scene's drawn with, each frame (see scene_view.rs)
+    let resources = scene_view::Resources {
+        programs    : &default_programs,
+        models      : &default_models,
+        materials   : &materials,
+        backdrop    : &backdrop,
+        text_system : &text_system,
+        fxaa        : &fxaa,
+        oit         : &oit,
+        stereo      : &stereo,
+    };
+    let id_buffer = id_buffer::IdBuffer::new(&display);
+    // a big structure's opaque atoms and sticks, each drawn in one go (see batch.rs)
+    let instancing = batch::is_supported(&display);
+    if !instancing && molecule.atoms().len() >= batch::BATCH_ATOMS {
+        notifications.warning("Drawing every atom on its own, which is slow: the GPU can't draw instances");
+    }
+    let mut sphere_batch = batch::InstanceBuffer::new();
+    let mut stick_batch = batch::InstanceBuffer::new();
+    // the batches' instances are only made again when what they're made from may
+    // have changed: where the atoms are, their colours, radii and visibility, the
+    // copies drawn and the sticks. Whatever changes them (an event or command, an
+    // edit, the atoms moving, a fade or the occlusion being traced) sets this, along
+    // with the pair of atoms highlighted when they were made.
+    let mut batch_dirty = true;
+    let mut batched_highlighted : Option<(usize, usize)> = None;
+    let mut help_visible = false;
+    // F2 shows the frame rate, what's drawn and how
+    let mut hud = hud::Hud::new();
+    let mut hud_visible = false;
+    // F3 shows/hides the axes in the corner, which turn with the camera
+    let mut axes_visible = true;
+
+    // E steps the selection through nothing, everything, then each element in turn.
+    // Clicking on an atom adds it to (or removes it from) the selection.
+    let mut selection = Selection::new();
+    let mut selection_step = 0;
+    // Return opens a prompt for typing a selection query (see selection/query.rs)
+    let mut query_prompt : Option<String> = None;
+    // shift+Return opens it as a console instead, for a line of script (see script.rs)
+    let mut console = false;
+    for query in queries.iter().filter(|_| !recovering) {
+        match selection::query::run(query, &mut molecule, &fragments, &selection, &minimum_image) {
+            Ok((result, _)) => selection = result,
+            Err(message) => notifications.warning(&format!("Ignoring --select \"{}\": {}", query, message)),
+        }
+    }
+    // shift+C steps through the colour schemes
+    let palette = match palette_fname {
+        Some(ref fname) => colour::Palette::read(fname).unwrap_or_else(|message| {
+            notifications.warning(&format!("Ignoring --palette: {}", message));
+            colour::Palette::new()
+        }),
+        None => colour::Palette::new(),
+    };
+    if !recovering {
+        if !colour_scheme.applies_to(&molecule) {
+            notifications.warning(&format!("Nothing to colour the atoms by {} with in this file", colour_scheme.name()));
+        }
+        objects.colour(&colour_scheme, &mut molecule, &palette);
+    }
+    if let Some(scene) = scene.as_ref().filter(|_| !recovering) {
+        for &(ref group, ref opacity) in scene.opacities() {
+            match Selection::by_name(&molecule, &fragments, group) {
+                Ok(group) => for &i in group.atoms() {molecule.atoms_mut()[i].set_opacity(opacity)},
+                Err(message) => notifications.warning(&format!("Ignoring the scene's opacity for {}: {}", group, message)),
+            }
+        }
+        for &(ref group, ref colour) in scene.colours() {
+            match Selection::by_name(&molecule, &fragments, group) {
+                Ok(group) => for &i in group.atoms() {molecule.atoms_mut()[i].set_colour(colour)},
+                Err(message) => notifications.warning(&format!("Ignoring the scene's colour for {}: {}", group, message)),
+            }
+        }
+    }
+    // W steps the selected atoms' opacity through these
+    let opacity_steps = [1.0, 0.5, 0.2f32];
+    let mut statistics_visible = false;
+
+    // M shows the distance matrix of the selection. Hovering over it highlights
+    // the pair of atoms under the cursor.
+    let mut heat_map = heat_map::HeatMap::new(&display);
+    let mut heat_map_visible = false;
+    // the 2D skeletal diagram (shift+M), laid out when it's shown
+    let mut structure_diagram : Option<diagram::Diagram> = None;
+    let mut diagram_visible = false;
+    let mut highlighted : Option<(usize, usize)> = None;
+    let highlight_colour = [1.0, 0.0, 1.0f32];
+    // selected atoms are tinted towards this
+    let selection_tint = [1.0, 0.9, 0.2f32];
+    // G dims everything but the selection and its surroundings, instead of tinting
+    let mut focus = focus::Focus::new(&focus_shell);
+    let mut focus_enabled = false;
+    // ghost mode makes the whole molecule translucent
+    let mut ghost_enabled = false;
+    let ghost_opacity = 0.4;
+    // X steps through hiding bonds, drawing them plain, and colouring by length deviation
+    let mut bond_display = BondDisplay::Hidden;
+    let bond_radius = 0.03f32;
+    let bond_colour = [0.6, 0.6, 0.6f32];
+    // ctrl+X steps through hiding a crystal's bonds across its cell faces, cutting
+    // them off at the faces, and drawing them to ghosts of the atoms on the other side
+    let mut periodic_display = PeriodicBonds::Hidden;
+    // ctrl+S path-traces the frame to a file, with soft shadows and ambient occlusion
+    let mut trace_requested = false;
+    let trace_fname = "raytrace.png";
+    // (in another thread, as it takes a while)
+    let mut trace_job : Option<raytrace::TraceJob> = None;
+    // ctrl+P saves a print of the view, --print-size times the window's size
+    let mut print_requested = false;
+    let print_fname = "print.png";
+    // ctrl+E saves the scene's geometry, for Blender and the like
+    let mut geometry_requested = false;
+    let geometry_shortcut_fname = "scene.obj";
+    // ctrl+R saves the view as a POV-Ray scene
+    let mut povray_requested = false;
+    let povray_shortcut_fname = "scene.pov";
+    // ctrl+V saves the view as an SVG
+    let mut svg_requested = false;
+    let svg_shortcut_fname = "view.svg";
+    // shift+S steps through hiding the molecular surface, drawing it opaque and
+    // drawing it translucent. It's found (in another thread) when it's first shown,
+    // and again after edits.
+    let mut surface_display = if surface_shown {surface::SurfaceDisplay::Opaque} else {surface::SurfaceDisplay::Hidden};
+    let mut molecular_surface : Option<surface::Surface> = None;
+    let mut surface_job : Option<surface::SurfaceJob> = None;
+    let surface_colour = [0.8, 0.8, 0.8f32];
+    // shift+O shows/hides a cube file's orbital, as a blue lobe where it's positive
+    // and a red one where it's negative, and ctrl+PageUp/PageDown moves the level
+    // they're drawn at. They're made again whenever it moves.
+    let mut lobes_visible = orbital.is_some();
+    let mut lobes : Option<(surface::Surface, surface::Surface)> = None;
+    let lobe_colours = [[0.15, 0.35, 0.9], [0.9, 0.2, 0.15f32]];
+    // ctrl+M prints what's drawn and the memory it takes
+    let mut stats_requested = false;
+    // backbone gaps are dashed, in a colour that stands out from the bonds
+    let gap_colour = [0.9, 0.3, 0.1f32];
+    let gap_dash = 0.3f32;
+    // Q steps through labelling the selected atoms (or all, with none selected) with
+    // their element, number or residue
+    let mut label_content = LabelContent::Off;
+    let label_height = 0.25f32;
+    let label_colour = [0.1, 0.1, 0.1, 1.0f32];
+    // U switches between the chains in the file and the biological assembly
+    let mut assembly_view = false;
+    // Y shows the copies of the molecule packed around it in the crystal, with atoms
+    // in contact with another copy highlighted. Worked out the first time.
+    let mut packing_view = false;
+    let mut packing : Option<(Vec<assembly::Operator>, Vec<Option<f32>>)> = None;
+    // F4 switches clicking atoms from selecting them to measuring distances, angles
+    // and dihedrals between them, and F5 clears the measurements
+    let mut tool = Tool::Select;
+    let mut tool_atoms : Vec<usize> = Vec::new();
+    let mut measurements = recovery.as_mut().map_or(Vec::new(), |kept| mem::replace(&mut kept.measurements, Vec::new()));
+    let measurement_colour = [0.1, 0.5, 0.9f32];
+    // Home shows/hides the edges of the unit cell, if there is one, shift+Home labels
+    // its lattice vectors a, b and c, and ctrl+Home marks its origin
+    let mut cell_edges_visible = true;
+    let mut cell_labels_visible = false;
+    let mut cell_origin_visible = false;
+    let cell_colour = [0.3, 0.3, 0.3f32];
+    // End shows/hides the --miller planes, PageUp/PageDown draws more/fewer of them
+    // and shift+PageUp/PageDown makes them more/less opaque
+    if miller.is_some() && molecule.cell().is_none() {
+        notifications.warning("Ignoring --miller: the structure has no cell");
+        miller = None;
+    }
+    let mut miller_visible = miller.is_some();
+    let mut miller_count = 1usize;
+    let mut miller_opacity = 0.4f32;
+    let miller_colour = [0.85, 0.65, 0.2f32];
+    let miller_mesh = |in_planes : &miller::MillerPlanes, in_cell : &cell::Cell, in_count : &usize| {
+        let (vertices, indices) = in_planes.mesh(in_cell, in_count);
+        model::Model::new(
+            &display,
+            vertices,
+            &glium::index::PrimitiveType::TrianglesList,
+            indices,
+            default_programs.polyhedron(),
+            default_programs.polyhedron_translucent(),
+            default_programs.polyhedron_picking(),
+        )
+    };
+    let mut miller_model = match (miller, *molecule.cell()) {
+        (Some(planes), Some(cell)) => {
+            notifications.info(&format!("{} planes {:.3} Angstroms apart", planes.name(), planes.spacing(&cell)));
+            Some(miller_mesh(&planes, &cell, &miller_count))
+        },
+        _ => None,
+    };
+    let measurement_radius = 0.015f32;
+    // F6 shows just a ligand (the selection, or each ligand in the file in turn) and
+    // the residues around it as sticks, with its hydrogen bonds
+    let mut binding_site : Option<binding_site::BindingSite> = None;
+    let mut next_ligand = 0;
+    let hydrogen_bond_colour = [0.1, 0.75, 0.8f32];
+    // F9 draws the backbone as a tube (hiding the residues' atoms), then as putty,
+    // which is worked out the first time
+    let mut tube_display = TubeDisplay::Hidden;
+    let tube_colour = [0.35, 0.55, 0.75f32];
+    let mut putty_values : Option<Vec<f32>> = None;
+    // the edit tools (also on F4) delete atoms, add atoms of the element F7 picks,
+    // and drag the selection; the bonds and gaps are found again after each edit
+    let mut element = 0;
+    let mut edited = false;
+    // while relaxing, the atoms move without being added, deleted or bonded anew, so
+    // only what's worked out from where they are is found again each frame, and the
+    // rest (bonds, the surface, ...) once it's finished
+    let mut moved = false;
+    // ctrl+Z undoes edits (and changes of opacity), ctrl+shift+Z redoes them. A drag
+    // is one edit, from where the atoms started.
+    let mut history = recovery.as_mut().map_or(History::new(), |kept| mem::replace(&mut kept.history, History::new()));
+    // files dropped on the window, to load at the start of the next frame, and
+    // whether each is to be added to the structure showing rather than replace it
+    let mut dropped : Vec<(String, bool)> = Vec::new();
+    // a script's (or remote) turn of the camera: about what axis, the radians left to turn and
+    // how many a second (0 for all at once), and who to tell when it's done
+    let mut turning : Option<([f32;3], f32, f32, Sender<Result<(), String>>)> = None;
+    // everything animated from frame to frame is moved on by the time each frame
+    // takes, so it goes at the same speed however fast the frames come (without a
+    // window, there's no screen to keep pace with)
+    let mut frame_clock = frame_clock::FrameClock::new(&if headless {None} else {target_fps});
+    // the arrow keys held down, which spin the view anticlockwise and clockwise
+    let mut spin_keys = [false, false];
+    // shift+Period plays the trajectory, with the seconds since the frame showing
+    // came up
+    let mut trajectory_playing = false;
+    let mut frame_shown_for = 0.0f32;
+    // a script's (or remote) screenshot, saved once the frame's been drawn
+    let mut requested_screenshot : Option<(String, Sender<Result<(), String>>)> = None;
+    let mut file_watch = if watch_files {Some(watch::FileWatch::new(&loaded_fnames))} else {None};
+    let mut drag_start : Option<(Vec<usize>, Vec<[f32;3]>)> = None;
+    // F8 shows the sequence of each chain along the top; clicking a residue
+    // selects it and flies the camera to it
+    let mut sequence = sequence::Sequence::new(&molecule);
+    let mut sequence_visible = false;
+    // F10 relaxes the structure in a toy force field, a few steps a frame so it can
+    // be watched; the whole relaxation is one edit to undo
+    let mut relaxation : Option<optimize::Relaxation> = None;
+    let relaxation_steps_per_frame = 10;
+    // shift+F10 relaxes it with the --optimiser program instead, showing each geometry
+    // it sends back, until it finishes or shift+F10 (or clicking the panel saying
+    // it's running) cancels it and puts the atoms back
+    let mut external_relaxation : Option<optimize::ExternalRelaxation> = None;
+    // long tasks (finding the surface, making a movie, the optimiser) show their
+    // progress in panels, which cancel them when clicked, and in the window's title
+    let mut cancel_requested : Option<progress::TaskKind> = None;
+    let mut task_buttons : Vec<(progress::TaskKind, [f32;2], [f32;2])> = Vec::new();
+    let mut window_title = String::new();
+    // F11 animates each normal mode in turn and then stops (shift+F11 goes back
+    // through them), and F12 (shift+F12) makes the swing bigger (smaller)
+    let mut vibration : Option<vibration::Vibration> = None;
+    let mut vibration_amplitude = vibration::DEFAULT_AMPLITUDE;
+    // Tab draws atoms with displacement parameters as their thermal ellipsoids
+    let mut ellipsoids_visible = false;
+    let ellipsoid_radius = ellipsoid::probability_radius(&ellipsoid_probability);
+    // the bonds, the tube and whatever the binding site view hides fade in and out
+    // rather than popping. What the bonds and tube were last drawn as is kept to
+    // draw them fading out, as is what the last binding site showed.
+    let mut bond_fade = Fade::new(&false);
+    let mut bonds_drawn = bond_display;
+    let mut tube_fade = Fade::new(&false);
+    let mut tube_drawn = TubeDisplay::Tube;
+    let mut site_fade = Fade::new(&true);
+    let mut site_shown = Selection::new();
+
+    let mut mouse = Mouse::new(&mouse_bindings);
+    let mut wheel_mode = WheelMode::Dolly;
+
+    // which trajectory frame is shown, or None for the average structure
+    let mut frame : Option<usize> = Some(0);
+    // frames streamed from a simulation go on the end of the trajectory
+    let mut frame_stream = match (recovery.as_mut(), stream_address) {
+        (Some(kept), _) => kept.frame_stream.take(),
+        (None, Some(ref address)) => match stream::FrameStream::connect(address, &molecule.atoms().len()) {
+            Ok(frame_stream) => {
+                notifications.info(&format!("Taking frames from {}", address));
+                Some(frame_stream)
+            },
+            Err(message) => {
+                notifications.error(&message);
+                None
+            },
+        },
+        (None, None) => None,
+    };
+    if let Some(remote) = remote.as_ref().filter(|_| !recovering) {
+        notifications.info(&format!("Taking commands on {}", remote.address()));
+    }
+    let mut rmsf_colouring = false;
+    // radians spun and distance from the focus moved per pixel dragged
+    let spin_sensitivity = 0.01;
+    // radians a second the arrow keys spin the view
+    let spin_speed = 90f32.to_radians();
+    // radians alt+arrow keys turn the whole scene (and alt+shift+arrow keys an object)
+    let object_turn_step = 15f32.to_radians();
+    // and Angstroms ctrl+alt+arrow keys move an object
+    let object_move_step = 1.0f32;
+    // what ctrl+] and ctrl+[ scale every atom's radius by
+    let atom_scale_step = 1.1f32;
+    // and what shift+] and shift+[ scale the arrows' lengths by
+    let arrow_scale_step = 1.25f32;
+    let zoom_sensitivity = 0.01;
+    // how long the R key takes to glide back to the starting view
+    let reset_seconds = 0.5;
+    // keyframes recorded with N are this many seconds apart when played back with P
+    let mut camera_path = CameraPath::new(&Interpolation::CatmullRom);
+    let keyframe_interval = 2.0;
+    let mut playback : Option<Instant> = None;
+    // the demo plays a path round the crystal over and over, timing each lap, and the
+    // benchmark plays it once, with the trajectory playing too, so every atom moves
+    let mut benchmark = None;
+    if generated {
+        camera_path = demo::camera_path(&mut camera);
+        playback = Some(Instant::now());
+        benchmark = Some(demo::Benchmark::new());
+    }
+    if benchmark_atoms.is_some() {trajectory_playing = true}
+    // a movie being made, and whether it steps through the trajectory, a frame of it
+    // for each of the movie's
+    let mut movie : Option<movie::Movie> = None;
+    let mut movie_trajectory = false;
+    let movie_shortcut_fname = "movie.mp4";
+    if let Some(ref fname) = movie_fname {
+        match movie::Movie::start(fname, &display.get_framebuffer_dimensions(), &frame_rate) {
+            Ok(started) => {
+                movie = Some(started);
+                movie_trajectory = trajectory.frames().len() > 1;
+                if !movie_trajectory {
+                    camera_path = demo::camera_path(&mut camera);
+                    playback = Some(Instant::now());
+                }
+            },
+            Err(message) => println!("Couldn't make the movie: {}", message),
+        }
+    }
+    // frames are counted so sessions replay with each event in the same frame
+    let mut frame_count = recovery.as_ref().map_or(0, |kept| kept.frame_count);
+    // once nothing has changed for a moment, the bonds and ellipsoids are drawn
+    // finer, and (after Insert) ambient occlusion is traced, until something does
+    let mut refinement = refine::Refinement::new();
+    let mut view_changed = true;
+    let mut occlusion : Option<refine::Occlusion> = None;
+    let scene_bonds = scene.as_ref().and_then(|scene| *scene.bonds());
+    let scene_labels = scene.as_ref().and_then(|scene| *scene.labels());
+    let scene_backbone = scene.as_ref().and_then(|scene| *scene.backbone());
+    if let Some(bonds_shown) = view_bonds.or(scene_bonds) {bond_display = bonds_shown}
+    if let Some(labels) = view_labels.or(scene_labels) {label_content = labels}
+    if let Some(backbone) = view_backbone.or(scene_backbone) {
+        tube_display = backbone;
+        if tube_display == TubeDisplay::Putty {
+            putty_values = tube::mobility(&molecule, &trajectory)
+                .map(|values| tube::putty_values(&molecule, &traces, &values));
+        }
+    }
+    // carrying on from a saved session, where the files it loaded are still there
+    if let Some(session) = session.filter(|_| !recovering) {
+        let (kept_selection, kept_measurements) = session.restore_selections(&mut molecule);
+        camera = session.camera().clone();
+        let (w, h) = display.get_framebuffer_dimensions();
+        camera.set_screen_size(&w, &h);
+        selection = kept_selection;
+        measurements = kept_measurements;
+        // the frame it was on, or the average structure
+        let frames = trajectory.frames().len();
+        frame = match *session.frame() {
+            Some(k) if k < frames => Some(k),
+            None if frames > 1 => None,
+            _ => Some(0),
+        };
+        match frame {
+            Some(k) if k < frames => trajectory.show_frame(&k, &mut molecule),
+            Some(_) => {},
+            None => molecule.set_positions(&trajectory.average_structure()),
+        }
+        bond_display = *session.bond_display();
+        label_content = *session.label_content();
+        surface_display = *session.surface_display();
+        hidden_chains = session.hidden_chains().to_owned();
+        objects.restore(session.objects(), session.atom_objects(), &mut molecule);
+        // (after the objects, which may have colour schemes of their own)
+        colour_scheme = *session.colour_scheme();
+        objects.colour(&colour_scheme, &mut molecule, &palette);
+        if let Some(ref saved) = *session.scene_graph() {
+            scene_graph.restore(saved, &objects, &mut molecule, &mut trajectory);
+        }
+        tube_display = *session.tube_display();
+        if tube_display == TubeDisplay::Putty {
+            putty_values = tube::mobility(&molecule, &trajectory)
+                .map(|values| tube::putty_values(&molecule, &traces, &values));
+        }
+        edited = true;
+        notifications.info("Carried on from the saved session");
+    }
+    // carrying on after the last context was lost, with the atoms, undo history and
+    // measurements as they were (taken up above) and the view as it was
+    let recoveries = recovery.as_ref().map_or(0, |recovery| recovery.recoveries);
+    if let Some(recovery) = recovery {
+        camera = recovery.camera;
+        let (w, h) = display.get_framebuffer_dimensions();
+        camera.set_screen_size(&w, &h);
+        selection = recovery.selection;
+        frame = recovery.frame;
+        colour_scheme = recovery.colour_scheme;
+        bond_display = recovery.bond_display;
+        label_content = recovery.label_content;
+        surface_display = recovery.surface_display;
+        hidden_chains = recovery.hidden_chains;
+        tube_display = recovery.tube_display;
+        if tube_display == TubeDisplay::Putty {
+            putty_values = tube::mobility(&molecule, &trajectory)
+                .map(|values| tube::putty_values(&molecule, &traces, &values));
+        }
+        edited = true;
+        notifications.info("Carried on after the GL context was lost");
+    }
+    loop {
+        // (without the robustness extensions, a lost context only shows up when the
+        // frame is swapped)
+        if display.is_context_lost() {
+            break;
+        }
+        frame_count += 1;
+        // (replays and movies come out the same every time, whatever the frame rate)
+        let frame_seconds = frame_clock.tick(&(player.is_some() || movie.is_some()));
+        if player.as_ref().map_or(false, |player| player.is_finished()) {
+            player = None;
+            println!("Finished replaying the session");
+        }
+        let mut relaxed = false;
+        if let Some(ref mut relaxation) = relaxation {
+            for _ in 0..relaxation_steps_per_frame {relaxation.step(&mut molecule)}
+            relaxed = relaxation.is_finished(&molecule);
+            moved = true;
+        }
+        if relaxed {
+            let relaxation = relaxation.take().unwrap();
+            edited = true;
+            // unless atoms were added or deleted part way, which can't be undone past
+            if molecule.atoms().len() == relaxation.start().len() {
+                let to : Vec<[f32;3]> = molecule.atoms().iter().map(|atom| *atom.position()).collect();
+                if let Some(k) = frame {
+                    for (i, position) in to.iter().enumerate() {trajectory.set_position(&k, &i, position)}
+                }
+                history.record(Edit::MoveAtoms {
+                    atoms : (0..to.len()).collect(),
+                    from  : relaxation.start().to_owned(),
+                    to    : to,
+                });
+            }
+            println! ("Relaxed for {} steps, to {:.2} kcal/mol with no force over {:.3} kcal/mol/A",
+                      relaxation.steps(), relaxation.energy(), relaxation.max_force());
+        }
+        match cancel_requested.take() {
+            Some(progress::TaskKind::Optimiser) => if let Some(external) = external_relaxation.take() {
+                molecule.set_positions(external.start_positions());
+                edited = true;
+                println! ("Cancelled relaxing with {}", external.command());
+            },
+            Some(progress::TaskKind::Surface) => if surface_job.take().is_some() {
+                surface_display = surface::SurfaceDisplay::Hidden;
+                notifications.info("Stopped finding the molecular surface");
+            },
+            Some(progress::TaskKind::Movie) => if let Some(recording) = movie.take() {
+                // what's been recorded so far is kept
+                if !movie_trajectory {playback = None}
+                movie_trajectory = false;
+                match recording.finish() {
+                    Ok(report) => println!("Stopped the movie early. {}", report),
+                    Err(message) => println!("Couldn't finish the movie: {}", message),
+                }
+            },
+            Some(progress::TaskKind::Trace) => if let Some(job) = trace_job.take() {
+                notifications.info(&format!("Stopped tracing {}", job.fname()));
+            },
+            Some(progress::TaskKind::Loading) | None => {},
+        }
+        if external_relaxation.as_ref().map_or(false, |external| external.start_positions().len() != molecule.atoms().len()) {
+            external_relaxation = None;
+            notifications.warning("Stopped the optimiser: atoms were added or deleted while it ran");
+        }
+        let received = external_relaxation.as_mut().map(|external| external.receive());
+        if let Some((positions, finished)) = received {
+            if let Some(ref positions) = positions {
+                molecule.set_positions(positions);
+                edited = true;
+            }
+            if let Some(finished) = finished {
+                // what it got to is kept (and can be undone) even if it failed
+                let external = external_relaxation.take().unwrap();
+                let to : Vec<[f32;3]> = molecule.atoms().iter().map(|atom| *atom.position()).collect();
+                if let Some(k) = frame {
+                    for (i, position) in to.iter().enumerate() {trajectory.set_position(&k, &i, position)}
+                }
+                history.record(Edit::MoveAtoms {
+                    atoms : (0..to.len()).collect(),
+                    from  : external.start_positions().to_owned(),
+                    to    : to,
+                });
+                match finished {
+                    Ok(()) => notifications.info(&format!("Relaxed with {} over {} geometries", external.command(),
+                                                          external.steps())),
+                    Err(message) => notifications.warning(&format!("The optimiser stopped after {} geometries: {}",
+                                                                   external.steps(), message)),
+                }
+            }
+        }
+        // adding or deleting atoms stops the animation where it is
+        if vibration.as_ref().map_or(false, |_| normal_modes[0].displacements().len() != molecule.atoms().len()) {
+            vibration = None;
+        }
+        if let Some(ref vibration) = vibration {
+            molecule.set_positions(&vibration.positions(&normal_modes, &vibration_amplitude));
+            batch_dirty = true;
+        }
+        // the last frame stays in view as more arrive, unless another's been picked, and
+        // the oldest go once there are stream::KEPT_FRAMES (those picked stay, while kept)
+        let streamed = frame_stream.as_ref().map(|frame_stream| frame_stream.receive());
+        if let Some((frames, ended)) = streamed {
+            if trajectory.frames().is_empty() && !frames.is_empty() {
+                let positions : Vec<[f32;3]> = molecule.atoms().iter().map(|atom| *atom.position()).collect();
+                trajectory.add_frame(&positions).unwrap();
+            }
+            let following = match frame {Some(k) => k+1 >= trajectory.frames().len(), None => false};
+            let mut ended = ended;
+            for positions in &frames {
+                if let Err(message) = trajectory.add_frame(positions) {
+                    ended = Some(format!("{} (is it the structure loaded?)", message));
+                    break;
+                }
+            }
+            let dropped = trajectory.keep_last(&stream::KEPT_FRAMES);
+            if following && !frames.is_empty() && trajectory.frames().len() > 1 {
+                let last = trajectory.frames().len()-1;
+                frame = Some(last);
+                trajectory.show_frame(&last, &mut molecule);
+                batch_dirty = true;
+            } else if let Some(k) = frame.filter(|_| dropped > 0) {
+                frame = Some(k.saturating_sub(dropped));
+                if k < dropped {
+                    trajectory.show_frame(&0, &mut molecule);
+                    batch_dirty = true;
+                }
+            }
+            if let Some(message) = ended {
+                let address = frame_stream.take().map(|frame_stream| frame_stream.address().to_owned());
+                notifications.warning(&format!("Stopped taking frames from {}: {}", address.unwrap_or_default(),
+                                               message));
+            }
+        }
+        // the slideshow turns the structure showing, and every so often swaps it for the
+        // next, fitted in view. Nothing about the last one's atoms carries over.
+        let mut next_slide = None;
+        if let Some(ref mut show) = slideshow {
+            camera.rotate(&[0.0, 1.0, 0.0], &show.turn(&frame_seconds));
+            if show.is_due() {
+                match show.advance() {
+                    Ok(fname) => next_slide = fname,
+                    Err(message) => notifications.warning(&message),
+                }
+            }
+        }
+        // the script's commands and those from other programs are done as they come,
+        // each before the next is sent, until one waits on a turn or a screenshot.
+        // Files are loaded below, with those dropped on the window, so nothing more
+        // is done till they are.
+        while turning.is_none() && requested_screenshot.is_none() {
+            let (command, reply) = match script.as_ref().and_then(|script| script.next())
+                .or_else(|| remote.as_ref().and_then(|remote| remote.next())) {
+                Some(request) => request,
+                None => break,
+            };
+            let loading = match command {Command::Load(_) | Command::Add(_) => true, _ => false};
+            let done = match command {
+                Command::Load(fname) => Ok(dropped.push((fname, false))),
+                Command::Add(fname) => Ok(dropped.push((fname, true))),
+                Command::Select(query) => {
+                    selection::query::run(&query, &mut molecule, &fragments, &selection, &minimum_image)
+                        .map(|(result, _)| selection = result)
+                },
+                Command::Colour(group, colour) => Selection::by_name(&molecule, &fragments, &group).map(|group| {
+                    history.make(Edit::set_colour(&molecule, group.atoms(), &colour), &mut molecule, &mut trajectory,
+                                 &mut objects, &mut reference_positions, &frame);
+                }),
+                Command::ColourScheme(scheme) => {
+                    colour_scheme = scheme;
+                    objects.colour(&colour_scheme, &mut molecule, &palette);
+                    Ok(())
+                },
+                Command::Opacity(group, opacity) => Selection::by_name(&molecule, &fragments, &group).map(|group| {
+                    history.make(Edit::set_opacity(&molecule, group.atoms(), &opacity), &mut molecule, &mut trajectory,
+                                 &mut objects, &mut reference_positions, &frame);
+                }),
+                Command::Bonds(display) => Ok(bond_display = display),
+                Command::Backbone(display) => {
+                    tube_display = display;
+                    if tube_display == TubeDisplay::Putty {
+                        putty_values = tube::mobility(&molecule, &trajectory)
+                            .map(|values| tube::putty_values(&molecule, &traces, &values));
+                    }
+                    Ok(())
+                },
+                Command::Radii(source, scale) if scale > 0.0 => Ok(atom_radii = AtomRadii::new(&source, &scale)),
+                Command::Radii(_, scale) => Err(format!("the scale {} isn't positive", scale)),
+                Command::Arrows(display, scale) if scale > 0.0 => {
+                    arrow_scale = scale.max(arrows::MIN_SCALE).min(arrows::MAX_SCALE);
+                    Ok(arrow_display = display)
+                },
+                Command::Arrows(_, scale) => Err(format!("the scale {} isn't positive", scale)),
+                Command::Labels(content) => Ok(label_content = content),
+                Command::Surface(display) => Ok(surface_display = display),
+                Command::Projection(projection) => Ok(camera.set_projection(&projection)),
+                Command::Focus(focus) => Ok(camera.set_focus(&focus)),
+                Command::Angles(angles) => {
+                    let r = *camera.r();
+                    Ok(camera.set_angles(&angles[0], &angles[1], &angles[2], &r))
+                },
+                Command::Distance(distance) if distance > 0.0 => {
+                    let dr = distance - camera.r();
+                    Ok(camera.dolly(&dr))
+                },
+                Command::Distance(distance) => Err(format!("the distance {} isn't positive", distance)),
+                Command::Fit => Ok(camera.fit(&molecule, |atom| objects.radius(atom, &atom_radii))),
+                Command::Turn(axis, degrees, seconds) => {
+                    // turning backwards is turning forwards about the opposite axis
+                    let axis = if degrees < 0.0 {[-axis[0], -axis[1], -axis[2]]} else {axis};
+                    let radians = degrees.abs().to_radians();
+                    let speed = if seconds > 0.0 {radians/seconds} else {0.0};
+                    turning = Some((axis, radians, speed, reply));
+                    continue;
+                },
+                Command::Frame(k) if k < trajectory.frames().len() => {
+                    frame = Some(k);
+                    Ok(trajectory.show_frame(&k, &mut molecule))
+                },
+                Command::Frame(0) => Ok(()),
+                Command::Frame(k) => Err(format!("no frame {} (there are {})", k, trajectory.frames().len())),
+                Command::Show(k) => objects.set_visible(&k, &true),
+                Command::Hide(k) => objects.set_visible(&k, &false),
+                Command::Move(node, shift) => scene_graph.find(&node).and_then(|node| {
+                    scene_graph.transform(&node, &Matrix::translation(&shift), &objects, &mut molecule, &mut trajectory)
+                }).map(|_| edited = true),
+                Command::Rotate(_, axis, _) if axis == [0.0;3] => Err("the axis can't be 0 0 0".to_owned()),
+                Command::Rotate(node, axis, degrees) => scene_graph.find(&node).and_then(|node| {
+                    scene_graph.transform(&node, &Matrix::rotation(&axis, &degrees.to_radians()), &objects,
+                                          &mut molecule, &mut trajectory)
+                }).map(|_| edited = true),
+                Command::PutBack(node) => scene_graph.find(&node).and_then(|node| {
+                    scene_graph.put_back(&node, &objects, &mut molecule, &mut trajectory)
+                }).map(|_| edited = true),
+                Command::Group(node, group) => scene_graph.find(&node).and_then(|node| {
+                    let group = scene_graph.group(&group)?;
+                    scene_graph.set_parent(&node, &group)
+                }),
+                Command::Screenshot(fname) => {
+                    requested_screenshot = Some((fname, reply));
+                    continue;
+                },
+                Command::Quit => return None,
+            };
+            batch_dirty = true;
+            reply.send(done).ok();
+            if loading {break}
+        }
+        let script_ended = script.as_ref().and_then(|script| script.finished());
+        if let Some(ended) = script_ended {
+            match ended {
+                Ok(()) => notifications.info(&format!("Finished running {}", script.as_ref().unwrap().name())),
+                Err(message) => notifications.error(&format!("Stopped running {}: {}",
+                                                             script.as_ref().unwrap().name(), message)),
+            }
+            script = None;
+        }
+        // standard input ending is the remote's script ending, so a run without a
+        // window can quit
+        if remote.as_ref().map_or(false, |remote| remote.finished()) {
+            notifications.info("Finished taking commands on standard input");
+            remote = None;
+        }
+        let mut turned_all = false;
+        if let Some(ref mut turn) = turning {
+            let step = if turn.2 > 0.0 {(turn.2*frame_seconds).min(turn.1)} else {turn.1};
+            camera.rotate(&turn.0, &step);
+            turn.1 -= step;
+            turned_all = turn.1 <= 0.0;
+        }
+        if turned_all {
+            if let Some((_, _, _, reply)) = turning.take() {reply.send(Ok(())).ok();}
+        }
+        let spin = if spin_keys[0] {1.0} else {0.0} - if spin_keys[1] {1.0} else {0.0};
+        if spin != 0.0 {
+            camera.spin(&(spin*spin_speed*frame_seconds));
+            view_changed = true;
+        }
+        // a playing trajectory moves on as many frames as are due, going back to the
+        // start after the last
+        if trajectory_playing && trajectory.frames().len() > 1 {
+            frame_shown_for += frame_seconds;
+            let due = (frame_shown_for*trajectory::PLAYBACK_FRAMES_PER_SECOND) as usize;
+            if due > 0 {
+                frame_shown_for -= due as f32/trajectory::PLAYBACK_FRAMES_PER_SECOND;
+                let next = (frame.unwrap_or(0)+due)%trajectory.frames().len();
+                frame = Some(next);
+                trajectory.show_frame(&next, &mut molecule);
+                batch_dirty = true;
+            }
+        } else {
+            trajectory_playing = false;
+        }
+        // a file dropped on the window takes the structure's place, as a slide does,
+        // or with shift held its atoms are added where they are in its first frame
+        // (as the files after the first on the command line). Of several dropped at
+        // once, the rest are added to the first.
+        let mut added_fnames = Vec::new();
+        for (k, (fname, adding)) in dropped.drain(..).enumerate() {
+            if adding || k > 0 {
+                added_fnames.push(fname);
+            } else {
+                next_slide = Some(fname);
+            }
+        }
+        if let Some(ref fname) = next_slide {
+            match load_structure(fname) {
+                Some(Ok((file_molecule, file_trajectory))) => {
+                    molecule = file_molecule;
+                    trajectory = file_trajectory;
+                    loaded_fnames = vec![fname.to_owned()];
+                    objects.reset(fname);
+                    scene_graph.reset();
+                    frame = Some(0);
+                    rmsf_colouring = false;
+                    objects.colour(&colour_scheme, &mut molecule, &palette);
+                    hidden_chains.clear();
+                    orbital = None;
+                    lobes = None;
+                    selection = Selection::new();
+                    selection_step = 0;
+                    highlighted = None;
+                    tool_atoms.clear();
+                    measurements.clear();
+                    binding_site = None;
+                    site_shown = Selection::new();
+                    next_ligand = 0;
+                    assembly_view = false;
+                    packing_view = false;
+                    packing = None;
+                    relaxation = None;
+                    external_relaxation = None;
+                    vibration = None;
+                    normal_modes.clear();
+                    reference_positions = None;
+                    history = History::new();
+                    drag_start = None;
+                    miller_model = match (miller, *molecule.cell()) {
+                        (Some(planes), Some(cell)) => Some(miller_mesh(&planes, &cell, &miller_count)),
+                        _ => None,
+                    };
+                    camera.fit(&molecule, |atom| objects.radius(atom, &atom_radii));
+                    camera_focus = camera.focus().to_owned();
+                    camera_r = camera.r().to_owned();
+                    depth_cue_half_width = molecule.bounding_sphere(|atom| objects.radius(atom, &atom_radii)).1;
+                    edited = true;
+                    notifications.info(&if slideshow.is_some() {
+                        format!("Showing {}: {} atoms", slideshow::short_name(fname), molecule.atoms().len())
+                    } else {
+                        format!("Loaded {} atoms from {}", molecule.atoms().len(), fname)
+                    });
+                },
+                Some(Err(message)) => notifications.error(&message),
+                None => notifications.info(&format!("Stopped loading {}", fname)),
+            }
+        }
+        // with --watch, when the files loaded are written again (by a geometry
+        // optimisation, say) they're read back in, and the atoms moved to where they
+        // are now (to the last frame of a trajectory), keeping the camera, the
+        // selections and everything else
+        let changed = file_watch.as_mut().map_or(Vec::new(), |watch| watch.poll(&loaded_fnames));
+        if !changed.is_empty() {
+            let mut positions : Vec<[f32;3]> = Vec::new();
+            let mut reloaded = trajectory::Trajectory::new();
+            let mut read = Ok(());
+            for (k, fname) in loaded_fnames.iter().enumerate() {
+                match file_input::read_structure_file(fname, default_species) {
+                    Ok((file_molecule, file_trajectory)) => {
+                        if k == 0 {
+                            positions = match file_trajectory.frames().last() {
+                                Some(last) => last.to_owned(),
+                                None => file_molecule.atoms().iter().map(|atom| *atom.position()).collect(),
+                            };
+                            reloaded = file_trajectory;
+                        } else {
+                            for atom in file_molecule.atoms() {
+                                positions.push(*atom.position());
+                                if !reloaded.frames().is_empty() {reloaded.add_atom(atom.position())}
+                            }
+                        }
+                    },
+                    Err(message) => {
+                        read = Err(message);
+                        break;
+                    },
+                }
+            }
+            match read {
+                Ok(()) if positions.len() == molecule.atoms().len() => {
+                    trajectory = reloaded;
+                    frame = Some(trajectory.frames().len().max(1)-1);
+                    match frame {
+                        // (with the last frame's forces and velocities, if it has them)
+                        Some(k) if k < trajectory.frames().len() => trajectory.show_frame(&k, &mut molecule),
+                        _ => molecule.set_positions(&positions),
+                    }
+                    // (the objects moved are moved again)
+                    scene_graph.reapply(&objects, &mut molecule, &mut trajectory);
+                    edited = true;
+                    notifications.info(&format!("Reloaded {}", changed.join(", ")));
+                },
+                Ok(()) => notifications.warning(&format!(
+                    "{} changed, but now has {} atoms rather than {}, so it wasn't reloaded (drop it on the \
+                     window to load it again)", changed.join(", "), positions.len(), molecule.atoms().len()
+                )),
+                Err(message) => notifications.error(&message),
+            }
+        }
+        if !shader_watch.poll(&shader_files).is_empty() || shaders_requested {
+            shaders_requested = false;
+            match default_programs.reload(&display) {
+                Ok(()) => notifications.info(&format!("Compiled the shaders in {}",
+                                                      program::shader_directory().display())),
+                Err(message) => notifications.error(&format!("Kept the last shaders: {}", message)),
+            }
+        }
+        for fname in &added_fnames {
+            match load_structure(fname) {
+                Some(Ok((more_molecule, _))) => {
+                    let object = objects.add(fname);
+                    scene_graph.add_object(&object);
+                    for atom in more_molecule.atoms() {
+                        molecule.atoms_mut().push(atom.to_owned());
+                        molecule.atoms_mut().last_mut().unwrap().set_object(&object);
+                        if !trajectory.frames().is_empty() {trajectory.add_atom(atom.position())}
+                    }
+                    loaded_fnames.push(fname.to_owned());
+                    objects.colour(&colour_scheme, &mut molecule, &palette);
+                    // the edits undone would be of the atoms as they were numbered before
+                    history = History::new();
+                    edited = true;
+                    notifications.info(&format!("Added {} atoms from {}", more_molecule.atoms().len(), fname));
+                },
+                Some(Err(message)) => notifications.error(&message),
+                None => notifications.info(&format!("Stopped loading {}", fname)),
+            }
+        }
+        if edited {
+            molecular_surface = None;
+            surface_job = None;
+            structure_diagram = None;
+            bonds = bonds::find_bonds(&molecule);
+            fragments = bonds::fragments(&molecule.atoms().len(), &bonds);
+            fragment_count = fragments.iter().max().map_or(0, |&n| n+1);
+            periodic_bonds = bonds::find_periodic_bonds(&molecule);
+            reference_lengths = bonds::reference_lengths(&molecule, &bonds, &reference_positions);
+            gaps = missing_residues::gaps(&molecule);
+            traces = tube::traces(&molecule, &gaps);
+            on_traces = tube::on_traces(&molecule, &traces);
+            if putty_values.is_some() {
+                putty_values = tube::mobility(&molecule, &trajectory)
+                    .map(|values| tube::putty_values(&molecule, &traces, &values));
+            }
+            sequence = sequence::Sequence::new(&molecule);
+            edited = false;
+            moved = false;
+            batch_dirty = true;
+        }
+        if moved {
+            periodic_bonds = bonds::find_periodic_bonds(&molecule);
+            moved = false;
+            batch_dirty = true;
+        }
+        // the surface takes a while for big molecules, so it's only found when it's shown
+        if surface_display != surface::SurfaceDisplay::Hidden && molecular_surface.is_none() && surface_job.is_none() {
+            surface_job = Some(surface::SurfaceJob::start(&molecule, &surface_colour));
+        }
+        let found = surface_job.as_ref().and_then(|job| job.receive(&display).map(|built| (built, *job.started())));
+        if let Some((built, start)) = found {
+            surface_job = None;
+            match built {
+                Ok(built) => {
+                    let elapsed = start.elapsed();
+                    println!("Found the molecular surface ({} triangles) in {:.1} seconds", built.triangles(),
+                             elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32*1e-9);
+                    match *built.potential() {
+                        Some(potential) => println!("Surface coloured by potential, from -{:.2} V (red) to {:.2} V (blue)",
+                                                    potential, potential),
+                        None => println!("No partial charges, so the surface isn't coloured by potential"),
+                    }
+                    molecular_surface = Some(built);
+                },
+                Err(message) => {
+                    surface_display = surface::SurfaceDisplay::Hidden;
+                    notifications.error(&message);
+                },
+            }
+        }
+        if let (Some(ref volume), true, true) = (orbital.as_ref(), lobes_visible, lobes.is_none()) {
+            let positive = surface::Surface::isosurface(&display, volume, &iso_level, &lobe_colours[0]);
+            let negative = surface::Surface::isosurface(&display, &volume.negated(), &iso_level, &lobe_colours[1]);
+            println!("The orbital's lobes at +/-{} have {} and {} triangles", iso_level,
+                     positive.triangles(), negative.triangles());
+            lobes = Some((positive, negative));
+        }
+        hud.tick();
+        if let Some(ref mut benchmark) = benchmark {benchmark.tick()}
+        let camera_moving = camera.animate(&frame_seconds);
+        refinement.update(&(view_changed || camera_moving || playback.is_some() || relaxation.is_some()
+                            || external_relaxation.is_some() || vibration.is_some() || slideshow.is_some() || bond_fade.is_fading()
+                            || tube_fade.is_fading() || site_fade.is_fading()));
+        let refined = refinement.is_refined();
+        if refined {
+            if let Some(ref mut occlusion) = occlusion {
+                if occlusion.trace(&molecule, |atom| objects.radius(atom, &atom_radii)) {batch_dirty = true}
+            }
+        }
+        if let Some(start) = playback {
+            let elapsed = start.elapsed();
+            let time = match movie {
+                Some(ref movie) => movie.time(),
+                None => elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32*1e-9,
+            };
+            if let Some(keyframe) = camera_path.sample(&time) {
+                keyframe.apply(&mut camera);
+            }
+            if time >= camera_path.duration() {
+                match benchmark {
+                    Some(ref mut benchmark) => {
+                        println!("{}", benchmark.finish_lap(&molecule.atoms().len()));
+                        if benchmark_atoms.is_some() {return None}
+                        playback = Some(Instant::now());
+                    },
+                    None => {
+                        playback = None;
+                        println!("Finished playing the camera path");
+                    },
+                }
+            }
+        }
+        if movie_trajectory && !trajectory.frames().is_empty() {
+            if let Some(ref movie) = movie {
+                let k = (*movie.frames()).min(trajectory.frames().len()-1);
+                frame = Some(k);
+                trajectory.show_frame(&k, &mut molecule);
+                batch_dirty = true;
+            }
+        }
+        let depth_range = [
+            camera.r() - depth_cue_half_width,
+            camera.r() + depth_cue_half_width
+        ];
+
+        let slab = if slab_enabled {
+            let centre = camera.r() + slab_offset*depth_cue_half_width;
+            [
+                centre - slab_half_width*depth_cue_half_width,
+                centre + slab_half_width*depth_cue_half_width
+            ]
+        } else {
+            [-1e30, 1e30f32]
+        };
+
+        if focus_enabled {
+            focus.update(&molecule, &selection, &minimum_image);
+        }
+
+        // what's drawn: every atom where it is, or each of the assembly's copies of its chains
+        let operators = match packing {
+            Some((ref copies, _)) if packing_view => {
+                let mut operators = vec![assembly::Operator::identity()];
+                operators.extend(copies.iter().cloned());
+                operators
+            },
+            _ if assembly_view => molecule.assembly().operators().to_owned(),
+            _ => vec![assembly::Operator::identity()],
+        };
+        // each atom drawn, and how far its copy is from it
+        // in the binding site view, everything else is hidden, and the tube hides the
+        // residues it goes through
+        bond_fade.set(&(bond_display != BondDisplay::Hidden || binding_site.is_some()));
+        if bond_display != BondDisplay::Hidden {bonds_drawn = bond_display}
+        tube_fade.set(&(tube_display != TubeDisplay::Hidden && binding_site.is_none()));
+        if tube_display != TubeDisplay::Hidden {tube_drawn = tube_display}
+        site_fade.set(&binding_site.is_none());
+        if let Some(ref site) = binding_site {site_shown = site.shown().to_owned()}
+        let (bond_opacity, tube_opacity, site_opacity) = (bond_fade.opacity(), tube_fade.opacity(), site_fade.opacity());
+        // which atoms are in hidden chains or hidden objects, if any are
+        let in_hidden_chain : Vec<bool> = if hidden_chains.is_empty() && objects.objects().iter().all(|object| object.is_visible()) {
+            Vec::new()
+        } else {
+            molecule.atoms().iter().map(|atom| {
+                !objects.is_visible(atom.object())
+                    || atom.residue().as_ref().map_or(false, |residue| hidden_chains.iter().any(|chain| chain == residue.chain()))
+            }).collect()
+        };
+        // how much of each atom is showing, as it fades
+        let visibility = |i : usize| {
+            if in_hidden_chain.get(i) == Some(&true) {return 0.0}
+            let outside_site = if site_shown.contains(&i) {1.0} else {site_opacity};
+            let behind_tube = if on_traces[i] {1.0-tube_opacity} else {1.0};
+            outside_site*behind_tube
+        };
+        let visibility = &visibility;
+        let is_shown = |i : usize| visibility(i) > 0.0;
+        let is_shown = &is_shown;
+        // (with the operator, which turns the copy's ellipsoid as well as moving it)
+        let copies : Vec<(usize, [f32;3], usize)> = operators.iter().enumerate().flat_map(|(k, operator)| {
+            molecule.atoms().iter().enumerate()
+                .filter(move |&(i, atom)| is_shown(i) && operator.applies_to(atom))
+                .map(move |(i, atom)| (i, operator.shift(atom.position()), k))
+        }).collect();
+
+        // the binding site is drawn in sticks, so with bonds whatever bond_display is
+        let bond_lengths = if !bond_fade.is_visible() && !objects.shows_bonds() {
+            Vec::new()
+        } else {
+            bonds::lengths(&molecule, &bonds)
+        };
+        // each end of each bond across the cell's faces: the atom, the other atom and
+        // how far that one's image is from it
+        let periodic_ends : Vec<(usize, usize, [f32;3])> = match *molecule.cell() {
+            Some(cell) if periodic_display != PeriodicBonds::Hidden && bond_fade.is_visible() => {
+                periodic_bonds.iter()
+                    .filter(|&&(pair, _)| is_shown(pair[0]) && is_shown(pair[1]))
+                    .flat_map(|&(pair, image)| {
+                        let shift = cell.to_cartesian(&[image[0] as f32, image[1] as f32, image[2] as f32]);
+                        vec![(pair[0], pair[1], shift), (pair[1], pair[0], [-shift[0], -shift[1], -shift[2]])]
+                    })
+                    .collect()
+            },
+            _ => Vec::new(),
+        };
+        // the images at the far ends, drawn translucent, each once
+        let mut ghosts : Vec<(usize, [f32;3])> = Vec::new();
+        if periodic_display == PeriodicBonds::Ghosts {
+            for &(_, j, shift) in &periodic_ends {
+                if !ghosts.iter().any(|&(k, ref s)| k == j && s == &shift) {ghosts.push((j, shift))}
+            }
+        }
+        // with a big structure, or an assembly of many copies of its chains, the opaque
+        // atoms (but for ellipsoids) and sticks are each drawn in one go, from instance
+        // buffers, rather than with a draw call apiece
+        let batched = instancing && (benchmark_atoms.is_some() || copies.len() >= batch::BATCH_ATOMS);
+        let batch_atoms : Vec<bool> = molecule.atoms().iter().enumerate().map(|(i, atom)| {
+            batched && !atom.is_translucent() && visibility(i) >= 1.0 && !(ellipsoids_visible && atom.adp().is_some())
+        }).collect();
+        // each atom drawn apiece turns its quad to face the camera; a batch's quads are
+        // turned by its shader, so its atoms are left as they are (but for those drawn
+        // again as ghosts, and all of them in the ID view)
+        if batched && !id_view {
+            let apiece : Vec<usize> = (0..batch_atoms.len()).filter(|&i| !batch_atoms[i])
+                .chain(ghosts.iter().map(|&(i, _)| i)).collect();
+            molecule.rotate_some_against_camera(&camera, &apiece);
+        } else {
+            molecule.rotate_atoms_against_camera(&camera);
+        }
+        // each label is brought out in front of its atom, so the atom doesn't hide it
+        let forward = camera.view_matrix().contents()[2];
+        let forward_length = (forward[0]*forward[0]+forward[1]*forward[1]+forward[2]*forward[2]).sqrt();
+        let labels : Vec<([f32;3], String)> = copies.iter()
+            .filter(|&&(i, _, _)| selection.is_empty() || selection.contains(&i))
+            .filter_map(|&(i, shift, _)| {
+                let atom = &molecule.atoms()[i];
+                atom.label(&label_content, &i).map(|text| {
+                    let lift = 1.1*objects.radius(atom, &atom_radii)/forward_length;
+                    let p = atom.position();
+                    ([
+                        p[0]+shift[0]-lift*forward[0],
+                        p[1]+shift[1]-lift*forward[1],
+                        p[2]+shift[2]-lift*forward[2],
+                    ], text)
+                })
+            })
+            .collect();
+        let gap_dashes : Vec<([f32;3], [f32;3])> = operators.iter().flat_map(|operator| {
+            let atoms = molecule.atoms();
+            gaps.iter().filter(move |gap| {
+                operator.applies_to(&atoms[gap.atoms()[0]]) && is_shown(gap.atoms()[0]) && is_shown(gap.atoms()[1])
+            }).flat_map(move |gap| {
+                missing_residues::dashes(&operator.apply(atoms[gap.atoms()[0]].position()),
+                                         &operator.apply(atoms[gap.atoms()[1]].position()), &gap_dash)
+            })
+        }).collect();
+
+        // the tube as pieces, thick and coloured by mobility for the putty
+        let mut tube_pieces : Vec<([f32;3], [f32;3], [f32;3], f32, f32)> = Vec::new();
+        if tube_fade.is_visible() {
+            let atoms = molecule.atoms();
+            let putty = match putty_values {
+                Some(ref values) if tube_drawn == TubeDisplay::Putty => Some(values),
+                _ => None,
+            };
+            for operator in &operators {
+                for trace in traces.iter().filter(|trace| {
+                    operator.applies_to(&atoms[trace[0]]) && in_hidden_chain.get(trace[0]) != Some(&true)
+                }) {
+                    let points = trace.iter().map(|&i| operator.apply(atoms[i].position())).collect();
+                    let values : Vec<f32> = trace.iter().map(|&i| putty.map_or(0.0, |values| values[i])).collect();
+                    let radii = values.iter().map(|t| match putty {
+                        Some(_) => tube::PUTTY_RADII[0] + t*(tube::PUTTY_RADII[1]-tube::PUTTY_RADII[0]),
+                        None => tube::TUBE_RADIUS,
+                    }).collect();
+                    for (from, to, radius, value) in tube::pieces(&points, &radii, &values) {
+                        let colour = if putty.is_some() {molecule::value_colour(&value)} else {tube_colour};
+                        tube_pieces.push((from, to, colour, radius, tube_opacity));
+                    }
+                }
+            }
+        }
+
+        let hydrogen_bond_dashes : Vec<([f32;3], [f32;3])> = match binding_site {
+            Some(ref site) => site.hydrogen_bonds().iter().flat_map(|pair| {
+                let atoms = molecule.atoms();
+                missing_residues::dashes(atoms[pair[0]].position(), atoms[pair[1]].position(),
+                                         &(0.5*gap_dash))
+            }).collect(),
+            None => Vec::new(),
+        };
+        // each measurement as a dashed line through its atoms, with an arc for angles,
+        // and its value as a label
+        let mut measurement_sticks : Vec<([f32;3], [f32;3])> = Vec::new();
+        // a, b and c just past the ends of the lattice vectors, and 0 at the origin
+        let mut cell_labels : Vec<([f32;3], String)> = Vec::new();
+        if let Some(cell) = *molecule.cell() {
+            let o = cell.origin();
+            if cell_labels_visible {
+                for (k, name) in ["a", "b", "c"].iter().enumerate() {
+                    let v = cell.lattice()[k];
+                    cell_labels.push(([o[0]+1.05*v[0], o[1]+1.05*v[1], o[2]+1.05*v[2]], name.to_string()));
+                }
+            }
+            if cell_origin_visible {
+                cell_labels.push((*o, "0".to_owned()));
+            }
+        }
+        let mut measurement_labels : Vec<([f32;3], String)> = Vec::new();
+        for measurement in &measurements {
+            let points = measurement.points(&molecule, &minimum_image);
+            for pair in points.windows(2) {
+                measurement_sticks.extend(missing_residues::dashes(&pair[0], &pair[1], &(0.5*gap_dash)));
+            }
+            let midpoint = |a : &[f32;3], b : &[f32;3]| [(a[0]+b[0])/2.0, (a[1]+b[1])/2.0, (a[2]+b[2])/2.0];
+            let label_position = match *measurement {
+                Measurement::Angle(_) => {
+                    let legs = bonds::lengths_between(&points, &vec![[0, 1], [1, 2]]);
+                    let radius = 0.3*legs[0].min(legs[1]);
+                    let arc = measurement::arc(&points[1], &points[0], &points[2], &radius);
+                    measurement_sticks.extend(arc.windows(2).map(|pair| (pair[0], pair[1])));
+                    let middle = arc[arc.len()/2];
+                    [
+                        points[1][0] + 1.5*(middle[0]-points[1][0]),
+                        points[1][1] + 1.5*(middle[1]-points[1][1]),
+                        points[1][2] + 1.5*(middle[2]-points[1][2]),
+                    ]
+                },
+                Measurement::Distance(_) => midpoint(&points[0], &points[1]),
+                Measurement::Dihedral(_) => midpoint(&points[1], &points[2]),
+            };
+            measurement_labels.push((label_position, measurement.label(&molecule, &minimum_image)));
+        }
+
+        // the forces, velocities or dipole as arrows, of the atoms shown: their shafts
+        // are sticks, and their heads cones, placed as sticks are
+        let arrow_colour = arrow_display.colour();
+        let shown_arrows : Vec<_> = arrows::vectors(&molecule, &arrow_display)
+            .map_or(Vec::new(), |vectors| arrows::arrows(&vectors, &arrow_scale)).into_iter()
+            .filter(|&(atom, _, _)| atom.map_or(true, |i| is_shown(i)))
+            .collect();
+        let arrow_heads : Vec<([f32;3], [f32;3], [f32;3], f32, f32)> = shown_arrows.iter()
+            .map(|&(_, _, (from, tip, radius))| (from, tip, arrow_colour, radius, 1.0))
+            .collect();
+
+        // how each bond's drawn, and how opaque, if it is: as its atoms' object says,
+        // or as the scene does
+        let bond_style = |bond : &[usize;2]| -> Option<(BondDisplay, f32)> {
+            match objects.bond_display(molecule.atoms()[bond[0]].object()) {
+                Some(BondDisplay::Hidden) => None,
+                Some(display) => Some((display, 1.0)),
+                None if bond_opacity > 0.0 => Some((bonds_drawn, bond_opacity)),
+                None => None,
+            }
+        };
+        let bond_style = &bond_style;
+
+        // every stick drawn, as (from, to, colour, radius, opacity): the bonds, the
+        // dashes, the tube and the arrows' shafts
+        let sticks : Vec<([f32;3], [f32;3], [f32;3], f32, f32)> = {
+            let atoms = molecule.atoms();
+            let reference_lengths = &reference_lengths;
+            let bond_sticks = operators.iter().flat_map(|operator| {
+                bonds.iter().zip(bond_lengths.iter()).enumerate()
+                    .filter(move |&(_, (bond, _))| {
+                        operator.applies_to(&atoms[bond[0]]) && is_shown(bond[0]) && is_shown(bond[1])
+                    })
+                    .filter_map(move |(k, (bond, length))| {
+                        let (display, opacity) = bond_style(bond)?;
+                        let colour = match display {
+                            BondDisplay::Deviation => bonds::deviation_colour(
+                                &((length-reference_lengths[k])/reference_lengths[k].max(1e-6))
+                            ),
+                            _ => bond_colour,
+                        };
+                        Some((operator.apply(atoms[bond[0]].position()),
+                              operator.apply(atoms[bond[1]].position()), colour, bond_radius,
+                              opacity*visibility(bond[0]).min(visibility(bond[1]))))
+                    })
+            });
+            let periodic_sticks = periodic_ends.iter().map(|&(i, j, shift)| {
+                let from = *atoms[i].position();
+                let p = atoms[j].position();
+                let image = [p[0]+shift[0], p[1]+shift[1], p[2]+shift[2]];
+                let colour = match bonds_drawn {
+                    BondDisplay::Deviation => {
+                        let ideal = bonds::ideal_lengths(&molecule, &vec![[i, j]])[0];
+                        let length = bonds::lengths_between(&vec![from, image], &vec![[0, 1]])[0];
+                        bonds::deviation_colour(&((length-ideal)/ideal))
+                    },
+                    _ => bond_colour,
+                };
+                let to = match (periodic_display, *molecule.cell()) {
+                    (PeriodicBonds::Cut, Some(ref cell)) => bonds::cell_exit(cell, &from, &image),
+                    _ => image,
+                };
+                (from, to, colour, bond_radius, bond_opacity*visibility(i).min(visibility(j)))
+            });
+            let gap_sticks = gap_dashes.iter().map(|&(from, to)| (from, to, gap_colour, bond_radius, 1.0));
+            let measurement_sticks = measurement_sticks.iter()
+                .map(|&(from, to)| (from, to, measurement_colour, measurement_radius, 1.0));
+            let hydrogen_bond_sticks = hydrogen_bond_dashes.iter()
+                .map(|&(from, to)| (from, to, hydrogen_bond_colour, measurement_radius, 1.0-site_opacity));
+            let arrow_sticks = shown_arrows.iter().filter_map(|&(_, shaft, _)| shaft)
+                .map(|(from, to)| (from, to, arrow_colour, arrows::ARROW_RADIUS, 1.0));
+            bond_sticks.chain(periodic_sticks).chain(gap_sticks).chain(measurement_sticks).chain(hydrogen_bond_sticks)
+                       .chain(tube_pieces.iter().cloned()).chain(arrow_sticks).collect()
+        };
+
+        // what colour and size each atom is drawn, in the window or traced
+        let atom_colour = |i : usize, atom : &atom::Atom| {
+            match highlighted {
+                Some((a, b)) if a == i || b == i => highlight_colour,
+                _ if tool_atoms.contains(&i) => highlight_colour,
+                _ if packing_view => match packing {
+                    Some((_, ref contacts)) => match contacts[i] {
+                        Some(distance) => packing::contact_colour(&distance, &packing::CONTACT_DISTANCE),
+                        None => atom.colour().to_owned(),
+                    },
+                    None => atom.colour().to_owned(),
+                },
+                _ if focus_enabled => if focus.is_in_focus(&i) {
+                    atom.colour().to_owned()
+                } else {
+                    focus::dimmed(atom.colour())
+                },
+                _ if selection.contains(&i) => {
+                    let c = atom.colour();
+                    [
+                        0.4*c[0] + 0.6*selection_tint[0],
+                        0.4*c[1] + 0.6*selection_tint[1],
+                        0.4*c[2] + 0.6*selection_tint[2],
+                    ]
+                },
+                _ => atom.colour().to_owned(),
+            }
+        };
+        // the residues round a binding site are sticks, so their atoms are shrunk
+        // to the bonds' thickness
+        let atom_scale = |i : usize, atom : &atom::Atom| {
+            let scale = objects.factor(atom, &atom_radii);
+            match binding_site {
+                Some(ref site) if !site.ligand().contains(&i) => (bond_radius/atom.species().size()).min(scale),
+                _ => scale,
+            }
+        };
+        // each atom as it's drawn: its colour, darkened in crevices by the ambient
+        // occlusion, how much its size is scaled by and how much of it is showing
+        let shaded_colours : Vec<[f32;3]> = molecule.atoms().iter().enumerate().map(|(i, atom)| {
+            let colour = atom_colour(i, atom);
+            let shade = occlusion.as_ref().map_or(1.0, |occlusion| occlusion.shade(&i));
+            [shade*colour[0], shade*colour[1], shade*colour[2]]
+        }).collect();
+        let atom_scales : Vec<f32> = molecule.atoms().iter().enumerate().map(|(i, atom)| atom_scale(i, atom)).collect();
+        let visibilities : Vec<f32> = (0..molecule.atoms().len()).map(visibility).collect();
+        // the ID view draws the atoms as picking does, without the binding site's
+        // shrinking
+        let pick_scales : Vec<f32> = if id_view {
+            molecule.atoms().iter().map(|atom| objects.factor(atom, &atom_radii)).collect()
+        } else {
+            Vec::new()
+        };
+        // (fading sticks and atoms move in or out of the batches as they go)
+        if bond_fade.is_fading() || tube_fade.is_fading() || site_fade.is_fading() {batch_dirty = true}
+        if batched && (batch_dirty || highlighted != batched_highlighted) {
+            sphere_batch.update(&display, copies.iter().filter(|&&(i, _, _)| batch_atoms[i]).map(|&(i, shift, _)| {
+                let atom = &molecule.atoms()[i];
+                let p = atom.position();
+                batch::SphereInstance {
+                    instance_position : [p[0]+shift[0], p[1]+shift[1], p[2]+shift[2]],
+                    instance_radius   : *atom.species().size()*atom_scales[i],
+                    instance_colour   : shaded_colours[i],
+                }
+            }));
+            stick_batch.update(&display, sticks.iter().filter(|stick| stick.4 >= 1.0).map(|&(from, to, colour, radius, _)| {
+                batch::StickInstance {
+                    instance_from   : from,
+                    instance_to     : to,
+                    instance_radius : radius,
+                    instance_colour : colour,
+                }
+            }));
+            batch_dirty = false;
+            batched_highlighted = highlighted;
+        } else if batched {
+            sphere_batch.keep();
+            stick_batch.keep();
+        } else {
+            sphere_batch.clear();
+            stick_batch.clear();
+            // (so they're made when the structure's big enough again)
+            batch_dirty = true;
+        }
+
+        // a run without a window quits once everything has settled (so screenshots
+        // of the same session always come out the same)
+        let settling = camera_moving || bond_fade.is_fading() || tube_fade.is_fading() || site_fade.is_fading()
+            || !refinement.is_refined()
+            || occlusion.as_ref().map_or(false, |occlusion| occlusion.samples() < refine::RAYS_PER_ATOM);
+        let quitting = headless && player.is_none() && script.is_none() && remote.is_none() && movie.is_none()
+            && surface_job.is_none() && !settling;
+        if stats_requested || (print_stats && quitting) {
+            stats_requested = false;
+            let atoms = molecule.atoms();
+            let cylinder = if refined {default_models.fine_cylinder()} else {default_models.cylinder()};
+            let ellipsoid = if refined {default_models.fine_ellipsoid()} else {default_models.ellipsoid()};
+            let triangles : usize = copies.iter().map(|&(i, _, _)| i).chain(ghosts.iter().map(|&(i, _)| i)).map(|i| {
+                if ellipsoids_visible && atoms[i].adp().is_some() {ellipsoid.triangles()} else {default_models.sphere().triangles()}
+            }).sum::<usize>() + sticks.len()*cylinder.triangles() + arrow_heads.len()*default_models.cone().triangles()
+                + molecular_surface.as_ref().map_or(0, |surface| if surface_display == surface::SurfaceDisplay::Hidden {0} else {surface.triangles()})
+                + lobes.as_ref().map_or(0, |&(ref positive, ref negative)| if lobes_visible {positive.triangles()+negative.triangles()} else {0});
+            let mut report = stats::Report::new();
+            report.heading("Structure");
+            report.count("atoms", &atoms.len());
+            report.count("bonds", &bonds.len());
+            if molecule.cell().is_some() {report.count("bonds across the cell's faces", &periodic_bonds.len())}
+            report.count("trajectory frames", &trajectory.frames().len());
+            report.heading("Drawn each frame");
+            report.count("atoms (and copies)", &(copies.len()+ghosts.len()));
+            report.count("sticks (bonds, tube, dashes, arrows)", &sticks.len());
+            if !arrow_heads.is_empty() {report.count("arrow heads", &arrow_heads.len())}
+            report.count("triangles", &triangles);
+            // (each atom and stick is a draw call of its own, but for those in batches,
+            // which are one a batch)
+            let surface_drawn = molecular_surface.is_some() && surface_display != surface::SurfaceDisplay::Hidden;
+            let lobes_drawn = if lobes.is_some() && lobes_visible {2} else {0};
+            let batches = sphere_batch.buffer().is_some() as usize + stick_batch.buffer().is_some() as usize;
+            report.count("draw calls", &(copies.len()+ghosts.len()+sticks.len()+arrow_heads.len()+surface_drawn as usize
+                                         +lobes_drawn+batches-sphere_batch.count()-stick_batch.count()));
+            if batched {
+                report.count("atoms in a batch", &sphere_batch.count());
+                report.count("sticks in a batch", &stick_batch.count());
+                report.count("instances written this frame", &(sphere_batch.written()+stick_batch.written()));
+            }
+            report.heading("GPU memory");
+            report.memory("meshes", &(default_models.bytes() + miller_model.as_ref().map_or(0, |model| model.bytes())));
+            if batched {report.memory("instance buffers", &(sphere_batch.bytes()+stick_batch.bytes()))}
+            report.memory("render targets", &(fxaa.bytes() + oit.bytes() + stereo.bytes() + id_buffer.bytes()));
+            report.memory("textures (font, distance matrix)", &(text_system.bytes() + heat_map.bytes()));
+            if let Some(ref surface) = molecular_surface {report.memory("molecular surface", &surface.bytes())}
+            if let Some((ref positive, ref negative)) = lobes {report.memory("orbital", &(positive.bytes()+negative.bytes()))}
+            report.heading("CPU memory");
+            report.memory("atoms", &(atoms.len()*mem::size_of::<atom::Atom>()));
+            report.memory("bonds", &(bonds.len()*mem::size_of::<[usize;2]>()
+                                     + periodic_bonds.len()*mem::size_of::<([usize;2], [i32;3])>()));
+            if let Some(ref volume) = orbital {report.memory("orbital grid", &(volume.values().len()*mem::size_of::<f32>()))}
+            report.memory("trajectory frames", &(trajectory.frames().iter().map(|frame| frame.len()).sum::<usize>()
+                                                 *mem::size_of::<[f32;3]>()));
+            print!("{}", report.text());
+        }
+        // the frame's atoms (as spheres, even those drawn as ellipsoids), sticks and
+        // arrows' heads, without the labels, cell, planes or surface, to trace or export
+        let shapes = || -> Vec<raytrace::Shape> {
+            let atoms = molecule.atoms();
+            let sphere = |i : usize, shift : &[f32;3], fading : f32| {
+                let (atom, p) = (&atoms[i], atoms[i].position());
+                let colour = atom_colour(i, atom);
+                raytrace::Shape::Sphere {
+                    centre  : [p[0]+shift[0], p[1]+shift[1], p[2]+shift[2]],
+                    radius  : *atom.species().size()*atom_scale(i, atom),
+                    colour  : [colour[0], colour[1], colour[2]],
+                    opacity : *atom.opacity()*visibility(i)*fading,
+                }
+            };
+            copies.iter().map(|&(i, ref shift, _)| sphere(i, shift, 1.0))
+                .chain(ghosts.iter().map(|&(i, ref shift)| sphere(i, shift, ghost_opacity*bond_opacity)))
+                .chain(sticks.iter().map(|&(from, to, colour, radius, opacity)| {
+                    raytrace::Shape::Stick {from, to, radius, colour, opacity}
+                }))
+                .chain(arrow_heads.iter().map(|&(base, tip, colour, radius, opacity)| {
+                    raytrace::Shape::Cone {base, tip, radius, colour, opacity}
+                }))
+                .collect()
+        };
+        let light = [light_position[0], light_position[1], light_position[2]];
+        // a path-traced picture of the frame: render raytrace's before the run quits,
+        // and ctrl+S's in another thread, the window carrying on meanwhile
+        let traced_fname = match raytrace_fname {
+            Some(ref fname) if quitting => Some(fname.as_str()),
+            _ if trace_requested => Some(trace_fname),
+            _ => None,
+        };
+        let mut traced = None;
+        if let Some(fname) = traced_fname {
+            trace_requested = false;
+            let scene = raytrace::Scene::new(shapes(), &light, &background);
+            if quitting {
+                println!("Tracing {} paths through each pixel...", raytrace_samples);
+                let start = Instant::now();
+                traced = Some((fname.to_owned(), Ok(raytrace::render(scene, &camera, &raytrace_samples)), start));
+            } else if let Some(ref job) = trace_job {
+                println!("Still tracing {}", job.fname());
+            } else {
+                println!("Tracing {} paths through each pixel...", raytrace_samples);
+                trace_job = Some(raytrace::TraceJob::start(scene, &camera, &raytrace_samples, fname));
+            }
+        }
+        if let Some(done) = trace_job.as_ref().and_then(|job| job.receive()) {
+            let job = trace_job.take().unwrap();
+            traced = Some((job.fname().to_owned(), done, *job.started()));
+        }
+        if let Some((fname, done, start)) = traced {
+            let elapsed = start.elapsed();
+            match done.and_then(|(size, rgb)| screenshot::write_image(&fname, &size, &rgb)) {
+                Ok(()) => println!("Saved a traced picture to {} in {:.1} seconds", fname,
+                                   elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32*1e-9),
+                Err(message) => println!("Couldn't save the traced picture: {}", message),
+            }
+        }
+        // the same, as a POV-Ray scene with the view's camera
+        let povray_fname_now = match povray_fname {
+            Some(ref fname) if quitting => Some(fname.as_str()),
+            _ if povray_requested => Some(povray_shortcut_fname),
+            _ => None,
+        };
+        if let Some(fname) = povray_fname_now {
+            povray_requested = false;
+            match povray::write_scene(fname, &shapes(), &camera, &light, &background) {
+                Ok(()) => println!("Saved the view as a POV-Ray scene to {}", fname),
+                Err(message) => println!("Couldn't save the POV-Ray scene: {}", message),
+            }
+        }
+        // and flat, as an SVG drawing
+        let svg_fname_now = match svg_fname {
+            Some(ref fname) if quitting => Some(fname.as_str()),
+            _ if svg_requested => Some(svg_shortcut_fname),
+            _ => None,
+        };
+        if let Some(fname) = svg_fname_now {
+            svg_requested = false;
+            match svg::write_view(fname, &shapes(), &camera, &background) {
+                Ok(()) => println!("Saved the view as an SVG to {}", fname),
+                Err(message) => println!("Couldn't save the SVG: {}", message),
+            }
+        }
+        // the frame's atoms (as spheres, or their ellipsoids), sticks, arrows, surface
+        // and lobes as one mesh in world space, without the labels, cell or planes
+        let geometry_fname_now = match geometry_fname {
+            Some(ref fname) if quitting => Some(fname.as_str()),
+            _ if geometry_requested => Some(geometry_shortcut_fname),
+            _ => None,
+        };
+        if let Some(fname) = geometry_fname_now {
+            geometry_requested = false;
+            let mut scene_geometry = geometry::Geometry::new();
+            let atoms = molecule.atoms();
+            let mut add_atom = |i : usize, shift : &[f32;3], fading : f32| {
+                let (atom, p) = (&atoms[i], atoms[i].position());
+                let opacity = *atom.opacity()*visibility(i)*fading;
+                if opacity <= 0.0 {return}
+                let c = atom_colour(i, atom);
+                let colour = [c[0], c[1], c[2], opacity];
+                let centre = [p[0]+shift[0], p[1]+shift[1], p[2]+shift[2]];
+                let ellipsoid = atom.adp().as_ref().filter(|_| ellipsoids_visible).and_then(|adp| {
+                    ellipsoid::matrices(&centre, adp, &(ellipsoid_radius*atom_scale(i, atom)))
+                });
+                match ellipsoid {
+                    Some((model_matrix, normal_matrix)) => scene_geometry.add_model(
+                        default_models.fine_ellipsoid(), &model_matrix, &normal_matrix, &colour
+                    ),
+                    None => {
+                        let r = *atom.species().size()*atom_scale(i, atom);
+                        let model_matrix = Matrix::new([
+                            [r, 0.0, 0.0, centre[0]],
+                            [0.0, r, 0.0, centre[1]],
+                            [0.0, 0.0, r, centre[2]],
+                            [0.0, 0.0, 0.0, 1.0],
+                        ]);
+                        scene_geometry.add_model(default_models.fine_ellipsoid(), &model_matrix, &model_matrix, &colour);
+                    },
+                }
+            };
+            for &(i, ref shift, _) in &copies {add_atom(i, shift, 1.0)}
+            for &(i, ref shift) in &ghosts {add_atom(i, shift, ghost_opacity*bond_opacity)}
+            for &(from, to, colour, radius, opacity) in sticks.iter().filter(|stick| stick.4 > 0.0) {
+                let model_matrix = bonds::model_matrix(&from, &to, &radius);
+                scene_geometry.add_model(default_models.fine_cylinder(), &model_matrix, &model_matrix,
+                                         &[colour[0], colour[1], colour[2], opacity]);
+            }
+            for &(from, tip, colour, radius, opacity) in &arrow_heads {
+                let model_matrix = bonds::model_matrix(&from, &tip, &radius);
+                scene_geometry.add_model(default_models.cone(), &model_matrix, &model_matrix,
+                                         &[colour[0], colour[1], colour[2], opacity]);
+            }
+            match (molecular_surface.as_ref(), surface_display) {
+                (Some(surface), surface::SurfaceDisplay::Opaque) => scene_geometry.add_surface(surface, &1.0),
+                (Some(surface), surface::SurfaceDisplay::Translucent) => {
+                    scene_geometry.add_surface(surface, &surface::TRANSLUCENT_OPACITY)
+                },
+                _ => {},
+            }
+            if let (Some(&(ref positive, ref negative)), true) = (lobes.as_ref(), lobes_visible) {
+                scene_geometry.add_surface(positive, &lobe_opacities[0]);
+                scene_geometry.add_surface(negative, &lobe_opacities[1]);
+            }
+            match scene_geometry.write(fname) {
+                Ok(()) => println!("Saved the scene's geometry ({} triangles) to {}", scene_geometry.triangles().len(),
+                                   fname),
+                Err(message) => println!("Couldn't save the scene's geometry: {}", message),
+            }
+        }
+        let mut target = display.draw();
+        // everything in 3D (see scene_view.rs), the panels going over it
+        let view = SceneView {
+            molecule  : &molecule,
+            camera    : &camera,
+            resources : resources,
+
+            copies             : Some(&copies[..]),
+            operators          : &operators,
+            ghosts             : &ghosts,
+            ghost_opacity      : ghost_opacity*bond_opacity,
+            colours            : &shaded_colours,
+            scales             : &atom_scales,
+            visibilities       : &visibilities,
+            pick_scales        : &pick_scales,
+            sticks             : &sticks,
+            arrow_heads        : &arrow_heads,
+            labels             : &labels,
+            cell_labels        : &cell_labels,
+            measurement_labels : &measurement_labels,
+
+            sphere_batch : sphere_batch.buffer(),
+            stick_batch  : stick_batch.buffer(),
+            in_batch     : &batch_atoms,
+
+            surface         : molecular_surface.as_ref(),
+            surface_display : surface_display,
+            lobes           : lobes.as_ref().filter(|_| lobes_visible).map(|&(ref positive, ref negative)| (positive, negative)),
+            lobe_opacities  : lobe_opacities,
+            planes          : miller_model.as_ref().filter(|_| miller_visible),
+            plane_colour    : miller_colour,
+            plane_opacity   : miller_opacity,
+
+            light_position     : light_position,
+            background         : background,
+            depth_cue          : depth_cue,
+            depth_range        : depth_range,
+            slab               : slab,
+            shading            : shading,
+            wireframe_visible  : wireframe_visible,
+            id_view            : id_view,
+            ellipsoids_visible : ellipsoids_visible,
+            ellipsoid_radius   : ellipsoid_radius,
+            refined            : refined,
+            cell_edges_visible : cell_edges_visible,
+            cell_colour        : cell_colour,
+            label_height       : label_height,
+            label_colour       : label_colour,
+            measurement_colour : measurement_colour,
+            stereo_mode        : stereo_mode,
+            fxaa_enabled       : fxaa_enabled,
+        };
+        view.draw(&mut target);
+        // a print is the view drawn again off the screen, bigger, with the camera's
+        // aspect ratio fitted to it, in one image (even in stereo) and without the
+        // overlays
+        let printed_fname = match render_print_fname {
+            Some(ref fname) if quitting => Some(fname.as_str()),
+            _ if print_requested => Some(print_fname),
+            _ => None,
+        };
+        if let Some(fname) = printed_fname {
+            print_requested = false;
+            let size = print_size.pixels(&display.get_framebuffer_dimensions());
+            let mut print_camera = camera.clone();
+            print_camera.set_screen_size(&size.0, &size.1);
+            let printed = screenshot::render(&display, &size, |target| {
+                SceneView {camera : &print_camera, stereo_mode : StereoMode::Off, .. view}.draw(target)
+            }).and_then(|(size, rgb)| screenshot::write_image(fname, &size, &rgb));
+            match printed {
+                Ok(()) => println!("Saved a {}x{} print to {}", size.0, size.1, fname),
+                Err(message) => println!("Couldn't save the print: {}", message),
+            }
+        }
+        // each frame of a movie is drawn again off the screen like a print, at the
+        // movie's size, and the movie ends with the camera path or the trajectory
+        let mut movie_ended = false;
+        if let Some(ref mut recording) = movie {
+            let size = *recording.size();
+            let mut movie_camera = camera.clone();
+            movie_camera.set_screen_size(&size.0, &size.1);
+            let added = screenshot::render(&display, &size, |target| {
+                SceneView {camera : &movie_camera, stereo_mode : StereoMode::Off, .. view}.draw(target)
+            }).and_then(|(size, rgb)| recording.add_frame(&size, &rgb));
+            if let Err(message) = added {
+                println!("Stopped the movie: {}", message);
+                movie_ended = true;
+            }
+            movie_ended = movie_ended || if movie_trajectory {
+                *recording.frames() >= trajectory.frames().len()
+            } else {
+                playback.is_none()
+            };
+        }
+        if movie_ended {
+            movie_trajectory = false;
+            if let Some(recording) = movie.take() {
+                match recording.finish() {
+                    Ok(report) => println!("{}", report),
+                    Err(message) => println!("Couldn't finish the movie: {}", message),
+                }
+            }
+        }
+        if axes_visible {
+            axes::draw(&text_system, &mut target, default_models.cylinder(),
+                       &camera.quaternion().rotation_matrix());
+        }
+        notifications.expire();
+        notifications.draw(&text_system, &mut target);
+        // the sequence goes across the top, and the panels under it
+        let mut top = 10.0;
+        if sequence_visible {
+            top += sequence.draw(&text_system, &mut target, &selection, &selection_tint, &0.0);
+        }
+        highlighted = None;
+        let mut tooltip = None;
+        if heat_map_visible {
+            match heat_map.update(&molecule, &selection, minimum_image) {
+                Ok(()) => {
+                    let (_, h) = target.get_dimensions();
+                    let size = (0.5*h as f32).min(400.0);
+                    heat_map::draw(&mut heat_map, &mut target, [10.0, top], size);
+                    highlighted = heat_map.pair_at(mouse.position());
+                },
+                Err(message) => {
+                    heat_map_visible = false;
+                    notifications.warning(&message);
+                },
+            }
+        }
+        // the diagram goes down the left, under the distance matrix
+        if diagram_visible && structure_diagram.is_none() {
+            match diagram::Diagram::lay_out(&molecule, &bonds) {
+                Ok(laid_out) => structure_diagram = Some(laid_out),
+                Err(message) => {
+                    diagram_visible = false;
+                    notifications.warning(&message);
+                },
+            }
+        }
+        if let (Some(ref shown), true) = (structure_diagram.as_ref(), diagram_visible) {
+            let (_, h) = target.get_dimensions();
+            let size = (0.4*h as f32).min(400.0);
+            let corner = [10.0, if heat_map_visible {top + (0.5*h as f32).min(400.0) + 10.0} else {top}];
+            let (scale, origin) = shown.in_square(&size);
+            let pixel = |p : &[f32;2]| [corner[0] + origin[0] + scale*p[0], corner[1] + origin[1] - scale*p[1]];
+            text::draw_panel(&text_system, &mut target, corner, [size, size], [1.0, 1.0, 1.0, 0.9]);
+            let strokes : Vec<([f32;2], [f32;2])> = shown.strokes().iter().map(|&(from, to)| (pixel(&from), pixel(&to))).collect();
+            text::draw_lines(&text_system, &mut target, &strokes, 2.0, [0.0, 0.0, 0.0, 1.0]);
+            for (p, label) in shown.positions().iter().zip(shown.labels().iter()) {
+                let label_size = text::text_size(label, 1.5);
+                let at = pixel(p);
+                text::draw_text(&text_system, &mut target, label, [at[0]-label_size[0]/2.0, at[1]-label_size[1]/2.0],
+                                1.5, [0.0, 0.0, 0.0, 1.0]);
+            }
+        }
+        // the long tasks' panels, across the middle of the top, are their cancel buttons
+        let mut tasks = Vec::new();
+        if let Some(ref job) = surface_job {
+            tasks.push(progress::Task::new(&progress::TaskKind::Surface, "Finding the molecular surface",
+                                           &job.progress().fraction(), job.started(), &true));
+        }
+        if let Some(ref job) = trace_job {
+            tasks.push(progress::Task::new(&progress::TaskKind::Trace, &format!("Tracing {}", job.fname()),
+                                           &Some(job.fraction()), job.started(), &true));
+        }
+        if let Some(ref recording) = movie {
+            // to the end of the trajectory, or of the camera path
+            let total = if movie_trajectory {
+                trajectory.frames().len()
+            } else {
+                (camera_path.duration()*recording.frame_rate()).ceil() as usize
+            };
+            let fraction = if total > 0 {Some((*recording.frames() as f32/total as f32).min(1.0))} else {None};
+            tasks.push(progress::Task::new(&progress::TaskKind::Movie, &format!("Recording {}", recording.fname()),
+                                           &fraction, recording.started(), &true));
+        }
+        if let Some(ref external) = external_relaxation {
+            let label = format!("Relaxing with {} (or shift+F10): {} geometries so far", external.command(),
+                                external.steps());
+            tasks.push(progress::Task::new(&progress::TaskKind::Optimiser, &label, &None, external.started(), &true));
+        }
+        task_buttons = progress::draw(&text_system, &mut target, &top, &tasks);
+        let title = progress::window_title(&tasks);
+        if title != window_title {
+            if let Some(window) = display.get_window() {window.set_title(&title)}
+            window_title = title;
+        }
+        if let Some((i, j)) = highlighted {
+            tooltip = Some(measurement::Distance::between(&molecule, &i, &j, &minimum_image).label());
+        }
+        if sequence_visible {
+            if let Some(residue) = sequence.residue_at(mouse.position()) {
+                let residue = residue.residue();
+                tooltip = Some(format!("{} {} (chain {})", residue.name(), residue.number(), residue.chain()));
+            }
+        }
+        if let Some(label) = tooltip {
+            let cursor = mouse.position();
+            let size = text::text_size(&label, 2.0);
+            let position = [cursor[0] as f32 + 16.0, cursor[1] as f32 + 16.0];
+            text::draw_panel(&text_system, &mut target, [position[0]-4.0, position[1]-4.0],
+                             [size[0]+8.0, size[1]+8.0], [0.0, 0.0, 0.0, 0.75]);
+            text::draw_text(&text_system, &mut target, &label, position, 2.0, [1.0, 1.0, 1.0, 1.0]);
+        }
+        // the panels down the right-hand side, one under another
+        let mut right_top = top;
+        if hud_visible {
+            // what the slab leaves visible, as in the shaders
+            let in_slab = |point : &[f32;3], shift : &[f32;3]| {
+                let point = [point[0]+shift[0], point[1]+shift[1], point[2]+shift[2], 1.0];
+                let depth = (*camera.view_matrix() * point)[2];
+                depth >= slab[0] && depth <= slab[1]
+            };
+            let atoms = molecule.atoms();
+            let atoms_drawn = copies.iter().filter(|&&(i, ref shift, _)| in_slab(atoms[i].position(), shift)).count();
+            let bonds_drawn = if bond_lengths.is_empty() {0} else {
+                operators.iter().map(|operator| bonds.iter().filter(|bond| {
+                    let atom = &atoms[bond[0]];
+                    operator.applies_to(atom) && is_shown(bond[0]) && is_shown(bond[1]) && bond_style(*bond).is_some()
+                    && in_slab(atom.position(), &operator.shift(atom.position()))
+                }).count()).sum()
+            };
+            let mut lines = vec![format!("{} atoms, {} bonds drawn", atoms_drawn, bonds_drawn)];
+            if trajectory.frames().len() > 1 {
+                lines.push(match frame {
+                    Some(k) => format!("frame {} of {}", k+1, trajectory.frames().len()),
+                    None => format!("average of {} frames", trajectory.frames().len()),
+                });
+            }
+            if let Some(ref frame_stream) = frame_stream {
+                lines.push(format!("streaming from {}", frame_stream.address()));
+            }
+            let mut modes = vec![camera.projection().name().to_owned()];
+            if fxaa_enabled {modes.push("FXAA".to_owned())}
+            if stereo_mode != StereoMode::Off {modes.push(format!("stereo {}", stereo_mode.name()))}
+            if depth_cue != DepthCue::Off {modes.push(format!("depth cue {}", depth_cue.name()))}
+            if shading != Shading::Lit {modes.push(format!("{} shading", shading.name()))}
+            if wireframe_visible {modes.push("wireframe".to_owned())}
+            if id_view {modes.push("ID view".to_owned())}
+            if slab_enabled {modes.push("slab".to_owned())}
+            if ghost_enabled {modes.push("ghost".to_owned())}
+            if focus_enabled {modes.push("focus".to_owned())}
+            if rmsf_colouring {
+                modes.push("RMSF colours".to_owned());
+            } else if colour_scheme != colour::ColourScheme::Species {
+                modes.push(format!("colours by {}", colour_scheme.name()));
+            }
+            if bond_display != BondDisplay::Hidden {modes.push(format!("bonds {}", bond_display.name()))}
+            if bond_display != BondDisplay::Hidden && periodic_display != PeriodicBonds::Hidden && molecule.cell().is_some() {
+                modes.push(format!("periodic bonds {}", periodic_display.name()));
+            }
+            if label_content != LabelContent::Off {modes.push(format!("labels {}", label_content.name()))}
+            if !hidden_chains.is_empty() {modes.push(format!("chains {} hidden", hidden_chains.join(", ")))}
+            let hidden_objects : Vec<String> = objects.objects().iter().enumerate()
+                .filter(|&(_, object)| !object.is_visible()).map(|(k, _)| format!("{}", k+1)).collect();
+            if !hidden_objects.is_empty() {modes.push(format!("objects {} hidden", hidden_objects.join(", ")))}
+            if surface_display != surface::SurfaceDisplay::Hidden {modes.push(format!("surface {}", surface_display.name()))}
+            if orbital.is_some() && lobes_visible {modes.push(format!("orbital +/-{}", iso_level))}
+            if assembly_view {modes.push("assembly".to_owned())}
+            if packing_view {modes.push("crystal packing".to_owned())}
+            if tool != Tool::Select {modes.push(tool.name().to_owned())}
+            if binding_site.is_some() {modes.push("binding site".to_owned())}
+            if tube_display != TubeDisplay::Hidden {modes.push(format!("backbone {}", tube_display.name()))}
+            if arrow_display != ArrowDisplay::Hidden {modes.push(format!("arrows {}", arrow_display.name()))}
+            if relaxation.is_some() {modes.push("relaxing".to_owned())}
+            if let Some(ref external) = external_relaxation {modes.push(format!("relaxing with {}", external.command()))}
+            if let Some(ref vibration) = vibration {modes.push(format!("normal mode {}", vibration.mode()+1))}
+            if molecule.cell().is_some() && cell_edges_visible {modes.push("unit cell".to_owned())}
+            if let (Some(ref planes), true) = (miller, miller_visible) {
+                modes.push(format!("{} {} planes", miller_count, planes.name()));
+            }
+            if slideshow.is_some() {modes.push("slideshow".to_owned())}
+            if let Some(ref occlusion) = occlusion {
+                modes.push(format!("ambient occlusion, {} rays an atom", occlusion.samples()));
+            }
+            if refined {modes.push("refined".to_owned())}
+            if ellipsoids_visible {modes.push(format!("ellipsoids {:.0}%", 100.0*ellipsoid_probability))}
+            lines.push(modes.join(", "));
+            right_top += hud.draw(&text_system, &mut target, &lines) + 10.0;
+        }
+        if statistics_visible {
+            let statistics = if selection.is_empty() {
+                "Nothing selected (press E to select)".to_owned()
+            } else {
+                SelectionStatistics::new(&molecule, &selection).text()
+            };
+            let size = text::text_size(&statistics, 2.0);
+            let (w, _) = target.get_dimensions();
+            let left = w as f32 - size[0] - 30.0;
+            text::draw_panel(&text_system, &mut target, [left, right_top],
+                             [size[0]+20.0, size[1]+20.0], [0.0, 0.0, 0.0, 0.6]);
+            text::draw_text(&text_system, &mut target, &statistics, [left+10.0, right_top+10.0], 2.0,
+                            [1.0, 1.0, 1.0, 1.0]);
+            right_top += size[1] + 30.0;
+        }
+        if !measurements.is_empty() {
+            let list : Vec<String> = measurements.iter().enumerate()
+                .map(|(k, measurement)| format!("{}. {}", k+1, measurement.description(&molecule, &minimum_image)))
+                .collect();
+            let list = format!("Measurements (F5 clears)\n{}", list.join("\n"));
+            let size = text::text_size(&list, 2.0);
+            let (w, _) = target.get_dimensions();
+            let left = w as f32 - size[0] - 30.0;
+            text::draw_panel(&text_system, &mut target, [left, right_top],
+                             [size[0]+20.0, size[1]+20.0], [0.0, 0.0, 0.0, 0.6]);
+            text::draw_text(&text_system, &mut target, &list, [left+10.0, right_top+10.0], 2.0,
+                            [1.0, 1.0, 1.0, 1.0]);
+        }
+        // an empty window (nothing given, or nothing read) says how to fill it
+        if molecule.atoms().is_empty() && !headless {
+            let hint = "Drop a structure file here\n(or run oxide <file>)";
+            let size = text::text_size(hint, 3.0);
+            let (w, h) = target.get_dimensions();
+            let corner = [(w as f32 - size[0])/2.0, (h as f32 - size[1])/2.0];
+            text::draw_panel(&text_system, &mut target, [corner[0]-20.0, corner[1]-20.0],
+                             [size[0]+40.0, size[1]+40.0], [0.0, 0.0, 0.0, 0.6]);
+            text::draw_text(&text_system, &mut target, hint, corner, 3.0, [1.0, 1.0, 1.0, 1.0]);
+        }
+        if help_visible {
+            let help = help::help_text(&mouse_bindings, &key_bindings, &wheel_mode);
+            let size = text::text_size(&help, 2.0);
+            text::draw_panel(&text_system, &mut target, [10.0, 10.0],
+                             [size[0]+20.0, size[1]+20.0], [0.0, 0.0, 0.0, 0.75]);
+            text::draw_text(&text_system, &mut target, &help, [20.0, 20.0], 2.0, [1.0, 1.0, 1.0, 1.0]);
+        }
+        if let Some(ref command) = query_prompt {
+            // a bar across the top, with a cursor after the text
+            let line = format!("{}: {}_", if console {"Script"} else {"Select"}, command);
+            let (w, _) = target.get_dimensions();
+            let size = text::text_size(&line, 2.0);
+            text::draw_panel(&text_system, &mut target, [0.0, 0.0], [w as f32, size[1]+16.0],
+                             [0.0, 0.0, 0.0, 0.8]);
+            text::draw_text(&text_system, &mut target, &line, [8.0, 8.0], 2.0, [1.0, 1.0, 1.0, 1.0]);
+        }
+        if let Err(glium::SwapBuffersError::ContextLost) = target.finish() {
+            break;
+        }
+        if let Some((fname, reply)) = requested_screenshot.take() {
+            reply.send(screenshot::save(&display, &fname)).ok();
+        }
+        if quitting {
+            if let Some(ref fname) = graph_fname {
+                match topology::write_graph(fname, &molecule, &bonds) {
+                    Ok(()) => println!("Saved the bond graph to {}", fname),
+                    Err(message) => println!("Couldn't save the bond graph: {}", message),
+                }
+            }
+            if let Some(ref fname) = diagram_fname {
+                match diagram::Diagram::lay_out(&molecule, &bonds).and_then(|diagram| diagram.write_svg(fname)) {
+                    Ok(()) => println!("Saved the 2D diagram to {}", fname),
+                    Err(message) => println!("Couldn't save the 2D diagram: {}", message),
+                }
+            }
+            if let Some(ref fname) = structure_fname {
+                match file_output::write_structure_file(fname, &molecule) {
+                    Ok(()) => println!("Saved the structure to {}", fname),
+                    Err(message) => println!("Couldn't save the structure: {}", message),
+                }
+            }
+            if let Some(ref fname) = screenshot_fname {
+                match screenshot::save(&display, fname) {
+                    Ok(()) => println!("Saved the last frame to {}", fname),
+                    Err(message) => println!("Couldn't save a screenshot: {}", message),
+                }
+            }
+            return None;
+        }
+
+        // while a session is replayed, its events stand in for the user's
+        let mut events : Vec<glium::glutin::Event> = deferred_events.borrow_mut().drain(..)
+            .chain(display.poll_events()).collect();
+        if let Some(ref mut player) = player {
+            events.retain(|ev| match *ev {
+                glium::glutin::Event::Closed | glium::glutin::Event::Resized(_, _) => true,
+                _ => false,
+            });
+            events.extend(player.events(&frame_count));
+        }
+        if let Some(ref mut recorder) = recorder {
+            recorder.record(&frame_count, &events);
+        }
+        view_changed = !events.is_empty();
+        if view_changed {batch_dirty = true}
+        for ev in events {
+            // what the key is bound to, with the modifiers held (see keys.rs)
+            let mut key_action = None;
+            if let glium::glutin::Event::KeyboardInput(state, _, Some(key)) = ev {
+                mouse.key(&state, &key);
+                key_action = key_bindings.action(&key, mouse.modifiers());
+                let held = state == glium::glutin::ElementState::Pressed && query_prompt.is_none();
+                // letting go of a spin key stops it, whatever's held with it
+                for (k, spin) in [KeyAction::SpinAnticlockwise, KeyAction::SpinClockwise].iter().enumerate() {
+                    if key_bindings.is_bound(&key, spin) {spin_keys[k] = held && key_action == Some(*spin)}
+                }
+            }
+            match ev {
+                // ==============================
+                // Window is modified
+                // ==============================
+                glium::glutin::Event::Closed => return None,
+                glium::glutin::Event::DroppedFile(path) => {
+                    dropped.push((path.to_string_lossy().into_owned(), mouse.modifiers().shift()));
+                },
+                glium::glutin::Event::Resized(_, _) => {
+                    // the event gives the window size, which isn't the framebuffer
+                    // size on high-DPI screens. The FXAA and transparency targets
+                    // follow the frame's size by themselves.
+                    let (w, h) = display.get_framebuffer_dimensions();
+                    camera.set_screen_size(&w, &h);
+                },
+                
+                // ==============================
+                // Selection prompt is open
+                // ==============================
+                glium::glutin::Event::ReceivedCharacter(c) if query_prompt.is_some() => if !c.is_control() {
+                    query_prompt.as_mut().unwrap().push(c);
+                },
+                glium::glutin::Event::KeyboardInput (
+                    glium::glutin::ElementState::Pressed,
+                    _,
+                    Some(key)
+                ) if query_prompt.is_some() => match key {
+                    glium::glutin::VirtualKeyCode::Return => {
+                        let command = query_prompt.take().unwrap();
+                        if console {
+                            if command.trim().is_empty() {
+                            } else if script.is_some() {
+                                notifications.warning("A script's running already");
+                            } else {
+                                script = Some(script::Script::start("the console's script", &command));
+                            }
+                        } else if !command.trim().is_empty() { match selection::query::run(&command, &mut molecule, &fragments, &selection, &minimum_image) {
+                            Ok((result, name)) => {
+                                selection = result;
+                                notifications.info(&match name {
+                                    Some(name) => format!("Selected {} atoms as \"{}\"", selection.atoms().len(), name),
+                                    None => format!("Selected {} atoms", selection.atoms().len()),
+                                });
+                            },
+                            Err(message) => notifications.warning(&format!("Couldn't select \"{}\": {}", command, message)),
+                        }}
+                    },
+                    glium::glutin::VirtualKeyCode::Escape => query_prompt = None,
+                    glium::glutin::VirtualKeyCode::Back => {query_prompt.as_mut().unwrap().pop();},
+                    _ => {},
+                },
+
+                // ==============================
+                // Key is pressed
+                // ==============================
+                glium::glutin::Event::KeyboardInput (
+                    glium::glutin::ElementState::Pressed,
+                    _,
+                    Some(key)
+                ) if key_action.is_some() => match key_action.unwrap() {
+		    KeyAction::Quit => return None,
+                    KeyAction::Wireframe => {
+                        wireframe_visible = !wireframe_visible;
+                        println! ("Wireframe {}", if wireframe_visible {"on"} else {"off"});
+                    },
+                    KeyAction::Normals => {
+                        shading = if shading == Shading::Normals {Shading::Lit} else {Shading::Normals};
+                        println! ("Shading: {}", shading.name());
+                    },
+                    KeyAction::FlatShading => {
+                        shading = if shading == Shading::Flat {Shading::Lit} else {Shading::Flat};
+                        println! ("Shading: {}", shading.name());
+                    },
+                    KeyAction::IdView => {
+                        id_view = !id_view;
+                        println! ("ID view {}", if id_view {"on"} else {"off"});
+                    },
+                    KeyAction::Help => help_visible = !help_visible,
+                    KeyAction::Hud => hud_visible = !hud_visible,
+                    KeyAction::Axes => axes_visible = !axes_visible,
+                    KeyAction::NextTool => {
+                        tool = tool.next();
+                        tool_atoms.clear();
+                        println! ("Clicking atoms will now {}", tool.name());
+                    },
+                    KeyAction::NextElement => {
+                        element = (element+1) % tool::ELEMENTS.len();
+                        println! ("Adding {} atoms", tool::ELEMENTS[element]);
+                    },
+                    KeyAction::Sequence => if sequence.is_empty() {
+                        println! ("No sequence to show (load a PDB file)");
+                    } else {
+                        sequence_visible = !sequence_visible;
+                    },
+                    KeyAction::Backbone => if traces.is_empty() {
+                        println! ("No protein backbone to draw as a tube (load a PDB file)");
+                    } else {
+                        tube_display = tube_display.next();
+                        if tube_display == TubeDisplay::Putty && putty_values.is_none() {
+                            putty_values = tube::mobility(&molecule, &trajectory)
+                                .map(|values| tube::putty_values(&molecule, &traces, &values));
+                            if putty_values.is_none() {
+                                notifications.warning("No B-factors or trajectory to make putty from, so the tube stays even");
+                            }
+                        }
+                        println! ("The backbone is now {}", tube_display.name());
+                    },
+                    KeyAction::RelaxExternally => if external_relaxation.is_some() {
+                        cancel_requested = Some(progress::TaskKind::Optimiser);
+                    } else if molecule.atoms().is_empty() {
+                        println! ("No atoms to relax");
+                    } else if vibration.is_some() || relaxation.is_some() {
+                        println! ("Stop the normal mode (F11) or relaxation (F10) before running the optimiser");
+                    } else if let Some(ref command) = optimiser_command {
+                        match optimize::ExternalRelaxation::start(command, &molecule) {
+                            Ok(external) => {
+                                external_relaxation = Some(external);
+                                println! ("Relaxing {} atoms with {} (shift+F10 cancels)", molecule.atoms().len(), command);
+                            },
+                            Err(message) => notifications.error(&message),
+                        }
+                    } else {
+                        println! ("No optimiser to run (give one with e.g. --optimiser \"relax.sh {{}}\")");
+                    },
+                    KeyAction::Relax => match relaxation {
+                        Some(ref mut relaxation) => relaxation.stop(),
+                        None => if molecule.atoms().is_empty() {
+                            println! ("No atoms to relax");
+                        } else if external_relaxation.is_some() {
+                            println! ("The optimiser is already relaxing the structure (shift+F10 cancels it)");
+                        } else {
+                            relaxation = Some(optimize::Relaxation::new(&molecule, &bonds));
+                            println! ("Relaxing {} atoms (F10 stops)", molecule.atoms().len());
+                        },
+                    },
+                    KeyAction::NextMode | KeyAction::PreviousMode => if normal_modes.is_empty() {
+                        println! ("No normal modes to animate (load some with --modes)");
+                    } else {
+                        let current = vibration.as_ref().map(|vibration| *vibration.mode());
+                        if let Some(vibration) = vibration.take() {
+                            molecule.set_positions(vibration.equilibrium());
+                        }
+                        // round from no mode through each in turn
+                        let next = if key_action == Some(KeyAction::PreviousMode) {
+                            match current {
+                                None => Some(normal_modes.len()-1),
+                                Some(0) => None,
+                                Some(k) => Some(k-1),
+                            }
+                        } else {
+                            match current {
+                                None => Some(0),
+                                Some(k) if k+1 == normal_modes.len() => None,
+                                Some(k) => Some(k+1),
+                            }
+                        };
+                        match next {
+                            Some(k) => {
+                                vibration = Some(vibration::Vibration::new(&k, &molecule));
+                                println! ("Animating normal mode {} of {} ({:.1} cm^-1)",
+                                          k+1, normal_modes.len(), normal_modes[k].frequency());
+                            },
+                            None => println! ("Stopped animating normal modes"),
+                        }
+                    },
+                    KeyAction::SwingMore | KeyAction::SwingLess => {
+                        vibration_amplitude = if key_action == Some(KeyAction::SwingLess) {
+                            vibration_amplitude/1.5
+                        } else {
+                            vibration_amplitude*1.5
+                        }.max(0.01).min(5.0);
+                        println! ("Normal modes now move atoms up to {:.2} Angstroms", vibration_amplitude);
+                    },
+                    KeyAction::Ellipsoids => if ellipsoids_visible {
+                        ellipsoids_visible = false;
+                        println! ("Drawing atoms as spheres");
+                    } else if molecule.atoms().iter().any(|atom| atom.adp().is_some()) {
+                        ellipsoids_visible = true;
+                        println! ("Drawing thermal ellipsoids at {:.0}% probability", 100.0*ellipsoid_probability);
+                    } else {
+                        println! ("No displacement parameters to draw ellipsoids from (load a CIF with them)");
+                    },
+                    KeyAction::BindingSite => if binding_site.is_some() {
+                        binding_site = None;
+                        println! ("Showing everything");
+                    } else {
+                        let ligand = if selection.is_empty() {
+                            let ligands = binding_site::ligands(&molecule);
+                            if ligands.is_empty() {
+                                None
+                            } else {
+                                let k = next_ligand % ligands.len();
+                                next_ligand = k+1;
+                                println! ("Ligand {} of {}", k+1, ligands.len());
+                                Some(ligands[k].to_owned())
+                            }
+                        } else {
+                            Some(selection.to_owned())
+                        };
+                        match ligand {
+                            None => println! ("No ligand found (select one, or load a PDB file with one)"),
+                            Some(ligand) => {
+                                let site = binding_site::BindingSite::new(&molecule, &ligand,
+                                                                          &binding_site::SITE_CUTOFF);
+                                println! (
+                                    "Showing {} ligand atoms with the {} residues within {} of them; {} hydrogen bonds",
+                                    ligand.atoms().len(), site.residue_count(),
+                                    binding_site::SITE_CUTOFF, site.hydrogen_bonds().len()
+                                );
+                                for pair in site.hydrogen_bonds() {
+                                    println! ("  {}", Measurement::Distance(*pair).description(&molecule, &false));
+                                }
+                                let (centre, radius) = site.bounding_sphere(&molecule);
+                                camera.animate_to_sphere(&centre, &radius, &reset_seconds);
+                                binding_site = Some(site);
+                            },
+                        }
+                    },
+                    KeyAction::ReloadShaders => {
+                        shaders_requested = true;
+                    },
+                    KeyAction::ClearMeasurements => {
+                        measurements.clear();
+                        tool_atoms.clear();
+                        println! ("Cleared the measurements");
+                    },
+                    KeyAction::Query | KeyAction::Console => {
+                        query_prompt = Some(String::new());
+                        console = key_action == Some(KeyAction::Console);
+                    },
+                    KeyAction::Dismiss => notifications.dismiss_all(),
+		    KeyAction::Fxaa => {
+                        fxaa_enabled = !fxaa_enabled;
+                        println! (
+		            "FXAA is now {}",
+		            if fxaa_enabled { "on" } else { "off" }
+		        );
+	            },
+                    KeyAction::TurnSceneLeft | KeyAction::TurnSceneRight | KeyAction::TurnSceneUp
+                            | KeyAction::TurnSceneDown => {
+                        // the whole scene turns about its centre, and about the screen's
+                        // axes, as the camera sees them
+                        let view = camera.view_matrix().contents().to_owned();
+                        let (right, up) = ([view[0][0], view[0][1], view[0][2]], [view[1][0], view[1][1], view[1][2]]);
+                        let (axis, sign) = match key_action {
+                            Some(KeyAction::TurnSceneLeft) => (up, -1.0),
+                            Some(KeyAction::TurnSceneRight) => (up, 1.0),
+                            Some(KeyAction::TurnSceneUp) => (right, -1.0),
+                            _ => (right, 1.0),
+                        };
+                        let turn = Matrix::rotation(&axis, &(sign*object_turn_step));
+                        match scene_graph.transform(&scene_graph::ROOT, &turn, &objects, &mut molecule, &mut trajectory) {
+                            Ok(()) => edited = true,
+                            Err(message) => println! ("Couldn't turn the scene: {}", message),
+                        }
+                    },
+		    KeyAction::ZoomIn => {
+		        camera.zoom_in();
+			println! ("Zooming in");
+		    },
+		    KeyAction::ZoomOut => {
+		        camera.zoom_out();
+			println!("Zooming out");
+		    },
+		    KeyAction::AzimuthUp => {
+		        camera.azimuth_up();
+			println! ("Azimuthing up");
+		    },
+		    KeyAction::AzimuthDown => {
+		        camera.azimuth_down();
+			println! ("Azimuthing down");
+		    },
+                    KeyAction::HideChains => if selection.is_empty() {
+                        hidden_chains.clear();
+                        println! ("Showing every chain");
+                    } else {
+                        let atoms = molecule.atoms();
+                        for &i in selection.atoms() {
+                            if let Some(ref residue) = *atoms[i].residue() {
+                                if !hidden_chains.iter().any(|chain| chain == residue.chain()) {
+                                    hidden_chains.push(residue.chain().to_owned());
+                                }
+                            }
+                        }
+                        if hidden_chains.is_empty() {
+                            println! ("The selection isn't in any chain");
+                        } else {
+                            selection = Selection::new();
+                            println! ("Hiding chain{} {} (shift+H with nothing selected shows them again)",
+                                      if hidden_chains.len() == 1 {""} else {"s"}, hidden_chains.join(", "));
+                        }
+                    },
+		    KeyAction::OrbitLeft => {
+		        camera.orbit_left();
+			println! ("Orbiting left");
+		    },
+		    KeyAction::OrbitRight => {
+		        camera.orbit_right();
+			println! ("Orbiting right");
+		    },
+                    KeyAction::DepthCue => {
+                        depth_cue = depth_cue.next();
+                        println! ("Depth cueing is now {}", depth_cue.name());
+                    },
+                    KeyAction::ColourScheme => {
+                        // skipping schemes with nothing to go on, e.g. charges
+                        colour_scheme = colour_scheme.next();
+                        while !colour_scheme.applies_to(&molecule) {colour_scheme = colour_scheme.next()}
+                        rmsf_colouring = false;
+                        objects.colour(&colour_scheme, &mut molecule, &palette);
+                        println! ("Atoms are now coloured by {}", colour_scheme.name());
+                    },
+                    KeyAction::Slab => {
+                        slab_enabled = !slab_enabled;
+                        println! (
+                            "Slab mode is now {}",
+                            if slab_enabled { "on" } else { "off" }
+                        );
+                    },
+                    KeyAction::SlabThinner | KeyAction::SlabThicker if slab_enabled => {
+                        slab_half_width = if key_action == Some(KeyAction::SlabThinner) {
+                            (slab_half_width - slab_step).max(slab_step)
+                        } else {
+                            slab_half_width + slab_step
+                        };
+                        println! ("Slab is now {:.2} molecule widths thick", slab_half_width);
+                    },
+                    KeyAction::SlabNearer | KeyAction::SlabFurther if slab_enabled => {
+                        slab_offset += if key_action == Some(KeyAction::SlabFurther) {
+                            slab_step
+                        } else {
+                            -slab_step
+                        };
+                        println! ("Slab is now centred {:+.2} molecule radii behind the focus", slab_offset);
+                    },
+                    KeyAction::RadiusSource => {
+                        let source = atom_radii.source().next();
+                        atom_radii.set_source(&source);
+                        println! ("Atoms are now drawn with {} radii", source.name());
+                    },
+                    KeyAction::AtomsLarger | KeyAction::AtomsSmaller => {
+                        let scale = if key_action == Some(KeyAction::AtomsLarger) {
+                            atom_radii.scale()*atom_scale_step
+                        } else {
+                            atom_radii.scale()/atom_scale_step
+                        };
+                        atom_radii.set_scale(&scale);
+                        println! ("Atoms are now drawn at {:.2} times their radii", atom_radii.scale());
+                    },
+                    KeyAction::Arrows => {
+                        // skipping vectors the structure has none of
+                        let has = |display : &ArrowDisplay| arrows::vectors(&molecule, display).is_some();
+                        if ![ArrowDisplay::Forces, ArrowDisplay::Velocities, ArrowDisplay::Dipole].iter().any(|d| has(d)) {
+                            println! ("No forces, velocities or dipole to draw (give them in extended XYZ or with --vectors)");
+                        } else {
+                            arrow_display = arrow_display.next();
+                            while arrow_display != ArrowDisplay::Hidden && !has(&arrow_display) {
+                                arrow_display = arrow_display.next();
+                            }
+                            println! ("Arrows are now {}", arrow_display.name());
+                        }
+                    },
+                    KeyAction::ArrowsLonger | KeyAction::ArrowsShorter => {
+                        let scale = if key_action == Some(KeyAction::ArrowsLonger) {
+                            arrow_scale*arrow_scale_step
+                        } else {
+                            arrow_scale/arrow_scale_step
+                        };
+                        arrow_scale = scale.max(arrows::MIN_SCALE).min(arrows::MAX_SCALE);
+                        println! ("The longest arrow is now {:.2} Angstroms long", arrows::LONGEST*arrow_scale);
+                    },
+                    KeyAction::Ghost => {
+                        ghost_enabled = !ghost_enabled;
+                        molecule.set_opacity(if ghost_enabled { &ghost_opacity } else { &1.0 });
+                        println! (
+                            "Ghost mode is now {}",
+                            if ghost_enabled { "on" } else { "off" }
+                        );
+                    },
+                    KeyAction::Orbital => {
+                        if orbital.is_some() {
+                            lobes_visible = !lobes_visible;
+                            println! ("The orbital is now {}", if lobes_visible {"shown"} else {"hidden"});
+                        } else {
+                            println! ("No orbital to show (load a cube file)");
+                        }
+                    },
+                    KeyAction::Projection => {
+                        let projection = match *camera.projection() {
+                            Projection::Perspective  => Projection::Orthographic,
+                            Projection::Orthographic => Projection::Perspective,
+                        };
+                        camera.set_projection(&projection);
+                        println! ("Projection is now {}", projection.name());
+                    },
+                    KeyAction::Stereo => {
+                        stereo_mode = stereo_mode.next();
+                        println! ("Stereo is now {}", stereo_mode.name());
+                    },
+                    KeyAction::Undo | KeyAction::Redo => {
+                        let redo = key_action == Some(KeyAction::Redo);
+                        let done = if redo {
+                            history.redo(&mut molecule, &mut trajectory, &mut objects, &mut reference_positions, &frame)
+                        } else {
+                            history.undo(&mut molecule, &mut trajectory, &mut objects, &mut reference_positions, &frame)
+                        };
+                        match done {
+                            Some((description, renumbering)) => {
+                                selection = renumbering.selection(&selection);
+                                measurements = renumbering.measurements(&measurements);
+                                if renumbering != Renumbering::Unchanged {
+                                    tool_atoms.clear();
+                                    binding_site = None;
+                                    packing = None;
+                                }
+                                edited = true;
+                                println! ("{} {}", if redo { "Redid" } else { "Undid" }, description);
+                            },
+                            None => println! ("Nothing to {}", if redo { "redo" } else { "undo" }),
+                        }
+                    },
+                    KeyAction::WheelMode => {
+                        wheel_mode = wheel_mode.next();
+                        println! ("Scroll wheel now changes the {}", wheel_mode.name());
+                    },
+                    KeyAction::ShowObject | KeyAction::SelectObject | KeyAction::PutObjectBack => {
+                        // (the keys are in order, 1 to 9)
+                        let k = key as usize - glium::glutin::VirtualKeyCode::Key1 as usize;
+                        let done = if key_action == Some(KeyAction::PutObjectBack) {
+                            objects.get(&k).and_then(|_| scene_graph.object_node(&k)).and_then(|node| {
+                                scene_graph.put_back(&node, &objects, &mut molecule, &mut trajectory)
+                            }).map(|_| {
+                                edited = true;
+                                format!("Put object {} back", k+1)
+                            })
+                        } else if key_action == Some(KeyAction::SelectObject) {
+                            objects.get(&k).map(|object| object.name().to_owned()).map(|name| {
+                                selection = objects.atoms(&molecule, &k);
+                                format!("Selected object {} ({})", k+1, name)
+                            })
+                        } else {
+                            let visible = !objects.is_visible(&k);
+                            objects.set_visible(&k, &visible).map(|_| {
+                                format!("{} object {} ({})", if visible {"Showing"} else {"Hiding"}, k+1,
+                                        objects.objects()[k].name())
+                            })
+                        };
+                        match done {
+                            Ok(message) => println! ("{}", message),
+                            Err(message) => println! ("There's {}", message),
+                        }
+                    },
+                    KeyAction::PutSceneBack => {
+                        match scene_graph.put_back(&scene_graph::ROOT, &objects, &mut molecule, &mut trajectory) {
+                            Ok(()) => {
+                                edited = true;
+                                println! ("Put the whole scene back");
+                            },
+                            Err(message) => println! ("Couldn't put the scene back: {}", message),
+                        }
+                    },
+                    KeyAction::PlayTrajectory if trajectory.frames().len() > 1 => {
+                        trajectory_playing = !trajectory_playing;
+                        frame_shown_for = 0.0;
+                        println! ("{} the trajectory", if trajectory_playing {"Playing"} else {"Paused"});
+                    },
+                    KeyAction::NextFrame | KeyAction::PreviousFrame if trajectory.frames().len() > 1 => {
+                        let n_frames = trajectory.frames().len();
+                        let next = match (frame, key_action) {
+                            (Some(i), Some(KeyAction::NextFrame)) => (i+1)%n_frames,
+                            (Some(i), _) => (i+n_frames-1)%n_frames,
+                            (None, _) => 0,
+                        };
+                        frame = Some(next);
+                        trajectory.show_frame(&next, &mut molecule);
+                        println! ("Showing frame {} of {}", next+1, n_frames);
+                    },
+                    KeyAction::AverageStructure if trajectory.frames().len() > 1 => {
+                        if frame.is_some() {
+                            frame = None;
+                            molecule.set_positions(&trajectory.average_structure());
+                            println! ("Showing the average structure");
+                        } else {
+                            frame = Some(0);
+                            trajectory.show_frame(&0, &mut molecule);
+                            println! ("Showing frame 1 of {}", trajectory.frames().len());
+                        }
+                    },
+                    KeyAction::RmsfColouring if trajectory.frames().len() > 1 => {
+                        rmsf_colouring = !rmsf_colouring;
+                        if rmsf_colouring {
+                            molecule.colour_by_values(&trajectory.rmsf());
+                        } else {
+                            objects.colour(&colour_scheme, &mut molecule, &palette);
+                        }
+                        println! (
+                            "RMSF colouring is now {}",
+                            if rmsf_colouring { "on" } else { "off" }
+                        );
+                    },
+                    KeyAction::CellEdges | KeyAction::CellLabels | KeyAction::CellOrigin => if molecule.cell().is_none() {
+                        println! ("No unit cell to draw (load a CASTEP cell file or a CIF)");
+                    } else if key_action == Some(KeyAction::CellLabels) {
+                        cell_labels_visible = !cell_labels_visible;
+                        println! ("Lattice vector labels are now {}", if cell_labels_visible {"on"} else {"off"});
+                    } else if key_action == Some(KeyAction::CellOrigin) {
+                        cell_origin_visible = !cell_origin_visible;
+                        println! ("The cell's origin is now {}", if cell_origin_visible {"marked"} else {"unmarked"});
+                    } else {
+                        cell_edges_visible = !cell_edges_visible;
+                        println! ("The unit cell is now {}", if cell_edges_visible {"shown"} else {"hidden"});
+                    },
+                    KeyAction::AmbientOcclusion => {
+                        occlusion = match occlusion {
+                            Some(_) => None,
+                            None => Some(refine::Occlusion::new()),
+                        };
+                        println! ("Ambient occlusion is now {}",
+                                  if occlusion.is_some() {"traced while the view is still"} else {"off"});
+                    },
+                    KeyAction::LatticePlanes => if miller.is_none() {
+                        println! ("No lattice planes to draw (run with e.g. --miller \"1 1 0\" on a crystal)");
+                    } else {
+                        miller_visible = !miller_visible;
+                        println! ("The lattice planes are now {}", if miller_visible {"shown"} else {"hidden"});
+                    },
+                    KeyAction::OrbitalOut | KeyAction::OrbitalIn => {
+                        if orbital.is_some() {
+                            let up = key_action == Some(KeyAction::OrbitalOut);
+                            // (bigger lobes, at a lower level, for PageUp)
+                            iso_level *= if up {0.8} else {1.25};
+                            lobes = None;
+                            lobes_visible = true;
+                            println! ("The orbital is now drawn at +/-{}", iso_level);
+                        }
+                    },
+                    KeyAction::MorePlanes | KeyAction::FewerPlanes | KeyAction::PlanesMoreOpaque
+                            | KeyAction::PlanesLessOpaque => {
+                        if let (Some(planes), Some(cell)) = (miller, *molecule.cell()) {
+                            let up = key_action == Some(KeyAction::MorePlanes) || key_action == Some(KeyAction::PlanesMoreOpaque);
+                            let opacity = key_action == Some(KeyAction::PlanesMoreOpaque)
+                                || key_action == Some(KeyAction::PlanesLessOpaque);
+                            if opacity {
+                                let step = if up {0.1} else {-0.1};
+                                miller_opacity = (miller_opacity+step).max(0.1).min(1.0);
+                                println! ("The lattice planes' opacity is now {:.1}", miller_opacity);
+                            } else {
+                                let count = if up {miller_count+1} else {miller_count.saturating_sub(1)};
+                                // no more than cut the cell
+                                let cutting = planes.polygons(&cell, &miller::MAX_PLANES).len();
+                                let count = count.max(1).min(cutting.max(1));
+                                if count != miller_count {
+                                    miller_count = count;
+                                    miller_model = Some(miller_mesh(&planes, &cell, &miller_count));
+                                }
+                                println! ("Drawing {} {} planes, {:.3} Angstroms apart",
+                                          miller_count, planes.name(), planes.spacing(&cell));
+                            }
+                            miller_visible = true;
+                        }
+                    },
+                    KeyAction::Wrap => match *molecule.cell() {
+                        None => println! ("No unit cell to wrap the atoms into"),
+                        Some(_) if vibration.is_some() || relaxation.is_some() => {
+                            println! ("Stop the normal mode (F11) or relaxation (F10) before wrapping the atoms");
+                        },
+                        Some(cell) => {
+                            // every frame is wrapped, but only the one shown can be unwrapped again
+                            let from : Vec<[f32;3]> = molecule.atoms().iter().map(|atom| *atom.position()).collect();
+                            let to : Vec<[f32;3]> = from.iter().map(|position| cell.wrap(position)).collect();
+                            let moved = from.iter().zip(to.iter()).filter(|&(a, b)| a != b).count();
+                            trajectory.wrap(&cell);
+                            history.make(Edit::MoveAtoms {
+                                atoms : (0..from.len()).collect(),
+                                from  : from,
+                                to    : to,
+                            }, &mut molecule, &mut trajectory, &mut objects, &mut reference_positions, &frame);
+                            edited = true;
+                            println! ("Wrapped {} atoms into the cell", moved);
+                        },
+                    },
+                    KeyAction::MinimumImage if molecule.cell().is_some() => {
+                        minimum_image = !minimum_image;
+                        println! (
+                            "Minimum-image distances are now {}",
+                            if minimum_image { "on" } else { "off" }
+                        );
+                        if let Some(distance) = measurement::shortest_distance(&molecule, &minimum_image) {
+                            println!("Shortest distance: {}", distance.label());
+                        }
+                    },
+                    KeyAction::Svg => {
+                        svg_requested = true;
+                    },
+                    KeyAction::Fit => {
+                        camera.fit(&molecule, |atom| objects.radius(atom, &atom_radii));
+                        println! ("Fitting the molecule to the view");
+                    },
+                    KeyAction::ExportObj => {
+                        geometry_requested = true;
+                    },
+                    KeyAction::SelectNext => {
+                        // then each fragment, if there's more than one, then each named selection
+                        let symbols = selection::symbols(&molecule);
+                        let n_fragments = if fragment_count > 1 {fragment_count} else {0};
+                        let first_named = symbols.len()+n_fragments+2;
+                        selection_step = (selection_step+1)%(first_named+molecule.named_selections().len());
+                        selection = match selection_step {
+                            0 => Selection::new(),
+                            1 => Selection::all(&molecule),
+                            n if n < symbols.len()+2 => Selection::by_symbol(&molecule, &symbols[n-2]),
+                            n if n < first_named => Selection::by_fragment(&fragments, &(n-symbols.len()-2)),
+                            n => molecule.named_selections()[n-first_named].1.to_owned(),
+                        };
+                        println! (
+                            "Selected {}",
+                            match selection_step {
+                                0 => "nothing".to_owned(),
+                                1 => "everything".to_owned(),
+                                n if n < symbols.len()+2 => format!("every {} atom", symbols[n-2]),
+                                n if n < first_named => format!("fragment {} of {}", n-symbols.len()-1, fragment_count),
+                                n => format!("\"{}\"", molecule.named_selections()[n-first_named].0),
+                            }
+                        );
+                    },
+                    KeyAction::PeriodicBonds => if molecule.cell().is_none() {
+                        println! ("No unit cell for bonds to cross (load a CASTEP cell file or a CIF)");
+                    } else {
+                        periodic_display = periodic_display.next();
+                        println! ("Bonds across the cell's faces are now {} ({} of them)",
+                                  periodic_display.name(), periodic_bonds.len());
+                    },
+                    KeyAction::Bonds => {
+                        bond_display = bond_display.next();
+                        println! ("Bonds are now {}", bond_display.name());
+                        if bond_display == BondDisplay::Deviation {
+                            // list the most suspicious bonds
+                            let lengths = bonds::lengths(&molecule, &bonds);
+                            let mut deviations : Vec<(usize, f32)> = lengths.iter().enumerate()
+                                .map(|(k, length)| (k, (length-reference_lengths[k])/reference_lengths[k].max(1e-6)))
+                                .collect();
+                            deviations.sort_by(|a, b| b.1.abs().partial_cmp(&a.1.abs()).unwrap_or(::std::cmp::Ordering::Equal));
+                            for &(k, deviation) in deviations.iter().take(5) {
+                                let atoms = molecule.atoms();
+                                println! (
+                                    "  atoms {}-{} ({}-{}): {:.4} against {:.4} ({:+.1}%)",
+                                    bonds[k][0]+1, bonds[k][1]+1,
+                                    atoms[bonds[k][0]].species().symbol(),
+                                    atoms[bonds[k][1]].species().symbol(),
+                                    lengths[k], reference_lengths[k], 100.0*deviation
+                                );
+                            }
+                        }
+                    },
+                    KeyAction::Assembly => if molecule.assembly().is_empty() {
+                        println! ("The file doesn't give a biological assembly");
+                    } else {
+                        assembly_view = !assembly_view;
+                        packing_view = false;
+                        if assembly_view {
+                            println! ("Showing the biological assembly ({} copies)",
+                                      molecule.assembly().operators().len());
+                        } else {
+                            println! ("Showing the asymmetric unit");
+                        }
+                    },
+                    KeyAction::Packing => match *molecule.crystal() {
+                        None => println! ("The file doesn't give the crystal's symmetry"),
+                        Some(ref crystal) => {
+                            packing_view = !packing_view;
+                            assembly_view = false;
+                            if packing_view {
+                                if packing.is_none() {
+                                    packing = Some(packing::neighbours(&molecule, crystal,
+                                                                       &packing::CONTACT_DISTANCE));
+                                }
+                                if let Some((ref copies, ref contacts)) = packing {
+                                    println! (
+                                        "Showing {} neighbouring copies; {} atoms within {} of another copy",
+                                        copies.len(),
+                                        contacts.iter().filter(|d| d.is_some()).count(),
+                                        packing::CONTACT_DISTANCE
+                                    );
+                                }
+                            } else {
+                                println! ("Showing the asymmetric unit");
+                            }
+                        },
+                    },
+                    KeyAction::Labels => {
+                        label_content = label_content.next();
+                        println! ("Labels are now {}", label_content.name());
+                    },
+                    KeyAction::SaveStructure => {
+                        // PDB keeps residues, and extended XYZ the cell and charges
+                        let fname = if molecule.atoms().iter().any(|atom| atom.residue().is_some()) {
+                            "structure.pdb"
+                        } else {
+                            "structure.xyz"
+                        };
+                        match file_output::write_structure_file(fname, &molecule) {
+                            Ok(()) => notifications.info(&format!("Saved {} atoms and {} groups to {}",
+                                                                  molecule.atoms().len(),
+                                                                  molecule.named_selections().len(), fname)),
+                            Err(message) => notifications.error(&message),
+                        }
+                    },
+                    KeyAction::Opacity => {
+                        if selection.is_empty() {
+                            println! ("Nothing selected (press E to select)");
+                        } else {
+                            let current = *molecule.atoms()[selection.atoms()[0]].opacity();
+                            // the next step down, or back to opaque
+                            let opacity = opacity_steps.iter().cloned()
+                                .find(|&step| step < current-1e-3)
+                                .unwrap_or(opacity_steps[0]);
+                            history.make(Edit::set_opacity(&molecule, selection.atoms(), &opacity), &mut molecule,
+                                         &mut trajectory, &mut objects, &mut reference_positions, &frame);
+                            println! ("The selected atoms' opacity is now {}", opacity);
+                        }
+                    },
+                    KeyAction::SplitObject => if selection.is_empty() {
+                        println! ("Select the atoms to make an object of first");
+                    } else {
+                        let from = *molecule.atoms()[selection.atoms()[0]].object();
+                        let name = format!("part of {}", objects.get(&from).map(|object| object.name()).unwrap_or("the scene"));
+                        let object = objects.split(&name, &selection, &mut molecule);
+                        scene_graph.add_split(&object, &from);
+                        println! ("The selection is now object {} ({})", object+1, name);
+                    },
+                    KeyAction::ObjectColours | KeyAction::ObjectBonds | KeyAction::ObjectRadii => {
+                        // each steps through the scene's, then each of its own
+                        let done = objects.of_selection(&molecule, &selection).and_then(|k| {
+                            let object = objects.get(&k)?.to_owned();
+                            let shown = match key_action {
+                                Some(KeyAction::ObjectColours) => {
+                                    let scheme = match *object.colour_scheme() {
+                                        None => Some(colour::ColourScheme::Species),
+                                        Some(colour::ColourScheme::Residue) => None,
+                                        Some(scheme) => Some(scheme.next()),
+                                    };
+                                    let colours : Vec<[f32;3]> = molecule.atoms().iter().map(|atom| *atom.colour()).collect();
+                                    objects.set_colour_scheme(&k, &scheme)?;
+                                    rmsf_colouring = false;
+                                    objects.colour(&colour_scheme, &mut molecule, &palette);
+                                    history.record(Edit::set_object_colours(&objects, &molecule, &k,
+                                                                            object.colour_scheme(), &colours));
+                                    format!("coloured by {}", scheme.map_or("the scene's scheme", |scheme| scheme.name()))
+                                },
+                                Some(KeyAction::ObjectBonds) => {
+                                    let display = match *object.bond_display() {
+                                        None => Some(BondDisplay::Hidden),
+                                        Some(BondDisplay::Deviation) => None,
+                                        Some(display) => Some(display.next()),
+                                    };
+                                    history.make(Edit::SetObjectBonds {object : k, from : *object.bond_display(), to : display},
+                                                 &mut molecule, &mut trajectory, &mut objects, &mut reference_positions, &frame);
+                                    format!("bonds {}", display.map_or("as the scene's", |display| display.name()))
+                                },
+                                _ => {
+                                    let source = match *object.radius_source() {
+                                        None => Some(RadiusSource::Species),
+                                        Some(RadiusSource::VanDerWaals) => None,
+                                        Some(source) => Some(source.next()),
+                                    };
+                                    history.make(Edit::SetObjectRadii {object : k, from : *object.radius_source(), to : source},
+                                                 &mut molecule, &mut trajectory, &mut objects, &mut reference_positions, &frame);
+                                    format!("{} radii", source.map_or("the scene's", |source| source.name()))
+                                },
+                            };
+                            Ok(format!("Object {} ({}) now has {}", k+1, object.name(), shown))
+                        });
+                        match done {
+                            Ok(message) => println! ("{}", message),
+                            Err(message) => println! ("There's {}", message),
+                        }
+                    },
+                    KeyAction::TurnObjectLeft | KeyAction::TurnObjectRight | KeyAction::TurnObjectUp
+                            | KeyAction::TurnObjectDown | KeyAction::MoveObjectLeft | KeyAction::MoveObjectRight
+                            | KeyAction::MoveObjectUp | KeyAction::MoveObjectDown => {
+                        // as the whole scene turns, but the selection's object, or along the
+                        // screen's axes
+                        let view = camera.view_matrix().contents().to_owned();
+                        let (right, up) = ([view[0][0], view[0][1], view[0][2]], [view[1][0], view[1][1], view[1][2]]);
+                        let along = |axis : [f32;3], step : f32| [step*axis[0], step*axis[1], step*axis[2]];
+                        let transformation = match key_action {
+                            Some(KeyAction::TurnObjectLeft) => Matrix::rotation(&up, &-object_turn_step),
+                            Some(KeyAction::TurnObjectRight) => Matrix::rotation(&up, &object_turn_step),
+                            Some(KeyAction::TurnObjectUp) => Matrix::rotation(&right, &-object_turn_step),
+                            Some(KeyAction::TurnObjectDown) => Matrix::rotation(&right, &object_turn_step),
+                            Some(KeyAction::MoveObjectLeft) => Matrix::translation(&along(right, -object_move_step)),
+                            Some(KeyAction::MoveObjectRight) => Matrix::translation(&along(right, object_move_step)),
+                            Some(KeyAction::MoveObjectUp) => Matrix::translation(&along(up, object_move_step)),
+                            _ => Matrix::translation(&along(up, -object_move_step)),
+                        };
+                        let moved = objects.of_selection(&molecule, &selection)
+                            .and_then(|k| scene_graph.object_node(&k))
+                            .and_then(|node| {
+                                scene_graph.transform(&node, &transformation, &objects, &mut molecule, &mut trajectory)
+                            });
+                        match moved {
+                            Ok(()) => edited = true,
+                            Err(message) => println! ("Couldn't move the object: there's {}", message),
+                        }
+                    },
+                    KeyAction::FocusMode => {
+                        focus_enabled = !focus_enabled;
+                        if focus_enabled {
+                            println! ("Focus mode is now on: dimming all but the selection and {} around it",
+                                      focus.shell());
+                        } else {
+                            println! ("Focus mode is now off");
+                        }
+                    },
+                    KeyAction::MemoryStats => {
+                        stats_requested = true;
+                    },
+                    KeyAction::Diagram => {
+                        diagram_visible = !diagram_visible;
+                        println! (
+                            "The 2D diagram is now {}",
+                            if diagram_visible { "shown" } else { "hidden" }
+                        );
+                    },
+                    KeyAction::DistanceMatrix => {
+                        heat_map_visible = !heat_map_visible;
+                        println! (
+                            "The distance matrix is now {}",
+                            if heat_map_visible { "shown" } else { "hidden" }
+                        );
+                    },
+                    KeyAction::SaveSession => {
+                        let saved = session::Session::new(
+                            &loaded_fnames, &camera, &frame, &colour_scheme, &bond_display, &tube_display,
+                            &label_content, &surface_display, &hidden_chains, &selection, &measurements,
+                            &molecule, &objects, &scene_graph
+                        );
+                        match saved.save(session::SHORTCUT_FNAME) {
+                            Ok(()) => notifications.info(&format!("Saved the session to {} (load it with oxide {})",
+                                                                  session::SHORTCUT_FNAME, session::SHORTCUT_FNAME)),
+                            Err(message) => notifications.error(&message),
+                        }
+                    },
+                    KeyAction::Raytrace => {
+                        trace_requested = true;
+                    },
+                    KeyAction::Surface => {
+                        surface_display = surface_display.next();
+                        println! ("The molecular surface is now {}", surface_display.name());
+                    },
+                    KeyAction::Statistics => {
+                        statistics_visible = !statistics_visible;
+                        println! (
+                            "Selection statistics are now {}",
+                            if statistics_visible { "shown" } else { "hidden" }
+                        );
+                    },
+                    KeyAction::Keyframe => {
+                        let time = camera_path.keyframes().len() as f32*keyframe_interval;
+                        camera_path.add_keyframe(&Keyframe::from_camera(&camera, &time));
+                        println! ("Recorded keyframe {}", camera_path.keyframes().len());
+                    },
+                    KeyAction::Print => {
+                        print_requested = true;
+                    },
+                    KeyAction::RecordMovie => {
+                        if movie.is_some() {
+                            // (the movie ends with the path)
+                            playback = None;
+                        } else if camera_path.keyframes().len() > 1 {
+                            match movie::Movie::start(movie_shortcut_fname, &display.get_framebuffer_dimensions(),
+                                                      &frame_rate) {
+                                Ok(started) => {
+                                    println! ("Recording the camera path into {}", movie_shortcut_fname);
+                                    movie = Some(started);
+                                    playback = Some(Instant::now());
+                                },
+                                Err(message) => println! ("Couldn't make a movie: {}", message),
+                            }
+                        } else {
+                            println! ("Record at least two keyframes with N first");
+                        }
+                    },
+                    KeyAction::PlayKeyframes => {
+                        if playback.is_some() {
+                            playback = None;
+                            println! ("Stopped playing the camera path");
+                        } else if camera_path.keyframes().len() > 1 {
+                            playback = Some(Instant::now());
+                            println! (
+                                "Playing {} keyframes over {} seconds",
+                                camera_path.keyframes().len(),
+                                camera_path.duration()
+                            );
+                        } else {
+                            println! ("Record at least two keyframes with N first");
+                        }
+                    },
+                    KeyAction::ClearKeyframes => {
+                        camera_path.clear();
+                        playback = None;
+                        println! ("Cleared the camera path");
+                    },
+                    KeyAction::PovRay => {
+                        povray_requested = true;
+                    },
+                    KeyAction::ResetCamera => {
+                        let orientation = Camera::orientation_from_angles (
+                            &camera_theta_degrees,
+                            &camera_phi_degrees,
+                            &camera_psi_degrees
+                        );
+                        camera.animate_to_focus(&camera_focus, &camera_r, &orientation, &reset_seconds);
+                        println! ("Resetting camera");
+                    },
+		    _ => {},
+                },
+
+                // ==============================
+                // Mouse is used
+                // ==============================
+                glium::glutin::Event::MouseInput(glium::glutin::ElementState::Released, button) if drag_start.is_some() => {
+                    mouse.button(&glium::glutin::ElementState::Released, &button);
+                    let (atoms, from) = drag_start.take().unwrap();
+                    let to = atoms.iter().map(|&i| *molecule.atoms()[i].position()).collect();
+                    history.record(Edit::MoveAtoms {atoms : atoms, from : from, to : to});
+                },
+                glium::glutin::Event::MouseInput(state, button) => if let Some(cursor) = mouse.button(&state, &button) {
+                    let on_cancel = task_buttons.iter().find(|&&(_, corner, size)| {
+                        let (x, y) = (cursor[0] as f32, cursor[1] as f32);
+                        x >= corner[0] && x <= corner[0]+size[0] && y >= corner[1] && y <= corner[1]+size[1]
+                    }).map(|&(kind, _, _)| kind);
+                    let on_sequence = if sequence_visible {sequence.residue_at(&cursor)} else {None};
+                    if on_cancel.is_some() {
+                        cancel_requested = on_cancel;
+                    } else if let Some(residue) = on_sequence {
+                        selection = residue.atoms().to_owned();
+                        let (centre, radius) = selection.bounding_sphere(&molecule);
+                        camera.animate_to_sphere(&centre, &radius, &reset_seconds);
+                        let residue = residue.residue();
+                        println! ("Selected {} {} of chain {}", residue.name(), residue.number(), residue.chain());
+                    } else {
+                        // (batched atoms are picked by ray, since drawing each into the
+                        // picking buffer is what batching saves)
+                        let picked = if cpu_picking || sphere_batch.buffer().is_some() {
+                            let (origin, direction) = camera.ray(&cursor);
+                            // only pick what the slab leaves visible
+                            let is_visible = |point : &[f32;3]| {
+                                let depth = (*camera.view_matrix() * [point[0], point[1], point[2], 1.0])[2];
+                                depth >= slab[0] && depth <= slab[1]
+                            };
+                            let radius = |atom : &atom::Atom| objects.radius(atom, &atom_radii);
+                            picking::pick(&molecule, radius, &origin, &direction, is_visible).map(|(i, _)| i)
+                        } else {
+                            let pick_vp_matrix = id_buffer::pick_matrix(&cursor, display.get_framebuffer_dimensions())
+                                               * materials.atoms().offset_matrix(camera.vp_matrix());
+                            // picking any copy of an atom picks the atom
+                            id_buffer::pick(&id_buffer, |target| {
+                                for &(i, ref shift, _) in &copies {
+                                    let atom = &molecule.atoms()[i];
+                                    let scale = objects.factor(atom, &atom_radii);
+                                    let model_matrix = assembly::shifted(atom.model_matrix(), shift)
+                                        *Matrix::scaling(&[scale;3]);
+                                    let mv_matrix = *camera.view_matrix() * model_matrix;
+                                    let mvp_matrix = pick_vp_matrix * model_matrix;
+                                    target.draw(
+                                        default_models.sphere().vertex_buffer(),
+                                        default_models.sphere().index_buffer(),
+                                        &default_models.sphere().picking_program(),
+                                        &uniform!{
+                                            mv_matrix  : mv_matrix.contents().to_owned(),
+                                            mvp_matrix : mvp_matrix.contents().to_owned(),
+                                            size       : objects.radius(atom, &atom_radii),
+                                            slab       : slab,
+                                            id         : id_buffer::id_colour(i),
+                                        },
+                                        &atom_params,
+                                    ).unwrap();
+                                }
+                            })
+                        };
+                        if tool == Tool::Add {
+                            let position = camera.point_on_focus_plane(&cursor);
+                            let species = default_species.by_symbol(tool::ELEMENTS[element]).unwrap();
+                            // (it goes in the object of the atom clicked on, if any)
+                            let mut atom = atom::Atom::new(species, &position);
+                            atom.set_object(&picked.map_or(objects::NO_OBJECT, |i| *molecule.atoms()[i].object()));
+                            history.make(Edit::AddAtom(atom), &mut molecule,
+                                         &mut trajectory, &mut objects, &mut reference_positions, &frame);
+                            edited = true;
+                            println! ("Added {} atom {}", tool::ELEMENTS[element], molecule.atoms().len());
+                        } else if let (Some(i), Tool::Delete) = (picked, tool) {
+                            println! ("Deleted atom {} ({})", i+1, molecule.atoms()[i].species().symbol());
+                            let edit = Edit::delete_atom(&molecule, &trajectory, &reference_positions, &i);
+                            let renumbering = history.make(edit, &mut molecule, &mut trajectory, &mut objects,
+                                                           &mut reference_positions, &frame);
+                            selection = renumbering.selection(&selection);
+                            measurements = renumbering.measurements(&measurements);
+                            tool_atoms.clear();
+                            highlighted = None;
+                            binding_site = None;
+                            packing = None;
+                            edited = true;
+                        } else if let (Some(i), true) = (picked, tool.atoms_needed() > 0) {
+                            // the same atom twice in a row doesn't measure anything
+                            if tool_atoms.last() != Some(&i) {tool_atoms.push(i)}
+                            if tool_atoms.len() == tool.atoms_needed() {
+                                if let Some(measurement) = Measurement::of(&tool_atoms) {
+                                    let description = measurement.description(&molecule, &minimum_image);
+                                    println! ("Measured {}", description);
+                                    if !measurements.contains(&measurement) {measurements.push(measurement)}
+                                }
+                                tool_atoms.clear();
+                            } else {
+                                println! ("Picked atom {} ({} of {})", i+1, tool_atoms.len(), tool.atoms_needed());
+                            }
+                        } else if let Some(i) = picked {
+                            let selected = selection.toggle(&i);
+                            println! (
+                                "{} atom {} ({}), {} selected",
+                                if selected { "Selected" } else { "Deselected" },
+                                i+1,
+                                molecule.atoms()[i].species().symbol(),
+                                selection.atoms().len()
+                            );
+                        }
+                    }
+                },
+                glium::glutin::Event::MouseMoved(x, y) => if let Some(drag) = mouse.moved(&x, &y) {
+                    match *drag.action() {
+                        MouseAction::Rotate if tool == Tool::Drag && !selection.is_empty() => {
+                            let vector = camera.drag_vector(&drag.delta());
+                            if drag_start.is_none() {
+                                let positions = selection.atoms().iter().map(|&i| *molecule.atoms()[i].position()).collect();
+                                drag_start = Some((selection.atoms().to_owned(), positions));
+                            }
+                            for &i in selection.atoms() {
+                                let mut position = *molecule.atoms()[i].position();
+                                for k in 0..3 {position[k] += vector[k]}
+                                molecule.move_atom(&i, &position);
+                                if let Some(k) = frame {trajectory.set_position(&k, &i, &position)}
+                            }
+                            edited = true;
+                        },
+                        MouseAction::Rotate => camera.arcball(drag.from(), drag.to()),
+                        MouseAction::Spin => camera.rotate(
+                            &[0.0, 0.0, 1.0],
+                            &(-drag.delta()[0]*spin_sensitivity)
+                        ),
+                        MouseAction::Pan => camera.pan(&drag.delta()),
+                        MouseAction::Zoom => camera.dolly(&(drag.delta()[1]*zoom_sensitivity)),
+                    }
+                },
+                glium::glutin::Event::MouseWheel(delta, _) => if sequence_visible && sequence.contains(mouse.position()) {
+                    // scrolling towards the user goes down the sequence
+                    sequence.scroll(&-mouse.scrolled(&delta));
+                } else {
+                    // scrolling away from the user zooms in
+                    let factor = (-mouse.scrolled(&delta)*wheel_sensitivity).exp();
+                    match wheel_mode {
+                        WheelMode::Dolly => {
+                            let dr = camera.r()*(factor-1.0);
+                            camera.dolly(&dr);
+                        },
+                        WheelMode::FieldOfView => camera.scale_field_of_view(&factor),
+                    }
+                },
+
+                // ==============================
+                // Other
+                // ==============================
+                _ => ()
+            }
+        }
+    }
+    // the context was lost: what's kept, to carry on with in a new one
+    Some(recovery::Recovery {
+        molecule            : molecule,
+        trajectory          : trajectory,
+        objects             : objects,
+        scene_graph         : scene_graph,
+        loaded_fnames       : loaded_fnames,
+        orbital             : orbital,
+        miller              : miller,
+        normal_modes        : normal_modes,
+        reference_positions : reference_positions,
+        history             : history,
+        measurements        : measurements,
+        frame_count         : frame_count,
+        recorder            : recorder,
+        player              : player,
+        script              : script,
+        remote              : remote,
+        frame_stream        : frame_stream,
+        camera              : camera,
+        selection           : selection,
+        frame               : frame,
+        colour_scheme       : colour_scheme,
+        bond_display        : bond_display,
+        tube_display        : tube_display,
+        label_content       : label_content,
+        surface_display     : surface_display,
+        atom_radii          : atom_radii,
+        hidden_chains       : hidden_chains,
+        recoveries          : recoveries+1,
+    })
+}
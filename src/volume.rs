@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+// ============================================================
+// Volume
+// ============================================================
+/// Values on a grid of points, e.g. a density or an orbital from a cube file. The
+/// grid's axes needn't be at right angles.
+pub struct Volume {
+    /// Where the first point is, in Angstroms.
+    _origin : [f32;3],
+    /// The step from one point to the next along each of the grid's axes.
+    _axes   : [[f32;3];3],
+    /// How many points there are along each axis.
+    _shape  : [usize;3],
+    /// The values, with the third axis changing fastest (as cube files have them).
+    _values : Vec<f32>,
+}
+
+impl Volume {
+    pub fn new(in_origin : &[f32;3], in_axes : &[[f32;3];3], in_shape : &[usize;3], in_values : Vec<f32>)
+               -> Volume {
+        Volume {
+            _origin : *in_origin,
+            _axes   : *in_axes,
+            _shape  : *in_shape,
+            _values : in_values,
+        }
+    }
+
+    pub fn values(&self) -> &Vec<f32> {&self._values}
+    pub fn values_mut(&mut self) -> &mut Vec<f32> {&mut self._values}
+
+    /// Where a point's value is in values().
+    pub fn index(&self, in_point : &[usize;3]) -> usize {
+        (in_point[0]*self._shape[1]+in_point[1])*self._shape[2]+in_point[2]
+    }
+
+    /// Where a point of the grid is, in Angstroms.
+    pub fn position(&self, in_point : &[usize;3]) -> [f32;3] {
+        let mut position = self._origin;
+        for axis in 0..3 {
+            for k in 0..3 {
+                position[k] += in_point[axis] as f32*self._axes[axis][k];
+            }
+        }
+        position
+    }
+
+    /// The same grid with every value the other way round, so the negative lobes of
+    /// an orbital come out as isosurfaces like the positive ones.
+    pub fn negated(&self) -> Volume {
+        Volume::new(&self._origin, &self._axes, &self._shape, self._values.iter().map(|v| -v).collect())
+    }
+
+    /// How fast the value changes at a point, per Angstrom, from its neighbours.
+    fn gradient(&self, in_point : &[usize;3]) -> [f32;3] {
+        // along each of the grid's axes, per step
+        let mut steps = [0.0f32;3];
+        for axis in 0..3 {
+            let c = in_point[axis];
+            let (down, up) = (c.saturating_sub(1), (c+1).min(self._shape[axis]-1));
+            let (mut below, mut above) = (*in_point, *in_point);
+            below[axis] = down;
+            above[axis] = up;
+            steps[axis] = (self._values[self.index(&above)]-self._values[self.index(&below)])/(up-down).max(1) as f32;
+        }
+        // then in space, through the inverse transpose of the axes
+        let a = &self._axes;
+        let cross = |u : &[f32;3], v : &[f32;3]| [u[1]*v[2]-u[2]*v[1], u[2]*v[0]-u[0]*v[2], u[0]*v[1]-u[1]*v[0]];
+        let duals = [cross(&a[1], &a[2]), cross(&a[2], &a[0]), cross(&a[0], &a[1])];
+        let volume = a[0][0]*duals[0][0]+a[0][1]*duals[0][1]+a[0][2]*duals[0][2];
+        let mut gradient = [0.0f32;3];
+        for axis in 0..3 {
+            for k in 0..3 {
+                gradient[k] += steps[axis]*duals[axis][k]/volume;
+            }
+        }
+        gradient
+    }
+
+    /// The vertices (with their normals) and triangles of the surface where the
+    /// value is `in_level`, found by marching tetrahedra. The normals point down
+    /// the values, out of where they're above the level.
+    pub fn isosurface(&self, in_level : &f32) -> (Vec<[f32;3]>, Vec<[f32;3]>, Vec<u32>) {
+        let n = self._shape;
+        let mut positions : Vec<[f32;3]> = Vec::new();
+        let mut normals : Vec<[f32;3]> = Vec::new();
+        let mut indices : Vec<u32> = Vec::new();
+        if n.iter().any(|&points| points < 2) {return (positions, normals, indices)}
+
+        // each cube of the grid is split into six tetrahedra round the diagonal from
+        // corner 0 to corner 7, which line up with the neighbouring cubes'
+        let corner = |c : usize| [c & 1, (c >> 1) & 1, (c >> 2) & 1];
+        let tetrahedra = [[0, 7, 1, 3], [0, 7, 3, 2], [0, 7, 2, 6], [0, 7, 6, 4], [0, 7, 4, 5], [0, 7, 5, 1]];
+        // each vertex is on an edge of the grid, shared between tetrahedra
+        let mut on_edge : HashMap<(usize, usize), u32> = HashMap::new();
+        for x in 0..n[0]-1 {
+            for y in 0..n[1]-1 {
+                for z in 0..n[2]-1 {
+                    let corners : Vec<[usize;3]> = (0..8).map(|c| {
+                        let o = corner(c);
+                        [x+o[0], y+o[1], z+o[2]]
+                    }).collect();
+                    let values : Vec<f32> = corners.iter().map(|c| self._values[self.index(c)]).collect();
+                    if values.iter().all(|v| v > in_level) || values.iter().all(|v| v <= in_level) {continue}
+                    for tetrahedron in &tetrahedra {
+                        let (inside, outside) : (Vec<usize>, Vec<usize>) = tetrahedron.iter()
+                            .partition(|&&c| values[c] > *in_level);
+                        let mut vertex = |a : usize, b : usize| -> u32 {
+                            let (ia, ib) = (self.index(&corners[a]), self.index(&corners[b]));
+                            let key = (ia.min(ib), ia.max(ib));
+                            if let Some(&v) = on_edge.get(&key) {return v}
+                            let t = (in_level-values[a])/(values[b]-values[a]);
+                            let (pa, pb) = (self.position(&corners[a]), self.position(&corners[b]));
+                            let (na, nb) = (self.gradient(&corners[a]), self.gradient(&corners[b]));
+                            let mut normal = [0.0f32;3];
+                            let mut position = [0.0f32;3];
+                            for k in 0..3 {
+                                position[k] = pa[k]+t*(pb[k]-pa[k]);
+                                normal[k] = -(na[k]+t*(nb[k]-na[k]));
+                            }
+                            let length = (normal[0]*normal[0]+normal[1]*normal[1]+normal[2]*normal[2]).sqrt().max(1e-12);
+                            positions.push(position);
+                            normals.push([normal[0]/length, normal[1]/length, normal[2]/length]);
+                            let v = (positions.len()-1) as u32;
+                            on_edge.insert(key, v);
+                            v
+                        };
+                        match (inside.len(), outside.len()) {
+                            (1, 3) | (3, 1) => {
+                                let (lone, others) = if inside.len() == 1 {(inside[0], &outside)} else {(outside[0], &inside)};
+                                let triangle = [vertex(lone, others[0]), vertex(lone, others[1]), vertex(lone, others[2])];
+                                indices.extend_from_slice(&triangle);
+                            },
+                            (2, 2) => {
+                                let (a, b, c, d) = (inside[0], inside[1], outside[0], outside[1]);
+                                let quad = [vertex(a, c), vertex(a, d), vertex(b, d), vertex(b, c)];
+                                indices.extend_from_slice(&[quad[0], quad[1], quad[2], quad[0], quad[2], quad[3]]);
+                            },
+                            _ => {},
+                        }
+                    }
+                }
+            }
+        }
+        (positions, normals, indices)
+    }
+}
@@ -0,0 +1,61 @@
+use std::fs;
+use std::time::{Instant, SystemTime};
+
+/// How often the files are looked at, in seconds.
+const POLL_SECONDS : f32 = 0.5;
+
+/// What changes when a file's written again: when it was modified and how big it is.
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(in_fname : &str) -> Stamp {
+    let metadata = fs::metadata(in_fname).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+// ============================================================
+// File Watch
+// ============================================================
+/// Watches the structure files loaded (--watch), so that when a program writes one
+/// again, a geometry optimisation say, the viewer can read it back in. A file is
+/// only given as changed once it's stopped changing, for a look at it or two, so
+/// it isn't read half written.
+pub struct FileWatch {
+    /// Each file, how it was when last looked at, and whether it's changed since
+    /// it was last given as changed.
+    _files  : Vec<(String, Stamp, bool)>,
+    _polled : Instant,
+}
+
+impl FileWatch {
+    pub fn new(in_fnames : &[String]) -> FileWatch {
+        FileWatch {
+            _files  : in_fnames.iter().map(|fname| (fname.to_owned(), stamp(fname), false)).collect(),
+            _polled : Instant::now(),
+        }
+    }
+
+    /// The files that have been written again and since left alone, if it's time
+    /// to look. `in_fnames` are the files loaded now; if they aren't the ones being
+    /// watched, those are watched instead, from as they are now.
+    pub fn poll(&mut self, in_fnames : &[String]) -> Vec<String> {
+        if !self._files.iter().map(|&(ref fname, _, _)| fname).eq(in_fnames.iter()) {
+            *self = FileWatch::new(in_fnames);
+            return Vec::new();
+        }
+        let elapsed = self._polled.elapsed();
+        if (elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32*1e-9) < POLL_SECONDS {return Vec::new()}
+        self._polled = Instant::now();
+        let mut changed = Vec::new();
+        for &mut (ref fname, ref mut last, ref mut changing) in &mut self._files {
+            let now = stamp(fname);
+            if now != *last {
+                *last = now;
+                *changing = true;
+            } else if *changing && now.is_some() {
+                *changing = false;
+                changed.push(fname.to_owned());
+            }
+        }
+        changed
+    }
+}
@@ -0,0 +1,265 @@
+//! Golden-image tests: each scene is drawn in software (see --software), by
+//! replaying a session on a structure, and the last frame is compared with the
+//! image stored for it in test/golden (as a PNG), so changes to shading or
+//! representations don't slip through unnoticed. Differences a viewer wouldn't
+//! notice (e.g. from another version of Mesa) are allowed.
+//!
+//! Run with OXIDE_BLESS=1 to save the frames drawn as the new golden images, after
+//! a change meant to alter them or for a new scene; without it, a scene with no
+//! golden image fails. The tests are skipped on machines without libOSMesa, and
+//! aren't built without the graphics feature, as the viewer isn't.
+
+#![cfg(feature = "graphics")]
+
+extern crate miniz_oxide;
+extern crate oxide;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use oxide::screenshot;
+
+/// Each scene's name, which its session and golden image are named after, and the
+/// structure the session is replayed on.
+const SCENES : [(&'static str, &'static str); 4] = [
+    ("water"            , "test/water.xyz"),
+    ("water_bonds"      , "test/water.xyz"),
+    ("salt_cell"        , "test/salt.cell"),
+    ("quartz_ellipsoids", "test/quartz.cif"),
+];
+
+/// How different two pixels' colours can be, as a fraction of the largest
+/// difference there is, before they count as different.
+const PIXEL_TOLERANCE : f32 = 0.1;
+
+/// The fraction of pixels which can differ, e.g. along antialiased edges.
+const IMAGE_TOLERANCE : f32 = 0.002;
+
+// ============================================================
+// Images
+// ============================================================
+struct Image {
+    _width  : usize,
+    _height : usize,
+    /// RGB, from the top left, a row at a time.
+    _pixels : Vec<u8>,
+}
+
+impl Image {
+    /// Reads a binary PPM, as --screenshot writes.
+    fn read_ppm(in_path : &Path) -> Result<Image, String> {
+        let mut bytes = Vec::new();
+        File::open(in_path).and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(|e| format!("couldn't read {}: {}", in_path.display(), e))?;
+        let bad_file = || format!("{} isn't a binary PPM", in_path.display());
+        // the magic number, width, height and maximum value, each followed by one
+        // whitespace character
+        let mut fields = Vec::new();
+        let mut start = 0;
+        while fields.len() < 4 {
+            let end = start + bytes[start..].iter().position(|b| b.is_ascii_whitespace()).ok_or_else(&bad_file)?;
+            fields.push(String::from_utf8_lossy(&bytes[start..end]).into_owned());
+            start = end+1;
+        }
+        let width : usize = fields[1].parse().map_err(|_| bad_file())?;
+        let height : usize = fields[2].parse().map_err(|_| bad_file())?;
+        if fields[0] != "P6" || fields[3] != "255" || bytes.len() - start != 3*width*height {
+            return Err(bad_file());
+        }
+        Ok(Image {
+            _width  : width,
+            _height : height,
+            _pixels : bytes[start..].to_vec(),
+        })
+    }
+
+    /// Reads an 8-bit RGB or RGBA PNG (dropping the alpha), as screenshots are
+    /// saved and image editors save them.
+    fn read_png(in_path : &Path) -> Result<Image, String> {
+        let mut bytes = Vec::new();
+        File::open(in_path).and_then(|mut file| file.read_to_end(&mut bytes))
+            .map_err(|e| format!("couldn't read {}: {}", in_path.display(), e))?;
+        let bad_file = |why : &str| format!("{} isn't a PNG oxide can read: {}", in_path.display(), why);
+        if bytes.len() < 8 || bytes[..8] != PNG_SIGNATURE {return Err(bad_file("no PNG signature"))}
+        let mut header = None;
+        let mut compressed = Vec::new();
+        let mut start = 8;
+        while start + 12 <= bytes.len() {
+            let length = be_u32(&bytes[start..]) as usize;
+            let kind = &bytes[start+4..start+8];
+            let data = bytes.get(start+8..start+8+length).ok_or_else(|| bad_file("a chunk runs off the end"))?;
+            match kind {
+                b"IHDR" if length == 13 => header = Some((be_u32(data) as usize, be_u32(&data[4..]) as usize,
+                                                          data[8], data[9], data[12])),
+                b"IDAT" => compressed.extend_from_slice(data),
+                b"IEND" => break,
+                _ => {},
+            }
+            start += 12 + length;
+        }
+        let (width, height, depth, colour_type, interlace) = header.ok_or_else(|| bad_file("no header"))?;
+        let channels = match (depth, colour_type, interlace) {
+            (8, 2, 0) => 3,
+            (8, 6, 0) => 4,
+            _ => return Err(bad_file("not 8-bit RGB or RGBA without interlacing")),
+        };
+        let filtered = miniz_oxide::inflate::decompress_to_vec_zlib(&compressed)
+            .map_err(|e| bad_file(&format!("the pixels don't decompress ({:?})", e)))?;
+        let stride = channels*width;
+        if filtered.len() != (stride+1)*height {return Err(bad_file("the pixels are the wrong size"))}
+        // each row is stored as the difference from a guess made from the pixels to
+        // its left and above, by the filter named in its first byte
+        let mut unfiltered = vec![0u8; stride*height];
+        for row in 0..height {
+            let filter = filtered[row*(stride+1)];
+            for k in 0..stride {
+                let value = filtered[row*(stride+1)+1+k];
+                let left = if k >= channels {unfiltered[row*stride+k-channels]} else {0};
+                let up = if row > 0 {unfiltered[(row-1)*stride+k]} else {0};
+                let up_left = if row > 0 && k >= channels {unfiltered[(row-1)*stride+k-channels]} else {0};
+                let guess = match filter {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => ((left as u16 + up as u16)/2) as u8,
+                    4 => paeth(left, up, up_left),
+                    _ => return Err(bad_file(&format!("row {} has an unknown filter, {}", row, filter))),
+                };
+                unfiltered[row*stride+k] = value.wrapping_add(guess);
+            }
+        }
+        let pixels = unfiltered.chunks(channels).flat_map(|pixel| pixel[..3].to_vec()).collect();
+        Ok(Image {
+            _width  : width,
+            _height : height,
+            _pixels : pixels,
+        })
+    }
+
+    /// Writes the image as a PNG, as the viewer writes screenshots.
+    fn write_png(&self, in_path : &Path) -> Result<(), String> {
+        screenshot::write_image(&in_path.to_string_lossy(), &(self._width, self._height), &self._pixels)
+    }
+}
+
+const PNG_SIGNATURE : [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+fn be_u32(in_bytes : &[u8]) -> u32 {
+    (in_bytes[0] as u32) << 24 | (in_bytes[1] as u32) << 16 | (in_bytes[2] as u32) << 8 | in_bytes[3] as u32
+}
+
+/// Whichever of left, up and up-left is nearest to left + up - up-left.
+fn paeth(in_left : u8, in_up : u8, in_up_left : u8) -> u8 {
+    let (a, b, c) = (in_left as i16, in_up as i16, in_up_left as i16);
+    let p = a + b - c;
+    let (pa, pb, pc) = ((p-a).abs(), (p-b).abs(), (p-c).abs());
+    if pa <= pb && pa <= pc {in_left} else if pb <= pc {in_up} else {in_up_left}
+}
+
+/// How different two colours look, from 0 to 1: the distance between them in YIQ,
+/// weighted for how sensitive the eye is to each (as by Kotsarenko and Ramos, 2010).
+fn pixel_difference(in_a : &[u8], in_b : &[u8]) -> f32 {
+    let yiq = |c : &[u8]| {
+        let (r, g, b) = (c[0] as f32, c[1] as f32, c[2] as f32);
+        [
+            0.2988953*r + 0.5866225*g + 0.1144822*b,
+            0.5959780*r - 0.2741761*g - 0.3218019*b,
+            0.2114702*r - 0.5226171*g + 0.3111469*b,
+        ]
+    };
+    let (a, b) = (yiq(in_a), yiq(in_b));
+    let (y, i, q) = (a[0]-b[0], a[1]-b[1], a[2]-b[2]);
+    // the difference between black and white
+    let largest = 35215.0;
+    (0.5053*y*y + 0.299*i*i + 0.1957*q*q)/largest
+}
+
+/// The fraction of pixels which differ noticeably, and an image of the frame with
+/// them in red. An error if the images aren't the same size.
+fn compare(in_frame : &Image, in_golden : &Image) -> Result<(f32, Image), String> {
+    if in_frame._width != in_golden._width || in_frame._height != in_golden._height {
+        return Err(format!("the frame is {}x{}, but the golden image is {}x{}",
+                           in_frame._width, in_frame._height, in_golden._width, in_golden._height));
+    }
+    let mut different = 0;
+    let mut pixels = Vec::with_capacity(in_frame._pixels.len());
+    for (a, b) in in_frame._pixels.chunks(3).zip(in_golden._pixels.chunks(3)) {
+        if pixel_difference(a, b) > PIXEL_TOLERANCE*PIXEL_TOLERANCE {
+            different += 1;
+            pixels.extend_from_slice(&[255, 0, 0]);
+        } else {
+            // faded, so the red stands out
+            pixels.extend(a.iter().map(|&c| 191 + c/4));
+        }
+    }
+    let fraction = different as f32/(in_frame._width*in_frame._height) as f32;
+    Ok((fraction, Image {_width : in_frame._width, _height : in_frame._height, _pixels : pixels}))
+}
+
+// ============================================================
+// Scenes
+// ============================================================
+/// Draws a scene, returning its last frame, or None if it can't be drawn in
+/// software on this machine.
+fn draw(in_name : &str, in_structure : &str, in_output : &Path) -> Option<Image> {
+    let session = format!("test/golden/{}.session", in_name);
+    let output = Command::new(env!("CARGO_BIN_EXE_oxide"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(&[in_structure, "--replay", &session, "--software", "--screenshot"])
+        .arg(in_output)
+        .output()
+        .expect("couldn't run oxide");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.contains("couldn't draw in software") {return None}
+    assert!(output.status.success(), "drawing {} failed:\n{}{}",
+            in_name, stdout, String::from_utf8_lossy(&output.stderr));
+    Some(Image::read_ppm(in_output).unwrap())
+}
+
+#[test]
+fn golden_images() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let bless = env::var("OXIDE_BLESS").map_or(false, |value| value == "1");
+    let out_dir = root.join("target").join("golden");
+    fs::create_dir_all(&out_dir).unwrap();
+
+    let mut failures = Vec::new();
+    for &(name, structure) in SCENES.iter() {
+        let frame_path = out_dir.join(format!("{}.ppm", name));
+        let frame = match draw(name, structure, &frame_path) {
+            Some(frame) => frame,
+            None => {
+                println!("Skipping the golden images: can't draw in software here (is libOSMesa installed?)");
+                return;
+            },
+        };
+        let golden_path = root.join("test").join("golden").join(format!("{}.png", name));
+        if bless {
+            frame.write_png(&golden_path).unwrap();
+            println!("{}: saved {} as the golden image", name, golden_path.display());
+            continue;
+        }
+        let golden = match Image::read_png(&golden_path) {
+            Ok(golden) => golden,
+            Err(message) => {
+                failures.push(format!("{}: no golden image ({})", name, message));
+                continue;
+            },
+        };
+        match compare(&frame, &golden) {
+            Ok((fraction, _)) if fraction <= IMAGE_TOLERANCE => (),
+            Ok((fraction, difference)) => {
+                let difference_path = out_dir.join(format!("{}.difference.png", name));
+                difference.write_png(&difference_path).unwrap();
+                failures.push(format!("{}: {:.2}% of pixels differ (see {})",
+                                      name, 100.0*fraction, difference_path.display()));
+            },
+            Err(message) => failures.push(format!("{}: {}", name, message)),
+        }
+    }
+    assert!(failures.is_empty(), "the frames don't match their golden images:\n{}\n\
+             (if the change is intended, or the scene is new, run with OXIDE_BLESS=1 and commit test/golden)", failures.join("\n"));
+}
@@ -0,0 +1,113 @@
+//! Tests of the Matrix type's algebra and transformations: products with the
+//! identity, transposes and inverses, and that the transformations move points,
+//! directions and normals as they should.
+
+extern crate oxide;
+
+use oxide::{Matrix, Quaternion};
+
+/// How far apart matrices' elements (or vectors') can be and still count as equal.
+const EPSILON : f32 = 1e-5;
+
+fn assert_matrices_equal(in_a : &Matrix, in_b : &Matrix) {
+    for i in 0..4 {
+        for j in 0..4 {
+            assert!((in_a.contents()[i][j]-in_b.contents()[i][j]).abs() < EPSILON,
+                    "the matrices differ at [{}][{}]:\n{}\n\n{}", i, j, in_a, in_b);
+        }
+    }
+}
+
+fn assert_vectors_equal(in_a : &[f32;4], in_b : &[f32;4]) {
+    for i in 0..4 {
+        assert!((in_a[i]-in_b[i]).abs() < EPSILON, "{:?} isn't {:?}", in_a, in_b);
+    }
+}
+
+/// A matrix with nothing special about it: rotated, sheared, scaled unevenly and
+/// translated.
+fn general() -> Matrix {
+    let shear = Matrix::new([
+        [1.0, 0.3, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.2, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+    Matrix::translation(&[1.5, -2.0, 0.5]) * Matrix::rotation(&[1.0, 2.0, -0.5], &0.7) * shear
+        * Matrix::scaling(&[2.0, 0.5, 3.0])
+}
+
+#[test]
+fn identity() {
+    let m = general();
+    assert_matrices_equal(&(Matrix::identity()*m), &m);
+    assert_matrices_equal(&(m*Matrix::identity()), &m);
+    assert_vectors_equal(&(Matrix::identity()*[1.0, 2.0, 3.0, 1.0]), &[1.0, 2.0, 3.0, 1.0]);
+}
+
+#[test]
+fn transpose() {
+    let (a, b) = (general(), Matrix::rotation(&[0.0, 1.0, 1.0], &-1.2)*Matrix::translation(&[3.0, 0.0, 1.0]));
+    assert_matrices_equal(&a.transpose().transpose(), &a);
+    assert_matrices_equal(&(a*b).transpose(), &(b.transpose()*a.transpose()));
+    assert_eq!(a.transpose().contents()[0][3], a.contents()[3][0]);
+}
+
+#[test]
+fn inverse() {
+    let m = general();
+    let inverse = m.inverse().expect("the matrix should have an inverse");
+    assert_matrices_equal(&(m*inverse), &Matrix::identity());
+    assert_matrices_equal(&(inverse*m), &Matrix::identity());
+    assert_matrices_equal(&Matrix::identity().inverse().unwrap(), &Matrix::identity());
+    assert!(Matrix::scaling(&[1.0, 0.0, 2.0]).inverse().is_none());
+}
+
+#[test]
+fn rigid_inverse() {
+    let rigid = Matrix::translation(&[4.0, -1.0, 2.5])*Matrix::rotation(&[0.3, -1.0, 0.2], &2.1);
+    assert_matrices_equal(&rigid.rigid_inverse(), &rigid.inverse().unwrap());
+}
+
+#[test]
+fn determinant() {
+    assert!((Matrix::scaling(&[2.0, 0.5, 3.0]).determinant()-3.0).abs() < EPSILON);
+    assert!((Matrix::rotation(&[1.0, 1.0, 0.0], &0.4).determinant()-1.0).abs() < EPSILON);
+    assert!((general().determinant()-3.0).abs() < EPSILON*10.0);
+}
+
+#[test]
+fn transformations() {
+    let point = [1.0, 2.0, 3.0, 1.0];
+    let direction = [1.0, 2.0, 3.0, 0.0];
+    // translations move points but not directions
+    assert_vectors_equal(&(Matrix::translation(&[1.0, -1.0, 0.5])*point), &[2.0, 1.0, 3.5, 1.0]);
+    assert_vectors_equal(&(Matrix::translation(&[1.0, -1.0, 0.5])*direction), &direction);
+    assert_vectors_equal(&(Matrix::scaling(&[2.0, 3.0, -1.0])*point), &[2.0, 6.0, -3.0, 1.0]);
+    // a quarter turn anticlockwise about z takes x to y, and y to -x
+    let quarter = Matrix::rotation(&[0.0, 0.0, 2.0], &::std::f32::consts::FRAC_PI_2);
+    assert_vectors_equal(&(quarter*[1.0, 0.0, 0.0, 1.0]), &[0.0, 1.0, 0.0, 1.0]);
+    assert_vectors_equal(&(quarter*[0.0, 1.0, 0.0, 1.0]), &[-1.0, 0.0, 0.0, 1.0]);
+    assert!(Matrix::rotation(&[1.0, 2.0, 3.0], &0.9).is_rotation(&EPSILON));
+}
+
+#[test]
+fn rotation_matches_quaternion() {
+    let (axis, angle) = ([0.6f32, 0.0, 0.8], 1.3f32);
+    let (s, c) = (angle/2.0).sin_cos();
+    let quaternion = Quaternion::new(&c, &(axis[0]*s), &(axis[1]*s), &(axis[2]*s));
+    assert_matrices_equal(&Matrix::rotation(&axis, &angle), &quaternion.rotation_matrix());
+}
+
+#[test]
+fn normal_matrix() {
+    // a plane x + y = 0 squashed along x; its normal must stay perpendicular to it
+    let squash = Matrix::scaling(&[0.25, 1.0, 1.0])*Matrix::translation(&[0.0, 0.0, 5.0]);
+    let in_plane = squash*[1.0, -1.0, 0.0, 0.0];
+    let normal = squash.normal_matrix().unwrap()*[1.0, 1.0, 0.0, 0.0];
+    let dot = in_plane[0]*normal[0]+in_plane[1]*normal[1]+in_plane[2]*normal[2];
+    assert!(dot.abs() < EPSILON);
+    // and for a rotation, normals turn with everything else
+    let rotation = Matrix::rotation(&[1.0, 0.0, 1.0], &0.5);
+    assert_matrices_equal(&rotation.normal_matrix().unwrap(), &rotation);
+}
@@ -0,0 +1,122 @@
+//! Tests of the scene graph's arithmetic: that moving a node moves its atoms (in
+//! every frame) about their centre, that putting a node in a group leaves it where
+//! it is, and that putting it back undoes what it was moved by.
+
+extern crate oxide;
+
+use std::f32::consts::FRAC_PI_2;
+
+use oxide::{DefaultSpecies, Matrix, Molecule, Trajectory};
+use oxide::objects::Objects;
+use oxide::scene_graph::{SceneGraph, ROOT};
+
+/// How far apart positions (or matrices' elements) can be and still count as equal.
+const EPSILON : f32 = 1e-5;
+
+fn assert_positions_equal(in_a : &[f32;3], in_b : &[f32;3]) {
+    for k in 0..3 {
+        assert!((in_a[k]-in_b[k]).abs() < EPSILON, "{:?} isn't {:?}", in_a, in_b);
+    }
+}
+
+fn assert_matrices_equal(in_a : &Matrix, in_b : &Matrix) {
+    for i in 0..4 {
+        for j in 0..4 {
+            assert!((in_a.contents()[i][j]-in_b.contents()[i][j]).abs() < EPSILON,
+                    "the matrices differ at [{}][{}]:\n{}\n\n{}", i, j, in_a, in_b);
+        }
+    }
+}
+
+/// Where the atoms start: two along x in the first object, and two along y, about
+/// (0, 6, 0), in the second.
+const POSITIONS : [[f32;3];4] = [[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 5.0, 0.0], [0.0, 7.0, 0.0]];
+
+/// Two objects, in a trajectory of one frame, each a node in the whole scene.
+fn scene<'a>(in_species : &'a DefaultSpecies) -> (Molecule<'a>, Trajectory, Objects, SceneGraph) {
+    let mut molecule = Molecule::new();
+    for position in &POSITIONS {
+        molecule.add_atom(in_species.oxygen(), position);
+    }
+    let mut objects = Objects::new();
+    objects.add("first.xyz");
+    objects.add("second.xyz");
+    for i in 2..4 {molecule.atoms_mut()[i].set_object(&1)}
+    let mut trajectory = Trajectory::new();
+    trajectory.add_frame(&POSITIONS.to_vec()).unwrap();
+    let mut graph = SceneGraph::new();
+    graph.add_object(&0);
+    graph.add_object(&1);
+    (molecule, trajectory, objects, graph)
+}
+
+#[test]
+fn transform() {
+    let species = DefaultSpecies::new();
+    let (mut molecule, mut trajectory, objects, mut graph) = scene(&species);
+    let node = graph.object_node(&1).unwrap();
+    graph.transform(&node, &Matrix::rotation(&[0.0, 0.0, 1.0], &FRAC_PI_2), &objects, &mut molecule,
+                    &mut trajectory).unwrap();
+    // the second object turns about its centre, and the first stays put
+    let expected = [[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [1.0, 6.0, 0.0], [-1.0, 6.0, 0.0]];
+    for (i, position) in expected.iter().enumerate() {
+        assert_positions_equal(molecule.atoms()[i].position(), position);
+        assert_positions_equal(&trajectory.frames()[0][i], position);
+    }
+    let moved = graph.world(&node)*[0.0, 5.0, 0.0, 1.0];
+    assert_positions_equal(&[moved[0], moved[1], moved[2]], &expected[2]);
+}
+
+#[test]
+fn put_back() {
+    let species = DefaultSpecies::new();
+    let (mut molecule, mut trajectory, objects, mut graph) = scene(&species);
+    let node = graph.object_node(&1).unwrap();
+    let moves = [Matrix::rotation(&[1.0, 2.0, 0.5], &0.9), Matrix::translation(&[3.0, -1.0, 2.0])];
+    for transformation in &moves {
+        graph.transform(&node, transformation, &objects, &mut molecule, &mut trajectory).unwrap();
+    }
+    graph.put_back(&node, &objects, &mut molecule, &mut trajectory).unwrap();
+    for (i, position) in POSITIONS.iter().enumerate() {
+        assert_positions_equal(molecule.atoms()[i].position(), position);
+        assert_positions_equal(&trajectory.frames()[0][i], position);
+    }
+    assert_matrices_equal(graph.nodes()[node].local(), &Matrix::identity());
+}
+
+#[test]
+fn set_parent() {
+    let species = DefaultSpecies::new();
+    let (mut molecule, mut trajectory, objects, mut graph) = scene(&species);
+    let (first, second) = (graph.object_node(&0).unwrap(), graph.object_node(&1).unwrap());
+    graph.transform(&second, &Matrix::rotation(&[0.0, 1.0, 0.0], &0.4), &objects, &mut molecule,
+                    &mut trajectory).unwrap();
+    let group = graph.group("pair").unwrap();
+    graph.set_parent(&first, &group).unwrap();
+    graph.transform(&group, &Matrix::translation(&[0.0, 0.0, 4.0]), &objects, &mut molecule,
+                    &mut trajectory).unwrap();
+
+    // put in the group, which has been moved, the second is where it was
+    let before = graph.world(&second);
+    let positions : Vec<[f32;3]> = molecule.atoms().iter().map(|atom| *atom.position()).collect();
+    graph.set_parent(&second, &group).unwrap();
+    assert_matrices_equal(&graph.world(&second), &before);
+    assert!(graph.is_under(&first, &group) && graph.is_under(&second, &group));
+    for (atom, position) in molecule.atoms().iter().zip(positions.iter()) {
+        assert_positions_equal(atom.position(), position);
+    }
+
+    // and moving the group moves both
+    graph.transform(&group, &Matrix::translation(&[1.0, 0.0, 0.0]), &objects, &mut molecule,
+                    &mut trajectory).unwrap();
+    for (atom, position) in molecule.atoms().iter().zip(positions.iter()) {
+        assert_positions_equal(atom.position(), &[position[0]+1.0, position[1], position[2]]);
+    }
+
+    // the whole scene isn't in a group, and a group can't go in what's in it
+    assert!(graph.set_parent(&ROOT, &group).is_err());
+    let inner = graph.group("inner").unwrap();
+    graph.set_parent(&inner, &group).unwrap();
+    assert!(graph.set_parent(&group, &inner).is_err());
+    assert!(graph.set_parent(&group, &first).is_err());
+}
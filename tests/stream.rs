@@ -0,0 +1,135 @@
+//! Tests of streaming frames from a simulation: the WebSocket handshake's key
+//! against the example in RFC 6455, and frames sent by a stand-in simulation over
+//! TCP and over a WebSocket, split into fragments and with a ping among them.
+
+extern crate oxide;
+
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use oxide::stream::{self, FrameStream};
+
+/// A frame of water, as a simulation would send it.
+const WATER : &'static str = "3\nwater\nO 0.0 0.0 0.0\nH 0.96 0.0 0.0\nH -0.24 0.93 0.0\n";
+
+/// Listens on a free port, handing the first connection to `serve` on a thread of
+/// its own, and gives the port.
+fn serve<F>(serve : F) -> u16 where F : FnOnce(TcpStream) + Send + 'static {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || serve(listener.accept().unwrap().0));
+    port
+}
+
+/// Every frame the stream gives until it ends, and why it ended.
+fn receive_all(in_stream : &FrameStream) -> (Vec<Vec<[f32;3]>>, String) {
+    let start = Instant::now();
+    let mut frames = Vec::new();
+    while start.elapsed() < Duration::from_secs(10) {
+        let (more, ended) = in_stream.receive();
+        frames.extend(more);
+        if let Some(ended) = ended {return (frames, ended)}
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("the stream didn't end");
+}
+
+/// A frame from the server, which isn't masked.
+fn frame(in_first_byte : u8, in_payload : &[u8]) -> Vec<u8> {
+    let mut frame = vec![in_first_byte];
+    match in_payload.len() {
+        length if length < 126 => frame.push(length as u8),
+        length => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        },
+    }
+    frame.extend_from_slice(in_payload);
+    frame
+}
+
+#[test]
+fn accept_key() {
+    // RFC 6455, 1.3
+    assert_eq!(stream::accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+}
+
+#[test]
+fn frames_over_tcp() {
+    let port = serve(|mut connection| {
+        connection.write_all(format!("{}\n{}", WATER, WATER).as_bytes()).unwrap();
+    });
+    let frame_stream = FrameStream::connect(&format!("127.0.0.1:{}", port), &3).unwrap();
+    let (frames, ended) = receive_all(&frame_stream);
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[1][2], [-0.24, 0.93, 0.0]);
+    assert_eq!(ended, "the simulation closed the connection");
+}
+
+#[test]
+fn frames_of_the_wrong_size() {
+    // a count nothing could be made that big for ends the stream, as any other
+    // count but the structure's does
+    let port = serve(|mut connection| {
+        connection.write_all(format!("{}18446744073709551615\n", WATER).as_bytes()).unwrap();
+    });
+    let frame_stream = FrameStream::connect(&format!("127.0.0.1:{}", port), &3).unwrap();
+    let (frames, ended) = receive_all(&frame_stream);
+    assert_eq!(frames.len(), 1);
+    assert!(ended.contains("the structure has 3"), "{}", ended);
+}
+
+#[test]
+fn frames_over_a_websocket() {
+    let (pong_sender, pong_receiver) = ::std::sync::mpsc::channel();
+    let port = serve(move |connection| {
+        let mut reader = BufReader::new(connection.try_clone().unwrap());
+        let mut writer = connection;
+        let mut key = None;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {break}
+            if line.to_lowercase().starts_with("sec-websocket-key:") {
+                key = Some(line["sec-websocket-key:".len()..].trim().to_owned());
+            }
+        }
+        write!(writer, "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                        Sec-WebSocket-Accept: {}\r\n\r\n", stream::accept_key(&key.unwrap())).unwrap();
+
+        // a frame in a text message of two fragments, with a ping between them, then
+        // one long enough to need a 16-bit length in a binary message
+        let (first, second) = WATER.split_at(20);
+        writer.write_all(&frame(0x01, first.as_bytes())).unwrap();
+        writer.write_all(&frame(0x89, b"still there?")).unwrap();
+        writer.write_all(&frame(0x80, second.as_bytes())).unwrap();
+        let long = format!("3\n{}\nO 0 0 0\nH 1 0 0\nH 0 1 0\n", "x".repeat(200));
+        writer.write_all(&frame(0x82, long.as_bytes())).unwrap();
+
+        // the pong, which the client masks
+        let mut header = [0u8;6];
+        reader.read_exact(&mut header).unwrap();
+        let mut payload = vec![0u8; (header[1] & 0x7f) as usize];
+        reader.read_exact(&mut payload).unwrap();
+        let unmasked : Vec<u8> = payload.iter().enumerate().map(|(k, byte)| byte ^ header[2+k % 4]).collect();
+        pong_sender.send((header[0], header[1] & 0x80 != 0, unmasked)).unwrap();
+
+        writer.write_all(&frame(0x88, &1000u16.to_be_bytes())).unwrap();
+        // (waiting for the client's close)
+        let mut close = [0u8;8];
+        reader.read_exact(&mut close).ok();
+    });
+    let frame_stream = FrameStream::connect(&format!("ws://127.0.0.1:{}/frames", port), &3).unwrap();
+    let (frames, ended) = receive_all(&frame_stream);
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0][1], [0.96, 0.0, 0.0]);
+    assert_eq!(frames[1][2], [0.0, 1.0, 0.0]);
+    assert_eq!(ended, "the simulation closed the connection");
+    let (first_byte, masked, payload) = pong_receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(first_byte, 0x8a);
+    assert!(masked);
+    assert_eq!(payload, b"still there?".to_vec());
+}